@@ -0,0 +1,142 @@
+//! Options for making the game easier to read.
+//!
+//! The [`ColorPalette`] decides whether anything relies on hue alone. The accessible palettes put
+//! a symbol on every tile overlay, and swap the team colors for ones that stay distinct with any
+//! kind of color blindness. High contrast goes further and swaps the menu colors from
+//! [`ui_consts`](crate::menu::ui_consts) for punchier ones.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    battle_phase::PlayerEnemyPhase, menu::ui_consts::HIGH_CONTRAST_SWAPS, tr,
+    unit::overlay::TileOverlayAssets,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum ColorPalette {
+    #[default]
+    Standard,
+    Colorblind,
+    HighContrast,
+}
+
+impl ColorPalette {
+    pub const OPTIONS: [ColorPalette; 3] = [
+        ColorPalette::Standard,
+        ColorPalette::Colorblind,
+        ColorPalette::HighContrast,
+    ];
+
+    pub fn name(&self) -> String {
+        match self {
+            ColorPalette::Standard => tr!("settings.palette.standard"),
+            ColorPalette::Colorblind => tr!("settings.palette.colorblind"),
+            ColorPalette::HighContrast => tr!("settings.palette.high_contrast"),
+        }
+    }
+
+    /// Whether tile overlays get a symbol on top, so they don't rely on color alone
+    pub fn marks_overlays(&self) -> bool {
+        !matches!(self, ColorPalette::Standard)
+    }
+
+    /// The color used for each side in banners and the turn order.
+    ///
+    /// The accessible ones come from the Okabe-Ito palette.
+    pub fn team_color(&self, phase: PlayerEnemyPhase) -> Color {
+        match (self, phase) {
+            (ColorPalette::Standard, PlayerEnemyPhase::Player) => Color::linear_rgb(0.0, 0.0, 1.0),
+            (ColorPalette::Standard, PlayerEnemyPhase::Ally) => Color::linear_rgb(0.0, 0.6, 0.0),
+            (ColorPalette::Standard, PlayerEnemyPhase::Enemy) => Color::linear_rgb(1.0, 0.0, 0.0),
+            // #0072B2
+            (_, PlayerEnemyPhase::Player) => Color::srgb_u8(0x00, 0x72, 0xB2),
+            // #F0E442
+            (_, PlayerEnemyPhase::Ally) => Color::srgb_u8(0xF0, 0xE4, 0x42),
+            // #D55E00
+            (_, PlayerEnemyPhase::Enemy) => Color::srgb_u8(0xD5, 0x5E, 0x00),
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccessibilitySettings {
+    pub palette: ColorPalette,
+}
+
+pub fn accessibility_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        sync_overlay_palette.run_if(resource_changed::<AccessibilitySettings>),
+    )
+    // After everything in Update has had a chance to set its colors
+    .add_systems(PostUpdate, apply_ui_contrast);
+}
+
+/// Overlays pick up the palette from [`TileOverlayAssets`], which only exists during a battle.
+pub fn sync_overlay_palette(
+    settings: Res<AccessibilitySettings>,
+    tile_overlay_assets: Option<ResMut<TileOverlayAssets>>,
+) {
+    if let Some(mut assets) = tile_overlay_assets
+        && assets.palette != settings.palette
+    {
+        assets.palette = settings.palette;
+    }
+}
+
+fn same_rgb(a: Color, b: Color) -> bool {
+    a.to_linear().with_alpha(1.0) == b.to_linear().with_alpha(1.0)
+}
+
+/// The high contrast version of `color` (or the other way around), keeping its alpha
+fn contrast_swap(color: Color, high_contrast: bool) -> Option<Color> {
+    HIGH_CONTRAST_SWAPS.iter().find_map(|(standard, boosted)| {
+        let (from, to) = if high_contrast {
+            (standard, boosted)
+        } else {
+            (boosted, standard)
+        };
+        same_rgb(color, *from).then(|| to.with_alpha(color.alpha()))
+    })
+}
+
+/// Swaps the standard menu colors for high contrast ones.
+///
+/// Menus set their own backgrounds all the time (highlighting does it every frame), so this
+/// just keeps swapping whatever changed. When the setting changes everything gets swapped.
+pub fn apply_ui_contrast(
+    settings: Res<AccessibilitySettings>,
+    mut backgrounds: Query<&mut BackgroundColor>,
+) {
+    let high_contrast = settings.palette == ColorPalette::HighContrast;
+    if !high_contrast && !settings.is_changed() {
+        return;
+    }
+
+    for mut background in backgrounds.iter_mut() {
+        if !settings.is_changed() && !background.is_changed() {
+            continue;
+        }
+
+        if let Some(swapped) = contrast_swap(background.0, high_contrast) {
+            background.0 = swapped;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::menu::ui_consts::UI_MENU_BACKGROUND;
+
+    #[test]
+    fn test_contrast_swap_round_trips() {
+        let faded = UI_MENU_BACKGROUND.with_alpha(0.5);
+        let boosted = contrast_swap(faded, true).unwrap();
+        assert!(!same_rgb(boosted, faded));
+        assert_eq!(boosted.alpha(), 0.5);
+        assert!(same_rgb(contrast_swap(boosted, false).unwrap(), faded));
+        assert_eq!(contrast_swap(Color::WHITE, true), None);
+    }
+}
@@ -11,11 +11,11 @@ use crate::{
             AnimatedSpriteId, AnimationDB, AnimationKey, AnimationStartIndexKey,
             FollowerAnimationKey, RegisteredAnimationId,
         },
-        combat::ATTACK_FRAME_DURATION,
         tinytactics::{Character, WeaponType},
     },
     assets::BATTLE_TACTICS_TILESHEET,
     combat::{CombatAnimationId, UnitIsAttacking},
+    equipment::{EquippableItem, PlaceholderWeapon, UnitEquipment},
     grid::{GridManagerResource, GridMovement, GridVec},
     unit_stats::UnitDerivedStats,
 };
@@ -38,7 +38,7 @@ pub enum AnimationPriority {
     Combat,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum AnimationMarker {
     /// The frame at which the animation "hit" the target.
     ///
@@ -103,19 +103,41 @@ pub enum AnimationId {
 
 pub fn animation_follower_system(
     anim_db: Res<AnimationDB>,
-    anim_query: Query<(Option<&FacingDirection>, &UnitAnimationPlayer)>,
+    anim_query: Query<(
+        Option<&FacingDirection>,
+        &UnitAnimationPlayer,
+        Option<&UnitEquipment>,
+    )>,
     mut follower_query: Query<
-        (&AnimationFollower, &mut Sprite, &mut Visibility),
+        (
+            &AnimationFollower,
+            &mut Sprite,
+            &mut Visibility,
+            Option<&EquippableItem>,
+            Has<PlaceholderWeapon>,
+        ),
         With<AnimationFollower>,
     >,
 ) {
-    for (follower, mut sprite, mut vis) in follower_query.iter_mut() {
-        if let Ok((facing_direction, player)) = anim_query.get(follower.leader) {
+    for (follower, mut sprite, mut vis, item, is_placeholder) in follower_query.iter_mut() {
+        if let Ok((facing_direction, player, equipment)) = anim_query.get(follower.leader) {
             let Some(anim) = &player.current_animation else {
                 *vis = Visibility::Hidden;
                 continue;
             };
 
+            // Whatever the unit has equipped gets drawn instead of the placeholder. Only melee
+            // weapons get swung: ranged ones fire a projectile, shields stay put, and broken
+            // weapons don't get used at all.
+            let armed = equipment.is_some_and(|t| t.weapon_data().is_some());
+            let swung = item.is_none_or(|t| {
+                !t.is_broken() && t.weapon_data().is_some_and(|t| t.projectile.is_none())
+            });
+            if (is_placeholder && armed) || !swung {
+                *vis = Visibility::Hidden;
+                continue;
+            }
+
             let Some(follower_start_index) = anim_db.get_follower_animation_start_index(
                 &FollowerAnimationKey {
                     follower_id: follower.animated_sprite_id,
@@ -319,6 +341,16 @@ pub struct UnitAnimationKey {
 }
 
 impl UnitAnimationKind {
+    pub const ALL: [UnitAnimationKind; 7] = [
+        UnitAnimationKind::IdleWalk,
+        UnitAnimationKind::IdleHurt,
+        UnitAnimationKind::IdleDead,
+        UnitAnimationKind::Charge,
+        UnitAnimationKind::Attack,
+        UnitAnimationKind::TakeDamage,
+        UnitAnimationKind::Release,
+    ];
+
     fn priority(&self) -> AnimationPriority {
         match self {
             UnitAnimationKind::IdleWalk => AnimationPriority::Idle,
@@ -426,7 +458,7 @@ pub struct UnitAnimationData {
     pub inner: UnitAnimationDataInner,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Deserialize)]
 pub struct UnitAnimationDataInner {
     pub frame_duration: f32,
     pub frame_count: usize,
@@ -461,31 +493,10 @@ pub fn startup_load_tinytactics_assets(
         asset_server.load(tinytactics::weapon_spritesheet_path(WeaponType::IronAxe));
     let scepter_spritesheet =
         asset_server.load(tinytactics::weapon_spritesheet_path(WeaponType::Scepter));
-    let weapon_layout = texture_atlas_layouts.add(TextureAtlasLayout::from_grid(
-        UVec2::new(
-            tinytactics::FRAME_SIZE_X + 16,
-            tinytactics::FRAME_SIZE_Y + 16,
-        ),
-        4,
-        2,
-        None,
-        None,
-    ));
-    let layout = texture_atlas_layouts.add(TextureAtlasLayout::from_grid(
-        UVec2::new(tinytactics::FRAME_SIZE_X, tinytactics::FRAME_SIZE_Y),
-        4,
-        16,
-        None,
-        None,
-    ));
+    let weapon_layout = texture_atlas_layouts.add(tinytactics::weapon_layout());
+    let layout = texture_atlas_layouts.add(tinytactics::unit_layout());
     let tile_spritesheet = asset_server.load(BATTLE_TACTICS_TILESHEET);
-    let tile_layout = texture_atlas_layouts.add(TextureAtlasLayout::from_grid(
-        UVec2::new(tinytactics::FRAME_SIZE_X, tinytactics::FRAME_SIZE_Y),
-        16,
-        13,
-        None,
-        None,
-    ));
+    let tile_layout = texture_atlas_layouts.add(tinytactics::tile_layout());
 
     // TODO: Use AnimationData to populate le db?
     let animation_data = asset_server.load(tinytactics::spritesheet_data_path(Character::Fighter));
@@ -539,7 +550,7 @@ pub fn update_facing_direction_on_movement(
 /// Mod for handling specifics about tinytactics assets
 pub mod tinytactics {
     use bevy::prelude::*;
-    use std::{path::PathBuf, str::FromStr};
+    use std::{collections::HashMap, path::PathBuf, str::FromStr};
 
     use image::{ImageBuffer, Rgba};
 
@@ -572,12 +583,38 @@ pub mod tinytactics {
         y * SPRITESHEET_GRID_X + x
     }
 
+    /// The atlas every unit spritesheet is cut up with
+    pub fn unit_layout() -> TextureAtlasLayout {
+        TextureAtlasLayout::from_grid(UVec2::new(FRAME_SIZE_X, FRAME_SIZE_Y), 4, 16, None, None)
+    }
+
+    /// Weapons swing out past the unit's frame, so they get a bit more room
+    pub fn weapon_layout() -> TextureAtlasLayout {
+        TextureAtlasLayout::from_grid(
+            UVec2::new(FRAME_SIZE_X + 16, FRAME_SIZE_Y + 16),
+            4,
+            2,
+            None,
+            None,
+        )
+    }
+
+    pub fn tile_layout() -> TextureAtlasLayout {
+        TextureAtlasLayout::from_grid(UVec2::new(FRAME_SIZE_X, FRAME_SIZE_Y), 16, 13, None, None)
+    }
+
     #[derive(Debug, serde::Serialize, serde::Deserialize)]
     pub struct AnimationData {
-        action: Action,
-        direction: Direction,
-        frame_count: u32,
-        frame_indices: Vec<(u32, u32)>,
+        pub action: Action,
+        pub direction: Direction,
+        pub frame_count: u32,
+        pub frame_indices: Vec<(u32, u32)>,
+        /// Where the first frame is in the spritesheet's atlas
+        #[serde(default)]
+        pub start_index: u32,
+        /// The markers the game has registered for the animation, by frame
+        #[serde(default)]
+        pub markers: HashMap<usize, super::AnimationMarker>,
     }
 
     #[derive(Debug, serde::Serialize, serde::Deserialize, Asset, TypePath)]
@@ -636,6 +673,31 @@ pub mod tinytactics {
         Dead,
     }
 
+    impl Action {
+        pub const ALL: [Action; 7] = [
+            Action::Walking,
+            Action::Attack,
+            Action::Release,
+            Action::Charging,
+            Action::Damage,
+            Action::Weak,
+            Action::Dead,
+        ];
+
+        /// What the action gets registered as in the [`AnimationDB`](crate::animation::animation_db::AnimationDB)
+        pub fn animation_kind(&self) -> super::UnitAnimationKind {
+            match self {
+                Action::Walking => super::UnitAnimationKind::IdleWalk,
+                Action::Attack => super::UnitAnimationKind::Attack,
+                Action::Release => super::UnitAnimationKind::Release,
+                Action::Charging => super::UnitAnimationKind::Charge,
+                Action::Damage => super::UnitAnimationKind::TakeDamage,
+                Action::Weak => super::UnitAnimationKind::IdleHurt,
+                Action::Dead => super::UnitAnimationKind::IdleDead,
+            }
+        }
+    }
+
     impl std::fmt::Display for Action {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             match self {
@@ -761,7 +823,9 @@ pub mod tinytactics {
             action,
             direction,
             frame_count: hort_index_count * vert_index_count,
+            start_index: spritesheet_coords_to_index((0, y_offset)),
             frame_indices,
+            markers: HashMap::new(),
         }
     }
 
@@ -830,6 +894,7 @@ pub mod tinytactics {
 }
 
 pub mod animation_db {
+    use anyhow::Context;
     use registered_sprite_ids::UNIT_DEMO_SPRITE_ID;
 
     use crate::animation::animation_db::registered_sprite_ids::{
@@ -839,12 +904,41 @@ pub mod animation_db {
 
     use super::*;
 
+    pub const ANIMATION_TIMINGS_PATH: &str = "assets/unit_assets/animation_timings.json";
+    const BUILT_IN_ANIMATION_TIMINGS: &str =
+        include_str!("../assets/unit_assets/animation_timings.json");
+
+    /// How long each registered animation runs for and where its markers are, by the name it's
+    /// registered under. Kept out of the code so it can be tuned without a rebuild.
+    #[derive(Debug, Default, serde::Deserialize)]
+    #[serde(transparent)]
+    pub struct AnimationTimings(HashMap<String, UnitAnimationDataInner>);
+
+    fn parse_animation_timings(raw: &str) -> anyhow::Result<AnimationTimings> {
+        serde_json::from_str::<AnimationTimings>(raw).context("Parsing the animation timings")
+    }
+
+    /// The timings on disk, or the ones baked in if there's no file system or the file's broken
+    pub fn load_animation_timings() -> AnimationTimings {
+        #[cfg(not(target_arch = "wasm32"))]
+        match std::fs::read_to_string(ANIMATION_TIMINGS_PATH)
+            .context("Reading the animation timings")
+            .and_then(|t| parse_animation_timings(&t))
+        {
+            Ok(timings) => return timings,
+            Err(e) => warn!("Using the built in animation timings: {:?}", e),
+        }
+
+        parse_animation_timings(BUILT_IN_ANIMATION_TIMINGS)
+            .expect("The built in animation timings should parse")
+    }
+
     pub fn load_animation_data(
         mut commands: Commands,
         mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
     ) {
-        let mut animation_db =
-            build_animation_db().expect("Must be able to build static animation data");
+        let mut animation_db = build_animation_db(&load_animation_timings())
+            .expect("Must be able to build static animation data");
 
         animation_db.initialize_atlas_map(&mut texture_atlas_layouts);
         commands.insert_resource(animation_db);
@@ -856,20 +950,15 @@ pub mod animation_db {
         pub(crate) followee_key: AnimationKey,
     }
 
-    // TODO: Build this from json?
-    fn build_animation_db() -> anyhow::Result<AnimationDB> {
-        let mut db = AnimationDB::new();
+    /// Everything that plays is registered here, with its timings looked up from `timings`
+    pub fn build_animation_db(timings: &AnimationTimings) -> anyhow::Result<AnimationDB> {
+        let mut db = AnimationDB::new(timings);
         db.register_animation(
             "weapon_attack",
             AnimationKey {
                 animated_sprite_id: TT_WEAPON_ANIMATED_SPRITE_ID,
                 animation_id: UnitAnimationKind::Attack.into(),
             },
-            UnitAnimationDataInner {
-                frame_count: 4,
-                frame_duration: ATTACK_FRAME_DURATION,
-                animation_offset_markers: HashMap::new(),
-            },
             &[(Some(Direction::NE), 0), (Some(Direction::SE), 4)],
         )?
         .register_animation(
@@ -878,14 +967,6 @@ pub mod animation_db {
                 animated_sprite_id: TT_UNIT_ANIMATED_SPRITE_ID,
                 animation_id: UnitAnimationKind::Attack.into(),
             },
-            UnitAnimationDataInner {
-                frame_count: 4,
-                frame_duration: ATTACK_FRAME_DURATION,
-                animation_offset_markers: HashMap::from([
-                    (2, AnimationMarker::HitFrame),
-                    (4, AnimationMarker::Complete),
-                ]),
-            },
             &[(Some(Direction::NE), 16), (Some(Direction::SE), 20)],
         )?
         .register_animation(
@@ -894,11 +975,6 @@ pub mod animation_db {
                 animated_sprite_id: TT_UNIT_ANIMATED_SPRITE_ID,
                 animation_id: UnitAnimationKind::IdleWalk.into(),
             },
-            UnitAnimationDataInner {
-                frame_count: 8,
-                frame_duration: (1.0 / 8.),
-                animation_offset_markers: HashMap::new(),
-            },
             &[(Some(Direction::NE), 0), (Some(Direction::SE), 8)],
         )?
         .register_animation(
@@ -907,11 +983,6 @@ pub mod animation_db {
                 animated_sprite_id: TT_UNIT_ANIMATED_SPRITE_ID,
                 animation_id: UnitAnimationKind::TakeDamage.into(),
             },
-            UnitAnimationDataInner {
-                frame_count: 1,
-                frame_duration: (1.0 / 4.),
-                animation_offset_markers: HashMap::new(),
-            },
             &[(Some(Direction::NE), 40), (Some(Direction::SE), 44)],
         )?
         .register_animation(
@@ -920,11 +991,6 @@ pub mod animation_db {
                 animated_sprite_id: TT_UNIT_ANIMATED_SPRITE_ID,
                 animation_id: UnitAnimationKind::IdleHurt.into(),
             },
-            UnitAnimationDataInner {
-                frame_count: 1,
-                frame_duration: 1.0,
-                animation_offset_markers: HashMap::new(),
-            },
             &[(Some(Direction::NE), 48), (Some(Direction::SE), 52)],
         )?
         .register_animation(
@@ -933,11 +999,6 @@ pub mod animation_db {
                 animated_sprite_id: TT_UNIT_ANIMATED_SPRITE_ID,
                 animation_id: UnitAnimationKind::IdleDead.into(),
             },
-            UnitAnimationDataInner {
-                frame_count: 1,
-                frame_duration: (1.0),
-                animation_offset_markers: HashMap::new(),
-            },
             &[(Some(Direction::NE), 56), (Some(Direction::SE), 60)],
         )?
         .register_animation(
@@ -946,11 +1007,6 @@ pub mod animation_db {
                 animated_sprite_id: TT_UNIT_ANIMATED_SPRITE_ID,
                 animation_id: UnitAnimationKind::Charge.into(),
             },
-            UnitAnimationDataInner {
-                frame_count: 1,
-                frame_duration: (1.0),
-                animation_offset_markers: HashMap::from([(1, AnimationMarker::Complete)]),
-            },
             // TODO: Fix Spritesheet
             &[(Some(Direction::NE), 36), (Some(Direction::SE), 32)],
         )?
@@ -960,11 +1016,6 @@ pub mod animation_db {
                 animated_sprite_id: TT_UNIT_ANIMATED_SPRITE_ID,
                 animation_id: UnitAnimationKind::Release.into(),
             },
-            UnitAnimationDataInner {
-                frame_count: 1,
-                frame_duration: (1.0),
-                animation_offset_markers: HashMap::from([(1, AnimationMarker::Complete)]),
-            },
             &[(Some(Direction::NE), 24), (Some(Direction::SE), 28)],
         )?
         .register_follower(
@@ -990,14 +1041,6 @@ pub mod animation_db {
                     priority: AnimationPriority::Combat,
                 },
             },
-            UnitAnimationDataInner {
-                frame_duration: (1.0 / 9.),
-                frame_count: 18,
-                animation_offset_markers: HashMap::from([
-                    (9, AnimationMarker::HitFrame),
-                    (18, AnimationMarker::Complete),
-                ]),
-            },
             &[(None, 0)],
         )?
         .register_animation(
@@ -1009,14 +1052,6 @@ pub mod animation_db {
                     priority: AnimationPriority::Combat,
                 },
             },
-            UnitAnimationDataInner {
-                frame_duration: (1.0 / 16.),
-                frame_count: 16,
-                animation_offset_markers: HashMap::from([
-                    (10, AnimationMarker::HitFrame),
-                    (16, AnimationMarker::Complete),
-                ]),
-            },
             &[(None, 0)],
         )?
         .register_animation(
@@ -1025,18 +1060,24 @@ pub mod animation_db {
                 animated_sprite_id: UNIT_DEMO_SPRITE_ID,
                 animation_id: UnitAnimationKind::IdleWalk.into(),
             },
-            UnitAnimationDataInner {
-                frame_duration: 1.0,
-                frame_count: 1,
-                animation_offset_markers: HashMap::new(),
-            },
             &[(None, 0), (Some(Direction::SE), 0)],
         )?;
 
         Ok(db)
     }
 
-    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    #[derive(
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+        PartialOrd,
+        Ord,
+        Hash,
+        serde::Serialize,
+        serde::Deserialize,
+    )]
     pub struct AnimatedSpriteId(pub u32);
 
     /// TODO: Might need to encode priority of the animation in this struct too?
@@ -1072,6 +1113,7 @@ pub mod animation_db {
 
     #[derive(Debug, Resource)]
     pub struct AnimationDB {
+        timings: HashMap<String, UnitAnimationDataInner>,
         index_key_to_start_frame: HashMap<AnimationStartIndexKey, u8>,
         animation_data: HashMap<AnimationKey, UnitAnimationDataInner>,
         follower_map: HashMap<FollowerAnimationKey, AnimationKey>,
@@ -1079,8 +1121,9 @@ pub mod animation_db {
     }
 
     impl AnimationDB {
-        fn new() -> Self {
+        fn new(timings: &AnimationTimings) -> Self {
             Self {
+                timings: timings.0.clone(),
                 index_key_to_start_frame: HashMap::new(),
                 animation_data: HashMap::new(),
                 follower_map: HashMap::new(),
@@ -1090,11 +1133,13 @@ pub mod animation_db {
 
         fn register_animation(
             &mut self,
-            _name: &str,
+            name: &str,
             key: AnimationKey,
-            data: UnitAnimationDataInner,
             offsets: &[(Option<Direction>, u8)],
         ) -> anyhow::Result<&mut Self> {
+            let Some(data) = self.timings.get(name).cloned() else {
+                anyhow::bail!("No timings for the {} animation", name);
+            };
             if let Some(t) = self.animation_data.insert(key.clone(), data) {
                 anyhow::bail!("Data already existed for key {:?}, data: {:?}", key, t);
             };
@@ -1130,6 +1175,11 @@ pub mod animation_db {
             self.index_key_to_start_frame.get(key)
         }
 
+        /// Every registered start frame, for every direction it's registered for
+        pub fn start_indices(&self) -> impl Iterator<Item = (&AnimationStartIndexKey, u8)> {
+            self.index_key_to_start_frame.iter().map(|(k, v)| (k, *v))
+        }
+
         pub fn get_follower_animation_start_index(
             &self,
             key: &FollowerAnimationKey,
@@ -1149,6 +1199,8 @@ pub mod animation_db {
             self.atlas_map.get(key).cloned()
         }
 
+        /// Sets up each animated sprite's atlas. Anything that already has one gets it updated in
+        /// place, so sprites already using it pick the changes up.
         pub fn initialize_atlas_map(
             &mut self,
             texture_atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
@@ -1156,10 +1208,32 @@ pub mod animation_db {
             let map = build_animated_sprite_to_atlas_layout();
 
             for (id, layout) in map {
+                if let Some(current) = self
+                    .atlas_map
+                    .get(&id)
+                    .and_then(|t| texture_atlas_layouts.get_mut(t))
+                {
+                    *current = layout;
+                    continue;
+                }
                 let handle = texture_atlas_layouts.add(layout);
                 self.atlas_map.insert(id, handle.clone());
             }
         }
+
+        /// Builds the db again from `timings`, keeping hold of (and refreshing) the atlases it
+        /// already handed out
+        pub fn rebuild(
+            &mut self,
+            timings: &AnimationTimings,
+            texture_atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
+        ) -> anyhow::Result<()> {
+            let mut db = build_animation_db(timings)?;
+            db.atlas_map = std::mem::take(&mut self.atlas_map);
+            db.initialize_atlas_map(texture_atlas_layouts);
+            *self = db;
+            Ok(())
+        }
     }
 
     pub mod registered_sprite_ids {
@@ -1181,39 +1255,9 @@ pub mod animation_db {
         pub fn build_animated_sprite_to_atlas_layout()
         -> HashMap<AnimatedSpriteId, TextureAtlasLayout> {
             HashMap::from([
-                (
-                    TT_UNIT_ANIMATED_SPRITE_ID,
-                    TextureAtlasLayout::from_grid(
-                        UVec2::new(tinytactics::FRAME_SIZE_X, tinytactics::FRAME_SIZE_Y),
-                        4,
-                        16,
-                        None,
-                        None,
-                    ),
-                ),
-                (
-                    TT_WEAPON_ANIMATED_SPRITE_ID,
-                    TextureAtlasLayout::from_grid(
-                        UVec2::new(
-                            tinytactics::FRAME_SIZE_X + 16,
-                            tinytactics::FRAME_SIZE_Y + 16,
-                        ),
-                        4,
-                        2,
-                        None,
-                        None,
-                    ),
-                ),
-                (
-                    BATTLE_TACTICS_TILESHEET,
-                    TextureAtlasLayout::from_grid(
-                        UVec2::new(tinytactics::FRAME_SIZE_X, tinytactics::FRAME_SIZE_Y),
-                        16,
-                        13,
-                        None,
-                        None,
-                    ),
-                ),
+                (TT_UNIT_ANIMATED_SPRITE_ID, tinytactics::unit_layout()),
+                (TT_WEAPON_ANIMATED_SPRITE_ID, tinytactics::weapon_layout()),
+                (BATTLE_TACTICS_TILESHEET, tinytactics::tile_layout()),
                 (
                     FLAME_VFX_ANIMATED_SPRITE_ID,
                     TextureAtlasLayout::from_grid(UVec2::new(48, 48), 18, 1, None, None),
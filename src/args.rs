@@ -6,7 +6,22 @@ use clap::Parser;
 pub struct Cli {
     /// Whether to enable God Mode or not during battle.
     ///
-    /// Also enables the Inspector
+    /// Also enables the Inspector, and rewinding the last unit action with R
     #[arg(long, env = "TACTICS_EXPLORATION_GOD_MODE")]
     pub god_mode: bool,
+
+    /// Have units take turns in Speed order, instead of
+    /// alternating Player and Enemy phases.
+    #[arg(long, env = "TACTICS_EXPLORATION_INITIATIVE")]
+    pub initiative: bool,
+
+    /// Hand out the same XP for every attack as it happens, instead of
+    /// based on what each unit did once the battle is won.
+    #[arg(long, env = "TACTICS_EXPLORATION_FLAT_XP")]
+    pub flat_xp: bool,
+
+    /// Check that every sprite, animation, item and room the game points at
+    /// actually exists, print whatever's broken, and exit.
+    #[arg(long)]
+    pub validate_assets: bool,
 }
@@ -0,0 +1,222 @@
+//! Checking that everything the game points at actually exists.
+//!
+//! Sprites, animations, items and rooms all refer to each other by id or by path, and a typo in
+//! any of them usually only shows up as an `expect` going off (or a `bail!` getting logged) once
+//! that exact thing gets used mid-battle. [`validate_assets`] goes through all of it up front and
+//! collects every broken reference into one [`AssetReport`].
+//!
+//! It runs at startup in god mode, and `--validate-assets` runs it on its own and exits.
+
+use bevy::prelude::*;
+
+use crate::{
+    animation::{
+        Direction, UnitAnimationKind,
+        animation_db::{
+            AnimationKey, AnimationStartIndexKey, build_animation_db, load_animation_timings,
+            registered_sprite_ids::{
+                TT_UNIT_ANIMATED_SPRITE_ID, build_animated_sprite_to_atlas_layout,
+            },
+        },
+    },
+    assets::sprite_db::build_sprite_map,
+    combat::skills::{CastingData, SkillStageAction, build_skill_table},
+    equipment::build_item_db,
+    room_templates::{RoomTemplate, raw_templates},
+    tiled_import::{import_tiled_map, raw_tiled_maps},
+    unit::jobs::UnitJob,
+};
+
+/// Everything that turned out to be broken
+#[derive(Debug, Default)]
+pub struct AssetReport {
+    pub problems: Vec<String>,
+}
+
+impl AssetReport {
+    fn check(&mut self, ok: bool, problem: impl FnOnce() -> String) {
+        if !ok {
+            self.problems.push(problem());
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl std::fmt::Display for AssetReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_ok() {
+            return write!(f, "Every asset reference checks out");
+        }
+        writeln!(f, "Found {} broken asset references:", self.problems.len())?;
+        for problem in &self.problems {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Goes through every sprite, animation, skill, item and room, noting down anything that points at
+/// something that isn't there
+pub fn validate_assets() -> AssetReport {
+    let mut report = AssetReport::default();
+    let sprites = build_sprite_map();
+    let atlases = build_animated_sprite_to_atlas_layout();
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use crate::animation::tinytactics::{self, Character};
+
+        let data_paths = [Character::Fighter, Character::Mage, Character::Cleric].map(|t| {
+            tinytactics::spritesheet_data_path(t)
+                .to_string_lossy()
+                .to_string()
+        });
+        for path in sprites.values().chain(data_paths.iter()) {
+            report.check(std::path::Path::new("assets").join(path).exists(), || {
+                format!("{} doesn't exist", path)
+            });
+        }
+    }
+
+    for job in UnitJob::ALL {
+        for sprite_id in [job.base_sprite_id(), job.demo_sprite_id()] {
+            report.check(sprites.contains_key(&sprite_id), || {
+                format!(
+                    "{} has a sprite that isn't registered: {:?}",
+                    job.name(),
+                    sprite_id
+                )
+            });
+        }
+    }
+
+    let anim_db = match build_animation_db(&load_animation_timings()) {
+        Ok(anim_db) => Some(anim_db),
+        Err(e) => {
+            report.problems.push(format!("Animations: {:#}", e));
+            None
+        }
+    };
+    if let Some(anim_db) = &anim_db {
+        for (key, start) in anim_db.start_indices() {
+            let sprite = key.key.animated_sprite_id;
+            let Some(layout) = atlases.get(&sprite) else {
+                report
+                    .problems
+                    .push(format!("{:?} has no atlas registered", sprite));
+                continue;
+            };
+            let Some(data) = anim_db.get_data(&key.key) else {
+                report.problems.push(format!("{:?} has no timings", key));
+                continue;
+            };
+            report.check(
+                start as usize + data.frame_count <= layout.textures.len(),
+                || format!("{:?} runs off the end of its atlas", key),
+            );
+        }
+
+        for kind in UnitAnimationKind::ALL {
+            for direction in [Direction::NE, Direction::SE] {
+                let key = AnimationStartIndexKey {
+                    facing_direction: Some(direction),
+                    key: AnimationKey {
+                        animated_sprite_id: TT_UNIT_ANIMATED_SPRITE_ID,
+                        animation_id: kind.into(),
+                    },
+                };
+                report.check(anim_db.get_start_index(&key).is_some(), || {
+                    format!("Units can't play {:?} facing {}", kind, direction)
+                });
+            }
+        }
+    }
+
+    match build_skill_table() {
+        Ok(skill_db) => {
+            for skill in skill_db.skills() {
+                for stage in &skill.animation_data {
+                    let SkillStageAction::Cast(casting) = &stage.stage else {
+                        continue;
+                    };
+                    let sprite_id = match casting {
+                        CastingData::Projectile(sprite_id, _) => sprite_id,
+                        CastingData::TileSprite(sprite_id, key, _) => {
+                            let start = AnimationStartIndexKey {
+                                facing_direction: None,
+                                key: key.clone(),
+                            };
+                            report.check(
+                                anim_db
+                                    .as_ref()
+                                    .is_none_or(|t| t.get_start_index(&start).is_some()),
+                                || {
+                                    format!(
+                                        "{} casts an animation that isn't registered",
+                                        skill.name
+                                    )
+                                },
+                            );
+                            sprite_id
+                        }
+                    };
+                    report.check(sprites.contains_key(sprite_id), || {
+                        format!(
+                            "{} casts a sprite that isn't registered: {:?}",
+                            skill.name, sprite_id
+                        )
+                    });
+                }
+            }
+
+            if let Err(e) = build_item_db().and_then(|t| t.validate(&skill_db)) {
+                report.problems.push(format!("Items: {:#}", e));
+            }
+        }
+        Err(e) => report.problems.push(format!("Skills: {:#}", e)),
+    }
+
+    for raw in raw_templates() {
+        let template = ron::from_str::<RoomTemplate>(raw)
+            .map_err(anyhow::Error::from)
+            .and_then(|t| t.validate());
+        if let Err(e) = template {
+            report.problems.push(format!("Rooms: {:#}", e));
+        }
+    }
+    for (name, tmx) in raw_tiled_maps() {
+        if let Err(e) = import_tiled_map(name, tmx) {
+            report.problems.push(format!("Rooms: {:#}", e));
+        }
+    }
+
+    report
+}
+
+/// God mode won't start with anything broken
+pub fn fail_on_broken_assets() {
+    let report = validate_assets();
+    if !report.is_ok() {
+        panic!("{}", report);
+    }
+    info!("{}", report);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_asset_reference_resolves() {
+        let report = validate_assets();
+        assert!(report.is_ok(), "{}", report);
+
+        let mut broken = AssetReport::default();
+        broken.check(false, || "Nope".to_string());
+        assert!(!broken.is_ok());
+        assert!(broken.to_string().contains("Nope"));
+    }
+}
@@ -5,10 +5,12 @@ pub const CURSOR32_PATH: &str = "utility_assets/cursor.png";
 pub const CURSOR_PATH: &str = "utility_assets/cursor-16.png";
 pub const OVERLAY32_PATH: &str = "utility_assets/iso_color.png";
 pub const OVERLAY_PATH: &str = "utility_assets/iso_color-16.png";
+pub const OVERLAY_SYMBOLS_PATH: &str = "utility_assets/overlay_symbols.png";
 pub const BATTLE_TACTICS_TILESHEET: &str =
     "map_assets/tinytactics-32-map/20240420tinyTacticsTileset00.png";
 
 pub const GRADIENT_PATH: &str = "utility_assets/gradient.png";
+pub const INPUT_GLYPHS_PATH: &str = "utility_assets/input_glyphs.png";
 
 use std::path::PathBuf;
 
@@ -243,6 +245,7 @@ pub mod sounds {
             voice_sounds::BASE_OUCH,
         },
         combat::skills::SkillId,
+        tr,
     };
 
     /// JD Sherbert holding down the fort on these ui sounds
@@ -283,11 +286,25 @@ pub mod sounds {
         MoveCursor,
     }
 
+    /// Which volume a sound plays at. Everything gets the global volume on top.
+    #[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum SoundChannel {
+        Music,
+        Sfx,
+    }
+
     #[derive(Resource, Debug, Clone, serde::Serialize, serde::Deserialize)]
     pub struct SoundSettings {
         pub global_volume: f64,
         pub sfx_volume: f64,
         pub music_volume: f64,
+        /// Go quiet while the window's in the background, mostly for the browser tab
+        #[serde(default = "default_mute_when_unfocused")]
+        pub mute_when_unfocused: bool,
+    }
+
+    fn default_mute_when_unfocused() -> bool {
+        cfg!(target_arch = "wasm32")
     }
 
     impl Default for SoundSettings {
@@ -296,10 +313,42 @@ pub mod sounds {
                 global_volume: 1.0,
                 sfx_volume: 1.0,
                 music_volume: 1.0,
+                mute_when_unfocused: default_mute_when_unfocused(),
+            }
+        }
+    }
+
+    impl SoundSettings {
+        /// How loud anything on the channel actually plays
+        pub fn volume(&self, channel: SoundChannel) -> Volume {
+            let channel_volume = match channel {
+                SoundChannel::Music => self.music_volume,
+                SoundChannel::Sfx => self.sfx_volume,
+            };
+            Volume::Linear((self.global_volume * channel_volume) as f32)
+        }
+
+        pub fn mute_text(enabled: bool) -> String {
+            if enabled {
+                tr!("settings.mute_unfocused.on")
+            } else {
+                tr!("settings.mute_unfocused.off")
             }
         }
     }
 
+    /// Whether the game window's the one in front
+    #[derive(Resource, Debug)]
+    pub struct WindowFocus {
+        pub focused: bool,
+    }
+
+    impl Default for WindowFocus {
+        fn default() -> Self {
+            Self { focused: true }
+        }
+    }
+
     /// TBD if this should be an enum or just an id
     #[derive(Hash, PartialEq, Eq, Copy, Clone, Debug)]
     pub enum SkillSound {
@@ -394,18 +443,37 @@ pub mod sounds {
                 .unwrap()
         }
 
+        /// Every sound goes through here, so it plays at its channel's volume and can be turned
+        /// up or down while it's playing
+        fn play(
+            commands: &mut Commands,
+            settings: &SoundSettings,
+            source: Handle<AudioSource>,
+            playback: PlaybackSettings,
+            channel: SoundChannel,
+        ) -> Entity {
+            commands
+                .spawn((
+                    AudioPlayer::new(source),
+                    playback.with_volume(settings.volume(channel)),
+                    channel,
+                ))
+                .id()
+        }
+
         pub fn play_ui_sound(
             &self,
             commands: &mut Commands,
             settings: &SoundSettings,
             sound: UiSound,
         ) {
-            commands.spawn((
-                AudioPlayer::new(self.get_ui_sound(sound)),
-                PlaybackSettings::DESPAWN.with_volume(Volume::Linear(
-                    (settings.global_volume * settings.sfx_volume) as f32,
-                )),
-            ));
+            Self::play(
+                commands,
+                settings,
+                self.get_ui_sound(sound),
+                PlaybackSettings::DESPAWN,
+                SoundChannel::Sfx,
+            );
         }
 
         pub fn get_combat_sound(&self, sound: CombatSound) -> Handle<AudioSource> {
@@ -418,12 +486,13 @@ pub mod sounds {
             settings: &SoundSettings,
             sound: CombatSound,
         ) {
-            commands.spawn((
-                AudioPlayer::new(self.get_combat_sound(sound)),
-                PlaybackSettings::DESPAWN.with_volume(Volume::Linear(
-                    (settings.global_volume * settings.sfx_volume) as f32,
-                )),
-            ));
+            Self::play(
+                commands,
+                settings,
+                self.get_combat_sound(sound),
+                PlaybackSettings::DESPAWN,
+                SoundChannel::Sfx,
+            );
         }
 
         pub fn start_music(
@@ -432,13 +501,14 @@ pub mod sounds {
             sound_settings: &SoundSettings,
             music: Music,
         ) {
-            commands.spawn((
-                AudioPlayer::new(self.get_music(music)),
-                PlaybackSettings::LOOP.with_volume(Volume::Linear(
-                    (sound_settings.global_volume * sound_settings.music_volume) as f32,
-                )),
-                BackgroundMusicPlayer,
-            ));
+            let player = Self::play(
+                commands,
+                sound_settings,
+                self.get_music(music),
+                PlaybackSettings::LOOP,
+                SoundChannel::Music,
+            );
+            commands.entity(player).insert(BackgroundMusicPlayer);
         }
 
         pub(crate) fn get_all_sound_paths(&self) -> Vec<PathBuf> {
@@ -470,17 +540,43 @@ pub mod sounds {
         commands.insert_resource(SoundManager::initialize(&asset_server));
     }
 
+    /// Turns everything that's playing up or down to match the settings
     pub fn apply_volume_settings(
         sound_settings: Res<SoundSettings>,
-        mut global_volume: ResMut<GlobalVolume>,
-        mut audio_query: Query<&mut AudioSink, With<BackgroundMusicPlayer>>,
+        mut audio_query: Query<(&mut AudioSink, &SoundChannel)>,
     ) {
-        global_volume.volume = Volume::Linear(sound_settings.global_volume as f32);
+        for (mut sink, channel) in audio_query.iter_mut() {
+            sink.set_volume(sound_settings.volume(*channel));
+        }
+    }
 
-        for mut sink in audio_query.iter_mut() {
-            sink.set_volume(Volume::Linear(
-                (sound_settings.global_volume * sound_settings.music_volume) as f32,
-            ));
+    pub fn track_window_focus(
+        mut reader: MessageReader<bevy::window::WindowFocused>,
+        mut focus: ResMut<WindowFocus>,
+    ) {
+        if let Some(message) = reader.read().last() {
+            focus.focused = message.focused;
+        }
+    }
+
+    /// Mutes everything while the window's in the background, if the settings say to. Anything
+    /// that starts playing in the background gets muted too.
+    pub fn mute_when_unfocused(
+        sound_settings: Res<SoundSettings>,
+        focus: Res<WindowFocus>,
+        mut sinks: Query<&mut AudioSink>,
+    ) {
+        let muted = sound_settings.mute_when_unfocused && !focus.focused;
+        let everything = sound_settings.is_changed() || focus.is_changed();
+        for mut sink in sinks.iter_mut() {
+            if !everything && !sink.is_added() {
+                continue;
+            }
+            if muted {
+                sink.mute();
+            } else {
+                sink.unmute();
+            }
         }
     }
 
@@ -499,6 +595,18 @@ pub mod sounds {
             self.manager
                 .play_combat_sound(commands, &self.settings, sound);
         }
+
+        /// Plays any one off sound effect, like the ones in the [combat sfx
+        /// table](crate::combat_sfx)
+        pub fn play_sfx(&self, commands: &mut Commands, source: Handle<AudioSource>) {
+            SoundManager::play(
+                commands,
+                &self.settings,
+                source,
+                PlaybackSettings::DESPAWN,
+                SoundChannel::Sfx,
+            );
+        }
     }
 
     /// AudioCues allow us to generalize a bit for different
@@ -515,6 +623,8 @@ pub mod sounds {
         Miss,
         Hit,
         Healed,
+        /// A shield took some of the hit
+        Blocked,
     }
 
     #[derive(Debug, Clone)]
@@ -591,6 +701,25 @@ pub mod sounds {
 
             assert_eq!(lines, vec![VoiceSound::Ouch]);
         }
+
+        #[test]
+        fn test_channels_play_under_the_global_volume() {
+            let settings = SoundSettings {
+                global_volume: 0.5,
+                sfx_volume: 0.4,
+                music_volume: 2.0,
+                ..Default::default()
+            };
+            assert_eq!(settings.volume(SoundChannel::Sfx).to_linear(), 0.2);
+            assert_eq!(settings.volume(SoundChannel::Music).to_linear(), 1.0);
+
+            // Settings saved before there was a mute option still load
+            let old: SoundSettings = serde_json::from_str(
+                r#"{"global_volume":1.0,"sfx_volume":1.0,"music_volume":1.0}"#,
+            )
+            .expect("Old sound settings should still load");
+            assert_eq!(old.mute_when_unfocused, cfg!(target_arch = "wasm32"));
+        }
     }
 
     #[derive(Debug, Clone, Copy, PartialEq, Hash)]
@@ -749,11 +878,14 @@ pub mod active_assets {
     use anyhow::Context;
     use bevy::prelude::*;
 
-    use crate::assets::{
-        BATTLE_TACTICS_TILESHEET, CURSOR_PATH, FontResource, GRADIENT_PATH, OVERLAY_PATH,
-        setup_fonts,
-        sounds::{SoundManager, setup_sounds},
-        sprite_db::build_sprite_map,
+    use crate::{
+        assets::{
+            BATTLE_TACTICS_TILESHEET, CURSOR_PATH, FontResource, GRADIENT_PATH, OVERLAY_PATH,
+            setup_fonts,
+            sounds::{SoundManager, setup_sounds},
+            sprite_db::build_sprite_map,
+        },
+        combat_sfx::{CombatSfxLibrary, setup_combat_sfx},
     };
 
     pub const MISC_USED_ASSET_PATHS: &[&str] = &[
@@ -773,7 +905,7 @@ pub mod active_assets {
         // Let's think about restructuring these loaders to not need
         // to pull in an AssetServer to specify the paths they actively
         // depend on.
-        app.add_systems(Startup, (setup_fonts, setup_sounds));
+        app.add_systems(Startup, (setup_fonts, setup_sounds, setup_combat_sfx));
         app.add_plugins(DefaultPlugins);
         app.world_mut().run_schedule(Startup);
 
@@ -786,9 +918,14 @@ pub mod active_assets {
             .get_resource::<SoundManager>()
             .context("We just setup the SoundManager, and we need it to know what sounds exist")?;
 
+        let combat_sfx = w.get_resource::<CombatSfxLibrary>().context(
+            "We just setup the CombatSfxLibrary, and we need it to know what sounds exist",
+        )?;
+
         let mut used = Vec::new();
 
         used.extend(sound_manager.get_all_sound_paths());
+        used.extend(combat_sfx.get_all_sound_paths());
         used.extend(font_resource.get_all_paths());
 
         for path in MISC_USED_ASSET_PATHS {
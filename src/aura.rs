@@ -0,0 +1,259 @@
+//! Auras, buffs that come from standing near somebody.
+//!
+//! A unit with an [`EffectType::Aura`] on hands its [`StatModification`] out to every ally within
+//! the aura's radius. Whoever's in range gets it as a permanent [`Effect`] with the aura's owner as
+//! its source, so it shows up and stacks like any other buff does. It all gets worked out again
+//! whenever anyone steps onto a new tile or goes down, so walking into range picks the buff up and
+//! walking away drops it.
+//!
+//! Some jobs come with an aura, like the Knight's Rally. Anyone with one gets a faint ring on the
+//! ground around them. [Bonds](crate::bonds) are auras too, that only reach one partner.
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::{
+    combat::UnitHealthChangedEvent,
+    dungeon::DungeonState,
+    gameplay_effects::{
+        ActiveEffects, AuraData, Effect, EffectData, EffectDuration, EffectMetadata, EffectType,
+        StatModification,
+    },
+    grid::{GridPosition, TILE_X_SIZE, TILE_Y_SIZE, manhattan_distance},
+    unit::{Team, Unit, jobs::UnitJob},
+    unit_stats::{StatsDirty, UnitDerivedStats},
+};
+
+const AURA_RING_COLOR: Color = Color::linear_rgba(1.0, 0.85, 0.4, 0.25);
+const AURA_RING_THICKNESS: f32 = 1.5;
+
+/// The faint ring on the ground around a unit with an aura
+#[derive(Component)]
+pub struct AuraRing;
+
+pub fn aura_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (apply_job_auras, spread_auras, spawn_aura_rings)
+            .chain()
+            .run_if(in_state(DungeonState::InBattle)),
+    );
+}
+
+/// Where an aura is coming from
+struct AuraSource<'a> {
+    unit: Entity,
+    team: Team,
+    position: GridPosition,
+    aura: &'a AuraData,
+}
+
+impl AuraSource<'_> {
+    fn reaches(&self, unit: Entity, team: Team, position: &GridPosition) -> bool {
+        unit != self.unit
+            && team == self.team
+            && self.aura.partner.is_none_or(|t| t == unit)
+            && manhattan_distance(&self.position, position) <= self.aura.radius
+    }
+}
+
+/// Every aura buff the unit standing at `position` should have, along with whose aura it's from
+fn buffs_in_reach(
+    sources: &[AuraSource],
+    unit: Entity,
+    team: Team,
+    position: &GridPosition,
+) -> Vec<(Entity, StatModification)> {
+    sources
+        .iter()
+        .filter(|t| t.reaches(unit, team, position))
+        .map(|t| (t.unit, t.aura.modification.clone()))
+        .collect()
+}
+
+/// Whether the effect is a buff some unit's aura handed out
+fn from_aura(effect: &Effect, owners: &HashSet<Entity>) -> bool {
+    matches!(effect.data.effect_type, EffectType::StatBuff(..))
+        && matches!(effect.data.duration, EffectDuration::Permanent)
+        && effect.metadata.source.is_some_and(|t| owners.contains(&t))
+}
+
+fn apply_job_auras(mut units: Query<(Entity, &UnitJob, &mut ActiveEffects), Added<UnitJob>>) {
+    for (entity, job, mut effects) in units.iter_mut() {
+        let Some(aura) = job.aura() else {
+            continue;
+        };
+        effects.apply_effect(Effect {
+            metadata: EffectMetadata {
+                target: entity,
+                source: None,
+            },
+            data: EffectData {
+                effect_type: EffectType::Aura(aura),
+                duration: EffectDuration::Permanent,
+            },
+        });
+    }
+}
+
+/// Works out who's in whose aura again, whenever somebody moves, goes down or shows up
+pub fn spread_auras(
+    mut commands: Commands,
+    moved: Query<
+        (),
+        (
+            With<Unit>,
+            Or<(Changed<GridPosition>, Added<ActiveEffects>)>,
+        ),
+    >,
+    mut health_changes: MessageReader<UnitHealthChangedEvent>,
+    mut units: Query<(
+        Entity,
+        &Unit,
+        &GridPosition,
+        &UnitDerivedStats,
+        &mut ActiveEffects,
+    )>,
+) {
+    let anyone_downed = health_changes.read().count() > 0;
+    if moved.is_empty() && !anyone_downed {
+        return;
+    }
+
+    let owners = units
+        .iter()
+        .filter(|t| !t.4.auras().is_empty())
+        .map(|t| t.0)
+        .collect::<HashSet<_>>();
+    let auras = units
+        .iter()
+        .filter(|t| !t.3.downed())
+        .flat_map(|(unit, u, position, _, effects)| {
+            effects
+                .auras()
+                .into_iter()
+                .map(|aura| (unit, u.team, *position, aura.clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let sources = auras
+        .iter()
+        .map(|(unit, team, position, aura)| AuraSource {
+            unit: *unit,
+            team: *team,
+            position: *position,
+            aura,
+        })
+        .collect::<Vec<_>>();
+
+    for (entity, unit, position, _, mut effects) in units.iter_mut() {
+        let wanted = buffs_in_reach(&sources, entity, unit.team, position);
+        let current = effects
+            .effects
+            .iter()
+            .filter(|t| from_aura(t, &owners))
+            .filter_map(|t| match &t.data.effect_type {
+                EffectType::StatBuff(modification) => {
+                    Some((t.metadata.source?, modification.description()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let unchanged = current.len() == wanted.len()
+            && wanted
+                .iter()
+                .all(|(source, t)| current.contains(&(*source, t.description())));
+        if unchanged {
+            continue;
+        }
+
+        effects.effects.retain(|t| !from_aura(t, &owners));
+        for (source, modification) in wanted {
+            effects.apply_effect(Effect {
+                metadata: EffectMetadata {
+                    target: entity,
+                    source: Some(source),
+                },
+                data: EffectData {
+                    effect_type: EffectType::StatBuff(modification),
+                    duration: EffectDuration::Permanent,
+                },
+            });
+        }
+        commands.entity(entity).insert(StatsDirty);
+    }
+}
+
+fn spawn_aura_rings(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    units: Query<(Entity, &ActiveEffects, Option<&Children>), Changed<ActiveEffects>>,
+    rings: Query<(), With<AuraRing>>,
+) {
+    for (unit, effects, children) in units {
+        let has_ring = children.into_iter().flatten().any(|t| rings.contains(*t));
+        let Some(radius) = effects
+            .auras()
+            .iter()
+            .filter(|t| t.partner.is_none())
+            .map(|t| t.radius)
+            .max()
+        else {
+            continue;
+        };
+        if has_ring {
+            continue;
+        }
+
+        // Out to the middle of the furthest tile the aura reaches, squashed flat onto the grid
+        let outer = (radius as f32 + 0.5) * TILE_X_SIZE / 2.;
+        commands.entity(unit).with_child((
+            AuraRing,
+            Mesh2d(meshes.add(Annulus::new(outer - AURA_RING_THICKNESS, outer))),
+            MeshMaterial2d(materials.add(AURA_RING_COLOR)),
+            Transform::from_translation(Vec3::new(0., 0., -0.5)).with_scale(Vec3::new(
+                1.,
+                TILE_Y_SIZE / TILE_X_SIZE,
+                1.,
+            )),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        gameplay_effects::Operator,
+        unit::{ENEMY_TEAM, PLAYER_TEAM},
+        unit_stats::StatType,
+    };
+
+    #[test]
+    fn test_auras_only_reach_nearby_allies() {
+        let rally = UnitJob::Knight.aura().expect("Knights should Rally");
+        let knight = Entity::from_bits(1);
+        let ally = Entity::from_bits(2);
+        let sources = [AuraSource {
+            unit: knight,
+            team: PLAYER_TEAM,
+            position: GridPosition { x: 3, y: 3 },
+            aura: &rally,
+        }];
+
+        let next_door = buffs_in_reach(&sources, ally, PLAYER_TEAM, &GridPosition { x: 3, y: 4 });
+        assert_eq!(next_door.len(), 1);
+        assert_eq!(next_door[0].0, knight);
+        assert_eq!(next_door[0].1.attribute_type, StatType::Defense);
+        assert!(matches!(next_door[0].1.operator, Operator::Add));
+
+        let far_away = GridPosition { x: 5, y: 3 };
+        assert!(buffs_in_reach(&sources, ally, PLAYER_TEAM, &far_away).is_empty());
+        let enemy = GridPosition { x: 3, y: 2 };
+        assert!(buffs_in_reach(&sources, ally, ENEMY_TEAM, &enemy).is_empty());
+        let own = GridPosition { x: 3, y: 3 };
+        assert!(buffs_in_reach(&sources, knight, PLAYER_TEAM, &own).is_empty());
+    }
+}
@@ -8,6 +8,7 @@ use bevy_ecs_tilemap::prelude::*;
 
 use crate::{
     GameState,
+    accessibility::AccessibilitySettings,
     animation::{
         AnimationMarkerMessage, Direction, TinytacticsAssets,
         animation_db::{AnimationDB, load_animation_data},
@@ -17,58 +18,99 @@ use crate::{
         tinytactics::AnimationAsset,
         update_facing_direction_on_movement,
     },
+    asset_validation::fail_on_broken_assets,
     assets::{
         BATTLE_TACTICS_TILESHEET, CURSOR_PATH, FontResource, GRADIENT_PATH, OVERLAY_PATH,
+        OVERLAY_SYMBOLS_PATH,
         sound_resolvers::{resolve_skill_audio_events, resolve_voice_audio_events},
         sounds::AudioEventMessage,
         sprite_db::{SpriteDB, build_sprite_db},
     },
+    aura::aura_plugin,
+    battle_log::{
+        BattleLog, BattleLogMessage, record_battle_log, reset_battle_log, scroll_battle_log_panel,
+        spawn_battle_log_panel, toggle_battle_log_panel, update_battle_log_panel,
+    },
     battle_menu::{
         battle_menu_ui_definition::{PlayerBattleMenu, battle_ui_setup},
         player_battle_ui_systems::{
-            activate_battle_ui, clear_stale_battle_menus_on_activate, close_player_battle_menus,
-            handle_battle_ui_interactions, on_unit_completed_action_reopen_battle_menu,
-            reactivate_ui_on_back_message, set_active_battle_menu_on_player_turn,
+            activate_battle_ui, clear_stale_battle_menus_on_activate,
+            close_battle_menus_on_phase_timeout, close_player_battle_menus,
+            handle_battle_menu_confirm_dialogs, handle_battle_ui_interactions,
+            on_unit_completed_action_reopen_battle_menu, reactivate_ui_on_back_message,
+            set_active_battle_menu_on_player_turn, transfer_unit_ownership,
         },
         player_info_ui_systems::update_unit_viewer_ui,
         update_controlled_ui_info,
     },
     battle_phase::{
-        PhaseMessage, StartOfPhaseEffectsMessage, TurnStartMessage,
-        advance_after_start_of_phase_effects, check_for_active_effect_damage_on_turn_start,
-        check_should_advance_phase, decrement_turn_count_effects_on_turn_start, init_phase_system,
-        is_enemy_phase, is_running_enemy_phase, is_running_player_phase,
+        ActionCost, EndPhaseEarlyMessage, PhaseManager, PhaseMessage, StartOfPhaseEffectsMessage,
+        TurnAdvancedMessage, TurnModel, TurnStartMessage, advance_after_start_of_phase_effects,
+        advance_turn_queue, check_for_active_effect_damage_on_turn_start,
+        check_should_advance_phase, decrement_turn_count_effects_on_turn_start, end_phase_early,
+        init_phase_system, is_ai_phase, is_running_ai_phase, is_running_player_phase,
+        phase_timer::{
+            PhaseTimer, PhaseTimerExpiredMessage, manage_phase_timer, spawn_phase_timer_ui,
+            tick_phase_timer, update_phase_timer_ui,
+        },
         phase_ui::{
-            BattlePhaseMessageComplete, ShowBattleBannerMessage, banner_animation_system,
-            spawn_banner_system,
+            BattleBannerMessage, BattleBannerQueue, BattlePhaseMessageComplete,
+            ShowBattleBannerMessage, announce_battle_start, banner_animation_system,
+            clear_banner_queue, spawn_banner_system,
+        },
+        prepare_for_phase, start_phase, tint_units_on_phase_change,
+        tint_units_on_phase_resources_changed,
+        turn_order_ui::{
+            highlight_unit_on_turn_order_icon_over, spawn_turn_order_bar,
+            tint_unit_on_turn_order_highlight_added, tint_unit_on_turn_order_highlight_removed,
+            toggle_unit_highlight_on_turn_order_icon_click,
+            unhighlight_unit_on_turn_order_icon_out, update_turn_order_bar,
         },
-        prepare_for_phase, start_phase,
+        uses_initiative, uses_phases,
     },
-    camera::change_zoom,
+    bonds::bonds_plugin,
+    camera::{change_zoom, pan_camera, smooth_zoom, start_overview_zoom},
     combat::{
-        CombatStageComplete, DamageText, ImpactEvent, UnitHealthChangedEvent,
+        AttackBlockedEvent, CombatStageComplete, DamageText, ImpactEvent, UnitHealthChangedEvent,
         attack_execution_despawner, attack_intent_system, check_combat_timeline_should_advance,
-        cleanup_vfx_on_animation_complete, despawn_after_timer_completed,
-        handle_combat_stage_enter, impact_event_handler, listen_for_combat_conditions,
-        skills::{SkillId, UnitSkills, setup_skill_system},
-        spawn_damage_text,
+        cleanup_vfx_on_animation_complete,
+        delayed_skills::resolve_delayed_skills,
+        despawn_after_timer_completed, handle_combat_stage_enter, impact_event_handler,
+        listen_for_combat_conditions,
+        skills::{ATTACK_SKILL_ID, SkillDB, SkillId, UnitSkills, setup_skill_system},
+        spawn_block_text, spawn_damage_text,
     },
+    combat_formulas::{combat_formulas_plugin, reload_combat_formulas},
+    combat_sfx::play_combat_sfx,
+    contribution::{BattleXpAwards, contribution_plugin, distribute_battle_xp, uses_per_attack_xp},
     dungeon::{
-        DungeonEntity, DungeonState, RoomId, Teleporter, handle_teleporter_interaction,
+        DungeonEntity, DungeonState, RoomId, Teleporter, carry_over_party_equipment,
+        carry_over_party_health, carry_over_party_progression, handle_teleporter_interaction,
         init_dungeon_manager, load_room, unload_room,
     },
+    encounters::{PlannedEnemy, spawn_planned_enemy},
     enemy::{
-        begin_enemy_phase, execute_enemy_action, init_enemy_ai_system, plan_enemy_action,
-        resolve_enemy_action, select_next_enemy,
+        Boss, begin_enemy_phase,
+        behaviors::{Behavior, EnemyAiBehavior, PatrolRoute},
+        execute_enemy_action, init_enemy_ai_system, plan_enemy_action, resolve_enemy_action,
+        select_next_enemy,
     },
-    equipment::setup_item_db,
+    equipment::{setup_item_db, sync_granted_skills},
+    gameplay_effects::status_icons::sync_status_icons,
     grid::{self, GridManager, GridPosition},
     grid_cursor,
+    hot_reload::hot_reload_plugin,
+    injury::injury_plugin,
     interactable::{
         InteractionEnabled, ObtainableItem, TreasureChest, handle_interactions,
         update_player_ui_available_options,
     },
+    inventory::{Consumable, USE_CONSUMABLE_COST},
     join_game_menu::get_sprite_resources_for_job,
+    loading::loading_plugin,
+    localization::localized_text,
+    loot::{EnemyLoot, LootTableId},
+    map_editor::map_editor_plugin,
     map_generation::{MapData, build_tilemap_from_map, init_map_params},
     menu::{
         menu_navigation::{
@@ -77,22 +119,44 @@ use crate::{
         show_active_game_menu_only,
         ui_consts::{SELECTABLE_BUTTON_BACKGROUND, UI_MENU_BACKGROUND, UI_TEXT_COLOR},
     },
+    palette::palette_plugin,
+    pause_menu::{BattlePauseState, pause_menu_plugin},
     player::{self, Player, RegisteredBattlePlayers},
     projectile::{ProjectileArrived, projectile_arrival_system, projectile_bezier_system},
+    promotion::promotion_plugin,
+    props::spawn_decorations,
+    rest_room::rest_room_plugin,
+    rewind::rewind_plugin,
+    route_select::route_select_plugin,
+    run_save::{autosave_run, respawn_saved_reinforcements, restore_saved_units},
+    save_game::{SaveProgressLabel, record_battle_played, save_progression},
+    shop::shop_plugin,
+    tr,
+    trade::{TRADE_COST, TradeOffer},
+    turn_events::{blow_blizzard, check_turn_limit, raise_water, spawn_reinforcements},
     unit::{
-        CombatActionMarker, ENEMY_TEAM, ObstacleSprite, PLAYER_TEAM, UnitActionCompletedMessage,
-        UnitExecuteActionMessage, equip_starting_items_on_unit, execute_unit_actions,
-        handle_unit_cursor_actions, handle_unit_ui_command,
-        overlay::{OverlaysMessage, TileOverlayAssets, handle_overlays_events_system},
-        spawn_enemy, spawn_obstacle_unit, spawn_unit, unlock_cursor_after_unit_ui_command,
+        ALLY_TEAM, CombatActionMarker, MoveRejectedMessage, PLAYER_TEAM, TileOccupiedNudge, Unit,
+        UnitActionCompletedMessage, UnitExecuteActionMessage, equip_starting_items_on_unit,
+        execute_unit_actions, handle_unit_cursor_actions, handle_unit_ui_command,
+        overlay::{
+            OverlaysMessage, TileOverlayAssets, handle_overlays_events_system, toggle_danger_zone,
+            update_danger_zone_overlays,
+        },
+        show_tile_occupied_nudge, spawn_enemy, spawn_obstacle_unit, spawn_unit,
+        unlock_cursor_after_unit_ui_command,
+    },
+    unit_inspection::{
+        close_unit_inspection, open_unit_inspection, show_highlighted_inspection_section,
     },
     unit_stats::{
         StatType, StatValue, UnitDerivedStats, UnitStatChangeRequest, derive_stats,
         experience::{
-            LevelUpMessage, apply_level_up_to_stats, give_flat_xp_after_attack_action_complete,
+            LevelUpMessage, LevelUpPopup, apply_level_up_to_stats,
+            give_flat_xp_after_attack_action_complete,
         },
         handle_stat_changes,
     },
+    weather::weather_plugin,
 };
 
 // TODO: Need to decide how we want to
@@ -100,6 +164,10 @@ use crate::{
 #[derive(Component)]
 pub struct Enemy {}
 
+/// AI controlled units on the Player's side. They take their own phase.
+#[derive(Component)]
+pub struct Ally {}
+
 #[derive(Message, Debug)]
 pub struct UnitSelectionMessage {
     /// Unit that was selected
@@ -119,6 +187,17 @@ pub struct UnitSelectionBackMessage {
     pub player: Player,
 }
 
+/// Hands a unit over to another player partway through a battle, say when someone has to leave
+/// or one player is calling the shots for several units.
+///
+/// The unit's menus and phase follow the new owner straight away. It only lasts for the rest of
+/// the battle though, the party gets handed out fresh in the next room.
+#[derive(Message, Debug, Clone, Copy)]
+pub struct UnitTransferMessage {
+    pub unit: Entity,
+    pub new_owner: Player,
+}
+
 #[derive(Message, Debug, Clone)]
 pub struct UnitUiCommandMessage {
     /// Player that sent command
@@ -138,16 +217,52 @@ pub enum UnitCommand {
     UseSkill(SkillId),
     ViewMap,
     Interact(Entity),
+    UseItem(Consumable),
+    Trade(TradeOffer),
+}
+
+impl UnitCommand {
+    /// What a unit needs to have left in the phase to carry out this command
+    pub fn cost(&self, skill_db: &SkillDB) -> ActionCost {
+        match self {
+            UnitCommand::Move => ActionCost {
+                move_actions: 1,
+                ..Default::default()
+            },
+            UnitCommand::Attack => ActionCost {
+                action_points: skill_db.get_skill(&ATTACK_SKILL_ID).cost.ap.into(),
+                ..Default::default()
+            },
+            UnitCommand::UseSkill(skill_id) => ActionCost {
+                action_points: skill_db.get_skill(skill_id).cost.ap.into(),
+                ..Default::default()
+            },
+            UnitCommand::Interact(_) => ActionCost {
+                minor_actions: 1,
+                ..Default::default()
+            },
+            UnitCommand::UseItem(_) => USE_CONSUMABLE_COST,
+            UnitCommand::Trade(_) => TRADE_COST,
+            UnitCommand::Wait | UnitCommand::Cancel | UnitCommand::ViewMap => ActionCost::default(),
+        }
+    }
 }
 
 pub fn god_mode_plugin(app: &mut App) {
-    app.add_systems(Update, handle_god_mode_input);
+    app.add_systems(Startup, fail_on_broken_assets)
+        .add_systems(Update, (handle_god_mode_input, reload_combat_formulas))
+        .add_plugins(rewind_plugin)
+        .add_plugins(map_editor_plugin)
+        .add_plugins(hot_reload_plugin);
 }
 
 pub fn handle_god_mode_input(
     keyboard_input: Res<ButtonInput<KeyCode>>,
     mut player_unit_query: Query<&mut UnitDerivedStats, (With<Player>, Without<Enemy>)>,
     mut enemy_unit_query: Query<&mut UnitDerivedStats, (With<Enemy>, Without<Player>)>,
+    cursors: Query<(&Player, &GridPosition), With<grid_cursor::Cursor>>,
+    units: Query<(Entity, &Player, &GridPosition), With<Unit>>,
+    mut transfer_writer: MessageWriter<UnitTransferMessage>,
 ) {
     if keyboard_input.just_pressed(KeyCode::KeyP) {
         for mut player in player_unit_query.iter_mut() {
@@ -160,6 +275,25 @@ pub fn handle_god_mode_input(
             enemy.stats.with_stat(StatType::Health, StatValue(0.));
         }
     }
+
+    // Hand the units under each cursor over to the player after their owner
+    if keyboard_input.just_pressed(KeyCode::KeyO) {
+        let mut players = cursors.iter().map(|(p, _)| *p).collect::<Vec<_>>();
+        players.sort_by_key(|t| t.id());
+        players.dedup();
+
+        for (_, cursor_pos) in cursors {
+            for (unit, owner, _) in units.iter().filter(|(_, _, pos)| *pos == cursor_pos) {
+                let Some(index) = players.iter().position(|t| t == owner) else {
+                    continue;
+                };
+                transfer_writer.write(UnitTransferMessage {
+                    unit,
+                    new_owner: players[(index + 1) % players.len()],
+                });
+            }
+        }
+    }
 }
 
 /// All logic necessary during a battle
@@ -167,6 +301,7 @@ pub fn battle_plugin(app: &mut App) {
     app.add_message::<OverlaysMessage>()
         .add_message::<UnitSelectionMessage>()
         .add_message::<UnitUiCommandMessage>()
+        .add_message::<UnitTransferMessage>()
         .add_message::<UnitSelectionBackMessage>()
         .add_message::<AnimationMarkerMessage>()
         .add_message::<PhaseMessage>()
@@ -179,11 +314,33 @@ pub fn battle_plugin(app: &mut App) {
         .add_message::<ProjectileArrived>()
         .add_message::<TurnStartMessage>()
         .add_message::<StartOfPhaseEffectsMessage>()
+        .add_message::<EndPhaseEarlyMessage>()
+        .add_message::<TurnAdvancedMessage>()
+        .add_message::<PhaseTimerExpiredMessage>()
+        .add_message::<MoveRejectedMessage>()
         .add_message::<UnitHealthChangedEvent>()
+        .add_message::<AttackBlockedEvent>()
         .add_message::<AudioEventMessage>()
         .add_message::<UnitStatChangeRequest>()
         .add_message::<LevelUpMessage>()
+        .add_message::<BattleLogMessage>()
+        .init_resource::<BattleLog>()
+        .init_resource::<BattleBannerQueue>()
+        .init_resource::<TurnModel>()
         .add_plugins((TilemapPlugin,))
+        .add_plugins(pause_menu_plugin)
+        .add_plugins(route_select_plugin)
+        .add_plugins(rest_room_plugin)
+        .add_plugins(promotion_plugin)
+        .add_plugins(contribution_plugin)
+        .add_plugins(aura_plugin)
+        .add_plugins(bonds_plugin)
+        .add_plugins(injury_plugin)
+        .add_plugins(combat_formulas_plugin)
+        .add_plugins(loading_plugin)
+        .add_plugins(palette_plugin)
+        .add_plugins(shop_plugin)
+        .add_plugins(weather_plugin)
         .add_plugins(JsonAssetPlugin::<AnimationAsset>::new(&[".json"]))
         .add_systems(
             OnEnter(GameState::Dungeon),
@@ -194,6 +351,8 @@ pub fn battle_plugin(app: &mut App) {
                 init_enemy_ai_system,
                 setup_skill_system,
                 battle_ui_setup,
+                spawn_phase_timer_ui,
+                spawn_turn_order_bar,
                 load_battle_asset_resources,
                 load_animation_data,
                 build_sprite_db,
@@ -201,34 +360,73 @@ pub fn battle_plugin(app: &mut App) {
             )
                 .chain(),
         )
+        .add_systems(
+            OnEnter(GameState::Dungeon),
+            (reset_battle_log, spawn_battle_log_panel),
+        )
         .add_systems(OnEnter(DungeonState::LoadRoom), load_room)
         .add_systems(
             OnEnter(DungeonState::InBattle),
-            (equip_starting_items_on_unit, init_phase_system),
+            (
+                equip_starting_items_on_unit,
+                init_phase_system,
+                announce_battle_start,
+                start_overview_zoom,
+                (respawn_saved_reinforcements, restore_saved_units)
+                    .chain()
+                    .after(init_phase_system),
+            ),
+        )
+        .add_systems(
+            OnExit(DungeonState::InBattle),
+            (
+                clear_banner_queue,
+                carry_over_party_equipment,
+                carry_over_party_progression,
+            ),
+        )
+        .add_systems(
+            OnEnter(DungeonState::UnloadRoom),
+            (carry_over_party_health, unload_room).chain(),
         )
-        .add_systems(OnEnter(DungeonState::UnloadRoom), unload_room)
         .add_systems(
             Update,
-            (handle_stat_changes, derive_stats)
+            (sync_granted_skills, handle_stat_changes, derive_stats)
                 .chain()
                 .run_if(in_state(DungeonState::InBattle)),
         )
         .add_systems(
             Update,
-            (set_active_battle_menu_on_player_turn).run_if(in_state(DungeonState::InBattle)),
+            (set_active_battle_menu_on_player_turn)
+                .run_if(in_state(DungeonState::InBattle))
+                .run_if(in_state(BattlePauseState::Running)),
         )
         .add_systems(
             Update,
             (
                 check_battle_complete,
-                check_should_advance_phase::<Player>,
-                check_should_advance_phase::<Enemy>,
-                prepare_for_phase::<Player>.after(check_should_advance_phase::<Player>),
-                prepare_for_phase::<Enemy>.after(check_should_advance_phase::<Enemy>),
-                decrement_turn_count_effects_on_turn_start::<Player>,
-                decrement_turn_count_effects_on_turn_start::<Enemy>,
-                check_for_active_effect_damage_on_turn_start::<Player>,
-                check_for_active_effect_damage_on_turn_start::<Enemy>,
+                (
+                    check_should_advance_phase::<Player>,
+                    check_should_advance_phase::<Ally>,
+                    check_should_advance_phase::<Enemy>,
+                )
+                    .run_if(uses_phases),
+                advance_turn_queue.run_if(uses_initiative),
+                (
+                    prepare_for_phase::<Player>,
+                    prepare_for_phase::<Ally>,
+                    prepare_for_phase::<Enemy>,
+                ),
+                (
+                    decrement_turn_count_effects_on_turn_start::<Player>,
+                    decrement_turn_count_effects_on_turn_start::<Ally>,
+                    decrement_turn_count_effects_on_turn_start::<Enemy>,
+                ),
+                (
+                    check_for_active_effect_damage_on_turn_start::<Player>,
+                    check_for_active_effect_damage_on_turn_start::<Ally>,
+                    check_for_active_effect_damage_on_turn_start::<Enemy>,
+                ),
                 advance_after_start_of_phase_effects,
                 spawn_banner_system,
                 banner_animation_system,
@@ -236,13 +434,37 @@ pub fn battle_plugin(app: &mut App) {
                 handle_teleporter_interaction,
             )
                 .run_if(in_state(DungeonState::InBattle))
+                .run_if(in_state(BattlePauseState::Running))
                 .chain()
                 .after(handle_stat_changes),
         )
         .add_systems(
             Update,
-            (begin_enemy_phase)
-                .run_if(is_enemy_phase)
+            end_phase_early
+                .before(check_should_advance_phase::<Player>)
+                .before(advance_turn_queue)
+                .run_if(is_running_player_phase)
+                .run_if(in_state(DungeonState::InBattle))
+                .run_if(in_state(BattlePauseState::Running)),
+        )
+        .add_systems(
+            Update,
+            (
+                manage_phase_timer,
+                tick_phase_timer
+                    .run_if(resource_exists::<PhaseTimer>)
+                    .before(end_phase_early),
+                update_phase_timer_ui,
+                close_battle_menus_on_phase_timeout,
+            )
+                .chain()
+                .run_if(in_state(DungeonState::InBattle))
+                .run_if(in_state(BattlePauseState::Running)),
+        )
+        .add_systems(
+            Update,
+            (begin_enemy_phase::<Ally>, begin_enemy_phase::<Enemy>)
+                .run_if(is_ai_phase)
                 .run_if(in_state(DungeonState::InBattle)),
         )
         .add_systems(
@@ -259,6 +481,7 @@ pub fn battle_plugin(app: &mut App) {
                 clear_stale_battle_menus_on_activate.run_if(is_running_player_phase),
                 activate_battle_ui.run_if(is_running_player_phase),
                 handle_battle_ui_interactions.run_if(is_running_player_phase),
+                handle_battle_menu_confirm_dialogs.run_if(is_running_player_phase),
                 unlock_cursor_after_unit_ui_command.after(handle_battle_ui_interactions),
                 // Player UI System
                 handle_unit_cursor_actions.run_if(is_running_player_phase),
@@ -275,13 +498,94 @@ pub fn battle_plugin(app: &mut App) {
                 update_unit_viewer_ui,
                 update_controlled_ui_info,
             )
+                .run_if(in_state(DungeonState::InBattle))
+                .run_if(in_state(BattlePauseState::Running)),
+        )
+        .add_systems(
+            Update,
+            grid_cursor::handle_cursor_pointer_input
+                .before(grid_cursor::handle_cursor_movement)
+                .before(handle_unit_cursor_actions)
+                .before(handle_menu_cursor_navigation)
+                .run_if(in_state(DungeonState::InBattle))
+                .run_if(in_state(BattlePauseState::Running)),
+        )
+        .add_systems(
+            Update,
+            (update_turn_order_bar, sync_status_icons).run_if(in_state(DungeonState::InBattle)),
+        )
+        .add_systems(
+            Update,
+            (
+                record_battle_log,
+                toggle_battle_log_panel,
+                update_battle_log_panel,
+                scroll_battle_log_panel,
+            )
+                .chain()
+                .run_if(in_state(DungeonState::InBattle)),
+        )
+        .add_systems(
+            Update,
+            (
+                open_unit_inspection,
+                show_highlighted_inspection_section,
+                close_unit_inspection.after(handle_battle_ui_interactions),
+            )
+                .run_if(in_state(DungeonState::InBattle))
+                .run_if(in_state(BattlePauseState::Running)),
+        )
+        .add_systems(
+            Update,
+            (toggle_danger_zone, update_danger_zone_overlays)
+                .chain()
+                .after(grid::resolve_grid_movement)
+                .run_if(in_state(DungeonState::InBattle))
+                .run_if(in_state(BattlePauseState::Running)),
+        )
+        .add_systems(
+            Update,
+            (
+                // Saved before the turn's events, so picking the run back up replays them
+                autosave_run
+                    .before(spawn_reinforcements)
+                    .before(raise_water)
+                    .before(blow_blizzard),
+                spawn_reinforcements,
+                raise_water,
+                blow_blizzard,
+                check_turn_limit,
+            )
+                .after(check_battle_complete)
+                .run_if(in_state(DungeonState::InBattle)),
+        )
+        .add_systems(
+            Update,
+            (
+                tint_units_on_phase_resources_changed,
+                tint_units_on_phase_change.run_if(resource_changed::<PhaseManager>),
+            )
+                .run_if(in_state(DungeonState::InBattle)),
+        )
+        .add_systems(
+            Update,
+            on_unit_completed_action_reopen_battle_menu
+                .run_if(in_state(DungeonState::InBattle))
+                .run_if(in_state(BattlePauseState::Running)),
+        )
+        .add_systems(
+            Update,
+            transfer_unit_ownership
+                .before(handle_unit_cursor_actions)
                 .run_if(in_state(DungeonState::InBattle)),
         )
         .add_systems(
             Update,
-            on_unit_completed_action_reopen_battle_menu.run_if(in_state(DungeonState::InBattle)),
+            (change_zoom, smooth_zoom, pan_camera)
+                .chain()
+                .run_if(in_state(DungeonState::InBattle))
+                .run_if(in_state(BattlePauseState::Running)),
         )
-        .add_systems(Update, change_zoom.run_if(in_state(DungeonState::InBattle)))
         .add_systems(
             Update,
             (
@@ -306,6 +610,10 @@ pub fn battle_plugin(app: &mut App) {
                 .chain()
                 .run_if(in_state(DungeonState::InBattle)),
         )
+        .add_systems(
+            Update,
+            resolve_delayed_skills.run_if(in_state(DungeonState::InBattle)),
+        )
         .add_systems(
             Update,
             (
@@ -316,8 +624,10 @@ pub fn battle_plugin(app: &mut App) {
             )
                 .chain()
                 .after(prepare_for_phase::<Enemy>)
+                .after(prepare_for_phase::<Ally>)
                 .run_if(in_state(DungeonState::InBattle))
-                .run_if(is_running_enemy_phase)
+                .run_if(is_running_ai_phase)
+                .run_if(in_state(BattlePauseState::Running))
                 .after(handle_stat_changes),
         )
         .add_systems(
@@ -325,13 +635,14 @@ pub fn battle_plugin(app: &mut App) {
             (projectile_bezier_system, projectile_arrival_system)
                 .run_if(in_state(DungeonState::InBattle)),
         )
-        .add_systems(
-            Update,
-            check_battle_complete.run_if(in_state(DungeonState::InBattle)),
-        )
         .add_systems(
             OnEnter(GameState::BattleResolution),
-            (close_player_battle_menus, spawn_battle_resolution_ui),
+            (
+                close_player_battle_menus,
+                distribute_battle_xp.before(spawn_battle_resolution_ui),
+                spawn_battle_resolution_ui,
+                record_battle_played,
+            ),
         )
         .add_systems(
             Update,
@@ -342,38 +653,54 @@ pub fn battle_plugin(app: &mut App) {
         )
         .add_systems(
             Update,
-            (handle_menu_cursor_navigation, highlight_menu_option)
+            (
+                handle_menu_cursor_navigation,
+                highlight_menu_option,
+                save_progression.after(apply_level_up_to_stats),
+            )
                 .run_if(in_state(GameState::BattleResolution)),
         )
         .add_systems(
             Update,
             (
                 spawn_damage_text,
+                spawn_block_text,
                 despawn_after_timer_completed::<DamageText>,
+                show_tile_occupied_nudge,
+                despawn_after_timer_completed::<TileOccupiedNudge>,
             ),
         )
         .add_systems(
             Update,
             (update_player_ui_available_options, handle_interactions)
-                .run_if(in_state(DungeonState::InBattle)),
+                .run_if(in_state(DungeonState::InBattle))
+                .run_if(in_state(BattlePauseState::Running)),
         )
         .add_systems(
             Update,
             (resolve_skill_audio_events, resolve_voice_audio_events)
                 .run_if(in_state(DungeonState::InBattle)),
         )
+        // Level ups can still happen once the battle's over
+        .add_systems(Update, play_combat_sfx)
         .add_systems(
             Update,
             (
-                give_flat_xp_after_attack_action_complete,
+                give_flat_xp_after_attack_action_complete.run_if(uses_per_attack_xp),
                 apply_level_up_to_stats,
+                despawn_after_timer_completed::<LevelUpPopup>,
             ),
         )
         .add_observer(handle_battle_resolution_ui_buttons)
+        .add_observer(highlight_unit_on_turn_order_icon_over)
+        .add_observer(unhighlight_unit_on_turn_order_icon_out)
+        .add_observer(toggle_unit_highlight_on_turn_order_icon_click)
+        .add_observer(tint_unit_on_turn_order_highlight_added)
+        .add_observer(tint_unit_on_turn_order_highlight_removed)
         .add_systems(OnExit(GameState::BattleResolution), cleanup_battle);
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BattleEndCondition {
     Victory,
     Defeat,
@@ -396,6 +723,7 @@ pub enum BattleResolutionMenuAction {
 pub fn spawn_battle_resolution_ui(
     mut commands: Commands,
     battle_result: Res<BattleResultResource>,
+    xp_awards: Res<BattleXpAwards>,
     fonts: Res<FontResource>,
 ) {
     let ui_container = commands
@@ -449,8 +777,13 @@ pub fn spawn_battle_resolution_ui(
     };
 
     let (condition_text, color) = match battle_result.0.battle_condition {
-        BattleEndCondition::Victory => ("Victory", Color::linear_rgb(0.4, 0.7, 0.4)),
-        BattleEndCondition::Defeat => ("Defeat", Color::linear_rgb(0.7, 0.4, 0.4)),
+        BattleEndCondition::Victory => (
+            "battle_resolution.victory",
+            Color::linear_rgb(0.4, 0.7, 0.4),
+        ),
+        BattleEndCondition::Defeat => {
+            ("battle_resolution.defeat", Color::linear_rgb(0.7, 0.4, 0.4))
+        }
     };
 
     let condition_node = commands
@@ -475,7 +808,7 @@ pub fn spawn_battle_resolution_ui(
                         font_size: 65.,
                         ..Default::default()
                     },
-                    Text(condition_text.to_string()),
+                    localized_text(condition_text),
                 ),
                 (
                     TextColor(UI_TEXT_COLOR),
@@ -484,12 +817,47 @@ pub fn spawn_battle_resolution_ui(
                         font_size: 32.,
                         ..Default::default()
                     },
-                    Text("Thanks for playing! :)".to_string()),
+                    localized_text("battle_resolution.thanks"),
                 )
             ],
         ))
         .id();
 
+    // Only the winners have anything worth keeping
+    if battle_result.0.battle_condition == BattleEndCondition::Victory {
+        for award in &xp_awards.0 {
+            let text = if award.levels_gained > 0 {
+                tr!(
+                    "battle_resolution.level_up",
+                    name = award.name,
+                    xp = award.xp,
+                    level = award.level
+                )
+            } else {
+                tr!("battle_resolution.xp", name = award.name, xp = award.xp)
+            };
+            commands.entity(condition_node).with_child((
+                TextColor(UI_TEXT_COLOR),
+                TextFont {
+                    font: fonts.pixelify_sans_regular.clone(),
+                    font_size: 22.,
+                    ..Default::default()
+                },
+                Text(text),
+            ));
+        }
+        commands.entity(condition_node).with_child((
+            TextColor(UI_TEXT_COLOR),
+            TextFont {
+                font: fonts.pixelify_sans_regular.clone(),
+                font_size: 22.,
+                ..Default::default()
+            },
+            localized_text("battle_resolution.saving"),
+            SaveProgressLabel,
+        ));
+    }
+
     let main_menu_button = commands
         .spawn((
             Name::new("MainMenuButton"),
@@ -498,7 +866,7 @@ pub fn spawn_battle_resolution_ui(
             BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
             BattleResolutionMenuAction::MainMenu,
             children![(
-                Text::new("Main Menu"),
+                localized_text("battle_resolution.main_menu"),
                 button_font.clone(),
                 TextColor(Color::WHITE),
             ),],
@@ -513,7 +881,7 @@ pub fn spawn_battle_resolution_ui(
             BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
             BattleResolutionMenuAction::Quit,
             children![(
-                Text::new("Quit"),
+                localized_text("battle_resolution.quit"),
                 button_font.clone(),
                 TextColor(Color::WHITE),
             ),],
@@ -569,32 +937,48 @@ pub fn handle_battle_resolution_ui_buttons(
     }
 }
 
+/// Records how the battle went, and plays a stinger banner. The banner heads to the
+/// BattleResolution screen once it's done.
+pub fn finish_battle(
+    commands: &mut Commands,
+    banner_writer: &mut MessageWriter<ShowBattleBannerMessage>,
+    battle_condition: BattleEndCondition,
+) {
+    commands.insert_resource(BattleResultResource(BattleResult { battle_condition }));
+    banner_writer.write(ShowBattleBannerMessage {
+        message: BattleBannerMessage::BattleEnd(battle_condition),
+    });
+}
+
 // Naively assumes the BattleObjective is to defeat all enemies
 pub fn check_battle_complete(
     mut commands: Commands,
     player_unit_query: Query<&UnitDerivedStats, With<Player>>,
     enemy_unit_query: Query<&UnitDerivedStats, With<Enemy>>,
-    mut game_state: ResMut<NextState<GameState>>,
+    battle_result: Option<Res<BattleResultResource>>,
+    mut banner_writer: MessageWriter<ShowBattleBannerMessage>,
     combat_marker_query: Query<Entity, With<CombatActionMarker>>,
 ) {
-    // Wait until combat is finished before calling the fight complete
-    if !combat_marker_query.is_empty() {
+    // Wait until combat is finished before calling the fight complete, and only call it once
+    if !combat_marker_query.is_empty() || battle_result.is_some() {
         return;
     }
 
     // All Players have been downed :(
     if player_unit_query.iter().all(|t| t.downed()) {
-        commands.insert_resource(BattleResultResource(BattleResult {
-            battle_condition: BattleEndCondition::Defeat,
-        }));
-        game_state.set(GameState::BattleResolution);
+        finish_battle(
+            &mut commands,
+            &mut banner_writer,
+            BattleEndCondition::Defeat,
+        );
     }
     // All Enemies have been downed :)
     else if enemy_unit_query.iter().all(|t| t.downed()) {
-        commands.insert_resource(BattleResultResource(BattleResult {
-            battle_condition: BattleEndCondition::Victory,
-        }));
-        game_state.set(GameState::BattleResolution);
+        finish_battle(
+            &mut commands,
+            &mut banner_writer,
+            BattleEndCondition::Victory,
+        );
     }
 }
 
@@ -627,15 +1011,19 @@ pub fn cleanup_battle(
     for e in query {
         commands.entity(e).despawn();
     }
+
+    commands.remove_resource::<BattleResultResource>();
 }
 
 pub fn load_battle_asset_resources(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    accessibility_settings: Res<AccessibilitySettings>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
 ) {
     let debug_color_spritesheet = asset_server.load(OVERLAY_PATH);
     let cursor_image: Handle<Image> = asset_server.load(CURSOR_PATH);
+    let symbol_image: Handle<Image> = asset_server.load(OVERLAY_SYMBOLS_PATH);
 
     // TODO: Better asset management resources
     commands.insert_resource(TileOverlayAssets {
@@ -651,6 +1039,19 @@ pub fn load_battle_asset_resources(
             texture_atlas_layouts.add(layout)
         },
         cursor_image: cursor_image.clone(),
+        symbol_image_handle: symbol_image,
+        symbol_atlas_layout_handle: {
+            // Same size as the overlays, so the symbols sit right on top of them
+            let layout = TextureAtlasLayout::from_grid(
+                UVec2::new(grid::TILE_X_SIZE as u32, grid::TILE_Y_SIZE as u32),
+                6,
+                1,
+                None,
+                None,
+            );
+            texture_atlas_layouts.add(layout)
+        },
+        palette: accessibility_settings.palette,
     });
 
     startup_load_tinytactics_assets(&mut commands, &asset_server, &mut texture_atlas_layouts);
@@ -669,6 +1070,19 @@ pub fn spawn_background_gradient(mut commands: Commands, asset_server: Res<Asset
     ));
 }
 
+/// Whoever backs up the leader, in spawn order
+const SUPPORTING_ENEMY_NAMES: [&str; 4] = ["Deege", "Chaumwer", "Brannock", "Pim"];
+
+/// Names have to be unique within a room, since that's how a run save tells enemies apart
+fn supporting_enemy_name(index: usize) -> String {
+    let name = SUPPORTING_ENEMY_NAMES[index % SUPPORTING_ENEMY_NAMES.len()];
+    match index / SUPPORTING_ENEMY_NAMES.len() {
+        0 => name.to_string(),
+        round => format!("{} {}", name, round + 1),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn populate_room(
     commands: &mut Commands,
     asset_server: &AssetServer,
@@ -678,6 +1092,8 @@ pub fn populate_room(
     anim_db: &AnimationDB,
     sprite_db: &SpriteDB,
     room_id: RoomId,
+    encounter: &[PlannedEnemy],
+    boss: bool,
 ) {
     commands.spawn((
         GridPosition { x: 3, y: 3 },
@@ -687,11 +1103,16 @@ pub fn populate_room(
         InteractionEnabled,
     ));
 
-    commands.spawn((
-        GridPosition { x: 2, y: 3 },
-        TreasureChest,
-        InteractionEnabled,
-    ));
+    for chest in &map_data.chests {
+        commands.spawn((
+            chest.position,
+            TreasureChest {
+                loot_table: chest.loot_table,
+            },
+            InteractionEnabled,
+            DungeonEntity,
+        ));
+    }
 
     build_tilemap_from_map(
         commands,
@@ -704,15 +1125,10 @@ pub fn populate_room(
 
     let mut valid_player_positions = Vec::from(map_data.player_start_locations);
 
-    let enemy_1_grid_pos = GridPosition { x: 7, y: 3 };
-    let enemy_2_grid_pos = GridPosition { x: 4, y: 2 };
-    let enemy_3_grid_pos = GridPosition { x: 4, y: 4 };
-
     for loc in &map_data.bridge_end_locations {
         commands.spawn((
             Teleporter {
                 current_room: room_id,
-                next_room: RoomId(room_id.0 + 1),
             },
             // TODO: Add `InteractionEnabled` only when the room is clear.
             InteractionEnabled,
@@ -721,14 +1137,21 @@ pub fn populate_room(
         ));
     }
 
+    let (game_width, game_height) = map_data.game_size();
     commands.insert_resource(grid::GridManagerResource {
-        grid_manager: GridManager::new(13, 13),
+        grid_manager: GridManager::new(game_width, game_height)
+            .with_elevation(map_data.elevation.clone()),
     });
 
     load_demo_battle_players(commands, &registered_players);
-    let save_files = registered_players.save_files.clone().into_iter();
+    let party = registered_players
+        .units()
+        .map(|(player, t)| (player, t.clone()))
+        .collect::<Vec<_>>();
 
-    for (player, player_unit_info) in save_files {
+    // Players with more than one unit only get one cursor, which starts on their own character
+    let mut players_with_cursors = HashSet::new();
+    for (player, player_unit_info) in party {
         let Some(position) = valid_player_positions.pop() else {
             log::warn!("Not enough valid player positions for all registered players!");
             break;
@@ -737,7 +1160,7 @@ pub fn populate_room(
         let Ok((image, texture_atlas)) = get_sprite_resources_for_job(
             &anim_db,
             &sprite_db,
-            &player_unit_info,
+            &player_unit_info.job,
             Direction::NE,
             false,
         ) else {
@@ -753,73 +1176,89 @@ pub fn populate_room(
             position,
             image,
             texture_atlas,
-            player_unit_info.job.base_unit_skills(),
             player,
             PLAYER_TEAM,
             Direction::NE,
-            player_unit_info.job,
-            player_unit_info.save_file_key,
+            player_unit_info,
         );
 
-        grid_cursor::spawn_cursor(commands, cursor_image.clone(), player, position);
+        if players_with_cursors.insert(player) {
+            grid_cursor::spawn_cursor(
+                commands,
+                cursor_image.clone(),
+                player,
+                registered_players.player_color(&player),
+                position,
+            );
+        }
     }
 
-    if registered_players.save_files.len() > 1 {
-        spawn_enemy(
-            commands,
-            "Deege".to_string(),
-            tt_assets,
-            &anim_db,
-            enemy_2_grid_pos,
-            tt_assets.cleric_spritesheet.clone(),
-            UnitSkills {
-                learned_skills: HashSet::new(),
-                equipped_skill_categories: Vec::new(),
-            },
-            ENEMY_TEAM,
-        );
+    // Rooms with somewhere for an ally get one to help with the fight
+    if !encounter.is_empty() {
+        for ally_position in &map_data.ally_spawns {
+            spawn_enemy(
+                commands,
+                "Old Maxwell".to_string(),
+                tt_assets,
+                &anim_db,
+                *ally_position,
+                tt_assets.fighter_spritesheet.clone(),
+                UnitSkills {
+                    learned_skills: HashSet::new(),
+                    equipped_skill_categories: Vec::new(),
+                    granted_skills: HashSet::new(),
+                },
+                ALLY_TEAM,
+            );
+        }
+    }
+
+    let mut obstacle_entities = Vec::new();
+    for (obstacle_location, obstacle) in &map_data.obstacles {
+        info!("Obstacle spawning at {:?}", obstacle_location);
+        let e = spawn_obstacle_unit(commands, &tt_assets, *obstacle_location, (*obstacle).into());
+        obstacle_entities.push(e);
+    }
+    spawn_decorations(commands, tt_assets, map_data);
 
-        spawn_enemy(
+    // Rest rooms don't have a fight
+    let Some((leader, supporting)) = encounter.split_first() else {
+        return;
+    };
+    for (i, planned) in supporting.iter().enumerate() {
+        spawn_planned_enemy(
             commands,
-            "Chaumwer".to_string(),
+            supporting_enemy_name(i),
             tt_assets,
-            &anim_db,
-            enemy_3_grid_pos,
-            tt_assets.cleric_spritesheet.clone(),
-            UnitSkills {
-                learned_skills: HashSet::new(),
-                equipped_skill_categories: Vec::new(),
-            },
-            ENEMY_TEAM,
+            anim_db,
+            planned,
         );
     }
 
-    spawn_enemy(
+    let jimothy = spawn_planned_enemy(
         commands,
         "Jimothy Timbers".to_string(),
         tt_assets,
-        &anim_db,
-        enemy_1_grid_pos,
-        tt_assets.cleric_spritesheet.clone(),
-        UnitSkills {
-            learned_skills: HashSet::new(),
-            equipped_skill_categories: Vec::new(),
-        },
-        ENEMY_TEAM,
+        anim_db,
+        leader,
     );
 
-    let mut obstacle_entities = Vec::new();
-    for (obstacle_location, obstacle) in &map_data.obstacles {
-        info!("Obstacle spawning at {:?}", obstacle_location);
-        let sprite_type = match obstacle {
-            crate::map_generation::Obstacle::Rock1 => ObstacleSprite::Rock,
-            crate::map_generation::Obstacle::Rock2 => ObstacleSprite::Rock,
-            crate::map_generation::Obstacle::Bush => ObstacleSprite::Bush,
-            crate::map_generation::Obstacle::Tree => ObstacleSprite::Tree,
-        };
+    if boss {
+        commands
+            .entity(jimothy)
+            .insert((Boss, EnemyLoot::guaranteed(LootTableId::Rare)));
+    }
 
-        let e = spawn_obstacle_unit(commands, &tt_assets, *obstacle_location, sprite_type);
-        obstacle_entities.push(e);
+    // If the map authored a patrol route, Jimothy walks it instead of charging in.
+    if let Some(route) = map_data.patrol_routes.first()
+        && route.first() == Some(&leader.position)
+    {
+        commands.entity(jimothy).insert((
+            EnemyAiBehavior {
+                behavior: Behavior::Wanderer,
+            },
+            PatrolRoute::new(route.clone()),
+        ));
     }
 }
 
@@ -0,0 +1,337 @@
+//! A running log of everything that happened in the battle.
+//!
+//! Systems that do something worth remembering write a [`BattleLogMessage`], which gets turned
+//! into a line of text on the [`BattleLog`]. The log panel is toggled with
+//! `PlayerInputAction::ToggleBattleLog` and scrolls with the mouse wheel.
+
+use bevy::{input::mouse::MouseWheel, prelude::*};
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    assets::FontResource,
+    battle::BattleEntity,
+    battle_phase::PlayerEnemyPhase,
+    combat::skills::{SkillDBResource, SkillId},
+    menu::ui_consts::{UI_MENU_BACKGROUND, UI_TEXT_COLOR},
+    player::{Player, PlayerInputAction},
+    unit::Unit,
+};
+
+/// Only hang on to so many lines
+pub const MAX_LOG_ENTRIES: usize = 200;
+
+#[derive(Message, Debug, Clone)]
+pub enum BattleLogMessage {
+    PhaseBegin {
+        phase: PlayerEnemyPhase,
+        turn: u32,
+    },
+    SkillImpact {
+        attacker: Option<Entity>,
+        defender: Entity,
+        skill: SkillId,
+        /// Negative for damage, positive for healing
+        health_change: i32,
+    },
+    SkillMissed {
+        attacker: Option<Entity>,
+        defender: Entity,
+        skill: SkillId,
+    },
+    Blocked {
+        defender: Entity,
+    },
+    CriticalHit {
+        attacker: Entity,
+    },
+    /// The attacker was fast enough to get a second hit in
+    StruckTwice {
+        attacker: Entity,
+    },
+    EffectApplied {
+        target: Entity,
+        description: String,
+    },
+    LevelUp {
+        unit: Entity,
+        level: u32,
+    },
+    SkillLearned {
+        unit: Entity,
+        skill: String,
+    },
+    UnitTransferred {
+        unit: Entity,
+        new_owner: Player,
+    },
+    LootFound {
+        unit: Entity,
+        description: String,
+    },
+    GoldEarned {
+        gold: u32,
+    },
+    LootDropped {
+        unit: Entity,
+        description: String,
+    },
+    ItemUsed {
+        user: Entity,
+        target: Entity,
+        item: String,
+    },
+    /// Gear changing hands. Nobody on one side means the party's bag.
+    ItemTraded {
+        from: Option<Entity>,
+        to: Option<Entity>,
+        item: String,
+    },
+    WeaponBroke {
+        unit: Entity,
+        item: String,
+    },
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct BattleLog {
+    pub entries: Vec<String>,
+}
+
+impl BattleLog {
+    pub fn push(&mut self, entry: String) {
+        if self.entries.len() >= MAX_LOG_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.entries.push(entry);
+    }
+}
+
+#[derive(Component)]
+pub struct BattleLogPanel;
+
+#[derive(Component)]
+pub struct BattleLogText;
+
+pub fn reset_battle_log(mut commands: Commands) {
+    commands.insert_resource(BattleLog::default());
+}
+
+fn unit_name(units: &Query<&Unit>, entity: Entity) -> String {
+    units
+        .get(entity)
+        .map(|t| t.name.clone())
+        .unwrap_or_else(|_| "Someone".to_string())
+}
+
+pub fn record_battle_log(
+    mut reader: MessageReader<BattleLogMessage>,
+    mut battle_log: ResMut<BattleLog>,
+    units: Query<&Unit>,
+    skill_db: Res<SkillDBResource>,
+) {
+    for message in reader.read() {
+        let entry = match message {
+            BattleLogMessage::PhaseBegin { phase, turn } => {
+                format!("-- Turn {}: {:?} Phase --", turn, phase)
+            }
+            BattleLogMessage::SkillImpact {
+                attacker,
+                defender,
+                skill,
+                health_change,
+            } => {
+                let skill_name = &skill_db.skill_db.get_skill(skill).name;
+                let defender = unit_name(&units, *defender);
+                let outcome = if *health_change < 0 {
+                    format!("{} takes {} damage", defender, health_change.unsigned_abs())
+                } else if *health_change > 0 {
+                    format!("{} recovers {} HP", defender, health_change)
+                } else {
+                    format!("{} is unharmed", defender)
+                };
+
+                match attacker {
+                    Some(attacker) => format!(
+                        "{} uses {}: {}",
+                        unit_name(&units, *attacker),
+                        skill_name,
+                        outcome
+                    ),
+                    None => format!("{}: {}", skill_name, outcome),
+                }
+            }
+            BattleLogMessage::SkillMissed {
+                attacker,
+                defender,
+                skill,
+            } => {
+                let skill_name = &skill_db.skill_db.get_skill(skill).name;
+                let defender = unit_name(&units, *defender);
+                match attacker {
+                    Some(attacker) => format!(
+                        "{} uses {}: misses {}",
+                        unit_name(&units, *attacker),
+                        skill_name,
+                        defender
+                    ),
+                    None => format!("{}: misses {}", skill_name, defender),
+                }
+            }
+            BattleLogMessage::Blocked { defender } => {
+                format!("{} blocks with their shield", unit_name(&units, *defender))
+            }
+            BattleLogMessage::CriticalHit { attacker } => {
+                format!("{} lands a critical hit", unit_name(&units, *attacker))
+            }
+            BattleLogMessage::StruckTwice { attacker } => {
+                format!("{} strikes twice", unit_name(&units, *attacker))
+            }
+            BattleLogMessage::EffectApplied {
+                target,
+                description,
+            } => format!("{} gains {}", unit_name(&units, *target), description),
+            BattleLogMessage::LevelUp { unit, level } => {
+                format!("{} reached level {}!", unit_name(&units, *unit), level)
+            }
+            BattleLogMessage::SkillLearned { unit, skill } => {
+                format!("{} learned {}!", unit_name(&units, *unit), skill)
+            }
+            BattleLogMessage::UnitTransferred { unit, new_owner } => format!(
+                "{} is now commanded by Player {}",
+                unit_name(&units, *unit),
+                new_owner.id()
+            ),
+            BattleLogMessage::LootFound { unit, description } => {
+                format!("{} finds {}", unit_name(&units, *unit), description)
+            }
+            BattleLogMessage::GoldEarned { gold } => {
+                format!("The party earns {} gold for the win", gold)
+            }
+            BattleLogMessage::LootDropped { unit, description } => {
+                format!("{} dropped {}", unit_name(&units, *unit), description)
+            }
+            BattleLogMessage::ItemUsed { user, target, item } if user == target => {
+                format!("{} uses a {}", unit_name(&units, *user), item)
+            }
+            BattleLogMessage::ItemUsed { user, target, item } => format!(
+                "{} uses a {} on {}",
+                unit_name(&units, *user),
+                item,
+                unit_name(&units, *target)
+            ),
+            BattleLogMessage::ItemTraded { from, to, item } => match (from, to) {
+                (Some(from), Some(to)) => format!(
+                    "{} hands {} to {}",
+                    unit_name(&units, *from),
+                    item,
+                    unit_name(&units, *to)
+                ),
+                (Some(from), None) => {
+                    format!("{} puts {} in the bag", unit_name(&units, *from), item)
+                }
+                (None, Some(to)) => {
+                    format!("{} takes {} out of the bag", unit_name(&units, *to), item)
+                }
+                (None, None) => format!("{} gets shuffled around the bag", item),
+            },
+            BattleLogMessage::WeaponBroke { unit, item } => {
+                format!("{}'s {} breaks", unit_name(&units, *unit), item)
+            }
+        };
+
+        info!("Battle Log: {}", entry);
+        battle_log.push(entry);
+    }
+}
+
+pub fn spawn_battle_log_panel(mut commands: Commands, fonts: Res<FontResource>) {
+    commands.spawn((
+        Name::new("BattleLogPanel"),
+        Node {
+            display: Display::None,
+            position_type: PositionType::Absolute,
+            top: percent(15),
+            right: percent(1),
+            width: percent(25),
+            height: percent(50),
+            padding: UiRect::all(px(8)),
+            border_radius: BorderRadius::all(px(8)),
+            overflow: Overflow::scroll_y(),
+            ..Default::default()
+        },
+        ScrollPosition::default(),
+        BackgroundColor(UI_MENU_BACKGROUND.with_alpha(0.85)),
+        GlobalZIndex(5),
+        BattleLogPanel,
+        BattleEntity {},
+        children![(
+            Text::default(),
+            TextColor(UI_TEXT_COLOR),
+            TextFont {
+                font: fonts.pixelify_sans_regular.clone(),
+                font_size: 16.,
+                ..Default::default()
+            },
+            BattleLogText,
+        )],
+    ));
+}
+
+pub fn toggle_battle_log_panel(
+    input_query: Query<&ActionState<PlayerInputAction>>,
+    mut panel: Query<&mut Node, With<BattleLogPanel>>,
+) {
+    if !input_query
+        .iter()
+        .any(|t| t.just_pressed(&PlayerInputAction::ToggleBattleLog))
+    {
+        return;
+    }
+
+    for mut node in panel.iter_mut() {
+        node.display = match node.display {
+            Display::None => Display::Flex,
+            _ => Display::None,
+        };
+    }
+}
+
+pub fn update_battle_log_panel(
+    battle_log: Res<BattleLog>,
+    mut text: Query<&mut Text, With<BattleLogText>>,
+    mut panel: Query<&mut ScrollPosition, With<BattleLogPanel>>,
+) {
+    if !battle_log.is_changed() {
+        return;
+    }
+
+    for mut text in text.iter_mut() {
+        text.0 = battle_log.entries.join("\n");
+    }
+
+    // Follow the newest entry. Layout clamps this to the bottom of the content
+    for mut scroll in panel.iter_mut() {
+        scroll.y = f32::MAX;
+    }
+}
+
+pub fn scroll_battle_log_panel(
+    mut wheel: MessageReader<MouseWheel>,
+    mut panel: Query<(&Node, &ComputedNode, &mut ScrollPosition), With<BattleLogPanel>>,
+) {
+    const LINE_HEIGHT: f32 = 20.;
+    let delta: f32 = wheel.read().map(|t| t.y).sum();
+    if delta == 0. {
+        return;
+    }
+
+    for (node, computed, mut scroll) in panel.iter_mut() {
+        if node.display == Display::None {
+            continue;
+        }
+
+        let max_scroll = (computed.content_size().y - computed.size().y).max(0.)
+            * computed.inverse_scale_factor();
+        scroll.y = (scroll.y - delta * LINE_HEIGHT).clamp(0., max_scroll);
+    }
+}
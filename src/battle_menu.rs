@@ -13,13 +13,18 @@ use crate::{
     },
     battle_phase::UnitPhaseResources,
     combat::skills,
+    gameplay_effects::ActiveEffects,
     grid::{self, GridManagerResource},
     grid_cursor::Cursor,
+    inventory::Consumable,
     menu::{
+        MenuCleanup, MenuStackCommands,
         menu_navigation::{ActiveMenu, GameMenuController, GameMenuGrid},
         ui_consts::{SELECTABLE_BUTTON_BACKGROUND, UI_TEXT_COLOR},
     },
     player::{self, Player, PlayerInputAction},
+    tr,
+    trade::{TradeOffer, TradePartner},
     unit::Unit,
     unit_stats::{StatType, UnitDerivedStats},
 };
@@ -43,6 +48,10 @@ pub struct PlayerUiHealthText {}
 #[derive(Component)]
 pub struct PlayerUiNameText {}
 
+/// A marker component for the row along the bottom holding every player's battle UI
+#[derive(Component)]
+pub struct BattleUiRoot;
+
 /// A marker component for the Objective UI
 #[derive(Component)]
 pub struct ObjectiveUi {}
@@ -63,6 +72,18 @@ pub struct BattleUiContainer {
     pub(crate) map_viewer: Entity,
 }
 
+impl BattleUiContainer {
+    /// Every menu in the container, from the standard menu up
+    pub(crate) fn menus(&self) -> [Entity; 4] {
+        [
+            self.standard,
+            self.skills_menu,
+            self.filtered_skills_menu,
+            self.map_viewer,
+        ]
+    }
+}
+
 /// Marker component for the third tier of the Battle Menu
 #[derive(Component)]
 pub struct SkillsFilteredByCategoryMenu {}
@@ -84,7 +105,15 @@ pub enum BattleMenuAction {
     Action(UnitMenuAction),
     OpenSkillMenu,
     OpenSkillsFilteredByCategoryMenu(skills::SkillCategoryId),
+    /// Everything the party has that can be used up
+    OpenItemMenu,
+    /// Who the unit could trade gear with
+    OpenTradeMenu,
+    /// The unit's gear next to the partner's, to swap between
+    OpenTradeWith(TradePartner),
     ViewMap,
+    /// Ask the player if they really want to end their phase
+    OpenEndTurnPrompt,
 }
 
 /// A terminal node in the BattleMenu. Turned into a `UnitCommand` and sent out
@@ -96,6 +125,8 @@ pub enum UnitMenuAction {
     UseSkill(skills::SkillId),
     Wait,
     Interact(Entity),
+    UseItem(Consumable),
+    Trade(TradeOffer),
 }
 
 #[derive(Component)]
@@ -106,6 +137,8 @@ pub struct UnitViewerScreen {
     name: Entity,
     info_container: Entity,
     stat_box: StatBox,
+    /// What all the status icons over the unit mean
+    effects: Entity,
 }
 
 #[derive(Component)]
@@ -140,6 +173,7 @@ fn build_stat_box_ui(commands: &mut Commands) -> StatBox {
 /// Includes the definition of the Battle Menus, PlayerUI, and the ObjectiveUI.
 pub mod battle_menu_ui_definition {
     use crate::{
+        localization::localized_text,
         menu::{
             menu_navigation::GameMenuLatch,
             ui_consts::{UI_MENU_BACKGROUND, UI_TEXT_COLOR},
@@ -189,8 +223,7 @@ pub mod battle_menu_ui_definition {
                 BackgroundColor(UI_MENU_BACKGROUND),
                 ObjectiveUi {},
                 children![(
-                    // Lol
-                    Text("Objective:    Defeat all Enemies".to_string()),
+                    localized_text("battle_menu.objective"),
                     TextColor(UI_TEXT_COLOR),
                     ObjectiveText {},
                     TextFont {
@@ -228,10 +261,12 @@ pub mod battle_menu_ui_definition {
                     ..Default::default()
                 },
                 BattleEntity {},
+                BattleUiRoot,
             ))
             .id();
 
         for player in registered_players.save_files.keys().cloned() {
+            let player_color = registered_players.player_color(&player);
             let player_ui_container = commands
                 .spawn((
                     Name::new(format!("PlayerUiContainer {:?}", player)),
@@ -242,9 +277,11 @@ pub mod battle_menu_ui_definition {
                         padding: UiRect::bottom(percent(2)),
                         justify_content: JustifyContent::SpaceEvenly,
                         flex_direction: FlexDirection::Column,
+                        border: UiRect::all(px(3)),
                         border_radius: BorderRadius::all(percent(20)),
                         ..Default::default()
                     },
+                    BorderColor::all(player_color),
                 ))
                 .id();
 
@@ -270,7 +307,7 @@ pub mod battle_menu_ui_definition {
                     font_style
                         .clone()
                         .with_font(fonts.pixelify_sans_regular.clone()),
-                    TextColor(UI_TEXT_COLOR),
+                    TextColor(player_color),
                 ))
                 .id();
             let ap_text = commands
@@ -339,6 +376,7 @@ pub mod battle_menu_ui_definition {
                     player,
                     SkillMenu {},
                     GameMenuLatch::default(),
+                    MenuCleanup::DespawnChildren,
                     PlayerBattleMenu,
                 ))
                 .id();
@@ -347,7 +385,7 @@ pub mod battle_menu_ui_definition {
                 .spawn(battle_ui_button(
                     fonts,
                     BattleMenuAction::Action(UnitMenuAction::Move),
-                    "Move",
+                    &tr!("battle_menu.move"),
                 ))
                 .id();
 
@@ -355,7 +393,23 @@ pub mod battle_menu_ui_definition {
                 .spawn(battle_ui_button(
                     fonts,
                     BattleMenuAction::OpenSkillMenu,
-                    "Skills",
+                    &tr!("battle_menu.skills"),
+                ))
+                .id();
+
+            let items_button = commands
+                .spawn(battle_ui_button(
+                    fonts,
+                    BattleMenuAction::OpenItemMenu,
+                    &tr!("battle_menu.items"),
+                ))
+                .id();
+
+            let trade_button = commands
+                .spawn(battle_ui_button(
+                    fonts,
+                    BattleMenuAction::OpenTradeMenu,
+                    &tr!("battle_menu.trade"),
                 ))
                 .id();
 
@@ -363,7 +417,7 @@ pub mod battle_menu_ui_definition {
                 .spawn(battle_ui_button(
                     fonts,
                     BattleMenuAction::Action(UnitMenuAction::Wait),
-                    "Wait",
+                    &tr!("battle_menu.wait"),
                 ))
                 .id();
 
@@ -371,12 +425,28 @@ pub mod battle_menu_ui_definition {
                 .spawn(battle_ui_button(
                     fonts,
                     BattleMenuAction::ViewMap,
-                    "View Map",
+                    &tr!("battle_menu.view_map"),
+                ))
+                .id();
+
+            let end_turn_button = commands
+                .spawn(battle_ui_button(
+                    fonts,
+                    BattleMenuAction::OpenEndTurnPrompt,
+                    &tr!("battle_menu.end_turn"),
                 ))
                 .id();
 
             let mut menu = GameMenuGrid::new_vertical();
-            menu.push_buttons_to_stack(&[move_button, skills_button, wait_button, view_map_button]);
+            menu.push_buttons_to_stack(&[
+                move_button,
+                skills_button,
+                items_button,
+                trade_button,
+                wait_button,
+                view_map_button,
+                end_turn_button,
+            ]);
 
             let standard_battle_menu_container = commands
                 .spawn((
@@ -423,6 +493,7 @@ pub mod battle_menu_ui_definition {
                     player,
                     SkillsFilteredByCategoryMenu {},
                     GameMenuLatch::default(),
+                    MenuCleanup::DespawnChildren,
                     PlayerBattleMenu,
                 ))
                 .id();
@@ -458,10 +529,20 @@ pub mod battle_menu_ui_definition {
                 ))
                 .id();
 
+            let unit_view_effects_text = commands
+                .spawn((
+                    Text::new(""),
+                    font_style.clone().with_font_size(18.),
+                    TextColor(UI_TEXT_COLOR),
+                    UnitViewerItem,
+                ))
+                .id();
+
             let stat_box = build_stat_box_ui(commands);
             let mut view_map_children = Vec::new();
             view_map_children.push(unit_view_name_text);
             view_map_children.extend(stat_box.stat_texts.values());
+            view_map_children.push(unit_view_effects_text);
 
             let view_map_container = commands
                 .spawn((
@@ -482,6 +563,7 @@ pub mod battle_menu_ui_definition {
                         name: unit_view_name_text,
                         info_container: view_map_info_container,
                         stat_box,
+                        effects: unit_view_effects_text,
                     },
                     GameMenuLatch::default(),
                     PlayerBattleMenu,
@@ -497,7 +579,15 @@ pub mod battle_menu_ui_definition {
 
             commands
                 .entity(standard_battle_menu_container)
-                .add_children(&[move_button, skills_button, wait_button, view_map_button]);
+                .add_children(&[
+                    move_button,
+                    skills_button,
+                    items_button,
+                    trade_button,
+                    wait_button,
+                    view_map_button,
+                    end_turn_button,
+                ]);
 
             // Build Battle UI
             let battle_menu_container = commands
@@ -593,24 +683,46 @@ pub struct ControlledUnitUiEntities {
 }
 
 // We want this to update anytime the Unit's resources change or
-// if the controlled unit changes. I'm not sure how to express that with Bevy's
-// filters.
+// if the controlled unit changes, so the change detection is done by hand.
 pub fn update_controlled_ui_info(
     player_unit_ui: Query<(&player::Player, &ControlledUnitUiEntities)>,
-    unit_query: Query<
-        (&Unit, &UnitPhaseResources, &Player, &UnitDerivedStats),
-        Or<(
-            Changed<Unit>,
-            Changed<UnitDerivedStats>,
-            Changed<UnitPhaseResources>,
-        )>,
+    selected_units: Query<
+        (&Player, Ref<player_battle_ui_systems::ActiveBattleMenu>),
+        With<BattlePlayerUI>,
     >,
+    unit_query: Query<(
+        Entity,
+        Ref<Unit>,
+        Ref<UnitPhaseResources>,
+        Ref<Player>,
+        Ref<UnitDerivedStats>,
+    )>,
     // So would this block any other queries updating text in the Game?
     mut text: Query<&mut Text>,
 ) {
     for (player, controlled_ui) in player_unit_ui {
-        for (unit, resources, unit_player, unit_stats) in unit_query {
-            if player != unit_player {
+        // Players with more than one unit see whichever one they're commanding
+        let selected = selected_units.iter().find(|(p, _)| *p == player);
+
+        for (unit_e, unit, resources, unit_player, unit_stats) in unit_query {
+            if *player != *unit_player {
+                continue;
+            }
+
+            if let Some((_, menu)) = &selected
+                && menu.selected_unit != unit_e
+            {
+                continue;
+            }
+
+            let selection_changed = selected.as_ref().is_some_and(|(_, t)| t.is_changed());
+            // Units handed over from another player count as changed too
+            if !selection_changed
+                && !unit.is_changed()
+                && !unit_player.is_changed()
+                && !resources.is_changed()
+                && !unit_stats.is_changed()
+            {
                 continue;
             }
 
@@ -619,19 +731,25 @@ pub fn update_controlled_ui_info(
             }
 
             if let Ok(mut text_item) = text.get_mut(controlled_ui.health_text) {
-                text_item.0 = format!(
-                    "HP: {} / {}",
-                    unit_stats.stats.stat(StatType::Health).0 as u32,
-                    unit_stats.stats.stat(StatType::MaxHealth).0 as u32,
+                text_item.0 = tr!(
+                    "battle_menu.health",
+                    health = unit_stats.stats.stat(StatType::Health).0 as u32,
+                    max_health = unit_stats.stats.stat(StatType::MaxHealth).0 as u32,
                 );
             }
 
             if let Ok(mut text_item) = text.get_mut(controlled_ui.move_text) {
-                text_item.0 = format!("Move: {}", resources.movement_points_left_in_phase);
+                text_item.0 = tr!(
+                    "battle_menu.movement",
+                    movement = resources.movement_available()
+                );
             }
 
             if let Ok(mut text_item) = text.get_mut(controlled_ui.ap_text) {
-                text_item.0 = format!("AP: {}", resources.action_points_left_in_phase);
+                text_item.0 = tr!(
+                    "battle_menu.action_points",
+                    ap = resources.action_points_left_in_phase
+                );
             }
         }
     }
@@ -652,6 +770,7 @@ pub mod player_info_ui_systems {
             &Unit,
             Option<&UnitPhaseResources>,
             Option<&UnitDerivedStats>,
+            Option<&ActiveEffects>,
         )>,
         player_unit_viewer: Query<(&player::Player, &UnitViewerScreen)>,
         mut vis_mutator: Query<&mut Visibility, With<UnitViewerItem>>,
@@ -663,7 +782,7 @@ pub mod player_info_ui_systems {
                     continue;
                 }
 
-                let Some((unit, _phase_resources, stats)) = grid_manager
+                let Some((unit, _phase_resources, stats, effects)) = grid_manager
                     .grid_manager
                     .get_by_position(grid_pos)
                     .and_then(|t| t.iter().filter_map(|t| unit_query.get(*t).ok()).next())
@@ -689,6 +808,19 @@ pub mod player_info_ui_systems {
                     text_item.0 = unit.name.clone();
                 }
 
+                if let Ok(mut text_item) = text_query.get_mut(unit_viewer_screen.effects) {
+                    text_item.0 = effects
+                        .map(|t| {
+                            t.effects
+                                .iter()
+                                .filter(|t| t.is_temporary())
+                                .map(|t| t.description())
+                                .collect::<Vec<_>>()
+                                .join("\n")
+                        })
+                        .unwrap_or_default();
+                }
+
                 if let Some(stats) = stats {
                     for (stat, stat_ui_entity) in &unit_viewer_screen.stat_box.stat_texts {
                         let Some(mut text_item) = text_query.get_mut(*stat_ui_entity).ok() else {
@@ -714,13 +846,28 @@ pub mod player_battle_ui_systems {
 
     use crate::{
         assets::sounds::{SoundManagerParam, UiSound},
-        battle_phase::{PhaseMessage, PhaseMessageType, PlayerEnemyPhase},
+        battle::UnitTransferMessage,
+        battle_log::BattleLogMessage,
+        battle_phase::{
+            EndPhaseEarlyMessage, PhaseMessage, PhaseMessageType, PlayerEnemyPhase, TurnQueue,
+            has_turn, phase_timer::PhaseTimerExpiredMessage,
+        },
         combat::skills::{ATTACK_SKILL_ID, SkillDBResource, UnitSkills},
+        confirm_dialog::{
+            ConfirmDialogAction, ConfirmDialogMessage, open_confirm_dialog_with_detail,
+        },
         equipment::UnitEquipment,
         grid::GridPosition,
         grid_cursor::LockedOn,
+        inventory::PartyInventory,
         menu::NestedDynamicMenu,
-        unit::UnitActionCompletedMessage,
+        threat_map::{AtRiskUnit, ThreatMapParam},
+        tooltip::Tooltip,
+        trade::TradeParam,
+        unit::{
+            UnitActionCompletedMessage,
+            overlay::{OverlaysAction, OverlaysMessage},
+        },
     };
 
     use super::*;
@@ -742,6 +889,138 @@ pub mod player_battle_ui_systems {
         }
     }
 
+    /// Picks up the answers to the End Turn and Wait prompts opened from the battle menu.
+    pub fn handle_battle_menu_confirm_dialogs(
+        mut commands: Commands,
+        mut reader: MessageReader<ConfirmDialogMessage>,
+        mut battle_command_writer: MessageWriter<UnitUiCommandMessage>,
+        mut end_phase_writer: MessageWriter<EndPhaseEarlyMessage>,
+    ) {
+        for message in reader.read().filter(|t| t.confirmed) {
+            match message.action {
+                ConfirmDialogAction::EndTurn { player, menu } => {
+                    // The standard menu comes back at the start of the next Player Phase
+                    clean_stale_menu(&mut commands, menu);
+                    end_phase_writer.write(EndPhaseEarlyMessage { player });
+                }
+                ConfirmDialogAction::WaitAnyway { player, unit, menu } => {
+                    clean_stale_menu(&mut commands, menu);
+                    battle_command_writer.write(UnitUiCommandMessage {
+                        player,
+                        command: UnitCommand::Wait,
+                        unit,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Once the Player Phase timer runs out, nobody gets to finish what they were doing.
+    ///
+    /// The standard menu comes back at the start of the next Player Phase.
+    pub fn close_battle_menus_on_phase_timeout(
+        mut commands: Commands,
+        mut reader: MessageReader<PhaseTimerExpiredMessage>,
+        mut player_state: ResMut<player::PlayerGameStates>,
+        open_menus: Query<Entity, With<ActiveBattleMenu>>,
+        mut overlay_message_writer: MessageWriter<OverlaysMessage>,
+    ) {
+        if reader.read().count() == 0 {
+            return;
+        }
+
+        for menu in open_menus {
+            clean_stale_menu(&mut commands, menu);
+        }
+
+        for (player, state) in player_state.player_state.iter_mut() {
+            state.cursor_state = player::PlayerCursorState::Idle;
+            overlay_message_writer.write(OverlaysMessage {
+                player: *player,
+                action: OverlaysAction::Despawn,
+            });
+        }
+    }
+
+    /// Handles [`UnitTransferMessage`]s.
+    ///
+    /// If the previous owner was in the middle of commanding the unit, their menus get closed and
+    /// their cursor is set free, so they can pick one of their other units from the map. The new
+    /// owner picks it up the same way.
+    #[allow(clippy::too_many_arguments)]
+    pub fn transfer_unit_ownership(
+        mut commands: Commands,
+        mut reader: MessageReader<UnitTransferMessage>,
+        mut player_state: ResMut<player::PlayerGameStates>,
+        mut units: Query<&mut Player, With<Unit>>,
+        battle_menus: Query<(&Player, &ActiveBattleMenu), Without<Unit>>,
+        battle_ui_containers: Query<(&Player, &BattleUiContainer), Without<Unit>>,
+        cursors: Query<(Entity, &Player), (With<Cursor>, Without<Unit>)>,
+        mut overlay_message_writer: MessageWriter<OverlaysMessage>,
+        mut battle_log_writer: MessageWriter<BattleLogMessage>,
+    ) {
+        for message in reader.read() {
+            let Ok(mut owner) = units.get_mut(message.unit) else {
+                warn!(
+                    "Can't transfer {:?}, it isn't a player's unit",
+                    message.unit
+                );
+                continue;
+            };
+
+            let previous_owner = *owner;
+            if previous_owner == message.new_owner {
+                continue;
+            }
+            *owner = message.new_owner;
+            info!(
+                "Transferring {:?} from {:?} to {:?}",
+                message.unit, previous_owner, message.new_owner
+            );
+
+            let was_commanding = battle_menus
+                .iter()
+                .any(|(p, menu)| *p == previous_owner && menu.selected_unit == message.unit);
+            let state = player_state.player_state.get_mut(&previous_owner);
+            let was_targeting = state.as_ref().is_some_and(|t| match &t.cursor_state {
+                player::PlayerCursorState::MovingUnit(e, ..)
+                | player::PlayerCursorState::LookingForTargetWithAttack(e, ..) => {
+                    *e == message.unit
+                }
+                player::PlayerCursorState::Idle => false,
+            });
+
+            if was_commanding || was_targeting {
+                for (_, ui_container) in battle_ui_containers
+                    .iter()
+                    .filter(|(p, _)| **p == previous_owner)
+                {
+                    for menu in ui_container.menus() {
+                        clean_stale_menu(&mut commands, menu);
+                    }
+                }
+
+                if let Some(state) = state {
+                    state.cursor_state = player::PlayerCursorState::Idle;
+                }
+                overlay_message_writer.write(OverlaysMessage {
+                    player: previous_owner,
+                    action: OverlaysAction::Despawn,
+                });
+
+                for (cursor, _) in cursors.iter().filter(|(_, p)| **p == previous_owner) {
+                    commands.entity(cursor).remove::<LockedOn>();
+                }
+            }
+
+            battle_log_writer.write(BattleLogMessage::UnitTransferred {
+                unit: message.unit,
+                new_owner: message.new_owner,
+            });
+        }
+    }
+
     /// If the player has selected a terminal node in the BattleUi, but then clicks back
     /// we use this handler to reactivate the battle menu, without clearing the previous state.
     pub fn reactivate_ui_on_back_message(
@@ -784,7 +1063,7 @@ pub mod player_battle_ui_systems {
         mut commands: Commands,
         mut reader: MessageReader<UnitActionCompletedMessage>,
         grid_manager: Res<GridManagerResource>,
-        player_query: Query<&Player, With<Unit>>,
+        player_query: Query<(Entity, &Player, &UnitPhaseResources), With<Unit>>,
         battle_ui_container_query: Query<(&Player, &BattleUiContainer)>,
         mut battle_ui_query: Query<(Entity, &Player, &mut GameMenuGrid), With<BattlePlayerUI>>,
         mut cursor_query: Query<(Entity, &Player, &mut GridPosition), With<Cursor>>,
@@ -792,10 +1071,21 @@ pub mod player_battle_ui_systems {
         for m in reader.read() {
             info!("Unit Action Completed: {:?}", m);
 
-            let Some(player) = player_query.get(m.unit).ok() else {
+            let Some((_, player, resources)) = player_query.get(m.unit).ok() else {
                 continue;
             };
 
+            // Once a unit is done for the phase, move on to one of the player's other units
+            let next_unit = if resources.can_act() {
+                m.unit
+            } else {
+                player_query
+                    .iter()
+                    .find(|(_, p, r)| *p == player && r.can_act())
+                    .map(|(e, ..)| e)
+                    .unwrap_or(m.unit)
+            };
+
             // The unit is controlled by a player and just finished an action, re-open
             // the player's menu.
             for (menu, menu_player, mut menu_grid) in battle_ui_query.iter_mut() {
@@ -806,7 +1096,7 @@ pub mod player_battle_ui_systems {
                 commands.entity(menu).insert((
                     ActiveMenu {},
                     ActiveBattleMenu {
-                        selected_unit: m.unit,
+                        selected_unit: next_unit,
                     },
                 ));
 
@@ -818,7 +1108,7 @@ pub mod player_battle_ui_systems {
                     continue;
                 }
 
-                if let Some(unit_pos) = grid_manager.grid_manager.get_by_id(&m.unit) {
+                if let Some(unit_pos) = grid_manager.grid_manager.get_by_id(&next_unit) {
                     *pos = unit_pos;
                 }
 
@@ -829,9 +1119,9 @@ pub mod player_battle_ui_systems {
                 if p != player {
                     continue;
                 }
-                clean_stale_menu(&mut commands, ui_container.skills_menu, true);
-                clean_stale_menu(&mut commands, ui_container.filtered_skills_menu, true);
-                clean_stale_menu(&mut commands, ui_container.map_viewer, false);
+                clean_stale_menu(&mut commands, ui_container.skills_menu);
+                clean_stale_menu(&mut commands, ui_container.filtered_skills_menu);
+                clean_stale_menu(&mut commands, ui_container.map_viewer);
             }
         }
     }
@@ -864,26 +1154,20 @@ pub mod player_battle_ui_systems {
         }
     }
 
-    /// Utility function for cleaning up a stale skill menu
-    pub fn clean_stale_menu(commands: &mut Commands, menu_e: Entity, despawn_children: bool) {
-        let mut skill_menu = commands.entity(menu_e);
-        if despawn_children {
-            skill_menu.despawn_children();
-        }
-        skill_menu.remove::<(
-            GameMenuGrid,
-            ActiveBattleMenu,
-            NestedDynamicMenu,
-            ActiveMenu,
-        )>();
+    /// Utility function for cleaning up a stale battle menu. Skill menus get emptied out,
+    /// see [`MenuCleanup`].
+    pub fn clean_stale_menu(commands: &mut Commands, menu_e: Entity) {
+        commands.close_menu(menu_e);
+        commands.entity(menu_e).try_remove::<ActiveBattleMenu>();
     }
 
-    /// At the moment a player has exactly one Unit on the board.
-    /// This system links the UI that the player will get to that entity.
+    /// Links each player's UI to one of their units when their phase begins.
+    /// Players with more than one unit can swap to another one from the map.
     pub fn set_active_battle_menu_on_player_turn(
         mut commands: Commands,
         mut reader: MessageReader<PhaseMessage>,
-        player_units: Query<(Entity, &Player), With<Unit>>,
+        turn_queue: Option<Res<TurnQueue>>,
+        player_units: Query<(Entity, &Player, &UnitDerivedStats), With<Unit>>,
         battle_menus: Query<(Entity, &Player), With<BattlePlayerUI>>,
     ) {
         for message in reader.read() {
@@ -891,7 +1175,16 @@ pub mod player_battle_ui_systems {
                 continue;
             };
 
-            for (e, player) in player_units {
+            let mut handled_players = HashSet::new();
+            for (e, player, stats) in player_units {
+                // Players with every unit downed are spectating, see `crate::spectator`
+                if stats.downed()
+                    || !has_turn(turn_queue.as_deref(), e)
+                    || !handled_players.insert(*player)
+                {
+                    continue;
+                }
+
                 for (battle_menu_e, battle_player) in battle_menus {
                     if player != battle_player {
                         continue;
@@ -919,19 +1212,105 @@ pub mod player_battle_ui_systems {
                 if *p != message.player {
                     continue;
                 }
-                clean_stale_menu(&mut commands, ui_container.skills_menu, true);
-                clean_stale_menu(&mut commands, ui_container.filtered_skills_menu, true);
-                clean_stale_menu(&mut commands, ui_container.map_viewer, false);
+                clean_stale_menu(&mut commands, ui_container.skills_menu);
+                clean_stale_menu(&mut commands, ui_container.filtered_skills_menu);
+                clean_stale_menu(&mut commands, ui_container.map_viewer);
             }
         }
     }
 
-    /// The set of components that one menu should pass to the next.
-    #[derive(Bundle)]
+    /// What one menu should pass to the next.
     struct SkillMenuHandMeDowns {
         battle_menu: ActiveBattleMenu,
         controller: GameMenuController,
-        nested: NestedDynamicMenu,
+        /// The menu underneath this one in the stack
+        parent: Entity,
+    }
+
+    /// Lays out both sides of a trade next to each other, each under the name of whoever's
+    /// giving it up.
+    fn open_trade_menu(
+        commands: &mut Commands,
+        fonts: &FontResource,
+        trades: &TradeParam,
+        trade_menu: Entity,
+        names: [String; 2],
+        columns: [Vec<TradeOffer>; 2],
+        hand_me_downs: SkillMenuHandMeDowns,
+    ) {
+        let mut menu = GameMenuGrid::new_with_width(2);
+        let row = commands
+            .spawn(Node {
+                width: percent(100),
+                height: percent(100),
+                flex_direction: FlexDirection::Row,
+                ..Default::default()
+            })
+            .id();
+
+        let mut first_button = None;
+        for (col, (name, offers)) in names.into_iter().zip(columns).enumerate() {
+            let column = commands
+                .spawn((
+                    Node {
+                        width: percent(50),
+                        height: percent(100),
+                        flex_direction: FlexDirection::Column,
+                        justify_content: JustifyContent::FlexStart,
+                        ..Default::default()
+                    },
+                    children![(
+                        Text::new(name),
+                        TextFont {
+                            font_size: 20.0,
+                            font: fonts.pixelify_sans_regular.clone(),
+                            font_smoothing: bevy::text::FontSmoothing::None,
+                            ..Default::default()
+                        },
+                        TextColor(UI_TEXT_COLOR),
+                    )],
+                ))
+                .id();
+
+            for offer in offers {
+                let button = commands
+                    .spawn(battle_ui_button(
+                        fonts,
+                        BattleMenuAction::Action(UnitMenuAction::Trade(offer)),
+                        &trades.item_name(offer.item),
+                    ))
+                    .id();
+                if let Err(e) = menu.push_button_to_column(col as u8 + 1, button) {
+                    error!("Couldn't add trade button: {:?}", e);
+                    continue;
+                }
+                first_button.get_or_insert(button);
+                commands.entity(column).add_child(button);
+            }
+
+            commands.entity(row).add_child(column);
+        }
+
+        // The unit might not have anything to give, so start wherever there's a button
+        if let Some(button) = first_button {
+            menu.set_active_menu_option(&button);
+        }
+
+        commands.entity(trade_menu).add_child(row).insert((
+            hand_me_downs.battle_menu,
+            hand_me_downs.controller,
+            menu,
+        ));
+        commands.push_menu(hand_me_downs.parent, trade_menu);
+    }
+
+    fn at_risk_warning(at_risk: &[AtRiskUnit]) -> String {
+        let names = at_risk
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("\n{}", tr!("battle_menu.at_risk", names = names))
     }
 
     fn initialize_skill_menu(
@@ -940,11 +1319,13 @@ pub mod player_battle_ui_systems {
         buttons: Vec<Entity>,
         hand_me_downs: SkillMenuHandMeDowns,
     ) {
-        let mut skill_menu_e = commands.entity(skill_menu_entity);
-        skill_menu_e.add_children(buttons.as_slice());
         let mut menu = GameMenuGrid::new_vertical();
         menu.push_buttons_to_stack(buttons.as_slice());
-        skill_menu_e.insert((hand_me_downs, menu, ActiveMenu {}));
+        commands
+            .entity(skill_menu_entity)
+            .add_children(buttons.as_slice())
+            .insert((hand_me_downs.battle_menu, hand_me_downs.controller, menu));
+        commands.push_menu(hand_me_downs.parent, skill_menu_entity);
     }
 
     /// The chonky function that handles most of the logic here.
@@ -958,10 +1339,12 @@ pub mod player_battle_ui_systems {
     ///
     /// TODO: Could split this into one query on ActiveMenu that handles Select / Deselect
     /// and then another that handles what to with a given Action being pressed?
+    #[allow(clippy::too_many_arguments)]
     pub fn handle_battle_ui_interactions(
         mut commands: Commands,
         fonts: Res<FontResource>,
         skill_db: Res<SkillDBResource>,
+        inventory: Option<Res<PartyInventory>>,
         player_input_query: Query<(&Player, &ActionState<PlayerInputAction>)>,
         battle_ui_container_query: Query<(&Player, &BattleUiContainer)>,
         mut active_player_battle_menu: Query<
@@ -970,17 +1353,20 @@ pub mod player_battle_ui_systems {
                 &ActiveBattleMenu,
                 &mut GameMenuGrid,
                 &GameMenuController,
-                Option<&NestedDynamicMenu>,
+                Has<NestedDynamicMenu>,
             ),
             With<ActiveMenu>,
         >,
         unit_menu_query: Query<&BattleMenuAction>,
         unit_info_query: Query<(&UnitSkills, &UnitPhaseResources, &UnitEquipment)>,
+        player_units: Query<(Entity, &Player, &UnitPhaseResources), With<Unit>>,
+        threat_map: ThreatMapParam,
+        trades: TradeParam,
         mut battle_command_writer: MessageWriter<UnitUiCommandMessage>,
         sounds: SoundManagerParam,
     ) {
         for (player, input_actions) in player_input_query.iter() {
-            let Some((battle_menu_e, battle_menu, menu, controller, nested)) =
+            let Some((battle_menu_e, battle_menu, menu, controller, is_nested)) =
                 active_player_battle_menu
                     .iter_mut()
                     .find(|(_, _, _, controller, _)| controller.players.contains(player))
@@ -1019,43 +1405,56 @@ pub mod player_battle_ui_systems {
                 // So then a Cancel goes back to
                 match menu_option {
                     BattleMenuAction::Action(action) => {
+                        let command = match action {
+                            UnitMenuAction::Move => UnitCommand::Move,
+                            UnitMenuAction::Attack => UnitCommand::Attack,
+                            UnitMenuAction::Wait => UnitCommand::Wait,
+                            UnitMenuAction::UseSkill(skill_id) => UnitCommand::UseSkill(*skill_id),
+                            UnitMenuAction::Interact(e) => UnitCommand::Interact(*e),
+                            UnitMenuAction::UseItem(item) => UnitCommand::UseItem(*item),
+                            UnitMenuAction::Trade(offer) => UnitCommand::Trade(*offer),
+                        };
+
                         // Check if the Unit can take this action or not!
-                        match action {
-                            UnitMenuAction::Move => {
-                                if unit_resources.movement_points_left_in_phase == 0 {
-                                    sounds.play_ui_sound(&mut commands, UiSound::Error);
-                                    info!(
-                                        "It'd be nice if the player was told why they can't move!"
-                                    );
-                                    continue;
-                                }
-                            }
-                            // TODO: Calculate the AP cost for the skill, don't assume it's just 1.
-                            UnitMenuAction::UseSkill(skill_id) => {
-                                if unit_resources.action_points_left_in_phase == 0 {
-                                    sounds.play_ui_sound(&mut commands, UiSound::Error);
-                                    info!(
-                                        "It'd be nice if the player was told why they can't use this skill! We are assuming {:?} has AP 1",
-                                        skill_id
-                                    );
-                                    continue;
-                                }
+                        if !unit_resources.can_afford(&command.cost(&skill_db.skill_db)) {
+                            sounds.play_ui_sound(&mut commands, UiSound::Error);
+                            info!(
+                                "It'd be nice if the player was told why they can't {:?}!",
+                                command
+                            );
+                            continue;
+                        }
+
+                        // If this is the last unit to Wait, double check that nobody is
+                        // being left out to dry before the phase ends.
+                        let last_to_act = !player_units.iter().any(|(e, p, resources)| {
+                            p == player && e != battle_menu.selected_unit && resources.can_act()
+                        });
+                        if matches!(command, UnitCommand::Wait) && last_to_act {
+                            let at_risk = threat_map.units_at_risk(player);
+                            if !at_risk.is_empty() {
+                                open_confirm_dialog_with_detail(
+                                    &mut commands,
+                                    &fonts,
+                                    ConfirmDialogAction::WaitAnyway {
+                                        player: *player,
+                                        unit: battle_menu.selected_unit,
+                                        menu: battle_menu_e,
+                                    },
+                                    at_risk_warning(&at_risk),
+                                    battle_menu_e,
+                                    controller.players.clone(),
+                                );
+
+                                sounds.play_ui_sound(&mut commands, UiSound::Select);
+                                continue;
                             }
-                            _ => {}
-                        };
+                        }
 
                         sounds.play_ui_sound(&mut commands, UiSound::Select);
                         battle_command_writer.write(UnitUiCommandMessage {
                             player: *player,
-                            command: match action {
-                                UnitMenuAction::Move => UnitCommand::Move,
-                                UnitMenuAction::Attack => UnitCommand::Attack,
-                                UnitMenuAction::Wait => UnitCommand::Wait,
-                                UnitMenuAction::UseSkill(skill_id) => {
-                                    UnitCommand::UseSkill(*skill_id)
-                                }
-                                UnitMenuAction::Interact(e) => UnitCommand::Interact(*e),
-                            },
+                            command,
                             unit: battle_menu.selected_unit,
                         });
                         info!("I am removing the active menu for {:?}", battle_menu_e);
@@ -1078,25 +1477,30 @@ pub mod player_battle_ui_systems {
                             .unwrap_or(ATTACK_SKILL_ID);
 
                         let attack_button = commands
-                            .spawn(battle_ui_button(
-                                &fonts,
-                                BattleMenuAction::Action(UnitMenuAction::UseSkill(attack_skill)),
-                                "Attack",
+                            .spawn((
+                                battle_ui_button(
+                                    &fonts,
+                                    BattleMenuAction::Action(UnitMenuAction::UseSkill(
+                                        attack_skill,
+                                    )),
+                                    &tr!("battle_menu.attack"),
+                                ),
+                                Tooltip::new(
+                                    skill_db.skill_db.get_skill(&attack_skill).description(),
+                                ),
                             ))
                             .id();
 
                         let mut buttons = Vec::new();
                         buttons.push(attack_button);
 
-                        for category_id in &unit_skills.equipped_skill_categories {
-                            let category = skill_db.skill_db.get_category(category_id);
+                        for category_id in unit_skills.skill_categories(&skill_db.skill_db) {
+                            let category = skill_db.skill_db.get_category(&category_id);
 
                             let button_id = commands
                                 .spawn(battle_ui_button(
                                     &fonts,
-                                    BattleMenuAction::OpenSkillsFilteredByCategoryMenu(
-                                        *category_id,
-                                    ),
+                                    BattleMenuAction::OpenSkillsFilteredByCategoryMenu(category_id),
                                     &category.name,
                                 ))
                                 .id();
@@ -1111,14 +1515,11 @@ pub mod player_battle_ui_systems {
                             SkillMenuHandMeDowns {
                                 battle_menu: battle_menu.to_owned(),
                                 controller: controller.to_owned(),
-                                nested: NestedDynamicMenu {
-                                    parent: battle_menu_e,
-                                },
+                                parent: battle_menu_e,
                             },
                         );
 
                         sounds.play_ui_sound(&mut commands, UiSound::Select);
-                        commands.entity(battle_menu_e).remove::<ActiveMenu>();
                     }
                     BattleMenuAction::OpenSkillsFilteredByCategoryMenu(selected_category) => {
                         let skill_menu_category = battle_ui_container.filtered_skills_menu;
@@ -1130,7 +1531,7 @@ pub mod player_battle_ui_systems {
                         };
 
                         let mut buttons = Vec::new();
-                        for skill_id in &unit_skills.learned_skills {
+                        for skill_id in unit_skills.known_skills() {
                             if selected_category
                                 != skill_db.skill_db.get_category_for_skill(skill_id)
                             {
@@ -1138,58 +1539,189 @@ pub mod player_battle_ui_systems {
                             }
 
                             let skill = skill_db.skill_db.get_skill(skill_id);
+                            let button_id = commands
+                                .spawn((
+                                    battle_ui_button(
+                                        &fonts,
+                                        BattleMenuAction::Action(UnitMenuAction::UseSkill(
+                                            *skill_id,
+                                        )),
+                                        &skill.name,
+                                    ),
+                                    Tooltip::new(skill.description()),
+                                ))
+                                .id();
+
+                            buttons.push(button_id)
+                        }
+
+                        initialize_skill_menu(
+                            &mut commands,
+                            skill_menu_category,
+                            buttons,
+                            SkillMenuHandMeDowns {
+                                battle_menu: battle_menu.to_owned(),
+                                controller: controller.to_owned(),
+                                parent: battle_menu_e,
+                            },
+                        );
+
+                        sounds.play_ui_sound(&mut commands, UiSound::Select);
+                    }
+                    BattleMenuAction::OpenItemMenu => {
+                        let consumables = inventory
+                            .as_ref()
+                            .map(|t| t.consumables.clone())
+                            .unwrap_or_default();
+                        if consumables.is_empty() {
+                            sounds.play_ui_sound(&mut commands, UiSound::Error);
+                            info!("The party doesn't have anything to use");
+                            continue;
+                        }
+
+                        let mut buttons = Vec::new();
+                        for (item, count) in consumables {
                             let button_id = commands
                                 .spawn(battle_ui_button(
                                     &fonts,
-                                    BattleMenuAction::Action(UnitMenuAction::UseSkill(*skill_id)),
-                                    &skill.name,
+                                    BattleMenuAction::Action(UnitMenuAction::UseItem(item)),
+                                    &tr!(
+                                        "battle_menu.item",
+                                        item = tr!(item.name_key()),
+                                        count = count
+                                    ),
                                 ))
                                 .id();
 
                             buttons.push(button_id)
                         }
 
+                        // Only one sub menu is open at a time, so the skill menu can hold these
                         initialize_skill_menu(
                             &mut commands,
-                            skill_menu_category,
+                            battle_ui_container.skills_menu,
                             buttons,
                             SkillMenuHandMeDowns {
                                 battle_menu: battle_menu.to_owned(),
                                 controller: controller.to_owned(),
-                                nested: NestedDynamicMenu {
-                                    parent: battle_menu_e,
-                                },
+                                parent: battle_menu_e,
                             },
                         );
 
                         sounds.play_ui_sound(&mut commands, UiSound::Select);
-                        commands.entity(battle_menu_e).remove::<ActiveMenu>();
                     }
-                    BattleMenuAction::ViewMap => {
+                    BattleMenuAction::OpenTradeMenu => {
+                        let neighbours = trades.neighbours(battle_menu.selected_unit);
+                        if neighbours.is_empty() {
+                            sounds.play_ui_sound(&mut commands, UiSound::Error);
+                            info!("Nobody is close enough to trade with");
+                            continue;
+                        }
+
+                        let mut buttons = Vec::new();
+                        for partner in neighbours
+                            .into_iter()
+                            .map(TradePartner::Unit)
+                            .chain([TradePartner::Bag])
+                        {
+                            let button_id = commands
+                                .spawn(battle_ui_button(
+                                    &fonts,
+                                    BattleMenuAction::OpenTradeWith(partner),
+                                    &trades.name(partner),
+                                ))
+                                .id();
+
+                            buttons.push(button_id)
+                        }
+
+                        initialize_skill_menu(
+                            &mut commands,
+                            battle_ui_container.skills_menu,
+                            buttons,
+                            SkillMenuHandMeDowns {
+                                battle_menu: battle_menu.to_owned(),
+                                controller: controller.to_owned(),
+                                parent: battle_menu_e,
+                            },
+                        );
+
                         sounds.play_ui_sound(&mut commands, UiSound::Select);
-                        commands.entity(battle_menu_e).remove::<ActiveMenu>();
-                        commands.entity(battle_ui_container.map_viewer).insert((
-                            battle_menu.to_owned(),
-                            NestedDynamicMenu {
+                    }
+                    BattleMenuAction::OpenTradeWith(partner) => {
+                        let unit = TradePartner::Unit(battle_menu.selected_unit);
+                        // Only offer what the other side could actually hold
+                        let columns = [(unit, *partner), (*partner, unit)].map(|(from, to)| {
+                            trades
+                                .items(from)
+                                .into_iter()
+                                .filter(|item| trades.can_take(to, *item))
+                                .map(|item| TradeOffer { from, to, item })
+                                .collect::<Vec<_>>()
+                        });
+                        if columns.iter().all(|t| t.is_empty()) {
+                            sounds.play_ui_sound(&mut commands, UiSound::Error);
+                            info!("Nothing either side can hand over");
+                            continue;
+                        }
+
+                        open_trade_menu(
+                            &mut commands,
+                            &fonts,
+                            &trades,
+                            battle_ui_container.filtered_skills_menu,
+                            [trades.name(unit), trades.name(*partner)],
+                            columns,
+                            SkillMenuHandMeDowns {
+                                battle_menu: battle_menu.to_owned(),
+                                controller: controller.to_owned(),
                                 parent: battle_menu_e,
                             },
-                            controller.to_owned(),
-                            ActiveMenu {},
-                        ));
+                        );
+
+                        sounds.play_ui_sound(&mut commands, UiSound::Select);
+                    }
+                    BattleMenuAction::ViewMap => {
+                        sounds.play_ui_sound(&mut commands, UiSound::Select);
+                        commands
+                            .entity(battle_ui_container.map_viewer)
+                            .insert((battle_menu.to_owned(), controller.to_owned()));
+                        commands.push_menu(battle_menu_e, battle_ui_container.map_viewer);
                         battle_command_writer.write(UnitUiCommandMessage {
                             player: *player,
                             command: UnitCommand::ViewMap,
                             unit: battle_menu.selected_unit,
                         });
                     }
+                    BattleMenuAction::OpenEndTurnPrompt => {
+                        let at_risk = threat_map.units_at_risk(player);
+                        let warning = if at_risk.is_empty() {
+                            String::new()
+                        } else {
+                            at_risk_warning(&at_risk)
+                        };
+
+                        open_confirm_dialog_with_detail(
+                            &mut commands,
+                            &fonts,
+                            ConfirmDialogAction::EndTurn {
+                                player: *player,
+                                menu: battle_menu_e,
+                            },
+                            warning,
+                            battle_menu_e,
+                            controller.players.clone(),
+                        );
+
+                        sounds.play_ui_sound(&mut commands, UiSound::Select);
+                    }
                 }
-            } else if input_actions.just_pressed(&PlayerInputAction::Deselect)
-                && let Some(dynamic_menu) = nested
-            {
+            } else if input_actions.just_pressed(&PlayerInputAction::Deselect) && is_nested {
                 sounds.play_ui_sound(&mut commands, UiSound::Cancel);
-                let parent = dynamic_menu.parent;
-                clean_stale_menu(&mut commands, battle_menu_e, true);
-                commands.entity(parent).insert(ActiveMenu {});
+                commands.pop_menu(battle_menu_e);
+                commands
+                    .entity(battle_menu_e)
+                    .try_remove::<ActiveBattleMenu>();
             }
         }
     }
@@ -1,22 +1,34 @@
 //! Managing your tactics game with a Player Phase / Enemy Phase? Use this!
 //!
 //! Remember a lil yagni never hurt anyone though. For now tries not to be too generic
-//! and just assumes there's only a Player / Enemy Phase.
+//! and just assumes there's a Player / Ally / Enemy Phase. Phases for factions without any
+//! units standing are skipped.
+//!
+//! Alternatively, the `TurnModel::Initiative` mode has individual units act in Speed order.
+//! The PhaseManager still tracks which side the active unit is on, so the phase
+//! gated systems keep working, but only the unit at the front of the `TurnQueue` gets resources.
+
+use std::collections::VecDeque;
 
 use bevy::prelude::*;
 
 use crate::{
-    battle::Enemy,
-    battle_phase::phase_ui::{BattlePhaseMessageComplete, ShowBattleBannerMessage},
+    battle::{Ally, Enemy},
+    battle_log::BattleLogMessage,
+    battle_phase::{
+        phase_ui::{BattlePhaseMessageComplete, ShowBattleBannerMessage},
+        turn_order_ui::TurnOrderHighlighted,
+    },
     combat::{
         AttackExecution, CombatTimeline,
         skills::{SkillDBResource, SkillId},
     },
-    gameplay_effects::{ActiveEffects, EffectDuration, StatusTag},
+    gameplay_effects::{ActiveEffects, EffectDuration, REGENERATION_HEAL, StatusTag},
     grid::GridPosition,
     player::Player,
-    unit::CombatActionMarker,
-    unit_stats::{StatType, UnitDerivedStats},
+    run_save::PendingRunRestore,
+    unit::{CombatActionMarker, Unit},
+    unit_stats::{StatType, StatValue, StatsDirty, UnitDerivedStats, UnitStatChangeRequest},
 };
 
 /// The Phase Manager keeps track of the current phase globally for the battle.
@@ -27,6 +39,66 @@ pub struct PhaseManager {
     pub turn_count: u32,
 }
 
+/// Sent whenever the battle moves on to a new turn, with the new `PhaseManager::turn_count`.
+///
+/// A turn starts with the Player Phase, or with a fresh round of the `TurnQueue`.
+/// Systems that care about the turn count should listen for this rather than checking the counter.
+#[derive(Message, Debug, Clone, Copy)]
+pub struct TurnAdvancedMessage {
+    pub turn: u32,
+}
+
+/// How turns are handed out during battle
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TurnModel {
+    /// Every Player unit acts, then every Enemy unit acts.
+    #[default]
+    Phases,
+    /// Units act one at a time in order of their Speed.
+    Initiative,
+}
+
+pub fn uses_phases(model: Res<TurnModel>) -> bool {
+    *model == TurnModel::Phases
+}
+
+pub fn uses_initiative(model: Res<TurnModel>) -> bool {
+    *model == TurnModel::Initiative
+}
+
+/// The shared timeline of units for the `TurnModel::Initiative` mode.
+///
+/// Only present while running in Initiative mode.
+#[derive(Resource, Debug, Default)]
+pub struct TurnQueue {
+    /// Units left to act this round, fastest first
+    pub upcoming: VecDeque<Entity>,
+    /// The unit whose turn it currently is
+    pub active: Option<Entity>,
+    pub round: u32,
+}
+
+impl TurnQueue {
+    pub fn is_units_turn(&self, entity: Entity) -> bool {
+        self.active == Some(entity)
+    }
+
+    /// Order units by Speed, fastest first. Ties are broken by spawn order
+    /// so the timeline is stable between rounds.
+    pub fn order_by_speed(units: impl IntoIterator<Item = (Entity, f32)>) -> VecDeque<Entity> {
+        let mut units = units.into_iter().collect::<Vec<_>>();
+        units.sort_by(|(e1, speed1), (e2, speed2)| speed2.total_cmp(speed1).then(e1.cmp(e2)));
+        units.into_iter().map(|(e, _)| e).collect()
+    }
+}
+
+/// Whether or not this unit should be affected by the current turn.
+///
+/// In `TurnModel::Phases` there's no TurnQueue, so every unit on the side whose phase it is has a turn.
+pub fn has_turn(turn_queue: Option<&TurnQueue>, entity: Entity) -> bool {
+    turn_queue.is_none_or(|queue| queue.is_units_turn(entity))
+}
+
 /// Basically a boolean gate to avoid fast ending a turn
 /// and coordinating between our systems
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -39,18 +111,56 @@ pub enum PhaseState {
 #[derive(PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord, Debug)]
 pub enum PlayerEnemyPhase {
     Player,
+    /// AI controlled units fighting alongside the Players
+    Ally,
     Enemy,
 }
 
 impl PlayerEnemyPhase {
+    pub const ALL: [PlayerEnemyPhase; 3] = [
+        PlayerEnemyPhase::Player,
+        PlayerEnemyPhase::Ally,
+        PlayerEnemyPhase::Enemy,
+    ];
+
     pub fn next(&self) -> Self {
         match self {
-            PlayerEnemyPhase::Player => PlayerEnemyPhase::Enemy,
+            PlayerEnemyPhase::Player => PlayerEnemyPhase::Ally,
+            PlayerEnemyPhase::Ally => PlayerEnemyPhase::Enemy,
             PlayerEnemyPhase::Enemy => PlayerEnemyPhase::Player,
         }
     }
+
+    /// The next phase that has a faction able to take it
+    pub fn next_with_units(&self, has_units: impl Fn(PlayerEnemyPhase) -> bool) -> Self {
+        let mut next = self.next();
+        for _ in 0..Self::ALL.len() {
+            if has_units(next) {
+                break;
+            }
+            next = next.next();
+        }
+        next
+    }
+
+    /// Which phase a unit acts in, based on the faction markers it carries
+    pub fn for_unit(is_player: bool, is_ally: bool) -> Self {
+        match (is_player, is_ally) {
+            (true, _) => PlayerEnemyPhase::Player,
+            (false, true) => PlayerEnemyPhase::Ally,
+            (false, false) => PlayerEnemyPhase::Enemy,
+        }
+    }
+
+    /// Whether the phase is run by the AI or not
+    pub fn is_ai_controlled(&self) -> bool {
+        *self != PlayerEnemyPhase::Player
+    }
 }
 
+/// Query filter for any unit belonging to a faction that takes a phase
+pub type FactionUnit = Or<(With<Player>, With<Ally>, With<Enemy>)>;
+
 pub fn is_running_player_phase(pm: Option<Res<PhaseManager>>) -> bool {
     pm.map(|pm| {
         pm.current_phase == PlayerEnemyPhase::Player && pm.phase_state == PhaseState::Running
@@ -70,6 +180,16 @@ pub fn is_enemy_phase(pm: Option<Res<PhaseManager>>) -> bool {
         .unwrap_or_default()
 }
 
+pub fn is_running_ai_phase(pm: Option<Res<PhaseManager>>) -> bool {
+    pm.map(|pm| pm.current_phase.is_ai_controlled() && pm.phase_state == PhaseState::Running)
+        .unwrap_or_default()
+}
+
+pub fn is_ai_phase(pm: Option<Res<PhaseManager>>) -> bool {
+    pm.map(|pm| pm.current_phase.is_ai_controlled())
+        .unwrap_or_default()
+}
+
 #[derive(Clone, Debug)]
 pub enum PhaseMessageType {
     PhaseBegin(PlayerEnemyPhase),
@@ -78,20 +198,175 @@ pub enum PhaseMessageType {
 #[derive(Message, Debug)]
 pub struct PhaseMessage(pub PhaseMessageType);
 
-#[derive(Component, Debug, Reflect, Default)]
+/// What a unit gets to spend each phase.
+///
+/// Jobs tweak this to play differently, e.g. an Archer that can move, shoot, and then move again.
+/// Units without one get the `Default`.
+#[derive(Component, Debug, Clone, Copy, Reflect)]
+pub struct ActionEconomy {
+    /// How many separate moves the unit can make. They all share the unit's movement points.
+    pub move_actions: u32,
+    /// Quick actions, like interacting with something next to the unit.
+    pub minor_actions: u32,
+    /// Spent on skills.
+    pub action_points: u32,
+    /// Whether the unit can still move after spending action points.
+    pub move_after_acting: bool,
+}
+
+impl Default for ActionEconomy {
+    fn default() -> Self {
+        Self {
+            move_actions: 1,
+            minor_actions: 1,
+            action_points: 1,
+            move_after_acting: true,
+        }
+    }
+}
+
+/// What it takes out of a unit's `UnitPhaseResources` to do something.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ActionCost {
+    pub move_actions: u32,
+    pub minor_actions: u32,
+    pub action_points: u32,
+}
+
+#[derive(Component, Debug, Clone, Reflect, Default, serde::Serialize, serde::Deserialize)]
 pub struct UnitPhaseResources {
     pub movement_points_left_in_phase: u32,
+    pub move_actions_left_in_phase: u32,
+    pub minor_actions_left_in_phase: u32,
     pub action_points_left_in_phase: u32,
+    pub move_after_acting: bool,
     pub waited: bool,
 }
 
 impl UnitPhaseResources {
+    pub fn for_phase(economy: &ActionEconomy, movement_points: u32) -> Self {
+        Self {
+            movement_points_left_in_phase: movement_points,
+            move_actions_left_in_phase: economy.move_actions,
+            minor_actions_left_in_phase: economy.minor_actions,
+            action_points_left_in_phase: economy.action_points,
+            move_after_acting: economy.move_after_acting,
+            waited: false,
+        }
+    }
+
+    /// Minor actions alone don't keep a unit's turn going, they're a bonus on top.
     pub fn can_act(&self) -> bool {
         if self.waited {
             return false;
         }
 
-        self.movement_points_left_in_phase > 0 || self.action_points_left_in_phase > 0
+        self.can_move() || self.action_points_left_in_phase > 0
+    }
+
+    pub fn can_move(&self) -> bool {
+        self.move_actions_left_in_phase > 0 && self.movement_points_left_in_phase > 0
+    }
+
+    /// The movement points the unit could use if it moved right now
+    pub fn movement_available(&self) -> u32 {
+        if self.can_move() {
+            self.movement_points_left_in_phase
+        } else {
+            0
+        }
+    }
+
+    pub fn can_afford(&self, cost: &ActionCost) -> bool {
+        if cost.move_actions > 0 && !self.can_move() {
+            return false;
+        }
+
+        self.move_actions_left_in_phase >= cost.move_actions
+            && self.minor_actions_left_in_phase >= cost.minor_actions
+            && self.action_points_left_in_phase >= cost.action_points
+    }
+
+    pub fn spend(&mut self, cost: &ActionCost) {
+        self.move_actions_left_in_phase = self
+            .move_actions_left_in_phase
+            .saturating_sub(cost.move_actions);
+        self.minor_actions_left_in_phase = self
+            .minor_actions_left_in_phase
+            .saturating_sub(cost.minor_actions);
+        self.action_points_left_in_phase = self
+            .action_points_left_in_phase
+            .saturating_sub(cost.action_points);
+
+        if cost.action_points > 0 && !self.move_after_acting {
+            self.move_actions_left_in_phase = 0;
+        }
+    }
+}
+
+/// Tint for units that have used up everything they can do this phase
+pub const ACTED_UNIT_TINT: Color = Color::linear_rgb(0.35, 0.35, 0.35);
+
+/// Units only look spent during their own phase, otherwise everybody would
+/// be greyed out while waiting for their phase to come back around.
+pub fn acted_unit_tint(
+    phase_manager: &PhaseManager,
+    unit_phase: PlayerEnemyPhase,
+    resources: &UnitPhaseResources,
+) -> Color {
+    if phase_manager.current_phase == unit_phase
+        && phase_manager.phase_state == PhaseState::Running
+        && !resources.can_act()
+    {
+        ACTED_UNIT_TINT
+    } else {
+        Color::WHITE
+    }
+}
+
+type TintableUnit<'a> = (
+    &'a UnitPhaseResources,
+    &'a mut Sprite,
+    Has<Player>,
+    Has<Ally>,
+);
+
+/// Dim units once they've acted
+pub fn tint_units_on_phase_resources_changed(
+    phase_manager: Option<Res<PhaseManager>>,
+    mut query: Query<
+        TintableUnit,
+        (
+            FactionUnit,
+            Changed<UnitPhaseResources>,
+            Without<TurnOrderHighlighted>,
+        ),
+    >,
+) {
+    let Some(phase_manager) = phase_manager else {
+        return;
+    };
+
+    for (resources, mut sprite, is_player, is_ally) in query.iter_mut() {
+        sprite.color = acted_unit_tint(
+            &phase_manager,
+            PlayerEnemyPhase::for_unit(is_player, is_ally),
+            resources,
+        );
+    }
+}
+
+/// Restore (or apply) the tint on every unit when the phase changes
+pub fn tint_units_on_phase_change(
+    phase_manager: Res<PhaseManager>,
+    mut query: Query<TintableUnit, (FactionUnit, Without<TurnOrderHighlighted>)>,
+) {
+    for (resources, mut sprite, is_player, is_ally) in query.iter_mut() {
+        sprite.color = acted_unit_tint(
+            &phase_manager,
+            PlayerEnemyPhase::for_unit(is_player, is_ally),
+            resources,
+        );
     }
 }
 
@@ -105,6 +380,11 @@ impl PhaseSystem<PlayerEnemyPhase> for Player {
     const OWNED_PHASE: PlayerEnemyPhase = PlayerEnemyPhase::Player;
 }
 
+impl PhaseSystem<PlayerEnemyPhase> for Ally {
+    type Marker = Self;
+    const OWNED_PHASE: PlayerEnemyPhase = PlayerEnemyPhase::Ally;
+}
+
 impl PhaseSystem<PlayerEnemyPhase> for Enemy {
     type Marker = Self;
     const OWNED_PHASE: PlayerEnemyPhase = PlayerEnemyPhase::Enemy;
@@ -112,24 +392,52 @@ impl PhaseSystem<PlayerEnemyPhase> for Enemy {
 
 pub fn init_phase_system(
     mut commands: Commands,
+    turn_model: Res<TurnModel>,
     mut phase_message_writer: MessageWriter<PhaseMessage>,
+    mut turn_advanced_writer: MessageWriter<TurnAdvancedMessage>,
+    mut battle_log: MessageWriter<BattleLogMessage>,
+    pending_restore: Option<Res<PendingRunRestore>>,
 ) {
+    // A saved run picks back up on the turn it was saved on
+    let first_turn = pending_restore.map(|t| t.0.turn()).unwrap_or(1);
+
     commands.insert_resource(PhaseManager {
-        turn_count: 0,
+        turn_count: if *turn_model == TurnModel::Phases {
+            first_turn
+        } else {
+            first_turn - 1
+        },
         phase_state: PhaseState::Initializing,
         current_phase: PlayerEnemyPhase::Player,
     });
 
+    // The TurnQueue picks who goes first once the units are spawned
+    if *turn_model == TurnModel::Initiative {
+        commands.insert_resource(TurnQueue {
+            round: first_turn - 1,
+            ..Default::default()
+        });
+        return;
+    }
+
     // Will this get picked up by the
     phase_message_writer.write(PhaseMessage(PhaseMessageType::PhaseBegin(
         PlayerEnemyPhase::Player,
     )));
+    turn_advanced_writer.write(TurnAdvancedMessage { turn: first_turn });
+    battle_log.write(BattleLogMessage::PhaseBegin {
+        phase: PlayerEnemyPhase::Player,
+        turn: first_turn,
+    });
 }
 
 pub fn check_should_advance_phase<T: PhaseSystem<PlayerEnemyPhase>>(
     mut phase_manager: ResMut<PhaseManager>,
     mut message_writer: MessageWriter<PhaseMessage>,
+    mut turn_advanced_writer: MessageWriter<TurnAdvancedMessage>,
+    mut battle_log: MessageWriter<BattleLogMessage>,
     query: Query<(&UnitPhaseResources, &UnitDerivedStats), With<T::Marker>>,
+    faction_query: Query<(&UnitDerivedStats, Has<Player>, Has<Ally>), FactionUnit>,
     wait_for_no_attacks_ongoing: Query<Entity, With<CombatActionMarker>>,
 ) {
     if phase_manager.current_phase != T::OWNED_PHASE
@@ -143,30 +451,193 @@ pub fn check_should_advance_phase<T: PhaseSystem<PlayerEnemyPhase>>(
         .iter()
         .all(|(resources, derived_stats)| !resources.can_act() || derived_stats.downed())
     {
-        let next_phase = T::OWNED_PHASE.next();
+        let next_phase = T::OWNED_PHASE.next_with_units(|phase| {
+            faction_query.iter().any(|(stats, is_player, is_ally)| {
+                !stats.downed() && PlayerEnemyPhase::for_unit(is_player, is_ally) == phase
+            })
+        });
         phase_manager.current_phase = next_phase;
         info!("Advancing To Next Phase: {:?}", next_phase);
+        if next_phase == PlayerEnemyPhase::Player {
+            phase_manager.turn_count += 1;
+            turn_advanced_writer.write(TurnAdvancedMessage {
+                turn: phase_manager.turn_count,
+            });
+        }
         phase_manager.phase_state = PhaseState::Initializing;
         message_writer.write(PhaseMessage(PhaseMessageType::PhaseBegin(next_phase)));
+        battle_log.write(BattleLogMessage::PhaseBegin {
+            phase: next_phase,
+            turn: phase_manager.turn_count,
+        });
+    }
+}
+
+/// Sent when a Player chooses to end their turn without having each unit Wait.
+#[derive(Message, Debug)]
+pub struct EndPhaseEarlyMessage {
+    pub player: Player,
+}
+
+/// Spends whatever is left of the Player's units' resources, so the phase
+/// (or in Initiative mode, the active unit's turn) can advance.
+pub fn end_phase_early(
+    mut reader: MessageReader<EndPhaseEarlyMessage>,
+    mut query: Query<(&Player, &mut UnitPhaseResources), With<Unit>>,
+) {
+    for message in reader.read() {
+        info!("{:?} is ending their turn early", message.player);
+        for (player, mut resources) in query.iter_mut() {
+            if *player != message.player {
+                continue;
+            }
+
+            *resources = UnitPhaseResources {
+                waited: true,
+                ..Default::default()
+            };
+        }
     }
 }
 
+/// Hands the next unit in the TurnQueue it's turn once the active unit is done.
+///
+/// Stands in for `check_should_advance_phase` in `TurnModel::Initiative`.
+pub fn advance_turn_queue(
+    mut phase_manager: ResMut<PhaseManager>,
+    turn_queue: Option<ResMut<TurnQueue>>,
+    mut message_writer: MessageWriter<PhaseMessage>,
+    mut turn_advanced_writer: MessageWriter<TurnAdvancedMessage>,
+    mut battle_log: MessageWriter<BattleLogMessage>,
+    units: Query<
+        (
+            Entity,
+            &UnitPhaseResources,
+            &UnitDerivedStats,
+            Has<Player>,
+            Has<Ally>,
+        ),
+        FactionUnit,
+    >,
+    wait_for_no_attacks_ongoing: Query<Entity, With<CombatActionMarker>>,
+) {
+    let Some(mut turn_queue) = turn_queue else {
+        return;
+    };
+
+    if !wait_for_no_attacks_ongoing.is_empty() {
+        return;
+    }
+
+    if let Some(active) = turn_queue.active {
+        if phase_manager.phase_state != PhaseState::Running {
+            return;
+        }
+
+        if let Ok((_, resources, stats, _, _)) = units.get(active)
+            && resources.can_act()
+            && !stats.downed()
+        {
+            return;
+        }
+    }
+
+    let alive = |e: &Entity| {
+        units
+            .get(*e)
+            .is_ok_and(|(_, _, stats, _, _)| !stats.downed())
+    };
+
+    let mut next = turn_queue.upcoming.pop_front();
+    while let Some(e) = next
+        && !alive(&e)
+    {
+        next = turn_queue.upcoming.pop_front();
+    }
+
+    if next.is_none() {
+        let order = TurnQueue::order_by_speed(
+            units
+                .iter()
+                .filter(|(_, _, stats, _, _)| !stats.downed())
+                .map(|(e, _, stats, _, _)| (e, stats.stats.stat(StatType::Speed).0)),
+        );
+
+        if !order.is_empty() {
+            turn_queue.round += 1;
+            turn_queue.upcoming = order;
+            info!("Starting initiative round {}", turn_queue.round);
+            phase_manager.turn_count = turn_queue.round;
+            turn_advanced_writer.write(TurnAdvancedMessage {
+                turn: turn_queue.round,
+            });
+            next = turn_queue.upcoming.pop_front();
+        }
+    }
+
+    let Some(next) = next else {
+        turn_queue.active = None;
+        return;
+    };
+
+    let Ok((_, _, _, is_player, is_ally)) = units.get(next) else {
+        return;
+    };
+
+    let phase = PlayerEnemyPhase::for_unit(is_player, is_ally);
+
+    info!("{:?} is up next in the TurnQueue", next);
+    turn_queue.active = Some(next);
+    phase_manager.current_phase = phase;
+    phase_manager.phase_state = PhaseState::Initializing;
+    message_writer.write(PhaseMessage(PhaseMessageType::PhaseBegin(phase)));
+    battle_log.write(BattleLogMessage::PhaseBegin {
+        phase,
+        turn: phase_manager.turn_count,
+    });
+}
+
 /// Prepares for Phase
 pub fn prepare_for_phase<T: PhaseSystem<PlayerEnemyPhase>>(
     phase_manager: ResMut<PhaseManager>,
+    turn_queue: Option<Res<TurnQueue>>,
     mut message_reader: MessageReader<PhaseMessage>,
-    mut query: Query<(&UnitDerivedStats, &mut UnitPhaseResources), With<T::Marker>>,
+    mut query: Query<
+        (
+            Entity,
+            &UnitDerivedStats,
+            Option<&ActionEconomy>,
+            &mut UnitPhaseResources,
+        ),
+        With<T::Marker>,
+    >,
     mut battle_phase_change_writer: MessageWriter<ShowBattleBannerMessage>,
+    mut banner_complete_writer: MessageWriter<BattlePhaseMessageComplete>,
 ) {
     for message in message_reader.read() {
         let PhaseMessageType::PhaseBegin(phase) = message.0;
 
         if phase == T::OWNED_PHASE && phase_manager.phase_state == PhaseState::Initializing {
-            for (unit, mut phase_resources) in query.iter_mut() {
-                phase_resources.action_points_left_in_phase = 1;
-                phase_resources.movement_points_left_in_phase =
-                    unit.stats.stat(StatType::Movement).0 as u32;
-                phase_resources.waited = false;
+            for (e, unit, economy, mut phase_resources) in query.iter_mut() {
+                // Units waiting on their initiative just sit this one out
+                if !has_turn(turn_queue.as_deref(), e) {
+                    *phase_resources = UnitPhaseResources {
+                        waited: true,
+                        ..Default::default()
+                    };
+                    continue;
+                }
+
+                *phase_resources = UnitPhaseResources::for_phase(
+                    &economy.copied().unwrap_or_default(),
+                    unit.stats.stat(StatType::Movement).0 as u32,
+                );
+            }
+
+            // A banner per unit would get old fast, so skip straight past it.
+            if turn_queue.is_some() {
+                banner_complete_writer.write(BattlePhaseMessageComplete {});
+                continue;
             }
 
             battle_phase_change_writer.write(ShowBattleBannerMessage {
@@ -178,12 +649,18 @@ pub fn prepare_for_phase<T: PhaseSystem<PlayerEnemyPhase>>(
 
 // TODO: It feels like I should apply poison damage here right?
 pub fn decrement_turn_count_effects_on_turn_start<T: PhaseSystem<PlayerEnemyPhase>>(
+    mut commands: Commands,
     mut message_reader: MessageReader<TurnStartMessage>,
-    mut query: Query<&mut ActiveEffects, With<T::Marker>>,
+    turn_queue: Option<Res<TurnQueue>>,
+    mut query: Query<(Entity, &mut ActiveEffects), With<T::Marker>>,
 ) {
     for message in message_reader.read() {
         if message.phase == T::OWNED_PHASE {
-            for mut active_effects in query.iter_mut() {
+            for (e, mut active_effects) in query.iter_mut() {
+                if !has_turn(turn_queue.as_deref(), e) {
+                    continue;
+                }
+
                 for effect in active_effects.effects.iter_mut() {
                     let EffectDuration::TurnCount(turn_count) = &mut effect.data.duration else {
                         continue;
@@ -193,6 +670,7 @@ pub fn decrement_turn_count_effects_on_turn_start<T: PhaseSystem<PlayerEnemyPhas
                 }
 
                 // TODO: Do we need an event here?
+                let before = active_effects.effects.len();
                 active_effects.effects.retain(|t| {
                     if let EffectDuration::TurnCount(turn_count) = t.data.duration {
                         turn_count != 0
@@ -200,6 +678,11 @@ pub fn decrement_turn_count_effects_on_turn_start<T: PhaseSystem<PlayerEnemyPhas
                         true
                     }
                 });
+
+                // Whatever wore off might have been holding the unit's stats up (or down)
+                if active_effects.effects.len() != before {
+                    commands.entity(e).insert(StatsDirty);
+                }
             }
         }
     }
@@ -209,7 +692,9 @@ pub fn check_for_active_effect_damage_on_turn_start<T: PhaseSystem<PlayerEnemyPh
     mut commands: Commands,
     mut message_reader: MessageReader<StartOfPhaseEffectsMessage>,
     skill_db: Res<SkillDBResource>,
-    query: Query<(Entity, &ActiveEffects, &GridPosition), With<T::Marker>>,
+    turn_queue: Option<Res<TurnQueue>>,
+    query: Query<(Entity, &ActiveEffects, &GridPosition, &UnitDerivedStats), With<T::Marker>>,
+    mut stat_change_writer: MessageWriter<UnitStatChangeRequest>,
 ) {
     for message in message_reader.read() {
         if message.phase != T::OWNED_PHASE {
@@ -218,7 +703,19 @@ pub fn check_for_active_effect_damage_on_turn_start<T: PhaseSystem<PlayerEnemyPh
 
         // Handle Poison Damage
         let poison_skill = skill_db.skill_db.get_skill(&SkillId(7));
-        for (e, active_effect, grid_position) in query {
+        for (e, active_effect, grid_position, stats) in query {
+            if !has_turn(turn_queue.as_deref(), e) {
+                continue;
+            }
+
+            if active_effect.statuses().contains(&StatusTag::Regenerating) && !stats.downed() {
+                stat_change_writer.write(UnitStatChangeRequest {
+                    entity: e,
+                    stat: StatType::Health,
+                    stat_change: StatValue(REGENERATION_HEAL),
+                });
+            }
+
             if active_effect.statuses().contains(&StatusTag::Poisoned) {
                 let mut poison_damage_e = commands.spawn(PoisonDamageEntity);
                 let Ok(poison_timeline) = CombatTimeline::build_without_attacker(
@@ -250,7 +747,7 @@ struct PoisonDamageEntity;
 
 #[derive(Message)]
 pub struct TurnStartMessage {
-    phase: PlayerEnemyPhase,
+    pub phase: PlayerEnemyPhase,
 }
 
 #[derive(Message)]
@@ -293,17 +790,58 @@ pub fn start_phase(
     }
 }
 
+/// Banners that sweep across the screen to announce what's happening in the battle.
+///
+/// Only one banner is on screen at a time. Anything requested while one is showing waits in the
+/// [`BattleBannerQueue`], so a boss intro can't clobber the objective, and the phase doesn't
+/// begin until everything ahead of its banner has played.
 pub mod phase_ui {
+    use std::collections::VecDeque;
+
     use bevy::prelude::*;
 
     use crate::{
-        assets::FontResource, battle::BattleEntity, battle_phase::PlayerEnemyPhase,
+        GameState,
+        accessibility::{AccessibilitySettings, ColorPalette},
+        assets::FontResource,
+        battle::{BattleEndCondition, BattleEntity},
+        battle_phase::PlayerEnemyPhase,
         dungeon::DungeonState,
+        enemy::Boss,
+        menu::ui_consts::UI_TEXT_COLOR,
+        tr,
+        unit::Unit,
+        weather::{CurrentWeather, RoomWeather},
     };
 
-    #[derive(Debug)]
+    const BOSS_PORTRAIT_SIZE: f32 = 128.;
+
+    #[derive(Debug, Clone)]
     pub enum BattleBannerMessage {
         PhaseBegin(PlayerEnemyPhase),
+        /// What the players need to do to win, shown as the battle starts
+        Objective(String),
+        BossIntro {
+            name: String,
+            portrait: Option<ImageNode>,
+        },
+        /// Played before heading to the BattleResolution screen
+        BattleEnd(BattleEndCondition),
+        /// Whatever's hanging over the battle, shown after the objective
+        Weather(RoomWeather),
+    }
+
+    impl BattleBannerMessage {
+        /// How long the banner sits on screen once it's entered
+        fn hold_seconds(&self) -> f32 {
+            match self {
+                BattleBannerMessage::PhaseBegin(_) => 0.6,
+                BattleBannerMessage::Objective(_) => 1.4,
+                BattleBannerMessage::BossIntro { .. } => 1.6,
+                BattleBannerMessage::BattleEnd(_) => 1.2,
+                BattleBannerMessage::Weather(_) => 1.4,
+            }
+        }
     }
 
     #[derive(Message, Debug)]
@@ -314,6 +852,12 @@ pub mod phase_ui {
     #[derive(Message)]
     pub struct BattlePhaseMessageComplete {}
 
+    /// Banners waiting on the one that's currently on screen
+    #[derive(Resource, Default, Debug)]
+    pub struct BattleBannerQueue {
+        pending: VecDeque<BattleBannerMessage>,
+    }
+
     #[derive(Component)]
     pub struct BattleBanner;
 
@@ -321,6 +865,7 @@ pub mod phase_ui {
     pub struct BannerAnimation {
         timer: Timer,
         state: BannerAnimState,
+        message: BattleBannerMessage,
     }
 
     enum BannerAnimState {
@@ -329,10 +874,23 @@ pub mod phase_ui {
         Exiting,
     }
 
-    fn spawn_phase_ui(
+    fn banner_text(text: String, color: Color, font: Handle<Font>, font_size: f32) -> impl Bundle {
+        (
+            TextColor(color),
+            Text::new(text),
+            TextFont {
+                font_size,
+                font,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn spawn_banner_ui(
         commands: &mut Commands,
         fonts: &Res<FontResource>,
-        event: &ShowBattleBannerMessage,
+        palette: ColorPalette,
+        message: BattleBannerMessage,
     ) {
         let container = commands
             .spawn((
@@ -345,58 +903,205 @@ pub mod phase_ui {
                 },
                 BackgroundColor(Color::NONE),
                 BattleBanner,
-                BannerAnimation {
-                    timer: Timer::from_seconds(0.4, TimerMode::Once),
-                    state: BannerAnimState::Entering,
-                },
                 BattleEntity {},
                 DespawnOnExit(DungeonState::InBattle),
             ))
             .id();
 
-        let blue = Color::linear_rgba(0.0, 0.0, 1.0, 1.0);
-        let red = Color::linear_rgba(1.0, 0.0, 0.0, 1.0);
-
-        let (color, text) = match &event.message {
-            BattleBannerMessage::PhaseBegin(phase) => match phase {
-                PlayerEnemyPhase::Player => (blue, "PLAYER PHASE"),
-                PlayerEnemyPhase::Enemy => (red, "ENEMY PHASE"),
-            },
+        let banner_node = Node {
+            width: percent(80),
+            height: percent(20),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            column_gap: px(24),
+            border_radius: BorderRadius::all(percent(20)),
+            ..Default::default()
         };
 
-        let banner = commands
-            .spawn((
+        let banner = match &message {
+            BattleBannerMessage::PhaseBegin(phase) => {
+                let text = match phase {
+                    PlayerEnemyPhase::Player => tr!("banner.player_phase"),
+                    PlayerEnemyPhase::Ally => tr!("banner.ally_phase"),
+                    PlayerEnemyPhase::Enemy => tr!("banner.enemy_phase"),
+                };
+
+                commands.spawn((
+                    banner_node,
+                    BackgroundColor(Color::linear_rgba(0.7, 0.7, 0.7, 0.8)),
+                    children![banner_text(
+                        text,
+                        palette.team_color(*phase),
+                        fonts.badge.clone(),
+                        60.
+                    )],
+                ))
+            }
+            BattleBannerMessage::Objective(objective) => commands.spawn((
                 Node {
-                    width: percent(80),
-                    height: percent(20),
-                    justify_content: JustifyContent::Center,
-                    align_items: AlignItems::Center,
-                    border_radius: BorderRadius::all(percent(20)),
-                    ..Default::default()
+                    flex_direction: FlexDirection::Column,
+                    ..banner_node
                 },
-                BackgroundColor(Color::linear_rgba(0.7, 0.7, 0.7, 0.8)),
-                children![(
-                    TextColor(color),
-                    Text::new(text),
-                    TextFont {
-                        font_size: 60.,
-                        font: fonts.badge.clone(),
+                BackgroundColor(Color::linear_rgba(0.055, 0.082, 0.227, 0.85)),
+                children![
+                    banner_text(
+                        tr!("banner.objective"),
+                        Color::linear_rgb(1.0, 0.8, 0.2),
+                        fonts.badge.clone(),
+                        48.
+                    ),
+                    banner_text(
+                        objective.clone(),
+                        UI_TEXT_COLOR,
+                        fonts.pixelify_sans_medium.clone(),
+                        36.
+                    ),
+                ],
+            )),
+            BattleBannerMessage::Weather(weather) => commands.spawn((
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    ..banner_node
+                },
+                BackgroundColor(Color::linear_rgba(0.15, 0.2, 0.25, 0.85)),
+                children![
+                    banner_text(
+                        tr!(weather.name_key()),
+                        Color::linear_rgb(0.6, 0.8, 1.0),
+                        fonts.badge.clone(),
+                        48.
+                    ),
+                    banner_text(
+                        tr!(weather.description_key()),
+                        UI_TEXT_COLOR,
+                        fonts.pixelify_sans_medium.clone(),
+                        36.
+                    ),
+                ],
+            )),
+            BattleBannerMessage::BossIntro { name, portrait } => {
+                let mut banner = commands.spawn((
+                    banner_node,
+                    BackgroundColor(Color::linear_rgba(0.3, 0.02, 0.02, 0.85)),
+                ));
+
+                if let Some(portrait) = portrait {
+                    banner.with_child((
+                        portrait.clone(),
+                        Node {
+                            width: px(BOSS_PORTRAIT_SIZE),
+                            height: px(BOSS_PORTRAIT_SIZE),
+                            ..Default::default()
+                        },
+                    ));
+                }
+
+                banner.with_child((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
                         ..Default::default()
                     },
-                )],
-            ))
-            .id();
+                    children![
+                        banner_text(
+                            tr!("banner.boss"),
+                            Color::linear_rgb(1.0, 0.3, 0.3),
+                            fonts.badge.clone(),
+                            36.
+                        ),
+                        banner_text(name.clone(), UI_TEXT_COLOR, fonts.badge.clone(), 60.),
+                    ],
+                ));
+                banner
+            }
+            BattleBannerMessage::BattleEnd(condition) => {
+                let (text, color) = match condition {
+                    BattleEndCondition::Victory => {
+                        (tr!("banner.victory"), Color::linear_rgb(0.4, 0.7, 0.4))
+                    }
+                    BattleEndCondition::Defeat => {
+                        (tr!("banner.defeat"), Color::linear_rgb(0.7, 0.4, 0.4))
+                    }
+                };
 
-        commands.entity(container).add_child(banner);
+                commands.spawn((
+                    banner_node,
+                    BackgroundColor(Color::linear_rgba(0.0, 0.0, 0.0, 0.8)),
+                    children![banner_text(text, color, fonts.badge.clone(), 80.)],
+                ))
+            }
+        }
+        .id();
+
+        commands
+            .entity(container)
+            .add_child(banner)
+            .insert(BannerAnimation {
+                timer: Timer::from_seconds(0.4, TimerMode::Once),
+                state: BannerAnimState::Entering,
+                message,
+            });
     }
 
+    /// Queues up requested banners, and shows the next one once the screen is clear
     pub fn spawn_banner_system(
         mut commands: Commands,
         font_res: Res<FontResource>,
+        accessibility_settings: Res<AccessibilitySettings>,
+        mut queue: ResMut<BattleBannerQueue>,
         mut events: MessageReader<ShowBattleBannerMessage>,
+        banners: Query<(), With<BattleBanner>>,
     ) {
-        for event in events.read() {
-            spawn_phase_ui(&mut commands, &font_res, event);
+        queue
+            .pending
+            .extend(events.read().map(|t| t.message.clone()));
+
+        if !banners.is_empty() {
+            return;
+        }
+
+        if let Some(message) = queue.pending.pop_front() {
+            spawn_banner_ui(
+                &mut commands,
+                &font_res,
+                accessibility_settings.palette,
+                message,
+            );
+        }
+    }
+
+    pub fn clear_banner_queue(mut queue: ResMut<BattleBannerQueue>) {
+        queue.pending.clear();
+    }
+
+    /// Reveals the objective and the weather, and introduces any bosses in the room
+    pub fn announce_battle_start(
+        bosses: Query<(&Unit, &Sprite), With<Boss>>,
+        weather: Option<Res<CurrentWeather>>,
+        mut writer: MessageWriter<ShowBattleBannerMessage>,
+    ) {
+        writer.write(ShowBattleBannerMessage {
+            message: BattleBannerMessage::Objective(tr!("banner.objective.defeat_all")),
+        });
+
+        if let Some(weather) = weather.and_then(|t| t.0) {
+            writer.write(ShowBattleBannerMessage {
+                message: BattleBannerMessage::Weather(weather),
+            });
+        }
+
+        for (unit, sprite) in bosses {
+            let portrait = match sprite.texture_atlas.clone() {
+                Some(atlas) => ImageNode::from_atlas_image(sprite.image.clone(), atlas),
+                None => ImageNode::new(sprite.image.clone()),
+            };
+
+            writer.write(ShowBattleBannerMessage {
+                message: BattleBannerMessage::BossIntro {
+                    name: unit.name.clone(),
+                    portrait: Some(portrait),
+                },
+            });
         }
     }
 
@@ -405,6 +1110,7 @@ pub mod phase_ui {
         mut commands: Commands,
         mut query: Query<(Entity, &mut BannerAnimation), With<BattleBanner>>,
         mut writer: MessageWriter<BattlePhaseMessageComplete>,
+        mut game_state: ResMut<NextState<GameState>>,
     ) {
         for (entity, mut anim) in &mut query {
             anim.timer.tick(time.delta());
@@ -412,7 +1118,8 @@ pub mod phase_ui {
                 match anim.state {
                     BannerAnimState::Entering => {
                         anim.state = BannerAnimState::Holding;
-                        anim.timer = Timer::from_seconds(0.6, TimerMode::Once);
+                        let hold = anim.message.hold_seconds();
+                        anim.timer = Timer::from_seconds(hold, TimerMode::Once);
                     }
                     BannerAnimState::Holding => {
                         anim.state = BannerAnimState::Exiting;
@@ -420,10 +1127,431 @@ pub mod phase_ui {
                     }
                     BannerAnimState::Exiting => {
                         commands.entity(entity).despawn();
-                        writer.write(BattlePhaseMessageComplete {});
+                        match anim.message {
+                            BattleBannerMessage::PhaseBegin(_) => {
+                                writer.write(BattlePhaseMessageComplete {});
+                            }
+                            BattleBannerMessage::BattleEnd(_) => {
+                                game_state.set(GameState::BattleResolution);
+                            }
+                            BattleBannerMessage::Objective(_)
+                            | BattleBannerMessage::BossIntro { .. }
+                            | BattleBannerMessage::Weather(_) => {}
+                        }
                     }
                 }
             }
         }
     }
 }
+
+/// A strip at the top of the battle screen showing which units are up next.
+///
+/// Works for either TurnModel. In `TurnModel::Phases` we just show the units that can still
+/// act this phase, followed by the other side.
+pub mod turn_order_ui {
+    use bevy::prelude::*;
+
+    use crate::{
+        accessibility::AccessibilitySettings,
+        battle::{Ally, BattleEntity},
+        battle_phase::{
+            FactionUnit, PhaseManager, PlayerEnemyPhase, TurnQueue, UnitPhaseResources,
+            acted_unit_tint,
+        },
+        player::Player,
+        unit_stats::{StatType, UnitDerivedStats},
+    };
+
+    const TURN_ORDER_ICON_SIZE: f32 = 48.;
+    const ICON_BACKGROUND_ALPHA: f32 = 0.6;
+    const HIGHLIGHTED_UNIT_COLOR: Color = Color::linear_rgb(1.0, 1.0, 0.4);
+
+    /// The container for the TurnOrderIcons
+    #[derive(Component, Default)]
+    pub struct TurnOrderBar {
+        /// The order of units the icons were last built for
+        order: Vec<Entity>,
+    }
+
+    /// An icon in the TurnOrderBar representing `unit`
+    #[derive(Component)]
+    pub struct TurnOrderIcon {
+        unit: Entity,
+    }
+
+    /// Marker for a unit whose TurnOrderIcon is being hovered over (or was clicked)
+    #[derive(Component)]
+    pub struct TurnOrderHighlighted;
+
+    /// Marker for a unit whose TurnOrderIcon was clicked, so it stays highlighted after the
+    /// pointer moves off it
+    #[derive(Component)]
+    pub struct TurnOrderPinned;
+
+    pub fn spawn_turn_order_bar(mut commands: Commands) {
+        commands.spawn((
+            Name::new("TurnOrderBar"),
+            Node {
+                position_type: PositionType::Absolute,
+                top: percent(2),
+                left: percent(35),
+                width: percent(60),
+                height: px(TURN_ORDER_ICON_SIZE * 1.25),
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                column_gap: px(8),
+                ..Default::default()
+            },
+            TurnOrderBar::default(),
+            BattleEntity {},
+        ));
+    }
+
+    type TurnOrderUnit<'a> = (
+        Entity,
+        &'a UnitPhaseResources,
+        &'a UnitDerivedStats,
+        &'a Sprite,
+        Has<Player>,
+        Has<Ally>,
+    );
+
+    /// Figure out who acts next, given whichever TurnModel is active
+    fn upcoming_turn_order(
+        phase_manager: &PhaseManager,
+        turn_queue: Option<&TurnQueue>,
+        units: &Query<TurnOrderUnit, FactionUnit>,
+    ) -> Vec<Entity> {
+        let alive = |e: &Entity| {
+            units
+                .get(*e)
+                .is_ok_and(|(_, _, stats, _, _, _)| !stats.downed())
+        };
+
+        if let Some(turn_queue) = turn_queue {
+            return turn_queue
+                .active
+                .iter()
+                .chain(turn_queue.upcoming.iter())
+                .filter(|e| alive(e))
+                .copied()
+                .collect();
+        }
+
+        // Units still able to act this phase, followed by every unit in the phases after it.
+        let mut order = Vec::new();
+        let mut phase = phase_manager.current_phase;
+        for i in 0..PlayerEnemyPhase::ALL.len() {
+            let acting = units
+                .iter()
+                .filter(|(_, resources, stats, _, is_player, is_ally)| {
+                    PlayerEnemyPhase::for_unit(*is_player, *is_ally) == phase
+                        && (i != 0 || resources.can_act())
+                        && !stats.downed()
+                })
+                .map(|(e, _, stats, _, _, _)| (e, stats.stats.stat(StatType::Speed).0));
+            order.extend(TurnQueue::order_by_speed(acting));
+            phase = phase.next();
+        }
+        order
+    }
+
+    /// Rebuilds the icons in the TurnOrderBar whenever the upcoming order changes.
+    pub fn update_turn_order_bar(
+        mut commands: Commands,
+        phase_manager: Option<Res<PhaseManager>>,
+        turn_queue: Option<Res<TurnQueue>>,
+        units: Query<TurnOrderUnit, FactionUnit>,
+        accessibility_settings: Res<AccessibilitySettings>,
+        mut bar_query: Query<(Entity, &mut TurnOrderBar)>,
+    ) {
+        let Some(phase_manager) = phase_manager else {
+            return;
+        };
+
+        let order = upcoming_turn_order(&phase_manager, turn_queue.as_deref(), &units);
+
+        for (bar_e, mut bar) in bar_query.iter_mut() {
+            if bar.order == order && !accessibility_settings.is_changed() {
+                continue;
+            }
+
+            commands.entity(bar_e).despawn_children();
+
+            for (i, unit_e) in order.iter().enumerate() {
+                let Ok((_, _, _, sprite, is_player, is_ally)) = units.get(*unit_e) else {
+                    continue;
+                };
+
+                // The unit acting right now gets a bigger icon
+                let size = if i == 0 {
+                    TURN_ORDER_ICON_SIZE * 1.25
+                } else {
+                    TURN_ORDER_ICON_SIZE
+                };
+
+                let image = match sprite.texture_atlas.clone() {
+                    Some(atlas) => ImageNode::from_atlas_image(sprite.image.clone(), atlas),
+                    None => ImageNode::new(sprite.image.clone()),
+                };
+
+                let icon = commands
+                    .spawn((
+                        Node {
+                            width: px(size),
+                            height: px(size),
+                            border_radius: BorderRadius::all(percent(20)),
+                            ..Default::default()
+                        },
+                        BackgroundColor(
+                            accessibility_settings
+                                .palette
+                                .team_color(PlayerEnemyPhase::for_unit(is_player, is_ally))
+                                .with_alpha(ICON_BACKGROUND_ALPHA),
+                        ),
+                        image,
+                        TurnOrderIcon { unit: *unit_e },
+                    ))
+                    .id();
+
+                commands.entity(bar_e).add_child(icon);
+            }
+
+            bar.order = order.clone();
+        }
+    }
+
+    pub fn highlight_unit_on_turn_order_icon_over(
+        over: On<Pointer<Over>>,
+        mut commands: Commands,
+        icon_query: Query<&TurnOrderIcon>,
+    ) {
+        if let Ok(icon) = icon_query.get(over.entity) {
+            commands.entity(icon.unit).try_insert(TurnOrderHighlighted);
+        }
+    }
+
+    pub fn unhighlight_unit_on_turn_order_icon_out(
+        out: On<Pointer<Out>>,
+        mut commands: Commands,
+        icon_query: Query<&TurnOrderIcon>,
+        pinned: Query<(), With<TurnOrderPinned>>,
+    ) {
+        if let Ok(icon) = icon_query.get(out.entity)
+            && !pinned.contains(icon.unit)
+        {
+            commands
+                .entity(icon.unit)
+                .try_remove::<TurnOrderHighlighted>();
+        }
+    }
+
+    /// Clicking (or tapping) an icon pins the highlight on, since there's no hover on touch.
+    /// Clicking it again unpins it, and it goes once the pointer moves off.
+    pub fn toggle_unit_highlight_on_turn_order_icon_click(
+        mut click: On<Pointer<Click>>,
+        mut commands: Commands,
+        icon_query: Query<&TurnOrderIcon>,
+        pinned: Query<(), With<TurnOrderPinned>>,
+    ) {
+        let Ok(icon) = icon_query.get(click.entity) else {
+            return;
+        };
+        click.propagate(false);
+
+        if pinned.contains(icon.unit) {
+            commands.entity(icon.unit).try_remove::<TurnOrderPinned>();
+        } else {
+            commands
+                .entity(icon.unit)
+                .try_insert((TurnOrderPinned, TurnOrderHighlighted));
+        }
+    }
+
+    pub fn tint_unit_on_turn_order_highlight_added(
+        added: On<Add, TurnOrderHighlighted>,
+        mut sprite_query: Query<&mut Sprite>,
+    ) {
+        if let Ok(mut sprite) = sprite_query.get_mut(added.entity) {
+            sprite.color = HIGHLIGHTED_UNIT_COLOR;
+        }
+    }
+
+    pub fn tint_unit_on_turn_order_highlight_removed(
+        remove: On<Remove, TurnOrderHighlighted>,
+        phase_manager: Option<Res<PhaseManager>>,
+        mut sprite_query: Query<(&mut Sprite, &UnitPhaseResources, Has<Player>, Has<Ally>)>,
+    ) {
+        let Some(phase_manager) = phase_manager else {
+            return;
+        };
+
+        if let Ok((mut sprite, resources, is_player, is_ally)) = sprite_query.get_mut(remove.entity)
+        {
+            sprite.color = acted_unit_tint(
+                &phase_manager,
+                PlayerEnemyPhase::for_unit(is_player, is_ally),
+                resources,
+            );
+        }
+    }
+}
+
+/// An optional countdown for the Player Phase, for when you want a little more party game energy.
+///
+/// When time runs out, any Player units that haven't acted yet just Wait. The timer ticks on
+/// virtual time, so it stops along with everything else while the game is paused.
+pub mod phase_timer {
+    use bevy::prelude::*;
+
+    use crate::{
+        assets::FontResource,
+        battle::BattleEntity,
+        battle_phase::{
+            EndPhaseEarlyMessage, PhaseManager, PhaseState, PlayerEnemyPhase, UnitPhaseResources,
+        },
+        menu::ui_consts::{UI_MENU_BACKGROUND, UI_TEXT_COLOR},
+        player::Player,
+        tr,
+        unit::Unit,
+    };
+
+    /// How long the Player Phase timer should be. Set from the Settings menu.
+    #[derive(Resource, Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+    pub struct PhaseTimerSettings {
+        /// 0 turns the timer off
+        pub seconds: u32,
+    }
+
+    impl PhaseTimerSettings {
+        pub const OPTIONS: [u32; 6] = [0, 15, 30, 45, 60, 90];
+
+        pub fn text(seconds: u32) -> String {
+            if seconds == 0 {
+                tr!("settings.phase_timer_off")
+            } else {
+                tr!("settings.phase_timer", seconds = seconds)
+            }
+        }
+    }
+
+    /// The countdown for the Player Phase currently running.
+    #[derive(Resource, Debug)]
+    pub struct PhaseTimer {
+        pub timer: Timer,
+    }
+
+    /// Sent when the Player Phase timer runs out.
+    #[derive(Message, Debug)]
+    pub struct PhaseTimerExpiredMessage;
+
+    #[derive(Component)]
+    pub struct PhaseTimerText;
+
+    fn player_phase_running(phase_manager: &PhaseManager) -> bool {
+        phase_manager.current_phase == PlayerEnemyPhase::Player
+            && phase_manager.phase_state == PhaseState::Running
+    }
+
+    /// Starts the countdown once the Player Phase is running, and drops it once it isn't.
+    pub fn manage_phase_timer(
+        mut commands: Commands,
+        settings: Res<PhaseTimerSettings>,
+        phase_manager: Res<PhaseManager>,
+        phase_timer: Option<Res<PhaseTimer>>,
+    ) {
+        let running = player_phase_running(&phase_manager);
+        match phase_timer {
+            None if running && settings.seconds > 0 => {
+                commands.insert_resource(PhaseTimer {
+                    timer: Timer::from_seconds(settings.seconds as f32, TimerMode::Once),
+                });
+            }
+            Some(_) if !running => {
+                commands.remove_resource::<PhaseTimer>();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn tick_phase_timer(
+        time: Res<Time>,
+        mut phase_timer: ResMut<PhaseTimer>,
+        player_units: Query<(&Player, &UnitPhaseResources), With<Unit>>,
+        mut end_phase_writer: MessageWriter<EndPhaseEarlyMessage>,
+        mut expired_writer: MessageWriter<PhaseTimerExpiredMessage>,
+    ) {
+        if phase_timer.timer.is_finished() {
+            return;
+        }
+
+        phase_timer.timer.tick(time.delta());
+        if !phase_timer.timer.just_finished() {
+            return;
+        }
+
+        info!("Out of time! Remaining Player units will Wait");
+        for (player, resources) in player_units {
+            if resources.can_act() {
+                end_phase_writer.write(EndPhaseEarlyMessage { player: *player });
+            }
+        }
+        expired_writer.write(PhaseTimerExpiredMessage);
+    }
+
+    pub fn spawn_phase_timer_ui(mut commands: Commands, fonts: Res<FontResource>) {
+        commands.spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: percent(5),
+                right: percent(5),
+                padding: UiRect::all(px(8)),
+                border_radius: BorderRadius::all(percent(20)),
+                ..Default::default()
+            },
+            BackgroundColor(UI_MENU_BACKGROUND),
+            Visibility::Hidden,
+            PhaseTimerText,
+            BattleEntity {},
+            children![(
+                Text::default(),
+                TextColor(UI_TEXT_COLOR),
+                TextFont {
+                    font: fonts.pixelify_sans_regular.clone(),
+                    ..Default::default()
+                },
+                PhaseTimerText,
+            )],
+        ));
+    }
+
+    pub fn update_phase_timer_ui(
+        phase_timer: Option<Res<PhaseTimer>>,
+        mut container: Query<&mut Visibility, (With<PhaseTimerText>, With<Node>, Without<Text>)>,
+        mut text: Query<(&mut Text, &mut TextColor), With<PhaseTimerText>>,
+    ) {
+        for mut visibility in container.iter_mut() {
+            *visibility = if phase_timer.is_some() {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+        }
+
+        let Some(phase_timer) = phase_timer else {
+            return;
+        };
+
+        let remaining = phase_timer.timer.remaining_secs().ceil() as u32;
+        for (mut text, mut color) in text.iter_mut() {
+            text.0 = tr!("phase_timer.time", seconds = remaining);
+            // Start sweating a little in the last few seconds
+            color.0 = if remaining <= 5 {
+                Color::linear_rgb(1.0, 0.2, 0.2)
+            } else {
+                UI_TEXT_COLOR
+            };
+        }
+    }
+}
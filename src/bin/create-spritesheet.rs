@@ -1,9 +1,97 @@
-use image::{ImageBuffer, Rgba};
-use std::{collections::BTreeMap, fs::OpenOptions, path::Path};
+//! Builds the unit, weapon and tile sheets (plus each unit sheet's animation data) from the
+//! tinytactics pack, checking them against the layouts and animations the game actually uses.
+//! Nothing gets written out unless they all line up.
+
+use anyhow::Context;
+use bevy::prelude::TextureAtlasLayout;
+use image::{ImageBuffer, Rgba, RgbaImage};
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+};
+
+use tactics_exploration::{
+    animation::{
+        animation_db::{
+            AnimationDB, AnimationKey, AnimationStartIndexKey, build_animation_db,
+            load_animation_timings, registered_sprite_ids::TT_UNIT_ANIMATED_SPRITE_ID,
+        },
+        tinytactics::*,
+    },
+    assets::BATTLE_TACTICS_TILESHEET,
+};
+
+const TILESET_FILENAME: &str = "20240420tinyTacticsTileset00.png";
+
+/// Everything about the new sheets that doesn't line up with the game. Errors stop anything from
+/// being written out, warnings just get printed.
+#[derive(Default)]
+struct Problems {
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+/// The sheet has to be exactly the size of the atlas the game cuts it up with, or frames end up
+/// in the wrong place
+fn check_layout(
+    name: &str,
+    image: &RgbaImage,
+    layout: &TextureAtlasLayout,
+    problems: &mut Problems,
+) {
+    if image.dimensions() != (layout.size.x, layout.size.y) {
+        problems.errors.push(format!(
+            "{} is {}x{}, but the game cuts it up as {}x{}",
+            name,
+            image.width(),
+            image.height(),
+            layout.size.x,
+            layout.size.y
+        ));
+    }
+}
+
+/// Lines the sheet's animations up against what the game has registered for them, filling in the
+/// markers along the way
+fn check_animations(asset: &mut AnimationAsset, anim_db: &AnimationDB, problems: &mut Problems) {
+    for data in asset.data.iter_mut() {
+        let name = format!("{} {} {}", asset.character, data.action, data.direction);
+        let key = AnimationKey {
+            animated_sprite_id: TT_UNIT_ANIMATED_SPRITE_ID,
+            animation_id: data.action.animation_kind().into(),
+        };
+
+        match anim_db.get_start_index(&AnimationStartIndexKey {
+            facing_direction: Some(data.direction),
+            key: key.clone(),
+        }) {
+            Some(start) if u32::from(*start) != data.start_index => {
+                problems.warnings.push(format!(
+                    "{} starts at frame {} on the sheet, but the game plays it from {}",
+                    name, data.start_index, start
+                ))
+            }
+            Some(_) => {}
+            None => problems
+                .errors
+                .push(format!("{} isn't registered in the animation db", name)),
+        }
 
-use tactics_exploration::animation::tinytactics::*;
+        let Some(timing) = anim_db.get_data(&key) else {
+            continue;
+        };
+        if timing.frame_count > data.frame_count as usize {
+            problems.errors.push(format!(
+                "The game plays {} frames of {}, but the sheet only has {}",
+                timing.frame_count, name, data.frame_count
+            ));
+        }
+        data.markers = timing.animation_offset_markers.clone();
+    }
+}
 
-pub fn generate_animations_for_weapons() -> anyhow::Result<()> {
+fn generate_weapon_sheets(problems: &mut Problems) -> anyhow::Result<Vec<(PathBuf, RgbaImage)>> {
     let mut image_data = BTreeMap::new();
     for weapon_type in WeaponType::variants() {
         for direction in [Direction::NE, Direction::SE] {
@@ -26,6 +114,7 @@ pub fn generate_animations_for_weapons() -> anyhow::Result<()> {
         .map(|(_, v)| v.height())
         .sum::<u32>();
 
+    let mut sheets = Vec::new();
     for weapon in WeaponType::variants() {
         let keys_of_weapon: Vec<&(WeaponType, Direction)> =
             image_data.keys().filter(|(w, _)| *w == weapon).collect();
@@ -40,25 +129,26 @@ pub fn generate_animations_for_weapons() -> anyhow::Result<()> {
             image::imageops::replace(&mut output_img, image, 0, height.into());
             height += image.height();
         }
-        output_img.save(Path::new("assets").join(weapon_spritesheet_path(weapon)))?;
+        check_layout(
+            &format!("The {} sheet", weapon),
+            &output_img,
+            &weapon_layout(),
+            problems,
+        );
+        sheets.push((weapon_spritesheet_path(weapon), output_img));
     }
 
-    Ok(())
+    Ok(sheets)
 }
 
-fn main() -> anyhow::Result<()> {
+fn generate_unit_sheets(
+    anim_db: &AnimationDB,
+    problems: &mut Problems,
+) -> anyhow::Result<Vec<(Character, RgbaImage, AnimationAsset)>> {
     let mut image_data = BTreeMap::new();
     for character in [Character::Cleric, Character::Fighter, Character::Mage] {
         for direction in [Direction::NE, Direction::SE] {
-            for action in [
-                Action::Walking,
-                Action::Attack,
-                Action::Charging,
-                Action::Damage,
-                Action::Dead,
-                Action::Release,
-                Action::Weak,
-            ] {
+            for action in Action::ALL {
                 let image_path = sprite_filename(character, action, direction);
                 let img_buffer = image::open(image_path)?.to_rgba8();
                 image_data.insert((character, action, direction), img_buffer);
@@ -79,6 +169,7 @@ fn main() -> anyhow::Result<()> {
         .map(|(_, v)| v.height())
         .sum::<u32>();
 
+    let mut sheets = Vec::new();
     for character in [Character::Cleric, Character::Fighter, Character::Mage] {
         let keys_of_character: Vec<&(Character, Action, Direction)> = image_data
             .keys()
@@ -99,23 +190,63 @@ fn main() -> anyhow::Result<()> {
 
             height += image.height();
         }
+
+        let mut asset = AnimationAsset {
+            character,
+            data: animation_data,
+        };
+        check_layout(
+            &format!("The {} sheet", character),
+            &output_img,
+            &unit_layout(),
+            problems,
+        );
+        check_animations(&mut asset, anim_db, problems);
+        sheets.push((character, output_img, asset));
+    }
+
+    Ok(sheets)
+}
+
+/// The tileset's used as it comes, it just needs to end up where the game looks for it
+fn generate_tile_sheet(problems: &mut Problems) -> anyhow::Result<RgbaImage> {
+    let tileset = image::open(Path::new(FILE_PREFIX).join(TILESET_FILENAME))?.to_rgba8();
+    check_layout("The tileset", &tileset, &tile_layout(), problems);
+    Ok(tileset)
+}
+
+fn main() -> anyhow::Result<()> {
+    let anim_db = build_animation_db(&load_animation_timings())
+        .context("The animation db needs to build to check the sheets against it")?;
+
+    let mut problems = Problems::default();
+    let units = generate_unit_sheets(&anim_db, &mut problems)?;
+    let weapons = generate_weapon_sheets(&mut problems)?;
+    let tileset = generate_tile_sheet(&mut problems)?;
+
+    for warning in &problems.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    if !problems.errors.is_empty() {
+        anyhow::bail!(
+            "The sheets don't match the game, so nothing was written:\n{}",
+            problems.errors.join("\n")
+        );
+    }
+
+    for (character, image, asset) in units {
         let animation_data_file = OpenOptions::new()
             .create(true)
             .write(true)
             .truncate(true)
             .open(Path::new("assets").join(spritesheet_data_path(character)))?;
-
-        serde_json::to_writer(
-            animation_data_file,
-            &AnimationAsset {
-                character,
-                data: animation_data,
-            },
-        )?;
-
-        output_img.save(Path::new("assets").join(spritesheet_path(character)))?;
+        serde_json::to_writer(animation_data_file, &asset)?;
+        image.save(Path::new("assets").join(spritesheet_path(character)))?;
+    }
+    for (path, image) in weapons {
+        image.save(Path::new("assets").join(path))?;
     }
-    generate_animations_for_weapons()?;
+    tileset.save(Path::new("assets").join(BATTLE_TACTICS_TILESHEET))?;
 
     Ok(())
 }
@@ -0,0 +1,193 @@
+//! Where a room is set, so a run doesn't look the same from start to finish.
+//!
+//! A [`Biome`] decides how the tiles are tinted, how much of the ground is dead grass, what gets
+//! scattered around as obstacles and decorations, who's waiting to fight, and which hazard the
+//! final room throws at the party. Every room picks its own biome from its seed, unless its
+//! [`RoomTemplate`](crate::room_templates::RoomTemplate) asks for one.
+
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use rand_seeder::Seeder;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    animation::{
+        TinytacticsAssets,
+        tinytactics::{self, Character},
+    },
+    map_generation::{Obstacle, TileType},
+    props::Decoration,
+    turn_events::DungeonModifier,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Biome {
+    #[default]
+    Forest,
+    Snow,
+    Ruins,
+    Cave,
+}
+
+/// Who can show up to fight
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnemyKind {
+    Fighter,
+    Mage,
+    Cleric,
+}
+
+impl EnemyKind {
+    pub fn spritesheet(&self, tt_assets: &TinytacticsAssets) -> Handle<Image> {
+        match self {
+            EnemyKind::Fighter => tt_assets.fighter_spritesheet.clone(),
+            EnemyKind::Mage => tt_assets.mage_spritesheet.clone(),
+            EnemyKind::Cleric => tt_assets.cleric_spritesheet.clone(),
+        }
+    }
+
+    /// Where [`EnemyKind::spritesheet`] gets loaded from
+    pub fn spritesheet_path(&self) -> PathBuf {
+        tinytactics::spritesheet_path(match self {
+            EnemyKind::Fighter => Character::Fighter,
+            EnemyKind::Mage => Character::Mage,
+            EnemyKind::Cleric => Character::Cleric,
+        })
+    }
+}
+
+impl Biome {
+    pub const OPTIONS: [Biome; 4] = [Biome::Forest, Biome::Snow, Biome::Ruins, Biome::Cave];
+
+    /// The same seed always lands in the same biome
+    pub fn for_room(seed: &str) -> Biome {
+        // Kept apart from the scatter's rng, so the biome doesn't reshuffle the room
+        let mut rng: Pcg64 = Seeder::from(format!("{}-biome", seed)).into_rng();
+        *Self::OPTIONS.choose(&mut rng).expect("There are biomes")
+    }
+
+    /// Tiles are tinted rather than swapped, so every biome gets by on the same tilesheet
+    pub fn tile_tint(&self, tile: &TileType) -> Color {
+        match (self, tile) {
+            (_, TileType::Bridge(_)) | (Biome::Forest, _) => Color::WHITE,
+            (Biome::Snow, TileType::Grass(_)) => Color::srgb(0.9, 0.95, 1.0),
+            (Biome::Snow, TileType::Water(_)) => Color::srgb(0.75, 0.85, 1.0),
+            (Biome::Ruins, TileType::Grass(_)) => Color::srgb(0.85, 0.8, 0.65),
+            (Biome::Ruins, TileType::Water(_)) => Color::srgb(0.7, 0.8, 0.7),
+            (Biome::Cave, TileType::Grass(_)) => Color::srgb(0.55, 0.5, 0.6),
+            (Biome::Cave, TileType::Water(_)) => Color::srgb(0.45, 0.45, 0.6),
+        }
+    }
+
+    pub fn dead_grass_chance(&self) -> f32 {
+        match self {
+            Biome::Forest => 0.05,
+            Biome::Snow => 0.02,
+            Biome::Ruins => 0.35,
+            Biome::Cave => 0.5,
+        }
+    }
+
+    /// How likely any open tile is to have something in the way
+    pub fn obstacle_chance(&self) -> f32 {
+        match self {
+            Biome::Forest => 0.05,
+            Biome::Snow => 0.03,
+            Biome::Ruins => 0.08,
+            Biome::Cave => 0.07,
+        }
+    }
+
+    /// What gets scattered around the middle of the room
+    pub fn obstacles(&self) -> &'static [Obstacle] {
+        match self {
+            Biome::Forest => &[Obstacle::Rock2, Obstacle::Bush],
+            Biome::Snow => &[Obstacle::Rock2, Obstacle::Tree],
+            Biome::Ruins => &[Obstacle::Rock1, Obstacle::Ruin, Obstacle::Ruin],
+            Biome::Cave => &[Obstacle::Rock1, Obstacle::Rock2, Obstacle::Bush],
+        }
+    }
+
+    /// What lines the edges of the room
+    pub fn edge_obstacle(&self) -> Obstacle {
+        match self {
+            Biome::Forest | Biome::Snow => Obstacle::Tree,
+            Biome::Ruins => Obstacle::Fence,
+            Biome::Cave => Obstacle::Rock1,
+        }
+    }
+
+    /// How likely any open tile is to have a decoration on it
+    pub fn decoration_chance(&self) -> f32 {
+        match self {
+            Biome::Forest => 0.12,
+            Biome::Snow => 0.04,
+            Biome::Ruins => 0.08,
+            Biome::Cave => 0.06,
+        }
+    }
+
+    /// What gets sprinkled over the open ground, which nobody trips over
+    pub fn decorations(&self) -> &'static [Decoration] {
+        match self {
+            Biome::Forest => &[
+                Decoration::Flowers,
+                Decoration::Flowers,
+                Decoration::Pebbles,
+            ],
+            Biome::Snow | Biome::Ruins | Biome::Cave => &[Decoration::Pebbles],
+        }
+    }
+
+    /// Who the room's enemies can be, see [`encounters`](crate::encounters)
+    pub fn enemy_spawn_table(&self) -> &'static [EnemyKind] {
+        match self {
+            Biome::Forest => &[EnemyKind::Cleric, EnemyKind::Cleric, EnemyKind::Fighter],
+            Biome::Snow => &[EnemyKind::Fighter, EnemyKind::Fighter, EnemyKind::Mage],
+            Biome::Ruins => &[EnemyKind::Mage, EnemyKind::Cleric, EnemyKind::Mage],
+            Biome::Cave => &[EnemyKind::Fighter, EnemyKind::Mage, EnemyKind::Cleric],
+        }
+    }
+
+    pub fn enemy_kind(&self, index: usize) -> EnemyKind {
+        let table = self.enemy_spawn_table();
+        table[index % table.len()]
+    }
+
+    /// What makes the final room harder the longer it goes on
+    pub fn hazard(&self, starting_turn: u32) -> DungeonModifier {
+        match self {
+            Biome::Forest | Biome::Ruins | Biome::Cave => {
+                DungeonModifier::RisingWater { starting_turn }
+            }
+            Biome::Snow => DungeonModifier::Blizzard { starting_turn },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rooms_keep_their_biome() {
+        assert_eq!(Biome::for_room("ABCD-EFGH0"), Biome::for_room("ABCD-EFGH0"));
+
+        // Not every room of a run should land in the same place
+        let biomes = (0..20)
+            .map(|t| Biome::for_room(&format!("ABCD-EFGH{}", t)))
+            .collect::<std::collections::HashSet<_>>();
+        assert!(biomes.len() > 1);
+    }
+
+    #[test]
+    fn test_spawn_tables_go_around_again() {
+        for biome in Biome::OPTIONS {
+            let table = biome.enemy_spawn_table();
+            assert_eq!(biome.enemy_kind(table.len()), table[0]);
+        }
+    }
+}
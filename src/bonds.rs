@@ -0,0 +1,328 @@
+//! Characters who fight side by side growing closer.
+//!
+//! At the start of every phase, any two characters standing right next to each other get noted
+//! down. Once the battle's over, each pair that was noted fought that battle together, which goes
+//! on both of their saves. Enough battles together and the pair gets a [`BondRank`], which gives
+//! each of them a bonus whenever they're within [`BOND_RADIUS`] tiles of the other. The bonus is
+//! just an [aura](crate::aura) that only reaches the partner, so it comes and goes as they move.
+//!
+//! The party can check on how their bonds are coming along at a rest room.
+
+use std::collections::{BTreeSet, HashMap};
+
+use bevy::prelude::*;
+
+use crate::{
+    assets::FontResource,
+    aura::spread_auras,
+    battle_phase::PhaseMessage,
+    dungeon::DungeonState,
+    gameplay_effects::{
+        ActiveEffects, AuraData, Effect, EffectData, EffectDuration, EffectMetadata, EffectType,
+        Operator, StatModification,
+    },
+    grid::{GridPosition, manhattan_distance},
+    localization::localized_text,
+    menu::ui_consts::UI_TEXT_COLOR,
+    player::RegisteredBattlePlayers,
+    rest_room::{BackToCampfireButton, RestScreen, menu_button, menu_panel},
+    save_game::{SaveFileKey, UnitSaveV2},
+    tr,
+    unit::Unit,
+    unit_stats::{StatType, UnitDerivedStats},
+};
+
+/// How close a bonded pair has to stand to get their bonus
+pub const BOND_RADIUS: u32 = 2;
+
+/// How well two characters know each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BondRank {
+    C,
+    B,
+    A,
+}
+
+impl BondRank {
+    const ALL: [BondRank; 3] = [BondRank::C, BondRank::B, BondRank::A];
+
+    /// How many battles a pair needs to fight together to get the rank
+    pub fn battles(&self) -> u32 {
+        match self {
+            BondRank::C => 2,
+            BondRank::B => 5,
+            BondRank::A => 9,
+        }
+    }
+
+    /// The best rank a pair has after fighting `battles` battles together, if they have one at all
+    pub fn from_battles(battles: u32) -> Option<BondRank> {
+        BondRank::ALL
+            .into_iter()
+            .rev()
+            .find(|t| battles >= t.battles())
+    }
+
+    /// The rank after this one
+    pub fn next(&self) -> Option<BondRank> {
+        match self {
+            BondRank::C => Some(BondRank::B),
+            BondRank::B => Some(BondRank::A),
+            BondRank::A => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BondRank::C => "C",
+            BondRank::B => "B",
+            BondRank::A => "A",
+        }
+    }
+
+    /// What each of the pair gets while they're close. Grows with every rank.
+    pub fn bonus(&self) -> Vec<StatModification> {
+        let value = match self {
+            BondRank::C => 1.,
+            BondRank::B => 2.,
+            BondRank::A => 3.,
+        };
+        [StatType::Strength, StatType::Defense]
+            .into_iter()
+            .map(|attribute_type| StatModification {
+                attribute_type,
+                operator: Operator::Add,
+                value,
+            })
+            .collect()
+    }
+}
+
+/// Notes down that the pair fought a battle together, on both of their saves
+pub fn record_battle_together(players: &mut RegisteredBattlePlayers, a: u32, b: u32) {
+    players.update_unit(a, |save| *save.bonds.entry(b).or_default() += 1);
+    players.update_unit(b, |save| *save.bonds.entry(a).or_default() += 1);
+}
+
+/// Every bond the character has a rank in, by their partner's uid
+pub fn bond_ranks(save: &UnitSaveV2) -> impl Iterator<Item = (u32, BondRank)> {
+    save.bonds
+        .iter()
+        .filter_map(|(uid, battles)| Some((*uid, BondRank::from_battles(*battles)?)))
+}
+
+/// The pairs of characters (by uid, lowest first) who've stood next to each other at the start of
+/// a phase this battle
+#[derive(Resource, Debug, Default)]
+pub struct BattleBonds(pub BTreeSet<(u32, u32)>);
+
+pub fn bonds_plugin(app: &mut App) {
+    app.init_resource::<BattleBonds>()
+        .add_systems(OnEnter(DungeonState::InBattle), reset_battle_bonds)
+        .add_systems(OnExit(DungeonState::InBattle), record_battle_bonds)
+        .add_systems(OnEnter(RestScreen::Bonds), open_bonds_menu)
+        .add_systems(
+            Update,
+            (note_side_by_side, apply_bond_auras.before(spread_auras))
+                .run_if(in_state(DungeonState::InBattle)),
+        );
+}
+
+fn reset_battle_bonds(mut bonds: ResMut<BattleBonds>) {
+    bonds.0.clear();
+}
+
+fn note_side_by_side(
+    mut phases: MessageReader<PhaseMessage>,
+    mut bonds: ResMut<BattleBonds>,
+    characters: Query<(&SaveFileKey, &Unit, &GridPosition, &UnitDerivedStats)>,
+) {
+    if phases.read().count() == 0 {
+        return;
+    }
+
+    let standing = characters
+        .iter()
+        .filter(|t| !t.3.downed())
+        .collect::<Vec<_>>();
+    for (i, (a, a_unit, a_position, _)) in standing.iter().enumerate() {
+        for (b, b_unit, b_position, _) in &standing[i + 1..] {
+            if a_unit.team == b_unit.team && manhattan_distance(a_position, b_position) == 1 {
+                bonds.0.insert((a.uid.min(b.uid), a.uid.max(b.uid)));
+            }
+        }
+    }
+}
+
+fn record_battle_bonds(bonds: Res<BattleBonds>, players: Option<ResMut<RegisteredBattlePlayers>>) {
+    let Some(mut players) = players else {
+        return;
+    };
+    for (a, b) in &bonds.0 {
+        record_battle_together(&mut players, *a, *b);
+    }
+}
+
+/// Gives everyone an aura for each partner they've got a rank with, once the party's all there
+fn apply_bond_auras(
+    players: Res<RegisteredBattlePlayers>,
+    spawned: Query<(), Added<SaveFileKey>>,
+    mut characters: Query<(Entity, &SaveFileKey, &mut ActiveEffects)>,
+) {
+    if spawned.is_empty() {
+        return;
+    }
+
+    let entities = characters
+        .iter()
+        .map(|(entity, key, _)| (key.uid, entity))
+        .collect::<HashMap<_, _>>();
+    let saves = players
+        .units()
+        .map(|(_, t)| (t.save_file_key.uid, t))
+        .collect::<HashMap<_, _>>();
+
+    for (entity, key, mut effects) in characters.iter_mut() {
+        effects.effects.retain(
+            |t| !matches!(&t.data.effect_type, EffectType::Aura(aura) if aura.partner.is_some()),
+        );
+        let Some(save) = saves.get(&key.uid) else {
+            continue;
+        };
+
+        for (uid, rank) in bond_ranks(save) {
+            let Some(partner) = entities.get(&uid) else {
+                continue;
+            };
+            for modification in rank.bonus() {
+                effects.apply_effect(Effect {
+                    metadata: EffectMetadata {
+                        target: entity,
+                        source: None,
+                    },
+                    data: EffectData {
+                        effect_type: EffectType::Aura(AuraData {
+                            radius: BOND_RADIUS,
+                            modification,
+                            partner: Some(*partner),
+                        }),
+                        duration: EffectDuration::Permanent,
+                    },
+                });
+            }
+        }
+    }
+}
+
+/// Every pair in the party who've fought together, with their rank and how far off the next one
+/// they are
+fn open_bonds_menu(
+    mut commands: Commands,
+    fonts: Res<FontResource>,
+    registered_players: Res<RegisteredBattlePlayers>,
+) {
+    let font = TextFont {
+        font_size: 26.0,
+        font: fonts.pixelify_sans_regular.clone(),
+        ..default()
+    };
+
+    let mut party = registered_players
+        .units()
+        .map(|(_, t)| t)
+        .collect::<Vec<_>>();
+    party.sort_by_key(|t| t.save_file_key.uid);
+
+    let mut lines = Vec::new();
+    for (i, save) in party.iter().enumerate() {
+        for partner in &party[i + 1..] {
+            let battles = save
+                .bonds
+                .get(&partner.save_file_key.uid)
+                .copied()
+                .unwrap_or_default();
+            if battles == 0 {
+                continue;
+            }
+
+            let rank = BondRank::from_battles(battles);
+            let rank_name = rank.map(|t| t.name()).unwrap_or("-");
+            let next = match rank {
+                Some(rank) => rank.next(),
+                None => Some(BondRank::C),
+            };
+            let line = match next {
+                Some(next) => tr!(
+                    "bonds.pair",
+                    a = save.save_file_key.name,
+                    b = partner.save_file_key.name,
+                    rank = rank_name,
+                    battles = battles,
+                    needed = next.battles()
+                ),
+                None => tr!(
+                    "bonds.pair_maxed",
+                    a = save.save_file_key.name,
+                    b = partner.save_file_key.name,
+                    rank = rank_name
+                ),
+            };
+            lines.push(line);
+        }
+    }
+    if lines.is_empty() {
+        lines.push(tr!("bonds.none"));
+    }
+
+    let back = commands
+        .spawn((
+            menu_button(&font, localized_text("action.back")),
+            BackToCampfireButton,
+        ))
+        .id();
+    let panel = menu_panel(
+        &mut commands,
+        &fonts,
+        &registered_players,
+        "bonds.title",
+        &[back],
+        RestScreen::Bonds,
+    );
+    for line in lines {
+        let text = commands
+            .spawn((Text(line), font.clone(), TextColor(UI_TEXT_COLOR)))
+            .id();
+        commands.entity(panel).add_child(text);
+    }
+    commands.entity(panel).add_child(back);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{save_game::SaveFileColor, unit::jobs::UnitJob};
+
+    #[test]
+    fn test_bonds_rank_up_with_battles_together() {
+        assert_eq!(BondRank::from_battles(0), None);
+        assert_eq!(BondRank::from_battles(2), Some(BondRank::C));
+        assert_eq!(BondRank::from_battles(8), Some(BondRank::B));
+        assert_eq!(BondRank::from_battles(50), Some(BondRank::A));
+        assert!(BondRank::A.bonus()[0].value > BondRank::C.bonus()[0].value);
+
+        let mut save = UnitSaveV2::new(
+            SaveFileKey {
+                uid: 1,
+                name: "Ada".to_string(),
+                color: SaveFileColor::Blue,
+            },
+            UnitJob::Knight,
+        );
+        save.bonds.insert(2, 5);
+        save.bonds.insert(3, 1);
+        assert_eq!(
+            bond_ranks(&save).collect::<Vec<_>>(),
+            vec![(2, BondRank::B)]
+        );
+    }
+}
@@ -1,12 +1,89 @@
-use bevy::prelude::*;
+//! The battle camera.
+//!
+//! The camera looks wherever it's panned to, separately from where anyone's cursor is. It pans
+//! with the right stick, with [`PAN_CAMERA_MODIFIER`](crate::player::PAN_CAMERA_MODIFIER) and the
+//! cursor keys, or by pushing the mouse up against the edge of the window. It glides after where
+//! it's been panned to rather than jumping, and never goes past the edge of the map.
+//! `RecenterCamera` brings it back to the player's own cursor.
+//!
+//! Zooming eases in and out too, between [`CameraSettings::min_zoom`] and
+//! [`CameraSettings::max_zoom`], and keeps the player's cursor (or the mouse, if they haven't got
+//! one) in the same spot on screen. Each battle opens on an overview of the whole map,
+//! before settling back in to the usual zoom.
+
+use bevy::{camera::visibility::RenderLayers, prelude::*, window::PrimaryWindow};
 use leafwing_input_manager::prelude::ActionState;
 
-use crate::player::{Player, PlayerInputAction};
+use crate::{
+    dungeon::DungeonManager,
+    grid::{TILE_X_SIZE, TILE_Y_SIZE},
+    grid_cursor::Cursor,
+    player::{Player, PlayerInputAction},
+};
+
+/// How fast the camera pans, in screen pixels per second
+const PAN_SPEED: f32 = 600.;
+/// How close (in pixels) the mouse has to be to the edge of the window to pan
+const EDGE_SCROLL_MARGIN: f32 = 16.;
+/// How quickly the camera catches up to where it's been panned to. Higher is snappier
+const PAN_SMOOTHING: f32 = 10.;
+/// How much each press of zoom in or out changes the zoom by
+const ZOOM_STEP: f32 = 1.25;
+/// How quickly the zoom catches up to where it's headed. Higher is snappier
+const ZOOM_SMOOTHING: f32 = 8.;
+/// How long the battle start overview holds before zooming in
+const OVERVIEW_SECONDS: f32 = 1.5;
+/// How much room to leave around the map in the overview
+const OVERVIEW_MARGIN: f32 = 1.1;
 
 /// Resource because one of them? Split screen maybe would need two?
+///
+/// Zoom is the orthographic scale, so smaller is closer in.
 #[derive(Debug, Resource)]
 pub struct CameraSettings {
+    /// The zoom the camera settles on
     pub zoom_value: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            zoom_value: 0.4,
+            min_zoom: 0.2,
+            max_zoom: 1.0,
+        }
+    }
+}
+
+/// The camera everything's normally seen through. With the screen
+/// [split](crate::split_screen), it only draws the UI everyone shares
+#[derive(Component, Debug)]
+pub struct MainCamera;
+
+/// Where the camera's headed. Panning moves this, and the camera follows it smoothly
+#[derive(Component, Debug)]
+pub struct CameraPan {
+    pub target: Vec2,
+}
+
+/// What the zoom's doing besides heading for [`CameraSettings::zoom_value`]
+#[derive(Component, Debug, Default)]
+pub struct CameraZoom {
+    /// The spot in the world that stays put on screen while zooming
+    pub focus: Option<Vec2>,
+    /// The zoom that frames the whole map at the start of a battle, and how long it's held for
+    pub overview: Option<(f32, Timer)>,
+}
+
+/// Everyone's looking at the same camera, so it draws every player's own
+/// [render layer](Player::render_layer) along with everything else
+pub fn shared_view_layers() -> RenderLayers {
+    Player::joinable()
+        .map(|t| t.render_layer())
+        .chain([0])
+        .collect()
 }
 
 pub fn setup_camera(mut commands: Commands) {
@@ -15,43 +92,293 @@ pub fn setup_camera(mut commands: Commands) {
     // TODO: Come up with some real camera positioning per
     // level / real positioning for the grid itself / world.
     let t = Transform::from_translation(Vec3::new(0.0, -75.0, 0.0));
-    let camera_settings = CameraSettings { zoom_value: 0.4 };
+    let camera_settings = CameraSettings::default();
 
     commands.spawn((
         Name::new("Main Camera"),
         Camera2d,
+        MainCamera,
+        // Otherwise the UI would follow whichever split screen camera came last
+        IsDefaultUiCamera,
         Projection::from(OrthographicProjection {
             scale: camera_settings.zoom_value,
             ..OrthographicProjection::default_2d()
         }),
+        CameraPan {
+            target: t.translation.truncate(),
+        },
+        CameraZoom::default(),
         t,
+        shared_view_layers(),
     ));
 
     commands.insert_resource(camera_settings);
 }
 
+/// Picks the zoom to head for, and what to zoom around
 pub fn change_zoom(
-    mut camera: Single<&mut Projection, With<Camera>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&Camera, &GlobalTransform, &mut CameraZoom), With<MainCamera>>,
     mut camera_settings: ResMut<CameraSettings>,
     player_query: Query<(&Player, &ActionState<PlayerInputAction>)>,
+    cursor_query: Query<(&Player, &GlobalTransform), With<Cursor>>,
 ) {
-    for (_, action_state) in player_query.iter() {
-        if action_state.just_pressed(&PlayerInputAction::ZoomIn) {
-            match **camera {
-                Projection::Orthographic(ref mut current_projection) => {
-                    current_projection.scale += 0.01;
-                    camera_settings.zoom_value = current_projection.scale;
-                }
-                _ => return,
-            }
+    let (camera, camera_transform, mut zoom) = camera.into_inner();
+    for (player, action_state) in player_query.iter() {
+        let step = if action_state.just_pressed(&PlayerInputAction::ZoomIn) {
+            ZOOM_STEP.recip()
         } else if action_state.just_pressed(&PlayerInputAction::ZoomOut) {
-            match **camera {
-                Projection::Orthographic(ref mut current_projection) => {
-                    current_projection.scale -= 0.01;
-                    camera_settings.zoom_value = current_projection.scale;
-                }
-                _ => return,
+            ZOOM_STEP
+        } else {
+            continue;
+        };
+
+        camera_settings.zoom_value = (camera_settings.zoom_value * step)
+            .clamp(camera_settings.min_zoom, camera_settings.max_zoom);
+        zoom.overview = None;
+        // The mouse player's cursor follows the mouse anyway
+        zoom.focus = cursor_query
+            .iter()
+            .find(|(p, _)| *p == player)
+            .map(|(_, t)| t.translation().truncate())
+            .or_else(|| {
+                window
+                    .cursor_position()
+                    .and_then(|t| camera.viewport_to_world_2d(camera_transform, t).ok())
+            });
+    }
+}
+
+/// Where the camera has to be for `focus` to stay in the same spot on screen, after the zoom
+/// changes by `ratio`
+pub fn zoom_about(center: Vec2, focus: Vec2, ratio: f32) -> Vec2 {
+    focus + (center - focus) * ratio
+}
+
+/// Eases the zoom towards where it's headed
+pub fn smooth_zoom(
+    time: Res<Time>,
+    camera_settings: Res<CameraSettings>,
+    camera: Single<
+        (
+            &mut Projection,
+            &mut CameraZoom,
+            &mut CameraPan,
+            &mut Transform,
+        ),
+        With<MainCamera>,
+    >,
+) {
+    let (mut projection, mut zoom, mut pan, mut transform) = camera.into_inner();
+    let Projection::Orthographic(ortho) = &mut *projection else {
+        return;
+    };
+
+    let overview = zoom
+        .overview
+        .as_mut()
+        .and_then(|(scale, timer)| (!timer.tick(time.delta()).is_finished()).then_some(*scale));
+    if overview.is_none() && zoom.overview.is_some() {
+        zoom.overview = None;
+    }
+    let target = overview.unwrap_or(camera_settings.zoom_value);
+    if ortho.scale == target {
+        return;
+    }
+
+    let mut next = ortho
+        .scale
+        .lerp(target, 1. - (-ZOOM_SMOOTHING * time.delta_secs()).exp());
+    if (next - target).abs() < 0.001 {
+        next = target;
+    }
+
+    if let Some(focus) = zoom.focus {
+        let ratio = next / ortho.scale;
+        pan.target = zoom_about(pan.target, focus, ratio);
+        let center = zoom_about(transform.translation.truncate(), focus, ratio);
+        transform.translation = center.extend(transform.translation.z);
+    }
+    if next == target {
+        zoom.focus = None;
+    }
+    ortho.scale = next;
+}
+
+/// The zoom that fits all of `bounds` into a viewport of `size`
+pub fn overview_zoom(bounds: Rect, size: Vec2) -> f32 {
+    (bounds.size() / size).max_element() * OVERVIEW_MARGIN
+}
+
+/// Opens the battle looking at the whole map
+pub fn start_overview_zoom(
+    window: Single<&Window, With<PrimaryWindow>>,
+    dungeon_manager: Option<Res<DungeonManager>>,
+    camera: Single<
+        (
+            &mut Projection,
+            &mut CameraZoom,
+            &mut CameraPan,
+            &mut Transform,
+        ),
+        With<MainCamera>,
+    >,
+) {
+    let Some(map) = dungeon_manager.as_ref().and_then(|t| t.current_map()) else {
+        return;
+    };
+    let (mut projection, mut zoom, mut pan, mut transform) = camera.into_inner();
+    let Projection::Orthographic(ortho) = &mut *projection else {
+        return;
+    };
+
+    let bounds = map_bounds(map.grid_size);
+    let scale = overview_zoom(bounds, window.size());
+    ortho.scale = scale;
+    pan.target = bounds.center();
+    transform.translation = bounds.center().extend(transform.translation.z);
+    zoom.focus = None;
+    zoom.overview = Some((
+        scale,
+        Timer::from_seconds(OVERVIEW_SECONDS, TimerMode::Once),
+    ));
+}
+
+/// The part of the world a map of `grid_size` tiles (water border included) covers
+pub fn map_bounds(grid_size: (u32, u32)) -> Rect {
+    // The same math as `grid::grid_to_world`, but for the corners of the map in game space,
+    // which run past 0 into the water
+    let corner = |x: f32, y: f32| {
+        Vec2::new(
+            (x + y - 12.) * (TILE_X_SIZE / 2.),
+            (x - y) * (TILE_Y_SIZE / 2.),
+        )
+    };
+    let far_x = grid_size.1 as f32 - 3.;
+    let far_y = grid_size.0 as f32 - 3.;
+    [
+        corner(-2., -2.),
+        corner(far_x, -2.),
+        corner(-2., far_y),
+        corner(far_x, far_y),
+    ]
+    .into_iter()
+    .fold(
+        Rect::from_center_size(corner(-2., -2.), Vec2::ZERO),
+        |rect, t| rect.union_point(t),
+    )
+}
+
+/// Which way to pan for the mouse being at `position` in a window of `size`, if it's near an edge
+pub fn edge_scroll_direction(position: Vec2, size: Vec2) -> Vec2 {
+    let mut direction = Vec2::ZERO;
+    if position.x < EDGE_SCROLL_MARGIN {
+        direction.x -= 1.;
+    }
+    if position.x > size.x - EDGE_SCROLL_MARGIN {
+        direction.x += 1.;
+    }
+    // Window y runs downwards
+    if position.y < EDGE_SCROLL_MARGIN {
+        direction.y += 1.;
+    }
+    if position.y > size.y - EDGE_SCROLL_MARGIN {
+        direction.y -= 1.;
+    }
+    direction
+}
+
+pub fn pan_camera(
+    time: Res<Time>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    dungeon_manager: Option<Res<DungeonManager>>,
+    player_query: Query<(&Player, &ActionState<PlayerInputAction>)>,
+    cursor_query: Query<(&Player, &Transform), (With<Cursor>, Without<Camera>)>,
+    camera: Single<(&mut Transform, &mut CameraPan, &Projection), With<MainCamera>>,
+) {
+    let (mut transform, mut pan, projection) = camera.into_inner();
+
+    let mut direction = Vec2::ZERO;
+    for (player, action_state) in player_query.iter() {
+        direction += action_state.axis_pair(&PlayerInputAction::PanCamera);
+
+        if action_state.just_pressed(&PlayerInputAction::RecenterCamera)
+            && let Some((_, cursor)) = cursor_query.iter().find(|(p, _)| *p == player)
+        {
+            pan.target = cursor.translation.truncate();
+        }
+    }
+    if window.focused
+        && let Some(position) = window.cursor_position()
+    {
+        direction += edge_scroll_direction(position, window.size());
+    }
+
+    let scale = match projection {
+        Projection::Orthographic(t) => t.scale,
+        _ => 1.,
+    };
+    pan.target += direction.clamp_length_max(1.) * PAN_SPEED * scale * time.delta_secs();
+
+    if let Some(map) = dungeon_manager.as_ref().and_then(|t| t.current_map()) {
+        let bounds = map_bounds(map.grid_size);
+        pan.target = pan.target.clamp(bounds.min, bounds.max);
+    }
+
+    glide_towards(&mut transform, pan.target, time.delta_secs());
+}
+
+/// Moves the camera part of the way to `target`, so it catches up smoothly
+pub fn glide_towards(transform: &mut Transform, target: Vec2, delta_secs: f32) {
+    let current = transform.translation.truncate();
+    let next = current.lerp(target, 1. - (-PAN_SMOOTHING * delta_secs).exp());
+    transform.translation = next.extend(transform.translation.z);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::{GridPosition, grid_to_world};
+
+    #[test]
+    fn test_zooming_keeps_the_focus_still_and_the_overview_fits() {
+        let focus = Vec2::new(40., -10.);
+        let center = Vec2::new(0., -75.);
+        // Wherever the focus is on screen, it's still there after zooming
+        let on_screen = (focus - center) / 0.4;
+        let zoomed = zoom_about(center, focus, 0.5 / 0.4);
+        assert!(((focus - zoomed) / 0.5 - on_screen).length() < 0.001);
+
+        let bounds = map_bounds((14, 18));
+        let size = Vec2::new(1920., 1080.);
+        let scale = overview_zoom(bounds, size);
+        assert!(bounds.width() <= size.x * scale && bounds.height() <= size.y * scale);
+    }
+
+    #[test]
+    fn test_map_bounds_cover_every_tile_and_edges_scroll() {
+        let grid_size = (14, 18);
+        let bounds = map_bounds(grid_size);
+        for x in 0..grid_size.1 - 4 {
+            for y in 0..grid_size.0 - 4 {
+                let world = grid_to_world(&GridPosition { x, y }, TILE_X_SIZE, TILE_Y_SIZE);
+                assert!(bounds.contains(world.truncate()), "({}, {})", x, y);
             }
         }
+
+        let size = Vec2::new(800., 600.);
+        assert_eq!(
+            edge_scroll_direction(Vec2::new(400., 300.), size),
+            Vec2::ZERO
+        );
+        assert_eq!(
+            edge_scroll_direction(Vec2::new(2., 300.), size),
+            Vec2::NEG_X
+        );
+        assert_eq!(edge_scroll_direction(Vec2::new(400., 2.), size), Vec2::Y);
+        assert_eq!(
+            edge_scroll_direction(Vec2::new(799., 599.), size),
+            Vec2::new(1., -1.)
+        );
     }
 }
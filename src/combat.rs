@@ -1,14 +1,18 @@
 use std::collections::BTreeMap;
 
 use bevy::prelude::*;
+use rand::prelude::*;
 
 use crate::assets::FontResource;
 use crate::assets::sounds::AudioContext;
 use crate::assets::sounds::AudioCue;
 use crate::assets::sounds::AudioEventMessage;
+use crate::battle_log::BattleLogMessage;
+use crate::combat_formulas::CombatFormulas;
 use crate::gameplay_effects::ActiveEffects;
 use crate::gameplay_effects::Effect;
 use crate::gameplay_effects::EffectMetadata;
+use crate::tr;
 use crate::unit_stats::StatsDirty;
 use crate::unit_stats::{StatType, StatValue, UnitDerivedStats, UnitStatChangeRequest};
 use crate::{
@@ -18,15 +22,19 @@ use crate::{
         animation_db::{AnimationDB, AnimationKey, AnimationStartIndexKey},
         combat::HURT_BY_ATTACK_FRAME_DURATION,
     },
-    assets::sprite_db::SpriteDB,
-    battle_phase::UnitPhaseResources,
+    assets::sprite_db::{SpriteDB, SpriteId},
+    battle_phase::{ActionCost, PhaseManager, UnitPhaseResources},
     combat::skills::{
         CastingData, Skill, SkillAction, SkillActionType, SkillAnimationId, SkillDBResource,
-        SkillEvent, SkillId,
+        SkillEvent, SkillId, SkillTiming,
     },
-    grid::{GridPosition, init_grid_to_world_transform},
+    equipment::UnitEquipment,
+    grid::{GridManagerResource, GridPosition, init_grid_to_world_transform},
     projectile::{ProjectileArrived, spawn_arrow},
-    unit::{TINY_TACTICS_ANCHOR, Unit, UnitAction, UnitActionCompletedMessage},
+    unit::{
+        TINY_TACTICS_ANCHOR, Unit, UnitAction, UnitActionCompletedMessage,
+        overlay::TileOverlayAssets, radius_range_at_position,
+    },
 };
 
 #[derive(Component)]
@@ -128,6 +136,50 @@ impl CombatTimeline {
 
         Ok(timeline)
     }
+
+    /// Builds the timeline for a skill landing after the caster has already moved on,
+    /// like a [`skills::SkillTiming::Delayed`] skill. Any UnitAttack stages are skipped.
+    pub fn build_detached(
+        ae_entity: Entity,
+        skill: &Skill,
+        caster: Option<Entity>,
+        defender_e: Entity,
+        defender_grid_pos: &GridPosition,
+    ) -> Self {
+        let mut timeline = CombatTimeline::new();
+        let mut stage_id = timeline.current_stage;
+        stage_id.0 += 1;
+        for skill_stage in &skill.animation_data {
+            let stage = match &skill_stage.stage {
+                skills::SkillStageAction::UnitAttack(..) => continue,
+                skills::SkillStageAction::Cast(casting_data) => {
+                    CombatStage::Cast(*defender_grid_pos, casting_data.clone())
+                }
+                skills::SkillStageAction::Impact(action_indices) => {
+                    let actions = action_indices
+                        .iter()
+                        .filter_map(|t| skill.actions.get(t.0).cloned())
+                        .collect();
+                    CombatStage::Impact(caster, defender_e, actions, skill.skill_id)
+                }
+            };
+
+            let triggers = Self::parse_triggers(ae_entity, &skill_stage.advancing_event);
+            timeline.stages.insert(stage_id, stage);
+            timeline.conditions_to_advance.insert(stage_id, triggers);
+            stage_id.0 += 1;
+        }
+
+        timeline.audio_events_to_emit_on_stage_complete = skill
+            .audio_cues_to_emit
+            .to_emit_on_exit
+            .clone()
+            .into_iter()
+            .map(|(k, v)| (k.into(), v))
+            .collect();
+
+        timeline
+    }
 }
 
 #[derive(Component, Clone, Debug)]
@@ -137,8 +189,8 @@ pub struct AttackIntent {
     pub skill: SkillId,
 }
 
-/// Assumes everything is gonna hit for now
-fn calculate_damage(
+/// Assumes everything is gonna hit, misses get rolled before this
+pub fn calculate_damage(
     attacker: Option<&UnitDerivedStats>,
     defender: &UnitDerivedStats,
     skill_actions: &Vec<SkillAction>,
@@ -161,7 +213,7 @@ fn calculate_damage(
                 .map(|t| defender.stats.stat(t.stat))
                 .unwrap_or_default()
                 .0 as u32;
-            damage += scaled_damage.power + bonus_attack - defense;
+            damage += (scaled_damage.power + bonus_attack).saturating_sub(defense);
         }
     }
     for action in skill_actions {
@@ -181,7 +233,7 @@ fn calculate_damage(
                 .map(|t| defender.stats.stat(t.stat))
                 .unwrap_or_default()
                 .0 as u32;
-            healing += scaled_damage.power + bonus_attack - defense;
+            healing += (scaled_damage.power + bonus_attack).saturating_sub(defense);
         }
     }
     healing as i32 - damage as i32
@@ -550,23 +602,36 @@ pub struct AttackResolved {
     attacker: Option<Entity>,
 }
 
+/// `projectile` is whatever the attacker's weapon fires, if it's a ranged one. Anywhere the skill
+/// would have them swing it, they shoot it at the defender instead.
 fn build_timeline_for_skill(
     ae_entity: Entity,
     attack_intent: &AttackIntent,
     skill: &Skill,
     defender_grid_pos: &GridPosition,
+    projectile: Option<SpriteId>,
 ) -> CombatTimeline {
     let mut timeline = CombatTimeline::new();
     let mut stage_id = timeline.current_stage;
     stage_id.0 += 1;
+    let mut fired = Vec::new();
     for skill_stage in &skill.animation_data {
         let stage = match &skill_stage.stage {
             skills::SkillStageAction::UnitAttack(skill_animation_id, unit_animation_kind) => {
-                CombatStage::UnitAttack(
-                    attack_intent.attacker,
-                    CombatAnimationId::new(ae_entity, *skill_animation_id),
-                    *unit_animation_kind,
-                )
+                match projectile {
+                    Some(sprite) if matches!(unit_animation_kind, UnitAnimationKind::Attack) => {
+                        fired.push(*skill_animation_id);
+                        CombatStage::Cast(
+                            *defender_grid_pos,
+                            CastingData::Projectile(sprite, *skill_animation_id),
+                        )
+                    }
+                    _ => CombatStage::UnitAttack(
+                        attack_intent.attacker,
+                        CombatAnimationId::new(ae_entity, *skill_animation_id),
+                        *unit_animation_kind,
+                    ),
+                }
             }
             skills::SkillStageAction::Cast(casting_data) => {
                 // TODO: Probably need the target Grid space?
@@ -593,7 +658,16 @@ fn build_timeline_for_skill(
             }
         };
 
-        let triggers = CombatTimeline::parse_triggers(ae_entity, &skill_stage.advancing_event);
+        // The swing's markers never come, so wait on the projectile landing instead
+        let advancing_event = match &skill_stage.advancing_event {
+            SkillEvent::AnimationMarker(skill_animation_id, _)
+                if fired.contains(skill_animation_id) =>
+            {
+                SkillEvent::ProjectileImpact(*skill_animation_id)
+            }
+            otherwise => otherwise.clone(),
+        };
+        let triggers = CombatTimeline::parse_triggers(ae_entity, &advancing_event);
 
         timeline.stages.insert(stage_id, stage);
 
@@ -620,6 +694,23 @@ pub struct UnitIsAttacking {
     ae_entity: Entity,
 }
 
+/// A shield took the brunt of a hit on the unit
+#[derive(Message)]
+pub struct AttackBlockedEvent {
+    pub unit: Entity,
+}
+
+/// How much of a hit still gets through a shield
+pub const BLOCKED_DAMAGE_MULTIPLIER: f32 = 0.5;
+
+/// What's left of `health_change` after a block. Only damage gets blocked.
+pub fn blocked_health_change(health_change: i32) -> i32 {
+    if health_change >= 0 {
+        return health_change;
+    }
+    (health_change as f32 * BLOCKED_DAMAGE_MULTIPLIER).round() as i32
+}
+
 #[derive(Message)]
 pub struct UnitHealthChangedEvent {
     pub unit: Entity,
@@ -630,11 +721,16 @@ pub struct UnitHealthChangedEvent {
 /// and spawn an AttackExecution for the engine to drive animations and
 /// changes to the game.
 ///
+#[allow(clippy::too_many_arguments)]
 pub fn attack_intent_system(
     mut commands: Commands,
     skill_db: Res<SkillDBResource>,
+    grid_manager: Res<GridManagerResource>,
+    phase_manager: Res<PhaseManager>,
+    overlay_assets: Res<TileOverlayAssets>,
     intent_query: Query<(Entity, &AttackIntent)>,
     unit_query: Query<(&Unit, &GridPosition)>,
+    equipment_query: Query<&UnitEquipment>,
     mut attacker_resource_query: Query<&mut UnitPhaseResources>,
 ) {
     for (e, intent) in intent_query {
@@ -656,7 +752,6 @@ pub fn attack_intent_system(
         };
 
         let skill = skill_db.skill_db.get_skill(&intent.skill);
-        let combat_timeline = build_timeline_for_skill(e, intent, skill, defender_grid_pos);
 
         let Some(mut attacker_resources) = attacker_resource_query.get_mut(intent.attacker).ok()
         else {
@@ -664,9 +759,44 @@ pub fn attack_intent_system(
             continue;
         };
 
-        attacker_resources.action_points_left_in_phase = attacker_resources
-            .action_points_left_in_phase
-            .saturating_sub(skill.cost.ap as u32);
+        attacker_resources.spend(&ActionCost {
+            action_points: skill.cost.ap.into(),
+            ..Default::default()
+        });
+
+        // Delayed skills just mark the tiles for now, and the attacker is free to go.
+        if let SkillTiming::Delayed { resolution, radius } = skill.timing {
+            tracker.insert(AttackResolved {
+                attacker: Some(intent.attacker),
+            });
+
+            let tiles = if radius == 0 {
+                vec![*defender_grid_pos]
+            } else {
+                radius_range_at_position(&grid_manager.grid_manager, defender_grid_pos, radius)
+            };
+
+            delayed_skills::spawn_delayed_skill(
+                &mut commands,
+                &overlay_assets,
+                delayed_skills::DelayedSkill {
+                    caster: intent.attacker,
+                    skill: skill.skill_id,
+                    cast_during: phase_manager.current_phase,
+                    resolution,
+                    tiles,
+                },
+            );
+            continue;
+        }
+
+        let projectile = equipment_query
+            .get(intent.attacker)
+            .ok()
+            .and_then(|t| t.weapon_data())
+            .and_then(|t| t.projectile);
+        let combat_timeline =
+            build_timeline_for_skill(e, intent, skill, defender_grid_pos, projectile);
 
         // TODO: Create the concept of an AttackPreview, and ask the player for confirmation.
         tracker.insert(AttackExecution {
@@ -751,6 +881,30 @@ pub fn spawn_damage_text(
     }
 }
 
+pub fn spawn_block_text(
+    mut commands: Commands,
+    mut message_reader: MessageReader<AttackBlockedEvent>,
+    fonts: Res<FontResource>,
+) {
+    for message in message_reader.read() {
+        commands.entity(message.unit).with_child((
+            Text2d(tr!("combat.blocked")),
+            TextColor(Color::linear_rgb(0.75, 0.75, 0.8)),
+            TextFont {
+                font: fonts.pixelify_sans_regular.clone(),
+                font_size: 12.,
+                font_smoothing: bevy::text::FontSmoothing::None,
+                ..Default::default()
+            },
+            DamageText,
+            DespawnTimer {
+                timer: Timer::from_seconds(0.5, TimerMode::Once),
+            },
+            Transform::from_translation(Vec3::new(0., 48., 0.)),
+        ));
+    }
+}
+
 // TODO: Should Attacker be Optional here?
 //
 // TODO: Stats how to handle Health changes here? Should that be an update to
@@ -765,8 +919,13 @@ pub fn impact_event_handler(
         Option<&mut UnitAnimationPlayer>,
         &mut ActiveEffects,
     )>,
+    equipment_query: Query<&UnitEquipment>,
+    skill_db: Res<SkillDBResource>,
+    formulas: Res<CombatFormulas>,
     mut stat_change_request: MessageWriter<UnitStatChangeRequest>,
     mut audio_writer: MessageWriter<AudioEventMessage>,
+    mut battle_log: MessageWriter<BattleLogMessage>,
+    mut blocked_writer: MessageWriter<AttackBlockedEvent>,
 ) {
     for impact in impact_events.read() {
         let attacker = impact
@@ -777,7 +936,76 @@ pub fn impact_event_handler(
             continue;
         };
 
-        let damage = calculate_damage(attacker, defender_derived, &impact.skill_actions);
+        let mut damage = calculate_damage(attacker, defender_derived, &impact.skill_actions);
+
+        // Only attacks can miss, and a ranged attacker's aim can get thrown off on top of that
+        let mut hit_chance = attacker
+            .filter(|_| damage < 0)
+            .map(|t| formulas.hit_chance(&t.stats, &defender_derived.stats))
+            .unwrap_or(1.);
+        if let Some((_, _, effects)) = impact.attacker.and_then(|t| unit_query.get(t).ok())
+            && skill_db
+                .skill_db
+                .get_skill(&impact.skill_id)
+                .targeting
+                .is_ranged()
+        {
+            hit_chance *= effects.ranged_hit_chance();
+        }
+        if hit_chance < 1. && rand::rng().random::<f32>() >= hit_chance {
+            battle_log.write(BattleLogMessage::SkillMissed {
+                attacker: impact.attacker,
+                defender: impact.defender,
+                skill: impact.skill_id,
+            });
+            continue;
+        }
+
+        if let (Some(attacker_entity), Some(attacker)) = (impact.attacker, attacker)
+            && damage < 0
+        {
+            if formulas.strikes_twice(&attacker.stats, &defender_derived.stats) {
+                damage *= 2;
+                battle_log.write(BattleLogMessage::StruckTwice {
+                    attacker: attacker_entity,
+                });
+            }
+            let crit_chance = formulas.crit_chance(&attacker.stats, &defender_derived.stats);
+            if rand::rng().random::<f32>() < crit_chance {
+                damage = formulas.critical_damage(damage);
+                battle_log.write(BattleLogMessage::CriticalHit {
+                    attacker: attacker_entity,
+                });
+            }
+        }
+
+        let block_chance = equipment_query
+            .get(impact.defender)
+            .map(|t| t.block_chance())
+            .unwrap_or_default();
+        if damage < 0 && rand::rng().random::<f32>() < block_chance {
+            damage = blocked_health_change(damage);
+            blocked_writer.write(AttackBlockedEvent {
+                unit: impact.defender,
+            });
+            battle_log.write(BattleLogMessage::Blocked {
+                defender: impact.defender,
+            });
+            audio_writer.write(AudioEventMessage {
+                source: impact.attack_execution,
+                cue: AudioCue::Blocked,
+                audio_context: AudioContext {
+                    skill_id: Some(impact.skill_id),
+                },
+            });
+        }
+
+        battle_log.write(BattleLogMessage::SkillImpact {
+            attacker: impact.attacker,
+            defender: impact.defender,
+            skill: impact.skill_id,
+            health_change: damage,
+        });
 
         if let Ok((_defender_derived_stats, mut animation_player, _)) =
             unit_query.get_mut(impact.defender)
@@ -837,13 +1065,18 @@ pub fn impact_event_handler(
 
                 // Attach a Gameplay effect to the unit
                 for effect in effects {
-                    defender_effects.apply_effect(Effect {
+                    let effect = Effect {
                         metadata: EffectMetadata {
                             source: impact.attacker,
                             target: impact.defender,
                         },
                         data: effect.clone(),
+                    };
+                    battle_log.write(BattleLogMessage::EffectApplied {
+                        target: impact.defender,
+                        description: effect.description(),
                     });
+                    defender_effects.apply_effect(effect);
                 }
 
                 if !effects.is_empty() {
@@ -879,6 +1112,147 @@ pub fn attack_execution_despawner(
     }
 }
 
+/// Skills that get telegraphed now, and land on whoever is still standing there later.
+pub mod delayed_skills {
+    use bevy::prelude::*;
+
+    use crate::{
+        battle::BattleEntity,
+        battle_phase::{
+            PhaseMessage, PhaseMessageType, PlayerEnemyPhase, StartOfPhaseEffect, TurnQueue,
+            has_turn,
+        },
+        combat::{
+            AttackExecution, CombatTimeline,
+            skills::{DelayedResolution, SkillDBResource, SkillId},
+        },
+        dungeon::DungeonEntity,
+        grid::{GridManagerResource, GridPosition, init_grid_to_world_transform},
+        unit::{
+            CombatActionMarker, Unit,
+            overlay::{OverlayKind, TileOverlayAssets},
+        },
+        unit_stats::UnitDerivedStats,
+    };
+
+    #[derive(Component, Debug, Clone)]
+    pub struct DelayedSkill {
+        pub caster: Entity,
+        pub skill: SkillId,
+        /// The phase the skill was used in
+        pub cast_during: PlayerEnemyPhase,
+        pub resolution: DelayedResolution,
+        pub tiles: Vec<GridPosition>,
+    }
+
+    impl DelayedSkill {
+        fn is_due(
+            &self,
+            beginning_phase: PlayerEnemyPhase,
+            turn_queue: Option<&TurnQueue>,
+        ) -> bool {
+            match self.resolution {
+                // Any new phase starting means the one we were cast in is over
+                DelayedResolution::EndOfPhase => true,
+                DelayedResolution::StartOfNextTurn => {
+                    beginning_phase == self.cast_during && has_turn(turn_queue, self.caster)
+                }
+            }
+        }
+    }
+
+    /// Marks one of the tiles a DelayedSkill is going to hit.
+    ///
+    /// These are children of the DelayedSkill, so they go away once it resolves. They don't get a
+    /// GridPosition on purpose, as the GridManager should only be tracking things that are actually on the tile.
+    #[derive(Component)]
+    pub struct TelegraphedTile;
+
+    pub fn spawn_delayed_skill(
+        commands: &mut Commands,
+        overlay_assets: &TileOverlayAssets,
+        delayed_skill: DelayedSkill,
+    ) -> Entity {
+        let tiles = delayed_skill.tiles.clone();
+        commands
+            .spawn((
+                delayed_skill,
+                Transform::default(),
+                Visibility::default(),
+                BattleEntity {},
+                DungeonEntity,
+            ))
+            .with_children(|parent| {
+                for tile in tiles {
+                    let mut transform = init_grid_to_world_transform(&tile);
+                    transform.translation.z -= 40.;
+                    let mut telegraph = parent.spawn((
+                        TelegraphedTile,
+                        overlay_assets.overlay_sprite(OverlayKind::Telegraph),
+                        transform,
+                    ));
+                    if let Some(symbol) = overlay_assets.overlay_symbol(OverlayKind::Telegraph) {
+                        telegraph.with_child(symbol);
+                    }
+                }
+            })
+            .id()
+    }
+
+    /// Lands any DelayedSkills that are due as a new phase begins.
+    ///
+    /// Hits are treated as StartOfPhaseEffects, so the phase waits on them before starting.
+    pub fn resolve_delayed_skills(
+        mut commands: Commands,
+        mut message_reader: MessageReader<PhaseMessage>,
+        skill_db: Res<SkillDBResource>,
+        grid_manager: Res<GridManagerResource>,
+        turn_queue: Option<Res<TurnQueue>>,
+        delayed_skills: Query<(Entity, &DelayedSkill)>,
+        unit_query: Query<&UnitDerivedStats, With<Unit>>,
+    ) {
+        for message in message_reader.read() {
+            let PhaseMessageType::PhaseBegin(phase) = message.0;
+
+            for (e, delayed_skill) in delayed_skills.iter() {
+                if !delayed_skill.is_due(phase, turn_queue.as_deref()) {
+                    continue;
+                }
+
+                let skill = skill_db.skill_db.get_skill(&delayed_skill.skill);
+                info!("{} is landing", skill.name);
+
+                for tile in &delayed_skill.tiles {
+                    let Some(entities) = grid_manager.grid_manager.get_by_position(tile) else {
+                        continue;
+                    };
+
+                    for defender in entities.iter().copied() {
+                        if unit_query.get(defender).is_ok_and(|t| !t.downed()) {
+                            let mut ae = commands.spawn((CombatActionMarker, StartOfPhaseEffect));
+                            let combat_timeline = CombatTimeline::build_detached(
+                                ae.id(),
+                                skill,
+                                Some(delayed_skill.caster),
+                                defender,
+                                tile,
+                            );
+                            ae.insert(AttackExecution {
+                                attacker: None,
+                                defender,
+                                skill: skill.clone(),
+                                combat_timeline,
+                            });
+                        }
+                    }
+                }
+
+                commands.entity(e).despawn();
+            }
+        }
+    }
+}
+
 pub mod skills {
     use anyhow::Context;
     use bevy::reflect::Reflect;
@@ -898,8 +1272,10 @@ pub mod skills {
         },
         combat::CombatStageId,
         gameplay_effects::{
-            EffectData, EffectDuration, EffectType, Operator, StatModification, StatusTag,
+            ActiveEffects, EffectData, EffectDuration, EffectType, Operator, StatModification,
+            StatusTag,
         },
+        tr,
         unit_stats::StatType,
     };
 
@@ -946,6 +1322,28 @@ pub mod skills {
         TargetInRange(u32),
     }
 
+    impl Targeting {
+        pub fn range(&self) -> u32 {
+            match self {
+                Targeting::TargetInRange(range) => *range,
+            }
+        }
+
+        /// Anything that reaches past the tiles next to the caster
+        pub fn is_ranged(&self) -> bool {
+            self.range() > 1
+        }
+
+        /// The targeting as far as the caster can actually see, given what's on them
+        pub fn seen_through(&self, effects: &ActiveEffects) -> Targeting {
+            match self {
+                Targeting::TargetInRange(range) => {
+                    Targeting::TargetInRange(effects.sight_range(*range))
+                }
+            }
+        }
+    }
+
     pub enum TargetType {
         Any,
     }
@@ -980,6 +1378,26 @@ pub mod skills {
         pub ap: u8,
     }
 
+    /// When a skill's actions actually land
+    #[derive(Debug, Clone)]
+    pub enum SkillTiming {
+        Immediate,
+        /// Marks the targeted tiles, and then hits whoever is standing on them later.
+        Delayed {
+            resolution: DelayedResolution,
+            /// How far out from the targeted tile the skill reaches
+            radius: u32,
+        },
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum DelayedResolution {
+        /// Once the phase the skill was used in is over
+        EndOfPhase,
+        /// When the caster's next phase (or turn, in Initiative) begins
+        StartOfNextTurn,
+    }
+
     #[derive(Debug, Clone)]
     pub enum SkillActionType {
         DamagingSkill { scaled_damage: DamagingSkill },
@@ -1072,6 +1490,9 @@ pub mod skills {
         /// If so I might need to change Targeting?
         pub cost: SkillCost,
 
+        /// Whether the skill lands right away, or gets telegraphed and lands later.
+        pub timing: SkillTiming,
+
         /// Cues that should be emitted when this skill is processed.
         pub audio_cues_to_emit: SkillAudioCues,
 
@@ -1080,6 +1501,45 @@ pub mod skills {
         pub audio_profile: AudioProfile,
     }
 
+    impl Skill {
+        /// What the skill costs and what it does, for tooltips.
+        pub fn description(&self) -> String {
+            let mut lines = vec![tr!(
+                "skill.cost",
+                ap = self.cost.ap,
+                range = self.targeting.range()
+            )];
+
+            for action in &self.actions {
+                let accuracy = (action.base_accuracy * 100.).round();
+                match &action.action_type {
+                    SkillActionType::DamagingSkill { scaled_damage } => lines.push(tr!(
+                        "skill.damage",
+                        power = scaled_damage.power,
+                        accuracy = accuracy
+                    )),
+                    SkillActionType::HealingSkill { scaled_damage } => {
+                        lines.push(tr!("skill.heal", power = scaled_damage.power))
+                    }
+                    SkillActionType::ApplyEffects { effects } => lines.extend(
+                        effects
+                            .iter()
+                            .map(|t| tr!("skill.effect", effect = t.name(), accuracy = accuracy)),
+                    ),
+                }
+            }
+
+            if let SkillTiming::Delayed { resolution, .. } = &self.timing {
+                lines.push(match resolution {
+                    DelayedResolution::EndOfPhase => tr!("skill.delayed_end_of_phase"),
+                    DelayedResolution::StartOfNextTurn => tr!("skill.delayed_next_turn"),
+                });
+            }
+
+            lines.join("\n")
+        }
+    }
+
     #[derive(bevy::prelude::Resource)]
     pub struct SkillDBResource {
         pub skill_db: SkillDB,
@@ -1117,6 +1577,14 @@ pub mod skills {
                 .unwrap_or_else(|| panic!("SkillID should be registered: {:?}", skill_id))
         }
 
+        pub fn has_skill(&self, skill_id: &SkillId) -> bool {
+            self.skills.contains_key(skill_id)
+        }
+
+        pub fn skills(&self) -> impl Iterator<Item = &Skill> {
+            self.skills.values()
+        }
+
         pub fn get_category(&self, category_id: &SkillCategoryId) -> &SkillCategory {
             self.skill_categories
                 .get(category_id)
@@ -1213,10 +1681,38 @@ pub mod skills {
         /// unit back to it's "Camp" state?
         pub learned_skills: HashSet<SkillId>,
         pub equipped_skill_categories: Vec<SkillCategoryId>,
+        /// Skills that come from whatever the unit has equipped. They go away with the gear, so
+        /// they never get saved.
+        pub granted_skills: HashSet<SkillId>,
         // pub primary_category: SkillCategoryId,
         // pub secondary_category: SkillCategoryId,
     }
 
+    impl UnitSkills {
+        /// Everything the unit can use right now, learned or not
+        pub fn known_skills(&self) -> impl Iterator<Item = &SkillId> {
+            self.learned_skills
+                .iter()
+                .chain(self.granted_skills.difference(&self.learned_skills))
+        }
+
+        /// The categories to show in the skill menu. Anything learned or granted by gear brings its
+        /// category along, even if the unit hasn't got it equipped.
+        pub fn skill_categories(&self, skill_db: &SkillDB) -> Vec<SkillCategoryId> {
+            let mut known_skills = self.known_skills().copied().collect::<Vec<_>>();
+            known_skills.sort_by_key(|t| t.0);
+
+            let mut categories = self.equipped_skill_categories.clone();
+            for skill in &known_skills {
+                let category = *skill_db.get_category_for_skill(skill);
+                if !categories.contains(&category) {
+                    categories.push(category);
+                }
+            }
+            categories
+        }
+    }
+
     pub fn setup_skill_system(mut commands: bevy::prelude::Commands) {
         let skill_db = build_skill_table().expect("Should be able to build the skill DB");
         commands.insert_resource(SkillDBResource { skill_db });
@@ -1282,6 +1778,7 @@ pub mod skills {
                         },
                     ],
                     cost: SkillCost { ap: 1 },
+                    timing: SkillTiming::Immediate,
                     audio_profile: AudioProfile::default(),
                     audio_cues_to_emit: SkillAudioCues::default(),
                 },
@@ -1393,6 +1890,7 @@ pub mod skills {
                         },
                     ],
                     cost: SkillCost { ap: 1 },
+                    timing: SkillTiming::Immediate,
                     // TODO: We could consider just having the SkillStageAction
                     // drive what is happening for Impact?
                     audio_cues_to_emit: SkillAudioCues {
@@ -1483,6 +1981,7 @@ pub mod skills {
                         },
                     ],
                     cost: SkillCost { ap: 1 },
+                    timing: SkillTiming::Immediate,
                     audio_cues_to_emit: SkillAudioCues::default(),
                     audio_profile: AudioProfile::default(),
                 },
@@ -1522,6 +2021,7 @@ pub mod skills {
                         },
                     ],
                     cost: SkillCost { ap: 1 },
+                    timing: SkillTiming::Immediate,
                     audio_cues_to_emit: SkillAudioCues::default(),
                     audio_profile: AudioProfile::default(),
                 },
@@ -1578,6 +2078,7 @@ pub mod skills {
                         },
                     ],
                     cost: SkillCost { ap: 1 },
+                    timing: SkillTiming::Immediate,
                     audio_cues_to_emit: SkillAudioCues::default(),
                     audio_profile: AudioProfile::default(),
                 },
@@ -1631,6 +2132,7 @@ pub mod skills {
                         },
                     ],
                     cost: SkillCost { ap: 1 },
+                    timing: SkillTiming::Immediate,
                     audio_cues_to_emit: SkillAudioCues::default(),
                     audio_profile: AudioProfile::default(),
                 },
@@ -1680,6 +2182,7 @@ pub mod skills {
                         },
                     ],
                     cost: SkillCost { ap: 1 },
+                    timing: SkillTiming::Immediate,
                     audio_cues_to_emit: SkillAudioCues::default(),
                     audio_profile: AudioProfile::default(),
                 },
@@ -1732,6 +2235,7 @@ pub mod skills {
                         },
                     ],
                     cost: SkillCost { ap: 1 },
+                    timing: SkillTiming::Immediate,
                     audio_cues_to_emit: SkillAudioCues::default(),
                     audio_profile: AudioProfile::default(),
                 },
@@ -1786,10 +2290,178 @@ pub mod skills {
                         },
                     ],
                     cost: SkillCost { ap: 1 },
+                    timing: SkillTiming::Immediate,
+                    audio_cues_to_emit: SkillAudioCues::default(),
+                    audio_profile: AudioProfile::default(),
+                },
+            )?
+            .register_skill(
+                SkillCategoryId(1),
+                SkillId(10),
+                Skill {
+                    skill_id: SkillId(10),
+                    name: "Meteor".to_owned(),
+                    actions: Vec::from([SkillAction {
+                        base_accuracy: 1.0,
+                        action_type: SkillActionType::DamagingSkill {
+                            scaled_damage: DamagingSkill {
+                                power: 4,
+                                offensive_modifier: Some(AttackModifier {
+                                    stat: StatType::Magic,
+                                }),
+                                defensive_modifier: Some(AttackModifier {
+                                    stat: StatType::Resistance,
+                                }),
+                            },
+                        },
+                    }]),
+                    targeting: Targeting::TargetInRange(4),
+                    // No UnitAttack stages, the Mage is long done by the time this lands.
+                    animation_data: vec![
+                        SkillStage {
+                            stage: SkillStageAction::Cast(CastingData::TileSprite(
+                                SpriteId(5),
+                                AnimationKey {
+                                    animated_sprite_id: AnimatedSpriteId(4),
+                                    animation_id: RegisteredAnimationId {
+                                        id: 1,
+                                        priority: crate::animation::AnimationPriority::Combat,
+                                    },
+                                },
+                                SkillAnimationId(1),
+                            )),
+                            advancing_event: SkillEvent::AnimationMarker(
+                                SkillAnimationId(1),
+                                AnimationMarker::HitFrame,
+                            ),
+                        },
+                        SkillStage {
+                            stage: SkillStageAction::Impact(vec![SkillActionIndex(0)]),
+                            advancing_event: SkillEvent::AnimationMarker(
+                                SkillAnimationId(1),
+                                AnimationMarker::Complete,
+                            ),
+                        },
+                    ],
+                    cost: SkillCost { ap: 1 },
+                    timing: SkillTiming::Delayed {
+                        resolution: DelayedResolution::StartOfNextTurn,
+                        radius: 1,
+                    },
+                    audio_cues_to_emit: SkillAudioCues {
+                        to_emit_on_exit: HashMap::from([(
+                            SkillStageIndex(1),
+                            vec![AudioCue::Impact],
+                        )]),
+                    },
+                    audio_profile: AudioProfile {
+                        on_cue: HashMap::from([(
+                            AudioCue::Impact,
+                            vec![CombatSound::Skill(SkillSound::FlameExplosion)],
+                        )]),
+                    },
+                },
+            )?
+            .register_skill(
+                SkillCategoryId(4),
+                SkillId(11),
+                Skill {
+                    skill_id: SkillId(11),
+                    name: "Warcry".to_owned(),
+                    actions: Vec::from([SkillAction {
+                        base_accuracy: 1.0,
+                        action_type: SkillActionType::ApplyEffects {
+                            effects: vec![EffectData {
+                                effect_type: EffectType::StatBuff(StatModification {
+                                    attribute_type: StatType::Strength,
+                                    operator: Operator::Add,
+                                    value: 2.0,
+                                }),
+                                duration: EffectDuration::TurnCount(2),
+                            }],
+                        },
+                    }]),
+                    targeting: Targeting::TargetInRange(1),
+                    animation_data: vec![
+                        SkillStage {
+                            stage: SkillStageAction::UnitAttack(
+                                SkillAnimationId(1),
+                                UnitAnimationKind::Charge,
+                            ),
+                            advancing_event: SkillEvent::AnimationMarker(
+                                SkillAnimationId(1),
+                                AnimationMarker::Complete,
+                            ),
+                        },
+                        SkillStage {
+                            stage: SkillStageAction::UnitAttack(
+                                SkillAnimationId(2),
+                                UnitAnimationKind::Release,
+                            ),
+                            advancing_event: SkillEvent::AnimationMarker(
+                                SkillAnimationId(2),
+                                AnimationMarker::Complete,
+                            ),
+                        },
+                        SkillStage {
+                            stage: SkillStageAction::Impact(vec![SkillActionIndex(0)]),
+                            advancing_event: SkillEvent::AnimationMarker(
+                                SkillAnimationId(2),
+                                AnimationMarker::Complete,
+                            ),
+                        },
+                    ],
+                    cost: SkillCost { ap: 1 },
+                    timing: SkillTiming::Immediate,
+                    audio_cues_to_emit: SkillAudioCues::default(),
+                    audio_profile: AudioProfile::default(),
+                },
+            )?
+            .register_skill(
+                SkillCategoryId(6),
+                SkillId(12),
+                Skill {
+                    skill_id: SkillId(12),
+                    name: "Sunder".to_owned(),
+                    actions: Vec::from([SkillAction {
+                        base_accuracy: 1.0,
+                        action_type: SkillActionType::ApplyEffects {
+                            effects: vec![EffectData {
+                                effect_type: EffectType::StatBuff(StatModification {
+                                    attribute_type: StatType::Defense,
+                                    operator: Operator::Add,
+                                    value: -2.0,
+                                }),
+                                duration: EffectDuration::TurnCount(2),
+                            }],
+                        },
+                    }]),
+                    targeting: Targeting::TargetInRange(1),
+                    animation_data: vec![
+                        SkillStage {
+                            stage: SkillStageAction::UnitAttack(
+                                SkillAnimationId(1),
+                                UnitAnimationKind::Attack,
+                            ),
+                            advancing_event: SkillEvent::AnimationMarker(
+                                SkillAnimationId(1),
+                                AnimationMarker::HitFrame,
+                            ),
+                        },
+                        SkillStage {
+                            stage: SkillStageAction::Impact(vec![SkillActionIndex(0)]),
+                            advancing_event: SkillEvent::AnimationMarker(
+                                SkillAnimationId(1),
+                                AnimationMarker::Complete,
+                            ),
+                        },
+                    ],
+                    cost: SkillCost { ap: 1 },
+                    timing: SkillTiming::Immediate,
                     audio_cues_to_emit: SkillAudioCues::default(),
                     audio_profile: AudioProfile::default(),
                 },
-            )?;
+            );
 
         // TODO: Validate SkillDB once we load it from an external source.
 
@@ -1806,3 +2478,90 @@ pub mod skills {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combat::skills::{ATTACK_SKILL_ID, build_skill_table};
+    use crate::unit_stats::StatContainer;
+
+    #[test]
+    fn test_ranged_weapons_shoot_instead_of_swinging() {
+        let skill_db = build_skill_table().expect("Should be able to build skill table");
+        let skill = skill_db.get_skill(&ATTACK_SKILL_ID);
+        let intent = AttackIntent {
+            attacker: Entity::PLACEHOLDER,
+            defender: Entity::PLACEHOLDER,
+            skill: ATTACK_SKILL_ID,
+        };
+        let target = GridPosition { x: 2, y: 3 };
+
+        let swung = build_timeline_for_skill(Entity::PLACEHOLDER, &intent, skill, &target, None);
+        assert!(matches!(
+            swung.stages.get(&CombatStageId(1)),
+            Some(CombatStage::UnitAttack(..))
+        ));
+
+        let shot = build_timeline_for_skill(
+            Entity::PLACEHOLDER,
+            &intent,
+            skill,
+            &target,
+            Some(SpriteId(6)),
+        );
+        assert!(matches!(
+            shot.stages.get(&CombatStageId(1)),
+            Some(CombatStage::Cast(
+                _,
+                CastingData::Projectile(SpriteId(6), _)
+            ))
+        ));
+        assert!(
+            shot.conditions_to_advance
+                .values()
+                .flatten()
+                .all(|t| matches!(t, AttackExecutionTrigger::ProjectileImpactEvent(..)))
+        );
+    }
+
+    #[test]
+    fn test_high_defense_takes_no_damage() {
+        let stats = |stat, value| UnitDerivedStats {
+            stats: StatContainer::new()
+                .with_stat(stat, StatValue(value))
+                .to_owned(),
+        };
+        let attacker = stats(StatType::Strength, 3.);
+        let defender = stats(StatType::Defense, 20.);
+        let scaled_damage = skills::DamagingSkill {
+            power: 2,
+            offensive_modifier: Some(skills::AttackModifier {
+                stat: StatType::Strength,
+            }),
+            defensive_modifier: Some(skills::AttackModifier {
+                stat: StatType::Defense,
+            }),
+        };
+        let action = |action_type| skills::SkillAction {
+            base_accuracy: 1.,
+            action_type,
+        };
+
+        let hit = vec![action(skills::SkillActionType::DamagingSkill {
+            scaled_damage: scaled_damage.clone(),
+        })];
+        assert_eq!(calculate_damage(Some(&attacker), &defender, &hit), 0);
+
+        let heal = vec![action(skills::SkillActionType::HealingSkill {
+            scaled_damage,
+        })];
+        assert_eq!(calculate_damage(Some(&attacker), &defender, &heal), 0);
+    }
+
+    #[test]
+    fn test_blocks_only_soften_damage() {
+        assert_eq!(blocked_health_change(-6), -3);
+        assert_eq!(blocked_health_change(-1), -1);
+        assert_eq!(blocked_health_change(4), 4);
+    }
+}
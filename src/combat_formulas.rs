@@ -0,0 +1,133 @@
+//! The numbers behind a fight, worked out from stats.
+//!
+//! How likely an attack is to land, how likely it is to crit, how hard a crit hits, and how much
+//! faster an attacker needs to be to strike twice all live in the [`CombatFormulas`] resource, which
+//! gets read from `assets/combat/formulas.ron`. The file gets read at runtime where there's a
+//! file system, so balancing doesn't need a recompile: god mode reloads it with F6, and the
+//! inspector can tweak the resource live. Builds without one (like the web) use the copy baked in.
+
+use anyhow::Context;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::unit_stats::{StatContainer, StatType};
+
+const FORMULAS_PATH: &str = "assets/combat/formulas.ron";
+const BUILT_IN_FORMULAS: &str = include_str!("../assets/combat/formulas.ron");
+
+/// `base`, plus each of the attacker's and the defender's stats times its weight, kept between
+/// `min` and `max`
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+pub struct Formula {
+    pub base: f32,
+    pub attacker: Vec<(StatType, f32)>,
+    pub defender: Vec<(StatType, f32)>,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Formula {
+    pub fn evaluate(&self, attacker: &StatContainer, defender: &StatContainer) -> f32 {
+        let weighted = |stats: &StatContainer, weights: &[(StatType, f32)]| {
+            weights
+                .iter()
+                .map(|(stat, weight)| stats.stat(*stat).0 * weight)
+                .sum::<f32>()
+        };
+        let value =
+            self.base + weighted(attacker, &self.attacker) + weighted(defender, &self.defender);
+        value.clamp(self.min, self.max)
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Resource)]
+pub struct CombatFormulas {
+    /// How likely a damaging skill is to land
+    pub hit_chance: Formula,
+    /// How likely a hit is to be critical
+    pub crit_chance: Formula,
+    /// How many times the damage a critical hit does
+    pub crit_multiplier: f32,
+    /// How much faster than the defender an attacker has to be to strike twice
+    pub double_attack_speed: f32,
+}
+
+impl CombatFormulas {
+    pub fn hit_chance(&self, attacker: &StatContainer, defender: &StatContainer) -> f32 {
+        self.hit_chance.evaluate(attacker, defender)
+    }
+
+    pub fn crit_chance(&self, attacker: &StatContainer, defender: &StatContainer) -> f32 {
+        self.crit_chance.evaluate(attacker, defender)
+    }
+
+    /// The damage a critical hit does, in place of `damage`
+    pub fn critical_damage(&self, damage: i32) -> i32 {
+        (damage as f32 * self.crit_multiplier).round() as i32
+    }
+
+    pub fn strikes_twice(&self, attacker: &StatContainer, defender: &StatContainer) -> bool {
+        attacker.stat(StatType::Speed).0 - defender.stat(StatType::Speed).0
+            >= self.double_attack_speed
+    }
+}
+
+fn parse_combat_formulas(raw: &str) -> anyhow::Result<CombatFormulas> {
+    ron::from_str::<CombatFormulas>(raw).context("Parsing the combat formulas")
+}
+
+/// The formulas on disk, or the ones baked in if there's no file system or the file's broken
+pub fn load_combat_formulas() -> CombatFormulas {
+    #[cfg(not(target_arch = "wasm32"))]
+    match std::fs::read_to_string(FORMULAS_PATH)
+        .context("Reading the combat formulas")
+        .and_then(|t| parse_combat_formulas(&t))
+    {
+        Ok(formulas) => return formulas,
+        Err(e) => warn!("Using the built in combat formulas: {:?}", e),
+    }
+
+    parse_combat_formulas(BUILT_IN_FORMULAS).expect("The built in combat formulas should parse")
+}
+
+pub fn combat_formulas_plugin(app: &mut App) {
+    app.register_type::<CombatFormulas>()
+        .insert_resource(load_combat_formulas());
+}
+
+/// Picks up any changes made to the formulas file, for god mode
+pub fn reload_combat_formulas(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut formulas: ResMut<CombatFormulas>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F6) {
+        *formulas = load_combat_formulas();
+        info!("Reloaded the combat formulas: {:?}", *formulas);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unit_stats::StatValue;
+
+    #[test]
+    fn test_built_in_formulas_parse_and_stay_in_bounds() {
+        let formulas =
+            parse_combat_formulas(BUILT_IN_FORMULAS).expect("The built in formulas should parse");
+
+        let mut slow = StatContainer::new();
+        slow.with_stat(StatType::Speed, StatValue(1.));
+        let mut fast = StatContainer::new();
+        fast.with_stat(StatType::Speed, StatValue(40.))
+            .with_stat(StatType::Skill, StatValue(100.));
+
+        assert_eq!(formulas.hit_chance(&slow, &fast), formulas.hit_chance.min);
+        assert_eq!(formulas.hit_chance(&fast, &slow), formulas.hit_chance.max);
+        assert_eq!(formulas.crit_chance(&fast, &slow), formulas.crit_chance.max);
+        assert!(formulas.strikes_twice(&fast, &slow));
+        assert!(!formulas.strikes_twice(&slow, &fast));
+        assert!(formulas.critical_damage(-4) < -4);
+    }
+}
@@ -0,0 +1,230 @@
+//! Sound effects for everything that happens in a fight.
+//!
+//! Like [`UiSound`](crate::assets::sounds::UiSound) is for the menus, [`CombatSfx`] is everything a
+//! fight can make a noise for: swings, hits (which sound different for physical and magical
+//! damage), misses, blocks, crits, heals, units going down and level ups. Which sound plays for
+//! which is all in `assets/sound_assets/combat_sounds.ron`, so they can be swapped around without
+//! touching any code. Builds without a file system (like the web) use the copy baked in.
+//!
+//! The sounds get picked from what's already being said about the fight, mostly the
+//! [battle log](crate::battle_log), rather than from each system that does the fighting.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use bevy::prelude::*;
+use rand::seq::IndexedRandom;
+use serde::Deserialize;
+
+use crate::{
+    animation::{AnimationId, AnimationMarker, AnimationMarkerMessage},
+    assets::sounds::SoundManagerParam,
+    battle_log::BattleLogMessage,
+    combat::{
+        UnitHealthChangedEvent,
+        skills::{Skill, SkillActionType, SkillDBResource},
+    },
+    unit::Unit,
+    unit_stats::{StatType, UnitDerivedStats},
+};
+
+const COMBAT_SOUNDS_PATH: &str = "assets/sound_assets/combat_sounds.ron";
+const BUILT_IN_COMBAT_SOUNDS: &str = include_str!("../assets/sound_assets/combat_sounds.ron");
+
+/// What kind of damage a hit did, since a fireball shouldn't sound like a sword
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum DamageKind {
+    Physical,
+    Magical,
+}
+
+impl DamageKind {
+    /// Anything scaling off of magic is magical, and everything else is physical
+    pub fn of(skill: &Skill) -> DamageKind {
+        let magical = skill.actions.iter().any(|t| {
+            let SkillActionType::DamagingSkill { scaled_damage } = &t.action_type else {
+                return false;
+            };
+            scaled_damage
+                .offensive_modifier
+                .as_ref()
+                .is_some_and(|t| t.stat == StatType::Magic)
+        });
+        if magical {
+            DamageKind::Magical
+        } else {
+            DamageKind::Physical
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum CombatSfx {
+    Swing,
+    Impact(DamageKind),
+    CriticalHit,
+    Miss,
+    Blocked,
+    Heal,
+    Death,
+    LevelUp,
+}
+
+/// How `combat_sounds.ron` is laid out: each effect, and the sounds it could play
+#[derive(Debug, Deserialize)]
+struct CombatSfxTable {
+    sounds: Vec<(CombatSfx, Vec<String>)>,
+}
+
+fn parse_combat_sfx_table(raw: &str) -> anyhow::Result<CombatSfxTable> {
+    ron::from_str::<CombatSfxTable>(raw).context("Parsing the combat sound table")
+}
+
+/// The table on disk, or the one baked in if there's no file system or the file's broken
+fn load_combat_sfx_table() -> CombatSfxTable {
+    #[cfg(not(target_arch = "wasm32"))]
+    match std::fs::read_to_string(COMBAT_SOUNDS_PATH)
+        .context("Reading the combat sound table")
+        .and_then(|t| parse_combat_sfx_table(&t))
+    {
+        Ok(table) => return table,
+        Err(e) => warn!("Using the built in combat sound table: {:?}", e),
+    }
+
+    parse_combat_sfx_table(BUILT_IN_COMBAT_SOUNDS)
+        .expect("The built in combat sound table should parse")
+}
+
+/// Every combat sound, loaded up and ready to play
+#[derive(Resource, Debug, Default)]
+pub struct CombatSfxLibrary {
+    sounds: HashMap<CombatSfx, Vec<Handle<AudioSource>>>,
+}
+
+impl CombatSfxLibrary {
+    /// One of the sounds for the effect, if it's got any
+    pub fn pick(&self, sfx: CombatSfx) -> Option<Handle<AudioSource>> {
+        self.sounds.get(&sfx)?.choose(&mut rand::rng()).cloned()
+    }
+
+    pub(crate) fn get_all_sound_paths(&self) -> Vec<PathBuf> {
+        self.sounds
+            .values()
+            .flatten()
+            .filter_map(|t| t.path())
+            .map(|t| t.path().to_path_buf())
+            .collect()
+    }
+}
+
+pub fn setup_combat_sfx(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let table = load_combat_sfx_table();
+    let mut library = CombatSfxLibrary::default();
+    for (sfx, paths) in table.sounds {
+        library
+            .sounds
+            .entry(sfx)
+            .or_default()
+            .extend(paths.into_iter().map(|t| asset_server.load(t)));
+    }
+    commands.insert_resource(library);
+}
+
+/// Works out what to play from the battle log, plus swings from attack animations and anyone who
+/// just went down
+pub fn play_combat_sfx(
+    mut commands: Commands,
+    library: Res<CombatSfxLibrary>,
+    sounds: SoundManagerParam,
+    skill_db: Option<Res<SkillDBResource>>,
+    mut battle_log: MessageReader<BattleLogMessage>,
+    mut animation_markers: MessageReader<AnimationMarkerMessage>,
+    mut health_changes: MessageReader<UnitHealthChangedEvent>,
+    units: Query<&UnitDerivedStats, With<Unit>>,
+) {
+    let mut to_play = Vec::new();
+
+    for message in battle_log.read() {
+        let sfx = match message {
+            BattleLogMessage::SkillImpact {
+                skill,
+                health_change,
+                ..
+            } if *health_change < 0 => CombatSfx::Impact(
+                skill_db
+                    .as_ref()
+                    .map(|t| DamageKind::of(t.skill_db.get_skill(skill)))
+                    .unwrap_or(DamageKind::Physical),
+            ),
+            BattleLogMessage::SkillImpact { health_change, .. } if *health_change > 0 => {
+                CombatSfx::Heal
+            }
+            BattleLogMessage::SkillMissed { .. } => CombatSfx::Miss,
+            BattleLogMessage::Blocked { .. } => CombatSfx::Blocked,
+            BattleLogMessage::CriticalHit { .. } => CombatSfx::CriticalHit,
+            BattleLogMessage::LevelUp { .. } => CombatSfx::LevelUp,
+            _ => continue,
+        };
+        to_play.push(sfx);
+    }
+
+    for marker in animation_markers.read() {
+        if marker.marker == AnimationMarker::HitFrame
+            && matches!(marker.id, Some(AnimationId::Combat(..)))
+            && units.contains(marker.entity)
+        {
+            to_play.push(CombatSfx::Swing);
+        }
+    }
+
+    for message in health_changes.read() {
+        if message.health_changed < 0 && units.get(message.unit).is_ok_and(|t| t.downed()) {
+            to_play.push(CombatSfx::Death);
+        }
+    }
+
+    for sfx in to_play {
+        if let Some(source) = library.pick(sfx) {
+            sounds.play_sfx(&mut commands, source);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combat::skills::{ATTACK_SKILL_ID, SkillId, build_skill_table};
+
+    #[test]
+    fn test_built_in_table_covers_every_hit() {
+        let table = parse_combat_sfx_table(BUILT_IN_COMBAT_SOUNDS)
+            .expect("The built in table should parse");
+        for sfx in [
+            CombatSfx::Impact(DamageKind::Physical),
+            CombatSfx::Impact(DamageKind::Magical),
+            CombatSfx::Miss,
+            CombatSfx::Death,
+        ] {
+            assert!(
+                table
+                    .sounds
+                    .iter()
+                    .any(|(t, paths)| *t == sfx && !paths.is_empty()),
+                "Nothing plays for {:?}",
+                sfx
+            );
+        }
+
+        let skill_db = build_skill_table().expect("Should be able to build skill table");
+        assert_eq!(
+            DamageKind::of(skill_db.get_skill(&ATTACK_SKILL_ID)),
+            DamageKind::Physical
+        );
+        // Flame
+        assert_eq!(
+            DamageKind::of(skill_db.get_skill(&SkillId(2))),
+            DamageKind::Magical
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! A reusable "Are you sure?" prompt.
+//!
+//! [`open_confirm_dialog`] takes over input from the menu that opened it, and gives it back once
+//! the player picks Confirm or Cancel (or backs out with Deselect). Either way a
+//! [`ConfirmDialogMessage`] gets written, so whoever cares about the [`ConfirmDialogAction`] can
+//! pick it up in a regular system.
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    assets::{
+        FontResource,
+        sounds::{SoundManagerParam, UiSound},
+    },
+    equipment::ItemId,
+    inventory::Consumable,
+    localization::localized_text,
+    menu::{
+        menu_navigation::{ActiveMenu, GameMenuController, GameMenuGrid, GameMenuLatch},
+        ui_consts::{SELECTABLE_BUTTON_BACKGROUND, UI_MENU_BACKGROUND, UI_TEXT_COLOR},
+    },
+    player::{Player, PlayerInputAction},
+    tr,
+};
+
+/// The thing the player is being asked about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfirmDialogAction {
+    QuitGame,
+    EraseAllData,
+    ConcedeBattle,
+    QuitToMainMenu,
+    /// Deleting the character with this uid
+    DeleteCharacter(u32),
+    /// Deleting the profile with this id
+    DeleteProfile(u32),
+    /// The save of the character with this uid can't be read, but it has a backup
+    RestoreCharacterBackup(u32),
+    /// Buying one of these for the party's bag
+    BuyItem(ItemId),
+    /// Selling one of these out of the party's bag
+    SellItem(ItemId),
+    /// Buying one of these for the party's bag
+    BuyConsumable(Consumable),
+    /// Paying to fix up all of the party's gear
+    RepairGear,
+    /// Ending the turn early for all of `player`'s units, from their battle `menu`
+    EndTurn {
+        player: Player,
+        menu: Entity,
+    },
+    /// `unit` waiting as the last of `player`'s units to act, which ends the phase
+    WaitAnyway {
+        player: Player,
+        unit: Entity,
+        menu: Entity,
+    },
+}
+
+impl ConfirmDialogAction {
+    fn prompt(&self) -> String {
+        match self {
+            ConfirmDialogAction::QuitGame => tr!("confirm_dialog.quit_game"),
+            ConfirmDialogAction::EraseAllData => tr!("confirm_dialog.erase_all_data"),
+            ConfirmDialogAction::ConcedeBattle => tr!("confirm_dialog.concede_battle"),
+            ConfirmDialogAction::QuitToMainMenu => tr!("confirm_dialog.quit_to_main_menu"),
+            ConfirmDialogAction::DeleteCharacter(_) => tr!("confirm_dialog.delete_character"),
+            ConfirmDialogAction::DeleteProfile(_) => tr!("confirm_dialog.delete_profile"),
+            ConfirmDialogAction::RestoreCharacterBackup(_) => {
+                tr!("confirm_dialog.restore_character_backup")
+            }
+            ConfirmDialogAction::BuyItem(_) | ConfirmDialogAction::BuyConsumable(_) => {
+                tr!("confirm_dialog.buy_item")
+            }
+            ConfirmDialogAction::SellItem(_) => tr!("confirm_dialog.sell_item"),
+            ConfirmDialogAction::RepairGear => tr!("confirm_dialog.repair_gear"),
+            ConfirmDialogAction::EndTurn { .. } => tr!("confirm_dialog.end_turn"),
+            ConfirmDialogAction::WaitAnyway { .. } => tr!("confirm_dialog.end_phase"),
+        }
+    }
+}
+
+/// Written once the player answers a dialog.
+#[derive(Message, Debug, Clone)]
+pub struct ConfirmDialogMessage {
+    pub action: ConfirmDialogAction,
+    pub confirmed: bool,
+}
+
+/// The menu part of an open dialog.
+#[derive(Component)]
+pub struct ConfirmDialog {
+    pub action: ConfirmDialogAction,
+    /// The menu that opened the dialog, which gets `ActiveMenu` back when it closes.
+    pub parent: Entity,
+    /// The full screen backdrop, which owns everything else.
+    screen: Entity,
+}
+
+#[derive(Component)]
+enum ConfirmDialogButton {
+    Confirm,
+    Cancel,
+}
+
+pub fn confirm_dialog_plugin(app: &mut App) {
+    app.add_message::<ConfirmDialogMessage>()
+        .add_systems(Update, cancel_confirm_dialog)
+        .add_observer(confirm_dialog_button_action);
+}
+
+fn dialog_button(font: &TextFont, action: ConfirmDialogButton, key: &'static str) -> impl Bundle {
+    (
+        Button,
+        Node {
+            width: px(180),
+            height: px(60),
+            margin: UiRect::all(px(12)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            border_radius: BorderRadius::all(percent(20)),
+            ..default()
+        },
+        BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+        action,
+        children![(localized_text(key), font.clone(), TextColor(UI_TEXT_COLOR))],
+    )
+}
+
+/// Opens a dialog asking about `action` on top of everything else, and suspends `parent` until
+/// it's answered.
+///
+/// Cancel is highlighted first, so mashing Select doesn't do anything drastic.
+pub fn open_confirm_dialog(
+    commands: &mut Commands,
+    fonts: &FontResource,
+    action: ConfirmDialogAction,
+    parent: Entity,
+    players: HashSet<Player>,
+) -> Entity {
+    open_confirm_dialog_with_detail(commands, fonts, action, String::new(), parent, players)
+}
+
+/// Same as [`open_confirm_dialog`], with `detail` tacked on after the question.
+pub fn open_confirm_dialog_with_detail(
+    commands: &mut Commands,
+    fonts: &FontResource,
+    action: ConfirmDialogAction,
+    detail: String,
+    parent: Entity,
+    players: HashSet<Player>,
+) -> Entity {
+    let font = TextFont {
+        font_size: 28.0,
+        font: fonts.pixelify_sans_regular.clone(),
+        ..default()
+    };
+
+    let cancel = commands
+        .spawn(dialog_button(
+            &font,
+            ConfirmDialogButton::Cancel,
+            "confirm_dialog.cancel",
+        ))
+        .id();
+    let confirm = commands
+        .spawn(dialog_button(
+            &font,
+            ConfirmDialogButton::Confirm,
+            "confirm_dialog.confirm",
+        ))
+        .id();
+
+    let mut grid = GameMenuGrid::new_with_width(2);
+    grid.push_buttons_in_rows(&[cancel, confirm]);
+
+    let screen = commands
+        .spawn((
+            Name::new(format!("ConfirmDialog {:?}", action)),
+            Node {
+                position_type: PositionType::Absolute,
+                width: percent(100),
+                height: percent(100),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            BackgroundColor(Color::BLACK.with_alpha(0.5)),
+            GlobalZIndex(150),
+        ))
+        .id();
+
+    let button_row = commands
+        .spawn(Node {
+            flex_direction: FlexDirection::Row,
+            ..default()
+        })
+        .add_children(&[cancel, confirm])
+        .id();
+
+    let dialog = commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                max_width: percent(60),
+                padding: UiRect::all(px(24)),
+                border_radius: BorderRadius::all(percent(10)),
+                ..default()
+            },
+            BackgroundColor(UI_MENU_BACKGROUND),
+            children![(
+                Text::new(format!("{}{}", action.prompt(), detail)),
+                TextFont {
+                    font_size: 36.0,
+                    font: fonts.pixelify_sans_medium.clone(),
+                    ..default()
+                },
+                TextColor(UI_TEXT_COLOR),
+                TextLayout::new_with_justify(Justify::Center),
+            )],
+            grid,
+            GameMenuController { players },
+            GameMenuLatch::default(),
+            ConfirmDialog {
+                action,
+                parent,
+                screen,
+            },
+            ActiveMenu {},
+        ))
+        .add_child(button_row)
+        .id();
+
+    commands.entity(screen).add_child(dialog);
+    commands.entity(parent).remove::<ActiveMenu>();
+    dialog
+}
+
+fn close_confirm_dialog(
+    commands: &mut Commands,
+    sounds: &SoundManagerParam,
+    writer: &mut MessageWriter<ConfirmDialogMessage>,
+    dialog: &ConfirmDialog,
+    confirmed: bool,
+) {
+    info!("{:?} confirmed: {}", dialog.action, confirmed);
+    commands.entity(dialog.screen).despawn();
+    // Confirming might have torn down the parent already
+    commands.entity(dialog.parent).try_insert(ActiveMenu {});
+    writer.write(ConfirmDialogMessage {
+        action: dialog.action,
+        confirmed,
+    });
+
+    sounds.play_ui_sound(
+        commands,
+        if confirmed {
+            UiSound::Select
+        } else {
+            UiSound::CloseMenu
+        },
+    );
+}
+
+fn confirm_dialog_button_action(
+    mut click: On<Pointer<Click>>,
+    mut commands: Commands,
+    buttons: Query<(&ConfirmDialogButton, &ChildOf), With<Button>>,
+    parent_query: Query<&ChildOf>,
+    dialogs: Query<&ConfirmDialog>,
+    mut writer: MessageWriter<ConfirmDialogMessage>,
+    sounds: SoundManagerParam,
+) {
+    let Ok((button, button_row)) = buttons.get(click.entity) else {
+        return;
+    };
+    click.propagate(false);
+
+    let Some(dialog) = parent_query
+        .get(button_row.parent())
+        .ok()
+        .and_then(|t| dialogs.get(t.parent()).ok())
+    else {
+        error!("Confirm dialog button isn't in a confirm dialog?");
+        return;
+    };
+
+    let confirmed = matches!(button, ConfirmDialogButton::Confirm);
+    close_confirm_dialog(&mut commands, &sounds, &mut writer, dialog, confirmed);
+}
+
+/// Backing out of a dialog is the same as picking Cancel.
+pub fn cancel_confirm_dialog(
+    mut commands: Commands,
+    dialogs: Query<(&ConfirmDialog, &GameMenuController), With<ActiveMenu>>,
+    input_query: Query<(&Player, &ActionState<PlayerInputAction>)>,
+    mut writer: MessageWriter<ConfirmDialogMessage>,
+    sounds: SoundManagerParam,
+) {
+    for (dialog, controller) in dialogs {
+        let deselected = input_query.iter().any(|(player, action_state)| {
+            controller.players.contains(player)
+                && action_state.just_pressed(&PlayerInputAction::Deselect)
+        });
+
+        if deselected {
+            close_confirm_dialog(&mut commands, &sounds, &mut writer, dialog, false);
+        }
+    }
+}
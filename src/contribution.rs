@@ -0,0 +1,241 @@
+//! Who pulled their weight, and the XP they get for it.
+//!
+//! Rather than handing out XP for every swing (which just rewards farming), each character's
+//! [`Contribution`] gets tallied up over the battle from the battle log: damage dealt, enemies finished
+//! off, healing, and objectives like opening chests. Once the battle's won it all gets turned into
+//! XP at once, and the resolution screen shows what everyone got out of it.
+//!
+//! The old flat XP per attack is still around behind [`XpModel::PerAttack`].
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{
+    battle::{BattleEndCondition, BattleResultResource, Enemy},
+    battle_log::BattleLogMessage,
+    combat::UnitHealthChangedEvent,
+    dungeon::DungeonState,
+    save_game::SaveFileKey,
+    unit::Unit,
+    unit_stats::{
+        UnitDerivedStats,
+        experience::{LevelUpMessage, UnitLevelManager},
+    },
+};
+
+const XP_PER_DAMAGE: f32 = 3.;
+const XP_PER_HEALING: f32 = 3.;
+const XP_PER_KILL: f32 = 30.;
+const XP_PER_OBJECTIVE: f32 = 20.;
+/// Everyone who's still up at the end gets this much just for winning
+const VICTORY_XP: f32 = 20.;
+/// Nobody gets more than a few levels out of one battle, however it went
+const MAX_BATTLE_XP: f32 = 300.;
+
+/// How XP gets handed out
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum XpModel {
+    /// Tally up what everyone did, and hand it all out when the battle's won
+    #[default]
+    EndOfBattle,
+    /// The same XP for every attack, straight away
+    PerAttack,
+}
+
+pub fn uses_per_attack_xp(model: Res<XpModel>) -> bool {
+    *model == XpModel::PerAttack
+}
+
+pub fn uses_end_of_battle_xp(model: Res<XpModel>) -> bool {
+    *model == XpModel::EndOfBattle
+}
+
+/// What one character did over the battle
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Contribution {
+    pub damage_dealt: u32,
+    pub kills: u32,
+    pub healing: u32,
+    pub objectives: u32,
+}
+
+impl Contribution {
+    /// What it's all worth, for someone who was `standing` when the battle was won
+    pub fn xp(&self, standing: bool) -> f32 {
+        let xp = self.damage_dealt as f32 * XP_PER_DAMAGE
+            + self.kills as f32 * XP_PER_KILL
+            + self.healing as f32 * XP_PER_HEALING
+            + self.objectives as f32 * XP_PER_OBJECTIVE
+            + if standing { VICTORY_XP } else { 0. };
+        xp.min(MAX_BATTLE_XP)
+    }
+}
+
+/// Everyone's [`Contribution`] so far, by the uid on their save
+#[derive(Resource, Debug, Default)]
+pub struct BattleContributions {
+    pub characters: HashMap<u32, Contribution>,
+    /// Whoever hit each unit last, to know who finished them off
+    last_hit_by: HashMap<Entity, Entity>,
+}
+
+/// What one character got out of the battle, for the resolution screen
+#[derive(Debug, Clone, PartialEq)]
+pub struct XpAward {
+    pub name: String,
+    pub xp: u32,
+    /// The level they ended up at
+    pub level: u32,
+    pub levels_gained: u32,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct BattleXpAwards(pub Vec<XpAward>);
+
+pub fn contribution_plugin(app: &mut App) {
+    app.init_resource::<XpModel>()
+        .init_resource::<BattleContributions>()
+        .init_resource::<BattleXpAwards>()
+        .add_systems(OnEnter(DungeonState::InBattle), reset_contributions)
+        .add_systems(
+            Update,
+            track_contributions
+                .run_if(uses_end_of_battle_xp)
+                .run_if(in_state(DungeonState::InBattle)),
+        );
+}
+
+fn reset_contributions(mut contributions: ResMut<BattleContributions>) {
+    *contributions = BattleContributions::default();
+}
+
+fn track_contributions(
+    mut contributions: ResMut<BattleContributions>,
+    mut battle_log: MessageReader<BattleLogMessage>,
+    mut health_changes: MessageReader<UnitHealthChangedEvent>,
+    characters: Query<&SaveFileKey>,
+    enemies: Query<&UnitDerivedStats, With<Enemy>>,
+) {
+    for message in battle_log.read() {
+        match message {
+            BattleLogMessage::SkillImpact {
+                attacker: Some(attacker),
+                defender,
+                health_change,
+                ..
+            } => {
+                if *health_change < 0 {
+                    contributions.last_hit_by.insert(*defender, *attacker);
+                }
+                let Ok(key) = characters.get(*attacker) else {
+                    continue;
+                };
+                let contribution = contributions.characters.entry(key.uid).or_default();
+                if *health_change < 0 {
+                    contribution.damage_dealt += health_change.unsigned_abs();
+                } else {
+                    contribution.healing += health_change.unsigned_abs();
+                }
+            }
+            BattleLogMessage::LootFound { unit, .. } => {
+                if let Ok(key) = characters.get(*unit) {
+                    contributions
+                        .characters
+                        .entry(key.uid)
+                        .or_default()
+                        .objectives += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for message in health_changes.read() {
+        if message.health_changed >= 0 || !enemies.get(message.unit).is_ok_and(|t| t.downed()) {
+            continue;
+        }
+        let Some(killer) = contributions.last_hit_by.remove(&message.unit) else {
+            continue;
+        };
+        if let Ok(key) = characters.get(killer) {
+            contributions.characters.entry(key.uid).or_default().kills += 1;
+        }
+    }
+}
+
+/// Turns everyone's contribution into XP, once the battle's been won. Has to go before the
+/// resolution screen is put up, so it can show the awards.
+pub fn distribute_battle_xp(
+    battle_result: Res<BattleResultResource>,
+    contributions: Res<BattleContributions>,
+    mut awards: ResMut<BattleXpAwards>,
+    mut units: Query<(
+        Entity,
+        &Unit,
+        &SaveFileKey,
+        &UnitDerivedStats,
+        &mut UnitLevelManager,
+    )>,
+    mut level_up_writer: MessageWriter<LevelUpMessage>,
+) {
+    awards.0.clear();
+    if battle_result.0.battle_condition != BattleEndCondition::Victory {
+        return;
+    }
+
+    let mut units = units.iter_mut().collect::<Vec<_>>();
+    units.sort_by_key(|t| t.2.uid);
+    for (entity, unit, key, stats, mut level_manager) in units {
+        let xp = contributions
+            .characters
+            .get(&key.uid)
+            .copied()
+            .unwrap_or_default()
+            .xp(!stats.downed());
+        let level_ups = level_manager.accept_experience(xp);
+        let levels_gained = level_ups.len() as u32;
+        for level_up in level_ups {
+            level_up_writer.write(LevelUpMessage { entity, level_up });
+        }
+
+        awards.0.push(XpAward {
+            name: unit.name.clone(),
+            xp: xp as u32,
+            level: level_manager.level() + levels_gained,
+            levels_gained,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_doing_more_is_worth_more() {
+        let idle = Contribution::default();
+        let fighter = Contribution {
+            damage_dealt: 10,
+            kills: 1,
+            ..Default::default()
+        };
+        let medic = Contribution {
+            healing: 10,
+            ..Default::default()
+        };
+
+        assert_eq!(idle.xp(false), 0.);
+        assert!(idle.xp(true) > idle.xp(false));
+        assert!(fighter.xp(true) > medic.xp(true));
+        assert!(medic.xp(true) > idle.xp(true));
+
+        let everything = Contribution {
+            damage_dealt: 500,
+            kills: 20,
+            healing: 500,
+            objectives: 5,
+        };
+        assert_eq!(everything.xp(true), MAX_BATTLE_XP);
+    }
+}
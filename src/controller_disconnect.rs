@@ -0,0 +1,256 @@
+//! Holding a player's spot when their controller disconnects mid run.
+//!
+//! The battle freezes (without opening the pause menu) and an overlay asks for the controller
+//! back. Plugging it back in picks up right where they left off, and pressing a button on any
+//! gamepad nobody is using hands that gamepad their controls instead. If they aren't coming
+//! back, anyone else can press Back to drop them out (see [`crate::drop_in`]).
+
+use std::collections::HashMap;
+
+use bevy::{input::gamepad::GamepadConnectionEvent, prelude::*};
+use leafwing_input_manager::prelude::{ActionState, InputMap};
+
+use crate::{
+    GameState,
+    assets::FontResource,
+    drop_in::DropOutMessage,
+    dungeon::DungeonState,
+    join_game_menu::{JoinedPlayers, PlayerController},
+    menu::ui_consts::{UI_MENU_BACKGROUND, UI_TEXT_COLOR},
+    pause_menu::BattlePauseState,
+    player::{Player, PlayerInputAction},
+    tr,
+};
+
+/// Players whose controller went away, along with the gamepad they were using
+#[derive(Resource, Default, Debug)]
+pub struct DisconnectedPlayers(pub HashMap<Player, Entity>);
+
+#[derive(Component)]
+pub struct DisconnectOverlay;
+
+pub fn controller_disconnect_plugin(app: &mut App) {
+    app.init_resource::<DisconnectedPlayers>()
+        .add_systems(
+            Update,
+            (
+                watch_gamepad_connections.run_if(in_state(DungeonState::InBattle)),
+                reassign_disconnected_players,
+                drop_out_disconnected_players,
+                update_disconnect_overlay.run_if(resource_changed::<DisconnectedPlayers>),
+            )
+                .chain()
+                .run_if(in_state(GameState::Dungeon)),
+        )
+        .add_systems(OnEnter(BattlePauseState::WaitingForController), freeze_time)
+        .add_systems(
+            OnExit(BattlePauseState::WaitingForController),
+            unfreeze_time,
+        )
+        .add_systems(OnExit(GameState::Dungeon), clear_disconnected_players);
+}
+
+/// Same as a pause, so animations and the phase timer hold still
+fn freeze_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn unfreeze_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn clear_disconnected_players(mut disconnected: ResMut<DisconnectedPlayers>) {
+    disconnected.0.clear();
+}
+
+/// Everyone playing on `gamepad`. More than one when they're taking turns with it (see
+/// [`crate::hotseat`]).
+fn gamepad_owners(joined_players: &JoinedPlayers, gamepad: Entity) -> Vec<Player> {
+    joined_players
+        .0
+        .iter()
+        .filter(|(_, t)| matches!(t.controller(), PlayerController::Gamepad(e) if e == gamepad))
+        .map(|(player, _)| *player)
+        .collect()
+}
+
+/// Freezes the battle when a player's gamepad disconnects, and lets them back in if it
+/// reconnects. If the pause menu's already open it stays that way.
+pub fn watch_gamepad_connections(
+    mut reader: MessageReader<GamepadConnectionEvent>,
+    joined_players: Res<JoinedPlayers>,
+    mut disconnected: ResMut<DisconnectedPlayers>,
+    pause_state: Res<State<BattlePauseState>>,
+    mut next_pause_state: ResMut<NextState<BattlePauseState>>,
+) {
+    for message in reader.read() {
+        for player in gamepad_owners(&joined_players, message.gamepad) {
+            if message.disconnected() {
+                warn!("{:?} lost their controller", player);
+                disconnected.0.insert(player, message.gamepad);
+                if *pause_state.get() == BattlePauseState::Running {
+                    next_pause_state.set(BattlePauseState::WaitingForController);
+                }
+            } else if disconnected.0.remove(&player).is_some() {
+                // Their InputMap still points at this gamepad, so there's nothing to rebind
+                info!("{:?} reconnected", player);
+            }
+        }
+    }
+}
+
+/// Hands the controls of a disconnected player to the first free gamepad that presses a button.
+/// Players that were sharing the lost gamepad keep sharing the new one.
+pub fn reassign_disconnected_players(
+    gamepads: Query<(Entity, &Gamepad)>,
+    mut joined_players: ResMut<JoinedPlayers>,
+    mut disconnected: ResMut<DisconnectedPlayers>,
+    mut input_maps: Query<&mut InputMap<PlayerInputAction>>,
+) {
+    if disconnected.0.is_empty() {
+        return;
+    }
+
+    for (gamepad_entity, gamepad) in gamepads {
+        if gamepad.get_just_pressed().next().is_none()
+            || !gamepad_owners(&joined_players, gamepad_entity).is_empty()
+        {
+            continue;
+        }
+
+        let Some((_, lost_gamepad)) = disconnected.0.iter().min_by_key(|(t, _)| t.id()) else {
+            return;
+        };
+        let lost_gamepad = *lost_gamepad;
+        let players = disconnected
+            .0
+            .iter()
+            .filter(|(_, t)| **t == lost_gamepad)
+            .map(|(player, _)| *player)
+            .collect::<Vec<_>>();
+
+        for player in players {
+            disconnected.0.remove(&player);
+
+            let Some(data) = joined_players.0.get_mut(&player) else {
+                continue;
+            };
+            data.set_controller(PlayerController::Gamepad(gamepad_entity));
+
+            match input_maps.get_mut(data.input_entity()) {
+                Ok(mut input_map) => {
+                    input_map.set_gamepad(gamepad_entity);
+                    info!("Reassigned {:?} to {:?}", player, gamepad_entity);
+                }
+                Err(e) => error!("No InputMap for {:?}: {:?}", player, e),
+            }
+        }
+    }
+}
+
+/// Anyone with a working controller can press Back to give up on the disconnected players
+pub fn drop_out_disconnected_players(
+    input_query: Query<(&Player, &ActionState<PlayerInputAction>)>,
+    mut disconnected: ResMut<DisconnectedPlayers>,
+    mut writer: MessageWriter<DropOutMessage>,
+) {
+    if disconnected.0.is_empty() {
+        return;
+    }
+
+    if !input_query.iter().any(|(player, action_state)| {
+        !disconnected.0.contains_key(player)
+            && action_state.just_pressed(&PlayerInputAction::Deselect)
+    }) {
+        return;
+    }
+
+    for (player, _) in disconnected.0.drain() {
+        writer.write(DropOutMessage { player });
+    }
+}
+
+/// Lists everyone who's missing their controller, and resumes once they're all sorted out
+pub fn update_disconnect_overlay(
+    mut commands: Commands,
+    fonts: Res<FontResource>,
+    disconnected: Res<DisconnectedPlayers>,
+    overlay: Query<Entity, With<DisconnectOverlay>>,
+    pause_state: Res<State<BattlePauseState>>,
+    mut next_pause_state: ResMut<NextState<BattlePauseState>>,
+) {
+    for e in overlay {
+        commands.entity(e).despawn();
+    }
+
+    if disconnected.0.is_empty() {
+        // Anyone who paused on purpose gets to unpause themselves
+        if *pause_state.get() == BattlePauseState::WaitingForController {
+            next_pause_state.set(BattlePauseState::Running);
+        }
+        return;
+    }
+
+    let font = TextFont {
+        font: fonts.pixelify_sans_regular.clone(),
+        font_size: 28.,
+        ..Default::default()
+    };
+
+    let mut players = disconnected.0.keys().copied().collect::<Vec<_>>();
+    players.sort_by_key(|t| t.id());
+
+    let mut lines = players
+        .iter()
+        .map(|player| {
+            commands
+                .spawn((
+                    Text(tr!("disconnect.player", player = player.id())),
+                    font.clone(),
+                    TextColor(UI_TEXT_COLOR),
+                ))
+                .id()
+        })
+        .collect::<Vec<_>>();
+    lines.push(
+        commands
+            .spawn((
+                Text(tr!("disconnect.drop_out")),
+                font.with_font_size(22.),
+                TextColor(UI_TEXT_COLOR),
+            ))
+            .id(),
+    );
+
+    let panel = commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: px(12),
+                padding: UiRect::all(px(20)),
+                border_radius: BorderRadius::all(percent(10)),
+                ..Default::default()
+            },
+            BackgroundColor(UI_MENU_BACKGROUND),
+        ))
+        .add_children(&lines)
+        .id();
+
+    commands
+        .spawn((
+            Name::new("DisconnectOverlay"),
+            Node {
+                position_type: PositionType::Absolute,
+                width: percent(100),
+                top: percent(5),
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            // Above the pause menu
+            GlobalZIndex(150),
+            DisconnectOverlay,
+            DespawnOnExit(GameState::Dungeon),
+        ))
+        .add_child(panel);
+}
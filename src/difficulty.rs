@@ -0,0 +1,205 @@
+//! How hard the dungeon pushes back.
+//!
+//! A new run picks a [`Difficulty`] on the join screen, next to the seed. Each one comes with a
+//! [`DungeonConfig`], which is what the dungeon actually reads: how many levels enemies pick up the
+//! deeper the party goes, how much bigger every room's enemy budget is, how likely rooms before
+//! the boss are to have their biome's hazard too, whether weapons wear out, and whether injuries
+//! carry from room to room. The difficulty goes in the run save, so a continued run keeps it.
+
+use bevy::prelude::*;
+
+use crate::{
+    GameState, assets::FontResource, menu::ui_consts::UI_TEXT_COLOR, run_save::PendingRunRestore,
+    tr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn config(&self) -> DungeonConfig {
+        let (
+            enemy_levels_per_room,
+            enemy_count_multiplier,
+            hazard_chance,
+            weapons_wear_out,
+            injuries_carry_over,
+        ) = match self {
+            Difficulty::Easy => (0., 0.75, 0., false, false),
+            Difficulty::Normal => (0., 1., 0., false, true),
+            Difficulty::Hard => (0.5, 1.5, 0.35, true, true),
+        };
+        DungeonConfig {
+            difficulty: *self,
+            enemy_levels_per_room,
+            enemy_count_multiplier,
+            hazard_chance,
+            weapons_wear_out,
+            injuries_carry_over,
+        }
+    }
+
+    fn next(&self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    fn label_key(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "difficulty.easy",
+            Difficulty::Normal => "difficulty.normal",
+            Difficulty::Hard => "difficulty.hard",
+        }
+    }
+}
+
+/// The knobs the dungeon gets generated and populated with
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct DungeonConfig {
+    pub difficulty: Difficulty,
+    /// Levels every enemy gets on top of their archetype's, for each room into the run
+    pub enemy_levels_per_room: f32,
+    /// Scales every room's enemy budget, along with how many enemies fit in a room
+    pub enemy_count_multiplier: f32,
+    /// How likely each room before the boss is to have its biome's hazard, from 0 to 1
+    pub hazard_chance: f32,
+    /// Whether attacking uses up a weapon's durability, so it needs repairing (see
+    /// [`durability`](crate::durability))
+    pub weapons_wear_out: bool,
+    /// Whether the party starts each room with the health they finished the last one on, and
+    /// anyone who went down comes back [injured](crate::injury). Otherwise everyone's back to full.
+    pub injuries_carry_over: bool,
+}
+
+impl Default for DungeonConfig {
+    fn default() -> Self {
+        Difficulty::default().config()
+    }
+}
+
+impl DungeonConfig {
+    /// Extra levels for the enemies of a room `depth` rooms into the run
+    pub fn bonus_levels(&self, depth: u32) -> u32 {
+        (self.enemy_levels_per_room * depth as f32).floor() as u32
+    }
+
+    pub fn scale_count(&self, count: u32) -> u32 {
+        (count as f32 * self.enemy_count_multiplier).round() as u32
+    }
+}
+
+/// The difficulty the next run will be played on
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct ChosenDifficulty(pub Difficulty);
+
+/// Cycles through the difficulties when clicked
+#[derive(Component)]
+pub struct DifficultyButton;
+
+/// Shows the [`ChosenDifficulty`]
+#[derive(Component)]
+pub struct DifficultyText;
+
+pub fn difficulty_plugin(app: &mut App) {
+    app.add_systems(OnEnter(GameState::JoinGame), choose_difficulty)
+        .add_systems(
+            Update,
+            display_difficulty
+                .run_if(resource_changed::<ChosenDifficulty>)
+                .run_if(in_state(GameState::JoinGame)),
+        )
+        .add_observer(cycle_difficulty);
+}
+
+/// A continued run is stuck with the difficulty it started on
+fn choose_difficulty(mut commands: Commands, pending_restore: Option<Res<PendingRunRestore>>) {
+    let difficulty = pending_restore.map(|t| t.0.difficulty).unwrap_or_default();
+    commands.insert_resource(ChosenDifficulty(difficulty));
+}
+
+/// The difficulty for the upcoming run, which can only be changed when it's a new run
+pub fn spawn_difficulty_widget(
+    commands: &mut Commands,
+    fonts: &FontResource,
+    editable: bool,
+) -> Entity {
+    let text = (
+        Text::default(),
+        TextFont {
+            font: fonts.pixelify_sans_regular.clone(),
+            ..Default::default()
+        },
+        TextColor(UI_TEXT_COLOR),
+        DifficultyText,
+    );
+
+    if !editable {
+        return commands.spawn(text).id();
+    }
+
+    commands
+        .spawn((
+            Button,
+            Node {
+                border: UiRect::all(px(2)),
+                padding: UiRect::horizontal(px(8)),
+                border_radius: BorderRadius::all(percent(20)),
+                ..default()
+            },
+            BorderColor::all(UI_TEXT_COLOR),
+            DifficultyButton,
+            children![text],
+        ))
+        .id()
+}
+
+fn cycle_difficulty(
+    mut click: On<Pointer<Click>>,
+    buttons: Query<(), With<DifficultyButton>>,
+    chosen: Option<ResMut<ChosenDifficulty>>,
+) {
+    if !buttons.contains(click.entity) {
+        return;
+    }
+    click.propagate(false);
+    let Some(mut chosen) = chosen else {
+        return;
+    };
+
+    chosen.0 = chosen.0.next();
+    info!("Playing on {:?}", chosen.0);
+}
+
+fn display_difficulty(
+    chosen: Res<ChosenDifficulty>,
+    mut texts: Query<&mut Text, With<DifficultyText>>,
+) {
+    for mut text in texts.iter_mut() {
+        text.0 = tr!("difficulty.label", difficulty = tr!(chosen.0.label_key()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_harder_dungeons_push_back_harder() {
+        let [easy, normal, hard] =
+            [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard].map(|t| t.config());
+
+        assert!(easy.scale_count(10) < normal.scale_count(10));
+        assert!(normal.scale_count(10) < hard.scale_count(10));
+        assert_eq!(normal.bonus_levels(3), 0);
+        assert!(hard.bonus_levels(3) > 0);
+        assert_eq!(hard.bonus_levels(0), 0);
+    }
+}
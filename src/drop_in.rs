@@ -0,0 +1,160 @@
+//! Players joining and leaving partway through a dungeon run.
+//!
+//! Anyone can drop in between rooms or during the Player Phase, with the same buttons as the join
+//! screen. They get a smaller version of the join screen in the corner to pick or create a
+//! character, and that character joins the party in the next room.
+//!
+//! Players drop out when their controller disconnects and nobody waits for it to come back (see
+//! [`controller_disconnect`](crate::controller_disconnect)). Their character becomes an orphaned
+//! unit (see [`RegisteredBattlePlayers::orphaned_units`]), which gets handed to whoever is left, or
+//! falls back to the AI if nobody is.
+
+use bevy::prelude::*;
+
+use crate::{
+    GameState,
+    battle::Ally,
+    battle_menu::BattleUiContainer,
+    battle_phase::{PhaseManager, PlayerEnemyPhase},
+    dungeon::DungeonState,
+    enemy::behaviors::{Behavior, EnemyAiBehavior},
+    grid_cursor::Cursor,
+    join_game_menu::{ControlledUiBlock, JoinedPlayers, PlayersUIContainer},
+    player::{Player, PlayerGameStates, RegisteredBattlePlayers},
+    save_game::SaveFileKey,
+    unit::{
+        Unit,
+        overlay::{OverlaysAction, OverlaysMessage},
+    },
+};
+
+/// Drops a player out of the run
+#[derive(Message, Debug, Clone, Copy)]
+pub struct DropOutMessage {
+    pub player: Player,
+}
+
+pub fn drop_in_plugin(app: &mut App) {
+    app.add_message::<DropOutMessage>()
+        .add_systems(OnEnter(GameState::Dungeon), spawn_drop_in_container)
+        .add_systems(
+            Update,
+            drop_out_players.run_if(in_state(GameState::Dungeon)),
+        );
+}
+
+/// Whether someone can start joining right now. Mid battle that's only during the Player Phase,
+/// so nobody pops in while the enemy is moving.
+pub fn can_drop_in(
+    dungeon_state: Option<Res<State<DungeonState>>>,
+    phase_manager: Option<Res<PhaseManager>>,
+) -> bool {
+    match dungeon_state.as_deref().map(State::get) {
+        None => false,
+        Some(DungeonState::InBattle) => {
+            phase_manager.is_some_and(|t| t.current_phase == PlayerEnemyPhase::Player)
+        }
+        Some(_) => true,
+    }
+}
+
+/// Where the join screens of players dropping in show up
+fn spawn_drop_in_container(mut commands: Commands) {
+    commands.spawn((
+        Name::new("DropInContainer"),
+        Node {
+            position_type: PositionType::Absolute,
+            top: percent(5),
+            width: percent(100),
+            height: percent(55),
+            flex_direction: FlexDirection::Row,
+            justify_content: JustifyContent::FlexEnd,
+            column_gap: percent(1),
+            padding: UiRect::right(percent(1)),
+            ..Default::default()
+        },
+        // Stay out of the way of the mouse player's cursor
+        Pickable::IGNORE,
+        GlobalZIndex(100),
+        PlayersUIContainer,
+        DespawnOnExit(GameState::Dungeon),
+    ));
+}
+
+/// Handles [`DropOutMessage`]s.
+///
+/// Their units get handed over right away, to the same players that will control them in the next
+/// room.
+#[allow(clippy::too_many_arguments)]
+pub fn drop_out_players(
+    mut commands: Commands,
+    mut reader: MessageReader<DropOutMessage>,
+    mut joined_players: ResMut<JoinedPlayers>,
+    mut registered_players: ResMut<RegisteredBattlePlayers>,
+    mut player_game_states: Option<ResMut<PlayerGameStates>>,
+    input_query: Query<&ControlledUiBlock>,
+    mut units: Query<(Entity, &mut Player, &SaveFileKey), With<Unit>>,
+    cursors: Query<(Entity, &Player), (With<Cursor>, Without<Unit>)>,
+    battle_ui: Query<(&Player, &ChildOf), (With<BattleUiContainer>, Without<Unit>)>,
+    mut overlay_writer: MessageWriter<OverlaysMessage>,
+) {
+    for DropOutMessage { player } in reader.read().copied() {
+        info!("Dropping out {:?}", player);
+        let Some(data) = joined_players.0.remove(&player) else {
+            continue;
+        };
+
+        // They might have been partway through dropping in themselves
+        if let Ok(ui_block) = input_query.get(data.input_entity()) {
+            commands.entity(ui_block.entity).try_despawn();
+        }
+        commands.entity(data.input_entity()).despawn();
+
+        if let Some(save) = registered_players.save_files.remove(&player) {
+            registered_players.orphaned_units.push(save);
+        }
+        registered_players.assign_filler_units();
+
+        let fallback = registered_players
+            .save_files
+            .keys()
+            .min_by_key(|t| t.id())
+            .copied();
+        for (unit, mut owner, save_file_key) in units.iter_mut() {
+            if *owner != player {
+                continue;
+            }
+
+            match registered_players.owner(save_file_key).or(fallback) {
+                Some(new_owner) => *owner = new_owner,
+                // Nobody left to hand it to, so the AI fights alongside until the room is over
+                None => {
+                    commands.entity(unit).remove::<Player>().insert((
+                        Ally {},
+                        EnemyAiBehavior {
+                            behavior: Behavior::Berserker,
+                        },
+                    ));
+                }
+            }
+        }
+
+        for (cursor, _) in cursors.iter().filter(|(_, t)| **t == player) {
+            commands.entity(cursor).despawn();
+        }
+
+        // Take their whole column of the battle UI with it
+        for (_, parent) in battle_ui.iter().filter(|(t, _)| **t == player) {
+            commands.entity(parent.parent()).despawn();
+        }
+
+        overlay_writer.write(OverlaysMessage {
+            player,
+            action: OverlaysAction::Despawn,
+        });
+
+        if let Some(states) = player_game_states.as_mut() {
+            states.player_state.remove(&player);
+        }
+    }
+}
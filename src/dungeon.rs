@@ -1,16 +1,44 @@
+//! A run through the dungeon, from the first room to the boss.
+//!
+//! The dungeon is a small map of rooms laid out in layers by depth. Every run starts in a single
+//! combat room and ends in a single boss room, and in between each layer has a few rooms to pick
+//! from, each one a [`RoomKind`]. Leaving a room over its exit bridge opens the
+//! [route selection](crate::route_select) screen, where the party picks where to go next out of
+//! the rooms the one they just left leads to.
+//!
+//! The map is rolled from the dungeon seed, so a continued run only needs to know which room it
+//! was in.
+
 use std::collections::HashMap;
 
 use bevy::prelude::*;
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use rand_seeder::Seeder;
 
 use crate::{
     GameState,
     animation::{TinytacticsAssets, animation_db::AnimationDB},
     assets::sprite_db::SpriteDB,
     battle::populate_room,
+    combat::skills::UnitSkills,
+    difficulty::DungeonConfig,
+    encounters::{plan_encounter, room_budget},
+    equipment::UnitEquipment,
+    gameplay_effects::{ActiveEffects, StatusTag},
+    injury::carried_injury,
     interactable::{Interactable, InteractionMenuLabel},
-    map_generation::{DungeonGenerationParams, MapData, RoomType, setup_map_data_from_params},
+    loot::{LootTableId, weighted_pick},
+    map_generation::{DungeonGenerationParams, MapData, RoomType, setup_room_map_data},
     player::RegisteredBattlePlayers,
+    rest_room::spawn_campfire,
+    run_save::PendingRunRestore,
+    save_game::SaveFileKey,
+    shop::spawn_vendor,
+    turn_events::{DEFAULT_REINFORCEMENT_TURN, DEFAULT_TURN_LIMIT, TurnEvent, TurnEventSchedule},
     unit::{UnitExecuteAction, UnitExecuteActionMessage},
+    unit_stats::{StatType, StatValue, UnitBaseStats, experience::UnitLevelManager},
+    weather::{CurrentWeather, RoomWeather, roll_weather},
 };
 
 #[derive(SubStates, Clone, PartialEq, Eq, Hash, Debug, Default, Reflect)]
@@ -18,10 +46,17 @@ use crate::{
 pub enum DungeonState {
     #[default]
     Initialize,
+    /// Waiting on the battle's assets, see [`crate::loading`]
+    Loading,
     LoadRoom,
     InBattle,
     LootRoom,
+    /// Nothing to fight, see [`crate::rest_room`]
+    RestRoom,
+    /// Nothing to fight either, see [`crate::shop`]
+    ShopRoom,
     UnloadRoom,
+    ChooseRoute,
 }
 
 #[derive(Component)]
@@ -30,14 +65,114 @@ pub struct DungeonEntity;
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Reflect)]
 pub struct RoomId(pub u32);
 
+/// What's waiting in a room of the dungeon map
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Reflect)]
+pub enum RoomKind {
+    Combat,
+    /// A tougher fight than usual
+    Elite,
+    /// A lighter fight, with only the good stuff in the chests
+    Treasure,
+    /// Nobody to fight, just a campfire and a chance to swap gear
+    Rest,
+    /// Nobody to fight, just somebody to spend gold with
+    Shop,
+    Boss,
+}
+
+impl RoomKind {
+    /// How likely each kind is to turn up in the middle of the dungeon, next to the rest
+    const WEIGHTS: [(u32, RoomKind); 5] = [
+        (4, RoomKind::Combat),
+        (2, RoomKind::Elite),
+        (1, RoomKind::Treasure),
+        (1, RoomKind::Rest),
+        (1, RoomKind::Shop),
+    ];
+
+    fn roll(rng: &mut Pcg64) -> RoomKind {
+        *weighted_pick(&Self::WEIGHTS, rng)
+    }
+
+    pub fn room_type(&self) -> RoomType {
+        match self {
+            RoomKind::Boss => RoomType::BossRoom,
+            _ => RoomType::Standard,
+        }
+    }
+
+    pub fn label_key(&self) -> &'static str {
+        match self {
+            RoomKind::Combat => "route.combat",
+            RoomKind::Elite => "route.elite",
+            RoomKind::Treasure => "route.treasure",
+            RoomKind::Rest => "route.rest",
+            RoomKind::Shop => "route.shop",
+            RoomKind::Boss => "route.boss",
+        }
+    }
+
+    pub fn description_key(&self) -> &'static str {
+        match self {
+            RoomKind::Combat => "route.combat_description",
+            RoomKind::Elite => "route.elite_description",
+            RoomKind::Treasure => "route.treasure_description",
+            RoomKind::Rest => "route.rest_description",
+            RoomKind::Shop => "route.shop_description",
+            RoomKind::Boss => "route.boss_description",
+        }
+    }
+}
+
+/// A room on the dungeon map, and the rooms it leads to
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteNode {
+    pub kind: RoomKind,
+    pub depth: u32,
+    pub next_rooms: Vec<RoomId>,
+}
+
 #[derive(Resource)]
 pub struct DungeonManager {
     pub current_room: RoomId,
     rooms: HashMap<RoomId, DungeonRoomData>,
 }
 
+impl DungeonManager {
+    pub fn route(&self, room_id: RoomId) -> Option<&RouteNode> {
+        self.rooms.get(&room_id).map(|t| &t.route)
+    }
+
+    /// Where the party can head once they leave the current room
+    pub fn next_rooms(&self) -> &[RoomId] {
+        self.route(self.current_room)
+            .map(|t| t.next_rooms.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// How many rooms into the run the current room is
+    pub fn current_depth(&self) -> u32 {
+        self.route(self.current_room)
+            .map(|t| t.depth)
+            .unwrap_or_default()
+    }
+
+    pub fn current_map(&self) -> Option<&MapData> {
+        self.room_map(self.current_room)
+    }
+
+    pub fn room_map(&self, room_id: RoomId) -> Option<&MapData> {
+        self.rooms.get(&room_id).map(|t| &t.map_data)
+    }
+}
+
 pub struct DungeonRoomData {
+    /// Who's waiting in the room gets rolled from this when it loads, once the party is known
+    seed: String,
+    route: RouteNode,
     map_data: MapData,
+    turn_events: TurnEventSchedule,
+    weather: Option<RoomWeather>,
 }
 
 #[derive(Component)]
@@ -46,38 +181,162 @@ pub struct DungeonRoomData {
 })]
 pub struct Teleporter {
     pub current_room: RoomId,
-    pub next_room: RoomId,
 }
 
-pub const DUNGEON_ROOM_COUNT: u32 = 3;
+/// How many rooms a run goes through, counting the boss
+pub const DUNGEON_DEPTH: u32 = 4;
+
+/// The most rooms there are to pick between at once
+const MAX_ROUTE_WIDTH: u32 = 3;
+
+/// How often a room gets a second way forward, on top of the one it always has
+const EXTRA_ROUTE_CHANCE: f32 = 0.5;
+
+/// Where room `index` out of `from` lines up in a layer of `to` rooms, so routes don't cross
+fn line_up(index: usize, from: usize, to: usize) -> usize {
+    if from <= 1 {
+        return 0;
+    }
+    (index * (to - 1) + (from - 1) / 2) / (from - 1)
+}
+
+/// Lays out the dungeon map, with rooms numbered from the start to the boss. Every room can be
+/// reached from the start, and every room but the boss leads somewhere.
+pub fn build_route_map(seed: &str) -> Vec<RouteNode> {
+    let mut rng: Pcg64 = Seeder::from(format!("{}-routes", seed)).into_rng();
+    let mut nodes: Vec<RouteNode> = Vec::new();
+    let mut layers = Vec::new();
+    for depth in 0..DUNGEON_DEPTH {
+        let (width, kind) = match depth {
+            0 => (1, Some(RoomKind::Combat)),
+            t if t == DUNGEON_DEPTH - 1 => (1, Some(RoomKind::Boss)),
+            _ => (rng.random_range(2..=MAX_ROUTE_WIDTH), None),
+        };
+
+        let mut layer = Vec::new();
+        for _ in 0..width {
+            layer.push(RoomId(nodes.len() as u32));
+            nodes.push(RouteNode {
+                kind: kind.unwrap_or_else(|| RoomKind::roll(&mut rng)),
+                depth,
+                next_rooms: Vec::new(),
+            });
+        }
+        layers.push(layer);
+    }
+
+    for pair in layers.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+        for (i, room) in from.iter().enumerate() {
+            let target = line_up(i, from.len(), to.len());
+            let next_rooms = &mut nodes[room.0 as usize].next_rooms;
+            next_rooms.push(to[target]);
+            if target + 1 < to.len() && rng.random::<f32>() < EXTRA_ROUTE_CHANCE {
+                next_rooms.push(to[target + 1]);
+            }
+        }
+
+        // Nobody gets left stranded
+        for (j, room) in to.iter().enumerate() {
+            if from
+                .iter()
+                .any(|t| nodes[t.0 as usize].next_rooms.contains(room))
+            {
+                continue;
+            }
+            let source = from[line_up(j, to.len(), from.len())];
+            let next_rooms = &mut nodes[source.0 as usize].next_rooms;
+            next_rooms.push(*room);
+            next_rooms.sort_by_key(|t| t.0);
+        }
+    }
+
+    nodes
+}
 
 pub fn init_dungeon_manager(
     mut commands: Commands,
     dungeon_params: Res<DungeonGenerationParams>,
+    config: Res<DungeonConfig>,
+    pending_restore: Option<Res<PendingRunRestore>>,
     mut next_state: ResMut<NextState<DungeonState>>,
 ) {
     let mut rooms = HashMap::new();
-    for room_id in 0..DUNGEON_ROOM_COUNT {
-        let map_data = setup_map_data_from_params(
-            &mut commands,
-            dungeon_params.options.seed.clone() + room_id.to_string().as_str(),
-            RoomType::Standard,
-        );
+    let route_map = build_route_map(&dungeon_params.options.seed);
+    for (room_id, route) in route_map.into_iter().enumerate() {
+        let seed = dungeon_params.options.seed.clone() + room_id.to_string().as_str();
+        let mut map_data = setup_room_map_data(&mut commands, seed.clone(), route.kind.room_type());
+        if route.kind == RoomKind::Treasure {
+            for chest in &mut map_data.chests {
+                chest.loot_table = LootTableId::Rare;
+            }
+        }
+
+        let turn_events = build_turn_events(&seed, &route, &map_data, &config);
+        let weather = roll_weather(&seed, route.kind);
 
-        rooms.insert(RoomId(room_id), DungeonRoomData { map_data });
+        rooms.insert(
+            RoomId(room_id as u32),
+            DungeonRoomData {
+                seed,
+                route,
+                map_data,
+                turn_events,
+                weather,
+            },
+        );
     }
 
     commands.insert_resource(DungeonManager {
-        current_room: RoomId(0),
+        current_room: RoomId(pending_restore.map(|t| t.0.room).unwrap_or(0)),
         rooms,
     });
 
-    next_state.set(DungeonState::LoadRoom);
+    next_state.set(DungeonState::Loading);
 }
 
+/// When the boss room's hazard kicks in
+const BOSS_HAZARD_TURN: u32 = 3;
+
+/// Rooms before the boss give the party a bit longer before their hazard kicks in
+const ROOM_HAZARD_TURN: u32 = 5;
+
+/// Every room is on the clock, reinforcements pour in over the exit bridge after the first room,
+/// and the boss room gets worse every turn in whatever way its biome does. Depending on the
+/// difficulty, other rooms might too.
+fn build_turn_events(
+    seed: &str,
+    route: &RouteNode,
+    map_data: &MapData,
+    config: &DungeonConfig,
+) -> TurnEventSchedule {
+    let mut schedule =
+        TurnEventSchedule::default().with_event(DEFAULT_TURN_LIMIT, TurnEvent::TurnLimit);
+
+    if route.depth > 0 && !map_data.bridge_end_locations.is_empty() {
+        schedule = schedule.with_event(
+            DEFAULT_REINFORCEMENT_TURN,
+            TurnEvent::Reinforcements {
+                positions: map_data.bridge_end_locations.clone(),
+            },
+        );
+    }
+
+    let mut rng: Pcg64 = Seeder::from(format!("{}-hazard", seed)).into_rng();
+    if route.kind == RoomKind::Boss {
+        schedule = schedule.with_modifier(map_data.biome.hazard(BOSS_HAZARD_TURN));
+    } else if rng.random::<f32>() < config.hazard_chance {
+        schedule = schedule.with_modifier(map_data.biome.hazard(ROOM_HAZARD_TURN));
+    }
+
+    schedule
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn load_room(
     mut commands: Commands,
     dungeon_manager: Res<DungeonManager>,
+    config: Res<DungeonConfig>,
     asset_server: Res<AssetServer>,
     registered_players: Res<RegisteredBattlePlayers>,
     tt_assets: Res<TinytacticsAssets>,
@@ -90,6 +349,46 @@ pub fn load_room(
         panic!("Dungeon is mis-initialized!!");
     };
 
+    // Rest rooms and shops skip the battle altogether
+    if matches!(room.route.kind, RoomKind::Rest | RoomKind::Shop) {
+        populate_room(
+            &mut commands,
+            &asset_server,
+            &room.map_data,
+            &registered_players,
+            &tt_assets,
+            &anim_db,
+            &sprite_db,
+            room_id,
+            &[],
+            false,
+        );
+        if room.route.kind == RoomKind::Shop {
+            spawn_vendor(&mut commands, &room.map_data);
+            next_state.set(DungeonState::ShopRoom);
+        } else {
+            spawn_campfire(&mut commands, &room.map_data);
+            next_state.set(DungeonState::RestRoom);
+        }
+        return;
+    }
+
+    let boss = room.route.kind == RoomKind::Boss || room.map_data.boss_spawn.is_some();
+    let budget = room_budget(
+        room.route.depth,
+        registered_players.party_size(),
+        room.route.kind,
+        &config,
+    );
+    let encounter = plan_encounter(
+        &room.seed,
+        budget,
+        &room.map_data,
+        boss,
+        &config,
+        room.route.depth,
+    );
+
     populate_room(
         &mut commands,
         &asset_server,
@@ -99,7 +398,11 @@ pub fn load_room(
         &anim_db,
         &sprite_db,
         room_id,
+        &encounter,
+        boss,
     );
+    commands.insert_resource(room.turn_events.clone());
+    commands.insert_resource(CurrentWeather(room.weather));
 
     next_state.set(DungeonState::InBattle);
 }
@@ -116,18 +419,76 @@ pub fn unload_room(
 
     // despawn map
 
-    // TODO: this needs to go through some other flow so it's not endless
-    next_state.set(DungeonState::LoadRoom)
+    next_state.set(DungeonState::ChooseRoute)
+}
+
+/// Whatever health the party has left when they leave a room is what they start the next one with,
+/// on the difficulties where [injuries carry over](DungeonConfig::injuries_carry_over). Otherwise
+/// everyone's patched back up to full.
+pub fn carry_over_party_health(
+    config: Res<DungeonConfig>,
+    party: Query<(&SaveFileKey, &UnitBaseStats, Option<&ActiveEffects>)>,
+    mut registered_players: ResMut<RegisteredBattlePlayers>,
+) {
+    for (key, base_stats, effects) in party.iter() {
+        let injury = carried_injury(
+            config.injuries_carry_over,
+            base_stats.stats.stat(StatType::Health).0,
+            effects.is_some_and(|t| t.has_status(StatusTag::Injured)),
+        );
+        registered_players.update_unit(key.uid, |save| {
+            let max_health = save.base_stats.stat(StatType::MaxHealth).0;
+            let health = injury.health.unwrap_or(max_health).min(max_health);
+            save.base_stats
+                .with_stat(StatType::Health, StatValue(health));
+            save.injured = injury.injured;
+        });
+    }
+}
+
+/// Whatever the party is holding when a battle's over is what they start the next room with, worn
+/// down, traded around and all
+pub fn carry_over_party_equipment(
+    party: Query<(&SaveFileKey, &UnitEquipment)>,
+    mut registered_players: ResMut<RegisteredBattlePlayers>,
+) {
+    for (key, equipment) in party.iter() {
+        let mut equipped_items = equipment
+            .equipped_items()
+            .map(|(_, item)| item.item_id())
+            .collect::<Vec<_>>();
+        equipped_items.sort_by_key(|t| t.0);
+        registered_players.update_unit(key.uid, |save| {
+            save.equipped_items = equipped_items.clone();
+        });
+    }
+}
+
+/// Levels, and whatever came with them, stick around for the rest of the run, so a unit can work
+/// their way up to a [promotion](crate::promotion) over a few rooms
+pub fn carry_over_party_progression(
+    party: Query<(&SaveFileKey, &UnitBaseStats, &UnitLevelManager, &UnitSkills)>,
+    mut registered_players: ResMut<RegisteredBattlePlayers>,
+) {
+    for (key, base_stats, level, skills) in party.iter() {
+        let mut learned_skills = skills.learned_skills.iter().copied().collect::<Vec<_>>();
+        learned_skills.sort_by_key(|t| t.0);
+        registered_players.update_unit(key.uid, |save| {
+            save.base_stats = base_stats.stats.clone();
+            save.level = level.level();
+            save.experience = level.experience();
+            save.learned_skills = learned_skills.clone();
+        });
+    }
 }
 
 /// Watches for [`UnitExecuteActionMessage`]s that use [`Teleporter`]s.
 ///
-/// When one is seen, updates the [`DungeonManager`] accordingly, and unloads the current room, and loads the next room.
+/// When one is seen, unloads the current room so the party can pick where to go next.
 pub fn handle_teleporter_interaction(
     mut reader: MessageReader<UnitExecuteActionMessage>,
     teleporter_query: Query<&Teleporter>,
     mut next_state: ResMut<NextState<DungeonState>>,
-    mut dungeon_manager: ResMut<DungeonManager>,
 ) {
     for message in reader.read() {
         let UnitExecuteAction::Interact {
@@ -137,11 +498,42 @@ pub fn handle_teleporter_interaction(
             continue;
         };
 
-        let Some(teleporter) = teleporter_query.get(interactable_entity).ok() else {
+        if !teleporter_query.contains(interactable_entity) {
             continue;
-        };
+        }
 
-        dungeon_manager.current_room = teleporter.next_room;
         next_state.set(DungeonState::UnloadRoom)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_room_is_on_some_route() {
+        for seed in 0..20 {
+            let nodes = build_route_map(&seed.to_string());
+            assert_eq!(nodes.first().map(|t| t.kind), Some(RoomKind::Combat));
+            assert_eq!(nodes.last().map(|t| t.kind), Some(RoomKind::Boss));
+
+            for (i, node) in nodes.iter().enumerate() {
+                if node.kind == RoomKind::Boss {
+                    assert!(node.next_rooms.is_empty());
+                } else {
+                    assert!(!node.next_rooms.is_empty());
+                }
+
+                for next in &node.next_rooms {
+                    assert_eq!(nodes[next.0 as usize].depth, node.depth + 1);
+                }
+
+                let reachable = i == 0
+                    || nodes
+                        .iter()
+                        .any(|t| t.next_rooms.contains(&RoomId(i as u32)));
+                assert!(reachable, "Room {} can't be reached", i);
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Weapons wearing out, on the difficulties where they do.
+//!
+//! With [`DungeonConfig::weapons_wear_out`] on, every attack made with a weapon uses it up a bit.
+//! Once it's out of uses it's broken, and does nothing for whoever's holding it: they're back to
+//! fighting bare handed, with the plain attack, until it gets fixed. How worn an item is rides
+//! along in its [`ItemId`] like its roll does, so it follows the item into the bag, through trades
+//! and into saves.
+//!
+//! Rest rooms fix everything up for free, and shops do it for a bit of gold.
+
+use bevy::prelude::*;
+
+use crate::{
+    battle_log::BattleLogMessage,
+    combat::AttackExecution,
+    difficulty::DungeonConfig,
+    dungeon::DungeonState,
+    equipment::{ItemId, UnitEquipment},
+    gameplay_effects::ActiveEffects,
+    inventory::PartyInventory,
+    player::RegisteredBattlePlayers,
+    unit_stats::StatsDirty,
+};
+
+/// What shops charge to give back each use
+pub const REPAIR_COST_PER_USE: u32 = 2;
+
+pub fn durability_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        wear_weapons.run_if(in_state(DungeonState::InBattle)),
+    );
+}
+
+/// Only attacking with the weapon wears it out, not casting a spell while holding it
+fn wear_weapons(
+    mut commands: Commands,
+    config: Res<DungeonConfig>,
+    executions: Query<&AttackExecution, Added<AttackExecution>>,
+    mut units: Query<(&mut UnitEquipment, &mut ActiveEffects)>,
+    mut battle_log: MessageWriter<BattleLogMessage>,
+) {
+    if !config.weapons_wear_out {
+        return;
+    }
+
+    for execution in executions {
+        let Some(attacker) = execution.attacker else {
+            continue;
+        };
+        let Ok((mut equipment, mut effects)) = units.get_mut(attacker) else {
+            continue;
+        };
+        if equipment
+            .weapon_data()
+            .is_none_or(|t| t.attack_skill != execution.skill.skill_id)
+        {
+            continue;
+        }
+
+        let Some((item_e, item)) = equipment.wear_weapon() else {
+            continue;
+        };
+        commands.entity(item_e).insert(item.clone());
+        if !item.is_broken() {
+            continue;
+        }
+
+        effects
+            .effects
+            .retain(|t| t.metadata.source != Some(item_e));
+        commands.entity(attacker).insert(StatsDirty);
+        battle_log.write(BattleLogMessage::WeaponBroke {
+            unit: attacker,
+            item: item.name().to_owned(),
+        });
+    }
+}
+
+/// Fixes up every item, returning how many uses that gave back
+pub fn repair_items(items: &mut [ItemId]) -> u32 {
+    let mut repaired = 0;
+    for item in items.iter_mut() {
+        repaired += item.wear();
+        *item = item.with_wear(0);
+    }
+    repaired
+}
+
+/// What a shop would charge to fix up everything the party has on, along with the bag
+pub fn repair_cost(players: &RegisteredBattlePlayers, inventory: &PartyInventory) -> u32 {
+    let worn = players
+        .units()
+        .flat_map(|(_, t)| &t.equipped_items)
+        .chain(&inventory.items)
+        .map(|t| t.wear())
+        .sum::<u32>();
+    worn * REPAIR_COST_PER_USE
+}
+
+/// Fixes up everything the party has on, along with the bag
+pub fn repair_party_gear(players: &mut RegisteredBattlePlayers, inventory: &mut PartyInventory) {
+    let uids = players
+        .units()
+        .map(|(_, t)| t.save_file_key.uid)
+        .collect::<Vec<_>>();
+    for uid in uids {
+        players.update_unit(uid, |save| {
+            repair_items(&mut save.equipped_items);
+        });
+    }
+    repair_items(&mut inventory.items);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equipment::build_item_db;
+
+    #[test]
+    fn test_weapons_break_and_get_fixed() {
+        let item_db = build_item_db().expect("Item DB should parse");
+        let mut axe = item_db
+            .equippable_item(&ItemId(1))
+            .expect("The axe should be in the catalogue");
+        let durability = axe.uses_left().expect("The axe should be able to wear out");
+        assert!(!axe.is_broken());
+
+        let mut equipment = UnitEquipment::default();
+        equipment.equip_item(axe, Entity::PLACEHOLDER);
+        for _ in 0..durability {
+            assert!(equipment.weapon_data().is_some());
+            axe = equipment.wear_weapon().expect("The axe should wear").1;
+        }
+        assert!(axe.is_broken());
+        assert!(equipment.weapon_data().is_none());
+        assert!(equipment.wear_weapon().is_none());
+
+        let mut items = [axe.item_id(), ItemId(3)];
+        assert_eq!(repair_items(&mut items), durability);
+        assert_eq!(items, [ItemId(1), ItemId(3)]);
+    }
+}
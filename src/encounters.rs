@@ -0,0 +1,392 @@
+//! Who's waiting for the party in each room.
+//!
+//! Every room gets a difficulty budget, which grows the deeper into the dungeon the room is and
+//! the bigger the party is, and depends on the room's [`RoomKind`]. It gets spent on
+//! [`EnemyArchetype`]s that fit the room's [`Biome`](crate::biome::Biome), and whoever gets bought
+//! is spread out over the room, keeping their distance from where the party starts and from each
+//! other. Spawns that a
+//! [`RoomTemplate`](crate::room_templates::RoomTemplate) put down by hand get filled first.
+//!
+//! The run's [`DungeonConfig`] scales the budget and how many enemies fit in a room, and hands
+//! out extra levels the deeper the room is.
+//!
+//! Everything is rolled from the room's seed and the party size, so a continued run meets the same
+//! enemies in the same places.
+
+use std::collections::BTreeSet;
+
+use bevy::prelude::*;
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use rand_seeder::Seeder;
+
+use crate::{
+    animation::{TinytacticsAssets, animation_db::AnimationDB},
+    biome::EnemyKind,
+    difficulty::DungeonConfig,
+    dungeon::RoomKind,
+    enemy::behaviors::{Behavior, EnemyAiBehavior},
+    grid::{GridPosition, manhattan_distance},
+    loot::{EnemyLoot, LootTableId},
+    map_generation::{MapData, reachable_tiles},
+    unit::{ENEMY_TEAM, jobs::UnitJob, spawn_enemy},
+    unit_stats::{StatContainer, StatType, StatValue, UnitBaseStats, UnitDerivedStats},
+};
+
+/// A kind of enemy a room can spend its budget on
+#[derive(Debug, Clone)]
+pub struct EnemyArchetype {
+    pub kind: EnemyKind,
+    pub job: UnitJob,
+    pub level: u32,
+    pub behavior: Behavior,
+    pub cost: u32,
+    pub loot: EnemyLoot,
+}
+
+/// Most enemies only sometimes have anything worth taking
+const COMMON_LOOT: EnemyLoot = EnemyLoot {
+    table: LootTableId::Common,
+    drop_chance: 0.3,
+};
+
+pub const ENEMY_ARCHETYPES: &[EnemyArchetype] = &[
+    EnemyArchetype {
+        kind: EnemyKind::Fighter,
+        job: UnitJob::Mercenary,
+        level: 1,
+        behavior: Behavior::Berserker,
+        cost: 2,
+        loot: COMMON_LOOT,
+    },
+    EnemyArchetype {
+        kind: EnemyKind::Fighter,
+        job: UnitJob::Archer,
+        level: 2,
+        behavior: Behavior::Trapper,
+        cost: 3,
+        loot: COMMON_LOOT,
+    },
+    EnemyArchetype {
+        kind: EnemyKind::Fighter,
+        job: UnitJob::Mercenary,
+        level: 3,
+        behavior: Behavior::Berserker,
+        cost: 5,
+        loot: EnemyLoot::guaranteed(LootTableId::Common),
+    },
+    EnemyArchetype {
+        kind: EnemyKind::Cleric,
+        job: UnitJob::Knight,
+        level: 1,
+        behavior: Behavior::Trapper,
+        cost: 2,
+        loot: COMMON_LOOT,
+    },
+    EnemyArchetype {
+        kind: EnemyKind::Cleric,
+        job: UnitJob::Knight,
+        level: 3,
+        behavior: Behavior::Berserker,
+        cost: 4,
+        loot: EnemyLoot::guaranteed(LootTableId::Common),
+    },
+    EnemyArchetype {
+        kind: EnemyKind::Mage,
+        job: UnitJob::Mage,
+        level: 1,
+        behavior: Behavior::Trapper,
+        cost: 3,
+        loot: COMMON_LOOT,
+    },
+    EnemyArchetype {
+        kind: EnemyKind::Mage,
+        job: UnitJob::Mage,
+        level: 3,
+        behavior: Behavior::Berserker,
+        cost: 5,
+        loot: EnemyLoot::guaranteed(LootTableId::Common),
+    },
+];
+
+impl EnemyArchetype {
+    /// The job's stats, plus what the job grows on average for each level past the first
+    pub fn stats_at(&self, level: u32) -> StatContainer {
+        let mut stats = self.job.default_stats();
+        let levels = level.saturating_sub(1) as f32;
+        for (stat, rate) in self.job.growth_rates() {
+            let value = stats.stat(stat).0 + (rate.expected() * levels).round();
+            stats.with_stat(stat, StatValue(value));
+        }
+        let max_health = stats.stat(StatType::MaxHealth);
+        stats.with_stat(StatType::Health, max_health);
+        stats
+    }
+}
+
+/// What every room gets to spend, no matter how deep or how big the party
+const BASE_BUDGET: u32 = 4;
+const BUDGET_PER_ROOM: u32 = 2;
+/// Solo players get the base budget, everyone after that makes the room a bit harder
+const BUDGET_PER_EXTRA_UNIT: u32 = 2;
+/// On top of what an elite room would have had otherwise
+const ELITE_BUDGET_BONUS: u32 = 4;
+/// Past this the room is more traffic jam than fight, before the difficulty scales it
+const MAX_ENEMIES: u32 = 6;
+/// How close (in tiles walked) an enemy can start to any of the party's starting tiles
+const MIN_PLAYER_DISTANCE: u32 = 5;
+/// How close two enemies can start to each other
+const MIN_ENEMY_SPACING: u32 = 2;
+
+/// What a room `depth` rooms into the run gets to spend
+pub fn room_budget(depth: u32, party_size: usize, kind: RoomKind, config: &DungeonConfig) -> u32 {
+    let budget = config.scale_count(
+        BASE_BUDGET
+            + BUDGET_PER_ROOM * depth
+            + BUDGET_PER_EXTRA_UNIT * party_size.saturating_sub(1) as u32,
+    );
+    match kind {
+        RoomKind::Elite => budget + ELITE_BUDGET_BONUS,
+        RoomKind::Treasure => budget / 2,
+        // Nobody fights in a rest room or a shop
+        RoomKind::Rest | RoomKind::Shop => 0,
+        RoomKind::Combat | RoomKind::Boss => budget,
+    }
+}
+
+/// An enemy the room bought, and where it starts
+#[derive(Debug, Clone)]
+pub struct PlannedEnemy {
+    pub archetype: &'static EnemyArchetype,
+    /// The archetype's level, plus whatever the difficulty adds this deep in
+    pub level: u32,
+    pub position: GridPosition,
+}
+
+/// Spends `budget` and finds everyone a place to stand. The first enemy is the room's leader,
+/// who's the priciest one the room can afford when `boss` is set.
+pub fn plan_encounter(
+    seed: &str,
+    mut budget: u32,
+    map_data: &MapData,
+    boss: bool,
+    config: &DungeonConfig,
+    depth: u32,
+) -> Vec<PlannedEnemy> {
+    let mut rng: Pcg64 = Seeder::from(format!("{}-enemies", seed)).into_rng();
+    let spawn_table = map_data.biome.enemy_spawn_table();
+    let options = ENEMY_ARCHETYPES
+        .iter()
+        .filter(|t| spawn_table.contains(&t.kind))
+        .collect::<Vec<_>>();
+
+    let max_enemies = config.scale_count(MAX_ENEMIES).max(1) as usize;
+    let mut archetypes = Vec::new();
+    while archetypes.len() < max_enemies {
+        let affordable = options
+            .iter()
+            .filter(|t| t.cost <= budget)
+            .copied()
+            .collect::<Vec<_>>();
+        let picked = if boss && archetypes.is_empty() {
+            affordable.iter().max_by_key(|t| t.cost).copied()
+        } else {
+            affordable.choose(&mut rng).copied()
+        };
+        let Some(picked) = picked else {
+            break;
+        };
+        budget -= picked.cost;
+        archetypes.push(picked);
+    }
+
+    // A room always has somebody in it, even if it can't really afford them
+    if archetypes.is_empty()
+        && let Some(cheapest) = options.iter().min_by_key(|t| t.cost)
+    {
+        archetypes.push(cheapest);
+    }
+
+    let bonus_levels = config.bonus_levels(depth);
+    let positions = place_enemies(&mut rng, map_data, archetypes.len());
+    archetypes
+        .into_iter()
+        .zip(positions)
+        .map(|(archetype, position)| PlannedEnemy {
+            archetype,
+            level: archetype.level + bonus_levels,
+            position,
+        })
+        .collect()
+}
+
+/// Up to `count` places for enemies to start, in the order they were bought. The boss spawn or a
+/// patrol route goes to the leader, then the hand placed spawns get used up, and anyone left over
+/// gets an open tile far enough away from the party and the other enemies.
+fn place_enemies(rng: &mut Pcg64, map_data: &MapData, count: usize) -> Vec<GridPosition> {
+    let leader_spawn = map_data.boss_spawn.or_else(|| {
+        map_data
+            .patrol_routes
+            .first()
+            .and_then(|t| t.first().copied())
+    });
+    let mut positions = leader_spawn
+        .into_iter()
+        .chain(map_data.enemy_spawns.iter().copied())
+        .take(count)
+        .collect::<Vec<_>>();
+
+    let blocked = map_data
+        .player_start_locations
+        .iter()
+        .chain(&map_data.bridge_end_locations)
+        .chain(map_data.chests.iter().map(|t| &t.position))
+        .chain(&map_data.ally_spawns)
+        .copied()
+        .collect::<BTreeSet<_>>();
+    // Sorted, so the same seed always picks the same tiles
+    let mut candidates = reachable_tiles(
+        map_data.game_size(),
+        &map_data.obstacles,
+        &map_data.player_start_locations,
+    )
+    .into_iter()
+    .filter(|t| !blocked.contains(t))
+    .filter(|t| {
+        map_data
+            .player_start_locations
+            .iter()
+            .all(|start| manhattan_distance(start, t) >= MIN_PLAYER_DISTANCE)
+    })
+    .collect::<Vec<_>>();
+
+    while positions.len() < count {
+        candidates.retain(|t| {
+            positions
+                .iter()
+                .all(|placed| manhattan_distance(placed, t) >= MIN_ENEMY_SPACING)
+        });
+        let Some(position) = candidates.choose(rng).copied() else {
+            warn!(
+                "Ran out of room for enemies, {} won't show up",
+                count - positions.len()
+            );
+            break;
+        };
+        positions.push(position);
+    }
+    positions
+}
+
+/// Spawns the enemy as its archetype's job, level and behavior
+pub fn spawn_planned_enemy(
+    commands: &mut Commands,
+    name: String,
+    tt_assets: &TinytacticsAssets,
+    anim_db: &AnimationDB,
+    planned: &PlannedEnemy,
+) -> Entity {
+    let archetype = planned.archetype;
+    let enemy = spawn_enemy(
+        commands,
+        name,
+        tt_assets,
+        anim_db,
+        planned.position,
+        archetype.kind.spritesheet(tt_assets),
+        archetype.job.base_unit_skills(),
+        ENEMY_TEAM,
+    );
+
+    let stats = archetype.stats_at(planned.level);
+    commands.entity(enemy).insert((
+        UnitBaseStats {
+            stats: stats.clone(),
+        },
+        UnitDerivedStats { stats },
+        EnemyAiBehavior {
+            behavior: archetype.behavior,
+        },
+        archetype.job.action_economy(),
+        archetype.loot,
+    ));
+    enemy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_generation::{RoomType, setup_map_data_from_params};
+
+    #[test]
+    fn test_enemies_grow_like_their_job() {
+        let archetype = |job| EnemyArchetype {
+            job,
+            ..ENEMY_ARCHETYPES[0].clone()
+        };
+        let stat_gain = |job: UnitJob, stat| {
+            archetype(job.clone()).stats_at(9).stat(stat).0 - job.default_stats().stat(stat).0
+        };
+
+        assert!(
+            stat_gain(UnitJob::Knight, StatType::Defense)
+                > stat_gain(UnitJob::Mage, StatType::Defense)
+        );
+        assert!(
+            stat_gain(UnitJob::Mage, StatType::Magic) > stat_gain(UnitJob::Knight, StatType::Magic)
+        );
+        assert!(
+            stat_gain(UnitJob::Archer, StatType::Skill)
+                > stat_gain(UnitJob::Mercenary, StatType::Skill)
+        );
+
+        let stats = archetype(UnitJob::Knight).stats_at(5);
+        assert_eq!(
+            stats.stat(StatType::Health),
+            stats.stat(StatType::MaxHealth)
+        );
+        assert_eq!(
+            archetype(UnitJob::Knight).stats_at(1),
+            UnitJob::Knight.default_stats()
+        );
+    }
+
+    #[test]
+    fn test_budget_grows_with_depth_and_party() {
+        let config = DungeonConfig::default();
+        let budget = |depth, party_size| room_budget(depth, party_size, RoomKind::Combat, &config);
+        assert!(budget(1, 1) > budget(0, 1));
+        assert!(budget(0, 4) > budget(0, 1));
+        assert_eq!(budget(0, 0), budget(0, 1));
+        assert!(room_budget(0, 1, RoomKind::Elite, &config) > budget(0, 1));
+    }
+
+    #[test]
+    fn test_enemies_keep_their_distance() {
+        let mut world = World::new();
+        let mut commands = world.commands();
+        let config = DungeonConfig::default();
+        for seed in 0..10 {
+            let seed = seed.to_string();
+            let map_data =
+                setup_map_data_from_params(&mut commands, seed.clone(), RoomType::Standard);
+            let budget = room_budget(2, 4, RoomKind::Combat, &config);
+            let encounter = plan_encounter(&seed, budget, &map_data, false, &config, 2);
+            assert!(!encounter.is_empty());
+
+            let spent = encounter.iter().map(|t| t.archetype.cost).sum::<u32>();
+            assert!(spent <= budget);
+
+            let patrol_start = map_data.patrol_routes.first().and_then(|t| t.first());
+            for (i, enemy) in encounter.iter().enumerate() {
+                assert!(!map_data.obstacles.contains_key(&enemy.position));
+                if i == 0 && patrol_start == Some(&enemy.position) {
+                    continue;
+                }
+                for start in &map_data.player_start_locations {
+                    assert!(manhattan_distance(start, &enemy.position) >= MIN_PLAYER_DISTANCE);
+                }
+            }
+        }
+    }
+}
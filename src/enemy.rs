@@ -1,24 +1,27 @@
 //! A Module for tracking some basic Enemy behaviors!
 
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 
 use bevy::prelude::*;
 
 use crate::{
-    battle::Enemy,
-    battle_phase::{PhaseMessage, PhaseMessageType, PlayerEnemyPhase, UnitPhaseResources},
+    battle_phase::{
+        PhaseMessage, PhaseMessageType, PhaseSystem, PlayerEnemyPhase, TurnQueue,
+        UnitPhaseResources, has_turn,
+    },
     combat::{
         AttackIntent,
         skills::{ATTACK_SKILL_ID, Targeting},
     },
-    enemy::behaviors::EnemyAiBehavior,
+    enemy::behaviors::{EnemyAiBehavior, PatrolRoute},
+    gameplay_effects::ActiveEffects,
     grid::{
         GridManager, GridManagerResource, GridPosition, GridPositionChangeResult,
         manhattan_distance,
     },
     unit::{
         CombatActionMarker, DIRECTION_VECS, MovementRequest, Unit, UnitActionCompletedMessage,
-        UnitExecuteAction, UnitExecuteActionMessage, build_attack_space_options,
+        UnitExecuteAction, UnitExecuteActionMessage, ValidMove, build_attack_space_options,
         get_valid_moves_for_unit,
     },
     unit_stats::UnitDerivedStats,
@@ -27,6 +30,10 @@ use crate::{
 #[derive(Component)]
 pub struct ActiveEnemy {}
 
+/// The enemy guarding the final room. Gets introduced with a banner when the battle starts.
+#[derive(Component)]
+pub struct Boss;
+
 #[derive(Resource)]
 pub struct EnemyTurnConductorResource(pub EnemyTurnConductor);
 pub struct EnemyTurnConductor {
@@ -39,22 +46,26 @@ pub fn init_enemy_ai_system(mut commands: Commands) {
     }));
 }
 
-pub fn begin_enemy_phase(
+/// Queues up the AI controlled units of `T` when their phase begins.
+///
+/// Despite the name, this also drives the Ally phase.
+pub fn begin_enemy_phase<T: PhaseSystem<PlayerEnemyPhase>>(
     mut commands: Commands,
     mut message_reader: MessageReader<PhaseMessage>,
     mut conductor: ResMut<EnemyTurnConductorResource>,
-    enemy_units: Query<(Entity, &Unit, &UnitDerivedStats), With<Enemy>>,
+    turn_queue: Option<Res<TurnQueue>>,
+    enemy_units: Query<(Entity, &Unit, &UnitDerivedStats), With<T::Marker>>,
 ) {
     for message in message_reader.read() {
         let PhaseMessageType::PhaseBegin(phase) = message.0;
-        if phase == PlayerEnemyPhase::Enemy {
+        if phase == T::OWNED_PHASE {
             for (e, unit, stats) in enemy_units.iter() {
                 // Clean up any potential stale references to Enemy Behaviors
                 commands
                     .entity(e)
                     .remove::<(ActiveEnemy, PlannedEnemyAction, EnemyActionInProgress)>();
 
-                if stats.downed() {
+                if stats.downed() || !has_turn(turn_queue.as_deref(), e) {
                     continue;
                 }
 
@@ -146,7 +157,7 @@ fn target_enemy_in_range(
 pub fn plan_enemy_action(
     grid_manager: Res<GridManagerResource>,
     mut commands: Commands,
-    query: Query<
+    mut query: Query<
         (
             Entity,
             &Unit,
@@ -154,6 +165,8 @@ pub fn plan_enemy_action(
             &UnitPhaseResources,
             &EnemyAiBehavior,
             &GridPosition,
+            Option<&mut PatrolRoute>,
+            Option<&ActiveEffects>,
         ),
         (With<ActiveEnemy>, Without<PlannedEnemyAction>),
     >,
@@ -163,7 +176,9 @@ pub fn plan_enemy_action(
     unit_query_with_position: Query<(Entity, &Unit, &UnitDerivedStats, &GridPosition)>,
 ) {
     // There should only be at most one ActiveEnemy but :shrug:
-    for (enemy, enemy_unit, stats, resources, behavior, enemy_pos) in query {
+    for (enemy, enemy_unit, stats, resources, behavior, enemy_pos, mut patrol_route, effects) in
+        query.iter_mut()
+    {
         if stats.downed() {
             commands.entity(enemy).remove::<ActiveEnemy>();
         }
@@ -177,12 +192,33 @@ pub fn plan_enemy_action(
                 }]),
             },
             behaviors::Behavior::Wanderer => {
+                // Patrolling Wanderers give up their route once someone gets too close.
+                let sight = |range: u32| effects.map_or(range, |t| t.sight_range(range));
+                if let Some(route) = patrol_route.as_deref()
+                    && find_targets_by_distance(enemy_unit, *enemy_pos, unit_query_with_position)
+                        .iter()
+                        .any(|(_, _, _, dist)| *dist <= sight(route.aggro_range))
+                {
+                    info!(
+                        "{:?} spotted a target, switching to {:?}",
+                        enemy_unit.name, route.aggro_behavior
+                    );
+                    // Replan next frame with the aggressive behavior.
+                    commands
+                        .entity(enemy)
+                        .remove::<PatrolRoute>()
+                        .insert(EnemyAiBehavior {
+                            behavior: route.aggro_behavior,
+                        });
+                    continue;
+                }
+
                 let valid_moves = get_valid_moves_for_unit(
                     &grid_manager.grid_manager,
                     MovementRequest {
                         origin: *enemy_pos,
                         unit: enemy_unit.clone(),
-                        movement_points_available: resources.movement_points_left_in_phase,
+                        movement_points_available: resources.movement_available(),
                     },
                     unit_query,
                 );
@@ -191,9 +227,14 @@ pub fn plan_enemy_action(
                     action: UnitExecuteAction::Wait,
                 }]);
 
-                if let Some((_, the_move)) = valid_moves.iter().next() {
+                let the_move = match patrol_route.as_deref_mut() {
+                    Some(route) => route.plan_move(enemy_pos, &valid_moves),
+                    None => valid_moves.values().next().cloned(),
+                };
+
+                if let Some(the_move) = the_move {
                     actions.push_front(PlannedAction {
-                        action: UnitExecuteAction::Move(the_move.clone()),
+                        action: UnitExecuteAction::Move(the_move),
                     });
                 }
 
@@ -237,7 +278,7 @@ pub fn plan_enemy_action(
                             MovementRequest {
                                 origin: *enemy_pos,
                                 unit: enemy_unit.clone(),
-                                movement_points_available: resources.movement_points_left_in_phase,
+                                movement_points_available: resources.movement_available(),
                             },
                             unit_query,
                         );
@@ -312,7 +353,7 @@ pub fn plan_enemy_action(
                     MovementRequest {
                         origin: *enemy_pos,
                         unit: enemy_unit.clone(),
-                        movement_points_available: resources.movement_points_left_in_phase,
+                        movement_points_available: resources.movement_available(),
                     },
                     unit_query,
                 );
@@ -448,6 +489,12 @@ pub fn resolve_enemy_action(
                 crate::unit::UnitAction::Interact => {
                     error!("Enemy {:?} interacted with something?", e);
                 }
+                crate::unit::UnitAction::UseItem => {
+                    error!("Enemy {:?} dug into the party's bag?", e);
+                }
+                crate::unit::UnitAction::Trade => {
+                    error!("Enemy {:?} traded gear?", e);
+                }
             }
         }
     }
@@ -464,11 +511,12 @@ pub mod behaviors {
     /// Would be interesting to link this to other behaviors.
     /// IE, you might want a Berserker that goes for the Weakest unit, or a Berserker that goes for
     /// the strongest unit
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy)]
     pub enum Behavior {
         /// The Pacifist simply waits
         Pacifist,
-        /// This enemy just moves around 'randomly'
+        /// This enemy just moves around 'randomly', or walks
+        /// it's `PatrolRoute` if it has one.
         Wanderer,
         /// This enemy lies in wait for a unit to enter it's "danger zone"
         /// Then this unit moves to attack it!
@@ -476,4 +524,62 @@ pub mod behaviors {
         /// This enemy hunts the closest unit not on it's team
         Berserker,
     }
+
+    /// A loop of waypoints for a Wanderer to walk until a unit
+    /// not on it's team comes within `aggro_range`.
+    #[derive(Component, Debug, Clone)]
+    pub struct PatrolRoute {
+        pub waypoints: Vec<GridPosition>,
+        pub next_waypoint: usize,
+        pub aggro_range: u32,
+        /// What the Wanderer becomes once it spots a target
+        pub aggro_behavior: Behavior,
+    }
+
+    impl PatrolRoute {
+        pub fn new(waypoints: Vec<GridPosition>) -> Self {
+            Self {
+                waypoints,
+                next_waypoint: 0,
+                aggro_range: 4,
+                aggro_behavior: Behavior::Berserker,
+            }
+        }
+
+        fn current_waypoint(&self) -> Option<GridPosition> {
+            self.waypoints.get(self.next_waypoint).copied()
+        }
+
+        fn advance(&mut self) {
+            if !self.waypoints.is_empty() {
+                self.next_waypoint = (self.next_waypoint + 1) % self.waypoints.len();
+            }
+        }
+
+        /// Pick the valid move that gets us closest to the next waypoint,
+        /// moving on to the following waypoint once we've arrived.
+        pub fn plan_move(
+            &mut self,
+            current_pos: &GridPosition,
+            valid_moves: &HashMap<GridPosition, ValidMove>,
+        ) -> Option<ValidMove> {
+            if self.current_waypoint() == Some(*current_pos) {
+                self.advance();
+            }
+
+            let waypoint = self.current_waypoint()?;
+            let (target, the_move) = valid_moves
+                .iter()
+                .filter(|(pos, _)| {
+                    manhattan_distance(pos, &waypoint) < manhattan_distance(current_pos, &waypoint)
+                })
+                .min_by_key(|(pos, _)| manhattan_distance(pos, &waypoint))?;
+
+            if *target == waypoint {
+                self.advance();
+            }
+
+            Some(the_move.clone())
+        }
+    }
 }
@@ -1,29 +1,55 @@
-use std::collections::HashMap;
+//! What units wear, and what the party can use up.
+//!
+//! Every item lives in the [`ItemDB`], which gets loaded from the catalogue in
+//! `assets/items/items.ron` and checked against the sprites and skills the game knows about, so
+//! new gear doesn't need any code.
+
+use std::collections::{HashMap, HashSet};
 
 use anyhow::Context;
 use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
 
 use crate::{
     animation::{
         AnimationFollower,
         animation_db::{
-            AnimatedSpriteId, AnimationDB, registered_sprite_ids::TT_WEAPON_ANIMATED_SPRITE_ID,
+            AnimatedSpriteId, AnimationDB,
+            registered_sprite_ids::{
+                TT_WEAPON_ANIMATED_SPRITE_ID, build_animated_sprite_to_atlas_layout,
+            },
         },
     },
-    assets::sprite_db::{SpriteDB, SpriteId, TinyTacticsSprites},
-    combat::skills::{ATTACK_SKILL_ID, SkillId},
-    gameplay_effects::{ActiveEffects, Effect, EffectData, EffectMetadata, StatModification},
-    unit::TINY_TACTICS_ANCHOR,
-    unit_stats::StatsDirty,
+    assets::sprite_db::{SpriteDB, SpriteId, build_sprite_map},
+    combat::skills::{SkillDB, SkillDBResource, SkillId, UnitSkills},
+    gameplay_effects::{
+        ActiveEffects, Effect, EffectData, EffectDuration, EffectMetadata, EffectType, Operator,
+        StatModification, StatusTag,
+    },
+    inventory::Consumable,
+    tr,
+    unit::{TINY_TACTICS_ANCHOR, jobs::UnitJob},
+    unit_stats::{StatType, StatsDirty},
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct WeaponData {
     pub range: u32,
     pub attack_skill: SkillId,
+    /// Ranged weapons fire this at whoever they're attacking, rather than being swung
+    #[serde(default)]
+    pub projectile: Option<SpriteId>,
+    /// How many attacks it's good for before it breaks, when weapons wear out at all
+    #[serde(default)]
+    pub durability: Option<u32>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The weapon a unit is drawn swinging until they've got one of their own equipped
+#[derive(Component)]
+pub struct PlaceholderWeapon;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
 pub enum EquippableSlot {
     BothHands,
     Primary,
@@ -32,6 +58,31 @@ pub enum EquippableSlot {
     Body,
     Gloves,
     Feet,
+    /// Rings, charms and the like
+    Accessory,
+}
+
+impl EquippableSlot {
+    /// Whether something in this slot has to come off to make room for something in `other`
+    pub fn overlaps(&self, other: &EquippableSlot) -> bool {
+        use EquippableSlot::*;
+        match (self, other) {
+            (BothHands, Primary | Offhand) | (Primary | Offhand, BothHands) => true,
+            _ => self == other,
+        }
+    }
+}
+
+/// Something an item does for whoever has it equipped
+#[derive(Debug, Clone, Deserialize)]
+pub enum ItemModifier {
+    Stat(StatModification),
+    /// A stat bonus that's better the rarer the item rolled
+    RolledStat(StatRange),
+    /// Lets them use a skill they haven't learned
+    Skill(SkillId),
+    /// A status they keep for as long as the item is on, like regenerating
+    Status(StatusTag),
 }
 
 #[derive(Debug)]
@@ -40,18 +91,248 @@ pub enum WeaponRestrictions {
     TwoHanded,
 }
 
+/// How hard an item is to come by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize)]
+pub enum Rarity {
+    #[default]
+    Common,
+    Uncommon,
+    Rare,
+    Legendary,
+}
+
+impl Rarity {
+    pub const ALL: [Rarity; 4] = [
+        Rarity::Common,
+        Rarity::Uncommon,
+        Rarity::Rare,
+        Rarity::Legendary,
+    ];
+
+    /// How often each rarity turns up when an item drops, next to the rest
+    fn drop_weight(&self) -> u32 {
+        match self {
+            Rarity::Common => 60,
+            Rarity::Uncommon => 25,
+            Rarity::Rare => 12,
+            Rarity::Legendary => 3,
+        }
+    }
+
+    /// What the item's name gets written in
+    pub fn color(&self) -> Color {
+        match self {
+            Rarity::Common => Color::WHITE,
+            Rarity::Uncommon => Color::linear_rgb(0.3, 0.9, 0.3),
+            Rarity::Rare => Color::linear_rgb(0.3, 0.5, 1.0),
+            Rarity::Legendary => Color::linear_rgb(1.0, 0.6, 0.1),
+        }
+    }
+}
+
+/// How an item came out when it dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemRoll {
+    pub rarity: Rarity,
+    /// Where its stats landed within the rarity's share of their ranges, out of `u8::MAX`
+    pub quality: u8,
+}
+
+impl ItemRoll {
+    /// Rolls a fresh drop, which is never any more common than the item itself
+    pub fn random(rng: &mut impl Rng, floor: Rarity) -> ItemRoll {
+        let total = Rarity::ALL.iter().map(|t| t.drop_weight()).sum::<u32>();
+        let mut roll = rng.random_range(0..total);
+        let mut rarity = Rarity::Common;
+        for candidate in Rarity::ALL {
+            if roll < candidate.drop_weight() {
+                rarity = candidate;
+                break;
+            }
+            roll -= candidate.drop_weight();
+        }
+
+        ItemRoll {
+            rarity: rarity.max(floor),
+            quality: rng.random(),
+        }
+    }
+}
+
+/// A stat bonus that lands somewhere between `min` and `max` when the item drops
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatRange {
+    pub attribute_type: StatType,
+    pub operator: Operator,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl StatRange {
+    /// Each rarity gets its own slice of the range, from the bottom for commons to the top for
+    /// legendaries, and the quality picks where in that slice it ends up
+    pub fn roll(&self, roll: ItemRoll) -> StatModification {
+        let tier = Rarity::ALL
+            .iter()
+            .position(|t| *t == roll.rarity)
+            .unwrap_or(0) as f32;
+        let t = (tier + roll.quality as f32 / u8::MAX as f32) / Rarity::ALL.len() as f32;
+        let value = self.min + (self.max - self.min) * t;
+        StatModification {
+            attribute_type: self.attribute_type,
+            operator: self.operator.clone(),
+            // Nobody wants to see STR +2.3716
+            value: match self.operator {
+                Operator::Add => value.round(),
+                Operator::Mul => (value * 100.).round() / 100.,
+            },
+        }
+    }
+}
+
 #[allow(dead_code)]
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Deserialize)]
 pub struct EquippableItem {
     item_name: String,
     /// The slot that this item can be equipped on
     slot: EquippableSlot,
-    modifiers: Vec<StatModification>,
+    #[serde(default)]
+    modifiers: Vec<ItemModifier>,
     item_id: ItemId,
     /// Should the SpriteDB maintain this reference?
     sprite_id: SpriteId,
+    #[serde(default = "default_animated_sprite_id")]
     animated_sprite_id: AnimatedSpriteId,
+    #[serde(default)]
     weapon_data: Option<WeaponData>,
+    /// What the shop asks for it, in gold
+    price: u32,
+    #[serde(default)]
+    rarity: Rarity,
+    /// Who can use it, or anyone if it doesn't say
+    #[serde(default)]
+    jobs: Vec<UnitJob>,
+    /// How likely it is to block a hit, from 0 to 1. Only shields have any.
+    #[serde(default)]
+    block_chance: f32,
+}
+
+/// Everything held in a hand so far uses the TinyTactics weapon animations
+fn default_animated_sprite_id() -> AnimatedSpriteId {
+    TT_WEAPON_ANIMATED_SPRITE_ID
+}
+
+impl EquippableItem {
+    pub fn name(&self) -> &str {
+        &self.item_name
+    }
+
+    pub fn item_id(&self) -> ItemId {
+        self.item_id
+    }
+
+    pub fn slot(&self) -> EquippableSlot {
+        self.slot
+    }
+
+    pub fn price(&self) -> u32 {
+        self.price
+    }
+
+    /// How it came out when it dropped. Anything that didn't drop (like from the shop) is as common
+    /// as it gets, with the worst stats for it.
+    pub fn roll(&self) -> ItemRoll {
+        self.item_id.roll().unwrap_or(ItemRoll {
+            rarity: self.rarity,
+            quality: 0,
+        })
+    }
+
+    pub fn rarity(&self) -> Rarity {
+        self.roll().rarity
+    }
+
+    /// The stat change the modifier makes on this particular item, if it changes one
+    fn stat_modification(&self, modifier: &ItemModifier) -> Option<StatModification> {
+        match modifier {
+            ItemModifier::Stat(modification) => Some(modification.clone()),
+            ItemModifier::RolledStat(range) => Some(range.roll(self.roll())),
+            ItemModifier::Skill(_) | ItemModifier::Status(_) => None,
+        }
+    }
+
+    pub fn weapon_data(&self) -> Option<&WeaponData> {
+        self.weapon_data.as_ref()
+    }
+
+    /// Skills whoever has it equipped gets to use
+    pub fn granted_skills(&self) -> impl Iterator<Item = SkillId> + '_ {
+        self.modifiers.iter().filter_map(|t| match t {
+            ItemModifier::Skill(skill) => Some(*skill),
+            _ => None,
+        })
+    }
+
+    /// How many uses it's got left before it breaks, if it ever does
+    pub fn uses_left(&self) -> Option<u32> {
+        let durability = self.weapon_data.as_ref()?.durability?;
+        Some(durability.saturating_sub(self.item_id.wear()))
+    }
+
+    /// A broken item doesn't do anything for whoever has it on until it's repaired
+    pub fn is_broken(&self) -> bool {
+        self.uses_left() == Some(0)
+    }
+
+    /// Promoted jobs can still use anything the job they came from could
+    pub fn usable_by(&self, job: &UnitJob) -> bool {
+        self.jobs.is_empty()
+            || self.jobs.contains(job)
+            || job.promoted_from().is_some_and(|t| self.jobs.contains(&t))
+    }
+
+    /// The name of the item and what it does for whoever is holding it, like "Sword: STR +2"
+    pub fn description(&self) -> String {
+        let mut effects = self
+            .modifiers
+            .iter()
+            .map(|t| match t {
+                ItemModifier::Stat(_) | ItemModifier::RolledStat(_) => self
+                    .stat_modification(t)
+                    .map(|t| t.description())
+                    .unwrap_or_default(),
+                ItemModifier::Skill(_) => tr!("item.skill"),
+                ItemModifier::Status(status) => format!("{:?}", status),
+            })
+            .collect::<Vec<_>>();
+        if let Some(weapon) = &self.weapon_data {
+            effects.push(tr!("item.range", range = weapon.range));
+        }
+        // Brand new weapons don't bother saying so
+        if let (Some(left), Some(max)) = (
+            self.uses_left(),
+            self.weapon_data.as_ref().and_then(|t| t.durability),
+        ) && left < max
+        {
+            effects.push(if left == 0 {
+                tr!("item.broken")
+            } else {
+                tr!("item.uses", left = left, max = max)
+            });
+        }
+        if self.block_chance > 0. {
+            effects.push(tr!(
+                "item.block",
+                chance = (self.block_chance * 100.).round() as u32
+            ));
+        }
+
+        if effects.is_empty() {
+            self.item_name.clone()
+        } else {
+            format!("{}: {}", self.item_name, effects.join(", "))
+        }
+    }
 }
 
 /// The equipment for a unit
@@ -96,77 +377,298 @@ impl UnitEquipment {
         unequipped
     }
 
-    /// Get the WeaponData that the Unit has, if any
+    pub fn equipped_items(&self) -> impl Iterator<Item = (&EquippableSlot, &EquippableItem)> {
+        self.equipment_slots
+            .iter()
+            .map(|(slot, (_, item))| (slot, item))
+    }
+
+    /// How likely the unit is to block a hit with whatever they're holding, from 0 to 1
+    pub fn block_chance(&self) -> f32 {
+        self.equipped_items()
+            .map(|(_, item)| item.block_chance)
+            .sum::<f32>()
+            .min(1.)
+    }
+
+    /// The slot holding the weapon the unit fights with, if they've got one that isn't broken
     ///
     /// Assumes that weapons can only be held in specified slots, and that specified slots
     /// have priority. IE does not allow for second hand to have different data than primary
-    pub fn weapon_data(&self) -> Option<WeaponData> {
-        for slot in [
+    fn weapon_slot(&self) -> Option<EquippableSlot> {
+        [
             EquippableSlot::BothHands,
             EquippableSlot::Primary,
             EquippableSlot::Offhand,
-        ] {
-            if let Some(weapon_data) = self
-                .equipment_slots
-                .get(&slot)
-                .map(|t| t.1.weapon_data.clone())
-                .flatten()
-            {
-                return Some(weapon_data);
-            }
-        }
+        ]
+        .into_iter()
+        .find(|slot| {
+            self.equipment_slots
+                .get(slot)
+                .is_some_and(|t| t.1.weapon_data.is_some() && !t.1.is_broken())
+        })
+    }
 
-        None
+    /// Get the WeaponData that the Unit has, if any
+    pub fn weapon_data(&self) -> Option<WeaponData> {
+        let slot = self.weapon_slot()?;
+        self.equipment_slots
+            .get(&slot)
+            .and_then(|t| t.1.weapon_data.clone())
+    }
+
+    /// Uses up one of the weapon's uses, if it has any to use up. Returns the weapon's entity
+    /// and how it looks now.
+    pub fn wear_weapon(&mut self) -> Option<(Entity, EquippableItem)> {
+        let slot = self.weapon_slot()?;
+        let (entity, item) = self.equipment_slots.get_mut(&slot)?;
+        item.uses_left()?;
+        item.item_id = item.item_id.with_wear(item.item_id.wear() + 1);
+        Some((*entity, item.clone()))
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, Hash, Reflect, serde::Serialize, serde::Deserialize,
+)]
 pub struct ItemId(pub u32);
 
+/// Dropped items carry their [`ItemRoll`] in the top half of their id, along with how worn they
+/// are, so the bag, trades, shops and saves can keep passing ids around like they always have. The
+/// bottom half is the id in the catalogue.
+const CATALOGUE_ID_MASK: u32 = 0xFFFF;
+const ROLL_RARITY_SHIFT: u32 = 16;
+const ROLL_RARITY_MASK: u32 = 0x7;
+const WEAR_SHIFT: u32 = 19;
+const WEAR_MASK: u32 = 0x1F;
+const ROLL_QUALITY_SHIFT: u32 = 24;
+
+/// The most uses a weapon can have, since that's all the wear its id has room for
+pub const MAX_DURABILITY: u32 = WEAR_MASK;
+
+impl ItemId {
+    /// The item in the catalogue this is, however it rolled
+    pub fn base(&self) -> ItemId {
+        ItemId(self.0 & CATALOGUE_ID_MASK)
+    }
+
+    /// How the item rolled, if it dropped from somewhere
+    pub fn roll(&self) -> Option<ItemRoll> {
+        // Zero is left for items that were never rolled
+        let rarity = ((self.0 >> ROLL_RARITY_SHIFT) & ROLL_RARITY_MASK).checked_sub(1)?;
+        Some(ItemRoll {
+            rarity: *Rarity::ALL.get(rarity as usize)?,
+            quality: (self.0 >> ROLL_QUALITY_SHIFT) as u8,
+        })
+    }
+
+    pub fn with_roll(&self, roll: ItemRoll) -> ItemId {
+        let rarity = Rarity::ALL
+            .iter()
+            .position(|t| *t == roll.rarity)
+            .unwrap_or(0) as u32
+            + 1;
+        ItemId(
+            self.base().with_wear(self.wear()).0
+                | rarity << ROLL_RARITY_SHIFT
+                | (roll.quality as u32) << ROLL_QUALITY_SHIFT,
+        )
+    }
+
+    /// How many uses have been gotten out of the item since it was last repaired
+    pub fn wear(&self) -> u32 {
+        (self.0 >> WEAR_SHIFT) & WEAR_MASK
+    }
+
+    pub fn with_wear(&self, wear: u32) -> ItemId {
+        ItemId(self.0 & !(WEAR_MASK << WEAR_SHIFT) | wear.min(WEAR_MASK) << WEAR_SHIFT)
+    }
+}
+
+/// What happens to whoever a consumable gets used on
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum UseEffect {
+    /// Gives back this much health
+    Heal(f32),
+    /// Takes the status off
+    Cure(StatusTag),
+    /// Hurts for this much, no matter their defense
+    Damage(f32),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConsumableItem {
+    pub use_effect: UseEffect,
+    /// How far away it can be used, where 0 is only on whoever's using it
+    pub range: u32,
+    /// What the shop asks for one, in gold
+    pub price: u32,
+}
+
 #[derive(Resource)]
 pub struct ItemDB {
     pub equippable_items: HashMap<ItemId, EquippableItem>,
+    pub consumables: HashMap<Consumable, ConsumableItem>,
 }
 
-pub fn build_item_db() -> ItemDB {
-    let equippable_items = HashMap::from([
-        (
-            ItemId(1),
-            EquippableItem {
-                item_name: "Iron Axe".to_string(),
-                item_id: ItemId(1),
-                slot: EquippableSlot::Primary,
-                modifiers: Vec::new(),
-                sprite_id: TinyTacticsSprites::IronAxe.into(),
-                animated_sprite_id: TT_WEAPON_ANIMATED_SPRITE_ID,
-                weapon_data: Some(WeaponData {
-                    range: 1,
-                    attack_skill: ATTACK_SKILL_ID,
-                }),
-            },
-        ),
-        (
-            ItemId(2),
-            EquippableItem {
-                item_name: "Bow".to_string(),
-                item_id: ItemId(2),
-                slot: EquippableSlot::BothHands,
-                modifiers: Vec::new(),
-                sprite_id: TinyTacticsSprites::IronAxe.into(),
-                animated_sprite_id: TT_WEAPON_ANIMATED_SPRITE_ID,
-                weapon_data: Some(WeaponData {
-                    range: 4,
-                    attack_skill: SkillId(4),
-                }),
-            },
-        ),
-    ]);
+impl ItemDB {
+    /// The item, with whatever it rolled when it dropped
+    pub fn equippable_item(&self, item: &ItemId) -> Option<EquippableItem> {
+        self.equippable_items
+            .get(&item.base())
+            .map(|t| EquippableItem {
+                item_id: *item,
+                ..t.clone()
+            })
+    }
+
+    /// Rolls how a freshly dropped `item` came out
+    pub fn roll_item(&self, item: ItemId, rng: &mut impl Rng) -> ItemId {
+        let floor = self
+            .equippable_items
+            .get(&item.base())
+            .map(|t| t.rarity)
+            .unwrap_or_default();
+        item.with_roll(ItemRoll::random(rng, floor))
+    }
+
+    pub fn consumable(&self, consumable: &Consumable) -> &ConsumableItem {
+        self.consumables
+            .get(consumable)
+            .unwrap_or_else(|| panic!("Consumable should be registered: {:?}", consumable))
+    }
+
+    /// Whatever in the catalogue points at something that doesn't exist, if anything
+    pub fn validate(&self, skill_db: &SkillDB) -> anyhow::Result<()> {
+        let sprites = build_sprite_map();
+        let atlases = build_animated_sprite_to_atlas_layout();
+        for item in self.equippable_items.values() {
+            if !sprites.contains_key(&item.sprite_id) {
+                anyhow::bail!(
+                    "{} has a sprite that isn't registered: {:?}",
+                    item.item_name,
+                    item.sprite_id
+                );
+            }
+            if !atlases.contains_key(&item.animated_sprite_id) {
+                anyhow::bail!(
+                    "{} animates with a sprite that isn't registered: {:?}",
+                    item.item_name,
+                    item.animated_sprite_id
+                );
+            }
+            if let Some(weapon) = &item.weapon_data
+                && !skill_db.has_skill(&weapon.attack_skill)
+            {
+                anyhow::bail!(
+                    "{} attacks with a skill that isn't registered: {:?}",
+                    item.item_name,
+                    weapon.attack_skill
+                );
+            }
+            if let Some(projectile) = item.weapon_data.as_ref().and_then(|t| t.projectile)
+                && !sprites.contains_key(&projectile)
+            {
+                anyhow::bail!(
+                    "{} fires a projectile that isn't registered: {:?}",
+                    item.item_name,
+                    projectile
+                );
+            }
+            if let Some(ItemModifier::RolledStat(range)) = item
+                .modifiers
+                .iter()
+                .find(|t| matches!(t, ItemModifier::RolledStat(range) if range.min > range.max))
+            {
+                anyhow::bail!(
+                    "{} rolls {:?} from {} down to {}",
+                    item.item_name,
+                    range.attribute_type,
+                    range.min,
+                    range.max
+                );
+            }
+            if let Some(durability) = item.weapon_data.as_ref().and_then(|t| t.durability)
+                && !(1..=MAX_DURABILITY).contains(&durability)
+            {
+                anyhow::bail!(
+                    "{} lasts for {} uses, which isn't between 1 and {}",
+                    item.item_name,
+                    durability,
+                    MAX_DURABILITY
+                );
+            }
+            if let Some(skill) = item.granted_skills().find(|t| !skill_db.has_skill(t)) {
+                anyhow::bail!(
+                    "{} grants a skill that isn't registered: {:?}",
+                    item.item_name,
+                    skill
+                );
+            }
+        }
 
-    ItemDB { equippable_items }
+        if let Some(missing) = Consumable::ALL
+            .iter()
+            .find(|t| !self.consumables.contains_key(t))
+        {
+            anyhow::bail!("{:?} isn't in the item catalogue", missing);
+        }
+
+        Ok(())
+    }
+}
+
+/// The catalogue as it's written in the asset
+#[derive(Deserialize)]
+struct ItemCatalogue {
+    equipment: Vec<EquippableItem>,
+    consumables: HashMap<Consumable, ConsumableItem>,
+}
+
+const ITEM_CATALOGUE: &str = include_str!("../assets/items/items.ron");
+
+fn parse_item_db(raw: &str) -> anyhow::Result<ItemDB> {
+    let catalogue = ron::from_str::<ItemCatalogue>(raw).context("Parsing the item catalogue")?;
+
+    let mut seen = HashSet::new();
+    if let Some(duplicate) = catalogue.equipment.iter().find(|t| !seen.insert(t.item_id)) {
+        anyhow::bail!(
+            "{} reuses item id {:?}",
+            duplicate.item_name,
+            duplicate.item_id
+        );
+    }
+    if let Some(too_big) = catalogue
+        .equipment
+        .iter()
+        .find(|t| t.item_id != t.item_id.base())
+    {
+        anyhow::bail!(
+            "{} has an id too big to roll: {:?}",
+            too_big.item_name,
+            too_big.item_id
+        );
+    }
+
+    Ok(ItemDB {
+        equippable_items: catalogue
+            .equipment
+            .into_iter()
+            .map(|t| (t.item_id, t))
+            .collect(),
+        consumables: catalogue.consumables,
+    })
+}
+
+pub fn build_item_db() -> anyhow::Result<ItemDB> {
+    parse_item_db(ITEM_CATALOGUE)
 }
 
-pub fn setup_item_db(mut commands: Commands) {
-    commands.insert_resource(build_item_db());
+pub fn setup_item_db(mut commands: Commands, skill_db: Res<SkillDBResource>) {
+    let item_db = build_item_db()
+        .and_then(|t| t.validate(&skill_db.skill_db).map(|_| t))
+        .expect("Should be able to build the item DB");
+    commands.insert_resource(item_db);
 }
 
 pub fn unequip_items_on_unit(
@@ -193,7 +695,8 @@ pub fn unequip_items_on_unit(
     Ok(())
 }
 
-/// Equip an item on a unit
+/// Equip an item on a unit, as long as their job lets them use it
+#[allow(clippy::too_many_arguments)]
 pub fn equip_item_on_unit(
     commands: &mut Commands,
     sprite_db: &SpriteDB,
@@ -201,8 +704,13 @@ pub fn equip_item_on_unit(
     unit_equipment: &mut UnitEquipment,
     unit_effects: &mut ActiveEffects,
     unit_e: Entity,
+    job: &UnitJob,
     item: EquippableItem,
 ) -> anyhow::Result<()> {
+    if !item.usable_by(job) {
+        anyhow::bail!("A {} can't use {}", job.name(), item.item_name);
+    }
+
     unequip_items_on_unit(
         commands,
         unit_equipment,
@@ -243,15 +751,32 @@ pub fn equip_item_on_unit(
         ))
         .id();
 
-    for modifier in &item.modifiers {
+    // A broken weapon is just something to carry around until it's fixed
+    let modifiers = if item.is_broken() {
+        &[][..]
+    } else {
+        &item.modifiers[..]
+    };
+    for modifier in modifiers {
+        let effect_type = match modifier {
+            ItemModifier::Stat(_) | ItemModifier::RolledStat(_) => {
+                let Some(modification) = item.stat_modification(modifier) else {
+                    continue;
+                };
+                EffectType::StatBuff(modification)
+            }
+            ItemModifier::Status(status) => EffectType::StatusInfliction(*status),
+            // Picked up by `sync_granted_skills` instead
+            ItemModifier::Skill(_) => continue,
+        };
         unit_effects.effects.push(Effect {
             metadata: EffectMetadata {
                 target: unit_e,
                 source: Some(item_e),
             },
             data: EffectData {
-                effect_type: crate::gameplay_effects::EffectType::StatBuff(modifier.clone()),
-                duration: crate::gameplay_effects::EffectDuration::Permanent,
+                effect_type,
+                duration: EffectDuration::Permanent,
             },
         })
     }
@@ -261,3 +786,112 @@ pub fn equip_item_on_unit(
 
     Ok(())
 }
+
+/// Keeps the skills a unit gets from their gear in line with what they've got on, so taking an
+/// item off takes its skills with it
+pub fn sync_granted_skills(
+    mut units: Query<(&UnitEquipment, &mut UnitSkills), Changed<UnitEquipment>>,
+) {
+    for (equipment, mut skills) in &mut units {
+        skills.granted_skills = equipment
+            .equipped_items()
+            .filter(|(_, item)| !item.is_broken())
+            .flat_map(|(_, item)| item.granted_skills())
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combat::skills::build_skill_table;
+
+    #[test]
+    fn test_bundled_catalogue_is_valid() {
+        let item_db = build_item_db().expect("Item DB should parse");
+        let skill_db = build_skill_table().expect("Skill DB should build");
+        if let Err(e) = item_db.validate(&skill_db) {
+            panic!("{:?}", e);
+        }
+        assert!(!item_db.equippable_items.is_empty());
+    }
+
+    #[test]
+    fn test_reused_item_ids_are_rejected() {
+        let raw = r#"(
+            equipment: [
+                (item_id: ItemId(1), item_name: "Axe", slot: Primary, sprite_id: SpriteId(12), price: 1),
+                (item_id: ItemId(1), item_name: "Other Axe", slot: Primary, sprite_id: SpriteId(12), price: 1),
+            ],
+            consumables: {},
+        )"#;
+        assert!(parse_item_db(raw).is_err());
+    }
+
+    #[test]
+    fn test_accessories_can_only_grant_real_skills() {
+        let skill_db = build_skill_table().expect("Skill DB should build");
+        let catalogue = |skill: u32| {
+            format!(
+                r#"(
+                    equipment: [
+                        (item_id: ItemId(1), item_name: "Charm", slot: Accessory, sprite_id: SpriteId(12), price: 1,
+                            modifiers: [Status(Regenerating), Skill(SkillId({}))]),
+                    ],
+                    consumables: {{
+                        Potion: (use_effect: Heal(1.0), range: 1, price: 1),
+                        Antidote: (use_effect: Cure(Poisoned), range: 1, price: 1),
+                        ThrowingKnife: (use_effect: Damage(1.0), range: 1, price: 1),
+                    }},
+                )"#,
+                skill
+            )
+        };
+
+        let item_db = parse_item_db(&catalogue(8)).expect("Catalogue should parse");
+        assert!(item_db.validate(&skill_db).is_ok());
+        let charm = &item_db.equippable_items[&ItemId(1)];
+        assert_eq!(charm.granted_skills().collect::<Vec<_>>(), vec![SkillId(8)]);
+
+        let item_db = parse_item_db(&catalogue(999)).expect("Catalogue should parse");
+        assert!(item_db.validate(&skill_db).is_err());
+    }
+
+    #[test]
+    fn test_rarer_rolls_land_higher() {
+        let range = StatRange {
+            attribute_type: StatType::Strength,
+            operator: Operator::Add,
+            min: 0.,
+            max: 8.,
+        };
+        let value = |rarity, quality| range.roll(ItemRoll { rarity, quality }).value;
+
+        assert_eq!(value(Rarity::Common, 0), 0.);
+        assert_eq!(value(Rarity::Legendary, u8::MAX), 8.);
+        for pair in Rarity::ALL.windows(2) {
+            assert!(value(pair[0], u8::MAX) <= value(pair[1], 0));
+        }
+
+        let rolled = ItemId(7).with_roll(ItemRoll {
+            rarity: Rarity::Rare,
+            quality: 200,
+        });
+        assert_eq!(rolled.base(), ItemId(7));
+        assert_eq!(
+            rolled.roll(),
+            Some(ItemRoll {
+                rarity: Rarity::Rare,
+                quality: 200
+            })
+        );
+        assert_eq!(ItemId(7).roll(), None);
+
+        let worn = rolled.with_wear(12);
+        assert_eq!(worn.wear(), 12);
+        assert_eq!(worn.roll(), rolled.roll());
+        assert_eq!(worn.base(), ItemId(7));
+        assert_eq!(worn.with_wear(0), rolled);
+        assert_eq!(ItemId(7).with_wear(100).wear(), MAX_DURABILITY);
+    }
+}
@@ -2,10 +2,12 @@ use crate::unit_stats::StatType;
 use bevy::prelude::*;
 use std::fmt::Debug;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub enum EffectType {
     StatBuff(StatModification),
     StatusInfliction(StatusTag),
+    /// Buffs allies standing close by, see [`aura`](crate::aura)
+    Aura(AuraData),
 }
 
 #[derive(Clone, Debug)]
@@ -28,20 +30,57 @@ impl<T: PartialEq + Eq + Debug + Clone> AppliesTo<T> {
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
 pub enum StatusTag {
     /// The target is poisoned
     Poisoned,
     /// The target is stunned
     Stunned,
+    /// Soaked by the rain, so the target's ranged skills can miss
+    Drenched,
+    /// Can't see far through the fog, so the target can't reach or spot as far
+    Fogbound,
+    /// Heals a little at the start of each of the target's turns
+    Regenerating,
+    /// Went down in the last room, and is still hurting from it
+    Injured,
+}
+
+impl StatusTag {
+    /// What the status does to the target's stats, on top of anything else it does
+    pub fn stat_modifications(&self) -> Vec<StatModification> {
+        match self {
+            StatusTag::Injured => [StatType::Strength, StatType::Magic, StatType::Speed]
+                .into_iter()
+                .map(|attribute_type| StatModification {
+                    attribute_type,
+                    operator: Operator::Mul,
+                    value: INJURED_STAT_MULTIPLIER,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
 }
 
+/// How much a regenerating unit heals each turn
+pub const REGENERATION_HEAL: f32 = 1.;
+
+/// How much of their strength, magic and speed an injured unit fights with
+pub const INJURED_STAT_MULTIPLIER: f32 = 0.75;
+
+/// How likely a drenched unit's ranged skills are to land
+pub const DRENCHED_HIT_CHANCE: f32 = 0.7;
+
+/// How much closer a fogbound unit has to be to reach or spot somebody
+pub const FOGBOUND_RANGE_PENALTY: u32 = 2;
+
 #[derive(Clone, Debug)]
 pub enum SkillParameter {
     Range,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub enum EffectDuration {
     /// The effect should last for this many turns
     TurnCount(u8),
@@ -53,7 +92,7 @@ pub enum EffectDuration {
     Permanent,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct EffectData {
     pub effect_type: EffectType,
     pub duration: EffectDuration,
@@ -76,19 +115,36 @@ pub enum AttributeType {
     Stat(StatType),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub enum Operator {
     Add,
     Mul,
 }
 
-#[derive(Clone, Debug)]
+/// A change to one of a unit's stats, from a buff, a piece of equipment, the weather...
+///
+/// However many a unit has on, they always get applied in the same order: every [`Operator::Add`]
+/// first, then every [`Operator::Mul`] on top of that, so `+2` and `x2` on a stat of 4 is always
+/// 12. See [`apply_stat_modifications`](crate::unit_stats::apply_stat_modifications).
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct StatModification {
     pub attribute_type: StatType,
     pub operator: Operator,
     pub value: f32,
 }
 
+/// Grants `modification` to every ally within `radius` tiles of whoever has the aura, for as long
+/// as they stay there
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct AuraData {
+    pub radius: u32,
+    pub modification: StatModification,
+    /// Only reaches this one unit, rather than every ally. Units only exist for the battle, so
+    /// this never gets saved.
+    #[serde(skip)]
+    pub partner: Option<Entity>,
+}
+
 #[derive(Clone, Debug, Component)]
 pub struct ActiveEffects {
     /// The ActiveEffects associated with this entity
@@ -108,9 +164,52 @@ impl ActiveEffects {
         self.has_status(StatusTag::Stunned)
     }
 
+    /// The chance a ranged skill used by this unit lands
+    pub fn ranged_hit_chance(&self) -> f32 {
+        if self.has_status(StatusTag::Drenched) {
+            DRENCHED_HIT_CHANCE
+        } else {
+            1.
+        }
+    }
+
+    /// How far this unit can reach or spot somebody, when it could see `range` tiles on a clear
+    /// day. Melee range is never cut short.
+    pub fn sight_range(&self, range: u32) -> u32 {
+        if self.has_status(StatusTag::Fogbound) && range > 1 {
+            range.saturating_sub(FOGBOUND_RANGE_PENALTY).max(1)
+        } else {
+            range
+        }
+    }
+
     pub fn apply_effect(&mut self, effect: Effect) {
         match effect.data.effect_type {
-            EffectType::StatBuff(..) => {
+            EffectType::StatBuff(ref modification) => {
+                // Casting the same timed buff again just tops it back up, rather than stacking
+                let description = modification.description();
+                for existing in self.effects.iter_mut() {
+                    let EffectType::StatBuff(existing_modification) = &existing.data.effect_type
+                    else {
+                        continue;
+                    };
+                    if existing.metadata.source != effect.metadata.source
+                        || existing_modification.description() != description
+                    {
+                        continue;
+                    }
+                    if let (
+                        EffectDuration::TurnCount(existing_turns),
+                        EffectDuration::TurnCount(turns),
+                    ) = (&mut existing.data.duration, &effect.data.duration)
+                    {
+                        *existing_turns = (*existing_turns).max(*turns);
+                        return;
+                    }
+                }
+                self.effects.push(effect);
+            }
+            EffectType::Aura(..) => {
                 self.effects.push(effect);
             }
             EffectType::StatusInfliction(status_tag) => {
@@ -137,7 +236,16 @@ impl ActiveEffects {
         }
     }
 
-    fn has_status(&self, tag: StatusTag) -> bool {
+    /// Gets rid of the status, returning whether the unit actually had it
+    pub fn cure(&mut self, tag: StatusTag) -> bool {
+        let had_status = self.has_status(tag);
+        self.effects.retain(
+            |t| !matches!(t.data.effect_type, EffectType::StatusInfliction(found) if found == tag),
+        );
+        had_status
+    }
+
+    pub fn has_status(&self, tag: StatusTag) -> bool {
         self.has_any_status(Vec::from([tag]))
     }
 
@@ -164,6 +272,19 @@ impl ActiveEffects {
             .collect()
     }
 
+    pub fn auras(&self) -> Vec<&AuraData> {
+        self.effects
+            .iter()
+            .filter_map(|t| {
+                if let EffectType::Aura(t) = &t.data.effect_type {
+                    Some(t)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
     pub fn stat_buffs(&self) -> Vec<&StatModification> {
         self.effects
             .iter()
@@ -177,3 +298,169 @@ impl ActiveEffects {
             .collect()
     }
 }
+
+impl EffectData {
+    pub fn name(&self) -> String {
+        match &self.effect_type {
+            EffectType::StatBuff(modification) => modification.description(),
+            EffectType::StatusInfliction(status) => format!("{:?}", status),
+            EffectType::Aura(aura) => format!(
+                "{} aura ({} tiles)",
+                aura.modification.description(),
+                aura.radius
+            ),
+        }
+    }
+}
+
+impl StatModification {
+    /// Like "STR +2" or "DEF x1.5"
+    pub fn description(&self) -> String {
+        match self.operator {
+            Operator::Add => format!("{} {:+}", self.attribute_type.abbreviation(), self.value),
+            Operator::Mul => format!("{} x{}", self.attribute_type.abbreviation(), self.value),
+        }
+    }
+}
+
+impl Effect {
+    pub fn name(&self) -> String {
+        self.data.name()
+    }
+
+    pub fn remaining(&self) -> String {
+        match self.data.duration {
+            EffectDuration::TurnCount(turns) => format!("{} turns left", turns),
+            EffectDuration::Consumable(uses) => format!("{} uses left", uses),
+            EffectDuration::Permanent => "Permanent".to_string(),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        format!("{} ({})", self.name(), self.remaining())
+    }
+
+    /// Passives (like the ones from equipment) are always on, so they aren't worth calling out.
+    pub fn is_temporary(&self) -> bool {
+        !matches!(self.data.duration, EffectDuration::Permanent)
+    }
+}
+
+/// Little markers stacked over a unit's head, one for each temporary effect on it.
+///
+/// There aren't any icon sprites yet, so each kind of effect just gets a color.
+pub mod status_icons {
+    use bevy::prelude::*;
+
+    use super::{ActiveEffects, Effect, EffectType, Operator, StatusTag};
+
+    const ICON_SIZE: f32 = 5.;
+    const ICON_SPACING: f32 = 6.;
+    /// Just under where the damage text shows up
+    const ICON_HEIGHT: f32 = 28.;
+
+    #[derive(Component, Debug)]
+    pub struct StatusIcon {
+        pub description: String,
+    }
+
+    fn icon_color(effect: &Effect) -> Color {
+        match &effect.data.effect_type {
+            EffectType::StatusInfliction(StatusTag::Poisoned) => Color::linear_rgb(0.4, 0.0, 0.6),
+            EffectType::StatusInfliction(StatusTag::Stunned) => Color::linear_rgb(1.0, 0.9, 0.0),
+            EffectType::StatusInfliction(StatusTag::Drenched) => Color::linear_rgb(0.3, 0.6, 0.9),
+            EffectType::StatusInfliction(StatusTag::Fogbound) => Color::linear_rgb(0.7, 0.7, 0.7),
+            EffectType::StatusInfliction(StatusTag::Regenerating) => {
+                Color::linear_rgb(0.3, 0.9, 0.4)
+            }
+            EffectType::StatusInfliction(StatusTag::Injured) => Color::linear_rgb(0.6, 0.1, 0.1),
+            EffectType::Aura(..) => Color::linear_rgb(1.0, 0.8, 0.3),
+            EffectType::StatBuff(modification) => {
+                let is_buff = match modification.operator {
+                    Operator::Add => modification.value >= 0.,
+                    Operator::Mul => modification.value >= 1.,
+                };
+                if is_buff {
+                    Color::linear_rgb(0.2, 0.5, 1.0)
+                } else {
+                    Color::linear_rgb(1.0, 0.2, 0.2)
+                }
+            }
+        }
+    }
+
+    /// Rebuilds the icons for a unit whenever its effects change.
+    pub fn sync_status_icons(
+        mut commands: Commands,
+        units: Query<(Entity, &ActiveEffects, Option<&Children>), Changed<ActiveEffects>>,
+        icons: Query<(), With<StatusIcon>>,
+    ) {
+        for (unit, effects, children) in units {
+            for child in children.into_iter().flatten() {
+                if icons.contains(*child) {
+                    commands.entity(*child).despawn();
+                }
+            }
+
+            let shown = effects
+                .effects
+                .iter()
+                .filter(|t| t.is_temporary())
+                .collect::<Vec<_>>();
+            let leftmost = -(shown.len().saturating_sub(1) as f32) * ICON_SPACING / 2.;
+            for (i, effect) in shown.into_iter().enumerate() {
+                commands.entity(unit).with_child((
+                    StatusIcon {
+                        description: effect.description(),
+                    },
+                    Sprite::from_color(icon_color(effect), Vec2::splat(ICON_SIZE)),
+                    Transform::from_translation(Vec3::new(
+                        leftmost + i as f32 * ICON_SPACING,
+                        ICON_HEIGHT,
+                        1.,
+                    )),
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recasting_a_timed_buff_tops_it_up() {
+        let knight = Entity::from_bits(1);
+        let ally = Entity::from_bits(2);
+        let warcry = |source: Entity, turns: u8| Effect {
+            metadata: EffectMetadata {
+                target: ally,
+                source: Some(source),
+            },
+            data: EffectData {
+                effect_type: EffectType::StatBuff(StatModification {
+                    attribute_type: StatType::Strength,
+                    operator: Operator::Add,
+                    value: 2.,
+                }),
+                duration: EffectDuration::TurnCount(turns),
+            },
+        };
+
+        let mut effects = ActiveEffects {
+            effects: Vec::new(),
+        };
+        effects.apply_effect(warcry(knight, 1));
+        effects.apply_effect(warcry(knight, 2));
+        assert_eq!(effects.stat_buffs().len(), 1);
+        assert!(matches!(
+            effects.effects[0].data.duration,
+            EffectDuration::TurnCount(2)
+        ));
+
+        // Somebody else's Warcry stacks on top
+        effects.apply_effect(warcry(Entity::from_bits(3), 2));
+        assert_eq!(effects.stat_buffs().len(), 2);
+    }
+}
@@ -10,6 +10,9 @@ use crate::{
 pub const TILE_X_SIZE: f32 = 32.0;
 pub const TILE_Y_SIZE: f32 = 16.0;
 
+/// How far up a tile gets drawn for each step of elevation
+pub const ELEVATION_STEP: f32 = 8.0;
+
 #[derive(Debug)]
 pub struct GridManager {
     width: u32,
@@ -18,6 +21,10 @@ pub struct GridManager {
     // a lil more expensive updates for now
     entities: HashMap<GridPosition, Vec<Entity>>,
     entity_positions: HashMap<Entity, GridPosition>,
+    /// Tiles that a unit has committed to moving onto, but hasn't reached yet.
+    reservations: HashMap<GridPosition, Entity>,
+    /// How many steps up each raised tile is, anything missing is flat ground
+    elevation: HashMap<GridPosition, u32>,
 }
 
 pub enum GridPositionChangeResult {
@@ -91,7 +98,48 @@ impl GridManager {
             height,
             entities: HashMap::new(),
             entity_positions: HashMap::new(),
+            reservations: HashMap::new(),
+            elevation: HashMap::new(),
+        }
+    }
+
+    pub fn with_elevation(mut self, elevation: HashMap<GridPosition, u32>) -> Self {
+        self.elevation = elevation;
+        self
+    }
+
+    pub fn elevation(&self, position: &GridPosition) -> u32 {
+        self.elevation.get(position).copied().unwrap_or_default()
+    }
+
+    /// Where something standing on `position` gets drawn, raised up by the tile's elevation
+    pub fn world_position(&self, position: &GridPosition) -> Vec3 {
+        grid_to_world(position, TILE_X_SIZE, TILE_Y_SIZE)
+            + Vec3::Y * self.elevation(position) as f32 * ELEVATION_STEP
+    }
+
+    /// Hold a tile for an entity that's on its way there, so nobody else can claim it in the meantime.
+    ///
+    /// Returns the entity holding the reservation if it's already taken by someone else.
+    /// An entity can only hold one reservation at a time.
+    pub fn reserve(&mut self, position: GridPosition, entity: Entity) -> Result<(), Entity> {
+        if let Some(holder) = self.reservations.get(&position)
+            && *holder != entity
+        {
+            return Err(*holder);
         }
+
+        self.release_reservation(&entity);
+        self.reservations.insert(position, entity);
+        Ok(())
+    }
+
+    pub fn release_reservation(&mut self, entity: &Entity) {
+        self.reservations.retain(|_, holder| holder != entity);
+    }
+
+    pub fn reserved_by(&self, position: &GridPosition) -> Option<Entity> {
+        self.reservations.get(position).copied()
     }
 
     /// Move an entity to a new position on the grid
@@ -131,6 +179,18 @@ impl GridManager {
         }
     }
 
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn contains(&self, position: &GridPosition) -> bool {
+        position.x < self.width && position.y < self.height
+    }
+
     pub fn get_by_position(&self, position: &GridPosition) -> Option<&Vec<Entity>> {
         self.entities.get(position)
     }
@@ -184,16 +244,33 @@ pub fn sync_grid_positions_to_manager(
 ///
 /// Not so sure about this just yet.
 pub fn sync_grid_position_to_transform(
+    grid_manager_res: Option<Res<GridManagerResource>>,
     mut query: Query<(&GridPosition, &mut Transform), Changed<GridPosition>>,
 ) {
     for (grid_pos, mut transform) in query.iter_mut() {
         // Convert grid coordinates to world coordinates
-        let world = grid_to_world(grid_pos, TILE_X_SIZE, TILE_Y_SIZE);
+        let world = match &grid_manager_res {
+            Some(t) => t.grid_manager.world_position(grid_pos),
+            None => grid_to_world(grid_pos, TILE_X_SIZE, TILE_Y_SIZE),
+        };
         transform.translation = Vec3::new(world.x, world.y, world.z);
     }
 }
 
-#[derive(Component, Hash, PartialEq, Eq, Debug, Copy, Clone, Reflect, PartialOrd, Ord)]
+#[derive(
+    Component,
+    Hash,
+    PartialEq,
+    Eq,
+    Debug,
+    Copy,
+    Clone,
+    Reflect,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 #[reflect(Component)]
 pub struct GridPosition {
     pub x: u32,
@@ -252,10 +329,13 @@ impl GridMovement {
 
 pub const MAGIC_Z_INDEX_OFFSET: f32 = 600.;
 
+/// The tile that lines up with the world origin, along both axes
+const GRID_ORIGIN_OFFSET: f32 = 6.;
+
 /// Diamond isometric grid conversion
 pub fn grid_to_world(grid_pos: &GridPosition, tile_width: f32, tile_height: f32) -> Vec3 {
-    let offset_x = grid_pos.x as f32 - 6.;
-    let offset_y = grid_pos.y as f32 - 6.;
+    let offset_x = grid_pos.x as f32 - GRID_ORIGIN_OFFSET;
+    let offset_y = grid_pos.y as f32 - GRID_ORIGIN_OFFSET;
 
     let world_x = (offset_x + offset_y) * (tile_width / 2.0);
     let world_y = (offset_x - offset_y) * (tile_height / 2.0);
@@ -267,6 +347,26 @@ pub fn grid_to_world(grid_pos: &GridPosition, tile_width: f32, tile_height: f32)
     )
 }
 
+/// The inverse of [`grid_to_world`], for figuring out which tile is under the mouse.
+///
+/// Returns None if the point is off the negative edges of the grid, callers still need to check
+/// the upper bounds.
+pub fn world_to_grid(world: Vec2, tile_width: f32, tile_height: f32) -> Option<GridPosition> {
+    let sum = world.x / (tile_width / 2.0);
+    let diff = world.y / (tile_height / 2.0);
+
+    let x = ((sum + diff) / 2.0 + GRID_ORIGIN_OFFSET).round();
+    let y = ((sum - diff) / 2.0 + GRID_ORIGIN_OFFSET).round();
+    if x < 0. || y < 0. {
+        return None;
+    }
+
+    Some(GridPosition {
+        x: x as u32,
+        y: y as u32,
+    })
+}
+
 /// Spawning an entity in the real world from a logical grid pos? Use this to hide some
 /// constants that probably shouldn't exist from yourself.
 pub fn init_grid_to_world_transform(grid_pos: &GridPosition) -> Transform {
@@ -292,6 +392,7 @@ pub fn resolve_grid_movement(
     {
         if movement.is_finished() {
             commands.entity(entity).remove::<GridMovement>();
+            grid_manager_res.grid_manager.release_reservation(&entity);
             action_completed_writer.write(UnitActionCompletedMessage {
                 unit: entity,
                 action: UnitAction::Move,
@@ -311,8 +412,8 @@ pub fn resolve_grid_movement(
             .next_position()
             .expect("No next position in movement, but movement isn't finished!");
 
-        let start_world = grid_to_world(current, TILE_X_SIZE, TILE_Y_SIZE);
-        let target_world = grid_to_world(next, TILE_X_SIZE, TILE_Y_SIZE);
+        let start_world = grid_manager_res.grid_manager.world_position(current);
+        let target_world = grid_manager_res.grid_manager.world_position(next);
 
         let lerped = start_world.lerp(target_world, progress);
 
@@ -405,6 +506,28 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_grid_manager_reservations() {
+        let mut grid_manager = GridManager::new(10, 10);
+        let mut world = World::new();
+        let first = world.spawn_empty().id();
+        let second = world.spawn_empty().id();
+        let position = GridPosition { x: 2, y: 3 };
+
+        assert_eq!(grid_manager.reserve(position, first), Ok(()));
+        assert_eq!(grid_manager.reserve(position, second), Err(first));
+        assert_eq!(grid_manager.reserve(position, first), Ok(()));
+
+        // Reserving somewhere else gives up the old tile
+        let other_position = GridPosition { x: 4, y: 3 };
+        assert_eq!(grid_manager.reserve(other_position, first), Ok(()));
+        assert_eq!(grid_manager.reserved_by(&position), None);
+
+        grid_manager.release_reservation(&first);
+        assert_eq!(grid_manager.reserved_by(&other_position), None);
+        assert_eq!(grid_manager.reserve(other_position, second), Ok(()));
+    }
+
     #[test]
     fn test_sync_grid_positions_system() {
         let mut app = App::new();
@@ -449,6 +572,29 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_world_to_grid_round_trip() {
+        for x in 0..12 {
+            for y in 0..12 {
+                let position = GridPosition { x, y };
+                let world = grid_to_world(&position, TILE_X_SIZE, TILE_Y_SIZE);
+                // Nudge it a bit off center to make sure we still land on the same tile
+                let nudged = world.truncate() + Vec2::new(3., 2.);
+                assert_eq!(
+                    world_to_grid(nudged, TILE_X_SIZE, TILE_Y_SIZE),
+                    Some(position)
+                );
+            }
+        }
+
+        let off_grid = grid_to_world(&GridPosition { x: 0, y: 0 }, TILE_X_SIZE, TILE_Y_SIZE)
+            - Vec3::new(TILE_X_SIZE, 0., 0.);
+        assert_eq!(
+            world_to_grid(off_grid.truncate(), TILE_X_SIZE, TILE_Y_SIZE),
+            None
+        );
+    }
+
     #[test]
     fn test_get_movement_options() {
         let options = get_movement_options(2);
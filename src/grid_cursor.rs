@@ -2,10 +2,17 @@ use crate::assets::sounds::{SoundManagerParam, UiSound};
 use crate::battle::BattleEntity;
 use crate::dungeon::DungeonEntity;
 use crate::grid;
-use crate::menu::menu_navigation::{GameMenuLatch, check_latch_on_axis_move};
 use crate::player;
+use crate::split_screen::SplitScreenCamera;
 
-use bevy::prelude::*;
+use std::time::Duration;
+
+use bevy::{
+    picking::{hover::HoverMap, pointer::PointerId},
+    prelude::*,
+    window::PrimaryWindow,
+};
+use leafwing_input_manager::prelude::{ActionState, InputMap};
 
 /// A cursor that can be moved on the grid
 #[derive(Component)]
@@ -28,6 +35,7 @@ pub fn spawn_cursor(
     commands: &mut Commands,
     image: Handle<Image>,
     player: player::Player,
+    color: Color,
     initial_grid_pos: grid::GridPosition,
 ) -> Entity {
     let mut initial_transform = grid::init_grid_to_world_transform(&initial_grid_pos);
@@ -41,7 +49,7 @@ pub fn spawn_cursor(
                 transform: initial_transform,
                 sprite: Sprite {
                     image,
-                    color: Color::linear_rgb(1.0, 0.0, 1.0),
+                    color,
                     ..Default::default()
                 },
                 cursor: Cursor {},
@@ -49,69 +57,294 @@ pub fn spawn_cursor(
             },
             BattleEntity {},
             DungeonEntity,
-            GameMenuLatch::default(),
+            CursorRepeat::default(),
             // Default state of cursor is to be locked on player
             LockedOn {},
         ))
         .id()
 }
 
+/// How long a direction has to be held before it starts repeating
+const REPEAT_DELAY: Duration = Duration::from_millis(300);
+/// The time between the first couple of repeats
+const REPEAT_START_INTERVAL: Duration = Duration::from_millis(150);
+/// Repeats never get faster than this
+const REPEAT_MIN_INTERVAL: Duration = Duration::from_millis(40);
+/// Each repeat comes this much sooner than the one before it
+const REPEAT_ACCELERATION: f32 = 0.8;
+/// Stick deflection below this doesn't move the cursor at all
+const STICK_DEADZONE: f32 = 0.3;
+/// A barely deflected stick repeats this much slower than a fully deflected one
+const STICK_MIN_RATE: f32 = 0.35;
+
+/// Key repeat for a held direction, so holding a direction moves the cursor steadily instead of
+/// once per press.
+///
+/// The first move happens right away, then there's a short delay before it starts repeating, and
+/// the repeats speed up the longer it's held. The keyboard and d-pad repeat at full speed, the
+/// stick repeats faster the further it's pushed.
+#[derive(Component, Debug, Default)]
+pub struct CursorRepeat {
+    direction: IVec2,
+    until_next: Duration,
+    interval: Duration,
+}
+
+impl CursorRepeat {
+    /// Advances the repeat by `elapsed` while `direction` is held at `rate` (between 0 and 1).
+    /// Returns whether the cursor should move this frame.
+    pub fn tick(&mut self, direction: IVec2, rate: f32, elapsed: Duration) -> bool {
+        if direction != self.direction {
+            // Letting go of one half of a diagonal shouldn't count as a new press
+            let newly_pressed = (direction.x != 0 && direction.x != self.direction.x)
+                || (direction.y != 0 && direction.y != self.direction.y);
+            self.direction = direction;
+            self.until_next = REPEAT_DELAY;
+            self.interval = REPEAT_START_INTERVAL;
+            return newly_pressed;
+        }
+
+        if direction == IVec2::ZERO {
+            return false;
+        }
+
+        self.until_next = self.until_next.saturating_sub(elapsed.mul_f32(rate));
+        if !self.until_next.is_zero() {
+            return false;
+        }
+
+        self.until_next = self.interval;
+        self.interval = self
+            .interval
+            .mul_f32(REPEAT_ACCELERATION)
+            .max(REPEAT_MIN_INTERVAL);
+        true
+    }
+}
+
+/// The direction held on the keyboard or d-pad, along with how fast it should repeat. Falls back
+/// to the stick if nothing digital is held.
+fn held_direction(action_state: &ActionState<player::PlayerInputAction>) -> (IVec2, f32) {
+    let mut direction = IVec2::ZERO;
+    if action_state.pressed(&player::PlayerInputAction::MoveCursorUp) {
+        direction.y -= 1;
+    }
+    if action_state.pressed(&player::PlayerInputAction::MoveCursorDown) {
+        direction.y += 1;
+    }
+    if action_state.pressed(&player::PlayerInputAction::MoveCursorLeft) {
+        direction.x -= 1;
+    }
+    if action_state.pressed(&player::PlayerInputAction::MoveCursorRight) {
+        direction.x += 1;
+    }
+    if direction != IVec2::ZERO {
+        return (direction, 1.0);
+    }
+
+    // Only the stick's strongest axis counts, diagonals are too easy to hit by accident
+    let axis = action_state.axis_pair(&player::PlayerInputAction::MoveCursor);
+    let (direction, deflection) = if axis.x.abs() > axis.y.abs() {
+        (IVec2::new(axis.x.signum() as i32, 0), axis.x.abs())
+    } else {
+        // Y Direction is inverted on the grid
+        (IVec2::new(0, -axis.y.signum() as i32), axis.y.abs())
+    };
+    if deflection < STICK_DEADZONE {
+        return (IVec2::ZERO, 0.0);
+    }
+
+    let deflection = ((deflection - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)).clamp(0.0, 1.0);
+    (
+        direction,
+        STICK_MIN_RATE + (1.0 - STICK_MIN_RATE) * deflection,
+    )
+}
+
 /// Translates Input Actions to grid movement for the cursor
 pub fn handle_cursor_movement(
     mut commands: Commands,
+    time: Res<Time>,
     grid_manager: Res<grid::GridManagerResource>,
     input_query: Query<(
         &player::Player,
         &leafwing_input_manager::prelude::ActionState<player::PlayerInputAction>,
     )>,
     mut cursor_query: Query<
-        (&player::Player, &mut grid::GridPosition, &mut GameMenuLatch),
+        (&player::Player, &mut grid::GridPosition, &mut CursorRepeat),
         (With<Cursor>, Without<LockedOn>),
     >,
     sounds: SoundManagerParam,
 ) {
     for (player, action_state) in input_query.iter() {
-        for (cursor_player, mut grid_pos, mut latch) in cursor_query.iter_mut() {
+        for (cursor_player, mut grid_pos, mut repeat) in cursor_query.iter_mut() {
             if player != cursor_player {
                 continue;
             }
-            let mut delta = grid::GridVec { x: 0, y: 0 };
-
-            if let Some(axis_delta) = check_latch_on_axis_move(action_state, &latch) {
-                latch.latch = axis_delta;
-                delta.x += axis_delta.x;
-                delta.y -= axis_delta.y;
-            }
 
-            if action_state.just_pressed(&player::PlayerInputAction::MoveCursorUp) {
-                delta.y -= 1;
-            }
-            if action_state.just_pressed(&player::PlayerInputAction::MoveCursorDown) {
-                delta.y += 1;
-            }
-            if action_state.just_pressed(&player::PlayerInputAction::MoveCursorLeft) {
-                delta.x -= 1;
-            }
-            if action_state.just_pressed(&player::PlayerInputAction::MoveCursorRight) {
-                delta.x += 1;
+            // The cursor keys pan the camera instead while the pan modifier's held
+            let panning =
+                action_state.axis_pair(&player::PlayerInputAction::PanCamera) != Vec2::ZERO;
+            let (direction, rate) = if panning {
+                (IVec2::ZERO, 0.0)
+            } else {
+                held_direction(action_state)
+            };
+            if !repeat.tick(direction, rate, time.delta()) {
+                continue;
             }
 
+            let delta = grid::GridVec {
+                x: direction.x,
+                y: direction.y,
+            };
             let new_pos = grid_manager
                 .grid_manager
                 .change_position_with_bounds(*grid_pos, delta);
 
             *grid_pos = new_pos.position();
 
-            if delta != (grid::GridVec { x: 0, y: 0 }) {
-                match new_pos {
-                    grid::GridPositionChangeResult::Moved(..) => {
-                        sounds.play_ui_sound(&mut commands, UiSound::MoveCursor);
-                    }
-                    grid::GridPositionChangeResult::OutOfBounds(..) => {
-                        sounds.play_ui_sound(&mut commands, UiSound::Error);
-                    }
+            match new_pos {
+                grid::GridPositionChangeResult::Moved(..) => {
+                    sounds.play_ui_sound(&mut commands, UiSound::MoveCursor);
+                }
+                grid::GridPositionChangeResult::OutOfBounds(..) => {
+                    sounds.play_ui_sound(&mut commands, UiSound::Error);
                 }
             }
         }
     }
 }
+
+/// The mouse belongs to whoever is playing on the keyboard.
+fn is_mouse_player(
+    player: &player::Player,
+    input_map: &InputMap<player::PlayerInputAction>,
+) -> bool {
+    *player != player::Player::PrePlayer && input_map.gamepad().is_none()
+}
+
+type PointerPlayer<'a> = (
+    &'a player::Player,
+    &'a InputMap<player::PlayerInputAction>,
+    &'a mut ActionState<player::PlayerInputAction>,
+);
+
+/// Lets the keyboard player drive their cursor with the mouse (or a touch screen, which shows up
+/// as a mouse).
+///
+/// Hovering over a tile moves the cursor there, left click moves and then selects, and right
+/// click backs out. Everyone else's cursor is left alone. Clicks are fed in as regular
+/// `PlayerInputAction`s, so this needs to run before anything that reads them.
+#[allow(clippy::too_many_arguments)]
+pub fn handle_cursor_pointer_input(
+    mut commands: Commands,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut cursor_moved: MessageReader<CursorMoved>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform, Has<SplitScreenCamera>), With<Camera2d>>,
+    hover_map: Res<HoverMap>,
+    ui_nodes: Query<(), With<Node>>,
+    grid_manager: Res<grid::GridManagerResource>,
+    mut players: Query<PointerPlayer>,
+    mut cursor_query: Query<
+        (&player::Player, &mut grid::GridPosition),
+        (With<Cursor>, Without<LockedOn>),
+    >,
+    sounds: SoundManagerParam,
+) {
+    let pointer_moved = cursor_moved.read().count() > 0;
+    let left_click = mouse_input.just_pressed(MouseButton::Left);
+    let right_click = mouse_input.just_pressed(MouseButton::Right);
+    if !pointer_moved && !left_click && !right_click {
+        return;
+    }
+
+    let Some((player, _, mut action_state)) = players
+        .iter_mut()
+        .find(|(player, input_map, _)| is_mouse_player(player, input_map))
+    else {
+        return;
+    };
+
+    if right_click {
+        action_state.press(&player::PlayerInputAction::Deselect);
+    }
+
+    // UI buttons get real clicks through picking, leave those alone
+    let over_ui = hover_map
+        .get(&PointerId::Mouse)
+        .is_some_and(|hits| hits.keys().any(|e| ui_nodes.contains(*e)));
+    if over_ui {
+        return;
+    }
+
+    let Some(position) = window.cursor_position() else {
+        return;
+    };
+    // With the screen split, it's whichever view the mouse is over
+    let split = cameras.iter().any(|(_, _, split)| split);
+    let Some(tile) = cameras
+        .iter()
+        .find(|(camera, _, is_split)| {
+            *is_split == split
+                && camera
+                    .logical_viewport_rect()
+                    .is_none_or(|t| t.contains(position))
+        })
+        .and_then(|(camera, transform, _)| camera.viewport_to_world_2d(transform, position).ok())
+        .and_then(|t| grid::world_to_grid(t, grid::TILE_X_SIZE, grid::TILE_Y_SIZE))
+        .filter(|t| grid_manager.grid_manager.contains(t))
+    else {
+        return;
+    };
+
+    let Some((_, mut cursor_pos)) = cursor_query.iter_mut().find(|(p, _)| *p == player) else {
+        return;
+    };
+
+    if *cursor_pos != tile {
+        *cursor_pos = tile;
+        sounds.play_ui_sound(&mut commands, UiSound::MoveCursor);
+    }
+
+    if left_click {
+        action_state.press(&player::PlayerInputAction::Select);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves_over(repeat: &mut CursorRepeat, rate: f32, frames: u32) -> u32 {
+        (0..frames)
+            .filter(|_| repeat.tick(IVec2::X, rate, Duration::from_millis(10)))
+            .count() as u32
+    }
+
+    #[test]
+    fn test_held_direction_repeats_after_delay_and_accelerates() {
+        let mut repeat = CursorRepeat::default();
+        assert!(repeat.tick(IVec2::X, 1.0, Duration::ZERO));
+        // Nothing until the delay runs out
+        assert_eq!(moves_over(&mut repeat, 1.0, 29), 0);
+
+        let first_second = moves_over(&mut repeat, 1.0, 100);
+        let second_second = moves_over(&mut repeat, 1.0, 100);
+        assert!(second_second > first_second);
+
+        // Letting go and pressing again moves right away
+        assert!(!repeat.tick(IVec2::ZERO, 0.0, Duration::ZERO));
+        assert!(repeat.tick(IVec2::X, 1.0, Duration::ZERO));
+    }
+
+    #[test]
+    fn test_light_stick_deflection_repeats_slower() {
+        let mut full = CursorRepeat::default();
+        let mut light = CursorRepeat::default();
+        assert!(full.tick(IVec2::X, 1.0, Duration::ZERO));
+        assert!(light.tick(IVec2::X, STICK_MIN_RATE, Duration::ZERO));
+        assert!(moves_over(&mut full, 1.0, 200) > moves_over(&mut light, STICK_MIN_RATE, 200));
+    }
+}
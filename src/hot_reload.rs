@@ -0,0 +1,125 @@
+//! Picking up animation and sprite changes without restarting, in god mode.
+//!
+//! Every so often the [animation timings](crate::animation::animation_db::AnimationTimings) and
+//! every image in the [`SpriteDB`] get checked on disk. If the timings changed, the
+//! [`AnimationDB`] gets built again from them (and its atlases refreshed in place, so whatever's
+//! already on screen keeps using them). If a spritesheet changed, it gets loaded again. That
+//! makes tuning frame counts and hit frames an edit, save and look loop.
+//!
+//! The sprite map itself is still in code, so adding a new sprite does need a restart.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    animation::animation_db::{ANIMATION_TIMINGS_PATH, AnimationDB, load_animation_timings},
+    assets::sprite_db::SpriteDB,
+};
+
+/// How often to go looking for changes
+const CHECK_INTERVAL_SECONDS: f32 = 0.5;
+
+/// When each file being watched was last changed, as of the last time it got checked
+#[derive(Resource)]
+pub struct WatchedFiles {
+    modified: HashMap<PathBuf, SystemTime>,
+    timer: Timer,
+}
+
+impl Default for WatchedFiles {
+    fn default() -> Self {
+        Self {
+            modified: HashMap::new(),
+            timer: Timer::from_seconds(CHECK_INTERVAL_SECONDS, TimerMode::Repeating),
+        }
+    }
+}
+
+impl WatchedFiles {
+    /// Whether the file's been changed since it was last checked. The first check on a file just
+    /// notes it down, and anything that can't be read hasn't changed.
+    pub fn changed(&mut self, path: &Path) -> bool {
+        let Ok(modified) = std::fs::metadata(path).and_then(|t| t.modified()) else {
+            return false;
+        };
+        match self.modified.insert(path.to_path_buf(), modified) {
+            Some(previous) => previous != modified,
+            None => false,
+        }
+    }
+}
+
+pub fn hot_reload_plugin(app: &mut App) {
+    app.init_resource::<WatchedFiles>()
+        .add_systems(Update, hot_reload_animations);
+}
+
+fn hot_reload_animations(
+    time: Res<Time>,
+    mut watched: ResMut<WatchedFiles>,
+    asset_server: Res<AssetServer>,
+    anim_db: Option<ResMut<AnimationDB>>,
+    sprite_db: Option<Res<SpriteDB>>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    if !watched.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    if watched.changed(Path::new(ANIMATION_TIMINGS_PATH))
+        && let Some(mut anim_db) = anim_db
+    {
+        match anim_db.rebuild(&load_animation_timings(), &mut texture_atlas_layouts) {
+            Ok(()) => info!("Reloaded the animation timings"),
+            Err(e) => warn!("Keeping the old animation timings: {:?}", e),
+        }
+    }
+
+    let Some(sprite_db) = sprite_db else {
+        return;
+    };
+    for handle in sprite_db.sprite_id_to_handle.values() {
+        let Some(path) = handle.path() else {
+            continue;
+        };
+        if watched.changed(&Path::new("assets").join(path.path())) {
+            info!("Reloading {}", path);
+            asset_server.reload(path.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::animation::animation_db::build_animation_db;
+
+    #[test]
+    fn test_only_changes_after_the_first_look_count() {
+        let path = std::env::temp_dir().join("tactics_hot_reload_test.json");
+        let file = std::fs::File::create(&path).expect("Should be able to make a temp file");
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(100))
+            .expect("Should be able to set the modified time");
+
+        let mut watched = WatchedFiles::default();
+        assert!(!watched.changed(&path));
+        assert!(!watched.changed(&path));
+
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(200))
+            .expect("Should be able to set the modified time");
+        assert!(watched.changed(&path));
+        assert!(!watched.changed(&path));
+        assert!(!watched.changed(Path::new("does/not/exist.json")));
+
+        std::fs::remove_file(&path).ok();
+        build_animation_db(&load_animation_timings())
+            .expect("Should be able to build the animation db from the timings on disk");
+    }
+}
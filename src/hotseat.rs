@@ -0,0 +1,283 @@
+//! Several players sharing one controller.
+//!
+//! Pressing the join buttons again on a controller that already joined adds another player to it.
+//! Only the player in the controller's seat gets its input, everyone else sharing it is ignored
+//! until it's their turn. The seat moves on by itself once its player is ready in the join screen,
+//! or runs out of units that can act in battle. Selecting another seated player's unit from the
+//! map hands the controller straight to them.
+//!
+//! An indicator at the top of the screen shows who the controller is standing in for.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    GameState,
+    assets::FontResource,
+    battle_phase::{PhaseManager, PlayerEnemyPhase, TurnQueue, UnitPhaseResources, has_turn},
+    dungeon::DungeonState,
+    grid::GridPosition,
+    grid_cursor::{Cursor, LockedOn},
+    join_game_menu::{JoinedPlayers, PlayerController},
+    player::{
+        Player, PlayerCursorState, PlayerGameStates, PlayerInputAction, RegisteredBattlePlayers,
+    },
+    tr,
+    unit::Unit,
+};
+
+/// Who's in the seat of each controller that's shared by more than one player
+#[derive(Resource, Default, Debug)]
+pub struct Hotseats(pub HashMap<PlayerController, Player>);
+
+impl Hotseats {
+    /// Everyone sharing a controller that isn't in its seat
+    pub fn waiting(&self, joined_players: &JoinedPlayers) -> HashSet<Player> {
+        shared_controllers(joined_players)
+            .into_iter()
+            .flat_map(|(controller, players)| {
+                let seated = self.0.get(&controller).copied();
+                players.into_iter().filter(move |t| Some(*t) != seated)
+            })
+            .collect()
+    }
+}
+
+#[derive(Component)]
+pub struct HotseatIndicator;
+
+pub fn hotseat_plugin(app: &mut App) {
+    app.init_resource::<Hotseats>()
+        // Fonts get loaded in Startup
+        .add_systems(PostStartup, spawn_hotseat_indicator)
+        .add_systems(
+            Update,
+            (
+                update_seats,
+                (advance_seat_in_battle, take_seat_from_map)
+                    .run_if(in_state(DungeonState::InBattle)),
+                apply_seats,
+                update_hotseat_indicator,
+            )
+                .chain()
+                .run_if(resource_exists::<JoinedPlayers>),
+        );
+}
+
+/// Players sharing each controller, sorted by id. Controllers with a single player are left out.
+pub fn shared_controllers(
+    joined_players: &JoinedPlayers,
+) -> HashMap<PlayerController, Vec<Player>> {
+    let mut controllers: HashMap<PlayerController, Vec<Player>> = HashMap::new();
+    for (player, data) in &joined_players.0 {
+        controllers
+            .entry(data.controller())
+            .or_default()
+            .push(*player);
+    }
+
+    controllers.retain(|_, players| players.len() > 1);
+    for players in controllers.values_mut() {
+        players.sort_by_key(|t| t.id());
+    }
+    controllers
+}
+
+/// Keeps a seat for every shared controller. In the join screen, the seat moves on to the next
+/// player once its player is ready.
+pub fn update_seats(
+    joined_players: Res<JoinedPlayers>,
+    game_state: Res<State<GameState>>,
+    mut hotseats: ResMut<Hotseats>,
+) {
+    let controllers = shared_controllers(&joined_players);
+    hotseats.0.retain(|controller, player| {
+        controllers
+            .get(controller)
+            .is_some_and(|players| players.contains(player))
+    });
+
+    for (controller, players) in controllers {
+        let seated = *hotseats.0.entry(controller).or_insert(players[0]);
+        if *game_state.get() != GameState::JoinGame
+            || !joined_players.0.get(&seated).is_some_and(|t| t.is_ready())
+        {
+            continue;
+        }
+
+        if let Some(next) = players
+            .iter()
+            .find(|t| joined_players.0.get(t).is_some_and(|t| !t.is_ready()))
+        {
+            hotseats.0.insert(controller, *next);
+        }
+    }
+}
+
+/// Hands the controller to the next player once everything in the seat is done for the phase
+pub fn advance_seat_in_battle(
+    joined_players: Res<JoinedPlayers>,
+    phase_manager: Option<Res<PhaseManager>>,
+    turn_queue: Option<Res<TurnQueue>>,
+    units: Query<(Entity, &Player, &UnitPhaseResources), With<Unit>>,
+    mut hotseats: ResMut<Hotseats>,
+) {
+    if phase_manager.is_none_or(|t| t.current_phase != PlayerEnemyPhase::Player) {
+        return;
+    }
+
+    let can_act = |player: &Player| {
+        units.iter().any(|(e, owner, resources)| {
+            owner == player && resources.can_act() && has_turn(turn_queue.as_deref(), e)
+        })
+    };
+
+    for (controller, players) in shared_controllers(&joined_players) {
+        let Some(seated) = hotseats.0.get(&controller).copied() else {
+            continue;
+        };
+        if can_act(&seated) {
+            continue;
+        }
+
+        if let Some(next) = players.iter().find(|t| can_act(t)) {
+            info!("Hotseat: {:?} is done, handing over to {:?}", seated, next);
+            hotseats.0.insert(controller, *next);
+        }
+    }
+}
+
+/// Selecting a unit that belongs to someone else on the same controller hands it to them
+pub fn take_seat_from_map(
+    joined_players: Res<JoinedPlayers>,
+    player_states: Option<Res<PlayerGameStates>>,
+    input_query: Query<(&Player, &ActionState<PlayerInputAction>)>,
+    cursors: Query<(&Player, &GridPosition), (With<Cursor>, Without<LockedOn>)>,
+    units: Query<(&Player, &GridPosition), (With<Unit>, Without<Cursor>)>,
+    mut hotseats: ResMut<Hotseats>,
+) {
+    let Some(player_states) = player_states else {
+        return;
+    };
+
+    for (controller, players) in shared_controllers(&joined_players) {
+        let Some(seated) = hotseats.0.get(&controller).copied() else {
+            continue;
+        };
+
+        let idle = player_states
+            .player_state
+            .get(&seated)
+            .is_some_and(|t| t.cursor_state == PlayerCursorState::Idle);
+        let pressed_select = input_query
+            .iter()
+            .any(|(p, t)| *p == seated && t.just_pressed(&PlayerInputAction::Select));
+        if !idle || !pressed_select {
+            continue;
+        }
+
+        let Some(cursor_pos) = cursors.iter().find(|(p, _)| **p == seated).map(|t| t.1) else {
+            continue;
+        };
+
+        if let Some((owner, _)) = units
+            .iter()
+            .find(|(p, pos)| *pos == cursor_pos && **p != seated && players.contains(p))
+        {
+            info!("Hotseat: handing over to {:?}", owner);
+            hotseats.0.insert(controller, *owner);
+        }
+    }
+}
+
+/// Only the seated players get their input, and only their cursors show up
+pub fn apply_seats(
+    hotseats: Res<Hotseats>,
+    joined_players: Res<JoinedPlayers>,
+    mut input_query: Query<(&Player, &mut ActionState<PlayerInputAction>)>,
+    mut cursors: Query<(&Player, &mut Visibility), With<Cursor>>,
+) {
+    let waiting = hotseats.waiting(&joined_players);
+    for (player, mut action_state) in input_query.iter_mut() {
+        let seated = !waiting.contains(player);
+        if seated && action_state.disabled() {
+            action_state.enable();
+        } else if !seated && !action_state.disabled() {
+            action_state.disable();
+        }
+    }
+
+    for (player, mut visibility) in cursors.iter_mut() {
+        let wanted = if !waiting.contains(player) {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        visibility.set_if_neq(wanted);
+    }
+}
+
+fn spawn_hotseat_indicator(mut commands: Commands, fonts: Res<FontResource>) {
+    commands.spawn((
+        Name::new("HotseatIndicator"),
+        Node {
+            display: Display::None,
+            position_type: PositionType::Absolute,
+            top: percent(1),
+            width: percent(100),
+            justify_content: JustifyContent::Center,
+            ..Default::default()
+        },
+        // Above the pause menu, since the controller still changes hands in there
+        GlobalZIndex(200),
+        children![(
+            Text::default(),
+            TextFont {
+                font: fonts.pixelify_sans_medium.clone(),
+                font_size: 30.,
+                ..Default::default()
+            },
+            TextShadow::default(),
+            HotseatIndicator,
+        )],
+    ));
+}
+
+/// Shows who every shared controller is standing in for. Hidden when nobody is sharing.
+pub fn update_hotseat_indicator(
+    hotseats: Res<Hotseats>,
+    registered_players: Option<Res<RegisteredBattlePlayers>>,
+    mut text: Query<(&mut Text, &mut TextColor, &ChildOf), With<HotseatIndicator>>,
+    mut nodes: Query<&mut Node>,
+) {
+    if !hotseats.is_changed() {
+        return;
+    }
+
+    let mut seated = hotseats.0.values().copied().collect::<Vec<_>>();
+    seated.sort_by_key(|t| t.id());
+
+    for (mut text, mut color, parent) in text.iter_mut() {
+        text.0 = seated
+            .iter()
+            .map(|t| tr!("hotseat.indicator", player = t.id()))
+            .collect::<Vec<_>>()
+            .join("   ");
+
+        // With one shared controller, its color makes it easier to tell at a glance
+        color.0 = match (seated.as_slice(), registered_players.as_deref()) {
+            ([player], Some(registered)) => registered.player_color(player),
+            _ => Color::WHITE,
+        };
+
+        if let Ok(mut node) = nodes.get_mut(parent.parent()) {
+            node.display = if seated.is_empty() {
+                Display::None
+            } else {
+                Display::Flex
+            };
+        }
+    }
+}
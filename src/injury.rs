@@ -0,0 +1,109 @@
+//! Going down hurts for longer than one room.
+//!
+//! On the difficulties where [injuries carry over](crate::difficulty::DungeonConfig), nobody's
+//! patched back up between rooms: the party starts each room with whatever health they left the
+//! last one with. Anyone who went down gets back up with 1 HP, and walks into the next room
+//! [`StatusTag::Injured`], which weakens them for a few turns. A campfire heals injuries right up,
+//! so healing items and rest rooms are worth a lot more on those runs.
+
+use bevy::prelude::*;
+
+use crate::{
+    GameState,
+    gameplay_effects::{
+        ActiveEffects, Effect, EffectData, EffectDuration, EffectMetadata, EffectType, StatusTag,
+    },
+    player::RegisteredBattlePlayers,
+    save_game::SaveFileKey,
+    unit_stats::StatsDirty,
+};
+
+/// How many turns an injury lasts into the next room
+pub const INJURY_TURNS: u8 = 3;
+
+/// What a unit takes with them into the next room
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CarriedInjury {
+    /// The health they start the next room with, or None to be back to full
+    pub health: Option<f32>,
+    pub injured: bool,
+}
+
+/// What a unit leaving a room with `health` left takes with them. Anyone still hurting from an
+/// injury they came in with keeps it too.
+pub fn carried_injury(
+    injuries_carry_over: bool,
+    health: f32,
+    still_injured: bool,
+) -> CarriedInjury {
+    if !injuries_carry_over {
+        return CarriedInjury {
+            health: None,
+            injured: false,
+        };
+    }
+
+    CarriedInjury {
+        // Getting downed on the way out shouldn't mean starting the next room downed
+        health: Some(health.max(1.)),
+        injured: health <= 0. || still_injured,
+    }
+}
+
+pub fn injury_plugin(app: &mut App) {
+    app.add_systems(Update, injure_units.run_if(in_state(GameState::Dungeon)));
+}
+
+/// Anyone whose save says they're injured shows up in the room that way
+fn injure_units(
+    mut commands: Commands,
+    registered_players: Option<Res<RegisteredBattlePlayers>>,
+    mut units: Query<(Entity, &SaveFileKey, &mut ActiveEffects), Added<SaveFileKey>>,
+) {
+    let Some(registered_players) = registered_players else {
+        return;
+    };
+
+    for (entity, key, mut effects) in units.iter_mut() {
+        let injured = registered_players
+            .units()
+            .any(|(_, t)| t.save_file_key.uid == key.uid && t.injured);
+        if !injured {
+            continue;
+        }
+
+        effects.apply_effect(Effect {
+            metadata: EffectMetadata {
+                target: entity,
+                source: None,
+            },
+            data: EffectData {
+                effect_type: EffectType::StatusInfliction(StatusTag::Injured),
+                duration: EffectDuration::TurnCount(INJURY_TURNS),
+            },
+        });
+        commands.entity(entity).insert(StatsDirty);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_the_downed_get_injured() {
+        let fine = carried_injury(true, 7., false);
+        assert_eq!(fine.health, Some(7.));
+        assert!(!fine.injured);
+
+        let downed = carried_injury(true, 0., false);
+        assert_eq!(downed.health, Some(1.));
+        assert!(downed.injured);
+
+        assert!(carried_injury(true, 5., true).injured);
+
+        let easy = carried_injury(false, 0., true);
+        assert_eq!(easy.health, None);
+        assert!(!easy.injured);
+    }
+}
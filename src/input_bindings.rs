@@ -0,0 +1,644 @@
+//! Remapping the controls.
+//!
+//! Every player id gets its own keyboard and gamepad `InputMap`, which are persisted in the
+//! [`InputBindings`] resource and handed out when a player joins. Players that never touched
+//! their controls just get the defaults from [`Player`].
+//!
+//! Each profile keeps its own copy of the bindings under [`INPUT_BINDINGS_KEY`], which gets
+//! swapped in when the profile is picked. A profile that hasn't saved any yet starts from
+//! whatever the device was last using.
+//!
+//! Only the button style actions can be remapped, the gamepad sticks always move the cursor and
+//! pan the camera.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use leafwing_input_manager::{prelude::*, user_input::Buttonlike};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assets::{
+        FontResource,
+        sounds::{SoundManagerParam, UiSound},
+    },
+    localization::localized_text,
+    menu::{
+        MenuCleanup,
+        menu_horizontal_selector::HorizontalSelector,
+        menu_navigation::{GameMenuController, GameMenuGrid, GameMenuLatch},
+        ui_consts::{SELECTABLE_BUTTON_BACKGROUND, UI_MENU_BACKGROUND, UI_TEXT_COLOR},
+    },
+    player::{MAX_PLAYERS, Player, PlayerInputAction},
+    tr,
+};
+
+/// Where a profile keeps its [`InputBindings`]
+pub const INPUT_BINDINGS_KEY: &str = "input-bindings";
+
+/// Everyone that can join, see [`Player::joinable`]
+pub const BINDABLE_PLAYER_IDS: [u32; MAX_PLAYERS as usize] = [1, 2, 3, 4];
+
+pub const REBINDABLE_ACTIONS: [PlayerInputAction; 14] = [
+    PlayerInputAction::MoveCursorUp,
+    PlayerInputAction::MoveCursorDown,
+    PlayerInputAction::MoveCursorLeft,
+    PlayerInputAction::MoveCursorRight,
+    PlayerInputAction::Select,
+    PlayerInputAction::Deselect,
+    PlayerInputAction::ZoomIn,
+    PlayerInputAction::ZoomOut,
+    PlayerInputAction::ToggleDangerZone,
+    PlayerInputAction::Inspect,
+    PlayerInputAction::Pause,
+    PlayerInputAction::ToggleBattleLog,
+    PlayerInputAction::Ping,
+    PlayerInputAction::RecenterCamera,
+];
+
+pub fn action_name(action: &PlayerInputAction) -> String {
+    let key = match action {
+        PlayerInputAction::MoveCursorUp => "action.cursor_up",
+        PlayerInputAction::MoveCursorDown => "action.cursor_down",
+        PlayerInputAction::MoveCursorLeft => "action.cursor_left",
+        PlayerInputAction::MoveCursorRight => "action.cursor_right",
+        PlayerInputAction::MoveCursor => "action.move_cursor",
+        PlayerInputAction::Select => "action.select",
+        PlayerInputAction::Deselect => "action.back",
+        PlayerInputAction::ZoomIn => "action.zoom_in",
+        PlayerInputAction::ZoomOut => "action.zoom_out",
+        PlayerInputAction::ToggleDangerZone => "action.danger_zone",
+        PlayerInputAction::Inspect => "action.inspect",
+        PlayerInputAction::Pause => "action.pause",
+        PlayerInputAction::ToggleBattleLog => "action.battle_log",
+        PlayerInputAction::Ping => "action.ping",
+        PlayerInputAction::PanCamera => "action.pan_camera",
+        PlayerInputAction::RecenterCamera => "action.recenter_camera",
+    };
+    tr!(key)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum BindingDevice {
+    Keyboard,
+    Gamepad,
+}
+
+impl BindingDevice {
+    pub const OPTIONS: [BindingDevice; 2] = [BindingDevice::Keyboard, BindingDevice::Gamepad];
+
+    pub fn name(&self) -> String {
+        match self {
+            BindingDevice::Keyboard => tr!("controls.device.keyboard"),
+            BindingDevice::Gamepad => tr!("controls.device.gamepad"),
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InputBindings {
+    /// Keyed by player id
+    keyboard: HashMap<u32, InputMap<PlayerInputAction>>,
+    /// Keyed by player id. These aren't tied to a gamepad until the player joins with one
+    gamepad: HashMap<u32, InputMap<PlayerInputAction>>,
+}
+
+impl InputBindings {
+    fn default_map(player: &Player, device: BindingDevice) -> InputMap<PlayerInputAction> {
+        match device {
+            BindingDevice::Keyboard => player.get_keyboard_input_map(),
+            BindingDevice::Gamepad => Player::get_default_gamepad_input_map(),
+        }
+    }
+
+    fn maps(&self, device: BindingDevice) -> &HashMap<u32, InputMap<PlayerInputAction>> {
+        match device {
+            BindingDevice::Keyboard => &self.keyboard,
+            BindingDevice::Gamepad => &self.gamepad,
+        }
+    }
+
+    fn maps_mut(
+        &mut self,
+        device: BindingDevice,
+    ) -> &mut HashMap<u32, InputMap<PlayerInputAction>> {
+        match device {
+            BindingDevice::Keyboard => &mut self.keyboard,
+            BindingDevice::Gamepad => &mut self.gamepad,
+        }
+    }
+
+    pub fn input_map(&self, player: &Player, device: BindingDevice) -> InputMap<PlayerInputAction> {
+        self.maps(device)
+            .get(&player.id())
+            .cloned()
+            .unwrap_or_else(|| Self::default_map(player, device))
+    }
+
+    pub fn keyboard_input_map(&self, player: &Player) -> InputMap<PlayerInputAction> {
+        self.input_map(player, BindingDevice::Keyboard)
+    }
+
+    pub fn gamepad_input_map(
+        &self,
+        player: &Player,
+        gamepad: Entity,
+    ) -> InputMap<PlayerInputAction> {
+        self.input_map(player, BindingDevice::Gamepad)
+            .with_gamepad(gamepad)
+    }
+
+    /// A readable version of whatever is bound to `action`, like "KeyW" or "South"
+    pub fn binding_text(
+        &self,
+        player: &Player,
+        device: BindingDevice,
+        action: &PlayerInputAction,
+    ) -> String {
+        let input_map = self.input_map(player, device);
+        match input_map.get_buttonlike(action) {
+            Some(bindings) if !bindings.is_empty() => bindings
+                .iter()
+                .map(|t| format!("{:?}", t))
+                .collect::<Vec<_>>()
+                .join(" / "),
+            _ => tr!("controls.unbound"),
+        }
+    }
+
+    /// Binds `input` to `action`, replacing whatever was there.
+    ///
+    /// If another action is already using `input`, nothing changes and the conflicting action is
+    /// returned instead.
+    pub fn rebind<T: Buttonlike + Clone>(
+        &mut self,
+        player: &Player,
+        device: BindingDevice,
+        action: PlayerInputAction,
+        input: T,
+    ) -> Result<(), PlayerInputAction> {
+        let boxed: Box<dyn Buttonlike> = Box::new(input.clone());
+        let input_map = self
+            .maps_mut(device)
+            .entry(player.id())
+            .or_insert_with(|| Self::default_map(player, device));
+
+        let conflict = REBINDABLE_ACTIONS.iter().find(|other| {
+            **other != action
+                && input_map
+                    .get_buttonlike(other)
+                    .is_some_and(|bindings| bindings.contains(&boxed))
+        });
+        if let Some(conflict) = conflict {
+            return Err(*conflict);
+        }
+
+        input_map.clear_action(&action);
+        input_map.insert(action, input);
+        Ok(())
+    }
+
+    pub fn reset_to_default(&mut self, player: &Player, device: BindingDevice) {
+        self.maps_mut(device).remove(&player.id());
+    }
+}
+
+/// The root of the controls menu.
+#[derive(Component)]
+pub struct ControlsMenu {
+    pub player_selector: Entity,
+    pub device_selector: Entity,
+    pub status_text: Entity,
+}
+
+impl ControlsMenu {
+    fn selected(
+        &self,
+        player_selectors: &Query<&HorizontalSelector<u32>>,
+        device_selectors: &Query<&HorizontalSelector<BindingDevice>>,
+    ) -> Option<(Player, BindingDevice)> {
+        let player = player_selectors
+            .get(self.player_selector)
+            .ok()
+            .and_then(|t| t.get_current())?;
+        let device = device_selectors
+            .get(self.device_selector)
+            .ok()
+            .and_then(|t| t.get_current())?;
+        Some((Player::PlayerId(player), device))
+    }
+}
+
+/// The controls menu is waiting for the next key or button press.
+///
+/// Nobody gets to navigate the menu while we're waiting, so the players are stashed here until
+/// the new binding comes in.
+#[derive(Component)]
+pub struct AwaitingRebind {
+    pub action: PlayerInputAction,
+    pub players: HashSet<Player>,
+}
+
+#[derive(Component)]
+pub enum ControlsMenuButtonAction {
+    Rebind(PlayerInputAction),
+    ResetToDefault,
+}
+
+#[derive(Component)]
+pub struct BindingPlayerSelector;
+
+#[derive(Component)]
+pub struct BindingDeviceSelector;
+
+/// The text showing what's currently bound to an action
+#[derive(Component)]
+pub struct BindingText {
+    pub action: PlayerInputAction,
+}
+
+pub fn build_controls_menu(commands: &mut Commands, font_resource: &FontResource) -> Entity {
+    let button_node = Node {
+        width: percent(70),
+        height: percent(5.1),
+        margin: UiRect::all(percent(0.3)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        border_radius: BorderRadius::all(percent(20)),
+        ..default()
+    };
+
+    let button_text_font = TextFont {
+        font_size: 18.0,
+        font: font_resource.pixelify_sans_regular.clone(),
+        ..default()
+    };
+
+    let player_selector = commands
+        .spawn((
+            Button,
+            button_node.clone(),
+            BindingPlayerSelector,
+            HorizontalSelector::new(&BINDABLE_PLAYER_IDS),
+            children![(
+                Text::default(),
+                BindingPlayerSelector,
+                button_text_font.clone()
+            )],
+        ))
+        .id();
+
+    let device_selector = commands
+        .spawn((
+            Button,
+            button_node.clone(),
+            BindingDeviceSelector,
+            HorizontalSelector::new(&BindingDevice::OPTIONS),
+            children![(
+                Text::default(),
+                BindingDeviceSelector,
+                button_text_font.clone()
+            )],
+        ))
+        .id();
+
+    let mut buttons = vec![player_selector, device_selector];
+    for action in REBINDABLE_ACTIONS.iter() {
+        let row = commands
+            .spawn((
+                Button,
+                button_node.clone(),
+                BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+                ControlsMenuButtonAction::Rebind(*action),
+                children![(
+                    Text::default(),
+                    BindingText { action: *action },
+                    button_text_font.clone(),
+                    TextColor(UI_TEXT_COLOR),
+                )],
+            ))
+            .id();
+        buttons.push(row);
+    }
+
+    let reset_button = commands
+        .spawn((
+            Button,
+            button_node.clone(),
+            BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+            ControlsMenuButtonAction::ResetToDefault,
+            children![(
+                localized_text("controls.reset_to_default"),
+                button_text_font.clone(),
+                TextColor(UI_TEXT_COLOR),
+            )],
+        ))
+        .id();
+    buttons.push(reset_button);
+
+    let status_text = commands
+        .spawn((
+            Text::default(),
+            button_text_font.clone(),
+            TextColor(UI_TEXT_COLOR),
+        ))
+        .id();
+
+    let mut controls_grid = GameMenuGrid::new_vertical();
+    controls_grid.push_buttons_to_stack(&buttons);
+
+    commands
+        .spawn((
+            Node {
+                display: Display::None,
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                width: percent(40),
+                height: percent(95),
+                border_radius: BorderRadius::all(percent(10)),
+                ..default()
+            },
+            BackgroundColor(UI_MENU_BACKGROUND),
+            children![(
+                localized_text("controls.title"),
+                TextFont {
+                    font_size: 30.0,
+                    font: font_resource.pixelify_sans_medium.clone(),
+                    ..default()
+                },
+                TextColor(UI_TEXT_COLOR),
+            )],
+            controls_grid,
+            GameMenuController {
+                players: HashSet::from([Player::PrePlayer]),
+            },
+            GameMenuLatch::default(),
+            ControlsMenu {
+                player_selector,
+                device_selector,
+                status_text,
+            },
+            MenuCleanup::Despawn,
+        ))
+        .add_children(&buttons)
+        .add_child(status_text)
+        .id()
+}
+
+pub fn display_binding_selector_text(
+    player_selectors: Query<
+        (&HorizontalSelector<u32>, &Children),
+        (
+            With<BindingPlayerSelector>,
+            Changed<HorizontalSelector<u32>>,
+        ),
+    >,
+    device_selectors: Query<
+        (&HorizontalSelector<BindingDevice>, &Children),
+        (
+            With<BindingDeviceSelector>,
+            Changed<HorizontalSelector<BindingDevice>>,
+        ),
+    >,
+    mut player_text: Query<
+        &mut Text,
+        (With<BindingPlayerSelector>, Without<BindingDeviceSelector>),
+    >,
+    mut device_text: Query<
+        &mut Text,
+        (With<BindingDeviceSelector>, Without<BindingPlayerSelector>),
+    >,
+) {
+    for (selector, children) in player_selectors {
+        if let Some(id) = selector.get_current() {
+            for child in children {
+                if let Ok(mut text) = player_text.get_mut(*child) {
+                    text.0 = tr!("controls.player_selector", player = id);
+                }
+            }
+        }
+    }
+
+    for (selector, children) in device_selectors {
+        if let Some(device) = selector.get_current() {
+            for child in children {
+                if let Ok(mut text) = device_text.get_mut(*child) {
+                    text.0 = tr!("controls.device_selector", device = device.name());
+                }
+            }
+        }
+    }
+}
+
+pub fn display_binding_text(
+    bindings: Res<InputBindings>,
+    menus: Query<(&ControlsMenu, Option<&AwaitingRebind>, &Children)>,
+    player_selectors: Query<&HorizontalSelector<u32>>,
+    device_selectors: Query<&HorizontalSelector<BindingDevice>>,
+    rows: Query<&Children, With<ControlsMenuButtonAction>>,
+    mut texts: Query<(&mut Text, &BindingText)>,
+) {
+    for (menu, awaiting, menu_children) in menus {
+        let Some((player, device)) = menu.selected(&player_selectors, &device_selectors) else {
+            continue;
+        };
+
+        for row_children in rows.iter_many(menu_children) {
+            for child in row_children {
+                let Ok((mut text, binding)) = texts.get_mut(*child) else {
+                    continue;
+                };
+
+                let value = if awaiting.is_some_and(|t| t.action == binding.action) {
+                    "...".to_string()
+                } else {
+                    bindings.binding_text(&player, device, &binding.action)
+                };
+                let line = format!("{}: {}", action_name(&binding.action), value);
+                if text.0 != line {
+                    text.0 = line;
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn controls_menu_action(
+    mut click: On<Pointer<Click>>,
+    mut commands: Commands,
+    buttons: Query<(&ControlsMenuButtonAction, &ChildOf), With<Button>>,
+    mut menus: Query<(&ControlsMenu, &mut GameMenuController), Without<AwaitingRebind>>,
+    player_selectors: Query<&HorizontalSelector<u32>>,
+    device_selectors: Query<&HorizontalSelector<BindingDevice>>,
+    mut texts: Query<&mut Text>,
+    mut bindings: ResMut<InputBindings>,
+    sounds: SoundManagerParam,
+) {
+    let Ok((action, parent)) = buttons.get(click.entity) else {
+        return;
+    };
+    click.propagate(false);
+
+    let menu_entity = parent.parent();
+    let Ok((menu, mut controller)) = menus.get_mut(menu_entity) else {
+        return;
+    };
+    let Some((player, device)) = menu.selected(&player_selectors, &device_selectors) else {
+        error!("No player or device selected in the controls menu?");
+        return;
+    };
+
+    let status = match action {
+        ControlsMenuButtonAction::Rebind(action) => {
+            commands.entity(menu_entity).insert(AwaitingRebind {
+                action: *action,
+                players: std::mem::take(&mut controller.players),
+            });
+            match device {
+                BindingDevice::Keyboard => {
+                    tr!("controls.press_key", action = action_name(action))
+                }
+                BindingDevice::Gamepad => {
+                    tr!("controls.press_button", action = action_name(action))
+                }
+            }
+        }
+        ControlsMenuButtonAction::ResetToDefault => {
+            bindings.reset_to_default(&player, device);
+            info!("Reset {:?} {:?} bindings to default", player, device);
+            tr!(
+                "controls.reset",
+                player = player.id(),
+                device = device.name()
+            )
+        }
+    };
+
+    if let Ok(mut text) = texts.get_mut(menu.status_text) {
+        text.0 = status;
+    }
+    sounds.play_ui_sound(&mut commands, UiSound::Select);
+}
+
+/// Grab the next key or button press for whatever action the controls menu is waiting on.
+///
+/// Escape cancels, and so does Back for whoever's in the menu, so there's a way out on a gamepad
+/// too. Needs to run before menu navigation, otherwise the press that opened the prompt would get
+/// picked up as the new binding.
+#[allow(clippy::too_many_arguments)]
+pub fn capture_rebind_input(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    player_actions: Query<(&Player, &ActionState<PlayerInputAction>)>,
+    mut bindings: ResMut<InputBindings>,
+    mut menus: Query<(
+        Entity,
+        &ControlsMenu,
+        &AwaitingRebind,
+        &mut GameMenuController,
+    )>,
+    player_selectors: Query<&HorizontalSelector<u32>>,
+    device_selectors: Query<&HorizontalSelector<BindingDevice>>,
+    mut texts: Query<&mut Text>,
+    sounds: SoundManagerParam,
+) {
+    for (menu_entity, menu, awaiting, mut controller) in menus.iter_mut() {
+        let Some((player, device)) = menu.selected(&player_selectors, &device_selectors) else {
+            continue;
+        };
+
+        let backed_out = player_actions.iter().any(|(player, actions)| {
+            awaiting.players.contains(player) && actions.just_pressed(&PlayerInputAction::Deselect)
+        });
+        let status = if keyboard_input.just_pressed(KeyCode::Escape) || backed_out {
+            sounds.play_ui_sound(&mut commands, UiSound::CloseMenu);
+            String::new()
+        } else {
+            let result = match device {
+                BindingDevice::Keyboard => keyboard_input
+                    .get_just_pressed()
+                    .next()
+                    .map(|key| bindings.rebind(&player, device, awaiting.action, *key)),
+                BindingDevice::Gamepad => gamepads
+                    .iter()
+                    .flat_map(|t| t.get_just_pressed())
+                    .next()
+                    .map(|button| bindings.rebind(&player, device, awaiting.action, *button)),
+            };
+
+            match result {
+                None => continue,
+                Some(Ok(())) => {
+                    info!(
+                        "Bound {:?} for {:?} to {}",
+                        awaiting.action,
+                        player,
+                        bindings.binding_text(&player, device, &awaiting.action)
+                    );
+                    sounds.play_ui_sound(&mut commands, UiSound::Select);
+                    String::new()
+                }
+                Some(Err(conflict)) => {
+                    sounds.play_ui_sound(&mut commands, UiSound::Error);
+                    tr!("controls.conflict", action = action_name(&conflict))
+                }
+            }
+        };
+
+        if let Ok(mut text) = texts.get_mut(menu.status_text) {
+            text.0 = status;
+        }
+        controller.players = awaiting.players.clone();
+        commands.entity(menu_entity).remove::<AwaitingRebind>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebind_refuses_inputs_used_elsewhere() {
+        let mut bindings = InputBindings::default();
+        let player = Player::PlayerId(1);
+        let before = bindings.input_map(&player, BindingDevice::Gamepad);
+
+        // East is Back by default
+        let result = bindings.rebind(
+            &player,
+            BindingDevice::Gamepad,
+            PlayerInputAction::Select,
+            GamepadButton::East,
+        );
+
+        assert_eq!(result, Err(PlayerInputAction::Deselect));
+        assert_eq!(bindings.input_map(&player, BindingDevice::Gamepad), before);
+    }
+
+    #[test]
+    fn test_reset_to_default() {
+        let mut bindings = InputBindings::default();
+        let player = Player::PlayerId(2);
+
+        bindings
+            .rebind(
+                &player,
+                BindingDevice::Keyboard,
+                PlayerInputAction::Ping,
+                KeyCode::KeyP,
+            )
+            .expect("Nothing else should be using P");
+        assert_ne!(
+            bindings.keyboard_input_map(&player),
+            player.get_keyboard_input_map()
+        );
+
+        bindings.reset_to_default(&player, BindingDevice::Keyboard);
+        assert_eq!(
+            bindings.keyboard_input_map(&player),
+            player.get_keyboard_input_map()
+        );
+    }
+}
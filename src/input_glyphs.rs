@@ -0,0 +1,521 @@
+//! Button glyphs for input prompts.
+//!
+//! Every player's prompts follow whatever they last touched, so someone on the keyboard sees
+//! keycaps, and a PlayStation controller gets its shapes instead of Xbox letters. The glyphs all
+//! live in one icon atlas, with any lettering drawn on top as text.
+//!
+//! Spawn an [`InputPrompt`] to get a line of text with glyphs mixed in.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use leafwing_input_manager::{prelude::*, user_input::Buttonlike};
+
+use crate::{
+    assets::INPUT_GLYPHS_PATH,
+    input_bindings::BindingDevice,
+    localization::{LanguageSettings, apply_language_settings, translate},
+    menu::ui_consts::UI_TEXT_COLOR,
+    player::{Player, PlayerInputAction},
+};
+
+const GLYPH_TILE_SIZE: u32 = 16;
+const GLYPH_ATLAS_COLUMNS: usize = 12;
+const GLYPH_ATLAS_ROWS: usize = 4;
+
+// The first row of the atlas is for the keyboard, and anything every gamepad shares
+const KEYCAP: usize = 0;
+const ROUND_BUTTON: usize = 3;
+// These two are twice as wide as everything else, so they're added to the layout by hand
+const WIDE_KEYCAP: usize = GLYPH_ATLAS_COLUMNS * GLYPH_ATLAS_ROWS;
+const WIDE_PILL: usize = WIDE_KEYCAP + 1;
+
+/// Every button we know how to draw, in the order we look for them in a binding
+pub const GAMEPAD_BUTTONS: [GamepadButton; 19] = [
+    GamepadButton::South,
+    GamepadButton::East,
+    GamepadButton::West,
+    GamepadButton::North,
+    GamepadButton::LeftTrigger,
+    GamepadButton::RightTrigger,
+    GamepadButton::LeftTrigger2,
+    GamepadButton::RightTrigger2,
+    GamepadButton::DPadUp,
+    GamepadButton::DPadDown,
+    GamepadButton::DPadLeft,
+    GamepadButton::DPadRight,
+    GamepadButton::Start,
+    GamepadButton::Select,
+    GamepadButton::LeftThumb,
+    GamepadButton::RightThumb,
+    GamepadButton::Mode,
+    GamepadButton::C,
+    GamepadButton::Z,
+];
+
+/// Which family of gamepad glyphs to draw
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum GlyphStyle {
+    #[default]
+    Xbox,
+    PlayStation,
+    SteamDeck,
+}
+
+impl GlyphStyle {
+    const SONY_VENDOR_ID: u16 = 0x054C;
+    const VALVE_VENDOR_ID: u16 = 0x28DE;
+
+    /// Anything we don't recognize gets Xbox glyphs, since that's what most pads copy
+    pub fn from_gamepad(gamepad: &Gamepad) -> Self {
+        match gamepad.vendor_id() {
+            Some(Self::SONY_VENDOR_ID) => GlyphStyle::PlayStation,
+            Some(Self::VALVE_VENDOR_ID) => GlyphStyle::SteamDeck,
+            _ => GlyphStyle::Xbox,
+        }
+    }
+
+    fn atlas_row(&self) -> usize {
+        match self {
+            GlyphStyle::Xbox => 1,
+            GlyphStyle::PlayStation => 2,
+            GlyphStyle::SteamDeck => 3,
+        }
+    }
+}
+
+/// What a player is currently using to play.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ActiveInputDevice {
+    pub device: BindingDevice,
+    /// The last gamepad the player touched, even if they've since gone back to the keyboard
+    pub gamepad_style: GlyphStyle,
+}
+
+impl Default for ActiveInputDevice {
+    fn default() -> Self {
+        Self {
+            device: BindingDevice::Keyboard,
+            gamepad_style: GlyphStyle::default(),
+        }
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct ActiveInputDevices(pub HashMap<Player, ActiveInputDevice>);
+
+impl ActiveInputDevices {
+    pub fn get(&self, player: &Player) -> ActiveInputDevice {
+        self.0.get(player).copied().unwrap_or_default()
+    }
+}
+
+#[derive(Resource)]
+pub struct InputGlyphAtlas {
+    pub image: Handle<Image>,
+    pub layout: Handle<TextureAtlasLayout>,
+}
+
+/// One icon out of the glyph atlas.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ButtonGlyph {
+    pub index: usize,
+    pub wide: bool,
+    /// Drawn over the icon
+    pub label: Option<String>,
+}
+
+impl ButtonGlyph {
+    fn keycap(label: String) -> Self {
+        let wide = label.chars().count() > 1;
+        Self {
+            index: if wide { WIDE_KEYCAP } else { KEYCAP },
+            wide,
+            label: Some(label),
+        }
+    }
+
+    pub fn for_key(key: &KeyCode) -> Self {
+        Self::keycap(key_label(&format!("{:?}", key)))
+    }
+
+    pub fn for_button(button: GamepadButton, style: GlyphStyle) -> Self {
+        let row_start = style.atlas_row() * GLYPH_ATLAS_COLUMNS;
+        let (index, wide) = match button {
+            GamepadButton::South => (row_start, false),
+            GamepadButton::East => (row_start + 1, false),
+            GamepadButton::West => (row_start + 2, false),
+            GamepadButton::North => (row_start + 3, false),
+            GamepadButton::LeftTrigger | GamepadButton::RightTrigger => (row_start + 4, false),
+            GamepadButton::LeftTrigger2 | GamepadButton::RightTrigger2 => (row_start + 5, false),
+            GamepadButton::DPadUp => (row_start + 6, false),
+            GamepadButton::DPadDown => (row_start + 7, false),
+            GamepadButton::DPadLeft => (row_start + 8, false),
+            GamepadButton::DPadRight => (row_start + 9, false),
+            GamepadButton::Start | GamepadButton::Select => (WIDE_PILL, true),
+            _ => (ROUND_BUTTON, false),
+        };
+
+        Self {
+            index,
+            wide,
+            label: button_label(button, style).map(String::from),
+        }
+    }
+
+    /// Keycaps are light, so they need dark lettering
+    fn label_color(&self) -> Color {
+        if matches!(self.index, KEYCAP | WIDE_KEYCAP) {
+            Color::BLACK
+        } else {
+            UI_TEXT_COLOR
+        }
+    }
+}
+
+/// The PlayStation face buttons are drawn as shapes, and the d-pad doesn't need lettering.
+fn button_label(button: GamepadButton, style: GlyphStyle) -> Option<&'static str> {
+    use GlyphStyle::*;
+
+    let label = match (button, style) {
+        (
+            GamepadButton::South | GamepadButton::East | GamepadButton::West | GamepadButton::North,
+            PlayStation,
+        ) => return None,
+        (GamepadButton::South, _) => "A",
+        (GamepadButton::East, _) => "B",
+        (GamepadButton::West, _) => "X",
+        (GamepadButton::North, _) => "Y",
+        (GamepadButton::LeftTrigger, Xbox) => "LB",
+        (GamepadButton::RightTrigger, Xbox) => "RB",
+        (GamepadButton::LeftTrigger2, Xbox) => "LT",
+        (GamepadButton::RightTrigger2, Xbox) => "RT",
+        (GamepadButton::LeftTrigger, _) => "L1",
+        (GamepadButton::RightTrigger, _) => "R1",
+        (GamepadButton::LeftTrigger2, _) => "L2",
+        (GamepadButton::RightTrigger2, _) => "R2",
+        (
+            GamepadButton::DPadUp
+            | GamepadButton::DPadDown
+            | GamepadButton::DPadLeft
+            | GamepadButton::DPadRight,
+            _,
+        ) => return None,
+        (GamepadButton::Start, PlayStation) => "Options",
+        (GamepadButton::Select, PlayStation) => "Share",
+        (GamepadButton::Start, _) => "Menu",
+        (GamepadButton::Select, _) => "View",
+        (GamepadButton::LeftThumb, Xbox) => "LS",
+        (GamepadButton::RightThumb, Xbox) => "RS",
+        (GamepadButton::LeftThumb, _) => "L3",
+        (GamepadButton::RightThumb, _) => "R3",
+        (GamepadButton::Mode, Xbox) => "Guide",
+        (GamepadButton::Mode, PlayStation) => "PS",
+        (GamepadButton::Mode, SteamDeck) => "Steam",
+        (GamepadButton::C, _) => "C",
+        (GamepadButton::Z, _) => "Z",
+        (GamepadButton::Other(_), _) => "?",
+    };
+    Some(label)
+}
+
+/// Turns a `KeyCode`'s debug name into something that fits on a keycap, like "KeyW" into "W"
+pub fn key_label(debug_name: &str) -> String {
+    let label = match debug_name {
+        "Escape" => "Esc",
+        "ShiftLeft" | "ShiftRight" => "Shift",
+        "ControlLeft" | "ControlRight" => "Ctrl",
+        "AltLeft" | "AltRight" => "Alt",
+        "Backspace" => "Bksp",
+        name => name
+            .strip_prefix("Key")
+            .or_else(|| name.strip_prefix("Digit"))
+            .or_else(|| name.strip_prefix("Arrow"))
+            .unwrap_or(name),
+    };
+    label.to_string()
+}
+
+fn is_gamepad_button(input: &dyn Buttonlike) -> bool {
+    GAMEPAD_BUTTONS
+        .iter()
+        .any(|button| *input == *(button as &dyn Buttonlike))
+}
+
+/// The glyph for whatever is bound to `action` on the device the player is using
+pub fn action_glyph(
+    input_map: &InputMap<PlayerInputAction>,
+    action: &PlayerInputAction,
+    device: ActiveInputDevice,
+) -> Option<ButtonGlyph> {
+    let bindings = input_map.get_buttonlike(action)?;
+    match device.device {
+        BindingDevice::Gamepad => GAMEPAD_BUTTONS
+            .iter()
+            .find(|button| {
+                let boxed: Box<dyn Buttonlike> = Box::new(**button);
+                bindings.contains(&boxed)
+            })
+            .map(|button| ButtonGlyph::for_button(*button, device.gamepad_style)),
+        BindingDevice::Keyboard => bindings
+            .iter()
+            .find(|t| !is_gamepad_button(t.as_ref()))
+            .map(|t| ButtonGlyph::keycap(key_label(&format!("{:?}", t)))),
+    }
+}
+
+/// Something to show in place of a `{name}` in an [`InputPrompt`]
+#[derive(Clone, Debug)]
+pub enum PromptGlyph {
+    /// Whatever the player has bound to the action, on the device they're using
+    Action(PlayerInputAction),
+    /// A specific key, no matter what the player is using
+    Key(KeyCode),
+    /// Specific gamepad buttons, in the style of the player's last gamepad
+    Buttons(Vec<GamepadButton>),
+}
+
+/// A localized line of text with button glyphs mixed in.
+///
+/// `key` is looked up like any other UI string, and every `{name}` in it is swapped out for the
+/// matching glyph. The whole line gets rebuilt when the player switches devices.
+#[derive(Component, Clone, Debug)]
+#[require(Node)]
+pub struct InputPrompt {
+    pub key: &'static str,
+    pub player: Player,
+    pub glyphs: Vec<(&'static str, PromptGlyph)>,
+    pub font: TextFont,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PromptSegment<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+fn split_placeholders(text: &str) -> Vec<PromptSegment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|t| t + start) else {
+            break;
+        };
+        if start > 0 {
+            segments.push(PromptSegment::Text(&rest[..start]));
+        }
+        segments.push(PromptSegment::Placeholder(&rest[start + 1..end]));
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        segments.push(PromptSegment::Text(rest));
+    }
+    segments
+}
+
+pub fn input_glyphs_plugin(app: &mut App) {
+    app.init_resource::<ActiveInputDevices>()
+        .add_systems(Startup, setup_input_glyph_atlas)
+        .add_systems(
+            Update,
+            (
+                track_active_input_devices,
+                refresh_input_prompts.after(apply_language_settings),
+            )
+                .chain(),
+        );
+}
+
+fn setup_input_glyph_atlas(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    let mut layout = TextureAtlasLayout::from_grid(
+        UVec2::splat(GLYPH_TILE_SIZE),
+        GLYPH_ATLAS_COLUMNS as u32,
+        GLYPH_ATLAS_ROWS as u32,
+        None,
+        None,
+    );
+    let wide_keycap = layout.add_texture(URect::new(16, 0, 48, 16));
+    let wide_pill = layout.add_texture(URect::new(64, 0, 96, 16));
+    debug_assert_eq!((wide_keycap, wide_pill), (WIDE_KEYCAP, WIDE_PILL));
+
+    commands.insert_resource(InputGlyphAtlas {
+        image: asset_server.load(INPUT_GLYPHS_PATH),
+        layout: texture_atlas_layouts.add(layout),
+    });
+}
+
+/// Keeps track of which device each player last pressed something on.
+pub fn track_active_input_devices(
+    mut devices: ResMut<ActiveInputDevices>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    players: Query<(&Player, &InputMap<PlayerInputAction>)>,
+) {
+    let keyboard_used = keyboard_input.get_just_pressed().next().is_some();
+    let gamepad_used = gamepads
+        .iter()
+        .find(|t| t.get_just_pressed().next().is_some());
+
+    for (player, input_map) in players {
+        let current = devices.get(player);
+        let mut next = current;
+        match (player, input_map.gamepad()) {
+            // Tied to one gamepad, so that's all they could be using
+            (_, Some(gamepad)) => {
+                if let Ok(gamepad) = gamepads.get(gamepad) {
+                    next.device = BindingDevice::Gamepad;
+                    next.gamepad_style = GlyphStyle::from_gamepad(gamepad);
+                }
+            }
+            // The PrePlayer listens to everything
+            (Player::PrePlayer, None) => {
+                if let Some(gamepad) = gamepad_used {
+                    next.device = BindingDevice::Gamepad;
+                    next.gamepad_style = GlyphStyle::from_gamepad(gamepad);
+                } else if keyboard_used {
+                    next.device = BindingDevice::Keyboard;
+                }
+            }
+            (Player::PlayerId(_), None) => next.device = BindingDevice::Keyboard,
+        }
+
+        // Only touch the resource on an actual change, every prompt gets rebuilt when it does
+        if next != current || !devices.0.contains_key(player) {
+            devices.0.insert(*player, next);
+        }
+    }
+}
+
+fn glyph_bundle(atlas: &InputGlyphAtlas, font: &TextFont, glyph: ButtonGlyph) -> impl Bundle {
+    let label_color = glyph.label_color();
+    (
+        Node {
+            width: px(if glyph.wide { 64 } else { 32 }),
+            height: px(32),
+            margin: UiRect::horizontal(px(4)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        ImageNode::from_atlas_image(
+            atlas.image.clone(),
+            TextureAtlas {
+                layout: atlas.layout.clone(),
+                index: glyph.index,
+            },
+        ),
+        children![(
+            Text::new(glyph.label.unwrap_or_default()),
+            TextFont {
+                font_size: 14.,
+                ..font.clone()
+            },
+            TextColor(label_color),
+        )],
+    )
+}
+
+pub fn refresh_input_prompts(
+    mut commands: Commands,
+    devices: Res<ActiveInputDevices>,
+    language: Res<LanguageSettings>,
+    atlas: Res<InputGlyphAtlas>,
+    input_maps: Query<(&Player, &InputMap<PlayerInputAction>)>,
+    prompts: Query<(Entity, Ref<InputPrompt>)>,
+) {
+    let refresh_all = devices.is_changed() || language.is_changed();
+
+    for (entity, prompt) in prompts {
+        if !refresh_all && !prompt.is_changed() {
+            continue;
+        }
+
+        let device = devices.get(&prompt.player);
+        let input_map = input_maps
+            .iter()
+            .find(|(p, _)| **p == prompt.player)
+            .map(|(_, t)| t);
+
+        commands.entity(entity).despawn_children();
+        for segment in split_placeholders(&translate(prompt.key)) {
+            let name = match segment {
+                PromptSegment::Text(text) => {
+                    commands.entity(entity).with_child((
+                        Text::new(text),
+                        prompt.font.clone(),
+                        TextColor(UI_TEXT_COLOR),
+                    ));
+                    continue;
+                }
+                PromptSegment::Placeholder(name) => name,
+            };
+
+            let Some((_, glyph)) = prompt.glyphs.iter().find(|(t, _)| *t == name) else {
+                warn!("No glyph for {{{}}} in {:?}", name, prompt.key);
+                continue;
+            };
+
+            let glyphs = match glyph {
+                PromptGlyph::Action(action) => input_map
+                    .and_then(|t| action_glyph(t, action, device))
+                    .into_iter()
+                    .collect(),
+                PromptGlyph::Key(key) => vec![ButtonGlyph::for_key(key)],
+                PromptGlyph::Buttons(buttons) => buttons
+                    .iter()
+                    .map(|t| ButtonGlyph::for_button(*t, device.gamepad_style))
+                    .collect::<Vec<_>>(),
+            };
+
+            for glyph in glyphs {
+                commands
+                    .entity(entity)
+                    .with_child(glyph_bundle(&atlas, &prompt.font, glyph));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_placeholders() {
+        assert_eq!(
+            split_placeholders("Press {keyboard} or {gamepad}!"),
+            vec![
+                PromptSegment::Text("Press "),
+                PromptSegment::Placeholder("keyboard"),
+                PromptSegment::Text(" or "),
+                PromptSegment::Placeholder("gamepad"),
+                PromptSegment::Text("!"),
+            ]
+        );
+        assert_eq!(
+            split_placeholders("{select}"),
+            vec![PromptSegment::Placeholder("select")]
+        );
+    }
+
+    #[test]
+    fn test_glyph_labels() {
+        assert_eq!(
+            ButtonGlyph::for_key(&KeyCode::KeyJ).label.as_deref(),
+            Some("J")
+        );
+        assert!(ButtonGlyph::for_key(&KeyCode::Space).wide);
+        assert_eq!(
+            ButtonGlyph::for_button(GamepadButton::LeftTrigger, GlyphStyle::Xbox).label,
+            Some("LB".to_string())
+        );
+        assert_eq!(
+            ButtonGlyph::for_button(GamepadButton::South, GlyphStyle::PlayStation).label,
+            None
+        );
+    }
+}
@@ -6,6 +6,7 @@ use crate::{
     assets::FontResource,
     battle_menu::{BattleMenuAction, BattlePlayerUI, UnitMenuAction, battle_ui_button},
     grid::GridPosition,
+    loot::{LootFoundMessage, LootTableId},
     menu::menu_navigation::{GameMenuGrid, MenuGridPosition},
     player::Player,
     unit::{
@@ -37,12 +38,31 @@ pub struct InteractionButton {
     menu_position: MenuGridPosition,
 }
 
-/// An example interactable for now.
+/// Rolls its loot table when opened, see [`crate::loot`]
 #[derive(Component, Debug)]
 #[require(Interactable, InteractionMenuLabel {
     label: "Open Chest"
 })]
-pub struct TreasureChest;
+pub struct TreasureChest {
+    pub loot_table: LootTableId,
+}
+
+/// Heals the party in a rest room, and then burns out. See [`crate::rest_room`]
+#[derive(Component, Debug)]
+#[require(Interactable, InteractionMenuLabel {
+    label: "Rest"
+})]
+pub struct Campfire {
+    /// How much of everyone's max health comes back, from 0 to 1
+    pub heal_percent: f32,
+}
+
+/// Opens the shop, see [`crate::shop`]
+#[derive(Component, Debug)]
+#[require(Interactable, InteractionMenuLabel {
+    label: "Shop"
+})]
+pub struct Vendor;
 
 /// Another example interactable
 #[derive(Component, Debug)]
@@ -67,6 +87,7 @@ pub fn handle_interactions(
     mut commands: Commands,
     mut message_reader: MessageReader<UnitExecuteActionMessage>,
     mut message_writer: MessageWriter<UnitActionCompletedMessage>,
+    mut loot_writer: MessageWriter<LootFoundMessage>,
     query: Query<(Option<&ObtainableItem>, Option<&TreasureChest>), With<Interactable>>,
 ) {
     for message in message_reader.read() {
@@ -93,6 +114,10 @@ pub fn handle_interactions(
             }
             (None, Some(t)) => {
                 info!("Opened Treasure Chest: {:?}", t);
+                loot_writer.write(LootFoundMessage {
+                    unit: message.entity,
+                    drop: t.loot_table.roll(&mut rand::rng()),
+                });
             }
             otherwise => {
                 error!("Invalid pair for interaction type: {:?}", otherwise);
@@ -0,0 +1,262 @@
+//! The bag the whole party shares over a run.
+//!
+//! Anything nobody is wearing goes in the [`PartyInventory`]: equipment that came out of a chest,
+//! got bought in a [shop](crate::shop), or got swapped off somebody in a
+//! [rest room](crate::rest_room), along with [`Consumable`]s, which stack. The bag rides along in
+//! the run save like the party's gold does, and is gone once the run is over.
+//!
+//! Consumables get used from the battle menu, on anyone within the item's range. What each one
+//! does lives in the [`ItemDB`].
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState,
+    battle_log::BattleLogMessage,
+    battle_phase::{ActionCost, UnitPhaseResources},
+    dungeon::DungeonState,
+    equipment::{ItemDB, ItemId, UseEffect},
+    gameplay_effects::ActiveEffects,
+    run_save::PendingRunRestore,
+    tr,
+    unit::{UnitAction, UnitActionCompletedMessage, UnitExecuteAction, UnitExecuteActionMessage},
+    unit_stats::{StatType, StatValue, StatsDirty, UnitStatChangeRequest},
+};
+
+/// What it takes to use a consumable in battle
+pub const USE_CONSUMABLE_COST: ActionCost = ActionCost {
+    move_actions: 0,
+    minor_actions: 0,
+    action_points: 1,
+};
+
+/// Something that gets used up, rather than worn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Consumable {
+    Potion,
+    Antidote,
+    ThrowingKnife,
+}
+
+impl Consumable {
+    pub const ALL: [Consumable; 3] = [
+        Consumable::Potion,
+        Consumable::Antidote,
+        Consumable::ThrowingKnife,
+    ];
+
+    pub fn name_key(&self) -> &'static str {
+        match self {
+            Consumable::Potion => "consumable.potion",
+            Consumable::Antidote => "consumable.antidote",
+            Consumable::ThrowingKnife => "consumable.throwing_knife",
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PartyInventory {
+    /// Equipment, in the order it went into the bag
+    pub items: Vec<ItemId>,
+    /// How many of each consumable the party has. Anything they've run out of isn't in here.
+    #[serde(default)]
+    pub consumables: BTreeMap<Consumable, u32>,
+}
+
+impl PartyInventory {
+    pub fn add_item(&mut self, item: ItemId) {
+        self.items.push(item);
+    }
+
+    /// Takes one of the item out of the bag, if the party has one
+    pub fn remove_item(&mut self, item: ItemId) -> bool {
+        let Some(index) = self.items.iter().position(|t| *t == item) else {
+            return false;
+        };
+        self.items.remove(index);
+        true
+    }
+
+    /// Whatever's been in the bag the longest
+    pub fn next_item(&self) -> Option<ItemId> {
+        self.items.first().copied()
+    }
+
+    /// Takes out whichever one of the item went in first, however it rolled, and says which it was
+    pub fn remove_item_of_kind(&mut self, item: ItemId) -> Option<ItemId> {
+        let index = self.items.iter().position(|t| t.base() == item.base())?;
+        Some(self.items.remove(index))
+    }
+
+    /// How many of the item the bag has, however they rolled
+    pub fn item_count(&self, item: ItemId) -> usize {
+        self.items
+            .iter()
+            .filter(|t| t.base() == item.base())
+            .count()
+    }
+
+    pub fn add_consumable(&mut self, consumable: Consumable, count: u32) {
+        if count > 0 {
+            *self.consumables.entry(consumable).or_default() += count;
+        }
+    }
+
+    /// Uses up one of the consumable, if the party has any left
+    pub fn remove_consumable(&mut self, consumable: Consumable) -> bool {
+        let Some(count) = self.consumables.get_mut(&consumable) else {
+            return false;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.consumables.remove(&consumable);
+        }
+        true
+    }
+
+    pub fn consumable_count(&self, consumable: Consumable) -> u32 {
+        self.consumables
+            .get(&consumable)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+pub fn inventory_plugin(app: &mut App) {
+    app.add_systems(OnEnter(GameState::Dungeon), init_party_inventory)
+        .add_systems(
+            Update,
+            use_consumables.run_if(in_state(DungeonState::InBattle)),
+        );
+}
+
+/// A continued run still has whatever was in the bag, including anything saved before the bag
+/// had its own place in the save
+fn init_party_inventory(mut commands: Commands, pending_restore: Option<Res<PendingRunRestore>>) {
+    let inventory = pending_restore
+        .map(|t| {
+            let mut inventory = t.0.inventory.clone();
+            inventory.items.extend(&t.0.loot.legacy_items);
+            inventory
+        })
+        .unwrap_or_default();
+    commands.insert_resource(inventory);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn use_consumables(
+    mut commands: Commands,
+    mut reader: MessageReader<UnitExecuteActionMessage>,
+    item_db: Res<ItemDB>,
+    inventory: Option<ResMut<PartyInventory>>,
+    mut resources: Query<&mut UnitPhaseResources>,
+    mut effects: Query<&mut ActiveEffects>,
+    mut stat_change_writer: MessageWriter<UnitStatChangeRequest>,
+    mut battle_log: MessageWriter<BattleLogMessage>,
+    mut completed_writer: MessageWriter<UnitActionCompletedMessage>,
+) {
+    let Some(mut inventory) = inventory else {
+        return;
+    };
+
+    for message in reader.read() {
+        let UnitExecuteAction::UseItem { item, target } = message.action else {
+            continue;
+        };
+
+        if !inventory.remove_consumable(item) {
+            warn!("The party is out of {:?}", item);
+            continue;
+        }
+
+        if let Ok(mut resources) = resources.get_mut(message.entity) {
+            resources.spend(&USE_CONSUMABLE_COST);
+        }
+
+        let health_change = match item_db.consumable(&item).use_effect {
+            UseEffect::Heal(amount) => Some(amount),
+            UseEffect::Damage(amount) => Some(-amount),
+            UseEffect::Cure(status) => {
+                if let Ok(mut effects) = effects.get_mut(target)
+                    && effects.cure(status)
+                {
+                    commands.entity(target).insert(StatsDirty);
+                }
+                None
+            }
+        };
+        if let Some(health_change) = health_change {
+            stat_change_writer.write(UnitStatChangeRequest {
+                entity: target,
+                stat: StatType::Health,
+                stat_change: StatValue(health_change),
+            });
+        }
+
+        battle_log.write(BattleLogMessage::ItemUsed {
+            user: message.entity,
+            target,
+            item: tr!(item.name_key()),
+        });
+        completed_writer.write(UnitActionCompletedMessage {
+            unit: message.entity,
+            action: UnitAction::UseItem,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equipment::{ItemRoll, Rarity};
+
+    #[test]
+    fn test_consumables_stack_and_run_out() {
+        let mut inventory = PartyInventory::default();
+        inventory.add_consumable(Consumable::Potion, 2);
+        inventory.add_consumable(Consumable::Antidote, 0);
+        assert_eq!(inventory.consumable_count(Consumable::Potion), 2);
+        assert!(!inventory.consumables.contains_key(&Consumable::Antidote));
+
+        assert!(inventory.remove_consumable(Consumable::Potion));
+        assert!(inventory.remove_consumable(Consumable::Potion));
+        assert!(!inventory.remove_consumable(Consumable::Potion));
+        assert!(inventory.consumables.is_empty());
+    }
+
+    #[test]
+    fn test_items_come_out_one_at_a_time() {
+        let mut inventory = PartyInventory::default();
+        inventory.add_item(ItemId(2));
+        inventory.add_item(ItemId(1));
+        inventory.add_item(ItemId(2));
+
+        assert!(inventory.remove_item(ItemId(2)));
+        assert_eq!(inventory.items, vec![ItemId(1), ItemId(2)]);
+        assert_eq!(inventory.item_count(ItemId(2)), 1);
+        assert!(!inventory.remove_item(ItemId(3)));
+        assert_eq!(inventory.next_item(), Some(ItemId(1)));
+    }
+
+    #[test]
+    fn test_rolled_items_count_as_their_kind() {
+        let rolled = ItemId(2).with_roll(ItemRoll {
+            rarity: Rarity::Rare,
+            quality: 40,
+        });
+        let mut inventory = PartyInventory::default();
+        inventory.add_item(rolled);
+        inventory.add_item(ItemId(2));
+
+        assert_eq!(inventory.item_count(ItemId(2)), 2);
+        assert!(!inventory.remove_item(ItemId(2).with_roll(ItemRoll {
+            rarity: Rarity::Common,
+            quality: 40,
+        })));
+        assert_eq!(inventory.remove_item_of_kind(ItemId(2)), Some(rolled));
+        assert_eq!(inventory.items, vec![ItemId(2)]);
+    }
+}
@@ -2,7 +2,6 @@ use std::collections::{HashMap, HashSet};
 
 use anyhow::Context;
 use bevy::prelude::*;
-use bevy_pkv::PkvStore;
 
 use crate::{
     GameState,
@@ -18,8 +17,16 @@ use crate::{
         sounds::{SoundManager, SoundManagerParam, SoundSettings, UiSound},
         sprite_db::{SpriteDB, SpriteId},
     },
+    confirm_dialog::{
+        ConfirmDialog, ConfirmDialogAction, ConfirmDialogMessage, open_confirm_dialog,
+    },
+    difficulty::spawn_difficulty_widget,
+    drop_in::can_drop_in,
+    input_bindings::InputBindings,
+    input_glyphs::{InputPrompt, PromptGlyph},
+    localization::{LocalizedText, localized_text},
     menu::{
-        NestedDynamicMenu,
+        MenuCleanup, MenuStackCommands, NestedDynamicMenu,
         menu_horizontal_selector::{HorizontalSelector, handle_horizontal_selection},
         menu_navigation::{
             ActiveMenu, GameMenuController, GameMenuGrid, GameMenuLatch,
@@ -29,9 +36,15 @@ use crate::{
         ui_consts::{SELECTABLE_BUTTON_BACKGROUND, UI_CONFIRMED_BUTTON_COLOR, UI_MENU_BACKGROUND},
     },
     player::{self, Player, RegisteredBattlePlayers},
+    profile::ProfileStore,
+    run_save::PendingRunRestore,
     save_game::{
-        SaveFileColor, SaveFileKey, SaveFiles, UnitSave, UnitSaveV1, upgrade_save_file_to_latest,
+        SaveFileColor, SaveFileKey, SaveFileMetadata, SaveFiles, UnitSave, UnitSaveV2, unix_now,
+        upgrade_save_file_to_latest,
     },
+    save_transfer::SaveTransferMessage,
+    seed_code::spawn_seed_code_widget,
+    tr,
     unit::jobs::UnitJob,
 };
 
@@ -54,12 +67,19 @@ pub fn join_game_plugin(app: &mut App) {
             OnEnter(GameState::JoinGame),
             (join_game_cleanup, join_game_menu_setup).chain(),
         )
+        .add_systems(
+            Update,
+            (handle_menu_cursor_navigation, highlight_menu_option)
+                .run_if(in_state(GameState::JoinGame)),
+        )
+        .add_systems(
+            Update,
+            wait_for_joining_player.run_if(in_state(GameState::JoinGame).or(can_drop_in)),
+        )
+        // Players dropping in mid run get the same screens, see `drop_in`
         .add_systems(
             Update,
             (
-                handle_menu_cursor_navigation,
-                highlight_menu_option,
-                wait_for_joining_player,
                 show_active_game_menu_only::<InactiveGameMenuFilter, ActiveGameMenuFilter>,
                 handle_unload_unit,
                 handle_button_commands,
@@ -68,8 +88,12 @@ pub fn join_game_plugin(app: &mut App) {
                 display_job_info_horizontal_selector,
                 display_colors_for_horizontal_selector,
                 handle_deselect_join_game_ready,
+                erase_data_on_confirm,
+                delete_character_on_confirm,
+                restore_character_on_confirm,
+                sync_load_screens.run_if(resource_changed::<SaveFiles>),
             )
-                .run_if(in_state(GameState::JoinGame)),
+                .run_if(in_state(GameState::JoinGame).or(in_state(GameState::Dungeon))),
         )
         .add_observer(highlight_button_on_join_game_added)
         .add_observer(highlight_button_on_join_game_removed);
@@ -82,8 +106,8 @@ pub struct JoinedPlayers(pub HashMap<Player, JoinedPlayerData>);
 pub enum LoadedUnitState {
     #[default]
     NoUnit,
-    LoadedUnit(UnitSaveV1),
-    ReadyUnit(UnitSaveV1),
+    LoadedUnit(UnitSaveV2),
+    ReadyUnit(UnitSaveV2),
 }
 
 #[derive(Clone, Debug, Reflect)]
@@ -93,7 +117,25 @@ pub struct JoinedPlayerData {
     unit_state: LoadedUnitState,
 }
 
-#[derive(Debug, Clone, Copy, Reflect)]
+impl JoinedPlayerData {
+    pub fn controller(&self) -> PlayerController {
+        self.controller
+    }
+
+    pub fn set_controller(&mut self, controller: PlayerController) {
+        self.controller = controller;
+    }
+
+    pub fn input_entity(&self) -> Entity {
+        self.input_entity
+    }
+
+    pub fn is_ready(&self) -> bool {
+        matches!(self.unit_state, LoadedUnitState::ReadyUnit(..))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
 pub enum PlayerController {
     Gamepad(Entity),
     Keyboard,
@@ -112,13 +154,17 @@ pub fn join_game_cleanup(
     }
 }
 
-pub fn join_game_menu_setup(mut commands: Commands, fonts: Res<FontResource>) {
+pub fn join_game_menu_setup(
+    mut commands: Commands,
+    fonts: Res<FontResource>,
+    pending_restore: Option<Res<PendingRunRestore>>,
+) {
     commands.insert_resource(JoinedPlayers::default());
     commands.insert_resource(RegisteredBattlePlayers::default());
-    build_ui(&mut commands, &fonts);
+    build_ui(&mut commands, &fonts, pending_restore.is_none());
 }
 
-fn build_ui(commands: &mut Commands, fonts: &FontResource) {
+fn build_ui(commands: &mut Commands, fonts: &FontResource, new_run: bool) {
     let screen_space = commands
         .spawn((
             Node {
@@ -144,11 +190,30 @@ fn build_ui(commands: &mut Commands, fonts: &FontResource) {
                 ..Default::default()
             },
             children![(
-                Text("Press \"J\" or LB and RB together to join the game".to_string()),
-                TextFont {
-                    font: fonts.pixelify_sans_regular.clone(),
+                Node {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
                     ..Default::default()
-                }
+                },
+                // Nobody has joined yet, so this follows whatever was pressed last
+                InputPrompt {
+                    key: "join_game.prompt",
+                    player: Player::PrePlayer,
+                    glyphs: vec![
+                        ("keyboard", PromptGlyph::Key(KeyCode::KeyJ)),
+                        (
+                            "gamepad",
+                            PromptGlyph::Buttons(vec![
+                                GamepadButton::LeftTrigger,
+                                GamepadButton::RightTrigger,
+                            ]),
+                        ),
+                    ],
+                    font: TextFont {
+                        font: fonts.pixelify_sans_regular.clone(),
+                        ..Default::default()
+                    },
+                },
             )],
         ))
         .id();
@@ -169,6 +234,13 @@ fn build_ui(commands: &mut Commands, fonts: &FontResource) {
         ))
         .id();
 
+    // Only a new run gets to pick its dungeon
+    let seed_code = spawn_seed_code_widget(commands, fonts, new_run);
+    let difficulty = spawn_difficulty_widget(commands, fonts, new_run);
+    commands
+        .entity(top_banner)
+        .add_children(&[seed_code, difficulty]);
+
     commands
         .entity(screen_space)
         .add_children(&[top_banner, bottom_space]);
@@ -187,6 +259,7 @@ fn add_player_ui(
     sprite_db: &SpriteDB,
     parent: Entity,
     player: Player,
+    mid_run: bool,
 ) -> Entity {
     let font_settings = TextFont {
         font: fonts.pixelify_sans_regular.clone(),
@@ -231,17 +304,8 @@ fn add_player_ui(
         ))
         .id();
 
-    let placeholder_save = UnitSaveV1 {
-        save_file_key: SaveFileKey {
-            uid: 0,
-            name: "lol".to_string(),
-            color: SaveFileColor::Red,
-        },
-        job: UnitJob::Archer,
-    };
-
     let (image, texture_atlas) =
-        get_sprite_resources_for_job(anim_db, sprite_db, &placeholder_save, Direction::SE, true)
+        get_sprite_resources_for_job(anim_db, sprite_db, &UnitJob::Archer, Direction::SE, true)
             .expect("Failed getting Sprite resources for hardcoded unit job");
 
     let character_job_selector = commands
@@ -317,6 +381,7 @@ fn add_player_ui(
                 SaveFileColor::Red,
                 SaveFileColor::Blue,
                 SaveFileColor::Green,
+                SaveFileColor::Purple,
             ]),
             children![(
                 Text("Save Color".to_string()),
@@ -341,7 +406,10 @@ fn add_player_ui(
                 border_radius: BorderRadius::all(percent(20)),
                 ..Default::default()
             },
-            children![(Text::new("Create Character"), font_settings.clone())],
+            children![(
+                localized_text("join_game.create_character"),
+                font_settings.clone()
+            )],
             BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
             UiCommands::CreateCharacter(CreateCharacterCommand {
                 text_input_entity: name_input_id,
@@ -404,7 +472,10 @@ fn add_player_ui(
                 border_radius: BorderRadius::all(percent(20)),
                 ..Default::default()
             },
-            children![(Text::new("New Character"), font_settings.clone())],
+            children![(
+                localized_text("join_game.new_character"),
+                font_settings.clone()
+            )],
             BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
             BorderColor::all(Color::NONE),
             UiCommands::OpenNestedScreen(new_character_screen),
@@ -425,38 +496,45 @@ fn add_player_ui(
                 border_radius: BorderRadius::all(percent(20)),
                 ..Default::default()
             },
-            children![(Text::new("Load Character"), font_settings.clone())],
+            children![(
+                localized_text("join_game.load_character"),
+                font_settings.clone()
+            )],
             BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
             BorderColor::all(Color::NONE),
             UiCommands::OpenLoadCharacterScreen,
         ))
         .id();
 
-    let delete_all_button = commands
-        .spawn((
-            Button,
-            Node {
-                width: percent(80),
-                height: percent(20),
-                justify_content: JustifyContent::Center,
-                justify_items: JustifyItems::Center,
-                align_content: AlignContent::Center,
-                align_items: AlignItems::Center,
-                border: UiRect::all(percent(0.5)),
-                border_radius: BorderRadius::all(percent(20)),
-                ..Default::default()
-            },
-            children![(Text::new("(DEV) Delete All Data"), font_settings.clone())],
-            BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
-            BorderColor::all(Color::NONE),
-            UiCommands::ErasePkvData,
-        ))
-        .id();
-    menu.push_buttons_to_stack(&[
-        new_character_button,
-        load_character_button,
-        delete_all_button,
-    ]);
+    let mut buttons = vec![new_character_button, load_character_button];
+    // Someone joining mid run shouldn't be able to delete everyone else's characters
+    if !mid_run {
+        let delete_all_button = commands
+            .spawn((
+                Button,
+                Node {
+                    width: percent(80),
+                    height: percent(20),
+                    justify_content: JustifyContent::Center,
+                    justify_items: JustifyItems::Center,
+                    align_content: AlignContent::Center,
+                    align_items: AlignItems::Center,
+                    border: UiRect::all(percent(0.5)),
+                    border_radius: BorderRadius::all(percent(20)),
+                    ..Default::default()
+                },
+                children![(
+                    localized_text("join_game.delete_all_data"),
+                    font_settings.clone()
+                )],
+                BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+                BorderColor::all(Color::NONE),
+                UiCommands::ErasePkvData,
+            ))
+            .id();
+        buttons.push(delete_all_button);
+    }
+    menu.push_buttons_to_stack(&buttons);
 
     let character_load_or_new_screen = commands
         .spawn((
@@ -476,11 +554,7 @@ fn add_player_ui(
             GameMenuLatch::default(),
             PlayerGameMenu,
         ))
-        .add_children(&[
-            new_character_button,
-            load_character_button,
-            delete_all_button,
-        ])
+        .add_children(&buttons)
         .id();
 
     commands
@@ -491,33 +565,38 @@ fn add_player_ui(
     player_block_container
 }
 
+#[allow(clippy::too_many_arguments)]
 fn join_game(
     commands: &mut Commands,
     fonts: &FontResource,
     anim_db: &AnimationDB,
     sprite_db: &SpriteDB,
     joined_players: &mut JoinedPlayers,
+    input_bindings: &InputBindings,
     player_ui_parent: Entity,
     controller: PlayerController,
+    mid_run: bool,
 ) -> anyhow::Result<()> {
-    // TODO: This is super stupid lol.
-    let players_count = joined_players.0.len();
-    let player = if players_count >= 4 {
+    // Hand out the first free slot, so someone rejoining after a player leaves doesn't end up
+    // with an id that has no bindings.
+    let Some(player) = Player::joinable().find(|t| !joined_players.0.contains_key(t)) else {
         anyhow::bail!("Maximum number of players reached.");
-    } else {
-        let max_player_id = joined_players
-            .0
-            .keys()
-            .map(|t| t.id())
-            .max()
-            .unwrap_or_default();
-
-        Player::PlayerId(max_player_id + 1)
     };
 
+    // Hotseat players use the bindings of whoever joined on the controller first
+    let bindings_player = joined_players
+        .0
+        .iter()
+        .filter(|(_, t)| t.controller == controller)
+        .map(|(p, _)| *p)
+        .min_by_key(|t| t.id())
+        .unwrap_or(player);
+
     let input_map = match controller {
-        PlayerController::Gamepad(entity) => Player::get_input_map_with_gamepad(entity),
-        PlayerController::Keyboard => player.get_keyboard_input_map(),
+        PlayerController::Gamepad(entity) => {
+            input_bindings.gamepad_input_map(&bindings_player, entity)
+        }
+        PlayerController::Keyboard => input_bindings.keyboard_input_map(&bindings_player),
     };
 
     let e = add_player_ui(
@@ -527,6 +606,7 @@ fn join_game(
         sprite_db,
         player_ui_parent,
         player,
+        mid_run,
     );
     let player_input = commands
         .spawn((
@@ -552,26 +632,37 @@ fn join_game(
 pub struct JoinedPlayerSpecificInputManager;
 
 /// Wait for specific inputs from a Gamepad or Controller to allow a player to join the game.
+#[allow(clippy::too_many_arguments)]
 fn wait_for_joining_player(
     mut commands: Commands,
     fonts: Res<FontResource>,
     anim_db: Res<AnimationDB>,
     sprite_db: Res<SpriteDB>,
     mut joined_players: ResMut<JoinedPlayers>,
+    input_bindings: Res<InputBindings>,
     sounds: Res<SoundManager>,
     sound_settings: Res<SoundSettings>,
     gamepads: Query<(Entity, &Gamepad)>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
     players_ui_container: Single<Entity, With<PlayersUIContainer>>,
+    game_state: Res<State<GameState>>,
+    text_inputs: Query<&TextInputInactive>,
 ) {
+    let mid_run = *game_state.get() == GameState::Dungeon;
+
     for (gamepad_entity, gamepad) in gamepads.iter() {
         if gamepad.pressed(GamepadButton::LeftTrigger)
             && gamepad.pressed(GamepadButton::RightTrigger)
         {
-            if joined_players.0.iter().any(|(_, v)| match v.controller {
-                PlayerController::Gamepad(e) => e == gamepad_entity,
-                _ => false,
-            }) {
+            // Pressing them again on a controller that already joined adds a hotseat player
+            let freshly_pressed = gamepad.just_pressed(GamepadButton::LeftTrigger)
+                || gamepad.just_pressed(GamepadButton::RightTrigger);
+            if !freshly_pressed
+                && joined_players
+                    .0
+                    .values()
+                    .any(|v| v.controller == PlayerController::Gamepad(gamepad_entity))
+            {
                 continue;
             }
 
@@ -581,8 +672,10 @@ fn wait_for_joining_player(
                 &anim_db,
                 &sprite_db,
                 &mut joined_players,
+                &input_bindings,
                 players_ui_container.entity(),
                 PlayerController::Gamepad(gamepad_entity),
+                mid_run,
             ) {
                 error!("Failed to add player: {:?}", e);
             } else {
@@ -591,31 +684,30 @@ fn wait_for_joining_player(
         }
     }
 
-    if keyboard_input.just_pressed(KeyCode::KeyJ) {
-        if !joined_players.0.iter().any(|(_, v)| match v.controller {
-            PlayerController::Keyboard => true,
-            _ => false,
-        }) {
-            if let Err(e) = join_game(
-                &mut commands,
-                &fonts,
-                &anim_db,
-                &sprite_db,
-                &mut joined_players,
-                players_ui_container.entity(),
-                PlayerController::Keyboard,
-            ) {
-                error!("Failed to add player: {:?}", e);
-            }
-        } else {
-            warn!("The Keyboard is already registered to a player!");
-        }
+    // Pressing it again once the keyboard has joined adds a hotseat player, unless someone's
+    // typing their name
+    let typing = text_inputs.iter().any(|t| !t.0);
+    if keyboard_input.just_pressed(KeyCode::KeyJ)
+        && !typing
+        && let Err(e) = join_game(
+            &mut commands,
+            &fonts,
+            &anim_db,
+            &sprite_db,
+            &mut joined_players,
+            &input_bindings,
+            players_ui_container.entity(),
+            PlayerController::Keyboard,
+            mid_run,
+        )
+    {
+        error!("Failed to add player: {:?}", e);
     }
 }
 
 #[derive(Component)]
 pub struct ControlledUiBlock {
-    entity: Entity,
+    pub entity: Entity,
 }
 
 #[derive(Component)]
@@ -640,7 +732,11 @@ fn handle_button_commands(
             &GameMenuGrid,
             Option<&NestedDynamicMenu>,
         ),
-        With<ActiveMenu>,
+        (
+            With<ActiveMenu>,
+            With<PlayerGameMenu>,
+            Without<ConfirmDialog>,
+        ),
     >,
     input_query: Query<(
         &player::Player,
@@ -656,11 +752,12 @@ fn handle_button_commands(
         Query<&HorizontalSelector<SaveFileColor>>,
     ),
     mut save_files: ResMut<SaveFiles>,
-    mut pkv_store: ResMut<PkvStore>,
+    mut pkv_store: ProfileStore,
     anim_db: Res<AnimationDB>,
     sprite_db: Res<SpriteDB>,
     mut registered_players: ResMut<RegisteredBattlePlayers>,
     mut next_state: ResMut<NextState<GameState>>,
+    game_state: Res<State<GameState>>,
     sounds: SoundManagerParam,
     fonts: Res<FontResource>,
 ) {
@@ -691,10 +788,7 @@ fn handle_button_commands(
                         }
                     }
                     UiCommands::OpenNestedScreen(entity) => {
-                        commands.entity(menu_e).remove::<ActiveMenu>();
-                        commands
-                            .entity(*entity)
-                            .insert((ActiveMenu {}, NestedDynamicMenu { parent: menu_e }));
+                        commands.push_menu(menu_e, *entity);
                     }
                     UiCommands::CreateCharacter(command) => {
                         let save_info = match handle_create_character_command(
@@ -744,40 +838,77 @@ fn handle_button_commands(
                             }
                         };
 
-                        commands.entity(menu_e).remove::<ActiveMenu>();
-
                         // Does this logically make sense? Or if you go back from here should you
                         // go back to the "New or Load Character Screen" because you have side effects?
-                        commands
-                            .entity(unit_preview_screen)
-                            .insert((ActiveMenu {}, NestedDynamicMenu { parent }));
+                        commands.close_menu(menu_e);
+                        commands.push_menu(parent, unit_preview_screen);
                     }
                     UiCommands::OpenLoadCharacterScreen => {
                         let load_file_screen = build_load_file_screen(
                             &mut commands,
                             &fonts,
+                            &anim_db,
+                            &sprite_db,
                             &joined_players,
                             &save_files,
                             controlled_ui_block.entity,
                             *player,
+                            *game_state.get() == GameState::Dungeon,
                         );
-                        // TODO: Maybe take active menu once you're spawned?
-                        commands
-                            .entity(load_file_screen)
-                            .insert(NestedDynamicMenu { parent: menu_e });
-                        commands.entity(menu_e).remove::<ActiveMenu>();
+                        commands.push_menu(menu_e, load_file_screen);
                     }
-                    UiCommands::ErasePkvData => {
-                        if let Err(e) = pkv_store.clear() {
-                            error!("Failed to clear PKV store: {:?}", e);
+                    UiCommands::OpenRenameScreen(save_file_key) => {
+                        let rename_screen = build_rename_screen(
+                            &mut commands,
+                            &fonts,
+                            save_file_key.clone(),
+                            controlled_ui_block.entity,
+                            *player,
+                        );
+                        commands.push_menu(menu_e, rename_screen);
+                    }
+                    UiCommands::RenameCharacter(command) => {
+                        if let Err(e) = handle_rename_character_command(
+                            &mut save_files,
+                            &mut pkv_store,
+                            character_creator_queries.0,
+                            command,
+                        ) {
+                            error!("Failed renaming character: {:?}", e);
+                            continue;
                         }
-                        save_files.save_file_keys.clear();
+
+                        commands.pop_menu(menu_e);
+                    }
+                    UiCommands::DeleteCharacter(save_file_key) => {
+                        open_confirm_dialog(
+                            &mut commands,
+                            &fonts,
+                            ConfirmDialogAction::DeleteCharacter(save_file_key.uid),
+                            menu_e,
+                            HashSet::from([*player]),
+                        );
+                    }
+                    UiCommands::ExportCharacter(save_file_key) => {
+                        commands.write_message(SaveTransferMessage::ExportCharacter(
+                            save_file_key.clone(),
+                        ));
+                    }
+                    UiCommands::ErasePkvData => {
+                        open_confirm_dialog(
+                            &mut commands,
+                            &fonts,
+                            ConfirmDialogAction::EraseAllData,
+                            menu_e,
+                            HashSet::from([*player]),
+                        );
                     }
                     UiCommands::LoadCharacter(save_file_key) => {
                         // Check race condition to see if this already has been loaded
                         if joined_players.0.values().any(|t| match &t.unit_state {
+                            // Names can change, so go by uid
                             LoadedUnitState::ReadyUnit(e) | LoadedUnitState::LoadedUnit(e) => {
-                                e.save_file_key == *save_file_key
+                                e.save_file_key.uid == save_file_key.uid
                             }
                             LoadedUnitState::NoUnit => false,
                         }) {
@@ -788,31 +919,48 @@ fn handle_button_commands(
                             continue;
                         }
 
-                        let save_file = pkv_store.get::<UnitSave>(&save_file_key.pkv_key());
-
-                        let Ok(save_file) = save_file else {
-                            error!("Failed loading character: {:?}", save_file);
+                        let unit_save = pkv_store
+                            .get::<UnitSave>(save_file_key.pkv_key())
+                            .and_then(upgrade_save_file_to_latest);
+
+                        let Ok(unit_save) = unit_save else {
+                            error!("Failed loading character: {:?}", unit_save);
+                            if pkv_store.has_backup::<UnitSave>(save_file_key.pkv_key()) {
+                                open_confirm_dialog(
+                                    &mut commands,
+                                    &fonts,
+                                    ConfirmDialogAction::RestoreCharacterBackup(save_file_key.uid),
+                                    menu_e,
+                                    HashSet::from([*player]),
+                                );
+                            } else {
+                                // Nothing to get it back from, so it shouldn't keep cluttering
+                                // up the list
+                                pkv_store.quarantine(save_file_key.pkv_key());
+                                save_files.remove(save_file_key.uid);
+                            }
                             continue;
                         };
 
-                        let Ok(v1_save) = upgrade_save_file_to_latest(save_file) else {
-                            error!("Failed upgrading save file to latest version");
-                            continue;
-                        };
+                        // Backfills characters saved before the load screen showed any details
+                        save_files
+                            .metadata
+                            .entry(save_file_key.uid)
+                            .or_insert_with(|| SaveFileMetadata::new(&unit_save));
 
                         let Some(player_state) = joined_players.0.get_mut(player) else {
                             error!("No player state for active player: {:?}", player);
                             continue;
                         };
 
-                        player_state.unit_state = LoadedUnitState::LoadedUnit(v1_save.clone());
+                        player_state.unit_state = LoadedUnitState::LoadedUnit(unit_save.clone());
 
                         let Ok(unit_preview_screen) = build_unit_preview_screen(
                             &mut commands,
                             &fonts,
                             &sprite_db,
                             &anim_db,
-                            v1_save,
+                            unit_save,
                             controlled_ui_block.entity,
                             *player,
                         ) else {
@@ -820,12 +968,17 @@ fn handle_button_commands(
                             continue;
                         };
 
-                        commands.entity(menu_e).remove::<ActiveMenu>();
-                        commands
-                            .entity(unit_preview_screen)
-                            .insert((ActiveMenu {}, NestedDynamicMenu { parent: menu_e }));
+                        commands.push_menu(menu_e, unit_preview_screen);
                     }
                     UiCommands::PlayerReadyForBattle(player, save_info) => {
+                        // Everyone's cursor and menus get their color, so no doubling up
+                        let color = &save_info.save_file_key.color;
+                        if color_taken(&joined_players, &registered_players, player, color) {
+                            sounds.play_ui_sound(&mut commands, UiSound::Error);
+                            info!("Someone is already playing as {}", color.name());
+                            continue;
+                        }
+
                         commands.entity(menu_e).remove::<ActiveMenu>();
                         commands.entity(menu_e).insert(JoinGameMenuPlayerReady);
 
@@ -836,6 +989,19 @@ fn handle_button_commands(
 
                         player_state.unit_state = LoadedUnitState::ReadyUnit(save_info.clone());
 
+                        // Dropping in mid run, the character joins the party in the next room
+                        if *game_state.get() == GameState::Dungeon {
+                            registered_players
+                                .orphaned_units
+                                .retain(|t| t.save_file_key != save_info.save_file_key);
+                            registered_players
+                                .save_files
+                                .insert(*player, save_info.clone());
+                            registered_players.assign_filler_units();
+                            commands.entity(controlled_ui_block.entity).despawn();
+                            continue;
+                        }
+
                         if joined_players
                             .0
                             .values()
@@ -846,6 +1012,7 @@ fn handle_button_commands(
                                     registered_players.save_files.insert(*k, t.clone());
                                 }
                             }
+                            registered_players.assign_filler_units();
 
                             next_state.set(GameState::Dungeon);
                         }
@@ -854,11 +1021,8 @@ fn handle_button_commands(
             }
 
             if action_state.just_pressed(&player::PlayerInputAction::Deselect) {
-                if let Some(parent) = nested.map(|t| t.parent) {
-                    // TODO: This leaves some dangling menus!
-                    commands.entity(menu_e).remove::<ActiveMenu>();
-                    commands.entity(parent).insert(ActiveMenu {});
-
+                if nested.is_some() {
+                    commands.pop_menu(menu_e);
                     sounds.play_ui_sound(&mut commands, UiSound::Cancel);
                 } else {
                     // Despawn the players UI
@@ -947,7 +1111,7 @@ pub fn get_sprite_resources(
 pub fn get_sprite_resources_for_job(
     anim_db: &AnimationDB,
     sprite_db: &SpriteDB,
-    unit_save: &UnitSaveV1,
+    job: &UnitJob,
     direction: Direction,
     // Bit of a hack, but we don't want to use caroline's sprites in battle until we
     // have animated versions, but also I don't want two copies of this lookup table
@@ -955,8 +1119,8 @@ pub fn get_sprite_resources_for_job(
     use_caros_sprites: bool,
 ) -> anyhow::Result<(Handle<Image>, TextureAtlas)> {
     let (sprite_id, animated_sprite_id) = match use_caros_sprites {
-        true => (unit_save.job.demo_sprite_id(), UNIT_DEMO_SPRITE_ID),
-        false => (unit_save.job.base_sprite_id(), TT_UNIT_ANIMATED_SPRITE_ID),
+        true => (job.demo_sprite_id(), UNIT_DEMO_SPRITE_ID),
+        false => (job.base_sprite_id(), TT_UNIT_ANIMATED_SPRITE_ID),
     };
 
     let key = AnimationStartIndexKey {
@@ -978,7 +1142,7 @@ fn build_unit_preview_screen(
     fonts: &FontResource,
     sprite_db: &SpriteDB,
     anim_db: &AnimationDB,
-    unit_save: UnitSaveV1,
+    unit_save: UnitSaveV2,
     player_ui_parent: Entity,
     player: Player,
 ) -> anyhow::Result<Entity> {
@@ -1008,7 +1172,7 @@ fn build_unit_preview_screen(
         .id();
 
     let (image, texture_atlas) =
-        get_sprite_resources_for_job(anim_db, sprite_db, &unit_save, Direction::SE, true)
+        get_sprite_resources_for_job(anim_db, sprite_db, &unit_save.job, Direction::SE, true)
             .context("Getting Sprite resources for Unit Job")?;
 
     let unit_preview_image = commands
@@ -1043,7 +1207,7 @@ fn build_unit_preview_screen(
             },
             ReadyButtonMarker,
             children![(
-                Text::new("Ready!"),
+                localized_text("join_game.ready"),
                 TextFont {
                     font: fonts.pixelify_sans_regular.clone(),
                     ..Default::default()
@@ -1071,6 +1235,7 @@ fn build_unit_preview_screen(
             },
             BackgroundColor(UI_MENU_BACKGROUND),
             PlayerGameMenu,
+            MenuCleanup::Despawn,
             ActiveMenu {},
             GameMenuController {
                 players: HashSet::from([player]),
@@ -1092,15 +1257,72 @@ fn build_unit_preview_screen(
     Ok(unit_preview_screen)
 }
 
+/// One character's row on the load screen
+#[derive(Component)]
+struct SaveFileRow {
+    uid: u32,
+    buttons: Vec<Entity>,
+}
+
+/// The name on a load screen row
+#[derive(Component)]
+struct SaveFileName(u32);
+
+/// The line under a character's name on the load screen, like "Lv 3 - 5 battles - Played today"
+fn save_file_details(metadata: &SaveFileMetadata, now: u64) -> String {
+    let last_played = match metadata.days_since_played(now) {
+        0 => tr!("save_file.played_today"),
+        days => tr!("save_file.played_days_ago", days = days),
+    };
+    tr!(
+        "save_file.details",
+        level = metadata.level,
+        battles = metadata.battles,
+        last_played = last_played
+    )
+}
+
+fn load_screen_button(font: &TextFont, label: (Text, LocalizedText)) -> impl Bundle {
+    (
+        Button,
+        BorderColor::all(Color::NONE),
+        Node {
+            height: percent(100),
+            padding: UiRect::horizontal(px(8)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            border_radius: BorderRadius::all(percent(20)),
+            ..Default::default()
+        },
+        BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+        children![(label, font.clone())],
+    )
+}
+
+/// Lists the characters nobody has picked yet. Outside of a run, each one can be renamed,
+/// deleted or exported from here too.
 fn build_load_file_screen(
     commands: &mut Commands,
     fonts: &FontResource,
+    anim_db: &AnimationDB,
+    sprite_db: &SpriteDB,
     joined_players: &JoinedPlayers,
     files: &SaveFiles,
     player_ui_parent: Entity,
     player: Player,
+    mid_run: bool,
 ) -> Entity {
-    let mut load_menu = GameMenuGrid::new_vertical();
+    let font = TextFont {
+        font: fonts.pixelify_sans_regular.clone(),
+        ..Default::default()
+    };
+
+    // Someone joining mid run shouldn't be able to mess with everyone else's characters
+    let mut load_menu = if mid_run {
+        GameMenuGrid::new_vertical()
+    } else {
+        GameMenuGrid::new_with_width(4)
+    };
     let load_screen = commands
         .spawn((
             Node {
@@ -1118,6 +1340,7 @@ fn build_load_file_screen(
             },
             BackgroundColor(UI_MENU_BACKGROUND),
             PlayerGameMenu,
+            MenuCleanup::Despawn,
             ActiveMenu {},
             GameMenuController {
                 players: HashSet::from([player]),
@@ -1139,34 +1362,118 @@ fn build_load_file_screen(
             continue;
         }
 
-        let button = commands
+        let metadata = files.metadata.get(&save_file_key.uid);
+        let load_button = commands
             .spawn((
                 Button,
                 BorderColor::all(Color::NONE),
                 Node {
-                    width: percent(80),
-                    height: percent(10),
-                    justify_items: JustifyItems::Center,
-                    justify_content: JustifyContent::SpaceEvenly,
+                    height: percent(100),
+                    flex_grow: 1.,
+                    justify_content: JustifyContent::Center,
                     align_items: AlignItems::Center,
-                    align_content: AlignContent::SpaceEvenly,
-                    flex_direction: FlexDirection::Column,
+                    flex_direction: FlexDirection::Row,
+                    column_gap: px(8),
                     border_radius: BorderRadius::all(percent(20)),
                     ..Default::default()
                 },
                 BackgroundColor(save_file_key.color.color()),
                 UiCommands::LoadCharacter(save_file_key.clone()),
+            ))
+            .id();
+
+        if let Some(metadata) = metadata {
+            match get_sprite_resources_for_job(
+                anim_db,
+                sprite_db,
+                &metadata.job,
+                Direction::SE,
+                true,
+            ) {
+                Ok((image, texture_atlas)) => {
+                    commands.entity(load_button).with_child((
+                        Node {
+                            height: percent(90),
+                            aspect_ratio: Some(1.),
+                            ..Default::default()
+                        },
+                        ImageNode::from_atlas_image(image, texture_atlas),
+                    ));
+                }
+                Err(e) => error!(
+                    "Failed getting the job icon for {:?}: {:?}",
+                    save_file_key, e
+                ),
+            }
+        }
+
+        let labels = commands
+            .spawn((
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
                 children![(
                     Text(save_file_key.name.clone()),
-                    TextFont {
-                        font: fonts.pixelify_sans_regular.clone(),
-                        ..Default::default()
-                    }
+                    SaveFileName(save_file_key.uid),
+                    font.clone()
                 )],
             ))
             .id();
-        load_menu.push_button_to_stack(button);
-        commands.entity(load_screen).add_child(button);
+        // Characters from before this was tracked only get their name
+        if let Some(metadata) = metadata {
+            commands.entity(labels).with_child((
+                Text(save_file_details(metadata, unix_now())),
+                TextFont {
+                    font_size: 12.,
+                    ..font.clone()
+                },
+            ));
+        }
+        commands.entity(load_button).add_child(labels);
+
+        let mut buttons = vec![load_button];
+        if !mid_run {
+            let rename_button = commands
+                .spawn((
+                    load_screen_button(&font, localized_text("join_game.rename")),
+                    UiCommands::OpenRenameScreen(save_file_key.clone()),
+                ))
+                .id();
+            let delete_button = commands
+                .spawn((
+                    load_screen_button(&font, localized_text("join_game.delete")),
+                    UiCommands::DeleteCharacter(save_file_key.clone()),
+                ))
+                .id();
+            let export_button = commands
+                .spawn((
+                    load_screen_button(&font, localized_text("join_game.export")),
+                    UiCommands::ExportCharacter(save_file_key.clone()),
+                ))
+                .id();
+            buttons.extend([rename_button, delete_button, export_button]);
+        }
+        load_menu.push_buttons_in_rows(&buttons);
+
+        let row = commands
+            .spawn((
+                Node {
+                    width: percent(80),
+                    height: percent(10),
+                    flex_direction: FlexDirection::Row,
+                    column_gap: px(4),
+                    ..Default::default()
+                },
+                SaveFileRow {
+                    uid: save_file_key.uid,
+                    buttons: buttons.clone(),
+                },
+            ))
+            .add_children(&buttons)
+            .id();
+        commands.entity(load_screen).add_child(row);
     }
 
     commands.entity(load_screen).insert(load_menu);
@@ -1174,6 +1481,135 @@ fn build_load_file_screen(
     load_screen
 }
 
+fn build_rename_screen(
+    commands: &mut Commands,
+    fonts: &FontResource,
+    save_file_key: SaveFileKey,
+    player_ui_parent: Entity,
+    player: Player,
+) -> Entity {
+    let font = TextFont {
+        font: fonts.pixelify_sans_regular.clone(),
+        ..Default::default()
+    };
+
+    let name_input = commands
+        .spawn((
+            Button,
+            Node {
+                width: percent(80),
+                height: percent(10),
+                border: UiRect::all(percent(0.5)),
+                border_radius: BorderRadius::all(percent(20)),
+                ..default()
+            },
+            BackgroundColor(save_file_key.color.color()),
+            TextInput,
+            TextInputTextFont(TextFont {
+                font_size: 34.,
+                ..font.clone()
+            }),
+            TextInputValue(save_file_key.name.clone()),
+            TextInputInactive(true),
+            TextInputSettings {
+                retain_on_submit: true,
+                ..default()
+            },
+        ))
+        .id();
+    commands
+        .entity(name_input)
+        .insert(UiCommands::FocusTextInput(name_input));
+
+    let save_name_button = commands
+        .spawn((
+            Button,
+            Node {
+                width: percent(80),
+                height: percent(10),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                border: UiRect::all(percent(0.5)),
+                border_radius: BorderRadius::all(percent(20)),
+                ..Default::default()
+            },
+            children![(localized_text("join_game.save_name"), font.clone())],
+            BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+            UiCommands::RenameCharacter(RenameCharacterCommand {
+                save_file_key,
+                text_input_entity: name_input,
+            }),
+        ))
+        .id();
+
+    let mut menu = GameMenuGrid::new_vertical();
+    menu.push_buttons_to_stack(&[name_input, save_name_button]);
+
+    let rename_screen = commands
+        .spawn((
+            Node {
+                width: percent(100),
+                height: percent(100),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::SpaceEvenly,
+                align_items: AlignItems::Center,
+                display: Display::None,
+                border_radius: BorderRadius::all(percent(20)),
+                ..Default::default()
+            },
+            BackgroundColor(UI_MENU_BACKGROUND),
+            PlayerGameMenu,
+            MenuCleanup::Despawn,
+            ActiveMenu {},
+            GameMenuController {
+                players: HashSet::from([player]),
+            },
+            GameMenuLatch::default(),
+            menu,
+        ))
+        .add_children(&[name_input, save_name_button])
+        .id();
+
+    commands.entity(player_ui_parent).add_child(rename_screen);
+    rename_screen
+}
+
+/// Keeps any open load screens in line with [`SaveFiles`] once a character is renamed or deleted
+fn sync_load_screens(
+    mut commands: Commands,
+    save_files: Res<SaveFiles>,
+    rows: Query<(Entity, &SaveFileRow, &ChildOf)>,
+    mut names: Query<(&SaveFileName, &mut Text)>,
+    mut menus: Query<&mut GameMenuGrid>,
+) {
+    for (row, save_file_row, load_screen) in rows {
+        if save_files
+            .save_file_keys
+            .iter()
+            .any(|t| t.uid == save_file_row.uid)
+        {
+            continue;
+        }
+
+        if let Ok(mut menu) = menus.get_mut(load_screen.parent()) {
+            for button in &save_file_row.buttons {
+                if let Err(e) = menu.remove_button_entity(button) {
+                    error!("Failed removing a deleted character from the menu: {:?}", e);
+                }
+            }
+        }
+        commands.entity(row).despawn();
+    }
+
+    for (name, mut text) in names.iter_mut() {
+        if let Some(key) = save_files.save_file_keys.iter().find(|t| t.uid == name.0)
+            && text.0 != key.name
+        {
+            text.0 = key.name.clone();
+        }
+    }
+}
+
 #[derive(Component)]
 struct JobNameDisplay;
 #[derive(Component)]
@@ -1272,6 +1708,24 @@ fn highlight_button_on_join_game_removed(
     }
 }
 
+/// Whether anyone other than `player` has already readied up with a character of this `color`
+fn color_taken(
+    joined_players: &JoinedPlayers,
+    registered_players: &RegisteredBattlePlayers,
+    player: &Player,
+    color: &SaveFileColor,
+) -> bool {
+    joined_players
+        .0
+        .iter()
+        .filter_map(|(p, t)| match &t.unit_state {
+            LoadedUnitState::ReadyUnit(unit) => Some((p, unit)),
+            _ => None,
+        })
+        .chain(registered_players.save_files.iter())
+        .any(|(p, unit)| p != player && unit.save_file_key.color == *color)
+}
+
 #[derive(Component)]
 struct SaveFileColorText;
 
@@ -1299,6 +1753,78 @@ fn display_colors_for_horizontal_selector(
     }
 }
 
+fn erase_data_on_confirm(
+    mut reader: MessageReader<ConfirmDialogMessage>,
+    mut save_files: ResMut<SaveFiles>,
+    mut pkv_store: ProfileStore,
+) {
+    if !reader
+        .read()
+        .any(|t| t.action == ConfirmDialogAction::EraseAllData && t.confirmed)
+    {
+        return;
+    }
+
+    // Only this profile's, everyone else's characters are safe
+    pkv_store.clear();
+    save_files.save_file_keys.clear();
+}
+
+fn delete_character_on_confirm(
+    mut reader: MessageReader<ConfirmDialogMessage>,
+    mut save_files: ResMut<SaveFiles>,
+    mut pkv_store: ProfileStore,
+) {
+    for message in reader.read().filter(|t| t.confirmed) {
+        let ConfirmDialogAction::DeleteCharacter(uid) = message.action else {
+            continue;
+        };
+
+        let Some(key) = save_files.remove(uid) else {
+            error!("No character with uid {} to delete", uid);
+            continue;
+        };
+
+        info!("Deleting {}", key.name);
+        if let Err(e) = pkv_store.remove(&key.pkv_key()) {
+            error!("Failed to delete {}'s save: {:?}", key.name, e);
+        }
+    }
+}
+
+/// Swaps an unreadable character save for its backup. If even that doesn't work, the character
+/// gets dropped from the list.
+fn restore_character_on_confirm(
+    mut reader: MessageReader<ConfirmDialogMessage>,
+    mut save_files: ResMut<SaveFiles>,
+    mut pkv_store: ProfileStore,
+) {
+    for message in reader.read().filter(|t| t.confirmed) {
+        let ConfirmDialogAction::RestoreCharacterBackup(uid) = message.action else {
+            continue;
+        };
+
+        let Some(key) = save_files
+            .save_file_keys
+            .iter()
+            .find(|t| t.uid == uid)
+            .cloned()
+        else {
+            error!("No character with uid {} to restore", uid);
+            continue;
+        };
+
+        match pkv_store.restore_backup::<UnitSave>(key.pkv_key()) {
+            Ok(_) => info!("Restored {} from their backup", key.name),
+            Err(e) => {
+                error!("Failed restoring {}: {:?}", key.name, e);
+                pkv_store.quarantine(key.pkv_key());
+                save_files.remove(uid);
+            }
+        }
+    }
+}
+
 #[derive(Component)]
 enum UiCommands {
     FocusTextInput(Entity),
@@ -1306,18 +1832,22 @@ enum UiCommands {
     OpenLoadCharacterScreen,
     CreateCharacter(CreateCharacterCommand),
     LoadCharacter(SaveFileKey),
+    OpenRenameScreen(SaveFileKey),
+    RenameCharacter(RenameCharacterCommand),
+    DeleteCharacter(SaveFileKey),
+    ExportCharacter(SaveFileKey),
     ErasePkvData,
-    PlayerReadyForBattle(Player, UnitSaveV1),
+    PlayerReadyForBattle(Player, UnitSaveV2),
 }
 
 fn handle_create_character_command(
     save_files: &mut SaveFiles,
-    pkv: &mut PkvStore,
+    pkv: &mut ProfileStore,
     text_input_query: Query<&TextInputValue>,
     job_selector: Query<&HorizontalSelector<UnitJob>>,
     color_selector: Query<&HorizontalSelector<SaveFileColor>>,
     create_character_submission: &CreateCharacterCommand,
-) -> anyhow::Result<UnitSaveV1> {
+) -> anyhow::Result<UnitSaveV2> {
     let Some(name) = text_input_query
         .get(create_character_submission.text_input_entity)
         .ok()
@@ -1348,23 +1878,16 @@ fn handle_create_character_command(
         anyhow::bail!("Name can't be empty!");
     }
 
-    save_files.cursor = save_files.cursor.overflowing_add(1).0;
-    let key = SaveFileKey {
-        uid: save_files.cursor,
-        name: name.0.clone(),
-        color,
-    };
-    save_files.save_file_keys.push(key.clone());
+    let key = save_files.add(name.0.clone(), color);
 
-    let unit_save = UnitSaveV1 {
-        save_file_key: key.clone(),
-        job,
-    };
+    let unit_save = UnitSaveV2::new(key.clone(), job);
+    save_files
+        .metadata
+        .insert(key.uid, SaveFileMetadata::new(&unit_save));
 
     // This clone is a bit expensive just to pass, I could return just the key in return type and require
     // the caller to pull from the DB, but probably fine for now.
     pkv.set(key.pkv_key(), &UnitSave::from(unit_save.clone()))
-        .map_err(|e| anyhow::anyhow!("Failed to set PKV: {:?}", e))
         .context("Failed saving unit to PKV store")?;
 
     Ok(unit_save)
@@ -1375,3 +1898,43 @@ pub struct CreateCharacterCommand {
     job_selector_entity: Entity,
     color_selector_entity: Entity,
 }
+
+fn handle_rename_character_command(
+    save_files: &mut SaveFiles,
+    pkv: &mut ProfileStore,
+    text_input_query: Query<&TextInputValue>,
+    rename_submission: &RenameCharacterCommand,
+) -> anyhow::Result<SaveFileKey> {
+    let Some(name) = text_input_query
+        .get(rename_submission.text_input_entity)
+        .ok()
+    else {
+        anyhow::bail!("RenameCharacterForm Misconfigured. No Name Input")
+    };
+
+    if name.0.is_empty() {
+        anyhow::bail!("Name can't be empty!");
+    }
+
+    let old_key = &rename_submission.save_file_key;
+    let save_file = pkv
+        .get::<UnitSave>(&old_key.pkv_key())
+        .context("Failed loading unit from PKV store")?;
+    let mut unit_save = upgrade_save_file_to_latest(save_file)?;
+
+    let Some(key) = save_files.rename(old_key.uid, name.0.clone()) else {
+        anyhow::bail!("No character with uid {} to rename", old_key.uid);
+    };
+    unit_save.save_file_key = key.clone();
+
+    pkv.set(key.pkv_key(), &UnitSave::from(unit_save))
+        .context("Failed saving unit to PKV store")?;
+
+    info!("Renamed {} to {}", old_key.name, key.name);
+    Ok(key)
+}
+
+pub struct RenameCharacterCommand {
+    save_file_key: SaveFileKey,
+    text_input_entity: Entity,
+}
@@ -1,27 +1,79 @@
+pub mod accessibility;
 pub mod animation;
 pub mod args;
+pub mod asset_validation;
 pub mod assets;
+pub mod aura;
 pub mod battle;
+pub mod battle_log;
 pub mod battle_menu;
 pub mod battle_phase;
+pub mod biome;
+pub mod bonds;
 pub mod camera;
 pub mod combat;
+pub mod combat_formulas;
+pub mod combat_sfx;
+pub mod confirm_dialog;
+pub mod contribution;
+pub mod controller_disconnect;
+pub mod difficulty;
+pub mod drop_in;
 pub mod dungeon;
+pub mod durability;
+pub mod encounters;
 pub mod enemy;
 pub mod equipment;
 pub mod gameplay_effects;
 pub mod grid;
 pub mod grid_cursor;
+pub mod hot_reload;
+pub mod hotseat;
+pub mod injury;
+pub mod input_bindings;
+pub mod input_glyphs;
 pub mod interactable;
+pub mod inventory;
 pub mod join_game_menu;
+pub mod loading;
+pub mod localization;
+pub mod loot;
 pub mod main_menu;
+pub mod map_editor;
 pub mod map_generation;
 pub mod menu;
+pub mod palette;
+pub mod pause_menu;
+pub mod ping;
+pub mod pixel_art;
 pub mod player;
+pub mod profile;
 pub mod projectile;
+pub mod promotion;
+pub mod props;
+pub mod rest_room;
+pub mod rewind;
+pub mod room_templates;
+pub mod route_select;
+pub mod rumble;
+pub mod run_save;
+pub mod run_stats;
 pub mod save_game;
+pub mod save_recovery;
+pub mod save_transfer;
+pub mod seed_code;
+pub mod shop;
+pub mod spectator;
+pub mod split_screen;
+pub mod threat_map;
+pub mod tiled_import;
+pub mod tooltip;
+pub mod trade;
+pub mod turn_events;
 pub mod unit;
+pub mod unit_inspection;
 pub mod unit_stats;
+pub mod weather;
 
 use bevy::prelude::*;
 
@@ -30,6 +82,7 @@ use bevy::prelude::*;
 pub enum GameState {
     #[default]
     Initializing,
+    ProfileSelect,
     MainMenu,
     JoinGame,
     Dungeon,
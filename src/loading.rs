@@ -0,0 +1,265 @@
+//! Waiting on the battle's assets before anything gets spawned.
+//!
+//! Entering the dungeon kicks off loads for the spritesheets, the tile overlays and the animation
+//! JSON, but none of them are ready straight away. Rather than spawning the first room into a race
+//! with the asset server, the dungeon sits in [`DungeonState::Loading`] with a progress bar up
+//! until every one of them has either loaded or failed, and only then loads the room.
+//!
+//! Later rooms shouldn't need the loading screen at all. While the party's in a room, everything
+//! the rooms it leads to will need gets [preloaded](NextRoomPreload), so heading onwards can go
+//! straight to loading the room. If it somehow isn't ready yet, the loading screen comes back up
+//! until it is.
+
+use std::path::PathBuf;
+
+use bevy::{
+    asset::{RecursiveDependencyLoadState, UntypedAssetId},
+    prelude::*,
+};
+
+use crate::{
+    animation::TinytacticsAssets,
+    assets::{BATTLE_TACTICS_TILESHEET, FontResource, sprite_db::SpriteDB},
+    biome::Biome,
+    dungeon::{DungeonManager, DungeonState},
+    localization::localized_text,
+    menu::ui_consts::{UI_BORDER_COLOR, UI_CONFIRMED_BUTTON_COLOR, UI_MENU_BACKGROUND},
+    unit::overlay::TileOverlayAssets,
+};
+
+const PROGRESS_BAR_WIDTH: f32 = 300.;
+const PROGRESS_BAR_HEIGHT: f32 = 16.;
+
+/// How many of the battle's assets are done loading
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LoadingProgress {
+    pub settled: usize,
+    pub total: usize,
+}
+
+impl LoadingProgress {
+    /// Tallies up the load states of everything being waited on. Anything that failed to load
+    /// isn't going to get any more loaded, so it counts as settled too.
+    pub fn from_states(states: impl IntoIterator<Item = RecursiveDependencyLoadState>) -> Self {
+        let mut progress = LoadingProgress::default();
+        for state in states {
+            progress.total += 1;
+            if matches!(
+                state,
+                RecursiveDependencyLoadState::Loaded | RecursiveDependencyLoadState::Failed(..)
+            ) {
+                progress.settled += 1;
+            }
+        }
+        progress
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 1.;
+        }
+        self.settled as f32 / self.total as f32
+    }
+
+    pub fn done(&self) -> bool {
+        self.settled == self.total
+    }
+}
+
+#[derive(Component)]
+struct LoadingProgressBar;
+
+/// Everything the rooms the party can head to next will need, held on to so it's loaded (and
+/// stays loaded) by the time they get there
+#[derive(Resource, Debug, Default)]
+pub struct NextRoomPreload(Vec<UntypedHandle>);
+
+impl NextRoomPreload {
+    /// Straight on to loading the next room if it's ready, or the loading screen until it is
+    pub fn next_state(&self, asset_server: &AssetServer) -> DungeonState {
+        let progress =
+            LoadingProgress::from_states(self.0.iter().map(|t| load_state(asset_server, t.id())));
+        if progress.done() {
+            DungeonState::LoadRoom
+        } else {
+            DungeonState::Loading
+        }
+    }
+}
+
+/// What a room in `biome` needs on top of what every room does
+pub fn room_asset_paths(biome: Biome) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(BATTLE_TACTICS_TILESHEET)];
+    paths.extend(
+        biome
+            .enemy_spawn_table()
+            .iter()
+            .map(|t| t.spritesheet_path()),
+    );
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+pub fn loading_plugin(app: &mut App) {
+    app.init_resource::<NextRoomPreload>()
+        .add_systems(OnEnter(DungeonState::Loading), spawn_loading_screen)
+        .add_systems(OnEnter(DungeonState::InBattle), preload_next_rooms)
+        .add_systems(OnEnter(DungeonState::RestRoom), preload_next_rooms)
+        .add_systems(OnEnter(DungeonState::ShopRoom), preload_next_rooms)
+        .add_systems(
+            Update,
+            wait_for_battle_assets.run_if(in_state(DungeonState::Loading)),
+        );
+}
+
+fn preload_next_rooms(
+    asset_server: Res<AssetServer>,
+    dungeon_manager: Res<DungeonManager>,
+    mut preload: ResMut<NextRoomPreload>,
+) {
+    preload.0 = dungeon_manager
+        .next_rooms()
+        .iter()
+        .filter_map(|t| dungeon_manager.room_map(*t))
+        .flat_map(|t| room_asset_paths(t.biome))
+        .map(|path| asset_server.load::<Image>(path).untyped())
+        .collect();
+}
+
+fn load_state(asset_server: &AssetServer, id: UntypedAssetId) -> RecursiveDependencyLoadState {
+    asset_server
+        .get_recursive_dependency_load_state(id)
+        .unwrap_or(RecursiveDependencyLoadState::NotLoaded)
+}
+
+/// Every asset the battle needs before it can be spawned
+fn battle_assets(
+    sprite_db: &SpriteDB,
+    tt_assets: &TinytacticsAssets,
+    overlay_assets: &TileOverlayAssets,
+) -> Vec<UntypedAssetId> {
+    let mut ids = sprite_db
+        .sprite_id_to_handle
+        .values()
+        .map(|t| t.id().untyped())
+        .collect::<Vec<_>>();
+    ids.extend([
+        tt_assets.fighter_spritesheet.id().untyped(),
+        tt_assets.mage_spritesheet.id().untyped(),
+        tt_assets.cleric_spritesheet.id().untyped(),
+        tt_assets.iron_axe_spritesheet.id().untyped(),
+        tt_assets.scepter_spritesheet.id().untyped(),
+        tt_assets.tile_spritesheet.id().untyped(),
+        tt_assets.animation_data.id().untyped(),
+        overlay_assets.tile_overlay_image_handle.id().untyped(),
+        overlay_assets.cursor_image.id().untyped(),
+        overlay_assets.symbol_image_handle.id().untyped(),
+    ]);
+    ids
+}
+
+fn spawn_loading_screen(mut commands: Commands, fonts: Res<FontResource>) {
+    commands.spawn((
+        Name::new("LoadingScreen"),
+        Node {
+            position_type: PositionType::Absolute,
+            width: percent(100),
+            height: percent(100),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            row_gap: px(16),
+            ..Default::default()
+        },
+        BackgroundColor(UI_MENU_BACKGROUND),
+        GlobalZIndex(200),
+        DespawnOnExit(DungeonState::Loading),
+        children![
+            (
+                localized_text("loading.title"),
+                TextFont {
+                    font_size: 36.0,
+                    font: fonts.pixelify_sans_regular.clone(),
+                    ..default()
+                },
+            ),
+            (
+                Node {
+                    width: px(PROGRESS_BAR_WIDTH),
+                    height: px(PROGRESS_BAR_HEIGHT),
+                    border: UiRect::all(px(2)),
+                    ..Default::default()
+                },
+                BorderColor::all(UI_BORDER_COLOR),
+                children![(
+                    LoadingProgressBar,
+                    Node {
+                        width: percent(0),
+                        height: percent(100),
+                        ..Default::default()
+                    },
+                    BackgroundColor(UI_CONFIRMED_BUTTON_COLOR),
+                )],
+            ),
+        ],
+    ));
+}
+
+fn wait_for_battle_assets(
+    asset_server: Res<AssetServer>,
+    sprite_db: Res<SpriteDB>,
+    tt_assets: Res<TinytacticsAssets>,
+    overlay_assets: Res<TileOverlayAssets>,
+    preload: Res<NextRoomPreload>,
+    mut bars: Query<&mut Node, With<LoadingProgressBar>>,
+    mut next_state: ResMut<NextState<DungeonState>>,
+) {
+    let mut ids = battle_assets(&sprite_db, &tt_assets, &overlay_assets);
+    ids.extend(preload.0.iter().map(|t| t.id()));
+    let progress = LoadingProgress::from_states(ids.iter().map(|id| {
+        let state = load_state(&asset_server, *id);
+        if let RecursiveDependencyLoadState::Failed(e) = &state {
+            error!("Couldn't load a battle asset: {:?}", e);
+        }
+        state
+    }));
+
+    for mut bar in bars.iter_mut() {
+        bar.width = percent(progress.fraction() * 100.);
+    }
+
+    if progress.done() {
+        next_state.set(DungeonState::LoadRoom);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loading_waits_on_everything() {
+        use RecursiveDependencyLoadState::*;
+
+        let progress = LoadingProgress::from_states([Loaded, Loading, NotLoaded, Loaded]);
+        assert_eq!(progress.fraction(), 0.5);
+        assert!(!progress.done());
+
+        assert!(LoadingProgress::from_states([Loaded, Loaded]).done());
+        assert!(LoadingProgress::from_states([]).done());
+    }
+
+    #[test]
+    fn test_rooms_preload_whoever_can_spawn_there() {
+        let paths = room_asset_paths(Biome::Forest);
+        assert_eq!(paths.len(), 3);
+        assert!(paths.contains(&PathBuf::from(BATTLE_TACTICS_TILESHEET)));
+        assert!(paths.contains(&PathBuf::from(
+            "unit_assets/spritesheets/cleric_spritesheet.png"
+        )));
+        assert!(!paths.contains(&PathBuf::from(
+            "unit_assets/spritesheets/mage_spritesheet.png"
+        )));
+    }
+}
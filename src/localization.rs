@@ -0,0 +1,186 @@
+//! Looking up UI text in the player's language.
+//!
+//! Every language is a flat JSON string table under `assets/locale`, keyed by things like
+//! `"main_menu.play_demo"`. The tables are baked into the binary so lookups can happen anywhere
+//! (including wasm) without waiting on the asset server.
+//!
+//! Use [`tr!`](crate::tr) to look up a string. Values can have `{name}` placeholders:
+//!
+//! ```ignore
+//! tr!("controls.conflict", action = "Select")
+//! ```
+//!
+//! Text that's spawned once and sticks around should also get a [`LocalizedText`], so it
+//! follows along when the language changes in the settings.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        LazyLock,
+        atomic::{AtomicU8, Ordering},
+    },
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    Spanish,
+}
+
+impl Language {
+    pub const OPTIONS: [Language; 2] = [Language::English, Language::Spanish];
+
+    /// Always shown in the language itself, so you can find your way back
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Spanish => "Español",
+        }
+    }
+
+    fn raw_table(&self) -> &'static str {
+        match self {
+            Language::English => include_str!("../assets/locale/en.json"),
+            Language::Spanish => include_str!("../assets/locale/es.json"),
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LanguageSettings {
+    pub language: Language,
+}
+
+type StringTable = HashMap<String, String>;
+
+static STRING_TABLES: LazyLock<HashMap<Language, StringTable>> = LazyLock::new(|| {
+    Language::OPTIONS
+        .iter()
+        .map(|language| {
+            let table =
+                serde_json::from_str::<StringTable>(language.raw_table()).unwrap_or_else(|e| {
+                    error!("Failed to parse string table for {:?}: {:?}", language, e);
+                    StringTable::default()
+                });
+            (*language, table)
+        })
+        .collect()
+});
+
+static ACTIVE_LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+pub fn active_language() -> Language {
+    Language::OPTIONS
+        .get(ACTIVE_LANGUAGE.load(Ordering::Relaxed) as usize)
+        .copied()
+        .unwrap_or_default()
+}
+
+pub fn set_active_language(language: Language) {
+    let index = Language::OPTIONS
+        .iter()
+        .position(|t| *t == language)
+        .unwrap_or_default();
+    ACTIVE_LANGUAGE.store(index as u8, Ordering::Relaxed);
+}
+
+/// Looks up `key` in the active language, falling back to English, and then to the key itself
+/// so missing strings are easy to spot.
+pub fn translate(key: &str) -> String {
+    let lookup = |language: Language| STRING_TABLES.get(&language).and_then(|t| t.get(key));
+
+    match lookup(active_language()).or_else(|| lookup(Language::English)) {
+        Some(value) => value.clone(),
+        None => {
+            warn!("No string for localization key {:?}", key);
+            key.to_string()
+        }
+    }
+}
+
+/// [`translate`], filling in `{name}` placeholders.
+pub fn translate_with(key: &str, args: &[(&str, String)]) -> String {
+    let mut value = translate(key);
+    for (name, arg) in args {
+        value = value.replace(&format!("{{{}}}", name), arg);
+    }
+    value
+}
+
+/// Looks up a UI string in the active language.
+///
+/// `tr!("pause.resume")`, or with placeholders `tr!("controls.conflict", action = name)`.
+#[macro_export]
+macro_rules! tr {
+    ($key:expr) => {
+        $crate::localization::translate($key)
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::localization::translate_with(
+            $key,
+            &[$((stringify!($name), $value.to_string())),+],
+        )
+    };
+}
+
+/// Text that gets re-translated whenever the language changes.
+#[derive(Component, Debug, Clone)]
+pub struct LocalizedText {
+    pub key: &'static str,
+}
+
+/// A `Text` for `key` that keeps itself up to date with the language setting.
+pub fn localized_text(key: &'static str) -> (Text, LocalizedText) {
+    (Text::new(translate(key)), LocalizedText { key })
+}
+
+pub fn apply_language_settings(
+    settings: Res<LanguageSettings>,
+    mut texts: Query<(&mut Text, &LocalizedText)>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    info!("Using language {:?}", settings.language);
+    set_active_language(settings.language);
+    for (mut text, localized) in texts.iter_mut() {
+        text.0 = translate(localized.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_language_has_every_key() {
+        let english = STRING_TABLES.get(&Language::English).unwrap();
+        assert!(!english.is_empty());
+
+        for language in Language::OPTIONS {
+            let table = STRING_TABLES.get(&language).unwrap();
+            for key in english.keys() {
+                assert!(
+                    table.contains_key(key),
+                    "{:?} is missing {:?}",
+                    language,
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_placeholders() {
+        assert_eq!(
+            translate_with("controls.conflict", &[("action", "Select".to_string())]),
+            "That's already bound to Select"
+        );
+        assert_eq!(translate("not.a.real.key"), "not.a.real.key");
+    }
+}
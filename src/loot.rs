@@ -0,0 +1,524 @@
+//! What the party digs out of treasure chests and off of enemies over a run.
+//!
+//! Every [`TreasureChest`](crate::interactable::TreasureChest) points at a [`LootTableId`], which
+//! gets rolled when the chest is opened. Gold goes into the party's [`RunLoot`], which rides along
+//! in the run save like the run's stats do, and everything else goes in the
+//! [`PartyInventory`]. Every enemy the party takes down is worth a bit of gold too, and so is
+//! winning the fight, more so the quicker it went and the more of the party is still standing.
+//! Gold gets spent in [shops](crate::shop), and how much the party has sits in the corner of the
+//! screen for the whole run.
+//!
+//! Enemies carry [`EnemyLoot`] from their archetype, which might drop something when they go
+//! down. It goes straight into the bag, with a little note over the enemy saying what it was.
+//!
+//! Gear that drops gets an [`ItemRoll`] right then, from the run's seed, so its rarity and stats
+//! come out the same if the run is played the same way.
+
+use bevy::prelude::*;
+use rand::Rng;
+use rand_pcg::Pcg64;
+use rand_seeder::Seeder;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState,
+    assets::FontResource,
+    battle::{BattleEndCondition, BattleResultResource, Enemy},
+    battle_log::BattleLogMessage,
+    battle_phase::PhaseManager,
+    combat::{DespawnTimer, UnitHealthChangedEvent, despawn_after_timer_completed},
+    dungeon::DungeonState,
+    equipment::{ItemDB, ItemId},
+    inventory::{Consumable, PartyInventory},
+    map_generation::DungeonGenerationParams,
+    menu::ui_consts::{UI_MENU_BACKGROUND, UI_TEXT_COLOR},
+    player::Player,
+    run_save::PendingRunRestore,
+    tr,
+    turn_events::{DEFAULT_TURN_LIMIT, TurnEventSchedule},
+    unit_stats::UnitDerivedStats,
+};
+
+/// What the party gets for each enemy they take down
+pub const GOLD_PER_ENEMY: u32 = 5;
+
+/// What winning a fight is worth, with everyone standing
+const VICTORY_GOLD: u32 = 20;
+
+/// Extra gold for every turn the fight was won ahead of the turn limit
+const GOLD_PER_TURN_TO_SPARE: u32 = 2;
+
+/// Extra gold for winning without anyone going down
+const FLAWLESS_VICTORY_GOLD: u32 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LootTableId {
+    Common,
+    Rare,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LootDrop {
+    Gold(u32),
+    Item(ItemId),
+    Consumable(Consumable),
+}
+
+impl LootTableId {
+    /// What can come out of a chest, each with how likely it is next to the rest
+    pub fn entries(&self) -> &'static [(u32, LootDrop)] {
+        match self {
+            LootTableId::Common => &[
+                (6, LootDrop::Gold(10)),
+                (3, LootDrop::Gold(25)),
+                (2, LootDrop::Consumable(Consumable::Potion)),
+                (1, LootDrop::Consumable(Consumable::Antidote)),
+                (1, LootDrop::Item(ItemId(2))),
+                (1, LootDrop::Item(ItemId(3))),
+            ],
+            LootTableId::Rare => &[
+                (2, LootDrop::Gold(50)),
+                (1, LootDrop::Gold(100)),
+                (1, LootDrop::Item(ItemId(1))),
+                (1, LootDrop::Item(ItemId(2))),
+                (1, LootDrop::Item(ItemId(4))),
+                (1, LootDrop::Item(ItemId(5))),
+            ],
+        }
+    }
+
+    pub fn roll(&self, rng: &mut impl Rng) -> LootDrop {
+        *weighted_pick(self.entries(), rng)
+    }
+}
+
+/// Picks one of `entries`, each as likely as its weight is next to the rest.
+///
+/// Panics if there's nothing to pick from.
+pub fn weighted_pick<'a, T>(entries: &'a [(u32, T)], rng: &mut impl Rng) -> &'a T {
+    let total = entries.iter().map(|t| t.0).sum::<u32>();
+    let mut roll = rng.random_range(0..total);
+    for (weight, value) in entries {
+        if roll < *weight {
+            return value;
+        }
+        roll -= weight;
+    }
+    unreachable!("The roll is always under the total weight")
+}
+
+/// What an enemy might have on them when they go down
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct EnemyLoot {
+    pub table: LootTableId,
+    /// How likely they are to drop anything at all, from 0 to 1
+    pub drop_chance: f32,
+}
+
+impl EnemyLoot {
+    /// Bosses and the tougher enemies always have something on them
+    pub const fn guaranteed(table: LootTableId) -> Self {
+        EnemyLoot {
+            table,
+            drop_chance: 1.,
+        }
+    }
+
+    pub fn roll(&self, rng: &mut impl Rng) -> Option<LootDrop> {
+        if rng.random::<f32>() >= self.drop_chance {
+            return None;
+        }
+        Some(self.table.roll(rng))
+    }
+}
+
+/// The gold the party has on them this run
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunLoot {
+    pub gold: u32,
+    /// How many items have dropped so far, so each one gets its own roll
+    #[serde(default)]
+    pub drops_rolled: u32,
+    /// Saves from before the [`PartyInventory`] kept the bag in here
+    #[serde(default, rename = "items", skip_serializing)]
+    pub(crate) legacy_items: Vec<ItemId>,
+}
+
+/// What the party gets for winning a fight in `turns` turns, with `standing` out of `party_size`
+/// of them still up at the end
+pub fn victory_gold(turns: u32, turn_limit: u32, standing: usize, party_size: usize) -> u32 {
+    if party_size == 0 {
+        return 0;
+    }
+    let earned = VICTORY_GOLD + turn_limit.saturating_sub(turns) * GOLD_PER_TURN_TO_SPARE;
+    let earned = (earned as f32 * standing as f32 / party_size as f32).round() as u32;
+    if standing == party_size {
+        earned + FLAWLESS_VICTORY_GOLD
+    } else {
+        earned
+    }
+}
+
+/// Rolls how a dropped item came out. Anything else comes out how it went in.
+pub fn roll_drop(drop: LootDrop, seed: &str, loot: &mut RunLoot, item_db: &ItemDB) -> LootDrop {
+    let LootDrop::Item(item) = drop else {
+        return drop;
+    };
+    let mut rng: Pcg64 = Seeder::from(format!("{}-drop-{}", seed, loot.drops_rolled)).into_rng();
+    loot.drops_rolled += 1;
+    LootDrop::Item(item_db.roll_item(item, &mut rng))
+}
+
+/// Puts whatever got found where it belongs
+pub fn stash_drop(drop: LootDrop, loot: &mut RunLoot, inventory: &mut PartyInventory) {
+    match drop {
+        LootDrop::Gold(gold) => loot.gold += gold,
+        LootDrop::Item(item) => inventory.add_item(item),
+        LootDrop::Consumable(consumable) => inventory.add_consumable(consumable, 1),
+    }
+}
+
+/// `unit` opened a chest and found `drop`
+#[derive(Message, Debug, Clone)]
+pub struct LootFoundMessage {
+    pub unit: Entity,
+    pub drop: LootDrop,
+}
+
+pub fn loot_plugin(app: &mut App) {
+    app.add_message::<LootFoundMessage>()
+        .add_systems(OnEnter(GameState::Dungeon), (init_run_loot, spawn_gold_hud))
+        .add_systems(
+            Update,
+            update_gold_hud
+                .run_if(resource_exists_and_changed::<RunLoot>)
+                .run_if(in_state(GameState::Dungeon)),
+        )
+        .add_systems(
+            Update,
+            collect_loot
+                .run_if(in_state(GameState::Dungeon))
+                .run_if(resource_exists::<RunLoot>)
+                .run_if(resource_exists::<PartyInventory>),
+        )
+        .add_systems(
+            Update,
+            (
+                collect_bounties,
+                award_victory_gold.run_if(resource_added::<BattleResultResource>),
+            )
+                .run_if(in_state(DungeonState::InBattle))
+                .run_if(resource_exists::<RunLoot>),
+        )
+        .add_systems(
+            Update,
+            (
+                drop_enemy_loot
+                    .run_if(resource_exists::<RunLoot>)
+                    .run_if(resource_exists::<PartyInventory>),
+                despawn_after_timer_completed::<LootToast>,
+            )
+                .run_if(in_state(DungeonState::InBattle)),
+        );
+}
+
+/// A continued run still has whatever it found before
+fn init_run_loot(mut commands: Commands, pending_restore: Option<Res<PendingRunRestore>>) {
+    let loot = pending_restore
+        .map(|t| t.0.loot.clone())
+        .unwrap_or_default();
+    commands.insert_resource(loot);
+}
+
+fn collect_loot(
+    mut reader: MessageReader<LootFoundMessage>,
+    mut loot: ResMut<RunLoot>,
+    mut inventory: ResMut<PartyInventory>,
+    item_db: Res<ItemDB>,
+    dungeon_params: Res<DungeonGenerationParams>,
+    mut battle_log: MessageWriter<BattleLogMessage>,
+) {
+    for message in reader.read() {
+        let drop = roll_drop(
+            message.drop,
+            &dungeon_params.options.seed,
+            &mut loot,
+            &item_db,
+        );
+        let Some(description) = describe_drop(drop, &item_db) else {
+            continue;
+        };
+
+        stash_drop(drop, &mut loot, &mut inventory);
+        battle_log.write(BattleLogMessage::LootFound {
+            unit: message.unit,
+            description,
+        });
+    }
+}
+
+fn describe_drop(drop: LootDrop, item_db: &ItemDB) -> Option<String> {
+    let description = match drop {
+        LootDrop::Gold(gold) => tr!("loot.gold", gold = gold),
+        LootDrop::Item(item) => match item_db.equippable_item(&item) {
+            Some(item) => item.name().to_string(),
+            None => {
+                error!("Found an item that doesn't exist: {:?}", item);
+                return None;
+            }
+        },
+        LootDrop::Consumable(consumable) => tr!(consumable.name_key()),
+    };
+    Some(description)
+}
+
+/// Gear is written in the color of its rarity
+fn drop_color(drop: LootDrop, item_db: &ItemDB) -> Color {
+    match drop {
+        LootDrop::Item(item) => item_db
+            .equippable_item(&item)
+            .map(|t| t.rarity().color())
+            .unwrap_or(Color::WHITE),
+        LootDrop::Gold(_) | LootDrop::Consumable(_) => Color::linear_rgb(1.0, 0.85, 0.2),
+    }
+}
+
+/// The note over an enemy saying what they dropped
+#[derive(Component)]
+pub struct LootToast;
+
+#[allow(clippy::too_many_arguments)]
+fn drop_enemy_loot(
+    mut commands: Commands,
+    mut health_changes: MessageReader<UnitHealthChangedEvent>,
+    enemies: Query<(&UnitDerivedStats, &EnemyLoot)>,
+    mut loot: ResMut<RunLoot>,
+    mut inventory: ResMut<PartyInventory>,
+    item_db: Res<ItemDB>,
+    dungeon_params: Res<DungeonGenerationParams>,
+    fonts: Res<FontResource>,
+    mut battle_log: MessageWriter<BattleLogMessage>,
+) {
+    for message in health_changes.read() {
+        let Ok((stats, enemy_loot)) = enemies.get(message.unit) else {
+            continue;
+        };
+        if message.health_changed >= 0 || !stats.downed() {
+            continue;
+        }
+
+        // Nobody gets to drop anything twice
+        commands.entity(message.unit).remove::<EnemyLoot>();
+        let Some(drop) = enemy_loot.roll(&mut rand::rng()) else {
+            continue;
+        };
+        let drop = roll_drop(drop, &dungeon_params.options.seed, &mut loot, &item_db);
+        let Some(description) = describe_drop(drop, &item_db) else {
+            continue;
+        };
+
+        stash_drop(drop, &mut loot, &mut inventory);
+        commands.entity(message.unit).with_child((
+            Text2d(tr!("loot.dropped", item = description.clone())),
+            TextColor(drop_color(drop, &item_db)),
+            TextFont {
+                font: fonts.pixelify_sans_regular.clone(),
+                font_size: 12.,
+                font_smoothing: bevy::text::FontSmoothing::None,
+                ..Default::default()
+            },
+            LootToast,
+            DespawnTimer {
+                timer: Timer::from_seconds(1.5, TimerMode::Once),
+            },
+            Transform::from_translation(Vec3::new(0., 48., 0.)),
+            TextBackgroundColor(Color::BLACK.with_alpha(0.5)),
+        ));
+        battle_log.write(BattleLogMessage::LootDropped {
+            unit: message.unit,
+            description,
+        });
+    }
+}
+
+fn collect_bounties(
+    mut health_changes: MessageReader<UnitHealthChangedEvent>,
+    enemies: Query<&UnitDerivedStats, With<Enemy>>,
+    mut loot: ResMut<RunLoot>,
+) {
+    for message in health_changes.read() {
+        if message.health_changed < 0 && enemies.get(message.unit).is_ok_and(|t| t.downed()) {
+            loot.gold += GOLD_PER_ENEMY;
+        }
+    }
+}
+
+fn award_victory_gold(
+    battle_result: Res<BattleResultResource>,
+    phase_manager: Res<PhaseManager>,
+    schedule: Option<Res<TurnEventSchedule>>,
+    party: Query<&UnitDerivedStats, With<Player>>,
+    mut loot: ResMut<RunLoot>,
+    mut battle_log: MessageWriter<BattleLogMessage>,
+) {
+    if battle_result.0.battle_condition != BattleEndCondition::Victory {
+        return;
+    }
+
+    let turn_limit = schedule
+        .and_then(|t| t.turn_limit())
+        .unwrap_or(DEFAULT_TURN_LIMIT);
+    let standing = party.iter().filter(|t| !t.downed()).count();
+    let gold = victory_gold(
+        phase_manager.turn_count,
+        turn_limit,
+        standing,
+        party.iter().count(),
+    );
+    loot.gold += gold;
+    battle_log.write(BattleLogMessage::GoldEarned { gold });
+}
+
+/// How much gold the party has, up in the corner
+#[derive(Component)]
+struct GoldHud;
+
+fn spawn_gold_hud(mut commands: Commands, fonts: Res<FontResource>) {
+    commands.spawn((
+        Name::new("GoldHud"),
+        Node {
+            position_type: PositionType::Absolute,
+            top: px(12),
+            right: px(12),
+            padding: UiRect::axes(px(10), px(4)),
+            border_radius: BorderRadius::all(percent(20)),
+            ..Default::default()
+        },
+        BackgroundColor(UI_MENU_BACKGROUND),
+        DespawnOnExit(GameState::Dungeon),
+        children![(
+            Text::default(),
+            TextFont {
+                font: fonts.pixelify_sans_regular.clone(),
+                font_size: 20.,
+                ..Default::default()
+            },
+            TextColor(UI_TEXT_COLOR),
+            GoldHud,
+        )],
+    ));
+}
+
+fn update_gold_hud(loot: Res<RunLoot>, mut texts: Query<&mut Text, With<GoldHud>>) {
+    for mut text in texts.iter_mut() {
+        text.0 = tr!("shop.gold", gold = loot.gold);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equipment::{ItemRoll, Rarity};
+
+    #[test]
+    fn test_weighted_pick_skips_zero_weights() {
+        let mut rng = rand::rng();
+        let entries = [(0, 'a'), (3, 'b'), (0, 'c')];
+        for _ in 0..100 {
+            assert_eq!(*weighted_pick(&entries, &mut rng), 'b');
+        }
+    }
+
+    #[test]
+    fn test_rolls_only_come_from_the_table() {
+        let mut rng = rand::rng();
+        for table in [LootTableId::Common, LootTableId::Rare] {
+            for _ in 0..100 {
+                let drop = table.roll(&mut rng);
+                assert!(table.entries().iter().any(|t| t.1 == drop));
+            }
+        }
+    }
+
+    #[test]
+    fn test_guaranteed_drops_always_drop() {
+        let mut rng = rand::rng();
+        let never = EnemyLoot {
+            table: LootTableId::Common,
+            drop_chance: 0.,
+        };
+        for _ in 0..100 {
+            assert!(
+                EnemyLoot::guaranteed(LootTableId::Rare)
+                    .roll(&mut rng)
+                    .is_some()
+            );
+            assert_eq!(never.roll(&mut rng), None);
+        }
+    }
+
+    #[test]
+    fn test_gold_adds_up() {
+        let mut loot = RunLoot::default();
+        let mut inventory = PartyInventory::default();
+        stash_drop(LootDrop::Gold(10), &mut loot, &mut inventory);
+        stash_drop(LootDrop::Item(ItemId(2)), &mut loot, &mut inventory);
+        stash_drop(
+            LootDrop::Consumable(Consumable::Potion),
+            &mut loot,
+            &mut inventory,
+        );
+        stash_drop(LootDrop::Gold(25), &mut loot, &mut inventory);
+        assert_eq!(loot.gold, 35);
+        assert_eq!(inventory.items, vec![ItemId(2)]);
+        assert_eq!(inventory.consumable_count(Consumable::Potion), 1);
+    }
+
+    #[test]
+    fn test_dropped_gear_rolls_the_same_for_the_same_run() {
+        let item_db = crate::equipment::build_item_db().expect("Item DB should parse");
+        let roll = |seed: &str| {
+            let mut loot = RunLoot::default();
+            (0..10)
+                .map(|_| roll_drop(LootDrop::Item(ItemId(2)), seed, &mut loot, &item_db))
+                .collect::<Vec<_>>()
+        };
+
+        let drops = roll("seed");
+        assert_eq!(drops, roll("seed"));
+        assert_ne!(drops, roll("other seed"));
+        for drop in drops {
+            let LootDrop::Item(item) = drop else {
+                panic!("Gear should stay gear: {:?}", drop);
+            };
+            assert_eq!(item.base(), ItemId(2));
+            // The bow is never any more common than uncommon
+            assert!(
+                item.roll()
+                    .is_some_and(|t: ItemRoll| t.rarity >= Rarity::Uncommon)
+            );
+        }
+        assert_eq!(
+            roll_drop(LootDrop::Gold(5), "seed", &mut RunLoot::default(), &item_db),
+            LootDrop::Gold(5)
+        );
+    }
+
+    #[test]
+    fn test_victory_gold_rewards_a_clean_fight() {
+        let flawless = victory_gold(5, 20, 4, 4);
+        assert!(flawless > victory_gold(15, 20, 4, 4));
+        assert!(flawless > victory_gold(5, 20, 3, 4));
+        assert!(victory_gold(5, 20, 1, 4) < victory_gold(5, 20, 3, 4));
+        // Running over the limit doesn't cost anything
+        assert_eq!(victory_gold(30, 20, 4, 4), victory_gold(20, 20, 4, 4));
+        assert_eq!(victory_gold(5, 20, 0, 0), 0);
+    }
+
+    #[test]
+    fn test_old_saves_keep_their_bag() {
+        let loot: RunLoot = serde_json::from_str(r#"{"gold": 5, "items": [2]}"#).unwrap();
+        assert_eq!(loot.legacy_items, vec![ItemId(2)]);
+        assert!(!serde_json::to_string(&loot).unwrap().contains("items"));
+    }
+}
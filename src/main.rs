@@ -6,23 +6,56 @@ use bevy_pkv::{PersistentResourceAppExtensions, PkvStore};
 use clap::Parser;
 use leafwing_input_manager::plugin::InputManagerPlugin;
 use tactics_exploration::GameState;
+use tactics_exploration::accessibility::{AccessibilitySettings, accessibility_plugin};
 use tactics_exploration::animation::animation_db::load_animation_data;
 use tactics_exploration::args::Cli;
+use tactics_exploration::asset_validation::validate_assets;
 use tactics_exploration::assets::setup_fonts;
 use tactics_exploration::assets::sounds::{
-    Music, SoundManager, SoundSettings, apply_volume_settings, setup_sounds,
+    Music, SoundManager, SoundSettings, WindowFocus, apply_volume_settings, mute_when_unfocused,
+    setup_sounds, track_window_focus,
 };
 use tactics_exploration::assets::sprite_db::build_sprite_db;
 use tactics_exploration::battle::{battle_plugin, god_mode_plugin, spawn_background_gradient};
+use tactics_exploration::battle_phase::TurnModel;
+use tactics_exploration::battle_phase::phase_timer::PhaseTimerSettings;
 use tactics_exploration::camera::setup_camera;
+use tactics_exploration::combat_sfx::setup_combat_sfx;
+use tactics_exploration::contribution::XpModel;
+use tactics_exploration::controller_disconnect::controller_disconnect_plugin;
+use tactics_exploration::difficulty::difficulty_plugin;
+use tactics_exploration::drop_in::drop_in_plugin;
 use tactics_exploration::dungeon::DungeonState;
+use tactics_exploration::durability::durability_plugin;
+use tactics_exploration::hotseat::hotseat_plugin;
+use tactics_exploration::input_bindings::InputBindings;
+use tactics_exploration::input_glyphs::input_glyphs_plugin;
+use tactics_exploration::inventory::inventory_plugin;
 use tactics_exploration::join_game_menu::join_game_plugin;
+use tactics_exploration::localization::LanguageSettings;
+use tactics_exploration::loot::loot_plugin;
 use tactics_exploration::main_menu::main_menu_plugin;
+use tactics_exploration::ping::ping_plugin;
+use tactics_exploration::pixel_art::{PixelSnapSettings, pixel_art_plugin};
 use tactics_exploration::player::{Player, PlayerBundle, PlayerInputAction};
-use tactics_exploration::save_game::SaveFiles;
+use tactics_exploration::profile::{Profiles, profile_plugin};
+use tactics_exploration::rumble::{RumbleSettings, rumble_plugin};
+use tactics_exploration::run_save::run_save_plugin;
+use tactics_exploration::run_stats::run_stats_plugin;
+use tactics_exploration::save_transfer::save_transfer_plugin;
+use tactics_exploration::seed_code::seed_code_plugin;
+use tactics_exploration::spectator::spectator_plugin;
+use tactics_exploration::split_screen::{SplitScreenSettings, split_screen_plugin};
+use tactics_exploration::tooltip::tooltip_plugin;
+use tactics_exploration::trade::trade_plugin;
 
 fn main() {
     let options = Cli::parse();
+    if options.validate_assets {
+        let report = validate_assets();
+        println!("{}", report);
+        std::process::exit(if report.is_ok() { 0 } else { 1 });
+    }
 
     let mut app = App::new();
     let mut runner = &mut app;
@@ -41,8 +74,16 @@ fn main() {
                 }),
         )
         .insert_resource(PkvStore::new("bkdaugherty", "tactics-exploration"))
-        .init_persistent_resource::<SaveFiles>()
+        .init_persistent_resource::<Profiles>()
         .init_persistent_resource::<SoundSettings>()
+        .init_persistent_resource::<PhaseTimerSettings>()
+        .init_persistent_resource::<InputBindings>()
+        .init_persistent_resource::<LanguageSettings>()
+        .init_persistent_resource::<AccessibilitySettings>()
+        .init_persistent_resource::<RumbleSettings>()
+        .init_persistent_resource::<SplitScreenSettings>()
+        .init_persistent_resource::<PixelSnapSettings>()
+        .init_resource::<WindowFocus>()
         .init_state::<GameState>()
         .add_sub_state::<DungeonState>()
         .add_systems(
@@ -50,6 +91,7 @@ fn main() {
             (
                 setup_camera,
                 setup_sounds,
+                setup_combat_sfx,
                 boot_game,
                 setup_fonts,
                 load_animation_data,
@@ -60,12 +102,46 @@ fn main() {
         )
         .add_systems(
             Update,
-            (apply_volume_settings.run_if(resource_changed::<SoundSettings>),),
+            (
+                apply_volume_settings.run_if(resource_changed::<SoundSettings>),
+                (track_window_focus, mute_when_unfocused).chain(),
+            ),
         )
         .add_plugins(InputManagerPlugin::<PlayerInputAction>::default())
+        .add_plugins(profile_plugin)
         .add_plugins(join_game_plugin)
+        .add_plugins(drop_in_plugin)
+        .add_plugins(controller_disconnect_plugin)
+        .add_plugins(hotseat_plugin)
+        .add_plugins(ping_plugin)
+        .add_plugins(pixel_art_plugin)
+        .add_plugins(spectator_plugin)
         .add_plugins(main_menu_plugin)
-        .add_plugins(battle_plugin);
+        .add_plugins(battle_plugin)
+        .add_plugins(tooltip_plugin)
+        .add_plugins(input_glyphs_plugin)
+        .add_plugins(accessibility_plugin)
+        .add_plugins(rumble_plugin)
+        .add_plugins(split_screen_plugin)
+        .add_plugins(run_save_plugin)
+        .add_plugins(run_stats_plugin)
+        .add_plugins(loot_plugin)
+        .add_plugins(inventory_plugin)
+        .add_plugins(trade_plugin)
+        .add_plugins(durability_plugin)
+        .add_plugins(save_transfer_plugin)
+        .add_plugins(seed_code_plugin)
+        .add_plugins(difficulty_plugin)
+        .insert_resource(if options.initiative {
+            TurnModel::Initiative
+        } else {
+            TurnModel::Phases
+        })
+        .insert_resource(if options.flat_xp {
+            XpModel::PerAttack
+        } else {
+            XpModel::EndOfBattle
+        });
 
     // TODO: I could probably compile this out for the real game?
     if options.god_mode {
@@ -90,5 +166,5 @@ fn start_music(
 fn boot_game(mut commands: Commands, mut game_state: ResMut<NextState<GameState>>) {
     // Spawn the "PrePlayer" only once!
     commands.spawn(PlayerBundle::new(Player::PrePlayer));
-    game_state.set(GameState::MainMenu)
+    game_state.set(GameState::ProfileSelect)
 }
@@ -1,28 +1,49 @@
 use std::collections::HashSet;
 
-use bevy::{input_focus::InputDispatchPlugin, prelude::*};
+use bevy::{ecs::system::SystemParam, input_focus::InputDispatchPlugin, prelude::*};
 
 use crate::{
     GameState,
+    accessibility::{AccessibilitySettings, ColorPalette},
     assets::{
         FontResource,
         sounds::{SoundManager, SoundSettings, UiSound},
     },
+    battle_phase::phase_timer::PhaseTimerSettings,
+    confirm_dialog::{
+        ConfirmDialogAction, ConfirmDialogMessage, confirm_dialog_plugin, open_confirm_dialog,
+    },
+    input_bindings::{
+        BindingDevice, build_controls_menu, capture_rebind_input, controls_menu_action,
+        display_binding_selector_text, display_binding_text,
+    },
+    localization::{Language, LanguageSettings, apply_language_settings, localized_text},
     menu::{
-        NestedDynamicMenu, deselect_nested_menu,
+        MenuCleanup, MenuStackCommands, deselect_nested_menu,
         menu_horizontal_selector::{HorizontalSelector, handle_horizontal_selection},
         menu_navigation::{
             self, ActiveMenu, GameMenuGrid, GameMenuLatch, handle_menu_cursor_navigation,
-            highlight_menu_option,
+            highlight_menu_option, highlight_menu_option_on_pointer_over,
         },
         show_active_game_menu_only,
         ui_consts::{SELECTABLE_BUTTON_BACKGROUND, UI_MENU_BACKGROUND, UI_TEXT_COLOR},
     },
+    pause_menu::{BattlePauseState, PauseMenuMarker},
+    pixel_art::PixelSnapSettings,
     player::Player,
+    profile::{ActiveProfile, ProfileStore},
+    rumble::RumbleSettings,
+    run_save::{PendingRunRestore, load_run_save},
+    run_stats::{build_history_menu, load_run_history},
+    save_transfer::SaveTransferMessage,
+    split_screen::SplitScreenSettings,
+    tooltip::Tooltip,
+    tr,
 };
 
 pub fn main_menu_plugin(app: &mut App) {
     app.add_plugins(InputDispatchPlugin)
+        .add_plugins(confirm_dialog_plugin)
         .add_systems(OnEnter(GameState::MainMenu), main_menu_setup)
         .add_systems(
             Update,
@@ -34,26 +55,66 @@ pub fn main_menu_plugin(app: &mut App) {
                     (With<MainMenuMarker>, Without<ActiveMenu>),
                     (With<MainMenuMarker>, With<ActiveMenu>),
                 >,
+                quit_on_confirm,
+            )
+                .run_if(in_state(GameState::MainMenu)),
+        )
+        // The settings menu can also be opened from the pause menu mid battle
+        .add_systems(
+            Update,
+            (
                 display_volume_text::<MusicVolumeSelector>,
                 display_volume_text::<SfxVolumeSelector>,
                 display_volume_text::<GlobalVolumeSelector>,
+                display_phase_timer_text,
+                display_language_text,
+                display_palette_text,
+                display_rumble_text,
+                display_split_screen_text,
+                display_pixel_snap_text,
+                display_mute_unfocused_text,
                 handle_horizontal_selection::<f64>,
+                handle_horizontal_selection::<u32>,
+                handle_horizontal_selection::<Language>,
+                handle_horizontal_selection::<ColorPalette>,
+                handle_horizontal_selection::<bool>,
+                handle_horizontal_selection::<BindingDevice>,
+                display_binding_selector_text,
+                display_binding_text,
+                capture_rebind_input.before(handle_menu_cursor_navigation),
             )
-                .run_if(in_state(GameState::MainMenu)),
+                .run_if(in_state(GameState::MainMenu).or(in_state(BattlePauseState::Paused))),
         )
-        .add_observer(main_menu_action);
+        .add_systems(Update, apply_language_settings)
+        // Lives here since the main menu is always around, but it applies to every menu
+        .add_observer(highlight_menu_option_on_pointer_over)
+        .add_observer(main_menu_action)
+        .add_observer(controls_menu_action);
 }
 
 pub struct SaveSettingsSubmit {
     global_volume_selector: Entity,
     music_volume_selector: Entity,
     sfx_volume_selector: Entity,
+    mute_unfocused_selector: Entity,
+    phase_timer_selector: Entity,
+    language_selector: Entity,
+    palette_selector: Entity,
+    rumble_selector: Entity,
+    split_screen_selector: Entity,
+    pixel_snap_selector: Entity,
 }
 
 #[derive(Component)]
 enum MainMenuButtonAction {
     PlayDemo,
+    ContinueRun,
+    ExportRun,
+    ImportSave,
+    OpenHistory,
+    SwitchProfile,
     OpenSettings,
+    OpenControls,
     // TODO: Maybe pull this out into its own thing?
     SaveSettings(SaveSettingsSubmit),
     Quit,
@@ -72,25 +133,51 @@ pub struct MusicVolumeSelector;
 pub struct SfxVolumeSelector;
 
 trait VolumeSelector: Component {
+    /// Localization key for the name of the volume
     const NAME: &str;
 
     fn text(v: f64) -> String {
-        format!("{}: <- {}% ->", Self::NAME, v * 100.)
+        tr!(
+            "settings.volume_selector",
+            name = tr!(Self::NAME),
+            percent = v * 100.
+        )
     }
 }
 
 impl VolumeSelector for GlobalVolumeSelector {
-    const NAME: &str = "Global Volume";
+    const NAME: &str = "settings.global_volume";
 }
 
 impl VolumeSelector for MusicVolumeSelector {
-    const NAME: &str = "Music Volume";
+    const NAME: &str = "settings.music_volume";
 }
 
 impl VolumeSelector for SfxVolumeSelector {
-    const NAME: &str = "Sfx Volume";
+    const NAME: &str = "settings.sfx_volume";
 }
 
+#[derive(Component)]
+pub struct MuteUnfocusedSelector;
+
+#[derive(Component)]
+pub struct PhaseTimerSelector;
+
+#[derive(Component)]
+pub struct LanguageSelector;
+
+#[derive(Component)]
+pub struct PaletteSelector;
+
+#[derive(Component)]
+pub struct RumbleSelector;
+
+#[derive(Component)]
+pub struct SplitScreenSelector;
+
+#[derive(Component)]
+pub struct PixelSnapSelector;
+
 // TODO: Do these actually need to be generic types?
 fn display_volume_text<T: VolumeSelector>(
     query: Query<
@@ -110,14 +197,168 @@ fn display_volume_text<T: VolumeSelector>(
     }
 }
 
-fn build_settings_menu(
+fn display_language_text(
+    query: Query<
+        (&HorizontalSelector<Language>, &Children),
+        (
+            With<LanguageSelector>,
+            Changed<HorizontalSelector<Language>>,
+        ),
+    >,
+    mut display_query: Query<&mut Text, With<LanguageSelector>>,
+) {
+    for (selector, children) in query {
+        if let Some(value) = selector.get_current() {
+            for child in children {
+                if let Ok(mut text) = display_query.get_mut(*child) {
+                    text.0 = tr!("settings.language_selector", language = value.name());
+                }
+            }
+        }
+    }
+}
+
+fn display_palette_text(
+    query: Query<
+        (&HorizontalSelector<ColorPalette>, &Children),
+        (
+            With<PaletteSelector>,
+            Changed<HorizontalSelector<ColorPalette>>,
+        ),
+    >,
+    mut display_query: Query<&mut Text, With<PaletteSelector>>,
+) {
+    for (selector, children) in query {
+        if let Some(value) = selector.get_current() {
+            for child in children {
+                if let Ok(mut text) = display_query.get_mut(*child) {
+                    text.0 = tr!("settings.palette_selector", palette = value.name());
+                }
+            }
+        }
+    }
+}
+
+fn display_rumble_text(
+    query: Query<
+        (&HorizontalSelector<bool>, &Children),
+        (With<RumbleSelector>, Changed<HorizontalSelector<bool>>),
+    >,
+    mut display_query: Query<&mut Text, With<RumbleSelector>>,
+) {
+    for (selector, children) in query {
+        if let Some(value) = selector.get_current() {
+            for child in children {
+                if let Ok(mut text) = display_query.get_mut(*child) {
+                    text.0 = tr!(
+                        "settings.rumble_selector",
+                        state = RumbleSettings::text(value)
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn display_split_screen_text(
+    query: Query<
+        (&HorizontalSelector<bool>, &Children),
+        (With<SplitScreenSelector>, Changed<HorizontalSelector<bool>>),
+    >,
+    mut display_query: Query<&mut Text, With<SplitScreenSelector>>,
+) {
+    for (selector, children) in query {
+        if let Some(value) = selector.get_current() {
+            for child in children {
+                if let Ok(mut text) = display_query.get_mut(*child) {
+                    text.0 = tr!(
+                        "settings.split_screen_selector",
+                        state = SplitScreenSettings::text(value)
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn display_pixel_snap_text(
+    query: Query<
+        (&HorizontalSelector<bool>, &Children),
+        (With<PixelSnapSelector>, Changed<HorizontalSelector<bool>>),
+    >,
+    mut display_query: Query<&mut Text, With<PixelSnapSelector>>,
+) {
+    for (selector, children) in query {
+        if let Some(value) = selector.get_current() {
+            for child in children {
+                if let Ok(mut text) = display_query.get_mut(*child) {
+                    text.0 = tr!(
+                        "settings.pixel_snap_selector",
+                        state = PixelSnapSettings::text(value)
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn display_mute_unfocused_text(
+    query: Query<
+        (&HorizontalSelector<bool>, &Children),
+        (
+            With<MuteUnfocusedSelector>,
+            Changed<HorizontalSelector<bool>>,
+        ),
+    >,
+    mut display_query: Query<&mut Text, With<MuteUnfocusedSelector>>,
+) {
+    for (selector, children) in query {
+        if let Some(value) = selector.get_current() {
+            for child in children {
+                if let Ok(mut text) = display_query.get_mut(*child) {
+                    text.0 = tr!(
+                        "settings.mute_unfocused_selector",
+                        state = SoundSettings::mute_text(value)
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn display_phase_timer_text(
+    query: Query<
+        (&HorizontalSelector<u32>, &Children),
+        (With<PhaseTimerSelector>, Changed<HorizontalSelector<u32>>),
+    >,
+    mut display_query: Query<&mut Text, With<PhaseTimerSelector>>,
+) {
+    for (selector, children) in query {
+        if let Some(value) = selector.get_current() {
+            for child in children {
+                if let Ok(mut text) = display_query.get_mut(*child) {
+                    text.0 = PhaseTimerSettings::text(value);
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn build_settings_menu(
     commands: &mut Commands,
     font_resource: &FontResource,
     sound_settings: &SoundSettings,
+    phase_timer_settings: &PhaseTimerSettings,
+    language_settings: &LanguageSettings,
+    accessibility_settings: &AccessibilitySettings,
+    rumble_settings: &RumbleSettings,
+    split_screen_settings: &SplitScreenSettings,
+    pixel_snap_settings: &PixelSnapSettings,
 ) -> Entity {
     let button_node = Node {
         width: percent(60),
-        height: percent(20),
+        height: percent(6),
         margin: UiRect::all(percent(0.5)),
         justify_content: JustifyContent::Center,
         align_items: AlignItems::Center,
@@ -177,6 +418,122 @@ fn build_settings_menu(
         ))
         .id();
 
+    let mut selector = HorizontalSelector::new(&[true, false]);
+    selector.set_index(sound_settings.mute_when_unfocused);
+    let mute_unfocused_selector = commands
+        .spawn((
+            Button,
+            button_node.clone(),
+            MuteUnfocusedSelector,
+            selector,
+            Tooltip::new(tr!("settings.mute_unfocused_tooltip")),
+            children![(
+                Text::default(),
+                MuteUnfocusedSelector,
+                button_text_font.clone()
+            )],
+        ))
+        .id();
+
+    let mut selector = HorizontalSelector::new(&PhaseTimerSettings::OPTIONS);
+    selector.set_index(phase_timer_settings.seconds);
+    let phase_timer_selector = commands
+        .spawn((
+            Button,
+            button_node.clone(),
+            PhaseTimerSelector,
+            selector,
+            Tooltip::new(tr!("settings.phase_timer_tooltip")),
+            children![(
+                Text::default(),
+                PhaseTimerSelector,
+                button_text_font.clone()
+            )],
+        ))
+        .id();
+
+    let mut selector = HorizontalSelector::new(&Language::OPTIONS);
+    selector.set_index(language_settings.language);
+    let language_selector = commands
+        .spawn((
+            Button,
+            button_node.clone(),
+            LanguageSelector,
+            selector,
+            children![(Text::default(), LanguageSelector, button_text_font.clone())],
+        ))
+        .id();
+
+    let mut selector = HorizontalSelector::new(&ColorPalette::OPTIONS);
+    selector.set_index(accessibility_settings.palette);
+    let palette_selector = commands
+        .spawn((
+            Button,
+            button_node.clone(),
+            PaletteSelector,
+            selector,
+            Tooltip::new(tr!("settings.palette_tooltip")),
+            children![(Text::default(), PaletteSelector, button_text_font.clone())],
+        ))
+        .id();
+
+    let mut selector = HorizontalSelector::new(&[true, false]);
+    selector.set_index(rumble_settings.enabled);
+    let rumble_selector = commands
+        .spawn((
+            Button,
+            button_node.clone(),
+            RumbleSelector,
+            selector,
+            Tooltip::new(tr!("settings.rumble_tooltip")),
+            children![(Text::default(), RumbleSelector, button_text_font.clone())],
+        ))
+        .id();
+
+    let mut selector = HorizontalSelector::new(&[true, false]);
+    selector.set_index(split_screen_settings.enabled);
+    let split_screen_selector = commands
+        .spawn((
+            Button,
+            button_node.clone(),
+            SplitScreenSelector,
+            selector,
+            Tooltip::new(tr!("settings.split_screen_tooltip")),
+            children![(
+                Text::default(),
+                SplitScreenSelector,
+                button_text_font.clone()
+            )],
+        ))
+        .id();
+
+    let mut selector = HorizontalSelector::new(&[true, false]);
+    selector.set_index(pixel_snap_settings.enabled);
+    let pixel_snap_selector = commands
+        .spawn((
+            Button,
+            button_node.clone(),
+            PixelSnapSelector,
+            selector,
+            Tooltip::new(tr!("settings.pixel_snap_tooltip")),
+            children![(Text::default(), PixelSnapSelector, button_text_font.clone())],
+        ))
+        .id();
+
+    let controls_button = commands
+        .spawn((
+            Button,
+            button_node.clone(),
+            BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+            MainMenuButtonAction::OpenControls,
+            children![(
+                localized_text("settings.controls"),
+                button_text_font.clone(),
+                TextColor(UI_TEXT_COLOR),
+            )],
+        ))
+        .id();
+
     let mut settings_grid = GameMenuGrid::new_vertical();
     let save_settings_button = commands
         .spawn((
@@ -187,9 +544,16 @@ fn build_settings_menu(
                 global_volume_selector,
                 music_volume_selector,
                 sfx_volume_selector,
+                mute_unfocused_selector,
+                phase_timer_selector,
+                language_selector,
+                palette_selector,
+                rumble_selector,
+                split_screen_selector,
+                pixel_snap_selector,
             }),
             children![(
-                Text::new("Apply"),
+                localized_text("settings.apply"),
                 button_text_font.clone(),
                 TextColor(UI_TEXT_COLOR),
             ),],
@@ -200,6 +564,14 @@ fn build_settings_menu(
         global_volume_selector,
         music_volume_selector,
         sfx_volume_selector,
+        mute_unfocused_selector,
+        phase_timer_selector,
+        language_selector,
+        palette_selector,
+        rumble_selector,
+        split_screen_selector,
+        pixel_snap_selector,
+        controls_button,
         save_settings_button,
     ]);
 
@@ -210,13 +582,13 @@ fn build_settings_menu(
                 flex_direction: FlexDirection::Column,
                 align_items: AlignItems::Center,
                 width: percent(40),
-                height: percent(85),
+                height: percent(92),
                 border_radius: BorderRadius::all(percent(20)),
                 ..default()
             },
             BackgroundColor(UI_MENU_BACKGROUND),
             children![(
-                Text::new("Settings"),
+                localized_text("settings.title"),
                 TextFont {
                     font_size: 40.0,
                     font: font_resource.pixelify_sans_medium.clone(),
@@ -234,17 +606,29 @@ fn build_settings_menu(
             },
             GameMenuLatch::default(),
             MainMenuMarker,
+            MenuCleanup::Despawn,
         ))
         .add_children(&[
             global_volume_selector,
             music_volume_selector,
             sfx_volume_selector,
+            mute_unfocused_selector,
+            phase_timer_selector,
+            language_selector,
+            palette_selector,
+            rumble_selector,
+            controls_button,
             save_settings_button,
         ])
         .id()
 }
 
-fn main_menu_setup(mut commands: Commands, font_resource: Res<FontResource>) {
+fn main_menu_setup(
+    mut commands: Commands,
+    font_resource: Res<FontResource>,
+    mut pkv: ProfileStore,
+    profile: Res<ActiveProfile>,
+) {
     let menu_screen = commands
         .spawn((
             DespawnOnExit(GameState::MainMenu),
@@ -282,7 +666,68 @@ fn main_menu_setup(mut commands: Commands, font_resource: Res<FontResource>) {
             BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
             MainMenuButtonAction::PlayDemo,
             children![(
-                Text::new("Play Demo"),
+                localized_text("main_menu.play_demo"),
+                button_text_font.clone(),
+                TextColor(UI_TEXT_COLOR),
+            ),],
+        ))
+        .id();
+
+    // Only worth showing when there's a run to pick back up
+    let continue_button = load_run_save(&mut pkv).is_some().then(|| {
+        commands
+            .spawn((
+                Button,
+                button_node.clone(),
+                BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+                MainMenuButtonAction::ContinueRun,
+                children![(
+                    localized_text("main_menu.continue_run"),
+                    button_text_font.clone(),
+                    TextColor(UI_TEXT_COLOR),
+                ),],
+            ))
+            .id()
+    });
+
+    let export_run_button = continue_button.is_some().then(|| {
+        commands
+            .spawn((
+                Button,
+                button_node.clone(),
+                BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+                MainMenuButtonAction::ExportRun,
+                children![(
+                    localized_text("main_menu.export_run"),
+                    button_text_font.clone(),
+                    TextColor(UI_TEXT_COLOR),
+                ),],
+            ))
+            .id()
+    });
+
+    let import_save_button = commands
+        .spawn((
+            Button,
+            button_node.clone(),
+            BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+            MainMenuButtonAction::ImportSave,
+            children![(
+                localized_text("main_menu.import_save"),
+                button_text_font.clone(),
+                TextColor(UI_TEXT_COLOR),
+            ),],
+        ))
+        .id();
+
+    let history_button = commands
+        .spawn((
+            Button,
+            button_node.clone(),
+            BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+            MainMenuButtonAction::OpenHistory,
+            children![(
+                localized_text("main_menu.history"),
                 button_text_font.clone(),
                 TextColor(UI_TEXT_COLOR),
             ),],
@@ -296,7 +741,21 @@ fn main_menu_setup(mut commands: Commands, font_resource: Res<FontResource>) {
             BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
             MainMenuButtonAction::OpenSettings,
             children![(
-                Text::new("Settings"),
+                localized_text("main_menu.settings"),
+                button_text_font.clone(),
+                TextColor(UI_TEXT_COLOR),
+            ),],
+        ))
+        .id();
+
+    let switch_profile_button = commands
+        .spawn((
+            Button,
+            button_node.clone(),
+            BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+            MainMenuButtonAction::SwitchProfile,
+            children![(
+                localized_text("main_menu.switch_profile"),
                 button_text_font.clone(),
                 TextColor(UI_TEXT_COLOR),
             ),],
@@ -310,15 +769,28 @@ fn main_menu_setup(mut commands: Commands, font_resource: Res<FontResource>) {
             BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
             MainMenuButtonAction::Quit,
             children![(
-                Text::new("Quit"),
+                localized_text("main_menu.quit"),
                 button_text_font.clone(),
                 TextColor(UI_TEXT_COLOR),
             ),],
         ))
         .id();
 
+    let buttons = continue_button
+        .into_iter()
+        .chain([play_button])
+        .chain(export_run_button)
+        .chain([
+            import_save_button,
+            history_button,
+            settings_button,
+            switch_profile_button,
+            quit_button,
+        ])
+        .collect::<Vec<_>>();
+
     let mut main_menu_grid = menu_navigation::GameMenuGrid::new_vertical();
-    main_menu_grid.push_buttons_to_stack(&[play_button, settings_button, quit_button]);
+    main_menu_grid.push_buttons_to_stack(&buttons);
 
     let mut main_menu_column = commands.spawn((
         Node {
@@ -328,19 +800,26 @@ fn main_menu_setup(mut commands: Commands, font_resource: Res<FontResource>) {
             ..default()
         },
         BackgroundColor(UI_MENU_BACKGROUND),
-        children![(
-            Text::new("Couch Tactics"),
-            TextFont {
-                font_size: 67.0,
-                font: font_resource.pixelify_sans_medium.clone(),
-                ..default()
-            },
-            TextColor(UI_TEXT_COLOR),
-            Node {
-                margin: UiRect::all(px(50)),
-                ..default()
-            },
-        )],
+        children![
+            (
+                localized_text("main_menu.title"),
+                TextFont {
+                    font_size: 67.0,
+                    font: font_resource.pixelify_sans_medium.clone(),
+                    ..default()
+                },
+                TextColor(UI_TEXT_COLOR),
+                Node {
+                    margin: UiRect::all(px(50)),
+                    ..default()
+                },
+            ),
+            (
+                Text(tr!("main_menu.profile", name = profile.0.name)),
+                button_text_font.clone(),
+                TextColor(UI_TEXT_COLOR),
+            )
+        ],
         main_menu_grid,
         menu_navigation::GameMenuController {
             players: HashSet::from([Player::PrePlayer]),
@@ -350,36 +829,114 @@ fn main_menu_setup(mut commands: Commands, font_resource: Res<FontResource>) {
         MainMenuMarker,
     ));
 
-    main_menu_column.add_children(&[play_button, settings_button, quit_button]);
+    main_menu_column.add_children(&buttons);
     let menu_column_id = main_menu_column.id();
 
     let mut menu_screen = commands.entity(menu_screen);
     menu_screen.add_children(&[menu_column_id]);
 }
 
+fn quit_on_confirm(
+    mut reader: MessageReader<ConfirmDialogMessage>,
+    mut app_exit_writer: MessageWriter<AppExit>,
+) {
+    if reader
+        .read()
+        .any(|t| t.action == ConfirmDialogAction::QuitGame && t.confirmed)
+    {
+        app_exit_writer.write(AppExit::Success);
+    }
+}
+
+/// Every persistent setting the settings menu can change
+#[derive(SystemParam)]
+struct SettingsResources<'w> {
+    sound: ResMut<'w, SoundSettings>,
+    phase_timer: ResMut<'w, PhaseTimerSettings>,
+    language: ResMut<'w, LanguageSettings>,
+    accessibility: ResMut<'w, AccessibilitySettings>,
+    rumble: ResMut<'w, RumbleSettings>,
+    split_screen: ResMut<'w, SplitScreenSettings>,
+    pixel_snap: ResMut<'w, PixelSnapSettings>,
+}
+
+#[allow(clippy::too_many_arguments)]
 fn main_menu_action(
     mut click: On<Pointer<Click>>,
     mut commands: Commands,
     menu_button: Query<&MainMenuButtonAction, With<Button>>,
     sounds: Res<SoundManager>,
-    mut app_exit_writer: MessageWriter<AppExit>,
     mut game_state: ResMut<NextState<GameState>>,
     parent_query: Query<&ChildOf>,
     setting_query: Query<&HorizontalSelector<f64>>,
+    phase_timer_query: Query<&HorizontalSelector<u32>>,
+    language_query: Query<&HorizontalSelector<Language>>,
+    palette_query: Query<&HorizontalSelector<ColorPalette>>,
+    rumble_query: Query<&HorizontalSelector<bool>>,
+    menu_query: Query<(&menu_navigation::GameMenuController, Has<PauseMenuMarker>)>,
     fonts: Res<FontResource>,
-    mut sound_settings: ResMut<SoundSettings>,
+    mut pkv: ProfileStore,
+    mut settings: SettingsResources,
 ) {
     let button_entity = click.entity;
     if let Ok(menu_button_action) = menu_button.get(button_entity) {
-        sounds.play_ui_sound(&mut commands, &sound_settings, UiSound::Select);
+        sounds.play_ui_sound(&mut commands, &settings.sound, UiSound::Select);
         click.propagate(false);
         match menu_button_action {
             MainMenuButtonAction::Quit => {
-                app_exit_writer.write(AppExit::Success);
+                let Some(main_menu_column) = parent_query.get(button_entity).ok() else {
+                    error!("No UI parent for Quit Button?");
+                    return;
+                };
+
+                open_confirm_dialog(
+                    &mut commands,
+                    &fonts,
+                    ConfirmDialogAction::QuitGame,
+                    main_menu_column.parent(),
+                    HashSet::from([Player::PrePlayer]),
+                );
             }
             MainMenuButtonAction::PlayDemo => {
                 game_state.set(GameState::JoinGame);
             }
+            MainMenuButtonAction::ContinueRun => {
+                let Some(save) = load_run_save(&mut pkv) else {
+                    error!("Run save went missing?");
+                    return;
+                };
+
+                // The controllers still need picking, so it goes through the join screen too
+                commands.insert_resource(PendingRunRestore(save));
+                game_state.set(GameState::JoinGame);
+            }
+            MainMenuButtonAction::ExportRun => {
+                commands.write_message(SaveTransferMessage::ExportRun);
+            }
+            MainMenuButtonAction::ImportSave => {
+                commands.write_message(SaveTransferMessage::Import);
+            }
+            MainMenuButtonAction::OpenHistory => {
+                let Some(ui) = parent_query.get(button_entity).ok() else {
+                    error!("No UI parent for OpenHistory Button?");
+                    return;
+                };
+                let main_menu_column = ui.parent();
+
+                let Some(menu_screen) = parent_query.get(main_menu_column).ok() else {
+                    error!("No parent for MainMenu column?");
+                    return;
+                };
+
+                let history =
+                    build_history_menu(&mut commands, &fonts, &load_run_history(&mut pkv));
+                commands.push_menu(main_menu_column, history);
+
+                commands.entity(menu_screen.parent()).add_child(history);
+            }
+            MainMenuButtonAction::SwitchProfile => {
+                game_state.set(GameState::ProfileSelect);
+            }
             MainMenuButtonAction::OpenSettings => {
                 let Some(ui) = parent_query.get(button_entity).ok() else {
                     error!("No UI parent for OpenSettings Button?");
@@ -392,21 +949,64 @@ fn main_menu_action(
                     return;
                 };
 
-                commands.entity(main_menu_column).remove::<ActiveMenu>();
-                let settings = build_settings_menu(&mut commands, &fonts, &sound_settings);
-                commands.entity(settings).insert((
-                    ActiveMenu {},
-                    NestedDynamicMenu {
-                        parent: main_menu_column,
-                    },
-                ));
+                let settings = build_settings_menu(
+                    &mut commands,
+                    &fonts,
+                    &settings.sound,
+                    &settings.phase_timer,
+                    &settings.language,
+                    &settings.accessibility,
+                    &settings.rumble,
+                    &settings.split_screen,
+                    &settings.pixel_snap,
+                );
+                commands.push_menu(main_menu_column, settings);
 
                 commands.entity(menu_screen.parent()).add_child(settings);
             }
+            MainMenuButtonAction::OpenControls => {
+                let Some(settings_menu) = parent_query.get(button_entity).ok().map(|t| t.parent())
+                else {
+                    error!("No UI parent for OpenControls Button?");
+                    return;
+                };
+
+                let Some(menu_screen) = parent_query.get(settings_menu).ok() else {
+                    error!("No parent for Settings menu?");
+                    return;
+                };
+
+                let Ok((settings_controller, paused)) = menu_query.get(settings_menu) else {
+                    error!("Settings menu has no controller?");
+                    return;
+                };
+
+                let controls = build_controls_menu(&mut commands, &fonts);
+                commands
+                    .entity(controls)
+                    .insert(menu_navigation::GameMenuController {
+                        players: settings_controller.players.clone(),
+                    });
+                commands.push_menu(settings_menu, controls);
+                if paused {
+                    commands.entity(controls).insert(PauseMenuMarker);
+                } else {
+                    commands.entity(controls).insert(MainMenuMarker);
+                }
+
+                commands.entity(menu_screen.parent()).add_child(controls);
+            }
             MainMenuButtonAction::SaveSettings(SaveSettingsSubmit {
                 global_volume_selector,
                 music_volume_selector,
                 sfx_volume_selector,
+                mute_unfocused_selector,
+                phase_timer_selector,
+                language_selector,
+                palette_selector,
+                rumble_selector,
+                split_screen_selector,
+                pixel_snap_selector,
             }) => {
                 let Some(global_volume) = setting_query
                     .get(*global_volume_selector)
@@ -435,11 +1035,96 @@ fn main_menu_action(
                     return;
                 };
 
-                sound_settings.global_volume = global_volume;
-                sound_settings.music_volume = music_volume;
-                sound_settings.sfx_volume = sfx_volume;
+                settings.sound.global_volume = global_volume;
+                settings.sound.music_volume = music_volume;
+                settings.sound.sfx_volume = sfx_volume;
+
+                let Some(mute_when_unfocused) = rumble_query
+                    .get(*mute_unfocused_selector)
+                    .ok()
+                    .and_then(|t| t.get_current())
+                else {
+                    error!("No Mute When Unfocused!");
+                    return;
+                };
+                settings.sound.mute_when_unfocused = mute_when_unfocused;
+
+                info!("Updated Sound Settings: {:?}", settings.sound);
+
+                let Some(phase_timer_seconds) = phase_timer_query
+                    .get(*phase_timer_selector)
+                    .ok()
+                    .and_then(|t| t.get_current())
+                else {
+                    error!("No Phase Timer!");
+                    return;
+                };
+
+                settings.phase_timer.seconds = phase_timer_seconds;
+                info!("Updated Phase Timer Settings: {:?}", settings.phase_timer);
+
+                let Some(language) = language_query
+                    .get(*language_selector)
+                    .ok()
+                    .and_then(|t| t.get_current())
+                else {
+                    error!("No Language!");
+                    return;
+                };
+
+                settings.language.language = language;
+                info!("Updated Language Settings: {:?}", settings.language);
+
+                let Some(palette) = palette_query
+                    .get(*palette_selector)
+                    .ok()
+                    .and_then(|t| t.get_current())
+                else {
+                    error!("No Palette!");
+                    return;
+                };
+
+                settings.accessibility.palette = palette;
+                info!(
+                    "Updated Accessibility Settings: {:?}",
+                    settings.accessibility
+                );
+
+                let Some(rumble) = rumble_query
+                    .get(*rumble_selector)
+                    .ok()
+                    .and_then(|t| t.get_current())
+                else {
+                    error!("No Rumble!");
+                    return;
+                };
+
+                settings.rumble.enabled = rumble;
+                info!("Updated Rumble Settings: {:?}", settings.rumble);
+
+                let Some(split_screen) = rumble_query
+                    .get(*split_screen_selector)
+                    .ok()
+                    .and_then(|t| t.get_current())
+                else {
+                    error!("No Split Screen!");
+                    return;
+                };
+
+                settings.split_screen.enabled = split_screen;
+                info!("Updated Split Screen Settings: {:?}", settings.split_screen);
+
+                let Some(pixel_snap) = rumble_query
+                    .get(*pixel_snap_selector)
+                    .ok()
+                    .and_then(|t| t.get_current())
+                else {
+                    error!("No Pixel Snap!");
+                    return;
+                };
 
-                info!("Updated Sound Settings: {:?}", sound_settings)
+                settings.pixel_snap.enabled = pixel_snap;
+                info!("Updated Pixel Snap Settings: {:?}", settings.pixel_snap);
             }
         }
     }
@@ -0,0 +1,416 @@
+//! A rough room editor for god mode, for designing encounters without leaving the game.
+//!
+//! F2 turns the room the party is in into a [`RoomTemplate`] to edit. The number keys (and minus)
+//! pick a brush, holding B paints it under every cursor, and F5 writes the template out as a RON
+//! file under `assets/rooms`. The ground and obstacles change as they get painted, and markers show
+//! where the party, the enemies, the boss, any allies and the chests go. The entrance bridge only moves once
+//! the room is played from the saved template.
+//!
+//! Saved rooms still need adding to the bundled templates before the dungeon will pick them.
+
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::{
+    GameState,
+    animation::TinytacticsAssets,
+    assets::FontResource,
+    dungeon::{DungeonEntity, DungeonManager, DungeonState},
+    grid::{GridManagerResource, GridPosition, init_grid_to_world_transform},
+    grid_cursor,
+    loot::LootTableId,
+    map_generation::{
+        GrassTileType, LayerId, MapData, Obstacle, RoomType, TileType, from_game_space,
+    },
+    menu::ui_consts::UI_TEXT_COLOR,
+    room_templates::{RoomTemplate, TEMPLATE_SIZE, TemplateCell},
+    save_game::unix_now,
+    unit::{ObstacleSprite, spawn_obstacle_unit},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Brush {
+    Cell(TemplateCell),
+    /// Moves the entrance so the party starts under the cursor
+    PlayerStart,
+}
+
+const BRUSHES: [(KeyCode, Brush); 11] = [
+    (
+        KeyCode::Digit1,
+        Brush::Cell(TemplateCell::Ground(GrassTileType::Grass)),
+    ),
+    (
+        KeyCode::Digit2,
+        Brush::Cell(TemplateCell::Ground(GrassTileType::DeadGrass)),
+    ),
+    (
+        KeyCode::Digit3,
+        Brush::Cell(TemplateCell::Obstacle(Obstacle::Rock1)),
+    ),
+    (
+        KeyCode::Digit4,
+        Brush::Cell(TemplateCell::Obstacle(Obstacle::Bush)),
+    ),
+    (
+        KeyCode::Digit5,
+        Brush::Cell(TemplateCell::Obstacle(Obstacle::Tree)),
+    ),
+    (KeyCode::Digit6, Brush::Cell(TemplateCell::EnemySpawn)),
+    (KeyCode::Digit7, Brush::Cell(TemplateCell::BossSpawn)),
+    (
+        KeyCode::Digit8,
+        Brush::Cell(TemplateCell::Chest(LootTableId::Common)),
+    ),
+    (
+        KeyCode::Digit9,
+        Brush::Cell(TemplateCell::Chest(LootTableId::Rare)),
+    ),
+    (KeyCode::Digit0, Brush::PlayerStart),
+    (KeyCode::Minus, Brush::Cell(TemplateCell::AllySpawn)),
+];
+
+/// The room being edited, around for as long as the editor is open
+#[derive(Resource)]
+pub struct MapEditor {
+    template: RoomTemplate,
+    brush: Brush,
+}
+
+/// Anything the editor puts on screen, which goes away when it closes
+#[derive(Component)]
+struct EditorMarker;
+
+pub fn map_editor_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            toggle_map_editor,
+            (
+                pick_brush,
+                paint_under_cursors,
+                save_template,
+                redraw_editor_markers.run_if(resource_changed::<MapEditor>),
+            )
+                .chain()
+                .run_if(resource_exists::<MapEditor>),
+        )
+            .chain()
+            .run_if(in_state(GameState::Dungeon)),
+    )
+    .add_systems(OnEnter(DungeonState::UnloadRoom), close_map_editor);
+}
+
+/// What's in the room at `position`, in game space
+fn cell_from_map(map_data: &MapData, position: GridPosition) -> TemplateCell {
+    if let Some(obstacle) = map_data.obstacles.get(&position) {
+        return TemplateCell::Obstacle(*obstacle);
+    }
+    if map_data.boss_spawn == Some(position) {
+        return TemplateCell::BossSpawn;
+    }
+    if map_data.enemy_spawns.contains(&position) {
+        return TemplateCell::EnemySpawn;
+    }
+    if map_data.ally_spawns.contains(&position) {
+        return TemplateCell::AllySpawn;
+    }
+    if let Some(chest) = map_data.chests.iter().find(|t| t.position == position) {
+        return TemplateCell::Chest(chest.loot_table);
+    }
+
+    let ground = map_data
+        .tiles
+        .get(&LayerId(1))
+        .and_then(|t| t.get(&from_game_space(position)));
+    match ground {
+        Some(TileType::Grass(grass)) => TemplateCell::Ground(*grass),
+        _ => TemplateCell::Ground(GrassTileType::Grass),
+    }
+}
+
+/// The room as a template, if it's the size templates are
+fn template_from_map(map_data: &MapData, name: String) -> Option<RoomTemplate> {
+    if map_data.game_size() != (TEMPLATE_SIZE, TEMPLATE_SIZE) {
+        return None;
+    }
+
+    let layout = (0..TEMPLATE_SIZE)
+        .map(|x| {
+            (0..TEMPLATE_SIZE)
+                .map(|y| cell_from_map(map_data, GridPosition { x, y }).to_char())
+                .collect::<String>()
+        })
+        .collect();
+    let exit = map_data.bridge_end_locations.first().map(|t| t.y);
+
+    Some(RoomTemplate {
+        name,
+        room_type: if exit.is_some() {
+            RoomType::Standard
+        } else {
+            RoomType::BossRoom
+        },
+        entrance: map_data.player_start_locations[0].y,
+        exit,
+        biome: Some(map_data.biome),
+        layout,
+    })
+}
+
+fn toggle_map_editor(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    editor: Option<Res<MapEditor>>,
+    dungeon_manager: Option<Res<DungeonManager>>,
+    markers: Query<Entity, With<EditorMarker>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F2) {
+        return;
+    }
+
+    if editor.is_some() {
+        info!("Closing the map editor");
+        commands.remove_resource::<MapEditor>();
+        for marker in markers.iter() {
+            commands.entity(marker).despawn();
+        }
+        return;
+    }
+
+    let Some(map_data) = dungeon_manager.as_ref().and_then(|t| t.current_map()) else {
+        warn!("No room to edit");
+        return;
+    };
+    let name = format!("Editor Room {}", unix_now());
+    let Some(template) = template_from_map(map_data, name) else {
+        warn!(
+            "Only rooms the size of a template can be edited, this one is {:?}",
+            map_data.game_size()
+        );
+        return;
+    };
+
+    info!("Editing the room, 1-0 picks a brush, B paints and F5 saves");
+    commands.insert_resource(MapEditor {
+        template,
+        brush: BRUSHES[0].1,
+    });
+}
+
+fn close_map_editor(mut commands: Commands) {
+    commands.remove_resource::<MapEditor>();
+}
+
+fn pick_brush(keyboard_input: Res<ButtonInput<KeyCode>>, mut editor: ResMut<MapEditor>) {
+    if let Some((_, brush)) = BRUSHES
+        .iter()
+        .find(|(key, _)| keyboard_input.just_pressed(*key))
+    {
+        editor.brush = *brush;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn paint_under_cursors(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut editor: ResMut<MapEditor>,
+    tt_assets: Res<TinytacticsAssets>,
+    mut grid_manager_res: ResMut<GridManagerResource>,
+    cursors: Query<&GridPosition, With<grid_cursor::Cursor>>,
+    obstacles: Query<(Entity, &GridPosition), With<ObstacleSprite>>,
+    tilemaps: Query<&TileStorage>,
+    mut tiles: Query<(&mut TileTextureIndex, &mut TileColor)>,
+) {
+    if !keyboard_input.pressed(KeyCode::KeyB) {
+        return;
+    }
+
+    for position in cursors.iter().copied() {
+        let cell = match editor.brush {
+            Brush::PlayerStart => {
+                let entrance = position.y.min(TEMPLATE_SIZE - 2);
+                if editor.template.entrance != entrance {
+                    editor.template.entrance = entrance;
+                }
+                continue;
+            }
+            Brush::Cell(cell) => cell,
+        };
+        if editor.template.cell(position).is_none_or(|t| t == cell) {
+            continue;
+        }
+
+        // A room only has the one boss
+        let old_boss_spawn = editor
+            .template
+            .cells()
+            .find(|(_, t)| *t == TemplateCell::BossSpawn)
+            .map(|(t, _)| t);
+        if cell == TemplateCell::BossSpawn
+            && let Some(old_spawn) = old_boss_spawn
+        {
+            editor
+                .template
+                .paint(old_spawn, TemplateCell::Ground(GrassTileType::Grass));
+        }
+        editor.template.paint(position, cell);
+
+        for (entity, _) in obstacles.iter().filter(|(_, t)| **t == position) {
+            grid_manager_res.grid_manager.remove_entity(&entity);
+            commands.entity(entity).despawn();
+        }
+        if let TemplateCell::Obstacle(obstacle) = cell {
+            spawn_obstacle_unit(&mut commands, &tt_assets, position, obstacle.into());
+        }
+
+        let grass = TileType::Grass(match cell {
+            TemplateCell::Ground(grass) => grass,
+            _ => GrassTileType::Grass,
+        });
+        let tile_pos = from_game_space(position);
+        let tile_pos = TilePos {
+            x: tile_pos.x,
+            y: tile_pos.y,
+        };
+        let tint = editor
+            .template
+            .biome
+            .map(|t| t.tile_tint(&grass))
+            .unwrap_or(Color::WHITE);
+        for storage in tilemaps.iter() {
+            let Some((mut texture, mut color)) =
+                storage.get(&tile_pos).and_then(|t| tiles.get_mut(t).ok())
+            else {
+                continue;
+            };
+            // Only the ground gets painted, not the water under it
+            if matches!(
+                TileType::from_texture_index(texture.0),
+                Some(TileType::Grass(_))
+            ) {
+                *texture = grass.tile_texture_index();
+                *color = TileColor(tint);
+            }
+        }
+    }
+}
+
+fn redraw_editor_markers(
+    mut commands: Commands,
+    editor: Res<MapEditor>,
+    fonts: Res<FontResource>,
+    markers: Query<Entity, With<EditorMarker>>,
+) {
+    for marker in markers.iter() {
+        commands.entity(marker).despawn();
+    }
+
+    let font = TextFont {
+        font: fonts.pixelify_sans_medium.clone(),
+        font_size: 14.,
+        ..default()
+    };
+
+    let spawns = editor.template.cells().filter_map(|(position, cell)| {
+        let label = match cell {
+            TemplateCell::EnemySpawn
+            | TemplateCell::BossSpawn
+            | TemplateCell::AllySpawn
+            | TemplateCell::Chest(_) => cell.to_char(),
+            _ => return None,
+        };
+        Some((position, label))
+    });
+    let starts = editor
+        .template
+        .player_start_locations()
+        .into_iter()
+        .map(|t| (t, 'P'));
+
+    for (position, label) in spawns.chain(starts) {
+        let mut transform = init_grid_to_world_transform(&position);
+        transform.translation.z += 1.;
+        commands.spawn((
+            Text2d::new(label.to_string()),
+            font.clone(),
+            TextColor(UI_TEXT_COLOR),
+            transform,
+            EditorMarker,
+            DungeonEntity,
+        ));
+    }
+
+    commands.spawn((
+        Text::new(format!("Map editor: {:?}", editor.brush)),
+        TextFont {
+            font: fonts.pixelify_sans_regular.clone(),
+            font_size: 24.,
+            ..default()
+        },
+        TextColor(UI_TEXT_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            top: px(8),
+            left: px(8),
+            ..default()
+        },
+        EditorMarker,
+        DungeonEntity,
+    ));
+}
+
+fn save_template(keyboard_input: Res<ButtonInput<KeyCode>>, editor: Res<MapEditor>) {
+    if !keyboard_input.just_pressed(KeyCode::F5) {
+        return;
+    }
+
+    // Still worth keeping, it just won't show up in the dungeon until it's fixed
+    if let Err(e) = editor.template.validate() {
+        warn!("The room has problems: {:?}", e);
+    }
+
+    match write_template(&editor.template) {
+        Ok(path) => info!("Saved the room to {}", path.display()),
+        Err(e) => error!("Failed saving the room: {:?}", e),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_template(template: &RoomTemplate) -> anyhow::Result<PathBuf> {
+    let contents = ron::ser::to_string_pretty(template, ron::ser::PrettyConfig::default())?;
+    let path = PathBuf::from("assets/rooms").join(format!("editor_{}.ron", unix_now()));
+    std::fs::write(&path, contents)?;
+    Ok(path)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_template(_template: &RoomTemplate) -> anyhow::Result<PathBuf> {
+    anyhow::bail!("Rooms can't be saved from the browser")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{biome::Biome, map_generation::setup_map_data_from_template};
+
+    #[test]
+    fn test_templates_survive_a_trip_through_the_editor() {
+        let template = crate::room_templates::room_templates(&RoomType::Standard)[0];
+        let map_data = setup_map_data_from_template(template, Biome::Forest);
+        let edited =
+            template_from_map(&map_data, template.name.clone()).expect("Template sized room");
+
+        assert_eq!(edited.layout, template.layout);
+        assert_eq!(edited.entrance, template.entrance);
+        assert_eq!(edited.exit, template.exit);
+
+        let saved = ron::ser::to_string_pretty(&edited, ron::ser::PrettyConfig::default())
+            .expect("Template should serialize");
+        let loaded = ron::from_str::<RoomTemplate>(&saved).expect("Template should parse");
+        assert!(loaded.validate().is_ok());
+    }
+}
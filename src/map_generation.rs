@@ -2,13 +2,33 @@
 //! For now, we assume that a dungeon
 //! should be linear, and should be composed
 //! of DEMO_DUNGEON rooms where the final room is a boss room.
-
-use std::collections::{BTreeMap, HashMap};
-
+//!
+//! Rooms are either scattered procedurally, or built from one of the hand authored
+//! [`room_templates`](crate::room_templates) or [Tiled maps](crate::tiled_import).
+//!
+//! Procedural rooms sometimes get a plateau or two, raised ground with a ramp up to it. How high
+//! each tile is goes into the [`GridManager`](crate::grid::GridManager) along with the rest of the
+//! room.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::ops::RangeInclusive;
+
+use crate::biome::Biome;
+use crate::difficulty::{ChosenDifficulty, Difficulty};
 use crate::dungeon::DungeonEntity;
-use crate::{animation::Direction, battle::BattleEntity, grid::GridPosition};
+use crate::loot::LootTableId;
+use crate::props::{DecorationPlacement, scatter_decorations};
+use crate::room_templates::{RoomTemplate, TEMPLATE_SIZE, TemplateCell, room_templates};
+use crate::run_save::PendingRunRestore;
+use crate::seed_code::{ChosenSeed, random_seed_code};
+use crate::tiled_import::tiled_rooms;
+use crate::{
+    animation::Direction,
+    battle::BattleEntity,
+    grid::{ELEVATION_STEP, GridPosition, manhattan_distance},
+};
 pub const DEMO_DUNGEON_ROOMS: u8 = 3;
-use rand::distr::{Alphanumeric, SampleString, Uniform};
+use rand::distr::Uniform;
 use rand::prelude::*;
 use rand_pcg::Pcg64;
 use rand_seeder::Seeder;
@@ -19,27 +39,88 @@ use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
 
 pub struct MapData {
+    pub biome: Biome,
     pub grid_size: (u32, u32),
     pub tiles: BTreeMap<LayerId, BTreeMap<GridPosition, TileType>>,
     pub player_start_locations: [GridPosition; 4],
     pub bridge_start_locations: [GridPosition; 2],
     pub bridge_end_locations: Vec<GridPosition>,
     pub obstacles: HashMap<GridPosition, Obstacle>,
+    /// Loops of waypoints (in game space) for patrolling enemies to walk.
+    /// The first waypoint is where the patrolling enemy should be spawned.
+    pub patrol_routes: Vec<Vec<GridPosition>>,
+    /// Where a template wants enemies to spawn, in game space. The
+    /// [`encounters`](crate::encounters) find room for anyone past these.
+    pub enemy_spawns: Vec<GridPosition>,
+    /// Where the leader spawns as the boss, no matter which room this is
+    pub boss_spawn: Option<GridPosition>,
+    /// Where allied NPCs join the fight, if the room has any
+    pub ally_spawns: Vec<GridPosition>,
+    pub chests: Vec<ChestPlacement>,
+    /// How many steps up each raised tile is, in game space. Anything missing is flat ground.
+    pub elevation: HashMap<GridPosition, u32>,
+    /// Filled in once the room is built, see [`scatter_decorations`]
+    pub decorations: Vec<DecorationPlacement>,
+}
+
+/// Where a treasure chest goes, and what's in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChestPlacement {
+    pub position: GridPosition,
+    pub loot_table: LootTableId,
+}
+
+impl MapData {
+    /// How much of the map units can stand on, inside the water border. This is in game space, so
+    /// it's the other way around from `grid_size`.
+    pub fn game_size(&self) -> (u32, u32) {
+        (self.grid_size.1 - 4, self.grid_size.0 - 4)
+    }
+
+    /// The open tile closest to the middle of the room that nothing else has claimed, for
+    /// whatever the party should gather around
+    pub fn open_tile_near_middle(&self) -> Option<GridPosition> {
+        let (width, height) = self.game_size();
+        let middle = GridPosition {
+            x: width / 2,
+            y: height / 2,
+        };
+        let taken = self
+            .player_start_locations
+            .iter()
+            .chain(&self.bridge_end_locations)
+            .chain(self.chests.iter().map(|t| &t.position))
+            .collect::<BTreeSet<_>>();
+
+        reachable_tiles(
+            self.game_size(),
+            &self.obstacles,
+            &self.player_start_locations,
+        )
+        .into_iter()
+        .filter(|t| !taken.contains(t))
+        .min_by_key(|t| manhattan_distance(t, &middle))
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Obstacle {
     Rock1,
     Rock2,
     Bush,
     Tree,
+    Ruin,
+    Fence,
 }
 
+/// Layer 0 is the water and layer 1 the ground. Every layer past that is raised ground, drawn one
+/// elevation step higher than the one below it.
 #[derive(Debug, PartialEq, Eq, Ord, PartialOrd)]
 pub struct LayerId(pub u32);
 
 /// Game space has x and y swapped and
 /// everything is shifted down by 2 for the water barrier
-fn to_game_space(g: GridPosition) -> GridPosition {
+pub(crate) fn to_game_space(g: GridPosition) -> GridPosition {
     GridPosition {
         x: g.y - 2,
         y: g.x - 2,
@@ -80,6 +161,7 @@ pub fn build_tilemap_from_map(
                         position: pos,
                         tilemap_id: TilemapId(tilemap_entity),
                         texture_index: tile.tile_texture_index(),
+                        color: TileColor(data.biome.tile_tint(tile)),
                         ..Default::default()
                     },
                     DungeonEntity,
@@ -103,7 +185,11 @@ pub fn build_tilemap_from_map(
                     y_sort: true,
                     render_chunk_size: UVec2 { x: 3, y: 1 },
                 },
-                transform: Transform::from_translation(Vec3::new(0., 0., layer_id.0 as f32)),
+                transform: Transform::from_translation(Vec3::new(
+                    0.,
+                    layer_id.0.saturating_sub(1) as f32 * ELEVATION_STEP,
+                    layer_id.0 as f32,
+                )),
                 ..Default::default()
             },
             // TODO: Remove me? once we are managing level movement more dynamically
@@ -132,6 +218,30 @@ pub enum TileType {
 }
 
 impl TileType {
+    /// Every tile we know how to draw, for going from a texture index back to the tile
+    fn all() -> Vec<TileType> {
+        let directions = [Direction::NE, Direction::NW, Direction::SE, Direction::SW];
+        let mut tiles = vec![
+            TileType::Water(WaterTileType::Plain),
+            TileType::Grass(GrassTileType::Grass),
+            TileType::Grass(GrassTileType::DeadGrass),
+            TileType::Bridge(BridgeTileType::Plain(Direction::NE)),
+            TileType::Bridge(BridgeTileType::Plain(Direction::SE)),
+        ];
+        for direction in directions {
+            tiles.push(TileType::Water(WaterTileType::Corner(direction)));
+            tiles.push(TileType::Water(WaterTileType::Edge(direction)));
+        }
+        tiles
+    }
+
+    /// The tile drawn with the tilesheet's `index`th tile, counting from 0
+    pub fn from_texture_index(index: u32) -> Option<TileType> {
+        Self::all()
+            .into_iter()
+            .find(|t| t.tile_texture_index().0 == index)
+    }
+
     pub fn tile_texture_index(&self) -> TileTextureIndex {
         let index = match self {
             TileType::Water(water_tile_type) => water_tile_type.get_tt_index().index(),
@@ -181,6 +291,7 @@ impl WaterTileType {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GrassTileType {
     Grass,
     DeadGrass,
@@ -217,6 +328,7 @@ pub struct BattleMapOptions {
     pub(crate) seed: String,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum RoomType {
     Standard,
     BossRoom,
@@ -231,18 +343,9 @@ impl RoomType {
     }
 }
 
-pub fn setup_map_data_from_params(
-    _commands: &mut Commands,
-    seed: String,
-    room_type: RoomType,
-) -> MapData {
-    let grid_size = (17, 17);
-    let game_grid_space_x = 2..(grid_size.0 - 2);
-    let game_grid_space_y = 2..(grid_size.1 - 2);
-    let mut rng: Pcg64 = Seeder::from(seed).into_rng();
-
+/// The water the room sits in, with its shore along the edges
+fn build_water_layer(grid_size: (u32, u32)) -> BTreeMap<GridPosition, TileType> {
     let mut water_layer = BTreeMap::new();
-    let mut ground_layer = BTreeMap::new();
     let bounds_max_x = grid_size.0 - 1;
     let bounds_max_y = grid_size.1 - 1;
 
@@ -266,9 +369,47 @@ pub fn setup_map_data_from_params(
         }
     }
 
+    water_layer
+}
+
+/// Lays a bridge two tiles wide starting at `x`, over `ys`. Works in tile space, not game space.
+fn lay_bridge(
+    ground_layer: &mut BTreeMap<GridPosition, TileType>,
+    x: u32,
+    ys: RangeInclusive<u32>,
+) {
+    for i in x..=(x + 1) {
+        for y in ys.clone() {
+            ground_layer.insert(
+                GridPosition { x: i, y },
+                TileType::Bridge(BridgeTileType::Plain(Direction::NE)),
+            );
+        }
+    }
+}
+
+pub fn setup_map_data_from_params(
+    _commands: &mut Commands,
+    seed: String,
+    room_type: RoomType,
+) -> MapData {
+    let grid_size = (17, 17);
+    let game_grid_space_x = 2..(grid_size.0 - 2);
+    let game_grid_space_y = 2..(grid_size.1 - 2);
+    let biome = Biome::for_room(&seed);
+    // Kept apart from the scatter's rng, so chests and plateaus don't reshuffle the room
+    let mut chest_rng: Pcg64 = Seeder::from(format!("{}-chests", seed)).into_rng();
+    let mut plateau_rng: Pcg64 = Seeder::from(format!("{}-plateaus", seed)).into_rng();
+    let mut rng: Pcg64 = Seeder::from(seed).into_rng();
+
+    let water_layer = build_water_layer(grid_size);
+    let mut ground_layer = BTreeMap::new();
+    let bounds_max_x = grid_size.0 - 1;
+    let bounds_max_y = grid_size.1 - 1;
+
     for x in 2..=(bounds_max_x - 2) {
         for y in 2..=(bounds_max_x - 2) {
-            let tile = if rng.random::<f32>() < 0.05 {
+            let tile = if rng.random::<f32>() < biome.dead_grass_chance() {
                 GrassTileType::DeadGrass
             } else {
                 GrassTileType::Grass
@@ -280,14 +421,7 @@ pub fn setup_map_data_from_params(
     // Need to tell someone about the bridge location we've chosen
     let bridge_location_x_1 = rng.random_range(2..=(bounds_max_x - 2 - 1));
 
-    for i in bridge_location_x_1..=(bridge_location_x_1 + 1) {
-        for y in 0..=2 {
-            ground_layer.insert(
-                GridPosition { x: i, y },
-                TileType::Bridge(BridgeTileType::Plain(Direction::NE)),
-            );
-        }
-    }
+    lay_bridge(&mut ground_layer, bridge_location_x_1, 0..=2);
 
     let player_start_positions = [
         to_game_space(GridPosition {
@@ -331,14 +465,11 @@ pub fn setup_map_data_from_params(
             }),
         ];
 
-        for i in bridge_location_x_2..=(bridge_location_x_2 + 1) {
-            for y in (bounds_max_y - 2)..=bounds_max_y {
-                ground_layer.insert(
-                    GridPosition { x: i, y },
-                    TileType::Bridge(BridgeTileType::Plain(Direction::NE)),
-                );
-            }
-        }
+        lay_bridge(
+            &mut ground_layer,
+            bridge_location_x_2,
+            (bounds_max_y - 2)..=bounds_max_y,
+        );
 
         let on_bridge_end_locations = [
             bridge_end_no_block_locations[0],
@@ -365,23 +496,21 @@ pub fn setup_map_data_from_params(
             }
 
             let sample = rng.sample(Uniform::new(0.0, 1.0).expect("0 is less than 1"));
-            if sample > 0.05 {
+            if sample > biome.obstacle_chance() {
                 continue;
             }
 
             // Spawn an obstacle
-            let obstacle = match rng.random_range(0..=1) {
-                0 => Obstacle::Rock2,
-                1 => Obstacle::Bush,
-                _ => unreachable!(),
-            };
+            let obstacle = *biome
+                .obstacles()
+                .choose(&mut rng)
+                .expect("Every biome has obstacles");
 
             obstacles.insert(game_position, obstacle);
         }
     }
 
-    // TODO: Can't put trees in as a layer actually as I have those Z Index problems
-    // I need to manage these as their own entities
+    // Trees can't be a tile layer because of Z index problems, so every obstacle is its own entity
     for y in game_grid_space_y {
         let candidate_pos = to_game_space(GridPosition { x: 2, y });
 
@@ -396,7 +525,7 @@ pub fn setup_map_data_from_params(
         }
 
         if rng.random::<f32>() < 0.1 {
-            obstacles.insert(candidate_pos, Obstacle::Tree);
+            obstacles.insert(candidate_pos, biome.edge_obstacle());
         }
     }
 
@@ -417,29 +546,453 @@ pub fn setup_map_data_from_params(
         }
 
         if rng.random::<f32>() < 0.1 {
-            obstacles.insert(candidate_pos, Obstacle::Tree);
+            obstacles.insert(candidate_pos, biome.edge_obstacle());
         }
     }
 
+    let patrol_routes = build_patrol_routes(&mut rng, &obstacles, &bridge_end_no_block_locations);
+
+    let blocked = player_start_positions
+        .iter()
+        .chain(&bridge_end_no_block_locations)
+        .chain(patrol_routes.iter().flatten())
+        .copied()
+        .collect::<Vec<_>>();
+    let chests = place_chests(
+        &mut chest_rng,
+        &room_type,
+        (grid_size.0 - 4, grid_size.1 - 4),
+        &obstacles,
+        &player_start_positions,
+        &blocked,
+    );
+
+    let blocked = blocked
+        .into_iter()
+        .chain(chests.iter().map(|t| t.position))
+        .collect::<Vec<_>>();
+    let elevation = raise_plateaus(
+        &mut plateau_rng,
+        (grid_size.0 - 4, grid_size.1 - 4),
+        &mut obstacles,
+        &blocked,
+    );
+    let mut tiles = BTreeMap::from([(LayerId(0), water_layer)]);
+    tiles.extend(build_raised_layers(&ground_layer, &elevation));
+    tiles.insert(LayerId(1), ground_layer);
+
     MapData {
+        biome,
         grid_size,
-        tiles: BTreeMap::from([(LayerId(0), water_layer), (LayerId(1), ground_layer)]),
+        tiles,
         player_start_locations: player_start_positions,
         bridge_start_locations: bridge_start_positions,
         bridge_end_locations: on_bridge_end_locations,
         obstacles,
+        patrol_routes,
+        enemy_spawns: Vec::new(),
+        boss_spawn: None,
+        ally_spawns: Vec::new(),
+        chests,
+        elevation,
+        decorations: Vec::new(),
+    }
+}
+
+/// How likely a procedural room is to get each of its plateaus
+const PLATEAU_CHANCE: f32 = 0.4;
+const MAX_PLATEAUS: usize = 2;
+/// How many elevation steps up a plateau is. The ramp up to it is a step lower.
+const PLATEAU_ELEVATION: u32 = 2;
+
+/// Raises a few rectangles of ground, each with a ramp on the side facing the entrance. Plateaus
+/// that would cover any of the `blocked` tiles get dropped, and ramps are kept clear of
+/// obstacles. Returns how high every raised tile is, in game space.
+fn raise_plateaus(
+    rng: &mut Pcg64,
+    game_size: (u32, u32),
+    obstacles: &mut HashMap<GridPosition, Obstacle>,
+    blocked: &[GridPosition],
+) -> HashMap<GridPosition, u32> {
+    let mut elevation = HashMap::new();
+    for _ in 0..MAX_PLATEAUS {
+        if rng.random::<f32>() >= PLATEAU_CHANCE {
+            continue;
+        }
+
+        let width = rng.random_range(2..=4);
+        let depth = rng.random_range(2..=4);
+        // Kept off the entrance rows, so the ramp has somewhere to go
+        let origin = GridPosition {
+            x: rng.random_range(3..=(game_size.0 - width - 1)),
+            y: rng.random_range(1..=(game_size.1 - depth - 1)),
+        };
+        let ramp = GridPosition {
+            x: origin.x - 1,
+            y: origin.y + rng.random_range(0..depth),
+        };
+
+        let plateau = (0..width)
+            .flat_map(|dx| {
+                (0..depth).map(move |dy| GridPosition {
+                    x: origin.x + dx,
+                    y: origin.y + dy,
+                })
+            })
+            .collect::<Vec<_>>();
+        if plateau
+            .iter()
+            .chain([&ramp])
+            .any(|t| blocked.contains(t) || elevation.contains_key(t))
+        {
+            continue;
+        }
+
+        obstacles.remove(&ramp);
+        elevation.insert(ramp, PLATEAU_ELEVATION - 1);
+        elevation.extend(plateau.into_iter().map(|t| (t, PLATEAU_ELEVATION)));
+    }
+    elevation
+}
+
+/// Stacks a copy of the ground tile for each step a tile is raised, so the block's sides make the
+/// cliff. Works in tile space, like the ground layer.
+fn build_raised_layers(
+    ground_layer: &BTreeMap<GridPosition, TileType>,
+    elevation: &HashMap<GridPosition, u32>,
+) -> BTreeMap<LayerId, BTreeMap<GridPosition, TileType>> {
+    let mut layers = BTreeMap::<LayerId, BTreeMap<GridPosition, TileType>>::new();
+    for (position, steps) in elevation {
+        let tile_position = from_game_space(*position);
+        let grass = match ground_layer.get(&tile_position) {
+            Some(TileType::Grass(grass)) => *grass,
+            _ => GrassTileType::Grass,
+        };
+        for step in 1..=*steps {
+            layers
+                .entry(LayerId(1 + step))
+                .or_default()
+                .insert(tile_position, TileType::Grass(grass));
+        }
+    }
+    layers
+}
+
+/// Any more than this and the room starts to look like a shop
+const MAX_CHESTS: usize = 2;
+
+/// How often a chest in a standard room has the good stuff in it
+const RARE_CHEST_CHANCE: f32 = 0.2;
+
+/// Every open tile (in game space) that can be walked to from `starts`
+pub(crate) fn reachable_tiles(
+    game_size: (u32, u32),
+    obstacles: &HashMap<GridPosition, Obstacle>,
+    starts: &[GridPosition],
+) -> BTreeSet<GridPosition> {
+    let mut reachable = BTreeSet::new();
+    let mut frontier = VecDeque::from_iter(starts.iter().copied());
+    while let Some(position) = frontier.pop_front() {
+        if position.x >= game_size.0
+            || position.y >= game_size.1
+            || obstacles.contains_key(&position)
+            || !reachable.insert(position)
+        {
+            continue;
+        }
+
+        frontier.push_back(GridPosition {
+            x: position.x + 1,
+            y: position.y,
+        });
+        frontier.push_back(GridPosition {
+            x: position.x,
+            y: position.y + 1,
+        });
+        if let Some(x) = position.x.checked_sub(1) {
+            frontier.push_back(GridPosition { x, y: position.y });
+        }
+        if let Some(y) = position.y.checked_sub(1) {
+            frontier.push_back(GridPosition { x: position.x, y });
+        }
+    }
+    reachable
+}
+
+/// Puts up to [`MAX_CHESTS`] chests on open tiles that the party can actually get to, leaving the
+/// `blocked` tiles alone. The further a tile is from the entrance, the likelier it is to get a
+/// chest, so they're worth going out of the way for.
+fn place_chests(
+    rng: &mut Pcg64,
+    room_type: &RoomType,
+    game_size: (u32, u32),
+    obstacles: &HashMap<GridPosition, Obstacle>,
+    starts: &[GridPosition],
+    blocked: &[GridPosition],
+) -> Vec<ChestPlacement> {
+    // Sorted, so the same seed always picks the same tiles
+    let mut candidates = reachable_tiles(game_size, obstacles, starts)
+        .into_iter()
+        .filter(|t| !blocked.contains(t))
+        .collect::<Vec<_>>();
+
+    let count = rng.random_range(0..=MAX_CHESTS);
+    let mut chests = Vec::new();
+    for _ in 0..count {
+        // The entrance is along x = 0
+        let total = candidates.iter().map(|t| t.x + 1).sum::<u32>();
+        if total == 0 {
+            break;
+        }
+
+        let mut roll = rng.random_range(0..total);
+        let Some(index) = candidates.iter().position(|t| {
+            let found = roll < t.x + 1;
+            roll = roll.saturating_sub(t.x + 1);
+            found
+        }) else {
+            break;
+        };
+
+        let loot_table = match room_type {
+            RoomType::BossRoom => LootTableId::Rare,
+            RoomType::Standard if rng.random::<f32>() < RARE_CHEST_CHANCE => LootTableId::Rare,
+            RoomType::Standard => LootTableId::Common,
+        };
+        chests.push(ChestPlacement {
+            position: candidates.remove(index),
+            loot_table,
+        });
+    }
+    chests
+}
+
+/// How often a standard room is swapped out for a template
+const ROOM_TEMPLATE_CHANCE: f32 = 0.35;
+
+/// Builds the map for a room, which is sometimes one of the hand authored templates or Tiled maps.
+/// Boss rooms always are, as long as there's one to use. Either way it gets decorated.
+pub fn setup_room_map_data(commands: &mut Commands, seed: String, room_type: RoomType) -> MapData {
+    let mut map_data = build_room_layout(commands, seed.clone(), room_type);
+    map_data.decorations = scatter_decorations(&seed, &map_data);
+    map_data
+}
+
+fn build_room_layout(commands: &mut Commands, seed: String, room_type: RoomType) -> MapData {
+    // Kept apart from the scatter's rng, so adding templates doesn't reshuffle procedural rooms
+    let mut rng: Pcg64 = Seeder::from(format!("{}-template", seed)).into_rng();
+    let use_template = match room_type {
+        RoomType::Standard => rng.random::<f32>() < ROOM_TEMPLATE_CHANCE,
+        RoomType::BossRoom => true,
+    };
+
+    if !use_template {
+        return setup_map_data_from_params(commands, seed, room_type);
+    }
+
+    let templates = room_templates(&room_type);
+    let mut tiled_rooms = tiled_rooms(&room_type);
+    let count = templates.len() + tiled_rooms.len();
+    if count > 0 {
+        let pick = rng.random_range(0..count);
+        if let Some(template) = templates.get(pick) {
+            info!("Using the {} room template", template.name);
+            let biome = template.biome.unwrap_or_else(|| Biome::for_room(&seed));
+            return setup_map_data_from_template(template, biome);
+        }
+
+        let tiled_room = tiled_rooms.swap_remove(pick - templates.len());
+        info!("Using the {} Tiled map", tiled_room.name);
+        let mut map_data = tiled_room.map_data;
+        map_data.biome = tiled_room.biome.unwrap_or_else(|| Biome::for_room(&seed));
+        return map_data;
+    }
+
+    setup_map_data_from_params(commands, seed, room_type)
+}
+
+/// Game space back to tile space, see [`to_game_space`]
+pub(crate) fn from_game_space(g: GridPosition) -> GridPosition {
+    GridPosition {
+        x: g.y + 2,
+        y: g.x + 2,
+    }
+}
+
+pub fn setup_map_data_from_template(template: &RoomTemplate, biome: Biome) -> MapData {
+    let grid_size = (TEMPLATE_SIZE + 4, TEMPLATE_SIZE + 4);
+    let bounds_max_y = grid_size.1 - 1;
+
+    let water_layer = build_water_layer(grid_size);
+    let mut ground_layer = BTreeMap::new();
+    let mut obstacles = HashMap::new();
+    let mut enemy_spawns = Vec::new();
+    let mut boss_spawn = None;
+    let mut ally_spawns = Vec::new();
+    let mut chests = Vec::new();
+
+    for (position, cell) in template.cells() {
+        let grass = match cell {
+            TemplateCell::Ground(grass) => grass,
+            _ => GrassTileType::Grass,
+        };
+        ground_layer.insert(from_game_space(position), TileType::Grass(grass));
+
+        match cell {
+            TemplateCell::Ground(_) => {}
+            TemplateCell::Obstacle(obstacle) => {
+                obstacles.insert(position, obstacle);
+            }
+            TemplateCell::EnemySpawn => enemy_spawns.push(position),
+            TemplateCell::BossSpawn => boss_spawn = Some(position),
+            TemplateCell::AllySpawn => ally_spawns.push(position),
+            TemplateCell::Chest(loot_table) => chests.push(ChestPlacement {
+                position,
+                loot_table,
+            }),
+        }
+    }
+
+    lay_bridge(&mut ground_layer, template.entrance + 2, 0..=2);
+    if let Some(exit) = template.exit {
+        lay_bridge(
+            &mut ground_layer,
+            exit + 2,
+            (bounds_max_y - 2)..=bounds_max_y,
+        );
+    }
+
+    let player_start_locations = template.player_start_locations();
+    MapData {
+        biome,
+        grid_size,
+        tiles: BTreeMap::from([(LayerId(0), water_layer), (LayerId(1), ground_layer)]),
+        player_start_locations,
+        bridge_start_locations: [player_start_locations[0], player_start_locations[1]],
+        bridge_end_locations: template.exit_locations().into_iter().take(2).collect(),
+        obstacles,
+        patrol_routes: Vec::new(),
+        enemy_spawns,
+        boss_spawn,
+        ally_spawns,
+        chests,
+        elevation: HashMap::new(),
+        decorations: Vec::new(),
     }
 }
 
+/// Lay out a small rectangular loop on the far side of the room for
+/// a Wanderer to walk. Waypoints that landed on an obstacle are dropped.
+fn build_patrol_routes(
+    rng: &mut Pcg64,
+    obstacles: &HashMap<GridPosition, Obstacle>,
+    blocked: &[GridPosition],
+) -> Vec<Vec<GridPosition>> {
+    let origin_x = rng.random_range(5..=9);
+    let origin_y = rng.random_range(2..=8);
+
+    let route: Vec<GridPosition> = [(0, 0), (0, 3), (2, 3), (2, 0)]
+        .into_iter()
+        .map(|(dx, dy)| GridPosition {
+            x: origin_x + dx,
+            y: origin_y + dy,
+        })
+        .filter(|pos| !obstacles.contains_key(pos) && !blocked.contains(pos))
+        .collect();
+
+    // A single waypoint isn't much of a patrol
+    if route.len() < 2 {
+        return Vec::new();
+    }
+
+    vec![route]
+}
+
 #[derive(Resource)]
 pub struct MapResource {
     pub data: MapData,
 }
 
-pub fn init_map_params(mut commands: Commands) {
-    let seed = Alphanumeric.sample_string(&mut rand::rng(), 16);
-    info!("Running with seed: {:?}", seed);
+pub fn init_map_params(
+    mut commands: Commands,
+    pending_restore: Option<Res<PendingRunRestore>>,
+    chosen_seed: Option<Res<ChosenSeed>>,
+    chosen_difficulty: Option<Res<ChosenDifficulty>>,
+) {
+    // A saved run gets the same dungeon back
+    let seed = match (&pending_restore, chosen_seed) {
+        (Some(restore), _) => restore.0.seed.clone(),
+        (None, Some(chosen)) => chosen.0.clone(),
+        (None, None) => random_seed_code(),
+    };
+    let difficulty = match (&pending_restore, chosen_difficulty) {
+        (Some(restore), _) => restore.0.difficulty,
+        (None, Some(chosen)) => chosen.0,
+        (None, None) => Difficulty::default(),
+    };
+    info!("Running with seed: {:?} on {:?}", seed, difficulty);
     commands.insert_resource(DungeonGenerationParams {
         options: BattleMapOptions { seed },
-    })
+    });
+    commands.insert_resource(difficulty.config());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chests_stay_out_of_the_way() {
+        // A wall of rocks cuts off everything past the third row
+        let obstacles = (0..13)
+            .map(|y| (GridPosition { x: 3, y }, Obstacle::Rock1))
+            .collect::<HashMap<_, _>>();
+        let starts = [GridPosition { x: 0, y: 5 }, GridPosition { x: 0, y: 6 }];
+
+        for seed in 0..20 {
+            let mut rng: Pcg64 = Seeder::from(seed.to_string()).into_rng();
+            let chests = place_chests(
+                &mut rng,
+                &RoomType::Standard,
+                (13, 13),
+                &obstacles,
+                &starts,
+                &starts,
+            );
+
+            assert!(chests.len() <= MAX_CHESTS);
+            for chest in chests {
+                assert!(chest.position.x < 3);
+                assert!(!starts.contains(&chest.position));
+            }
+        }
+    }
+
+    #[test]
+    fn test_plateaus_leave_the_way_through_flat() {
+        let mut world = World::new();
+        let mut commands = world.commands();
+        let mut raised_rooms = 0;
+        for seed in 0..20 {
+            let map_data =
+                setup_map_data_from_params(&mut commands, seed.to_string(), RoomType::Standard);
+            if !map_data.elevation.is_empty() {
+                raised_rooms += 1;
+            }
+
+            let kept_flat = map_data
+                .player_start_locations
+                .iter()
+                .chain(&map_data.bridge_end_locations)
+                .chain(map_data.chests.iter().map(|t| &t.position));
+            for position in kept_flat {
+                assert!(!map_data.elevation.contains_key(position));
+            }
+            for (position, steps) in &map_data.elevation {
+                let raised = map_data.tiles.get(&LayerId(1 + steps));
+                assert!(raised.is_some_and(|t| t.contains_key(&from_game_space(*position))));
+            }
+        }
+        assert!(raised_rooms > 0);
+    }
 }
@@ -25,6 +25,29 @@ pub mod ui_consts {
 
     pub const UI_BUTTON_BACKGROUND: Color = Color::linear_rgba(0.74, 0.69, 0.62, 1.0);
     pub const UI_HEADER_BACKGROUND: Color = Color::linear_rgba(0.64, 0.59, 0.52, 1.0);
+
+    /// What the colors above turn into with the high contrast palette, see
+    /// [`crate::accessibility`]
+    pub const HIGH_CONTRAST_SWAPS: [(Color, Color); 6] = [
+        (UI_MENU_BACKGROUND, Color::BLACK),
+        (
+            HIGHLIGHTED_BUTTON_BACKGROUND,
+            Color::linear_rgba(0.0, 0.12, 0.9, 1.0),
+        ),
+        (
+            SELECTABLE_BUTTON_BACKGROUND,
+            Color::linear_rgba(0.03, 0.03, 0.03, 1.0),
+        ),
+        (UI_CONFIRMED_BUTTON_COLOR, Color::linear_rgb(0.0, 0.8, 0.1)),
+        (
+            UI_BUTTON_BACKGROUND,
+            Color::linear_rgba(0.95, 0.92, 0.85, 1.0),
+        ),
+        (
+            UI_HEADER_BACKGROUND,
+            Color::linear_rgba(0.85, 0.8, 0.7, 1.0),
+        ),
+    ];
 }
 
 // UI Navigation
@@ -104,24 +127,50 @@ pub mod menu_navigation {
 
     impl GameMenuGrid {
         pub fn new_vertical() -> Self {
+            Self::new_with_width(1)
+        }
+
+        /// A grid with `width` columns, filled in with [`GameMenuGrid::push_button_to_column`]
+        /// or [`GameMenuGrid::push_buttons_in_rows`].
+        pub fn new_with_width(width: u8) -> Self {
+            let width = width.max(1);
             Self {
-                width: 1,
-                column_heights: HashMap::from([(1, 0)]),
+                width,
+                column_heights: (1..=width).map(|x| (x, 0)).collect(),
                 buttons: HashMap::default(),
                 // This is an invalid position at the start...
                 active_position: MenuGridPosition { x: 1, y: 1 },
             }
         }
 
+        pub fn width(&self) -> u8 {
+            self.width
+        }
+
+        fn column_height(&self, col: u8) -> u8 {
+            self.column_heights.get(&col).copied().unwrap_or_default()
+        }
+
         /// Returns true if the MenuVec resulted in a change to the original position
         pub fn apply_menu_vec_to_cursor(&mut self, menu_vec: MenuVec) -> bool {
-            let mut x = self.active_position.x as i8 + menu_vec.x;
+            let mut x = self.active_position.x as i8;
             let mut y = self.active_position.y as i8 + menu_vec.y;
 
-            if x <= 0 {
-                x = self.width as i8;
-            } else if x > self.width as i8 {
-                x = 1;
+            // Step over empty columns, wrapping around the edges
+            if menu_vec.x != 0 {
+                let step = menu_vec.x.signum();
+                for _ in 0..self.width {
+                    x += step;
+                    if x <= 0 {
+                        x = self.width as i8;
+                    } else if x > self.width as i8 {
+                        x = 1;
+                    }
+
+                    if self.column_height(x as u8) > 0 {
+                        break;
+                    }
+                }
             }
 
             let height_new = self.column_heights.get(&(x as u8));
@@ -135,7 +184,10 @@ pub mod menu_navigation {
                 return false;
             };
 
-            if y > *height_new as i8 {
+            if menu_vec.y == 0 {
+                // Moving sideways into a shorter column lands on its bottom button
+                y = y.clamp(1, (*height_new).max(1) as i8);
+            } else if y > *height_new as i8 {
                 y = 1;
             } else if y <= 0 {
                 y = *height_new as i8;
@@ -155,10 +207,30 @@ pub mod menu_navigation {
             self.buttons.get(&self.active_position)
         }
 
+        /// Moves the cursor onto `button_entity`, if it's in this menu.
+        ///
+        /// Returns true if the cursor moved.
+        pub fn set_active_menu_option(&mut self, button_entity: &Entity) -> bool {
+            match self.position_of(button_entity) {
+                Some(position) if position != self.active_position => {
+                    self.active_position = position;
+                    true
+                }
+                _ => false,
+            }
+        }
+
         pub fn reset_menu_option(&mut self) {
             self.active_position = MenuGridPosition { x: 1, y: 1 };
         }
 
+        pub fn position_of(&self, button_entity: &Entity) -> Option<MenuGridPosition> {
+            self.buttons
+                .iter()
+                .find(|(_, e)| *e == button_entity)
+                .map(|(pos, _)| *pos)
+        }
+
         /// Pushes a button the default stack of the Game Menu Grid.
         pub fn push_button_to_stack(&mut self, button_entity: Entity) -> MenuGridPosition {
             match self.add_button_to_column(1, button_entity) {
@@ -167,15 +239,83 @@ pub mod menu_navigation {
             }
         }
 
+        /// Pushes a button to the bottom of the given (1 indexed) column.
+        pub fn push_button_to_column(
+            &mut self,
+            col: u8,
+            button_entity: Entity,
+        ) -> anyhow::Result<MenuGridPosition> {
+            self.add_button_to_column(col, button_entity)
+        }
+
+        /// Lays out buttons left to right, top to bottom, so a width 3 grid with 9 buttons
+        /// ends up 3x3.
+        pub fn push_buttons_in_rows(&mut self, buttons: &[Entity]) {
+            for (i, button) in buttons.iter().enumerate() {
+                let col = (i % self.width as usize) as u8 + 1;
+                if let Err(e) = self.add_button_to_column(col, *button) {
+                    panic!("Failed to push button to column {}: {:?}", col, e);
+                }
+            }
+        }
+
+        /// Removes the button at `position`, and shifts everything below it in the column up
+        /// one to fill the gap.
+        ///
+        /// Positions of the shifted buttons change, so prefer
+        /// [`GameMenuGrid::remove_button_entity`] if you're holding on to entities.
         pub fn remove_button(&mut self, position: &MenuGridPosition) -> anyhow::Result<()> {
-            if self.buttons.remove(position).is_some()
-                && let Some(y) = self.column_heights.get_mut(&position.x)
-            {
+            if self.buttons.remove(position).is_none() {
+                return Err(anyhow::anyhow!("No button at {:?}", position));
+            }
+
+            let height = self.column_height(position.x);
+            for y in position.y + 1..=height {
+                if let Some(button) = self.buttons.remove(&MenuGridPosition { x: position.x, y }) {
+                    self.buttons.insert(
+                        MenuGridPosition {
+                            x: position.x,
+                            y: y - 1,
+                        },
+                        button,
+                    );
+                }
+            }
+            if let Some(y) = self.column_heights.get_mut(&position.x) {
                 *y = y.saturating_sub(1);
             }
+
+            self.reflow_active_position();
             Ok(())
         }
 
+        pub fn remove_button_entity(&mut self, button_entity: &Entity) -> anyhow::Result<()> {
+            let Some(position) = self.position_of(button_entity) else {
+                return Err(anyhow::anyhow!("{:?} is not in this menu", button_entity));
+            };
+            self.remove_button(&position)
+        }
+
+        /// Keeps the cursor on a real button after the grid shrinks.
+        fn reflow_active_position(&mut self) {
+            if self.buttons.contains_key(&self.active_position) {
+                return;
+            }
+
+            let height = self.column_height(self.active_position.x);
+            if height > 0 {
+                self.active_position.y = self.active_position.y.clamp(1, height);
+                return;
+            }
+
+            if let Some(x) = (1..=self.width).find(|x| self.column_height(*x) > 0) {
+                self.active_position = MenuGridPosition {
+                    x,
+                    y: self.active_position.y.clamp(1, self.column_height(x)),
+                };
+            }
+        }
+
         /// Pushes buttons to the default stack of the Game Menu Grid.
         pub fn push_buttons_to_stack(&mut self, buttons: &[Entity]) {
             for button in buttons {
@@ -188,7 +328,7 @@ pub mod menu_navigation {
             col: u8,
             button_entity: Entity,
         ) -> anyhow::Result<MenuGridPosition> {
-            if col > self.width {
+            if col == 0 || col > self.width {
                 return Err(anyhow::anyhow!(
                     "Tried to insert column, greater than width {:?}",
                     self.width
@@ -203,6 +343,79 @@ pub mod menu_navigation {
         }
     }
 
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn buttons(n: u64) -> Vec<Entity> {
+            (1..=n).map(Entity::from_bits).collect()
+        }
+
+        #[test]
+        fn test_horizontal_navigation_wraps() {
+            let buttons = buttons(9);
+            let mut grid = GameMenuGrid::new_with_width(3);
+            grid.push_buttons_in_rows(&buttons);
+
+            assert_eq!(grid.get_active_menu_option(), Some(&buttons[0]));
+            grid.apply_menu_vec_to_cursor(MenuVec { x: 1, y: 0 });
+            assert_eq!(grid.get_active_menu_option(), Some(&buttons[1]));
+            grid.apply_menu_vec_to_cursor(MenuVec { x: 0, y: 1 });
+            assert_eq!(grid.get_active_menu_option(), Some(&buttons[4]));
+            grid.apply_menu_vec_to_cursor(MenuVec { x: 1, y: 0 });
+            grid.apply_menu_vec_to_cursor(MenuVec { x: 1, y: 0 });
+            assert_eq!(grid.get_active_menu_option(), Some(&buttons[3]));
+            grid.apply_menu_vec_to_cursor(MenuVec { x: -1, y: 0 });
+            assert_eq!(grid.get_active_menu_option(), Some(&buttons[5]));
+        }
+
+        #[test]
+        fn test_moving_into_shorter_column_clamps() {
+            let buttons = buttons(5);
+            let mut grid = GameMenuGrid::new_with_width(2);
+            grid.push_buttons_in_rows(&buttons);
+
+            // Column 1 has 3 buttons, column 2 only has 2
+            grid.apply_menu_vec_to_cursor(MenuVec { x: 0, y: -1 });
+            assert_eq!(grid.get_active_menu_option(), Some(&buttons[4]));
+            grid.apply_menu_vec_to_cursor(MenuVec { x: 1, y: 0 });
+            assert_eq!(grid.get_active_menu_option(), Some(&buttons[3]));
+        }
+
+        #[test]
+        fn test_remove_button_reflows_column() {
+            let buttons = buttons(4);
+            let mut grid = GameMenuGrid::new_vertical();
+            grid.push_buttons_to_stack(&buttons);
+
+            grid.apply_menu_vec_to_cursor(MenuVec { x: 0, y: -1 });
+            assert_eq!(grid.get_active_menu_option(), Some(&buttons[3]));
+
+            grid.remove_button_entity(&buttons[1]).unwrap();
+            assert_eq!(
+                grid.position_of(&buttons[2]),
+                Some(MenuGridPosition { x: 1, y: 2 })
+            );
+            assert_eq!(grid.get_active_menu_option(), Some(&buttons[3]));
+
+            grid.remove_button_entity(&buttons[3]).unwrap();
+            assert_eq!(grid.get_active_menu_option(), Some(&buttons[2]));
+            assert!(grid.remove_button_entity(&buttons[3]).is_err());
+        }
+
+        #[test]
+        fn test_empty_columns_are_skipped() {
+            let buttons = buttons(2);
+            let mut grid = GameMenuGrid::new_with_width(3);
+            grid.push_button_to_column(1, buttons[0]).unwrap();
+            grid.push_button_to_column(3, buttons[1]).unwrap();
+
+            grid.apply_menu_vec_to_cursor(MenuVec { x: 1, y: 0 });
+            assert_eq!(grid.get_active_menu_option(), Some(&buttons[1]));
+            assert!(grid.push_button_to_column(4, buttons[0]).is_err());
+        }
+    }
+
     #[derive(Component, Clone, Reflect)]
     pub struct GameMenuController {
         /// The Vec of players that can control the Game Menu
@@ -255,6 +468,12 @@ pub mod menu_navigation {
                 if input_action_state.just_pressed(&player::PlayerInputAction::MoveCursorDown) {
                     delta.y += 1;
                 }
+                if input_action_state.just_pressed(&player::PlayerInputAction::MoveCursorLeft) {
+                    delta.x -= 1;
+                }
+                if input_action_state.just_pressed(&player::PlayerInputAction::MoveCursorRight) {
+                    delta.x += 1;
+                }
 
                 if delta != MenuVec::default() {
                     let changed = game_menu.apply_menu_vec_to_cursor(delta);
@@ -301,6 +520,22 @@ pub mod menu_navigation {
         }
     }
 
+    /// Hovering a button with a real mouse moves the menu cursor onto it, so the highlight and
+    /// what a click selects always agree.
+    pub fn highlight_menu_option_on_pointer_over(
+        over: On<Pointer<Over>>,
+        mut commands: Commands,
+        sounds: Res<SoundManager>,
+        sound_settings: Res<SoundSettings>,
+        mut menu_query: Query<&mut GameMenuGrid, With<ActiveMenu>>,
+    ) {
+        for mut menu in menu_query.iter_mut() {
+            if menu.set_active_menu_option(&over.entity) {
+                sounds.play_ui_sound(&mut commands, &sound_settings, UiSound::MoveCursor);
+            }
+        }
+    }
+
     fn click_entity_with_fake_mouse(c: &mut Commands, entity: Entity) {
         c.trigger(Pointer::<Click> {
             entity,
@@ -473,11 +708,105 @@ pub struct NestedDynamicMenu {
     pub parent: Entity,
 }
 
+/// How a menu gets cleaned up once it's closed.
+///
+/// Menus without one are built up front and stick around, so closing them only takes away
+/// their `ActiveMenu`.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuCleanup {
+    /// The menu was built when it was opened, so the whole thing goes
+    Despawn,
+    /// The menu is a container that gets new buttons every time it's opened
+    DespawnChildren,
+}
+
+/// Opening and closing menus as a stack.
+///
+/// Each nested menu points at the one underneath it with a [`NestedDynamicMenu`], and only the
+/// menu on top of a controller's stack has `ActiveMenu`. Pushing a menu also takes `ActiveMenu`
+/// away from any other menu its players were driving, so nobody steers two at once. Popping a menu hands `ActiveMenu` back
+/// down, and cleans up the popped menu according to its [`MenuCleanup`].
+pub trait MenuStackCommands {
+    /// Opens `menu` on top of `parent`, which stops taking input until `menu` is popped.
+    fn push_menu(&mut self, parent: Entity, menu: Entity);
+    /// Closes `menu` and reactivates whatever it was opened on top of.
+    fn pop_menu(&mut self, menu: Entity);
+    /// Closes `menu` without reactivating anything, for when the whole stack is going away.
+    fn close_menu(&mut self, menu: Entity);
+}
+
+impl MenuStackCommands for Commands<'_, '_> {
+    fn push_menu(&mut self, parent: Entity, menu: Entity) {
+        self.queue(move |world: &mut World| {
+            if let Ok(mut parent) = world.get_entity_mut(parent) {
+                parent.remove::<ActiveMenu>();
+            }
+
+            let players = [menu, parent]
+                .into_iter()
+                .find_map(|e| world.get::<GameMenuController>(e))
+                .map(|t| t.players.clone())
+                .unwrap_or_default();
+            let mut active_menus =
+                world.query_filtered::<(Entity, &GameMenuController), With<ActiveMenu>>();
+            let shared = active_menus
+                .iter(world)
+                .filter(|(e, controller)| *e != menu && !controller.players.is_disjoint(&players))
+                .map(|(e, _)| e)
+                .collect::<Vec<_>>();
+            for e in shared {
+                world.entity_mut(e).remove::<ActiveMenu>();
+            }
+
+            if let Ok(mut menu) = world.get_entity_mut(menu) {
+                menu.insert((ActiveMenu {}, NestedDynamicMenu { parent }));
+            }
+        });
+    }
+
+    fn pop_menu(&mut self, menu: Entity) {
+        self.queue(move |world: &mut World| {
+            let Some(parent) = close_menu_in_world(world, menu) else {
+                return;
+            };
+            if let Ok(mut parent) = world.get_entity_mut(parent) {
+                parent.insert(ActiveMenu {});
+            }
+        });
+    }
+
+    fn close_menu(&mut self, menu: Entity) {
+        self.queue(move |world: &mut World| {
+            close_menu_in_world(world, menu);
+        });
+    }
+}
+
+/// Returns the menu underneath the one that was closed, if there was one
+fn close_menu_in_world(world: &mut World, menu: Entity) -> Option<Entity> {
+    let Ok(mut entity) = world.get_entity_mut(menu) else {
+        return None;
+    };
+
+    let parent = entity.take::<NestedDynamicMenu>().map(|t| t.parent);
+    entity.remove::<ActiveMenu>();
+    match entity.get::<MenuCleanup>().copied() {
+        Some(MenuCleanup::Despawn) => entity.despawn(),
+        Some(MenuCleanup::DespawnChildren) => {
+            entity.despawn_children();
+            entity.remove::<menu_navigation::GameMenuGrid>();
+        }
+        None => {}
+    }
+
+    parent
+}
+
 pub fn deselect_nested_menu(
     mut commands: Commands,
     sounds: Res<SoundManager>,
     sound_settings: Res<SoundSettings>,
-    menu: Query<(Entity, &NestedDynamicMenu, &GameMenuController), With<ActiveMenu>>,
+    menu: Query<(Entity, &GameMenuController), (With<ActiveMenu>, With<NestedDynamicMenu>)>,
     player_input_query: Query<(&Player, &ActionState<PlayerInputAction>)>,
 ) {
     // I have this in so many places. We could have the UI just store the entity of the
@@ -486,13 +815,12 @@ pub fn deselect_nested_menu(
     // Maybe like an owned menu component or something
     for (player, action) in player_input_query {
         if action.just_pressed(&PlayerInputAction::Deselect) {
-            for (menu_e, nested, controller) in menu {
+            for (menu_e, controller) in menu {
                 if !controller.players.contains(player) {
                     continue;
                 }
 
-                commands.entity(menu_e).remove::<ActiveMenu>();
-                commands.entity(nested.parent).insert(ActiveMenu {});
+                commands.pop_menu(menu_e);
                 sounds.play_ui_sound(&mut commands, &sound_settings, UiSound::CloseMenu);
             }
         }
@@ -511,3 +839,71 @@ pub fn show_active_game_menu_only<Inactive: QueryFilter, Active: QueryFilter>(
         node.display = Display::None;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_menu_stack(world: &mut World) -> (Entity, Entity) {
+        let root = world.spawn(ActiveMenu {}).id();
+        let nested = world.spawn(MenuCleanup::Despawn).id();
+        world.commands().push_menu(root, nested);
+        world.flush();
+        (root, nested)
+    }
+
+    #[test]
+    fn test_push_menu_moves_active_menu() {
+        let mut world = World::new();
+        let (root, nested) = open_menu_stack(&mut world);
+
+        assert!(!world.entity(root).contains::<ActiveMenu>());
+        assert!(world.entity(nested).contains::<ActiveMenu>());
+        assert_eq!(
+            world
+                .entity(nested)
+                .get::<NestedDynamicMenu>()
+                .map(|t| t.parent),
+            Some(root)
+        );
+    }
+
+    #[test]
+    fn test_push_menu_deactivates_menus_with_the_same_players() {
+        let mut world = World::new();
+        let controller = |ids: &[u32]| GameMenuController {
+            players: ids.iter().map(|t| Player::PlayerId(*t)).collect(),
+        };
+        let root = world.spawn((ActiveMenu {}, controller(&[1]))).id();
+        let shared = world.spawn((ActiveMenu {}, controller(&[1, 2]))).id();
+        let other = world.spawn((ActiveMenu {}, controller(&[3]))).id();
+        let nested = world.spawn((MenuCleanup::Despawn, controller(&[1]))).id();
+
+        world.commands().push_menu(root, nested);
+        world.flush();
+
+        assert!(world.entity(nested).contains::<ActiveMenu>());
+        assert!(!world.entity(shared).contains::<ActiveMenu>());
+        assert!(world.entity(other).contains::<ActiveMenu>());
+    }
+
+    #[test]
+    fn test_pop_menu_cleans_up() {
+        let mut world = World::new();
+        let (root, nested) = open_menu_stack(&mut world);
+
+        world.commands().pop_menu(nested);
+        world.flush();
+        assert!(world.entity(root).contains::<ActiveMenu>());
+        assert!(world.get_entity(nested).is_err());
+
+        let container = world.spawn(MenuCleanup::DespawnChildren).id();
+        let button = world.spawn(ChildOf(container)).id();
+        world.commands().push_menu(root, container);
+        world.commands().close_menu(container);
+        world.flush();
+        assert!(!world.entity(root).contains::<ActiveMenu>());
+        assert!(world.get_entity(container).is_ok());
+        assert!(world.get_entity(button).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Recoloring unit sprites by team.
+//!
+//! Everyone's drawn from the same tinytactics sheets, so without some help a mirror match is a
+//! guessing game. The sheets' clothes are all drawn in blues, which the players keep. Every other
+//! team gets a [`PaletteSwap`] that moves those blues over to its
+//! [team color](crate::accessibility::ColorPalette::team_color), and leaves everything else (skin,
+//! hair, steel) alone. So the colorblind palette recolors units too.
+//!
+//! The recolored sheets get made once each, the first time a unit needs one, and then shared by
+//! everyone wearing the same sheet in the same color.
+
+use std::collections::HashMap;
+
+use bevy::{image::ImageSampler, prelude::*};
+
+use crate::{
+    accessibility::{AccessibilitySettings, ColorPalette},
+    battle_phase::PlayerEnemyPhase,
+    unit::{ALLY_TEAM, ENEMY_TEAM, Team, Unit},
+};
+
+/// The hues (in degrees) of the clothes on the tinytactics sheets
+const TEAM_COLOR_HUES: (f32, f32) = (190., 270.);
+/// Anything greyer than this isn't clothing, even if its hue says it might be
+const MIN_TEAM_COLOR_SATURATION: f32 = 0.2;
+
+/// Moves the team color over to a different hue, keeping the shading
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaletteSwap {
+    pub hue: f32,
+}
+
+impl PaletteSwap {
+    /// The swap for each team's units, if they need one
+    pub fn for_team(team: &Team, palette: ColorPalette) -> Option<PaletteSwap> {
+        let phase = match *team {
+            t if t == ENEMY_TEAM => PlayerEnemyPhase::Enemy,
+            t if t == ALLY_TEAM => PlayerEnemyPhase::Ally,
+            _ => return None,
+        };
+        Some(PaletteSwap {
+            hue: Hsva::from(palette.team_color(phase)).hue,
+        })
+    }
+
+    /// Near enough the same swap makes near enough the same sheet
+    fn cache_key(&self) -> u16 {
+        self.hue.round() as u16
+    }
+
+    pub fn apply(&self, color: Color) -> Color {
+        let hsva = Hsva::from(color);
+        let (low, high) = TEAM_COLOR_HUES;
+        if hsva.alpha == 0.
+            || hsva.saturation < MIN_TEAM_COLOR_SATURATION
+            || !(low..=high).contains(&hsva.hue)
+        {
+            return color;
+        }
+
+        let offset = hsva.hue - (low + high) / 2.;
+        Color::from(hsva.with_hue((self.hue + offset).rem_euclid(360.)))
+    }
+
+    /// A copy of `image` with the swap done to every pixel
+    pub fn recolor(&self, image: &Image) -> Image {
+        let mut swapped = image.clone();
+        let size = image.size();
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let Ok(color) = image.get_color_at(x, y) else {
+                    continue;
+                };
+                swapped.set_color_at(x, y, self.apply(color)).ok();
+            }
+        }
+        swapped.sampler = ImageSampler::nearest();
+        swapped
+    }
+}
+
+/// Which sheet a unit's recolor was made from, so it can be put back or redone if its team (or the
+/// palette) changes
+#[derive(Component, Debug, Clone)]
+pub struct PaletteSwapped {
+    original: Handle<Image>,
+    swapped: Handle<Image>,
+}
+
+/// Every recolored sheet made so far, by the sheet and hue it was made for
+#[derive(Resource, Debug, Default)]
+pub struct PaletteSwaps(HashMap<(AssetId<Image>, u16), Handle<Image>>);
+
+pub fn palette_plugin(app: &mut App) {
+    app.init_resource::<PaletteSwaps>()
+        .add_systems(PostUpdate, swap_team_palettes);
+}
+
+fn swap_team_palettes(
+    mut commands: Commands,
+    mut swaps: ResMut<PaletteSwaps>,
+    mut images: ResMut<Assets<Image>>,
+    settings: Res<AccessibilitySettings>,
+    mut units: Query<(Entity, &Unit, &mut Sprite, Option<&PaletteSwapped>)>,
+) {
+    for (entity, unit, mut sprite, swapped) in units.iter_mut() {
+        // Anything that's put a different sheet on the unit since gets swapped from scratch
+        let original = match swapped {
+            Some(swapped) if sprite.image == swapped.swapped => swapped.original.clone(),
+            _ => sprite.image.clone(),
+        };
+
+        let Some(palette) = PaletteSwap::for_team(&unit.team, settings.palette) else {
+            if swapped.is_some() {
+                sprite.image = original;
+                commands.entity(entity).remove::<PaletteSwapped>();
+            }
+            continue;
+        };
+
+        let key = (original.id(), palette.cache_key());
+        let variant = match swaps.0.get(&key) {
+            Some(variant) => variant.clone(),
+            None => {
+                // Not loaded yet, so try again next frame
+                let Some(image) = images.get(&original) else {
+                    continue;
+                };
+                let recolored = palette.recolor(image);
+                let variant = images.add(recolored);
+                swaps.0.insert(key, variant.clone());
+                variant
+            }
+        };
+
+        if sprite.image != variant {
+            sprite.image = variant.clone();
+            commands.entity(entity).insert(PaletteSwapped {
+                original,
+                swapped: variant,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unit::PLAYER_TEAM;
+
+    #[test]
+    fn test_only_team_colors_get_swapped() {
+        assert_eq!(
+            PaletteSwap::for_team(&PLAYER_TEAM, ColorPalette::Standard),
+            None
+        );
+        let enemy = PaletteSwap::for_team(&ENEMY_TEAM, ColorPalette::Standard)
+            .expect("Enemies should be recolored");
+
+        let blue = Color::hsv(230., 0.8, 0.6);
+        let swapped = Hsva::from(enemy.apply(blue));
+        assert!((swapped.hue - 0.).abs() < 0.5, "{:?}", swapped);
+        assert!((swapped.saturation - 0.8).abs() < 0.01);
+        assert!((swapped.value - 0.6).abs() < 0.01);
+
+        let skin = Color::hsv(25., 0.5, 0.9);
+        assert_eq!(enemy.apply(skin), skin);
+        let steel = Color::hsv(220., 0.05, 0.7);
+        assert_eq!(enemy.apply(steel), steel);
+    }
+}
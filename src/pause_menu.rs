@@ -0,0 +1,407 @@
+//! Pausing in the middle of a battle.
+//!
+//! Any joined player can pause with Start / Escape. While paused, virtual time stops (so
+//! animations, movement and the phase timer all freeze), and the battle systems that respond to
+//! input or drive the phase forward are gated on [`BattlePauseState::Running`].
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::TilePos;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    GameState,
+    accessibility::AccessibilitySettings,
+    assets::{
+        FontResource,
+        sounds::{SoundManagerParam, SoundSettings, UiSound},
+    },
+    battle::{BattleEndCondition, BattleEntity, BattleResult, BattleResultResource},
+    battle_phase::phase_timer::PhaseTimerSettings,
+    confirm_dialog::{
+        ConfirmDialog, ConfirmDialogAction, ConfirmDialogMessage, open_confirm_dialog,
+    },
+    controller_disconnect::DisconnectedPlayers,
+    dungeon::{DungeonEntity, DungeonState},
+    input_bindings::AwaitingRebind,
+    localization::{LanguageSettings, localized_text},
+    main_menu::build_settings_menu,
+    map_generation::DungeonGenerationParams,
+    menu::{
+        MenuStackCommands, deselect_nested_menu,
+        menu_navigation::{
+            ActiveMenu, GameMenuController, GameMenuGrid, GameMenuLatch,
+            handle_menu_cursor_navigation, highlight_menu_option,
+        },
+        show_active_game_menu_only,
+        ui_consts::{SELECTABLE_BUTTON_BACKGROUND, UI_MENU_BACKGROUND, UI_TEXT_COLOR},
+    },
+    pixel_art::PixelSnapSettings,
+    player::{Player, PlayerInputAction, RegisteredBattlePlayers},
+    rumble::RumbleSettings,
+    split_screen::SplitScreenSettings,
+    tr,
+};
+
+#[derive(SubStates, Clone, PartialEq, Eq, Hash, Debug, Default, Reflect)]
+#[source(GameState = GameState::Dungeon)]
+pub enum BattlePauseState {
+    #[default]
+    Running,
+    Paused,
+    /// Frozen like a pause, but without the pause menu, while a player's controller is missing.
+    /// See [`crate::controller_disconnect`].
+    WaitingForController,
+}
+
+#[derive(Component)]
+pub struct PauseMenuMarker;
+
+#[derive(Component)]
+enum PauseMenuAction {
+    Resume,
+    OpenSettings,
+    Concede,
+    QuitToMainMenu,
+}
+
+/// The menus that were active when the game was paused, so they can be handed back.
+#[derive(Resource, Default)]
+struct SuspendedMenus(Vec<Entity>);
+
+pub fn pause_menu_plugin(app: &mut App) {
+    app.add_sub_state::<BattlePauseState>()
+        .init_resource::<SuspendedMenus>()
+        .add_systems(
+            Update,
+            toggle_pause.run_if(in_state(DungeonState::InBattle)),
+        )
+        .add_systems(OnEnter(BattlePauseState::Paused), pause_battle)
+        .add_systems(OnExit(BattlePauseState::Paused), resume_battle)
+        .add_systems(
+            Update,
+            (
+                handle_menu_cursor_navigation,
+                highlight_menu_option,
+                deselect_nested_menu,
+                show_active_game_menu_only::<
+                    (With<PauseMenuMarker>, Without<ActiveMenu>),
+                    (With<PauseMenuMarker>, With<ActiveMenu>),
+                >,
+                leave_battle_on_confirm,
+            )
+                .run_if(in_state(BattlePauseState::Paused)),
+        )
+        .add_systems(
+            OnTransition {
+                exited: GameState::Dungeon,
+                entered: GameState::MainMenu,
+            },
+            cleanup_after_quitting,
+        )
+        .add_observer(pause_menu_action);
+}
+
+fn toggle_pause(
+    input_query: Query<&ActionState<PlayerInputAction>, With<Player>>,
+    pause_state: Res<State<BattlePauseState>>,
+    mut next_pause_state: ResMut<NextState<BattlePauseState>>,
+    rebinding: Query<(), With<AwaitingRebind>>,
+    dialogs: Query<(), With<ConfirmDialog>>,
+    disconnected: Res<DisconnectedPlayers>,
+) {
+    // Escape belongs to the controls menu while it's waiting on a new binding, and a
+    // question should get answered before the pause menu goes away. Same goes for a missing
+    // controller.
+    if !rebinding.is_empty() || !dialogs.is_empty() || !disconnected.0.is_empty() {
+        return;
+    }
+
+    // The PrePlayer shares the keyboard with Player 1, so only toggle once per frame
+    if !input_query
+        .iter()
+        .any(|t| t.just_pressed(&PlayerInputAction::Pause))
+    {
+        return;
+    }
+
+    next_pause_state.set(match pause_state.get() {
+        BattlePauseState::Running => BattlePauseState::Paused,
+        BattlePauseState::Paused => BattlePauseState::Running,
+        // Nobody's missing a controller by now, so the overlay's on its way out
+        BattlePauseState::WaitingForController => return,
+    });
+}
+
+fn pause_button(font: &TextFont, action: PauseMenuAction, key: &'static str) -> impl Bundle {
+    (
+        Button,
+        Node {
+            width: px(300),
+            height: px(65),
+            margin: UiRect::all(px(12)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            border_radius: BorderRadius::all(percent(20)),
+            ..default()
+        },
+        BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+        action,
+        children![(localized_text(key), font.clone(), TextColor(UI_TEXT_COLOR))],
+    )
+}
+
+fn pause_battle(
+    mut commands: Commands,
+    fonts: Res<FontResource>,
+    registered_players: Res<RegisteredBattlePlayers>,
+    active_menus: Query<Entity, With<ActiveMenu>>,
+    mut suspended_menus: ResMut<SuspendedMenus>,
+    mut time: ResMut<Time<Virtual>>,
+    sounds: SoundManagerParam,
+    dungeon_params: Res<DungeonGenerationParams>,
+) {
+    info!("Pausing the battle");
+    time.pause();
+
+    // Nobody else gets to move their menus around while we're paused
+    suspended_menus.0 = active_menus.iter().collect();
+    for menu in &suspended_menus.0 {
+        commands.entity(*menu).remove::<ActiveMenu>();
+    }
+
+    let font = TextFont {
+        font_size: 33.0,
+        font: fonts.pixelify_sans_regular.clone(),
+        ..default()
+    };
+
+    let buttons = [
+        commands
+            .spawn(pause_button(&font, PauseMenuAction::Resume, "pause.resume"))
+            .id(),
+        commands
+            .spawn(pause_button(
+                &font,
+                PauseMenuAction::OpenSettings,
+                "pause.settings",
+            ))
+            .id(),
+        commands
+            .spawn(pause_button(
+                &font,
+                PauseMenuAction::Concede,
+                "pause.concede",
+            ))
+            .id(),
+        commands
+            .spawn(pause_button(
+                &font,
+                PauseMenuAction::QuitToMainMenu,
+                "pause.quit_to_main_menu",
+            ))
+            .id(),
+    ];
+
+    let mut pause_grid = GameMenuGrid::new_vertical();
+    pause_grid.push_buttons_to_stack(&buttons);
+
+    let pause_column = commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(px(20)),
+                border_radius: BorderRadius::all(percent(10)),
+                ..default()
+            },
+            BackgroundColor(UI_MENU_BACKGROUND),
+            children![
+                (
+                    localized_text("pause.title"),
+                    TextFont {
+                        font_size: 50.0,
+                        font: fonts.pixelify_sans_medium.clone(),
+                        ..default()
+                    },
+                    TextColor(UI_TEXT_COLOR),
+                ),
+                // So whoever's racing you can start on the same dungeon
+                (
+                    Text(tr!("seed_code.label", code = dungeon_params.options.seed)),
+                    TextFont {
+                        font_size: 20.0,
+                        font: fonts.pixelify_sans_regular.clone(),
+                        ..default()
+                    },
+                    TextColor(UI_TEXT_COLOR),
+                ),
+            ],
+            pause_grid,
+            GameMenuController {
+                players: registered_players.save_files.keys().cloned().collect(),
+            },
+            GameMenuLatch::default(),
+            PauseMenuMarker,
+            ActiveMenu {},
+        ))
+        .add_children(&buttons)
+        .id();
+
+    commands
+        .spawn((
+            Name::new("PauseScreen"),
+            Node {
+                position_type: PositionType::Absolute,
+                width: percent(100),
+                height: percent(100),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            BackgroundColor(Color::BLACK.with_alpha(0.5)),
+            GlobalZIndex(100),
+            DespawnOnExit(BattlePauseState::Paused),
+        ))
+        .add_child(pause_column);
+
+    sounds.play_ui_sound(&mut commands, UiSound::OpenMenu);
+}
+
+fn resume_battle(
+    mut commands: Commands,
+    mut suspended_menus: ResMut<SuspendedMenus>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    info!("Resuming the battle");
+    time.unpause();
+
+    for menu in suspended_menus.0.drain(..) {
+        // The battle might have ended out from under us
+        commands.entity(menu).try_insert(ActiveMenu {});
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn pause_menu_action(
+    mut click: On<Pointer<Click>>,
+    mut commands: Commands,
+    menu_button: Query<&PauseMenuAction, With<Button>>,
+    parent_query: Query<&ChildOf>,
+    fonts: Res<FontResource>,
+    sounds: SoundManagerParam,
+    registered_players: Res<RegisteredBattlePlayers>,
+    sound_settings: Res<SoundSettings>,
+    phase_timer_settings: Res<PhaseTimerSettings>,
+    language_settings: Res<LanguageSettings>,
+    accessibility_settings: Res<AccessibilitySettings>,
+    rumble_settings: Res<RumbleSettings>,
+    split_screen_settings: Res<SplitScreenSettings>,
+    pixel_snap_settings: Res<PixelSnapSettings>,
+    disconnected: Res<DisconnectedPlayers>,
+    mut next_pause_state: ResMut<NextState<BattlePauseState>>,
+) {
+    let button_entity = click.entity;
+    let Ok(action) = menu_button.get(button_entity) else {
+        return;
+    };
+
+    sounds.play_ui_sound(&mut commands, UiSound::Select);
+    click.propagate(false);
+    match action {
+        PauseMenuAction::Resume => {
+            // The disconnect overlay resumes once everyone has a controller again
+            if disconnected.0.is_empty() {
+                next_pause_state.set(BattlePauseState::Running);
+            }
+        }
+        PauseMenuAction::OpenSettings => {
+            let Ok(pause_column) = parent_query.get(button_entity).map(|t| t.parent()) else {
+                error!("No parent for the Settings button?");
+                return;
+            };
+            let Ok(pause_screen) = parent_query.get(pause_column).map(|t| t.parent()) else {
+                error!("No parent for the pause menu?");
+                return;
+            };
+
+            let settings = build_settings_menu(
+                &mut commands,
+                &fonts,
+                &sound_settings,
+                &phase_timer_settings,
+                &language_settings,
+                &accessibility_settings,
+                &rumble_settings,
+                &split_screen_settings,
+                &pixel_snap_settings,
+            );
+            commands.entity(settings).insert((
+                GameMenuController {
+                    players: registered_players
+                        .save_files
+                        .keys()
+                        .cloned()
+                        .collect::<HashSet<_>>(),
+                },
+                PauseMenuMarker,
+            ));
+            commands.push_menu(pause_column, settings);
+            commands.entity(pause_screen).add_child(settings);
+        }
+        PauseMenuAction::Concede | PauseMenuAction::QuitToMainMenu => {
+            let Ok(pause_column) = parent_query.get(button_entity).map(|t| t.parent()) else {
+                error!("No parent for the pause menu button?");
+                return;
+            };
+
+            let confirm_action = match action {
+                PauseMenuAction::Concede => ConfirmDialogAction::ConcedeBattle,
+                _ => ConfirmDialogAction::QuitToMainMenu,
+            };
+            open_confirm_dialog(
+                &mut commands,
+                &fonts,
+                confirm_action,
+                pause_column,
+                registered_players.save_files.keys().cloned().collect(),
+            );
+        }
+    }
+}
+
+fn leave_battle_on_confirm(
+    mut commands: Commands,
+    mut reader: MessageReader<ConfirmDialogMessage>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for message in reader.read().filter(|t| t.confirmed) {
+        match message.action {
+            ConfirmDialogAction::ConcedeBattle => {
+                info!("Conceding the battle");
+                commands.insert_resource(BattleResultResource(BattleResult {
+                    battle_condition: BattleEndCondition::Defeat,
+                }));
+                game_state.set(GameState::BattleResolution);
+            }
+            ConfirmDialogAction::QuitToMainMenu => {
+                game_state.set(GameState::MainMenu);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Battles normally get cleaned up on the way out of the BattleResolution screen, which
+/// quitting from the pause menu skips.
+fn cleanup_after_quitting(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<BattleEntity>, With<DungeonEntity>, With<TilePos>)>>,
+) {
+    for e in query {
+        commands.entity(e).despawn();
+    }
+
+    commands.remove_resource::<BattleResultResource>();
+}
@@ -0,0 +1,199 @@
+//! Pointing things out on the map.
+//!
+//! Pressing Ping drops a marker on the tile under the cursor that everyone can see. Pinging the
+//! same tile again while the marker is still up cycles through the kinds of marker, so players can
+//! call out a target, a spot to head to, or somewhere to stay away from. Each player only has one
+//! marker up at a time.
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    assets::{
+        CURSOR_PATH, FontResource,
+        sounds::{SoundManagerParam, UiSound},
+    },
+    battle::BattleEntity,
+    combat::{DespawnTimer, despawn_after_timer_completed},
+    dungeon::DungeonState,
+    grid::{self, GridPosition},
+    grid_cursor::Cursor,
+    pause_menu::BattlePauseState,
+    player::{Player, PlayerInputAction, RegisteredBattlePlayers},
+    tr,
+};
+
+/// How long a ping stays on the map
+const PING_SECONDS: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PingKind {
+    #[default]
+    Attack,
+    GoHere,
+    Danger,
+}
+
+impl PingKind {
+    /// The kind a second ping on the same tile turns into
+    pub fn next(&self) -> Self {
+        match self {
+            PingKind::Attack => PingKind::GoHere,
+            PingKind::GoHere => PingKind::Danger,
+            PingKind::Danger => PingKind::Attack,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            PingKind::Attack => tr!("ping.attack"),
+            PingKind::GoHere => tr!("ping.go_here"),
+            PingKind::Danger => tr!("ping.danger"),
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            PingKind::Attack => Color::linear_rgb(1.0, 0.3, 0.3),
+            PingKind::GoHere => Color::linear_rgb(0.4, 1.0, 0.5),
+            PingKind::Danger => Color::linear_rgb(1.0, 0.8, 0.2),
+        }
+    }
+}
+
+/// Puts a ping on `position` for `player`
+#[derive(Message, Debug, Clone, Copy)]
+pub struct PingMessage {
+    pub player: Player,
+    pub position: GridPosition,
+}
+
+/// A tile a player pointed out
+#[derive(Component)]
+pub struct PingMarker {
+    pub player: Player,
+    pub kind: PingKind,
+    pub position: GridPosition,
+}
+
+pub fn ping_plugin(app: &mut App) {
+    app.add_message::<PingMessage>()
+        .add_systems(
+            Update,
+            (send_pings, place_pings)
+                .chain()
+                .run_if(in_state(DungeonState::InBattle))
+                .run_if(in_state(BattlePauseState::Running)),
+        )
+        .add_systems(
+            Update,
+            (
+                pulse_ping_markers,
+                despawn_after_timer_completed::<PingMarker>,
+            )
+                .run_if(in_state(DungeonState::InBattle)),
+        );
+}
+
+/// Pings the tile under a player's cursor when they press Ping
+pub fn send_pings(
+    input_query: Query<(&Player, &ActionState<PlayerInputAction>)>,
+    cursors: Query<(&Player, &GridPosition), With<Cursor>>,
+    mut writer: MessageWriter<PingMessage>,
+) {
+    for (player, action_state) in input_query {
+        if !action_state.just_pressed(&PlayerInputAction::Ping) {
+            continue;
+        }
+
+        if let Some((_, position)) = cursors.iter().find(|(p, _)| *p == player) {
+            writer.write(PingMessage {
+                player: *player,
+                position: *position,
+            });
+        }
+    }
+}
+
+/// Handles [`PingMessage`]s, replacing whatever ping the player already had up
+pub fn place_pings(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    fonts: Res<FontResource>,
+    registered_players: Res<RegisteredBattlePlayers>,
+    mut reader: MessageReader<PingMessage>,
+    pings: Query<(Entity, &PingMarker)>,
+    sounds: SoundManagerParam,
+) {
+    for message in reader.read() {
+        let mut kind = PingKind::default();
+        for (ping, marker) in pings.iter().filter(|(_, t)| t.player == message.player) {
+            if marker.position == message.position {
+                kind = marker.kind.next();
+            }
+            commands.entity(ping).despawn();
+        }
+
+        let mut transform = grid::init_grid_to_world_transform(&message.position);
+        // In front of the units, so it isn't hidden behind whoever is standing there
+        transform.translation.z += 50.;
+
+        commands.spawn((
+            Name::new(format!("PingMarker {:?}", message.player)),
+            Sprite {
+                image: asset_server.load(CURSOR_PATH),
+                color: registered_players.player_color(&message.player),
+                ..Default::default()
+            },
+            transform,
+            PingMarker {
+                player: message.player,
+                kind,
+                position: message.position,
+            },
+            DespawnTimer {
+                timer: Timer::from_seconds(PING_SECONDS, TimerMode::Once),
+            },
+            BattleEntity {},
+            children![(
+                Text2d(kind.label()),
+                TextFont {
+                    font: fonts.pixelify_sans_medium.clone(),
+                    font_size: 10.,
+                    font_smoothing: bevy::text::FontSmoothing::None,
+                    ..Default::default()
+                },
+                TextColor(kind.color()),
+                TextBackgroundColor(Color::BLACK.with_alpha(0.5)),
+                Transform::from_translation(Vec3::new(0., 14., 1.)),
+            )],
+        ));
+        sounds.play_ui_sound(&mut commands, UiSound::Select);
+    }
+}
+
+/// Pings pulse so they catch the eye, and fade out towards the end
+pub fn pulse_ping_markers(
+    mut pings: Query<(&DespawnTimer, &mut Transform, &mut Sprite), With<PingMarker>>,
+) {
+    for (despawn_timer, mut transform, mut sprite) in pings.iter_mut() {
+        let elapsed = despawn_timer.timer.elapsed_secs();
+        let pulse = 1.0 + 0.25 * (elapsed * std::f32::consts::TAU * 2.0).sin().abs();
+        transform.scale = Vec3::splat(pulse);
+
+        let remaining = 1.0 - despawn_timer.timer.fraction();
+        sprite.color.set_alpha((remaining * 3.0).min(1.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeated_pings_cycle_back_around() {
+        let first = PingKind::default();
+        assert_eq!(first.next().next().next(), first);
+        assert_ne!(first.next(), first);
+    }
+}
@@ -0,0 +1,199 @@
+//! Keeping pixel art crisp.
+//!
+//! Smoothing a spritesheet when it's scaled up blurs it, and bleeds the edges of neighbouring
+//! frames into each other. Images already default to nearest neighbour sampling (see
+//! `ImagePlugin::default_nearest` in main), but anything loaded with its own sampler settings gets
+//! to skip that. So every image that loads from one of the [`PixelArtFolders`] gets switched over to
+//! nearest neighbour too, and a new spritesheet dropped in one of them doesn't need any fixing up
+//! of its own.
+//!
+//! Nearest neighbour alone still shimmers as the camera moves, whenever a texel doesn't cover a
+//! whole number of screen pixels. With [`PixelSnapSettings`] on, every camera gets snapped to the
+//! pixel grid just before drawing: the zoom to a whole number of screen pixels per texel, and the
+//! position to a whole screen pixel. They're put back afterwards, so panning and zooming still
+//! ease along smoothly underneath.
+
+use bevy::{
+    asset::AssetPath, camera::CameraUpdateSystems, image::ImageSampler, prelude::*,
+    window::PrimaryWindow,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::tr;
+
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PixelSnapSettings {
+    pub enabled: bool,
+}
+
+impl PixelSnapSettings {
+    pub fn text(enabled: bool) -> String {
+        if enabled {
+            tr!("settings.pixel_snap.on")
+        } else {
+            tr!("settings.pixel_snap.off")
+        }
+    }
+}
+
+/// Where a snapped camera really is, to be put back once it's been drawn
+#[derive(Component, Debug)]
+struct Unsnapped {
+    translation: Vec3,
+    scale: f32,
+}
+
+/// The asset folders (relative to `assets/`) that are all pixel art
+#[derive(Resource, Debug, Clone)]
+pub struct PixelArtFolders(pub Vec<String>);
+
+impl Default for PixelArtFolders {
+    fn default() -> Self {
+        Self(
+            ["unit_assets", "map_assets", "utility_assets", "misc_assets"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        )
+    }
+}
+
+impl PixelArtFolders {
+    pub fn contains(&self, path: &AssetPath) -> bool {
+        self.0.iter().any(|t| path.path().starts_with(t))
+    }
+}
+
+pub fn pixel_art_plugin(app: &mut App) {
+    app.init_resource::<PixelArtFolders>()
+        .add_systems(Update, use_nearest_sampling)
+        .add_systems(First, unsnap_cameras)
+        .add_systems(
+            PostUpdate,
+            snap_cameras
+                .before(TransformSystems::Propagate)
+                .before(CameraUpdateSystems),
+        );
+}
+
+/// The closest zoom to `scale` that draws each texel as a whole number of screen pixels
+pub fn snapped_zoom(scale: f32, scale_factor: f32) -> f32 {
+    let pixels_per_texel = (scale_factor / scale).round().max(1.);
+    scale_factor / pixels_per_texel
+}
+
+/// The closest camera position to `translation` that lines texels up with screen pixels, at a
+/// snapped `scale` in a view `size` physical pixels across
+pub fn snapped_translation(translation: Vec2, scale: f32, scale_factor: f32, size: UVec2) -> Vec2 {
+    let pixel = scale / scale_factor;
+    // An odd sized view has its middle halfway across a pixel
+    let offset = Vec2::select(
+        (size % 2).cmpeq(UVec2::ONE),
+        Vec2::splat(pixel / 2.),
+        Vec2::ZERO,
+    );
+    ((translation - offset) / pixel).round() * pixel + offset
+}
+
+fn snap_cameras(
+    mut commands: Commands,
+    settings: Res<PixelSnapSettings>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    mut cameras: Query<(Entity, &Camera, &mut Transform, &mut Projection), With<Camera2d>>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    let scale_factor = window.scale_factor();
+    for (entity, camera, mut transform, mut projection) in cameras.iter_mut() {
+        let Projection::Orthographic(ortho) = &mut *projection else {
+            continue;
+        };
+        commands.entity(entity).insert(Unsnapped {
+            translation: transform.translation,
+            scale: ortho.scale,
+        });
+
+        ortho.scale = snapped_zoom(ortho.scale, scale_factor);
+        let size = camera
+            .physical_viewport_size()
+            .unwrap_or(window.physical_size());
+        let snapped = snapped_translation(
+            transform.translation.truncate(),
+            ortho.scale,
+            scale_factor,
+            size,
+        );
+        transform.translation = snapped.extend(transform.translation.z);
+    }
+}
+
+fn unsnap_cameras(
+    mut commands: Commands,
+    mut cameras: Query<(Entity, &Unsnapped, &mut Transform, &mut Projection)>,
+) {
+    for (entity, unsnapped, mut transform, mut projection) in cameras.iter_mut() {
+        transform.translation = unsnapped.translation;
+        if let Projection::Orthographic(ortho) = &mut *projection {
+            ortho.scale = unsnapped.scale;
+        }
+        commands.entity(entity).remove::<Unsnapped>();
+    }
+}
+
+fn use_nearest_sampling(
+    mut events: MessageReader<AssetEvent<Image>>,
+    folders: Res<PixelArtFolders>,
+    asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for event in events.read() {
+        let AssetEvent::LoadedWithDependencies { id } = event else {
+            continue;
+        };
+        if !asset_server
+            .get_path(*id)
+            .is_some_and(|t| folders.contains(&t))
+        {
+            continue;
+        }
+        // Default already means nearest, so there's no need to touch (and re-upload) those
+        if let Some(image) = images.get_mut(*id)
+            && !matches!(image.sampler, ImageSampler::Default)
+        {
+            image.sampler = ImageSampler::nearest();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_pixel_art_folders_count() {
+        let folders = PixelArtFolders::default();
+        assert!(folders.contains(&AssetPath::from("unit_assets/tinytactics/fighter.png")));
+        assert!(folders.contains(&AssetPath::from("map_assets/tiles.png")));
+        assert!(!folders.contains(&AssetPath::from("font_assets/pixelify.ttf")));
+        // Only whole folder names
+        assert!(!folders.contains(&AssetPath::from("unit_assets_old/fighter.png")));
+    }
+
+    #[test]
+    fn test_snapping_lines_texels_up_with_pixels() {
+        assert_eq!(snapped_zoom(0.4, 1.), 1. / 3.);
+        assert_eq!(snapped_zoom(0.45, 1.), 0.5);
+        assert_eq!(snapped_zoom(3., 1.), 1.);
+        assert_eq!(snapped_zoom(0.4, 2.), 0.4);
+
+        let scale = snapped_zoom(0.4, 1.);
+        for size in [UVec2::new(1280, 800), UVec2::new(1281, 801)] {
+            let snapped = snapped_translation(Vec2::new(10.3, -75.9), scale, 1., size);
+            // Where a texel edge at the world origin lands on screen
+            let edge = size.as_vec2() / 2. - snapped / scale;
+            assert!((edge - edge.round()).length() < 0.001, "{:?}", edge);
+        }
+    }
+}
@@ -7,12 +7,27 @@ use bevy::prelude::*;
 use leafwing_input_manager::prelude::*;
 
 use crate::{
-    combat::skills::SkillId,
     grid::GridPosition,
-    save_game::UnitSaveV1,
-    unit::{AttackOption, ValidMove},
+    save_game::{SaveFileColor, SaveFileKey, UnitSaveV2},
+    unit::{AttackOption, TargetedAction, ValidMove, jobs::UnitJob},
 };
 
+/// How many players can be in a game at once
+pub const MAX_PLAYERS: u32 = 4;
+
+/// The players' side always has at least this many units. If fewer players join, the ones that
+/// did get filler units to make up the difference.
+pub const MIN_PARTY_SIZE: usize = 2;
+
+/// Holding this turns the cursor keys into camera panning
+pub const PAN_CAMERA_MODIFIER: KeyCode = KeyCode::ControlLeft;
+
+/// Stand-ins for missing players, handed out in order
+const FILLER_UNITS: [(&str, UnitJob); 2] = [
+    ("Sellsword", UnitJob::Mercenary),
+    ("Hedge Knight", UnitJob::Knight),
+];
+
 #[derive(Component, Reflect, PartialEq, Eq, Hash, Debug, Copy, Clone)]
 pub enum Player {
     PlayerId(u32),
@@ -27,6 +42,16 @@ impl Player {
             Player::PrePlayer => 0,
         }
     }
+
+    /// The render layer for anything only this player should see. Layer 0 is for everyone.
+    pub fn render_layer(&self) -> usize {
+        self.id() as usize
+    }
+
+    /// Every player that can join a game, in the order they get handed out
+    pub fn joinable() -> impl Iterator<Item = Player> {
+        (1..=MAX_PLAYERS).map(Player::PlayerId)
+    }
 }
 
 #[derive(Bundle)]
@@ -58,7 +83,17 @@ impl Player {
                 (PlayerInputAction::Deselect, KeyCode::ShiftLeft),
                 (PlayerInputAction::ZoomIn, KeyCode::KeyQ),
                 (PlayerInputAction::ZoomOut, KeyCode::KeyE),
-            ]),
+                (PlayerInputAction::ToggleDangerZone, KeyCode::KeyF),
+                (PlayerInputAction::Inspect, KeyCode::KeyI),
+                (PlayerInputAction::Pause, KeyCode::Escape),
+                (PlayerInputAction::ToggleBattleLog, KeyCode::KeyL),
+                (PlayerInputAction::Ping, KeyCode::KeyG),
+                (PlayerInputAction::RecenterCamera, KeyCode::KeyC),
+            ])
+            .with_dual_axis(
+                PlayerInputAction::PanCamera,
+                DualAxislikeChord::new(PAN_CAMERA_MODIFIER, VirtualDPad::wasd()),
+            ),
 
             Player::PrePlayer => {
                 let mut base_map = InputMap::new([
@@ -70,6 +105,12 @@ impl Player {
                     (PlayerInputAction::Deselect, KeyCode::ShiftLeft),
                     (PlayerInputAction::ZoomIn, KeyCode::KeyQ),
                     (PlayerInputAction::ZoomOut, KeyCode::KeyE),
+                    (PlayerInputAction::ToggleDangerZone, KeyCode::KeyF),
+                    (PlayerInputAction::Inspect, KeyCode::KeyI),
+                    (PlayerInputAction::Pause, KeyCode::Escape),
+                    (PlayerInputAction::ToggleBattleLog, KeyCode::KeyL),
+                    (PlayerInputAction::Ping, KeyCode::KeyG),
+                    (PlayerInputAction::RecenterCamera, KeyCode::KeyC),
                 ]);
 
                 base_map.insert_multiple([
@@ -79,15 +120,31 @@ impl Player {
                     (PlayerInputAction::MoveCursorRight, GamepadButton::DPadRight),
                     (PlayerInputAction::Select, GamepadButton::South),
                     (PlayerInputAction::Deselect, GamepadButton::East),
+                    (PlayerInputAction::ToggleDangerZone, GamepadButton::North),
+                    (PlayerInputAction::Inspect, GamepadButton::West),
+                    (PlayerInputAction::Pause, GamepadButton::Start),
+                    (PlayerInputAction::ToggleBattleLog, GamepadButton::Select),
+                    (PlayerInputAction::Ping, GamepadButton::RightTrigger2),
+                    (PlayerInputAction::RecenterCamera, GamepadButton::RightThumb),
                 ]);
 
                 base_map.insert_dual_axis(PlayerInputAction::MoveCursor, GamepadStick::LEFT);
+                base_map.insert_dual_axis(
+                    PlayerInputAction::PanCamera,
+                    DualAxislikeChord::new(PAN_CAMERA_MODIFIER, VirtualDPad::wasd()),
+                );
+                base_map.insert_dual_axis(PlayerInputAction::PanCamera, GamepadStick::RIGHT);
                 base_map
             }
         }
     }
 
     pub fn get_input_map_with_gamepad(entity: Entity) -> InputMap<PlayerInputAction> {
+        Self::get_default_gamepad_input_map().with_gamepad(entity)
+    }
+
+    /// The gamepad bindings, not yet tied to any particular gamepad
+    pub fn get_default_gamepad_input_map() -> InputMap<PlayerInputAction> {
         InputMap::new([
             (PlayerInputAction::MoveCursorUp, GamepadButton::DPadUp),
             (PlayerInputAction::MoveCursorDown, GamepadButton::DPadDown),
@@ -95,13 +152,30 @@ impl Player {
             (PlayerInputAction::MoveCursorRight, GamepadButton::DPadRight),
             (PlayerInputAction::Select, GamepadButton::South),
             (PlayerInputAction::Deselect, GamepadButton::East),
+            (PlayerInputAction::ToggleDangerZone, GamepadButton::North),
+            (PlayerInputAction::Inspect, GamepadButton::West),
+            (PlayerInputAction::Pause, GamepadButton::Start),
+            (PlayerInputAction::ToggleBattleLog, GamepadButton::Select),
+            (PlayerInputAction::Ping, GamepadButton::RightTrigger2),
+            (PlayerInputAction::RecenterCamera, GamepadButton::RightThumb),
         ])
-        .with_gamepad(entity)
         .with_dual_axis(PlayerInputAction::MoveCursor, GamepadStick::LEFT)
+        .with_dual_axis(PlayerInputAction::PanCamera, GamepadStick::RIGHT)
     }
 }
 
-#[derive(Actionlike, Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[derive(
+    Actionlike,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Reflect,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub enum PlayerInputAction {
     MoveCursorUp,
     MoveCursorDown,
@@ -113,6 +187,21 @@ pub enum PlayerInputAction {
     Deselect,
     ZoomIn,
     ZoomOut,
+    /// Show or hide everywhere the enemy could reach next phase
+    ToggleDangerZone,
+    /// Open up the details for the unit under the cursor
+    Inspect,
+    /// Pause (or unpause) the battle
+    Pause,
+    /// Show or hide the battle log
+    ToggleBattleLog,
+    /// Mark the tile under the cursor for everyone to see
+    Ping,
+    /// Look around the map without moving the cursor
+    #[actionlike(DualAxis)]
+    PanCamera,
+    /// Bring the camera back to the player's cursor
+    RecenterCamera,
 }
 
 // TODO:  Is this really how I want to track this?
@@ -128,16 +217,183 @@ pub enum PlayerCursorState {
     Idle,
     /// Moving Entity from source position
     MovingUnit(Entity, GridPosition, HashMap<GridPosition, ValidMove>),
-    LookingForTargetWithAttack(Entity, HashMap<GridPosition, AttackOption>, SkillId),
+    LookingForTargetWithAttack(Entity, HashMap<GridPosition, AttackOption>, TargetedAction),
 }
 
 #[derive(Debug, Default)]
 pub struct PlayerState {
     pub cursor_state: PlayerCursorState,
+    /// Whether this player wants to see the enemy danger zone
+    pub show_danger_zone: bool,
 }
 
 /// I'm not that attached to this yet.
 #[derive(Resource, Default)]
 pub struct RegisteredBattlePlayers {
-    pub save_files: HashMap<Player, UnitSaveV1>,
+    pub save_files: HashMap<Player, UnitSaveV2>,
+    /// Extra units a player controls on top of their own character.
+    /// See [`RegisteredBattlePlayers::assign_filler_units`].
+    pub filler_units: HashMap<Player, Vec<UnitSaveV2>>,
+    /// Characters whose player dropped out partway through a run. They stay in the party, and get
+    /// handed out to the remaining players along with the filler units.
+    pub orphaned_units: Vec<UnitSaveV2>,
+}
+
+impl RegisteredBattlePlayers {
+    /// Every unit on the players' side and who controls it, each player's own character first.
+    ///
+    /// Always in player order, so everything built from it (like who starts where) doesn't
+    /// shuffle around between rooms.
+    pub fn units(&self) -> impl Iterator<Item = (Player, &UnitSaveV2)> {
+        let mut characters = self.save_files.iter().collect::<Vec<_>>();
+        characters.sort_by_key(|(p, _)| p.id());
+        let mut filler_units = self.filler_units.iter().collect::<Vec<_>>();
+        filler_units.sort_by_key(|(p, _)| p.id());
+
+        characters.into_iter().map(|(p, t)| (*p, t)).chain(
+            filler_units
+                .into_iter()
+                .flat_map(|(p, units)| units.iter().map(move |t| (*p, t))),
+        )
+    }
+
+    /// How many real characters are in the party. Filler units don't count, so a solo player
+    /// doesn't get the rooms a bigger party would.
+    pub fn party_size(&self) -> usize {
+        self.save_files.len() + self.orphaned_units.len()
+    }
+
+    /// Tops the party up to [`MIN_PARTY_SIZE`], handing filler units out to the players that
+    /// joined in turn. A solo player ends up with two units, while four players get one each.
+    ///
+    /// Orphaned units get handed out first, and count towards the party size.
+    pub fn assign_filler_units(&mut self) {
+        self.filler_units.clear();
+
+        let mut players = self.save_files.keys().copied().collect::<Vec<_>>();
+        players.sort_by_key(|t| t.id());
+        let mut owners = players.iter().cycle();
+
+        for unit in &self.orphaned_units {
+            let Some(player) = owners.next() else {
+                return;
+            };
+            self.filler_units
+                .entry(*player)
+                .or_default()
+                .push(unit.clone());
+        }
+
+        let missing = MIN_PARTY_SIZE.saturating_sub(players.len() + self.orphaned_units.len());
+        for (i, player) in owners.take(missing).enumerate() {
+            let color = self.save_files[player].save_file_key.color.clone();
+            self.filler_units
+                .entry(*player)
+                .or_default()
+                .push(filler_unit(i, color));
+        }
+    }
+
+    /// Makes the same change to every copy of the unit with this uid. An orphaned unit is also
+    /// somebody's filler unit, and both need to stay in step.
+    pub fn update_unit(&mut self, uid: u32, mut update: impl FnMut(&mut UnitSaveV2)) {
+        self.save_files
+            .values_mut()
+            .chain(self.filler_units.values_mut().flatten())
+            .chain(self.orphaned_units.iter_mut())
+            .filter(|t| t.save_file_key.uid == uid)
+            .for_each(&mut update);
+    }
+
+    /// Who controls the unit with this save, if anyone
+    pub fn owner(&self, save_file_key: &SaveFileKey) -> Option<Player> {
+        self.units()
+            // Characters can be renamed, so go by uid
+            .find(|(_, t)| t.save_file_key.uid == save_file_key.uid)
+            .map(|(player, _)| player)
+    }
+
+    /// The color the player picked in the join menu, so co-op players can tell their cursor,
+    /// menus and overlays apart.
+    pub fn player_color(&self, player: &Player) -> Color {
+        self.save_files
+            .get(player)
+            .map(|t| t.save_file_key.color.battle_color())
+            .unwrap_or(Color::WHITE)
+    }
+}
+
+/// Filler units don't have a save file of their own, so they get uids from the top of the range
+/// to stay clear of the real ones.
+fn filler_unit(index: usize, color: SaveFileColor) -> UnitSaveV2 {
+    let (name, job) = FILLER_UNITS[index % FILLER_UNITS.len()].clone();
+    UnitSaveV2::new(
+        SaveFileKey {
+            uid: u32::MAX - index as u32,
+            name: name.to_string(),
+            color,
+        },
+        job,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registered(players: &[u32]) -> RegisteredBattlePlayers {
+        let mut registered = RegisteredBattlePlayers::default();
+        for id in players {
+            registered.save_files.insert(
+                Player::PlayerId(*id),
+                filler_unit(*id as usize, SaveFileColor::Blue),
+            );
+        }
+        registered.assign_filler_units();
+        registered
+    }
+
+    #[test]
+    fn test_solo_player_gets_a_filler_unit() {
+        let registered = registered(&[1]);
+        assert_eq!(registered.units().count(), MIN_PARTY_SIZE);
+        assert_eq!(registered.filler_units[&Player::PlayerId(1)].len(), 1);
+    }
+
+    #[test]
+    fn test_orphaned_units_go_to_remaining_players() {
+        let mut registered = registered(&[1, 2]);
+        let orphan = registered.save_files.remove(&Player::PlayerId(2)).unwrap();
+        registered.orphaned_units.push(orphan.clone());
+        registered.assign_filler_units();
+
+        assert_eq!(registered.units().count(), MIN_PARTY_SIZE);
+        assert_eq!(
+            registered.owner(&orphan.save_file_key),
+            Some(Player::PlayerId(1))
+        );
+    }
+
+    #[test]
+    fn test_units_are_in_player_order() {
+        let registered = registered(&[3, 1, 4, 2]);
+        let ids = registered
+            .units()
+            .map(|(player, _)| player.id())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_filler_units_dont_grow_the_party() {
+        assert_eq!(registered(&[1]).party_size(), 1);
+        assert_eq!(registered(&[1, 2]).party_size(), 2);
+    }
+
+    #[test]
+    fn test_full_party_gets_no_filler_units() {
+        let registered = registered(&[1, 2, 3, 4]);
+        assert_eq!(registered.units().count(), 4);
+        assert!(registered.filler_units.is_empty());
+    }
 }
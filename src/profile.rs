@@ -0,0 +1,529 @@
+//! Local profiles, so people sharing a device don't share characters.
+//!
+//! Everything saved per player goes through [`ProfileStore`], which namespaces the [`PkvStore`]
+//! keys under the [`ActiveProfile`]. Deleting a profile, or erasing all data from the join screen,
+//! only touches that profile's keys. Settings are shared by everyone on the device, except for
+//! the controls, which each profile remaps for itself.
+//!
+//! Writes keep a backup of what they replace, and reads that can fall back on it go through
+//! [`save_recovery`].
+//!
+//! The profile gets picked on the way into the main menu. Saves from before there were profiles
+//! get moved into a default one the first time the game starts.
+
+use std::collections::HashSet;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_pkv::PkvStore;
+use bevy_simple_text_input::{
+    TextInput, TextInputInactive, TextInputPlaceholder, TextInputSettings, TextInputTextFont,
+    TextInputValue,
+};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    GameState,
+    assets::FontResource,
+    confirm_dialog::{ConfirmDialogAction, ConfirmDialogMessage, open_confirm_dialog},
+    input_bindings::{INPUT_BINDINGS_KEY, InputBindings},
+    localization::localized_text,
+    menu::{
+        menu_navigation::{
+            ActiveMenu, GameMenuController, GameMenuGrid, GameMenuLatch,
+            handle_menu_cursor_navigation, highlight_menu_option,
+        },
+        ui_consts::{SELECTABLE_BUTTON_BACKGROUND, UI_MENU_BACKGROUND, UI_TEXT_COLOR},
+    },
+    player::Player,
+    run_save::{LATEST_RUN_KEY, LEGACY_RUN_SAVE_KEY, RunSave, run_save_key},
+    run_stats::RUN_HISTORY_KEY,
+    save_game::{SaveFiles, UnitSave},
+    save_recovery, tr,
+};
+
+/// Where a profile keeps its [`SaveFiles`]
+pub const SAVE_FILES_KEY: &str = "save-files";
+
+/// bevy_pkv keys persistent resources by type name, which is where [`SaveFiles`] lived before
+/// there were profiles
+const LEGACY_SAVE_FILES_KEY: &str = "tactics_exploration::save_game::SaveFiles";
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Profile {
+    pub id: u32,
+    pub name: String,
+}
+
+impl Profile {
+    /// Namespaces `key` to this profile
+    pub fn key(&self, key: impl AsRef<str>) -> String {
+        format!("profile-{}-{}", self.id, key.as_ref())
+    }
+}
+
+/// Every profile on the device
+#[derive(Resource, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Profiles {
+    pub profiles: Vec<Profile>,
+    pub cursor: u32,
+}
+
+impl Profiles {
+    /// Adds a new profile, with an id nobody else has had
+    pub fn add(&mut self, name: String) -> Profile {
+        self.cursor = self.cursor.overflowing_add(1).0;
+        let profile = Profile {
+            id: self.cursor,
+            name,
+        };
+        self.profiles.push(profile.clone());
+        profile
+    }
+
+    pub fn remove(&mut self, id: u32) -> Option<Profile> {
+        let index = self.profiles.iter().position(|t| t.id == id)?;
+        Some(self.profiles.remove(index))
+    }
+}
+
+/// The profile everything gets saved under
+#[derive(Resource, Debug, Clone)]
+pub struct ActiveProfile(pub Profile);
+
+/// The [`PkvStore`], with every key namespaced to the [`ActiveProfile`]
+#[derive(SystemParam)]
+pub struct ProfileStore<'w> {
+    pkv: ResMut<'w, PkvStore>,
+    profile: Res<'w, ActiveProfile>,
+}
+
+impl ProfileStore<'_> {
+    pub fn get<T: DeserializeOwned>(&self, key: impl AsRef<str>) -> anyhow::Result<T> {
+        self.pkv
+            .get(self.profile.0.key(key))
+            .map_err(|e| anyhow::anyhow!("Failed to get PKV: {:?}", e))
+    }
+
+    /// See [`save_recovery::get_or_recover`]
+    pub fn get_or_recover<T: Serialize + DeserializeOwned>(
+        &mut self,
+        key: impl AsRef<str>,
+    ) -> Option<T> {
+        save_recovery::get_or_recover(&mut self.pkv, &self.profile.0.key(key))
+    }
+
+    /// Keeps whatever was in `key` before as its backup
+    pub fn set<T: Serialize + DeserializeOwned>(
+        &mut self,
+        key: impl AsRef<str>,
+        value: &T,
+    ) -> anyhow::Result<()> {
+        save_recovery::set_with_backup(&mut self.pkv, &self.profile.0.key(key), value)
+    }
+
+    pub fn remove(&mut self, key: impl AsRef<str>) -> anyhow::Result<()> {
+        save_recovery::remove_with_backup(&mut self.pkv, &self.profile.0.key(key))
+    }
+
+    pub fn has_backup<T: DeserializeOwned>(&self, key: impl AsRef<str>) -> bool {
+        save_recovery::has_backup::<T>(&self.pkv, &self.profile.0.key(key))
+    }
+
+    pub fn restore_backup<T: Serialize + DeserializeOwned>(
+        &mut self,
+        key: impl AsRef<str>,
+    ) -> anyhow::Result<T> {
+        save_recovery::restore_backup(&mut self.pkv, &self.profile.0.key(key))
+    }
+
+    pub fn quarantine(&mut self, key: impl AsRef<str>) {
+        save_recovery::quarantine(&mut self.pkv, &self.profile.0.key(key));
+    }
+
+    /// Deletes everything saved under the profile, and nothing else
+    pub fn clear(&mut self) {
+        delete_profile_saves(&mut self.pkv, &self.profile.0);
+    }
+}
+
+fn delete_profile_saves(pkv: &mut PkvStore, profile: &Profile) {
+    let mut keys = vec![
+        SAVE_FILES_KEY.to_string(),
+        LATEST_RUN_KEY.to_string(),
+        LEGACY_RUN_SAVE_KEY.to_string(),
+        RUN_HISTORY_KEY.to_string(),
+        INPUT_BINDINGS_KEY.to_string(),
+    ];
+    if let Ok(save_files) = pkv.get::<SaveFiles>(profile.key(SAVE_FILES_KEY)) {
+        keys.extend(save_files.save_file_keys.iter().map(|t| t.pkv_key()));
+    }
+    if let Ok(run_id) = pkv.get::<String>(profile.key(LATEST_RUN_KEY)) {
+        keys.push(run_save_key(&run_id));
+    }
+
+    for key in keys {
+        // Most profiles won't have all of these, which is fine
+        let key = profile.key(key);
+        let _ = save_recovery::remove_with_backup(pkv, &key);
+        let _ = pkv.remove(save_recovery::quarantine_key(&key));
+    }
+}
+
+fn move_key<T: Serialize + DeserializeOwned>(pkv: &mut PkvStore, from: &str, to: String) {
+    let Ok(value) = pkv.get::<T>(from) else {
+        return;
+    };
+
+    match pkv.set(&to, &value) {
+        Ok(()) => {
+            let _ = pkv.remove(from);
+        }
+        Err(e) => error!("Failed moving {} to {}: {:?}", from, to, e),
+    }
+}
+
+/// Moves saves from before there were profiles into a profile of their own
+fn migrate_legacy_saves(mut pkv: ResMut<PkvStore>, mut profiles: ResMut<Profiles>) {
+    if !profiles.profiles.is_empty() {
+        return;
+    }
+
+    let Ok(save_files) = pkv.get::<SaveFiles>(LEGACY_SAVE_FILES_KEY) else {
+        return;
+    };
+
+    let profile = profiles.add("Default".to_string());
+    info!("Moving old saves into {}", profile.name);
+
+    for key in &save_files.save_file_keys {
+        move_key::<UnitSave>(&mut pkv, &key.pkv_key(), profile.key(key.pkv_key()));
+    }
+    if let Ok(run_id) = pkv.get::<String>(LATEST_RUN_KEY) {
+        let key = run_save_key(&run_id);
+        move_key::<RunSave>(&mut pkv, &key, profile.key(&key));
+    }
+    move_key::<String>(&mut pkv, LATEST_RUN_KEY, profile.key(LATEST_RUN_KEY));
+    move_key::<RunSave>(
+        &mut pkv,
+        LEGACY_RUN_SAVE_KEY,
+        profile.key(LEGACY_RUN_SAVE_KEY),
+    );
+    move_key::<SaveFiles>(&mut pkv, LEGACY_SAVE_FILES_KEY, profile.key(SAVE_FILES_KEY));
+}
+
+/// Keeps the profile's copy of [`SaveFiles`] up to date
+fn persist_save_files(mut store: ProfileStore, save_files: Res<SaveFiles>) {
+    if let Err(e) = store.set(SAVE_FILES_KEY, &*save_files) {
+        error!("Failed saving the list of characters: {:?}", e);
+    }
+}
+
+/// Keeps the profile's copy of [`InputBindings`] up to date
+fn persist_input_bindings(mut store: ProfileStore, input_bindings: Res<InputBindings>) {
+    if let Err(e) = store.set(INPUT_BINDINGS_KEY, &*input_bindings) {
+        error!("Failed saving the controls: {:?}", e);
+    }
+}
+
+#[derive(Component)]
+struct ProfileMenu;
+
+#[derive(Component)]
+enum ProfileMenuAction {
+    Select(u32),
+    Delete(u32),
+    FocusName,
+    Create(Entity),
+}
+
+pub fn profile_plugin(app: &mut App) {
+    app.init_resource::<SaveFiles>()
+        .add_systems(
+            OnEnter(GameState::ProfileSelect),
+            (migrate_legacy_saves, profile_menu_setup).chain(),
+        )
+        .add_systems(
+            Update,
+            (
+                handle_menu_cursor_navigation,
+                highlight_menu_option,
+                delete_profile_on_confirm,
+            )
+                .run_if(in_state(GameState::ProfileSelect)),
+        )
+        .add_systems(
+            Update,
+            (
+                persist_save_files.run_if(resource_changed::<SaveFiles>),
+                persist_input_bindings.run_if(resource_changed::<InputBindings>),
+            )
+                .run_if(resource_exists::<ActiveProfile>),
+        )
+        .add_observer(profile_menu_action);
+}
+
+fn profile_button(font: &TextFont, label: impl Bundle, action: ProfileMenuAction) -> impl Bundle {
+    (
+        Button,
+        Node {
+            height: px(65),
+            margin: UiRect::all(px(10)),
+            padding: UiRect::horizontal(px(20)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            border_radius: BorderRadius::all(percent(20)),
+            ..default()
+        },
+        BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+        action,
+        children![(label, font.clone(), TextColor(UI_TEXT_COLOR))],
+    )
+}
+
+fn profile_menu_setup(mut commands: Commands, fonts: Res<FontResource>, profiles: Res<Profiles>) {
+    let font = TextFont {
+        font_size: 33.0,
+        font: fonts.pixelify_sans_regular.clone(),
+        ..default()
+    };
+
+    let mut rows = Vec::new();
+    let mut buttons = Vec::new();
+    for profile in &profiles.profiles {
+        let select = commands
+            .spawn(profile_button(
+                &font,
+                Text(profile.name.clone()),
+                ProfileMenuAction::Select(profile.id),
+            ))
+            .id();
+        let delete = commands
+            .spawn(profile_button(
+                &font,
+                localized_text("profile.delete"),
+                ProfileMenuAction::Delete(profile.id),
+            ))
+            .id();
+        buttons.extend([select, delete]);
+        rows.push([select, delete]);
+    }
+
+    let name_input = commands
+        .spawn((
+            Button,
+            Node {
+                width: px(300),
+                height: px(65),
+                margin: UiRect::all(px(10)),
+                border_radius: BorderRadius::all(percent(20)),
+                ..default()
+            },
+            BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+            TextInput,
+            TextInputTextFont(font.clone()),
+            TextInputPlaceholder {
+                value: tr!("profile.enter_name"),
+                ..default()
+            },
+            TextInputInactive(true),
+            TextInputSettings {
+                retain_on_submit: true,
+                ..default()
+            },
+            ProfileMenuAction::FocusName,
+        ))
+        .id();
+    let create = commands
+        .spawn(profile_button(
+            &font,
+            localized_text("profile.create"),
+            ProfileMenuAction::Create(name_input),
+        ))
+        .id();
+    buttons.extend([name_input, create]);
+    rows.push([name_input, create]);
+
+    let mut grid = GameMenuGrid::new_with_width(2);
+    grid.push_buttons_in_rows(&buttons);
+
+    let row_entities = rows
+        .into_iter()
+        .map(|row| {
+            commands
+                .spawn(Node {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    ..default()
+                })
+                .add_children(&row)
+                .id()
+        })
+        .collect::<Vec<_>>();
+
+    let column = commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(px(30)),
+                border_radius: BorderRadius::all(percent(10)),
+                ..default()
+            },
+            BackgroundColor(UI_MENU_BACKGROUND),
+            children![(
+                localized_text("profile.title"),
+                TextFont {
+                    font_size: 54.0,
+                    font: fonts.pixelify_sans_medium.clone(),
+                    ..default()
+                },
+                TextColor(UI_TEXT_COLOR),
+                Node {
+                    margin: UiRect::all(px(30)),
+                    ..default()
+                },
+            )],
+            grid,
+            GameMenuController {
+                players: HashSet::from([Player::PrePlayer]),
+            },
+            ActiveMenu {},
+            GameMenuLatch::default(),
+            ProfileMenu,
+        ))
+        .add_children(&row_entities)
+        .id();
+
+    commands
+        .spawn((
+            Name::new("ProfileSelect"),
+            DespawnOnExit(GameState::ProfileSelect),
+            Node {
+                width: percent(100),
+                height: percent(100),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+        ))
+        .add_child(column);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn profile_menu_action(
+    mut click: On<Pointer<Click>>,
+    mut commands: Commands,
+    actions: Query<&ProfileMenuAction, With<Button>>,
+    menu: Query<Entity, With<ProfileMenu>>,
+    mut name_inputs: Query<(&TextInputValue, &mut TextInputInactive)>,
+    mut profiles: ResMut<Profiles>,
+    mut pkv: ResMut<PkvStore>,
+    fonts: Res<FontResource>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    let Ok(action) = actions.get(click.entity) else {
+        return;
+    };
+    click.propagate(false);
+
+    match action {
+        ProfileMenuAction::Select(id) => {
+            let Some(profile) = profiles.profiles.iter().find(|t| t.id == *id).cloned() else {
+                error!("No profile with id {}", id);
+                return;
+            };
+
+            info!("Playing as {}", profile.name);
+            let save_files =
+                save_recovery::get_or_recover::<SaveFiles>(&mut pkv, &profile.key(SAVE_FILES_KEY))
+                    .unwrap_or_default();
+            commands.insert_resource(save_files);
+            if let Some(input_bindings) = save_recovery::get_or_recover::<InputBindings>(
+                &mut pkv,
+                &profile.key(INPUT_BINDINGS_KEY),
+            ) {
+                commands.insert_resource(input_bindings);
+            }
+            commands.insert_resource(ActiveProfile(profile));
+            game_state.set(GameState::MainMenu);
+        }
+        ProfileMenuAction::Delete(id) => {
+            let Ok(menu) = menu.single() else {
+                error!("No profile menu to open the dialog on?");
+                return;
+            };
+            open_confirm_dialog(
+                &mut commands,
+                &fonts,
+                ConfirmDialogAction::DeleteProfile(*id),
+                menu,
+                HashSet::from([Player::PrePlayer]),
+            );
+        }
+        ProfileMenuAction::FocusName => {
+            if let Ok((_, mut inactive)) = name_inputs.get_mut(click.entity) {
+                inactive.0 = !inactive.0;
+            }
+        }
+        ProfileMenuAction::Create(name_input) => {
+            let Ok((name, _)) = name_inputs.get(*name_input) else {
+                error!("Create profile button has no name input?");
+                return;
+            };
+            if name.0.is_empty() {
+                return;
+            }
+
+            let profile = profiles.add(name.0.clone());
+            info!("Created profile {}", profile.name);
+            // Rebuilds the menu with the new profile in it
+            game_state.set(GameState::ProfileSelect);
+        }
+    }
+}
+
+fn delete_profile_on_confirm(
+    mut commands: Commands,
+    mut reader: MessageReader<ConfirmDialogMessage>,
+    mut profiles: ResMut<Profiles>,
+    mut pkv: ResMut<PkvStore>,
+    active_profile: Option<Res<ActiveProfile>>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for message in reader.read().filter(|t| t.confirmed) {
+        let ConfirmDialogAction::DeleteProfile(id) = message.action else {
+            continue;
+        };
+
+        let Some(profile) = profiles.remove(id) else {
+            error!("No profile with id {} to delete", id);
+            continue;
+        };
+
+        info!("Deleting profile {}", profile.name);
+        delete_profile_saves(&mut pkv, &profile);
+        if active_profile.as_ref().is_some_and(|t| t.0.id == id) {
+            commands.remove_resource::<ActiveProfile>();
+            commands.insert_resource(SaveFiles::default());
+        }
+        game_state.set(GameState::ProfileSelect);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_keep_their_keys_apart() {
+        let mut profiles = Profiles::default();
+        let first = profiles.add("Sam".to_string());
+        let second = profiles.add("Sam".to_string());
+
+        assert_ne!(first.key("unit-save-1"), second.key("unit-save-1"));
+        assert_eq!(profiles.remove(first.id), Some(first));
+        assert_eq!(profiles.profiles, vec![second.clone()]);
+        // Ids aren't reused, so a new profile can't pick up a deleted one's leftovers
+        assert_eq!(profiles.add("Alex".to_string()).id, 3);
+    }
+}
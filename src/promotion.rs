@@ -0,0 +1,273 @@
+//! Moving up to an advanced job.
+//!
+//! Once a unit reaches the [`PromotionConfig`]'s level, the [rest room](crate::rest_room) offers
+//! them a choice between whatever their job [promotes to](UnitJob::promotions), like a Knight
+//! becoming a Paladin or a General. The new job keeps everything the unit had and adds a stat
+//! bonus, the new job's skills, its sprite and any gear only it can use. It's just the `job` on
+//! their save changing, so it carries into the next room and into their save file like anything
+//! else does.
+
+use bevy::prelude::*;
+
+use crate::{
+    animation::{FacingDirection, animation_db::AnimationDB},
+    assets::{FontResource, sprite_db::SpriteDB},
+    join_game_menu::get_sprite_resources_for_job,
+    localization::localized_text,
+    menu::ui_consts::UI_TEXT_COLOR,
+    player::RegisteredBattlePlayers,
+    rest_room::{BackToCampfireButton, RestScreen, menu_button, menu_panel},
+    save_game::{SaveFileKey, UnitSaveV2},
+    tr,
+    unit::{Unit, jobs::UnitJob},
+    unit_stats::{StatContainer, StatType, StatValue, UnitBaseStats},
+};
+
+/// The level a unit needs before they can be promoted, unless the [`PromotionConfig`] says
+/// otherwise
+pub const DEFAULT_PROMOTION_LEVEL: u32 = 10;
+
+#[derive(Resource, Debug, Clone)]
+pub struct PromotionConfig {
+    pub level: u32,
+}
+
+impl Default for PromotionConfig {
+    fn default() -> Self {
+        Self {
+            level: DEFAULT_PROMOTION_LEVEL,
+        }
+    }
+}
+
+impl PromotionConfig {
+    /// Every job the unit could be promoted to right now
+    pub fn options(&self, save: &UnitSaveV2) -> &'static [UnitJob] {
+        if save.level < self.level {
+            return &[];
+        }
+        save.job.promotions()
+    }
+}
+
+/// Adds a job's promotion bonus to the stats. Extra max health comes with the health to fill it.
+fn apply_promotion_bonus(stats: &mut StatContainer, job: &UnitJob) {
+    for (stat, bonus) in job.promotion_bonus() {
+        let value = stats.stat(stat).0 + bonus;
+        stats.with_stat(stat, StatValue(value));
+        if stat == StatType::MaxHealth {
+            let health = stats.stat(StatType::Health).0 + bonus;
+            stats.with_stat(StatType::Health, StatValue(health));
+        }
+    }
+}
+
+/// Moves the unit over to `job`, as long as it's a promotion of the one they've got
+pub fn promote(save: &mut UnitSaveV2, job: UnitJob) -> anyhow::Result<()> {
+    if !save.job.promotions().contains(&job) {
+        anyhow::bail!("{:?} can't be promoted to {:?}", save.job, job);
+    }
+
+    apply_promotion_bonus(&mut save.base_stats, &job);
+    for skill in job.base_unit_skills().learned_skills {
+        if !save.learned_skills.contains(&skill) {
+            save.learned_skills.push(skill);
+        }
+    }
+    save.learned_skills.sort_by_key(|t| t.0);
+    save.job = job;
+    Ok(())
+}
+
+/// Promotes the unit with this uid to `job`
+#[derive(Component)]
+struct PromotionButton {
+    uid: u32,
+    job: UnitJob,
+}
+
+pub fn promotion_plugin(app: &mut App) {
+    app.init_resource::<PromotionConfig>()
+        .add_systems(OnEnter(RestScreen::Promotion), open_promotion_menu)
+        .add_observer(promote_unit);
+}
+
+fn open_promotion_menu(
+    mut commands: Commands,
+    fonts: Res<FontResource>,
+    registered_players: Res<RegisteredBattlePlayers>,
+    config: Res<PromotionConfig>,
+) {
+    let font = TextFont {
+        font_size: 26.0,
+        font: fonts.pixelify_sans_regular.clone(),
+        ..default()
+    };
+
+    let mut party = registered_players
+        .units()
+        .map(|(_, t)| t)
+        .collect::<Vec<_>>();
+    party.sort_by_key(|t| t.save_file_key.uid);
+
+    let mut buttons = party
+        .iter()
+        .flat_map(|save| config.options(save).iter().map(move |job| (save, job)))
+        .map(|(save, job)| {
+            commands
+                .spawn((
+                    menu_button(
+                        &font,
+                        Text(tr!(
+                            "promotion.option",
+                            name = save.save_file_key.name,
+                            from = save.job.name(),
+                            to = job.name()
+                        )),
+                    ),
+                    PromotionButton {
+                        uid: save.save_file_key.uid,
+                        job: job.clone(),
+                    },
+                ))
+                .id()
+        })
+        .collect::<Vec<_>>();
+    let nobody_ready = buttons.is_empty();
+    buttons.push(
+        commands
+            .spawn((
+                menu_button(&font, localized_text("action.back")),
+                BackToCampfireButton,
+            ))
+            .id(),
+    );
+
+    let panel = menu_panel(
+        &mut commands,
+        &fonts,
+        &registered_players,
+        "promotion.title",
+        &buttons,
+        RestScreen::Promotion,
+    );
+    if nobody_ready {
+        let notice = commands
+            .spawn((
+                Text(tr!("promotion.nobody_ready", level = config.level)),
+                font.clone(),
+                TextColor(UI_TEXT_COLOR),
+            ))
+            .id();
+        commands.entity(panel).add_child(notice);
+    }
+    commands.entity(panel).add_children(&buttons);
+}
+
+/// Promotes the unit's save, and the unit standing in the room along with it so they look and
+/// hold up like their new job straight away
+fn promote_unit(
+    mut click: On<Pointer<Click>>,
+    buttons: Query<&PromotionButton>,
+    mut units: Query<
+        (
+            &SaveFileKey,
+            &FacingDirection,
+            &mut UnitBaseStats,
+            &mut Sprite,
+        ),
+        With<Unit>,
+    >,
+    registered_players: Option<ResMut<RegisteredBattlePlayers>>,
+    anim_db: Res<AnimationDB>,
+    sprite_db: Res<SpriteDB>,
+    mut next_screen: ResMut<NextState<RestScreen>>,
+) {
+    let (Ok(button), Some(mut registered_players)) =
+        (buttons.get(click.entity), registered_players)
+    else {
+        return;
+    };
+    click.propagate(false);
+
+    let mut promoted = true;
+    registered_players.update_unit(button.uid, |save| {
+        if let Err(e) = promote(save, button.job.clone()) {
+            error!("Couldn't promote {}: {:?}", save.save_file_key.name, e);
+            promoted = false;
+        }
+    });
+    if !promoted {
+        return;
+    }
+
+    for (key, direction, mut base_stats, mut sprite) in units.iter_mut() {
+        if key.uid != button.uid {
+            continue;
+        }
+        apply_promotion_bonus(&mut base_stats.stats, &button.job);
+        match get_sprite_resources_for_job(&anim_db, &sprite_db, &button.job, direction.0, false) {
+            Ok((image, texture_atlas)) => {
+                sprite.image = image;
+                sprite.texture_atlas = Some(texture_atlas);
+            }
+            Err(e) => error!("No sprite for {:?}: {:?}", button.job, e),
+        }
+    }
+
+    next_screen.set(RestScreen::Campfire);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        equipment::{ItemId, build_item_db},
+        save_game::SaveFileColor,
+    };
+
+    fn knight() -> UnitSaveV2 {
+        UnitSaveV2::new(
+            SaveFileKey {
+                uid: 3,
+                name: "Hedge Knight".to_string(),
+                color: SaveFileColor::Blue,
+            },
+            UnitJob::Knight,
+        )
+    }
+
+    #[test]
+    fn test_only_leveled_knights_get_promoted() {
+        let config = PromotionConfig::default();
+        let mut save = knight();
+        assert!(config.options(&save).is_empty());
+
+        save.level = config.level;
+        assert_eq!(config.options(&save), &[UnitJob::Paladin, UnitJob::General]);
+        assert!(promote(&mut save, UnitJob::Mage).is_err());
+
+        let before = save.base_stats.clone();
+        promote(&mut save, UnitJob::General).expect("A Knight can become a General");
+        assert_eq!(save.job, UnitJob::General);
+        assert!(save.base_stats.stat(StatType::Defense).0 > before.stat(StatType::Defense).0);
+        assert_eq!(
+            save.base_stats.stat(StatType::Health),
+            save.base_stats.stat(StatType::MaxHealth)
+        );
+        // Nothing promotes twice
+        assert!(config.options(&save).is_empty());
+    }
+
+    #[test]
+    fn test_promoted_jobs_keep_their_old_gear() {
+        let item_db = build_item_db().expect("Item DB should parse");
+        let buckler = item_db.equippable_item(&ItemId(3)).unwrap();
+        let tower_shield = item_db.equippable_item(&ItemId(6)).unwrap();
+
+        assert!(buckler.usable_by(&UnitJob::General));
+        assert!(tower_shield.usable_by(&UnitJob::General));
+        assert!(!tower_shield.usable_by(&UnitJob::Knight));
+        assert!(!tower_shield.usable_by(&UnitJob::Paladin));
+    }
+}
@@ -0,0 +1,147 @@
+//! Dressing a room up without changing how it plays.
+//!
+//! Anything that gets in the way is an [`Obstacle`](crate::map_generation::Obstacle), and is
+//! spawned as a unit nobody can walk through. Everything else is a [`Decoration`], sprinkled over
+//! the open ground by the room's [`Biome`](crate::biome::Biome). Both are their own sprites rather
+//! than a tile layer, placed with [`grid_to_world`](crate::grid::grid_to_world) like units are, so
+//! they sort in front of and behind the units properly.
+
+use std::collections::BTreeSet;
+
+use bevy::prelude::*;
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use rand_seeder::Seeder;
+
+use crate::{
+    animation::TinytacticsAssets,
+    battle::BattleEntity,
+    dungeon::DungeonEntity,
+    grid::{ELEVATION_STEP, GridPosition, init_grid_to_world_transform},
+    map_generation::{MapData, TtIndex},
+    unit::TINY_TACTICS_ANCHOR,
+};
+
+/// Decorations sit just behind anyone standing on the same tile
+const DECORATION_Z_OFFSET: f32 = -0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoration {
+    Flowers,
+    Pebbles,
+}
+
+impl Decoration {
+    fn tt_index(&self) -> TtIndex {
+        match self {
+            Decoration::Flowers => TtIndex::new(4, 3),
+            Decoration::Pebbles => TtIndex::new(4, 4),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecorationPlacement {
+    pub position: GridPosition,
+    pub decoration: Decoration,
+}
+
+/// Decorates the open ground, leaving alone anything that has to stay clear to read the room
+pub fn scatter_decorations(seed: &str, map_data: &MapData) -> Vec<DecorationPlacement> {
+    // Kept apart from the scatter's rng, so decorations don't reshuffle the room
+    let mut rng: Pcg64 = Seeder::from(format!("{}-props", seed)).into_rng();
+    let options = map_data.biome.decorations();
+    let taken = map_data
+        .player_start_locations
+        .iter()
+        .chain(&map_data.bridge_end_locations)
+        .chain(map_data.chests.iter().map(|t| &t.position))
+        .chain(&map_data.enemy_spawns)
+        .chain(&map_data.boss_spawn)
+        .chain(&map_data.ally_spawns)
+        .chain(map_data.obstacles.keys())
+        .collect::<BTreeSet<_>>();
+
+    let (width, height) = map_data.game_size();
+    let mut placements = Vec::new();
+    for x in 0..width {
+        for y in 0..height {
+            let position = GridPosition { x, y };
+            if taken.contains(&position)
+                || rng.random::<f32>() >= map_data.biome.decoration_chance()
+            {
+                continue;
+            }
+            let Some(decoration) = options.choose(&mut rng) else {
+                continue;
+            };
+            placements.push(DecorationPlacement {
+                position,
+                decoration: *decoration,
+            });
+        }
+    }
+    placements
+}
+
+pub fn spawn_decorations(
+    commands: &mut Commands,
+    tt_assets: &TinytacticsAssets,
+    map_data: &MapData,
+) {
+    for placement in &map_data.decorations {
+        let elevation = map_data
+            .elevation
+            .get(&placement.position)
+            .copied()
+            .unwrap_or_default();
+        let mut transform = init_grid_to_world_transform(&placement.position);
+        transform.translation.y += elevation as f32 * ELEVATION_STEP;
+        transform.translation.z += DECORATION_Z_OFFSET;
+
+        commands.spawn((
+            Name::new(format!("{:?}", placement.decoration)),
+            Sprite {
+                image: tt_assets.tile_spritesheet.clone(),
+                texture_atlas: Some(TextureAtlas {
+                    layout: tt_assets.tile_layout.clone(),
+                    index: (placement.decoration.tt_index().index() - 1) as usize,
+                }),
+                ..Default::default()
+            },
+            TINY_TACTICS_ANCHOR,
+            transform,
+            BattleEntity {},
+            DungeonEntity,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_generation::{RoomType, setup_room_map_data};
+
+    #[test]
+    fn test_decorations_stay_off_anything_important() {
+        let mut world = World::new();
+        let mut commands = world.commands();
+        let mut decorated_rooms = 0;
+        for seed in 0..20 {
+            let map_data = setup_room_map_data(&mut commands, seed.to_string(), RoomType::Standard);
+            if !map_data.decorations.is_empty() {
+                decorated_rooms += 1;
+            }
+
+            for placement in &map_data.decorations {
+                let position = &placement.position;
+                assert!(!map_data.obstacles.contains_key(position));
+                assert!(!map_data.player_start_locations.contains(position));
+                assert!(!map_data.bridge_end_locations.contains(position));
+                assert!(!map_data.chests.iter().any(|t| t.position == *position));
+                assert!(!map_data.enemy_spawns.contains(position));
+            }
+        }
+        assert!(decorated_rooms > 0);
+    }
+}
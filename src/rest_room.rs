@@ -0,0 +1,704 @@
+//! Rooms without a fight.
+//!
+//! A [`RoomKind::Rest`](crate::dungeon::RoomKind::Rest) room skips the battle altogether. The
+//! party gets a [`Campfire`] that patches everyone up once, injuries and all, and a chance to swap what they're
+//! holding for whatever's turned up in the [`PartyInventory`] so far, as long as their job lets
+//! them use it. Anything worn out gets [repaired](crate::durability) for free, and anyone with
+//! enough levels can get [promoted](crate::promotion). The party can also look over the
+//! [bonds](crate::bonds) they've built up. Moving on heads straight to picking the next room.
+
+use bevy::prelude::*;
+
+use crate::{
+    assets::FontResource,
+    dungeon::{DungeonEntity, DungeonState},
+    durability::repair_party_gear,
+    equipment::{ItemDB, ItemId},
+    gameplay_effects::{ActiveEffects, StatusTag},
+    interactable::{Campfire, InteractionEnabled},
+    inventory::PartyInventory,
+    localization::localized_text,
+    map_generation::MapData,
+    menu::{
+        menu_navigation::{
+            ActiveMenu, GameMenuController, GameMenuGrid, GameMenuLatch,
+            handle_menu_cursor_navigation, highlight_menu_option,
+        },
+        ui_consts::{SELECTABLE_BUTTON_BACKGROUND, UI_MENU_BACKGROUND, UI_TEXT_COLOR},
+    },
+    player::RegisteredBattlePlayers,
+    save_game::{SaveFileKey, UnitSaveV2},
+    tr,
+    unit::{Unit, equip_starting_items_on_unit, jobs::UnitJob},
+    unit_stats::{StatContainer, StatType, StatValue, StatsDirty, UnitBaseStats},
+};
+
+/// How much of everyone's max health a campfire gives back
+pub const CAMPFIRE_HEAL_PERCENT: f32 = 0.5;
+
+/// Which screen of the rest room is up
+#[derive(SubStates, Clone, PartialEq, Eq, Hash, Debug, Default, Reflect)]
+#[source(DungeonState = DungeonState::RestRoom)]
+pub enum RestScreen {
+    #[default]
+    Campfire,
+    Equipment,
+    Promotion,
+    Bonds,
+}
+
+#[derive(Component)]
+enum RestMenuAction {
+    Rest,
+    Repair,
+    OpenEquipment,
+    OpenPromotions,
+    OpenBonds,
+    MoveOn,
+}
+
+/// Swaps the unit with this uid over to the next item in the bag
+#[derive(Component)]
+struct EquipmentButton {
+    uid: u32,
+}
+
+/// Heads back to the main rest menu
+#[derive(Component)]
+pub(crate) struct BackToCampfireButton;
+
+/// Shows how a unit in the room is holding up
+#[derive(Component)]
+struct PartyHealthLabel(Entity);
+
+#[derive(Component)]
+struct BagLabel;
+
+/// Says why the last swap didn't happen
+#[derive(Component)]
+struct EquipmentNotice;
+
+/// Units with nothing in the bag they can use get their button dimmed
+const UNUSABLE_TEXT_COLOR: Color = Color::linear_rgba(1.0, 1.0, 1.0, 0.4);
+
+pub fn rest_room_plugin(app: &mut App) {
+    app.add_sub_state::<RestScreen>()
+        .add_systems(
+            OnEnter(DungeonState::RestRoom),
+            equip_starting_items_on_unit,
+        )
+        .add_systems(OnEnter(RestScreen::Campfire), open_campfire_menu)
+        .add_systems(OnEnter(RestScreen::Equipment), open_equipment_menu)
+        .add_systems(
+            Update,
+            (
+                handle_menu_cursor_navigation,
+                highlight_menu_option,
+                update_party_health_labels,
+                update_equipment_labels.run_if(resource_changed::<PartyInventory>),
+                dim_unusable_equipment_buttons.run_if(in_state(RestScreen::Equipment)),
+            )
+                .run_if(in_state(DungeonState::RestRoom)),
+        )
+        .add_observer(rest_menu_action)
+        .add_observer(swap_equipment)
+        .add_observer(back_to_campfire);
+}
+
+impl Campfire {
+    /// Gives back some of the unit's max health, without going over it
+    pub fn heal(&self, stats: &mut StatContainer) {
+        let max_health = stats.stat(StatType::MaxHealth).0;
+        let health = stats.stat(StatType::Health).0 + max_health * self.heal_percent;
+        stats.with_stat(StatType::Health, StatValue(health.min(max_health)));
+    }
+}
+
+pub fn spawn_campfire(commands: &mut Commands, map_data: &MapData) {
+    let Some(position) = map_data.open_tile_near_middle() else {
+        warn!("Nowhere to put the campfire");
+        return;
+    };
+
+    commands.spawn((
+        Name::new("Campfire"),
+        position,
+        Campfire {
+            heal_percent: CAMPFIRE_HEAL_PERCENT,
+        },
+        InteractionEnabled,
+        DungeonEntity,
+    ));
+}
+
+/// Whatever's been in the bag the longest that a `job` can use
+fn next_usable_item(inventory: &PartyInventory, job: &UnitJob, item_db: &ItemDB) -> Option<ItemId> {
+    inventory.items.iter().copied().find(|t| {
+        item_db
+            .equippable_item(t)
+            .is_some_and(|item| item.usable_by(job))
+    })
+}
+
+/// Takes the next item out of the bag that the unit can use and puts it on, and whatever it bumps
+/// off goes to the back of the bag. Returns what got put on, if anything.
+fn equip_next_item(
+    equipped: &mut Vec<ItemId>,
+    job: &UnitJob,
+    inventory: &mut PartyInventory,
+    item_db: &ItemDB,
+) -> Option<ItemId> {
+    let next = next_usable_item(inventory, job, item_db)?;
+    let Some(slot) = item_db.equippable_item(&next).map(|t| t.slot()) else {
+        error!("The bag has an item that doesn't exist: {:?}", next);
+        return None;
+    };
+    inventory.remove_item(next);
+
+    let (bumped, kept): (Vec<_>, Vec<_>) = equipped.iter().copied().partition(|t| {
+        item_db
+            .equippable_item(t)
+            .is_some_and(|item| item.slot().overlaps(&slot))
+    });
+    *equipped = kept;
+    equipped.push(next);
+    equipped.sort_by_key(|t| t.0);
+    for item in bumped {
+        inventory.add_item(item);
+    }
+
+    Some(next)
+}
+
+fn item_names(items: &[ItemId], item_db: &ItemDB) -> String {
+    if items.is_empty() {
+        return tr!("equipment.nothing");
+    }
+
+    items
+        .iter()
+        .filter_map(|t| item_db.equippable_item(t))
+        .map(|t| t.description())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Everything in the bag, consumables after the equipment
+fn bag_contents(inventory: &PartyInventory, item_db: &ItemDB) -> String {
+    let consumables = inventory.consumables.iter().map(|(consumable, count)| {
+        tr!(
+            "equipment.consumable",
+            item = tr!(consumable.name_key()),
+            count = count
+        )
+    });
+    let contents = inventory
+        .items
+        .iter()
+        .filter_map(|t| item_db.equippable_item(t))
+        .map(|t| t.description())
+        .chain(consumables)
+        .collect::<Vec<_>>();
+
+    if contents.is_empty() {
+        tr!("equipment.nothing")
+    } else {
+        contents.join(", ")
+    }
+}
+
+fn equipment_line(save: &UnitSaveV2, item_db: &ItemDB) -> String {
+    tr!(
+        "equipment.unit",
+        name = save.save_file_key.name,
+        items = item_names(&save.equipped_items, item_db)
+    )
+}
+
+pub(crate) fn menu_button(font: &TextFont, text: impl Bundle) -> impl Bundle {
+    (
+        Button,
+        Node {
+            min_width: px(300),
+            height: px(65),
+            margin: UiRect::all(px(8)),
+            padding: UiRect::horizontal(px(16)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            border_radius: BorderRadius::all(percent(20)),
+            ..default()
+        },
+        BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+        children![(text, font.clone(), TextColor(UI_TEXT_COLOR))],
+    )
+}
+
+pub(crate) fn menu_panel(
+    commands: &mut Commands,
+    fonts: &FontResource,
+    registered_players: &RegisteredBattlePlayers,
+    title: &'static str,
+    buttons: &[Entity],
+    screen: RestScreen,
+) -> Entity {
+    let mut grid = GameMenuGrid::new_vertical();
+    grid.push_buttons_to_stack(buttons);
+
+    commands
+        .spawn((
+            Name::new(format!("RestMenu {:?}", screen)),
+            Node {
+                position_type: PositionType::Absolute,
+                right: px(40),
+                top: percent(15),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(px(20)),
+                row_gap: px(8),
+                border_radius: BorderRadius::all(percent(10)),
+                ..default()
+            },
+            BackgroundColor(UI_MENU_BACKGROUND),
+            children![(
+                localized_text(title),
+                TextFont {
+                    font_size: 50.0,
+                    font: fonts.pixelify_sans_medium.clone(),
+                    ..default()
+                },
+                TextColor(UI_TEXT_COLOR),
+            )],
+            grid,
+            GameMenuController {
+                players: registered_players.save_files.keys().cloned().collect(),
+            },
+            GameMenuLatch::default(),
+            ActiveMenu {},
+            DespawnOnExit(screen),
+        ))
+        .id()
+}
+
+fn open_campfire_menu(
+    mut commands: Commands,
+    fonts: Res<FontResource>,
+    registered_players: Res<RegisteredBattlePlayers>,
+    party: Query<Entity, (With<Unit>, With<SaveFileKey>)>,
+    campfires: Query<(), (With<Campfire>, With<InteractionEnabled>)>,
+) {
+    let font = TextFont {
+        font_size: 33.0,
+        font: fonts.pixelify_sans_regular.clone(),
+        ..default()
+    };
+
+    // Coming back from the equipment screen after resting shouldn't relight the fire
+    let rest_label = if campfires.is_empty() {
+        "rest.burned_out"
+    } else {
+        "rest.rest"
+    };
+    let buttons = [
+        commands
+            .spawn((
+                menu_button(&font, localized_text(rest_label)),
+                RestMenuAction::Rest,
+            ))
+            .id(),
+        commands
+            .spawn((
+                menu_button(&font, localized_text("rest.repair")),
+                RestMenuAction::Repair,
+            ))
+            .id(),
+        commands
+            .spawn((
+                menu_button(&font, localized_text("rest.equipment")),
+                RestMenuAction::OpenEquipment,
+            ))
+            .id(),
+        commands
+            .spawn((
+                menu_button(&font, localized_text("rest.promotion")),
+                RestMenuAction::OpenPromotions,
+            ))
+            .id(),
+        commands
+            .spawn((
+                menu_button(&font, localized_text("rest.bonds")),
+                RestMenuAction::OpenBonds,
+            ))
+            .id(),
+        commands
+            .spawn((
+                menu_button(&font, localized_text("rest.move_on")),
+                RestMenuAction::MoveOn,
+            ))
+            .id(),
+    ];
+
+    let panel = menu_panel(
+        &mut commands,
+        &fonts,
+        &registered_players,
+        "rest.title",
+        &buttons,
+        RestScreen::Campfire,
+    );
+    commands.entity(panel).add_children(&buttons);
+
+    for unit in party.iter() {
+        let label = commands
+            .spawn((
+                Text::default(),
+                TextFont {
+                    font_size: 22.0,
+                    font: fonts.pixelify_sans_regular.clone(),
+                    ..default()
+                },
+                TextColor(UI_TEXT_COLOR),
+                PartyHealthLabel(unit),
+            ))
+            .id();
+        commands.entity(panel).add_child(label);
+    }
+}
+
+fn open_equipment_menu(
+    mut commands: Commands,
+    fonts: Res<FontResource>,
+    registered_players: Res<RegisteredBattlePlayers>,
+    item_db: Res<ItemDB>,
+    inventory: Res<PartyInventory>,
+) {
+    let font = TextFont {
+        font_size: 26.0,
+        font: fonts.pixelify_sans_regular.clone(),
+        ..default()
+    };
+
+    let mut party = registered_players
+        .units()
+        .map(|(_, t)| t)
+        .collect::<Vec<_>>();
+    party.sort_by_key(|t| t.save_file_key.uid);
+
+    let mut buttons = party
+        .iter()
+        .map(|save| {
+            commands
+                .spawn((
+                    menu_button(&font, Text(equipment_line(save, &item_db))),
+                    EquipmentButton {
+                        uid: save.save_file_key.uid,
+                    },
+                ))
+                .id()
+        })
+        .collect::<Vec<_>>();
+    buttons.push(
+        commands
+            .spawn((
+                menu_button(&font, localized_text("action.back")),
+                BackToCampfireButton,
+            ))
+            .id(),
+    );
+
+    let panel = menu_panel(
+        &mut commands,
+        &fonts,
+        &registered_players,
+        "equipment.title",
+        &buttons,
+        RestScreen::Equipment,
+    );
+    let bag = commands
+        .spawn((
+            Text(tr!(
+                "equipment.bag",
+                items = bag_contents(&inventory, &item_db)
+            )),
+            font.clone(),
+            TextColor(UI_TEXT_COLOR),
+            BagLabel,
+        ))
+        .id();
+    let notice = commands
+        .spawn((
+            Text::default(),
+            font.clone(),
+            TextColor(UI_TEXT_COLOR),
+            EquipmentNotice,
+        ))
+        .id();
+    commands
+        .entity(panel)
+        .add_children(&[bag, notice])
+        .add_children(&buttons);
+}
+
+fn dim_unusable_equipment_buttons(
+    buttons: Query<(&EquipmentButton, &Children)>,
+    mut colors: Query<&mut TextColor>,
+    registered_players: Res<RegisteredBattlePlayers>,
+    item_db: Res<ItemDB>,
+    inventory: Res<PartyInventory>,
+) {
+    for (button, children) in buttons.iter() {
+        let Some((_, save)) = registered_players
+            .units()
+            .find(|(_, t)| t.save_file_key.uid == button.uid)
+        else {
+            continue;
+        };
+        let color = if next_usable_item(&inventory, &save.job, &item_db).is_some() {
+            UI_TEXT_COLOR
+        } else {
+            UNUSABLE_TEXT_COLOR
+        };
+        for child in children.iter() {
+            if let Ok(mut text_color) = colors.get_mut(child)
+                && text_color.0 != color
+            {
+                text_color.0 = color;
+            }
+        }
+    }
+}
+
+fn update_party_health_labels(
+    mut labels: Query<(&mut Text, &PartyHealthLabel)>,
+    units: Query<(&Unit, &UnitBaseStats)>,
+) {
+    for (mut text, label) in labels.iter_mut() {
+        let Ok((unit, base_stats)) = units.get(label.0) else {
+            continue;
+        };
+        let line = tr!(
+            "rest.health",
+            name = unit.name,
+            health = base_stats.stats.stat(StatType::Health).0 as u32,
+            max_health = base_stats.stats.stat(StatType::MaxHealth).0 as u32
+        );
+        if text.0 != line {
+            text.0 = line;
+        }
+    }
+}
+
+fn update_equipment_labels(
+    buttons: Query<(&EquipmentButton, &Children)>,
+    mut texts: Query<&mut Text, Without<BagLabel>>,
+    mut bag: Query<&mut Text, With<BagLabel>>,
+    registered_players: Res<RegisteredBattlePlayers>,
+    item_db: Res<ItemDB>,
+    inventory: Res<PartyInventory>,
+) {
+    for (button, children) in buttons.iter() {
+        let Some((_, save)) = registered_players
+            .units()
+            .find(|(_, t)| t.save_file_key.uid == button.uid)
+        else {
+            continue;
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.0 = equipment_line(save, &item_db);
+            }
+        }
+    }
+
+    for mut text in bag.iter_mut() {
+        text.0 = tr!("equipment.bag", items = bag_contents(&inventory, &item_db));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn rest_menu_action(
+    mut click: On<Pointer<Click>>,
+    mut commands: Commands,
+    actions: Query<(&RestMenuAction, &Children)>,
+    campfires: Query<(Entity, &Campfire), With<InteractionEnabled>>,
+    mut party: Query<
+        (Entity, &mut UnitBaseStats, &mut ActiveEffects),
+        (With<Unit>, With<SaveFileKey>),
+    >,
+    registered_players: Option<ResMut<RegisteredBattlePlayers>>,
+    inventory: Option<ResMut<PartyInventory>>,
+    mut next_screen: ResMut<NextState<RestScreen>>,
+    mut next_state: ResMut<NextState<DungeonState>>,
+) {
+    let Ok((action, children)) = actions.get(click.entity) else {
+        return;
+    };
+    click.propagate(false);
+
+    match action {
+        RestMenuAction::Rest => {
+            let Some((entity, campfire)) = campfires.iter().next() else {
+                info!("The campfire has already burned out");
+                return;
+            };
+            for (unit, mut base_stats, mut effects) in party.iter_mut() {
+                campfire.heal(&mut base_stats.stats);
+                if effects.cure(StatusTag::Injured) {
+                    commands.entity(unit).insert(StatsDirty);
+                }
+            }
+            commands.entity(entity).remove::<InteractionEnabled>();
+            for child in children.iter() {
+                commands
+                    .entity(child)
+                    .insert(localized_text("rest.burned_out"));
+            }
+        }
+        RestMenuAction::Repair => {
+            let (Some(mut registered_players), Some(mut inventory)) =
+                (registered_players, inventory)
+            else {
+                return;
+            };
+            repair_party_gear(&mut registered_players, &mut inventory);
+            for child in children.iter() {
+                commands
+                    .entity(child)
+                    .insert(localized_text("rest.repaired"));
+            }
+        }
+        RestMenuAction::OpenEquipment => next_screen.set(RestScreen::Equipment),
+        RestMenuAction::OpenPromotions => next_screen.set(RestScreen::Promotion),
+        RestMenuAction::OpenBonds => next_screen.set(RestScreen::Bonds),
+        RestMenuAction::MoveOn => next_state.set(DungeonState::UnloadRoom),
+    }
+}
+
+fn swap_equipment(
+    mut click: On<Pointer<Click>>,
+    buttons: Query<&EquipmentButton>,
+    mut notices: Query<&mut Text, With<EquipmentNotice>>,
+    registered_players: Option<ResMut<RegisteredBattlePlayers>>,
+    inventory: Option<ResMut<PartyInventory>>,
+    item_db: Option<Res<ItemDB>>,
+) {
+    let (Ok(button), Some(mut registered_players), Some(mut inventory), Some(item_db)) = (
+        buttons.get(click.entity),
+        registered_players,
+        inventory,
+        item_db,
+    ) else {
+        return;
+    };
+    click.propagate(false);
+
+    let Some((mut equipped, job, name)) = registered_players
+        .units()
+        .find(|(_, t)| t.save_file_key.uid == button.uid)
+        .map(|(_, t)| {
+            (
+                t.equipped_items.clone(),
+                t.job.clone(),
+                t.save_file_key.name.clone(),
+            )
+        })
+    else {
+        return;
+    };
+
+    let notice = if equip_next_item(&mut equipped, &job, &mut inventory, &item_db).is_some() {
+        String::new()
+    } else if inventory.items.is_empty() {
+        tr!("equipment.bag_empty")
+    } else {
+        tr!("equipment.cant_use", name = name, job = job.name())
+    };
+    for mut text in notices.iter_mut() {
+        text.0 = notice.clone();
+    }
+    if !notice.is_empty() {
+        return;
+    }
+
+    registered_players.update_unit(button.uid, |save| {
+        save.equipped_items = equipped.clone();
+    });
+}
+
+fn back_to_campfire(
+    mut click: On<Pointer<Click>>,
+    buttons: Query<(), With<BackToCampfireButton>>,
+    mut next_screen: ResMut<NextState<RestScreen>>,
+) {
+    if !buttons.contains(click.entity) {
+        return;
+    }
+    click.propagate(false);
+    next_screen.set(RestScreen::Campfire);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equipment::build_item_db;
+
+    #[test]
+    fn test_campfire_heals_up_to_max_health() {
+        let campfire = Campfire { heal_percent: 0.5 };
+        let mut stats = StatContainer::default();
+        stats.with_stat(StatType::MaxHealth, StatValue(20.));
+        stats.with_stat(StatType::Health, StatValue(5.));
+
+        campfire.heal(&mut stats);
+        assert_eq!(stats.stat(StatType::Health), StatValue(15.));
+
+        campfire.heal(&mut stats);
+        assert_eq!(stats.stat(StatType::Health), StatValue(20.));
+    }
+
+    #[test]
+    fn test_swapped_out_items_go_back_in_the_bag() {
+        let item_db = build_item_db().expect("Item DB should parse");
+        let mut equipped = vec![ItemId(1)];
+        let mut inventory = PartyInventory {
+            items: vec![ItemId(2)],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            equip_next_item(&mut equipped, &UnitJob::Archer, &mut inventory, &item_db),
+            Some(ItemId(2))
+        );
+        assert_eq!(equipped, vec![ItemId(2)]);
+        assert_eq!(inventory.items, vec![ItemId(1)]);
+
+        inventory.items.clear();
+        assert_eq!(
+            equip_next_item(&mut equipped, &UnitJob::Archer, &mut inventory, &item_db),
+            None
+        );
+        assert_eq!(equipped, vec![ItemId(2)]);
+    }
+
+    #[test]
+    fn test_jobs_skip_what_they_cant_use() {
+        let item_db = build_item_db().expect("Item DB should parse");
+        let mut equipped = vec![ItemId(1)];
+        let mut inventory = PartyInventory {
+            items: vec![ItemId(2)],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            equip_next_item(&mut equipped, &UnitJob::Mage, &mut inventory, &item_db),
+            None
+        );
+        assert_eq!(equipped, vec![ItemId(1)]);
+        assert_eq!(inventory.items, vec![ItemId(2)]);
+
+        inventory.add_item(ItemId(1));
+        assert_eq!(
+            next_usable_item(&inventory, &UnitJob::Mage, &item_db),
+            Some(ItemId(1))
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! Rewinding the battle back to before the last unit action.
+//!
+//! Right before a unit action executes, every unit is snapshotted onto the [`RewindStack`].
+//! For now this is only exposed through god mode (press R), but it should be enough to build a
+//! player facing "Mercy rewind" on top of, and it's handy for getting back to right before a bug.
+//!
+//! Snapshots only last for the phase they were taken in. Rewinding across phases would mean
+//! rewinding the enemy AI too, and I'm not signing up for that yet.
+
+use bevy::prelude::*;
+
+use crate::{
+    animation::FacingDirection,
+    battle_phase::{PhaseMessage, UnitPhaseResources},
+    dungeon::DungeonState,
+    gameplay_effects::ActiveEffects,
+    grid::{GridMovement, GridPosition},
+    unit::{CombatActionMarker, Unit, UnitExecuteActionMessage, execute_unit_actions},
+    unit_stats::{StatContainer, UnitBaseStats, UnitDerivedStats},
+};
+
+/// Only hang on to so many actions worth of history
+pub const MAX_SNAPSHOTS: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct UnitSnapshot {
+    pub entity: Entity,
+    pub position: GridPosition,
+    pub facing: FacingDirection,
+    pub base_stats: StatContainer,
+    pub derived_stats: StatContainer,
+    pub phase_resources: UnitPhaseResources,
+    pub active_effects: Option<ActiveEffects>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BattleSnapshot {
+    pub units: Vec<UnitSnapshot>,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct RewindStack {
+    snapshots: Vec<BattleSnapshot>,
+}
+
+impl RewindStack {
+    pub fn push(&mut self, snapshot: BattleSnapshot) {
+        if self.snapshots.len() >= MAX_SNAPSHOTS {
+            self.snapshots.remove(0);
+        }
+        self.snapshots.push(snapshot);
+    }
+
+    pub fn pop(&mut self) -> Option<BattleSnapshot> {
+        self.snapshots.pop()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
+
+pub fn rewind_plugin(app: &mut App) {
+    app.init_resource::<RewindStack>().add_systems(
+        Update,
+        (
+            clear_rewind_stack_on_phase_begin,
+            snapshot_before_unit_action.before(execute_unit_actions),
+            rewind_last_action,
+        )
+            .chain()
+            .run_if(in_state(DungeonState::InBattle)),
+    );
+}
+
+type SnapshotUnit<'a> = (
+    Entity,
+    &'a GridPosition,
+    &'a FacingDirection,
+    &'a UnitBaseStats,
+    &'a UnitDerivedStats,
+    &'a UnitPhaseResources,
+    Option<&'a ActiveEffects>,
+);
+
+pub fn snapshot_before_unit_action(
+    mut reader: MessageReader<UnitExecuteActionMessage>,
+    mut rewind_stack: ResMut<RewindStack>,
+    units: Query<SnapshotUnit, With<Unit>>,
+) {
+    // One snapshot covers however many actions showed up this frame
+    if reader.read().count() == 0 {
+        return;
+    }
+
+    let units = units
+        .iter()
+        .map(
+            |(entity, position, facing, base, derived, resources, effects)| UnitSnapshot {
+                entity,
+                position: *position,
+                facing: facing.clone(),
+                base_stats: base.stats.clone(),
+                derived_stats: derived.stats.clone(),
+                phase_resources: resources.clone(),
+                active_effects: effects.cloned(),
+            },
+        )
+        .collect();
+
+    rewind_stack.push(BattleSnapshot { units });
+}
+
+pub fn clear_rewind_stack_on_phase_begin(
+    mut reader: MessageReader<PhaseMessage>,
+    mut rewind_stack: ResMut<RewindStack>,
+) {
+    if reader.read().count() > 0 {
+        rewind_stack.clear();
+    }
+}
+
+type RewindableUnit<'a> = (
+    &'a mut GridPosition,
+    &'a mut FacingDirection,
+    &'a mut UnitBaseStats,
+    &'a mut UnitDerivedStats,
+    &'a mut UnitPhaseResources,
+    Option<&'a mut ActiveEffects>,
+);
+
+pub fn rewind_last_action(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut rewind_stack: ResMut<RewindStack>,
+    mut units: Query<RewindableUnit, With<Unit>>,
+    busy: Query<(), Or<(With<CombatActionMarker>, With<GridMovement>)>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyR) {
+        return;
+    }
+
+    // Rewinding out from under an animation is asking for trouble
+    if !busy.is_empty() {
+        warn!("Can't rewind while an action is still playing out");
+        return;
+    }
+
+    let Some(snapshot) = rewind_stack.pop() else {
+        info!("Nothing to rewind");
+        return;
+    };
+
+    info!(
+        "Rewinding the last unit action, {} more snapshots left",
+        rewind_stack.len()
+    );
+
+    for unit in snapshot.units {
+        let Ok((mut position, mut facing, mut base, mut derived, mut resources, effects)) =
+            units.get_mut(unit.entity)
+        else {
+            warn!("Unit {:?} from the snapshot is gone", unit.entity);
+            continue;
+        };
+
+        if *position != unit.position {
+            *position = unit.position;
+        }
+        *facing = unit.facing;
+        base.stats = unit.base_stats;
+        derived.stats = unit.derived_stats;
+        *resources = unit.phase_resources;
+
+        if let (Some(mut effects), Some(snapshot_effects)) = (effects, unit.active_effects) {
+            *effects = snapshot_effects;
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! Hand authored rooms, for the fights that shouldn't be left up to the dice.
+//!
+//! Each template is a RON file under `assets/rooms`, laid out as rows of characters over the
+//! playable ground. Rows go from the entrance to the exit (game space x), and characters go along
+//! the row (game space y):
+//!
+//! | Char | Meaning                          |
+//! |------|----------------------------------|
+//! | `.`  | Grass                            |
+//! | `,`  | Dead grass                       |
+//! | `r`  | Rock                             |
+//! | `b`  | Bush                             |
+//! | `t`  | Tree                             |
+//! | `u`  | Ruined pillar                    |
+//! | `f`  | Fence                            |
+//! | `E`  | Enemy spawn, on grass            |
+//! | `B`  | Boss spawn, on grass             |
+//! | `C`  | Treasure chest, on grass         |
+//! | `$`  | Treasure chest with rare loot    |
+//!
+//! The entrance and exit bridges are two tiles wide, starting from `entrance` and `exit` along the
+//! first and last rows. A boss room has no exit. A template can pin itself to a
+//! [`Biome`], otherwise it takes on whichever one the room lands in.
+//!
+//! The dungeon mixes these in with the procedural rooms, see
+//! [`setup_room_map_data`](crate::map_generation::setup_room_map_data).
+
+use std::{collections::HashMap, sync::LazyLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    biome::Biome,
+    grid::GridPosition,
+    loot::LootTableId,
+    map_generation::{GrassTileType, Obstacle, RoomType, reachable_tiles},
+};
+
+/// How many tiles of ground a room has along each side
+pub const TEMPLATE_SIZE: u32 = 13;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomTemplate {
+    pub name: String,
+    pub room_type: RoomType,
+    /// Where the entrance bridge meets the first row
+    pub entrance: u32,
+    /// Where the exit bridge meets the last row
+    #[serde(default)]
+    pub exit: Option<u32>,
+    /// Left out to go with wherever the room lands
+    #[serde(default)]
+    pub biome: Option<Biome>,
+    pub layout: Vec<String>,
+}
+
+/// What a single character of [`RoomTemplate::layout`] stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateCell {
+    Ground(GrassTileType),
+    Obstacle(Obstacle),
+    EnemySpawn,
+    BossSpawn,
+    /// An allied NPC who fights alongside the party
+    AllySpawn,
+    Chest(LootTableId),
+}
+
+impl TemplateCell {
+    pub fn from_char(c: char) -> Option<Self> {
+        let cell = match c {
+            '.' => TemplateCell::Ground(GrassTileType::Grass),
+            ',' => TemplateCell::Ground(GrassTileType::DeadGrass),
+            'r' => TemplateCell::Obstacle(Obstacle::Rock1),
+            'b' => TemplateCell::Obstacle(Obstacle::Bush),
+            't' => TemplateCell::Obstacle(Obstacle::Tree),
+            'u' => TemplateCell::Obstacle(Obstacle::Ruin),
+            'f' => TemplateCell::Obstacle(Obstacle::Fence),
+            'E' => TemplateCell::EnemySpawn,
+            'B' => TemplateCell::BossSpawn,
+            'A' => TemplateCell::AllySpawn,
+            'C' => TemplateCell::Chest(LootTableId::Common),
+            '$' => TemplateCell::Chest(LootTableId::Rare),
+            _ => return None,
+        };
+        Some(cell)
+    }
+
+    pub fn to_char(&self) -> char {
+        match self {
+            TemplateCell::Ground(GrassTileType::Grass) => '.',
+            TemplateCell::Ground(GrassTileType::DeadGrass) => ',',
+            TemplateCell::Obstacle(Obstacle::Rock1 | Obstacle::Rock2) => 'r',
+            TemplateCell::Obstacle(Obstacle::Bush) => 'b',
+            TemplateCell::Obstacle(Obstacle::Tree) => 't',
+            TemplateCell::Obstacle(Obstacle::Ruin) => 'u',
+            TemplateCell::Obstacle(Obstacle::Fence) => 'f',
+            TemplateCell::EnemySpawn => 'E',
+            TemplateCell::BossSpawn => 'B',
+            TemplateCell::AllySpawn => 'A',
+            TemplateCell::Chest(LootTableId::Common) => 'C',
+            TemplateCell::Chest(LootTableId::Rare) => '$',
+        }
+    }
+}
+
+impl RoomTemplate {
+    /// Every cell of the layout along with where it is in game space
+    pub fn cells(&self) -> impl Iterator<Item = (GridPosition, TemplateCell)> + '_ {
+        self.layout.iter().enumerate().flat_map(|(x, row)| {
+            row.chars().enumerate().filter_map(move |(y, c)| {
+                let position = GridPosition {
+                    x: x as u32,
+                    y: y as u32,
+                };
+                TemplateCell::from_char(c).map(|cell| (position, cell))
+            })
+        })
+    }
+
+    /// The cell at `position`, in game space
+    pub fn cell(&self, position: GridPosition) -> Option<TemplateCell> {
+        self.layout
+            .get(position.x as usize)?
+            .chars()
+            .nth(position.y as usize)
+            .and_then(TemplateCell::from_char)
+    }
+
+    /// Swaps out the cell at `position`, in game space
+    pub fn paint(&mut self, position: GridPosition, cell: TemplateCell) {
+        let Some(row) = self.layout.get_mut(position.x as usize) else {
+            return;
+        };
+        *row = row
+            .chars()
+            .enumerate()
+            .map(|(y, c)| {
+                if y == position.y as usize {
+                    cell.to_char()
+                } else {
+                    c
+                }
+            })
+            .collect();
+    }
+
+    /// Where the party starts out, just off the entrance bridge
+    pub fn player_start_locations(&self) -> [GridPosition; 4] {
+        [
+            GridPosition {
+                x: 0,
+                y: self.entrance,
+            },
+            GridPosition {
+                x: 0,
+                y: self.entrance + 1,
+            },
+            GridPosition {
+                x: 1,
+                y: self.entrance,
+            },
+            GridPosition {
+                x: 1,
+                y: self.entrance + 1,
+            },
+        ]
+    }
+
+    /// The squares in front of the exit bridge, which have to be left open
+    pub fn exit_locations(&self) -> Vec<GridPosition> {
+        let Some(exit) = self.exit else {
+            return Vec::new();
+        };
+
+        let last_row = TEMPLATE_SIZE - 1;
+        vec![
+            GridPosition {
+                x: last_row,
+                y: exit,
+            },
+            GridPosition {
+                x: last_row,
+                y: exit + 1,
+            },
+            GridPosition {
+                x: last_row - 1,
+                y: exit,
+            },
+            GridPosition {
+                x: last_row - 1,
+                y: exit + 1,
+            },
+        ]
+    }
+
+    /// Whatever is wrong with the template, if anything
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.layout.len() != TEMPLATE_SIZE as usize {
+            anyhow::bail!(
+                "{} has {} rows instead of {}",
+                self.name,
+                self.layout.len(),
+                TEMPLATE_SIZE
+            );
+        }
+
+        for (x, row) in self.layout.iter().enumerate() {
+            if row.chars().count() != TEMPLATE_SIZE as usize {
+                anyhow::bail!("{} row {} isn't {} wide", self.name, x, TEMPLATE_SIZE);
+            }
+            if let Some(c) = row.chars().find(|t| TemplateCell::from_char(*t).is_none()) {
+                anyhow::bail!("{} row {} has an unknown character {:?}", self.name, x, c);
+            }
+        }
+
+        let bridge_fits = |t: u32| t + 1 < TEMPLATE_SIZE;
+        if !bridge_fits(self.entrance) || !self.exit.is_none_or(bridge_fits) {
+            anyhow::bail!("{} has a bridge hanging off the edge", self.name);
+        }
+
+        match (&self.room_type, self.exit) {
+            (RoomType::Standard, None) => anyhow::bail!("{} has no way out", self.name),
+            (RoomType::BossRoom, Some(_)) => anyhow::bail!("{} lets you skip the boss", self.name),
+            _ => {}
+        }
+
+        let cells = self.cells().collect::<Vec<_>>();
+        let open = self
+            .player_start_locations()
+            .into_iter()
+            .chain(self.exit_locations());
+        for position in open {
+            if cells
+                .iter()
+                .any(|(t, cell)| *t == position && !matches!(cell, TemplateCell::Ground(_)))
+            {
+                anyhow::bail!("{} blocks the bridge at {:?}", self.name, position);
+            }
+        }
+
+        let obstacles = cells
+            .iter()
+            .filter_map(|(position, cell)| match cell {
+                TemplateCell::Obstacle(obstacle) => Some((*position, *obstacle)),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+        let reachable = reachable_tiles(
+            (TEMPLATE_SIZE, TEMPLATE_SIZE),
+            &obstacles,
+            &self.player_start_locations(),
+        );
+        if let Some((position, _)) = cells
+            .iter()
+            .find(|(t, cell)| matches!(cell, TemplateCell::Chest(_)) && !reachable.contains(t))
+        {
+            anyhow::bail!(
+                "{} has a chest nobody can get to at {:?}",
+                self.name,
+                position
+            );
+        }
+
+        let spawns = cells
+            .iter()
+            .filter(|(_, cell)| matches!(cell, TemplateCell::EnemySpawn | TemplateCell::BossSpawn))
+            .count();
+        if spawns == 0 {
+            anyhow::bail!("{} has nobody to fight", self.name);
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn raw_templates() -> [&'static str; 3] {
+    [
+        include_str!("../assets/rooms/overgrown_crossing.ron"),
+        include_str!("../assets/rooms/rock_garden.ron"),
+        include_str!("../assets/rooms/boss_arena.ron"),
+    ]
+}
+
+static ROOM_TEMPLATES: LazyLock<Vec<RoomTemplate>> = LazyLock::new(|| {
+    raw_templates()
+        .into_iter()
+        .filter_map(|raw| {
+            let template = ron::from_str::<RoomTemplate>(raw)
+                .map_err(anyhow::Error::from)
+                .and_then(|t| t.validate().map(|_| t));
+            match template {
+                Ok(template) => Some(template),
+                Err(e) => {
+                    bevy::log::error!("Skipping a broken room template: {:?}", e);
+                    None
+                }
+            }
+        })
+        .collect()
+});
+
+/// The templates that can stand in for a room of `room_type`
+pub fn room_templates(room_type: &RoomType) -> Vec<&'static RoomTemplate> {
+    ROOM_TEMPLATES
+        .iter()
+        .filter(|t| t.room_type == *room_type)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_templates_are_all_valid() {
+        for raw in raw_templates() {
+            let template = ron::from_str::<RoomTemplate>(raw).expect("Template should parse");
+            if let Err(e) = template.validate() {
+                panic!("{:?}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_blocked_bridges_are_rejected() {
+        let mut layout = vec![".............".to_string(); TEMPLATE_SIZE as usize];
+        layout[6] = "......E......".to_string();
+        let mut template = RoomTemplate {
+            name: "Test".to_string(),
+            room_type: RoomType::Standard,
+            entrance: 4,
+            exit: Some(8),
+            biome: None,
+            layout,
+        };
+        assert!(template.validate().is_ok());
+
+        template.layout[1] = ".....r.......".to_string();
+        assert!(template.validate().is_err());
+    }
+}
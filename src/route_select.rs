@@ -0,0 +1,186 @@
+//! Picking the next room, in between rooms.
+//!
+//! Once the party leaves a room, every room it leads to on the dungeon map shows up as a card, and
+//! anyone in the party can pick one. When there's only one way forward, it gets taken without
+//! asking.
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::{
+    assets::FontResource,
+    dungeon::{DungeonManager, DungeonState, RoomId},
+    loading::NextRoomPreload,
+    localization::localized_text,
+    menu::{
+        menu_navigation::{
+            ActiveMenu, GameMenuController, GameMenuGrid, GameMenuLatch,
+            handle_menu_cursor_navigation, highlight_menu_option,
+        },
+        ui_consts::{SELECTABLE_BUTTON_BACKGROUND, UI_MENU_BACKGROUND, UI_TEXT_COLOR},
+    },
+    player::RegisteredBattlePlayers,
+};
+
+/// A room the party can head to next
+#[derive(Component)]
+pub struct RouteChoice(pub RoomId);
+
+pub fn route_select_plugin(app: &mut App) {
+    app.add_systems(OnEnter(DungeonState::ChooseRoute), open_route_select)
+        .add_systems(
+            Update,
+            (handle_menu_cursor_navigation, highlight_menu_option)
+                .run_if(in_state(DungeonState::ChooseRoute)),
+        )
+        .add_observer(pick_route);
+}
+
+fn open_route_select(
+    mut commands: Commands,
+    fonts: Res<FontResource>,
+    mut dungeon_manager: ResMut<DungeonManager>,
+    registered_players: Res<RegisteredBattlePlayers>,
+    asset_server: Res<AssetServer>,
+    preload: Res<NextRoomPreload>,
+    mut next_state: ResMut<NextState<DungeonState>>,
+) {
+    let next_rooms = dungeon_manager.next_rooms().to_vec();
+    match next_rooms.as_slice() {
+        [] => {
+            error!(
+                "Room {:?} doesn't lead anywhere",
+                dungeon_manager.current_room
+            );
+            return;
+        }
+        [only] => {
+            dungeon_manager.current_room = *only;
+            next_state.set(preload.next_state(&asset_server));
+            return;
+        }
+        _ => {}
+    }
+
+    let label_font = TextFont {
+        font: fonts.pixelify_sans_medium.clone(),
+        font_size: 36.,
+        ..Default::default()
+    };
+    let description_font = TextFont {
+        font: fonts.pixelify_sans_regular.clone(),
+        font_size: 22.,
+        ..Default::default()
+    };
+
+    let cards = next_rooms
+        .iter()
+        .filter_map(|room_id| {
+            let route = dungeon_manager.route(*room_id)?;
+            let card = commands
+                .spawn((
+                    Name::new(format!("RouteChoice {:?}", room_id)),
+                    Button,
+                    Node {
+                        width: px(260),
+                        height: px(200),
+                        flex_direction: FlexDirection::Column,
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        row_gap: px(12),
+                        padding: UiRect::all(px(16)),
+                        border: UiRect::all(px(2)),
+                        border_radius: BorderRadius::all(percent(10)),
+                        ..Default::default()
+                    },
+                    BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+                    RouteChoice(*room_id),
+                    children![
+                        (
+                            localized_text(route.kind.label_key()),
+                            label_font.clone(),
+                            TextColor(UI_TEXT_COLOR),
+                        ),
+                        (
+                            localized_text(route.kind.description_key()),
+                            description_font.clone(),
+                            TextColor(UI_TEXT_COLOR),
+                            TextLayout::new_with_justify(Justify::Center),
+                        ),
+                    ],
+                ))
+                .id();
+            Some(card)
+        })
+        .collect::<Vec<_>>();
+
+    let mut grid = GameMenuGrid::new_with_width(cards.len() as u8);
+    grid.push_buttons_in_rows(&cards);
+
+    let card_row = commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Row,
+                column_gap: px(32),
+                ..Default::default()
+            },
+            grid,
+            GameMenuController {
+                players: registered_players
+                    .save_files
+                    .keys()
+                    .copied()
+                    .collect::<HashSet<_>>(),
+            },
+            GameMenuLatch::default(),
+            ActiveMenu {},
+        ))
+        .add_children(&cards)
+        .id();
+
+    commands
+        .spawn((
+            Name::new("RouteSelect"),
+            Node {
+                width: percent(100),
+                height: percent(100),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: px(40),
+                ..Default::default()
+            },
+            BackgroundColor(UI_MENU_BACKGROUND),
+            DespawnOnExit(DungeonState::ChooseRoute),
+            children![(
+                localized_text("route.title"),
+                TextFont {
+                    font: fonts.pixelify_sans_medium.clone(),
+                    font_size: 55.,
+                    ..Default::default()
+                },
+                TextColor(UI_TEXT_COLOR),
+            )],
+        ))
+        .add_child(card_row);
+}
+
+fn pick_route(
+    mut click: On<Pointer<Click>>,
+    choices: Query<&RouteChoice>,
+    dungeon_manager: Option<ResMut<DungeonManager>>,
+    asset_server: Res<AssetServer>,
+    preload: Res<NextRoomPreload>,
+    mut next_state: ResMut<NextState<DungeonState>>,
+) {
+    let (Ok(choice), Some(mut dungeon_manager)) = (choices.get(click.entity), dungeon_manager)
+    else {
+        return;
+    };
+    click.propagate(false);
+
+    info!("Heading to room {:?}", choice.0);
+    dungeon_manager.current_room = choice.0;
+    next_state.set(preload.next_state(&asset_server));
+}
@@ -0,0 +1,207 @@
+//! Controller rumble for the things a player should feel.
+//!
+//! Players on a gamepad get a buzz when one of their units gets hit, when one of their units lands
+//! a big hit, and when their phase begins. Keyboard players have nothing to rumble, so they get
+//! skipped. Rumble can be turned off in the settings menu.
+
+use std::time::Duration;
+
+use bevy::{
+    input::gamepad::{GamepadRumbleIntensity, GamepadRumbleRequest},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    battle_log::BattleLogMessage,
+    battle_phase::{PlayerEnemyPhase, TurnQueue, TurnStartMessage, has_turn},
+    dungeon::DungeonState,
+    join_game_menu::{JoinedPlayers, PlayerController},
+    player::Player,
+    tr,
+    unit::Unit,
+    unit_stats::{StatType, UnitDerivedStats},
+};
+
+/// Hits that take at least this much of the target's max health count as big hits
+const BIG_HIT_THRESHOLD: f32 = 0.4;
+
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct RumbleSettings {
+    pub enabled: bool,
+}
+
+impl Default for RumbleSettings {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+impl RumbleSettings {
+    pub fn text(enabled: bool) -> String {
+        if enabled {
+            tr!("settings.rumble.on")
+        } else {
+            tr!("settings.rumble.off")
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RumbleKind {
+    /// One of the player's units got hurt. `severity` is how much of its max health it lost.
+    TookHit {
+        severity: f32,
+    },
+    /// One of the player's units took a big chunk out of something.
+    /// There aren't critical hits yet, so this is the closest thing.
+    LandedBigHit {
+        severity: f32,
+    },
+    PhaseBegin,
+}
+
+impl RumbleKind {
+    /// Harder hits rumble harder and longer. The square root makes small hits still noticeable.
+    pub fn intensity(&self) -> (GamepadRumbleIntensity, Duration) {
+        match self {
+            RumbleKind::TookHit { severity } => {
+                let severity = severity.clamp(0.0, 1.0);
+                (
+                    GamepadRumbleIntensity {
+                        strong_motor: 0.2 + 0.8 * severity.sqrt(),
+                        weak_motor: 0.3,
+                    },
+                    Duration::from_millis(150 + (250. * severity) as u64),
+                )
+            }
+            RumbleKind::LandedBigHit { severity } => (
+                GamepadRumbleIntensity::weak_motor(0.4 + 0.6 * severity.clamp(0.0, 1.0)),
+                Duration::from_millis(180),
+            ),
+            RumbleKind::PhaseBegin => (
+                GamepadRumbleIntensity::weak_motor(0.3),
+                Duration::from_millis(120),
+            ),
+        }
+    }
+}
+
+pub fn rumble_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (rumble_on_hits, rumble_on_phase_begin).run_if(in_state(DungeonState::InBattle)),
+    );
+}
+
+fn gamepad_for(joined_players: &JoinedPlayers, player: &Player) -> Option<Entity> {
+    match joined_players.0.get(player)?.controller() {
+        PlayerController::Gamepad(gamepad) => Some(gamepad),
+        PlayerController::Keyboard => None,
+    }
+}
+
+fn rumble(writer: &mut MessageWriter<GamepadRumbleRequest>, gamepad: Entity, kind: RumbleKind) {
+    let (intensity, duration) = kind.intensity();
+    writer.write(GamepadRumbleRequest::Add {
+        gamepad,
+        intensity,
+        duration,
+    });
+}
+
+pub fn rumble_on_hits(
+    settings: Res<RumbleSettings>,
+    joined_players: Option<Res<JoinedPlayers>>,
+    mut reader: MessageReader<BattleLogMessage>,
+    units: Query<(Option<&Player>, &UnitDerivedStats), With<Unit>>,
+    mut writer: MessageWriter<GamepadRumbleRequest>,
+) {
+    let Some(joined_players) = joined_players.filter(|_| settings.enabled) else {
+        reader.clear();
+        return;
+    };
+
+    for message in reader.read() {
+        let BattleLogMessage::SkillImpact {
+            attacker,
+            defender,
+            health_change,
+            ..
+        } = message
+        else {
+            continue;
+        };
+
+        if *health_change >= 0 {
+            continue;
+        }
+
+        let Ok((defender_player, defender_stats)) = units.get(*defender) else {
+            continue;
+        };
+        let max_health = defender_stats.stats.stat(StatType::MaxHealth).0.max(1.);
+        let severity = health_change.unsigned_abs() as f32 / max_health;
+
+        // Enemies and allies don't have a Player, so there's nobody to rumble for them
+        if let Some(gamepad) = defender_player.and_then(|t| gamepad_for(&joined_players, t)) {
+            rumble(&mut writer, gamepad, RumbleKind::TookHit { severity });
+        }
+
+        if severity >= BIG_HIT_THRESHOLD
+            && let Some(attacker_player) = attacker.and_then(|t| units.get(t).ok()?.0)
+            && let Some(gamepad) = gamepad_for(&joined_players, attacker_player)
+        {
+            rumble(&mut writer, gamepad, RumbleKind::LandedBigHit { severity });
+        }
+    }
+}
+
+/// Lets everyone with a unit up this phase know it's their go
+pub fn rumble_on_phase_begin(
+    settings: Res<RumbleSettings>,
+    joined_players: Option<Res<JoinedPlayers>>,
+    turn_queue: Option<Res<TurnQueue>>,
+    mut reader: MessageReader<TurnStartMessage>,
+    units: Query<(Entity, &Player), With<Unit>>,
+    mut writer: MessageWriter<GamepadRumbleRequest>,
+) {
+    let Some(joined_players) = joined_players.filter(|_| settings.enabled) else {
+        reader.clear();
+        return;
+    };
+
+    for message in reader.read() {
+        if message.phase != PlayerEnemyPhase::Player {
+            continue;
+        }
+
+        let mut players = units
+            .iter()
+            .filter(|(e, _)| has_turn(turn_queue.as_deref(), *e))
+            .map(|(_, player)| *player)
+            .collect::<Vec<_>>();
+        players.sort_by_key(|t| t.id());
+        players.dedup();
+
+        for player in players {
+            if let Some(gamepad) = gamepad_for(&joined_players, &player) {
+                rumble(&mut writer, gamepad, RumbleKind::PhaseBegin);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_harder_hits_rumble_harder() {
+        let (light, light_duration) = RumbleKind::TookHit { severity: 0.1 }.intensity();
+        let (heavy, heavy_duration) = RumbleKind::TookHit { severity: 0.9 }.intensity();
+        assert!(heavy.strong_motor > light.strong_motor);
+        assert!(heavy_duration > light_duration);
+        assert!(heavy.strong_motor <= 1.0);
+    }
+}
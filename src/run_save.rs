@@ -0,0 +1,586 @@
+//! Saving a dungeon run so it survives quitting the game.
+//!
+//! The run gets saved to the [`ProfileStore`] at the start of every turn, before anything that
+//! happens on that turn, and checkpointed on the way between rooms. Each run has its own id, and
+//! only the latest run can be continued, so starting a new one gives up on the last.
+//!
+//! Rooms are generated from the dungeon seed, so the seed plus the room number gets the map, the
+//! obstacles and the turn events back, and only the units need saving on top of that. Their level
+//! ups are seeded too, so replaying them puts the growth RNG back where it was (see
+//! [`UnitLevelManager::restore`]).
+//!
+//! "Continue Run" on the main menu goes through the join screen like a new run, since the
+//! controllers need picking again. Saved characters that nobody picks stay in the party as
+//! orphaned units. Once the room loads, the units are put back how they were and the battle
+//! starts over on the saved turn, replaying that turn's events.
+//!
+//! Picking up from a checkpoint between rooms just starts the next room fresh.
+//!
+//! The save gets deleted once the run is over, whether it was won or conceded. Anything done
+//! partway through a turn is lost, and so are opened chests, though not what was found in them.
+
+use bevy::prelude::*;
+use rand::distr::{Alphanumeric, SampleString};
+
+use crate::{
+    GameState,
+    animation::{Direction, FacingDirection, TinytacticsAssets, animation_db::AnimationDB},
+    battle::BattleResultResource,
+    battle_phase::{TurnAdvancedMessage, UnitPhaseResources},
+    difficulty::{Difficulty, DungeonConfig},
+    dungeon::{DungeonManager, DungeonState},
+    gameplay_effects::{ActiveEffects, Effect, EffectData, EffectDuration, EffectMetadata},
+    grid::GridPosition,
+    inventory::PartyInventory,
+    loot::RunLoot,
+    map_generation::DungeonGenerationParams,
+    player::RegisteredBattlePlayers,
+    profile::ProfileStore,
+    run_stats::RunStats,
+    save_game::{SaveFileKey, UnitSaveV2},
+    turn_events::{TurnEventSchedule, reinforcement_name, spawn_reinforcement},
+    unit::Unit,
+    unit_stats::{
+        StatContainer, StatsDirty, UnitBaseStats, UnitDerivedStats, experience::UnitLevelManager,
+    },
+};
+
+/// Where runs used to be saved, before each run had its own id
+pub const LEGACY_RUN_SAVE_KEY: &str = "run-save";
+
+/// Holds the [`RunId`] of the run that can be continued
+pub const LATEST_RUN_KEY: &str = "latest-run";
+
+/// Where a run gets saved in the [`ProfileStore`]
+pub fn run_save_key(run_id: &str) -> String {
+    format!("run-save-{}", run_id)
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[serde(tag = "version")]
+pub enum RunSave {
+    V1(RunSaveV1),
+    V2(RunSaveV2),
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct RunSaveV1 {
+    pub seed: String,
+    pub room: u32,
+    pub turn: u32,
+    /// The characters in the party, not counting filler units
+    pub party: Vec<UnitSaveV2>,
+    pub units: Vec<SavedUnit>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct RunSaveV2 {
+    pub run_id: String,
+    pub seed: String,
+    pub room: u32,
+    /// The characters in the party, not counting filler units
+    pub party: Vec<UnitSaveV2>,
+    /// Only there when the run was saved partway through a battle, rather than between rooms
+    pub battle: Option<BattleSave>,
+    #[serde(default)]
+    pub stats: RunStats,
+    #[serde(default)]
+    pub loot: RunLoot,
+    #[serde(default)]
+    pub inventory: PartyInventory,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+}
+
+impl RunSaveV2 {
+    /// The turn the battle picks back up on
+    pub fn turn(&self) -> u32 {
+        self.battle.as_ref().map(|t| t.turn).unwrap_or(1)
+    }
+
+    /// Hands the party member with `uid` a new key, wherever they show up in the save
+    pub fn rekey_character(&mut self, uid: u32, key: SaveFileKey) {
+        for unit in self.party.iter_mut().filter(|t| t.save_file_key.uid == uid) {
+            unit.save_file_key = key.clone();
+        }
+        for character in self.stats.characters.iter_mut().filter(|t| t.uid == uid) {
+            character.uid = key.uid;
+            character.name = key.name.clone();
+        }
+
+        let Some(battle) = &mut self.battle else {
+            return;
+        };
+        for unit in battle
+            .units
+            .iter_mut()
+            .filter(|t| t.key == SavedUnitKey::Character(uid))
+        {
+            unit.key = SavedUnitKey::Character(key.uid);
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct BattleSave {
+    pub turn: u32,
+    pub units: Vec<SavedUnit>,
+}
+
+/// V1 saves were always taken mid battle, and there was only ever one of them
+impl From<RunSaveV1> for RunSaveV2 {
+    fn from(value: RunSaveV1) -> Self {
+        RunSaveV2 {
+            run_id: "legacy".to_string(),
+            seed: value.seed,
+            room: value.room,
+            party: value.party,
+            battle: Some(BattleSave {
+                turn: value.turn,
+                units: value.units,
+            }),
+            stats: RunStats::default(),
+            loot: RunLoot::default(),
+            inventory: PartyInventory::default(),
+            difficulty: Difficulty::default(),
+        }
+    }
+}
+
+pub fn upgrade_run_save_to_latest(save: RunSave) -> RunSaveV2 {
+    match save {
+        RunSave::V1(v1) => v1.into(),
+        RunSave::V2(v2) => v2,
+    }
+}
+
+/// Identifies the run that's underway, so its saves don't get mixed up with any other run's
+#[derive(Resource, Debug, Clone)]
+pub struct RunId(pub String);
+
+/// How a saved unit gets matched back up with a unit in the room
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub enum SavedUnitKey {
+    /// A character from a save file, by uid
+    Character(u32),
+    /// Everyone else, by name. Unit names in a room are unique.
+    Named(String),
+}
+
+impl SavedUnitKey {
+    fn of(unit: &Unit, save_file_key: Option<&SaveFileKey>) -> Self {
+        match save_file_key {
+            Some(key) => SavedUnitKey::Character(key.uid),
+            None => SavedUnitKey::Named(unit.name.clone()),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct SavedUnit {
+    pub key: SavedUnitKey,
+    pub position: GridPosition,
+    pub facing: Direction,
+    pub base_stats: StatContainer,
+    pub derived_stats: StatContainer,
+    pub phase_resources: UnitPhaseResources,
+    /// Permanent effects come from equipment and passives, which get set up again on their own
+    pub effects: Vec<EffectData>,
+    pub level: Option<(u32, f32)>,
+}
+
+/// The run that's being picked back up. Gets removed once the units are restored.
+#[derive(Resource, Debug)]
+pub struct PendingRunRestore(pub RunSaveV2);
+
+/// The latest save of the run that can be continued, if there is one. A save that can't be read
+/// gets swapped for its backup, or given up on.
+pub fn load_run_save(pkv: &mut ProfileStore) -> Option<RunSaveV2> {
+    let key = match pkv.get_or_recover::<String>(LATEST_RUN_KEY) {
+        Some(run_id) => run_save_key(&run_id),
+        None => LEGACY_RUN_SAVE_KEY.to_string(),
+    };
+
+    pkv.get_or_recover::<RunSave>(&key)
+        .map(upgrade_run_save_to_latest)
+}
+
+/// Saves `save` as the latest save of its run
+pub fn write_run_save(pkv: &mut ProfileStore, save: RunSaveV2) {
+    // Only one run can be continued, so there's no point hanging on to the last one
+    if let Some(previous) = load_run_save(pkv)
+        && previous.run_id != save.run_id
+    {
+        delete_saves(pkv);
+    }
+
+    let result = pkv
+        .set(run_save_key(&save.run_id), &RunSave::V2(save.clone()))
+        .and_then(|_| pkv.set(LATEST_RUN_KEY, &save.run_id));
+    match result {
+        Ok(()) => info!(
+            "Saved run {} in room {} on turn {}",
+            save.run_id,
+            save.room,
+            save.turn()
+        ),
+        Err(e) => error!("Failed to save the run: {:?}", e),
+    }
+}
+
+/// Gets rid of the latest run's save, so there's nothing to continue
+fn delete_saves(pkv: &mut ProfileStore) {
+    let mut keys = vec![LATEST_RUN_KEY.to_string(), LEGACY_RUN_SAVE_KEY.to_string()];
+    if let Ok(run_id) = pkv.get::<String>(LATEST_RUN_KEY) {
+        keys.push(run_save_key(&run_id));
+    }
+
+    for key in keys {
+        // Most of these won't be there, which is fine
+        let _ = pkv.remove(&key);
+    }
+}
+
+pub fn run_save_plugin(app: &mut App) {
+    app.add_systems(OnEnter(GameState::MainMenu), clear_pending_restore)
+        .add_systems(OnEnter(GameState::Dungeon), (init_run_id, restore_party))
+        .add_systems(OnExit(DungeonState::ChooseRoute), checkpoint_run)
+        .add_systems(OnEnter(GameState::BattleResolution), delete_run_save);
+}
+
+/// Backing out to the main menu gives up on continuing
+fn clear_pending_restore(mut commands: Commands) {
+    commands.remove_resource::<PendingRunRestore>();
+}
+
+/// A continued run keeps its id, anything else is a new run
+fn init_run_id(mut commands: Commands, pending_restore: Option<Res<PendingRunRestore>>) {
+    let run_id = match pending_restore {
+        Some(restore) => restore.0.run_id.clone(),
+        None => Alphanumeric.sample_string(&mut rand::rng(), 8),
+    };
+    info!("Starting run {}", run_id);
+    commands.insert_resource(RunId(run_id));
+}
+
+/// The run is over one way or another
+fn delete_run_save(mut pkv: ProfileStore) {
+    delete_saves(&mut pkv);
+}
+
+/// The characters in the party, not counting filler units
+fn party(registered_players: &RegisteredBattlePlayers) -> Vec<UnitSaveV2> {
+    registered_players
+        .save_files
+        .values()
+        .chain(registered_players.orphaned_units.iter())
+        .cloned()
+        .collect()
+}
+
+/// Checkpoints the run on the way into the next room
+#[allow(clippy::too_many_arguments)]
+pub fn checkpoint_run(
+    mut pkv: ProfileStore,
+    run_id: Res<RunId>,
+    dungeon_params: Res<DungeonGenerationParams>,
+    dungeon_manager: Res<DungeonManager>,
+    registered_players: Res<RegisteredBattlePlayers>,
+    stats: Res<RunStats>,
+    loot: Res<RunLoot>,
+    inventory: Res<PartyInventory>,
+    config: Res<DungeonConfig>,
+) {
+    write_run_save(
+        &mut pkv,
+        RunSaveV2 {
+            run_id: run_id.0.clone(),
+            seed: dungeon_params.options.seed.clone(),
+            room: dungeon_manager.current_room.0,
+            party: party(&registered_players),
+            battle: None,
+            stats: stats.clone(),
+            loot: loot.clone(),
+            inventory: inventory.clone(),
+            difficulty: config.difficulty,
+        },
+    );
+}
+
+/// Saved characters that nobody picked in the join screen come along as orphaned units
+pub fn restore_party(
+    pending_restore: Option<Res<PendingRunRestore>>,
+    mut registered_players: ResMut<RegisteredBattlePlayers>,
+) {
+    let Some(pending_restore) = pending_restore else {
+        return;
+    };
+
+    for unit in &pending_restore.0.party {
+        if registered_players.owner(&unit.save_file_key).is_none() {
+            info!("{} wasn't picked, orphaning them", unit.save_file_key.name);
+            registered_players.orphaned_units.push(unit.clone());
+        }
+    }
+    registered_players.assign_filler_units();
+}
+
+type SaveableUnit<'a> = (
+    &'a Unit,
+    Option<&'a SaveFileKey>,
+    &'a GridPosition,
+    &'a FacingDirection,
+    &'a UnitBaseStats,
+    &'a UnitDerivedStats,
+    &'a UnitPhaseResources,
+    Option<&'a ActiveEffects>,
+    Option<&'a UnitLevelManager>,
+);
+
+/// Saves the run at the start of every turn
+#[allow(clippy::too_many_arguments)]
+pub fn autosave_run(
+    mut reader: MessageReader<TurnAdvancedMessage>,
+    mut pkv: ProfileStore,
+    run_id: Res<RunId>,
+    dungeon_params: Res<DungeonGenerationParams>,
+    dungeon_manager: Res<DungeonManager>,
+    registered_players: Res<RegisteredBattlePlayers>,
+    battle_result: Option<Res<BattleResultResource>>,
+    stats: Res<RunStats>,
+    loot: Res<RunLoot>,
+    inventory: Res<PartyInventory>,
+    config: Res<DungeonConfig>,
+    units: Query<SaveableUnit>,
+) {
+    let Some(turn) = reader.read().map(|t| t.turn).last() else {
+        return;
+    };
+
+    // Nothing left to continue
+    if battle_result.is_some() {
+        return;
+    }
+
+    let units = units
+        .iter()
+        .map(
+            |(unit, key, position, facing, base, derived, resources, effects, level)| SavedUnit {
+                key: SavedUnitKey::of(unit, key),
+                position: *position,
+                facing: facing.0,
+                base_stats: base.stats.clone(),
+                derived_stats: derived.stats.clone(),
+                phase_resources: resources.clone(),
+                effects: effects
+                    .map(|t| {
+                        t.effects
+                            .iter()
+                            .filter(|t| !matches!(t.data.duration, EffectDuration::Permanent))
+                            .map(|t| t.data.clone())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                level: level.map(|t| (t.level(), t.experience())),
+            },
+        )
+        .collect();
+
+    write_run_save(
+        &mut pkv,
+        RunSaveV2 {
+            run_id: run_id.0.clone(),
+            seed: dungeon_params.options.seed.clone(),
+            room: dungeon_manager.current_room.0,
+            party: party(&registered_players),
+            battle: Some(BattleSave { turn, units }),
+            stats: stats.clone(),
+            loot: loot.clone(),
+            inventory: inventory.clone(),
+            difficulty: config.difficulty,
+        },
+    );
+}
+
+/// Reinforcements that showed up before the saved turn aren't part of the room, so they need
+/// bringing back. Ones that never made it onto the map stay gone.
+pub fn respawn_saved_reinforcements(
+    mut commands: Commands,
+    pending_restore: Option<Res<PendingRunRestore>>,
+    schedule: Option<Res<TurnEventSchedule>>,
+    tt_assets: Res<TinytacticsAssets>,
+    anim_db: Res<AnimationDB>,
+) {
+    let (Some(pending_restore), Some(schedule)) = (pending_restore, schedule) else {
+        return;
+    };
+    let Some(battle) = &pending_restore.0.battle else {
+        return;
+    };
+
+    for positions in schedule.reinforcements_before(battle.turn) {
+        for (i, position) in positions.iter().enumerate() {
+            let key = SavedUnitKey::Named(reinforcement_name(i));
+            if battle.units.iter().any(|t| t.key == key) {
+                spawn_reinforcement(&mut commands, i, *position, &tt_assets, &anim_db);
+            }
+        }
+    }
+}
+
+type RestorableUnit<'a> = (
+    Entity,
+    &'a Unit,
+    Option<&'a SaveFileKey>,
+    &'a mut GridPosition,
+    &'a mut FacingDirection,
+    &'a mut UnitBaseStats,
+    &'a mut UnitDerivedStats,
+    &'a mut UnitPhaseResources,
+    Option<&'a mut ActiveEffects>,
+    Option<&'a mut UnitLevelManager>,
+);
+
+/// Puts every unit back how it was saved, and finishes picking the run back up
+pub fn restore_saved_units(
+    mut commands: Commands,
+    pending_restore: Option<Res<PendingRunRestore>>,
+    mut units: Query<RestorableUnit>,
+) {
+    let Some(pending_restore) = pending_restore else {
+        return;
+    };
+    // Checkpoints between rooms start the room fresh
+    let Some(battle) = &pending_restore.0.battle else {
+        info!("Picked the run back up in room {}", pending_restore.0.room);
+        commands.remove_resource::<PendingRunRestore>();
+        return;
+    };
+
+    for (
+        entity,
+        unit,
+        key,
+        mut position,
+        mut facing,
+        mut base,
+        mut derived,
+        mut resources,
+        effects,
+        level,
+    ) in units.iter_mut()
+    {
+        let key = SavedUnitKey::of(unit, key);
+        let Some(saved) = battle.units.iter().find(|t| t.key == key) else {
+            // Someone new joined the party since the save
+            warn!("{} isn't in the run save", unit.name);
+            continue;
+        };
+
+        if *position != saved.position {
+            *position = saved.position;
+        }
+        facing.0 = saved.facing;
+        base.stats = saved.base_stats.clone();
+        derived.stats = saved.derived_stats.clone();
+        *resources = saved.phase_resources.clone();
+
+        if let Some(mut effects) = effects {
+            effects
+                .effects
+                .retain(|t| matches!(t.data.duration, EffectDuration::Permanent));
+            for data in &saved.effects {
+                effects.apply_effect(Effect {
+                    metadata: EffectMetadata {
+                        target: entity,
+                        source: None,
+                    },
+                    data: data.clone(),
+                });
+            }
+            commands.entity(entity).insert(StatsDirty);
+        }
+
+        if let (Some(mut level), Some((saved_level, experience))) = (level, saved.level) {
+            level.restore(saved_level, experience);
+        }
+    }
+
+    info!("Picked the run back up on turn {}", battle.turn);
+    commands.remove_resource::<PendingRunRestore>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v1_saves_upgrade_to_a_mid_battle_save() {
+        let json = r#"{"version":"V1","seed":"abc","room":2,"turn":4,"party":[],"units":[]}"#;
+        let save = upgrade_run_save_to_latest(serde_json::from_str(json).unwrap());
+
+        assert_eq!(save.seed, "abc");
+        assert_eq!(save.room, 2);
+        assert_eq!(save.turn(), 4);
+        assert!(save.battle.is_some());
+    }
+
+    #[test]
+    fn test_rekeying_a_character_follows_them_into_the_battle() {
+        let key = |uid| SaveFileKey {
+            uid,
+            name: "Brandon".to_string(),
+            color: crate::save_game::SaveFileColor::Red,
+        };
+        let unit = |key| SavedUnit {
+            key,
+            position: GridPosition { x: 0, y: 0 },
+            facing: Direction::SE,
+            base_stats: StatContainer::default(),
+            derived_stats: StatContainer::default(),
+            phase_resources: UnitPhaseResources::default(),
+            effects: Vec::new(),
+            level: None,
+        };
+
+        let mut save = RunSaveV2 {
+            run_id: "run".to_string(),
+            seed: "abc".to_string(),
+            room: 1,
+            party: vec![UnitSaveV2::new(key(1), crate::unit::jobs::UnitJob::Knight)],
+            battle: Some(BattleSave {
+                turn: 2,
+                units: vec![
+                    unit(SavedUnitKey::Character(1)),
+                    unit(SavedUnitKey::Named("Goblin".to_string())),
+                ],
+            }),
+            stats: RunStats::default(),
+            loot: RunLoot::default(),
+            inventory: PartyInventory::default(),
+            difficulty: Difficulty::default(),
+        };
+        save.rekey_character(1, key(9));
+
+        assert_eq!(save.party[0].save_file_key, key(9));
+        let units = save.battle.unwrap().units;
+        assert_eq!(units[0].key, SavedUnitKey::Character(9));
+        assert_eq!(units[1].key, SavedUnitKey::Named("Goblin".to_string()));
+    }
+
+    #[test]
+    fn test_checkpoints_start_the_room_on_the_first_turn() {
+        let save = RunSaveV2 {
+            run_id: "run".to_string(),
+            seed: "abc".to_string(),
+            room: 1,
+            party: Vec::new(),
+            battle: None,
+            stats: RunStats::default(),
+            loot: RunLoot::default(),
+            inventory: PartyInventory::default(),
+            difficulty: Difficulty::default(),
+        };
+        assert_eq!(save.turn(), 1);
+    }
+}
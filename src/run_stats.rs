@@ -0,0 +1,402 @@
+//! Keeping score over a dungeon run, and remembering how past runs went.
+//!
+//! [`RunStats`] gets built up by watching the combat messages as the run goes, and rides along in
+//! the run save so continuing a run doesn't lose it. Once the run is over, it's added to the
+//! profile's [`RunHistory`], which the History screen on the main menu lists out.
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::{
+    GameState,
+    assets::FontResource,
+    battle::{BattleEndCondition, BattleResultResource, Enemy},
+    battle_log::BattleLogMessage,
+    battle_phase::TurnAdvancedMessage,
+    combat::UnitHealthChangedEvent,
+    dungeon::{DungeonManager, DungeonState},
+    localization::localized_text,
+    main_menu::MainMenuMarker,
+    map_generation::DungeonGenerationParams,
+    menu::{
+        MenuCleanup,
+        menu_navigation::{self, GameMenuGrid, GameMenuLatch},
+        ui_consts::{SELECTABLE_BUTTON_BACKGROUND, UI_MENU_BACKGROUND, UI_TEXT_COLOR},
+    },
+    player::Player,
+    profile::ProfileStore,
+    run_save::{PendingRunRestore, RunId},
+    save_game::{SaveFileKey, unix_now},
+    tr,
+    unit_stats::UnitDerivedStats,
+};
+
+/// Where a profile keeps its [`RunHistory`]
+pub const RUN_HISTORY_KEY: &str = "run-history";
+
+/// Only hang on to so many runs
+pub const MAX_RUN_HISTORY: usize = 50;
+
+/// Any more than this won't fit on the History screen
+const HISTORY_RUNS_SHOWN: usize = 5;
+
+/// How one character did over the run
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CharacterRunStats {
+    pub uid: u32,
+    pub name: String,
+    pub damage_dealt: u32,
+    pub damage_taken: u32,
+}
+
+/// How the run is going so far
+#[derive(Resource, Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RunStats {
+    pub rooms_cleared: u32,
+    /// The turn each room got up to, by how deep into the run it was
+    pub room_turns: Vec<u32>,
+    pub enemies_defeated: u32,
+    pub characters: Vec<CharacterRunStats>,
+}
+
+impl RunStats {
+    pub fn turns(&self) -> u32 {
+        self.room_turns.iter().sum()
+    }
+
+    /// Replaying a turn after continuing a run sends its turn again, so this sets rather than adds
+    pub fn set_room_turn(&mut self, room: u32, turn: u32) {
+        let room = room as usize;
+        if self.room_turns.len() <= room {
+            self.room_turns.resize(room + 1, 0);
+        }
+        self.room_turns[room] = turn;
+    }
+
+    pub fn character_mut(&mut self, key: &SaveFileKey) -> &mut CharacterRunStats {
+        let index = match self.characters.iter().position(|t| t.uid == key.uid) {
+            Some(index) => index,
+            None => {
+                self.characters.push(CharacterRunStats {
+                    uid: key.uid,
+                    name: key.name.clone(),
+                    ..Default::default()
+                });
+                self.characters.len() - 1
+            }
+        };
+        &mut self.characters[index]
+    }
+}
+
+/// A run that's over, won or lost
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FinishedRun {
+    pub run_id: String,
+    pub seed: String,
+    pub victory: bool,
+    /// Seconds since the Unix epoch
+    pub finished_at: u64,
+    pub stats: RunStats,
+}
+
+/// Every run the profile has finished, newest first
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct RunHistory {
+    pub runs: Vec<FinishedRun>,
+}
+
+impl RunHistory {
+    pub fn push(&mut self, run: FinishedRun) {
+        self.runs.insert(0, run);
+        self.runs.truncate(MAX_RUN_HISTORY);
+    }
+}
+
+pub fn load_run_history(pkv: &mut ProfileStore) -> RunHistory {
+    pkv.get_or_recover::<RunHistory>(RUN_HISTORY_KEY)
+        .unwrap_or_default()
+}
+
+pub fn run_stats_plugin(app: &mut App) {
+    app.add_systems(OnEnter(GameState::Dungeon), init_run_stats)
+        .add_systems(
+            Update,
+            track_run_stats
+                .run_if(in_state(DungeonState::InBattle))
+                .run_if(resource_exists::<RunStats>),
+        )
+        .add_systems(
+            OnEnter(DungeonState::UnloadRoom),
+            count_cleared_room.run_if(resource_exists::<RunStats>),
+        )
+        .add_systems(
+            OnEnter(GameState::BattleResolution),
+            record_finished_run.run_if(resource_exists::<RunStats>),
+        );
+}
+
+/// A continued run picks up its stats where the save left them
+fn init_run_stats(mut commands: Commands, pending_restore: Option<Res<PendingRunRestore>>) {
+    let stats = pending_restore
+        .map(|t| t.0.stats.clone())
+        .unwrap_or_default();
+    commands.insert_resource(stats);
+}
+
+type Combatant<'a> = (Option<&'a SaveFileKey>, &'a UnitDerivedStats, Has<Enemy>);
+
+fn track_run_stats(
+    mut stats: ResMut<RunStats>,
+    dungeon_manager: Res<DungeonManager>,
+    mut turns: MessageReader<TurnAdvancedMessage>,
+    mut impacts: MessageReader<BattleLogMessage>,
+    mut health_changes: MessageReader<UnitHealthChangedEvent>,
+    units: Query<Combatant>,
+) {
+    for message in turns.read() {
+        stats.set_room_turn(dungeon_manager.current_depth(), message.turn);
+    }
+
+    for message in impacts.read() {
+        let BattleLogMessage::SkillImpact {
+            attacker: Some(attacker),
+            health_change,
+            ..
+        } = message
+        else {
+            continue;
+        };
+
+        if *health_change < 0
+            && let Ok((Some(key), _, _)) = units.get(*attacker)
+        {
+            stats.character_mut(key).damage_dealt += health_change.unsigned_abs();
+        }
+    }
+
+    // Goes by the health that actually came off, so it catches hazards too
+    for message in health_changes.read() {
+        if message.health_changed >= 0 {
+            continue;
+        }
+
+        match units.get(message.unit) {
+            Ok((Some(key), _, _)) => {
+                stats.character_mut(key).damage_taken += message.health_changed.unsigned_abs();
+            }
+            Ok((None, derived_stats, true)) if derived_stats.downed() => {
+                stats.enemies_defeated += 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn count_cleared_room(mut stats: ResMut<RunStats>) {
+    stats.rooms_cleared += 1;
+}
+
+fn record_finished_run(
+    mut commands: Commands,
+    mut pkv: ProfileStore,
+    stats: Res<RunStats>,
+    run_id: Res<RunId>,
+    dungeon_params: Res<DungeonGenerationParams>,
+    battle_result: Res<BattleResultResource>,
+) {
+    let victory = battle_result.0.battle_condition == BattleEndCondition::Victory;
+    let mut stats = stats.clone();
+    // Winning the battle clears the room it was in
+    if victory {
+        stats.rooms_cleared += 1;
+    }
+
+    let mut history = load_run_history(&mut pkv);
+    history.push(FinishedRun {
+        run_id: run_id.0.clone(),
+        seed: dungeon_params.options.seed.clone(),
+        victory,
+        finished_at: unix_now(),
+        stats,
+    });
+    match pkv.set(RUN_HISTORY_KEY, &history) {
+        Ok(()) => info!("Recorded run {} in the history", run_id.0),
+        Err(e) => error!("Failed recording run {}: {:?}", run_id.0, e),
+    }
+
+    commands.remove_resource::<RunStats>();
+}
+
+/// The lines describing `run` on the History screen
+fn finished_run_summary(run: &FinishedRun) -> String {
+    let outcome = if run.victory {
+        tr!("history.victory")
+    } else {
+        tr!("history.defeat")
+    };
+
+    let mut lines = vec![
+        tr!("history.run", outcome = outcome, seed = run.seed),
+        tr!(
+            "history.totals",
+            rooms = run.stats.rooms_cleared,
+            turns = run.stats.turns(),
+            enemies = run.stats.enemies_defeated
+        ),
+    ];
+    lines.extend(run.stats.characters.iter().map(|t| {
+        tr!(
+            "history.character",
+            name = t.name,
+            dealt = t.damage_dealt,
+            taken = t.damage_taken
+        )
+    }));
+    lines.join("\n")
+}
+
+/// Lists the profile's latest finished runs, newest first. Each one is a button so the cursor can
+/// move through them, but there's nothing to do with them yet.
+pub fn build_history_menu(
+    commands: &mut Commands,
+    font_resource: &FontResource,
+    history: &RunHistory,
+) -> Entity {
+    let text_font = TextFont {
+        font_size: 20.0,
+        font: font_resource.pixelify_sans_regular.clone(),
+        ..default()
+    };
+
+    let rows = history
+        .runs
+        .iter()
+        .take(HISTORY_RUNS_SHOWN)
+        .map(|run| {
+            commands
+                .spawn((
+                    Button,
+                    Node {
+                        width: percent(90),
+                        margin: UiRect::all(percent(0.5)),
+                        padding: UiRect::all(px(8)),
+                        justify_content: JustifyContent::Center,
+                        border_radius: BorderRadius::all(px(12)),
+                        ..default()
+                    },
+                    BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+                    children![(
+                        Text(finished_run_summary(run)),
+                        text_font.clone(),
+                        TextColor(UI_TEXT_COLOR),
+                    )],
+                ))
+                .id()
+        })
+        .collect::<Vec<_>>();
+
+    let mut history_grid = GameMenuGrid::new_vertical();
+    history_grid.push_buttons_to_stack(&rows);
+
+    let title = commands
+        .spawn((
+            localized_text("history.title"),
+            TextFont {
+                font_size: 40.0,
+                font: font_resource.pixelify_sans_medium.clone(),
+                ..default()
+            },
+            TextColor(UI_TEXT_COLOR),
+            Node {
+                margin: UiRect::all(percent(3)),
+                ..default()
+            },
+        ))
+        .id();
+
+    let empty_text = history.runs.is_empty().then(|| {
+        commands
+            .spawn((
+                Text(tr!("history.empty")),
+                text_font.clone(),
+                TextColor(UI_TEXT_COLOR),
+            ))
+            .id()
+    });
+
+    commands
+        .spawn((
+            Node {
+                display: Display::None,
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                width: percent(50),
+                height: percent(92),
+                border_radius: BorderRadius::all(percent(5)),
+                ..default()
+            },
+            BackgroundColor(UI_MENU_BACKGROUND),
+            history_grid,
+            menu_navigation::GameMenuController {
+                players: HashSet::from([Player::PrePlayer]),
+            },
+            GameMenuLatch::default(),
+            MenuCleanup::Despawn,
+            MainMenuMarker,
+        ))
+        .add_child(title)
+        .add_children(&empty_text.into_iter().chain(rows).collect::<Vec<_>>())
+        .id()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_game::SaveFileColor;
+
+    #[test]
+    fn test_replayed_turns_arent_counted_twice() {
+        let mut stats = RunStats::default();
+        stats.set_room_turn(0, 4);
+        stats.set_room_turn(1, 2);
+        stats.set_room_turn(1, 3);
+        // Continuing the run replays turn 3
+        stats.set_room_turn(1, 3);
+        assert_eq!(stats.turns(), 7);
+    }
+
+    #[test]
+    fn test_characters_are_tracked_by_uid() {
+        let mut stats = RunStats::default();
+        let mut key = SaveFileKey {
+            uid: 3,
+            name: "Brandon".to_string(),
+            color: SaveFileColor::Blue,
+        };
+        stats.character_mut(&key).damage_dealt += 5;
+        key.name = "Renamed".to_string();
+        stats.character_mut(&key).damage_dealt += 2;
+
+        assert_eq!(stats.characters.len(), 1);
+        assert_eq!(stats.characters[0].damage_dealt, 7);
+    }
+
+    #[test]
+    fn test_history_keeps_the_newest_runs() {
+        let mut history = RunHistory::default();
+        for i in 0..MAX_RUN_HISTORY + 2 {
+            history.push(FinishedRun {
+                run_id: i.to_string(),
+                seed: "seed".to_string(),
+                victory: false,
+                finished_at: 0,
+                stats: RunStats::default(),
+            });
+        }
+
+        assert_eq!(history.runs.len(), MAX_RUN_HISTORY);
+        assert_eq!(history.runs[0].run_id, (MAX_RUN_HISTORY + 1).to_string());
+    }
+}
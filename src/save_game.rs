@@ -1,6 +1,16 @@
+use std::collections::{BTreeMap, HashMap};
+
 use bevy::prelude::*;
 
-use crate::unit::jobs::UnitJob;
+use crate::{
+    combat::skills::{SkillId, UnitSkills},
+    equipment::{ItemId, UnitEquipment},
+    localization::localized_text,
+    player::RegisteredBattlePlayers,
+    profile::ProfileStore,
+    unit::{Unit, jobs::UnitJob},
+    unit_stats::{StatContainer, StatType, UnitBaseStats, experience::UnitLevelManager},
+};
 
 #[derive(
     Debug, serde::Serialize, serde::Deserialize, Reflect, Clone, PartialEq, Eq, Hash, Component,
@@ -15,6 +25,54 @@ pub struct SaveFileKey {
 pub struct SaveFiles {
     pub save_file_keys: Vec<SaveFileKey>,
     pub cursor: u32,
+    /// Keyed by uid. Characters from before this was tracked don't have any until they're next
+    /// loaded.
+    #[serde(default)]
+    pub metadata: HashMap<u32, SaveFileMetadata>,
+}
+
+/// What the load screen shows about a character, so it doesn't need to load every save
+#[derive(Debug, serde::Serialize, serde::Deserialize, Reflect, Clone)]
+pub struct SaveFileMetadata {
+    pub job: UnitJob,
+    pub level: u32,
+    pub battles: u32,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    /// Seconds since the Unix epoch
+    pub last_played: u64,
+}
+
+impl SaveFileMetadata {
+    pub fn new(save: &UnitSaveV2) -> Self {
+        let now = unix_now();
+        Self {
+            job: save.job.clone(),
+            level: save.level,
+            battles: 0,
+            created_at: now,
+            last_played: now,
+        }
+    }
+
+    pub fn days_since_played(&self, now: u64) -> u64 {
+        now.saturating_sub(self.last_played) / (60 * 60 * 24)
+    }
+}
+
+/// Seconds since the Unix epoch. `SystemTime` doesn't work on the web.
+pub fn unix_now() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        (js_sys::Date::now() / 1000.) as u64
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|t| t.as_secs())
+            .unwrap_or_default()
+    }
 }
 
 impl SaveFileKey {
@@ -23,15 +81,127 @@ impl SaveFileKey {
     }
 }
 
+impl SaveFiles {
+    /// Adds a new character, with a uid nobody else has had
+    pub fn add(&mut self, name: String, color: SaveFileColor) -> SaveFileKey {
+        self.cursor = self.cursor.overflowing_add(1).0;
+        let key = SaveFileKey {
+            uid: self.cursor,
+            name,
+            color,
+        };
+        self.save_file_keys.push(key.clone());
+        key
+    }
+
+    /// Renames the character with `uid`, returning their updated key
+    pub fn rename(&mut self, uid: u32, name: String) -> Option<SaveFileKey> {
+        let key = self.save_file_keys.iter_mut().find(|t| t.uid == uid)?;
+        key.name = name;
+        Some(key.clone())
+    }
+
+    /// Forgets about the character with `uid`, returning their key
+    pub fn remove(&mut self, uid: u32) -> Option<SaveFileKey> {
+        let index = self.save_file_keys.iter().position(|t| t.uid == uid)?;
+        self.metadata.remove(&uid);
+        Some(self.save_file_keys.remove(index))
+    }
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize, Reflect, Clone)]
 pub struct UnitSaveV1 {
     pub save_file_key: SaveFileKey,
     pub job: UnitJob,
 }
 
-impl From<UnitSaveV1> for UnitSave {
+/// Everything about a character that carries over between runs
+#[derive(Debug, serde::Serialize, serde::Deserialize, Reflect, Clone)]
+pub struct UnitSaveV2 {
+    pub save_file_key: SaveFileKey,
+    pub job: UnitJob,
+    pub base_stats: StatContainer,
+    pub level: u32,
+    pub experience: f32,
+    pub learned_skills: Vec<SkillId>,
+    pub equipped_items: Vec<ItemId>,
+    /// How many battles they've fought side by side with each other character, by uid. See
+    /// [`bonds`](crate::bonds).
+    #[serde(default)]
+    pub bonds: BTreeMap<u32, u32>,
+    /// Went down in the last room of the run, see [`injury`](crate::injury)
+    #[serde(default)]
+    pub injured: bool,
+}
+
+impl UnitSaveV2 {
+    /// A brand new character, starting out how their job does
+    pub fn new(save_file_key: SaveFileKey, job: UnitJob) -> Self {
+        let mut learned_skills = job
+            .base_unit_skills()
+            .learned_skills
+            .into_iter()
+            .collect::<Vec<_>>();
+        learned_skills.sort_by_key(|t| t.0);
+
+        Self {
+            save_file_key,
+            base_stats: job.default_stats(),
+            level: 1,
+            experience: 0.,
+            learned_skills,
+            equipped_items: vec![job.starting_weapon()],
+            bonds: BTreeMap::new(),
+            injured: false,
+            job,
+        }
+    }
+
+    /// This save, caught up with how the character finished a battle. They're back to full health
+    /// by the next one.
+    pub fn with_progression(
+        &self,
+        base_stats: &StatContainer,
+        level: &UnitLevelManager,
+        skills: &UnitSkills,
+        equipment: &UnitEquipment,
+    ) -> Self {
+        let mut base_stats = base_stats.clone();
+        base_stats.with_stat(StatType::Health, base_stats.stat(StatType::MaxHealth));
+
+        let mut learned_skills = skills.learned_skills.iter().copied().collect::<Vec<_>>();
+        learned_skills.sort_by_key(|t| t.0);
+
+        let mut equipped_items = equipment
+            .equipped_items()
+            .map(|(_, item)| item.item_id())
+            .collect::<Vec<_>>();
+        equipped_items.sort_by_key(|t| t.0);
+
+        Self {
+            save_file_key: self.save_file_key.clone(),
+            job: self.job.clone(),
+            base_stats,
+            level: level.level(),
+            experience: level.experience(),
+            learned_skills,
+            equipped_items,
+            bonds: self.bonds.clone(),
+            injured: false,
+        }
+    }
+}
+
+/// V1 saves were made before characters could grow, so they're still how their job starts out
+impl From<UnitSaveV1> for UnitSaveV2 {
     fn from(value: UnitSaveV1) -> Self {
-        UnitSave::V1(value)
+        UnitSaveV2::new(value.save_file_key, value.job)
+    }
+}
+
+impl From<UnitSaveV2> for UnitSave {
+    fn from(value: UnitSaveV2) -> Self {
+        UnitSave::V2(value)
     }
 }
 
@@ -39,11 +209,106 @@ impl From<UnitSaveV1> for UnitSave {
 #[serde(tag = "version")]
 pub enum UnitSave {
     V1(UnitSaveV1),
+    V2(UnitSaveV2),
+}
+
+pub fn upgrade_save_file_to_latest(save_file: UnitSave) -> anyhow::Result<UnitSaveV2> {
+    match save_file {
+        UnitSave::V1(v1) => Ok(v1.into()),
+        UnitSave::V2(v2) => Ok(v2),
+    }
+}
+
+/// Shows how writing everyone's progress back to their save files went
+#[derive(Component)]
+pub struct SaveProgressLabel;
+
+/// After a victory, writes each character's level ups, experience, skills and equipment back to
+/// their save file, so they carry on growing next time. Filler units don't have a save file to
+/// write to.
+///
+/// Waits a frame after the label shows up, so "Saving..." gets a chance to be seen.
+pub fn save_progression(
+    mut commands: Commands,
+    mut pkv: ProfileStore,
+    mut save_files: ResMut<SaveFiles>,
+    registered_players: Res<RegisteredBattlePlayers>,
+    labels: Query<Entity, Added<SaveProgressLabel>>,
+    units: Query<
+        (
+            &SaveFileKey,
+            &UnitBaseStats,
+            &UnitLevelManager,
+            &UnitSkills,
+            &UnitEquipment,
+        ),
+        With<Unit>,
+    >,
+) {
+    if labels.is_empty() {
+        return;
+    }
+
+    let mut saved_everyone = true;
+    for (key, base_stats, level, skills, equipment) in units {
+        if !save_files.save_file_keys.contains(key) {
+            continue;
+        }
+
+        let Some((_, save)) = registered_players
+            .units()
+            .find(|(_, t)| t.save_file_key == *key)
+        else {
+            error!("No save for {:?} in the party?", key);
+            continue;
+        };
+
+        let save = save.with_progression(&base_stats.stats, level, skills, equipment);
+        let job = save.job.clone();
+        match pkv.set(key.pkv_key(), &UnitSave::from(save)) {
+            Ok(()) => {
+                info!("Saved {}'s progress", key.name);
+                if let Some(metadata) = save_files.metadata.get_mut(&key.uid) {
+                    metadata.level = level.level();
+                    metadata.job = job;
+                }
+            }
+            Err(e) => {
+                error!("Failed saving {}'s progress: {:?}", key.name, e);
+                saved_everyone = false;
+            }
+        }
+    }
+
+    let result = if saved_everyone {
+        "battle_resolution.saved"
+    } else {
+        "battle_resolution.save_failed"
+    };
+    for label in labels {
+        commands.entity(label).insert(localized_text(result));
+    }
 }
 
-pub fn upgrade_save_file_to_latest(save_file: UnitSave) -> anyhow::Result<UnitSaveV1> {
-    let UnitSave::V1(v1) = save_file;
-    Ok(v1)
+/// Counts the battle towards everyone in the party who has a save file, win or lose
+pub fn record_battle_played(
+    mut save_files: ResMut<SaveFiles>,
+    registered_players: Res<RegisteredBattlePlayers>,
+) {
+    let now = unix_now();
+    for save in registered_players.save_files.values() {
+        let uid = save.save_file_key.uid;
+        if !save_files.save_file_keys.iter().any(|t| t.uid == uid) {
+            continue;
+        }
+
+        let metadata = save_files
+            .metadata
+            .entry(uid)
+            .or_insert_with(|| SaveFileMetadata::new(save));
+        metadata.battles += 1;
+        metadata.last_played = now;
+    }
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Reflect, PartialEq, Eq, Hash)]
@@ -51,6 +316,7 @@ pub enum SaveFileColor {
     Blue,
     Green,
     Red,
+    Purple,
 }
 
 impl SaveFileColor {
@@ -59,6 +325,7 @@ impl SaveFileColor {
             SaveFileColor::Blue => "Blue".to_string(),
             SaveFileColor::Green => "Green".to_string(),
             SaveFileColor::Red => "Red".to_string(),
+            SaveFileColor::Purple => "Purple".to_string(),
         }
     }
 
@@ -67,6 +334,145 @@ impl SaveFileColor {
             SaveFileColor::Blue => Color::linear_rgb(0.0, 0.0, 0.7),
             SaveFileColor::Green => Color::linear_rgb(0.0, 0.7, 0.0),
             SaveFileColor::Red => Color::linear_rgb(0.7, 0.0, 0.0),
+            SaveFileColor::Purple => Color::linear_rgb(0.45, 0.0, 0.7),
+        }
+    }
+
+    /// A brighter version of the color, for marking a player's things in battle.
+    /// `color` gets lost against the dark menus and the map.
+    pub fn battle_color(&self) -> Color {
+        match self {
+            SaveFileColor::Blue => Color::linear_rgb(0.25, 0.45, 1.0),
+            SaveFileColor::Green => Color::linear_rgb(0.25, 0.9, 0.25),
+            SaveFileColor::Red => Color::linear_rgb(1.0, 0.3, 0.3),
+            SaveFileColor::Purple => Color::linear_rgb(0.75, 0.4, 1.0),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> SaveFileKey {
+        SaveFileKey {
+            uid: 7,
+            name: "Brandon".to_string(),
+            color: SaveFileColor::Green,
+        }
+    }
+
+    fn round_trip(save: UnitSave) -> UnitSave {
+        let json = serde_json::to_string(&save).expect("Saves serialize");
+        serde_json::from_str(&json).expect("Saves deserialize")
+    }
+
+    #[test]
+    fn test_v1_saves_upgrade_to_a_fresh_character() -> anyhow::Result<()> {
+        let v1 = serde_json::from_str(
+            r#"{"version":"V1","save_file_key":{"uid":7,"name":"Brandon","color":"Green"},"job":"Mage"}"#,
+        )?;
+        let latest = upgrade_save_file_to_latest(v1)?;
+
+        assert_eq!(latest.save_file_key, key());
+        assert_eq!(latest.level, 1);
+        assert_eq!(latest.experience, 0.);
+        assert_eq!(latest.equipped_items, vec![UnitJob::Mage.starting_weapon()]);
+        assert!(!latest.learned_skills.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_v2_saves_survive_a_round_trip() -> anyhow::Result<()> {
+        let mut save = UnitSaveV2::new(key(), UnitJob::Archer);
+        save.level = 4;
+        save.experience = 30.;
+        save.equipped_items.push(ItemId(3));
+
+        let latest = upgrade_save_file_to_latest(round_trip(save.clone().into()))?;
+        assert_eq!(latest.level, save.level);
+        assert_eq!(latest.experience, save.experience);
+        assert_eq!(latest.learned_skills, save.learned_skills);
+        assert_eq!(latest.equipped_items, save.equipped_items);
+        assert_eq!(latest.base_stats, save.base_stats);
+        Ok(())
+    }
+
+    #[test]
+    fn test_progression_heals_and_keeps_growth() {
+        let save = UnitSaveV2::new(key(), UnitJob::Knight);
+
+        let mut stats = save.base_stats.clone();
+        stats.with_stat(StatType::Health, 1.0.into());
+        stats.with_stat(StatType::Strength, 9.0.into());
+
+        let mut level = UnitLevelManager::new(UnitJob::Knight.default_growths("seed".into()));
+        level.restore(3, 20.);
+
+        let skills = UnitSkills {
+            learned_skills: [SkillId(4), SkillId(1)].into(),
+            equipped_skill_categories: Vec::new(),
+            granted_skills: Default::default(),
+        };
+
+        let progressed = save.with_progression(&stats, &level, &skills, &UnitEquipment::default());
+        assert_eq!(
+            progressed.base_stats.stat(StatType::Health),
+            stats.stat(StatType::MaxHealth)
+        );
+        assert_eq!(progressed.base_stats.stat(StatType::Strength).0, 9.);
+        assert_eq!(progressed.level, 3);
+        assert_eq!(progressed.experience, 20.);
+        assert_eq!(progressed.learned_skills, vec![SkillId(1), SkillId(4)]);
+        assert!(progressed.equipped_items.is_empty());
+    }
+
+    #[test]
+    fn test_renaming_and_removing_leaves_other_characters_alone() {
+        let other = SaveFileKey {
+            uid: 2,
+            name: "Other".to_string(),
+            color: SaveFileColor::Blue,
+        };
+        let mut files = SaveFiles {
+            save_file_keys: vec![key(), other.clone()],
+            cursor: 2,
+            metadata: HashMap::default(),
+        };
+
+        let renamed = files.rename(key().uid, "Renamed".to_string()).unwrap();
+        assert_eq!(renamed.uid, key().uid);
+        assert_eq!(renamed.name, "Renamed");
+        assert_eq!(files.save_file_keys, vec![renamed.clone(), other.clone()]);
+
+        assert_eq!(files.remove(renamed.uid), Some(renamed));
+        assert_eq!(files.save_file_keys, vec![other]);
+        assert!(files.remove(99).is_none());
+    }
+
+    #[test]
+    fn test_days_since_played_rounds_down() {
+        let mut metadata = SaveFileMetadata::new(&UnitSaveV2::new(key(), UnitJob::Mage));
+        metadata.last_played = 1_000_000;
+
+        let day = 60 * 60 * 24;
+        assert_eq!(metadata.days_since_played(1_000_000 + day - 1), 0);
+        assert_eq!(metadata.days_since_played(1_000_000 + 3 * day), 3);
+        // The clock going backwards shouldn't underflow
+        assert_eq!(metadata.days_since_played(0), 0);
+    }
+
+    #[test]
+    fn test_upgraded_v1_saves_survive_a_round_trip() -> anyhow::Result<()> {
+        let v1 = UnitSave::V1(UnitSaveV1 {
+            save_file_key: key(),
+            job: UnitJob::Knight,
+        });
+        let upgraded = upgrade_save_file_to_latest(round_trip(v1))?;
+        let again = upgrade_save_file_to_latest(round_trip(upgraded.clone().into()))?;
+
+        assert_eq!(again.save_file_key, upgraded.save_file_key);
+        assert_eq!(again.equipped_items, upgraded.equipped_items);
+        Ok(())
+    }
+}
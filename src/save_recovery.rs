@@ -0,0 +1,174 @@
+//! Getting past saves that can't be read anymore.
+//!
+//! Every write through [`ProfileStore`](crate::profile::ProfileStore) keeps the value it replaced
+//! as a backup, as long as that value could still be read. When a value turns out to be unreadable
+//! (a crash partway through a write, or a save from a version that's since changed shape), it gets
+//! quarantined: moved out from under its key so it stops failing every read, but kept around in
+//! case it's worth digging out by hand. The backup can then be put back in its place.
+//!
+//! Everything here works on full [`PkvStore`] keys, so it doesn't care about profiles.
+
+use bevy::prelude::*;
+use bevy_pkv::{GetError, PkvStore};
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Where the last readable value of `key` is kept
+pub fn backup_key(key: &str) -> String {
+    format!("{}.backup", key)
+}
+
+/// Where an unreadable value of `key` ends up
+pub fn quarantine_key(key: &str) -> String {
+    format!("{}.quarantined", key)
+}
+
+/// Reads `key`, telling a value that isn't there (`Ok(None)`) apart from one that is but can't be
+/// read (`Err`)
+pub fn try_get<T: DeserializeOwned>(pkv: &PkvStore, key: &str) -> anyhow::Result<Option<T>> {
+    match pkv.get::<T>(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(GetError::NotFound) => Ok(None),
+        Err(e) => Err(anyhow::anyhow!("Failed to get PKV: {:?}", e)),
+    }
+}
+
+/// Writes `value` to `key`, keeping the value it replaces as the backup
+pub fn set_with_backup<T: Serialize + DeserializeOwned>(
+    pkv: &mut PkvStore,
+    key: &str,
+    value: &T,
+) -> anyhow::Result<()> {
+    // An unreadable value would make a useless backup, so the last good one stays instead
+    if let Ok(previous) = pkv.get::<T>(key)
+        && let Err(e) = pkv.set(backup_key(key), &previous)
+    {
+        warn!("Failed backing up {}: {:?}", key, e);
+    }
+
+    pkv.set(key, value)
+        .map_err(|e| anyhow::anyhow!("Failed to set PKV: {:?}", e))
+}
+
+/// Removes `key` along with its backup
+pub fn remove_with_backup(pkv: &mut PkvStore, key: &str) -> anyhow::Result<()> {
+    // Plenty of keys never got a backup, which is fine
+    let _ = pkv.remove(backup_key(key));
+    pkv.remove(key)
+        .map_err(|e| anyhow::anyhow!("Failed to remove PKV: {:?}", e))
+}
+
+pub fn has_backup<T: DeserializeOwned>(pkv: &PkvStore, key: &str) -> bool {
+    pkv.get::<T>(&backup_key(key)).is_ok()
+}
+
+/// Moves whatever is in `key` out of the way
+pub fn quarantine(pkv: &mut PkvStore, key: &str) {
+    // Anything that's still structured data is worth keeping. Garbage just gets dropped.
+    if let Ok(raw) = pkv.get::<serde_json::Value>(key)
+        && let Err(e) = pkv.set(quarantine_key(key), &raw)
+    {
+        error!("Failed quarantining {}: {:?}", key, e);
+    }
+
+    if let Err(e) = pkv.remove(key) {
+        error!("Failed removing unreadable {}: {:?}", key, e);
+    }
+}
+
+/// Quarantines `key`, and puts its backup in its place
+pub fn restore_backup<T: Serialize + DeserializeOwned>(
+    pkv: &mut PkvStore,
+    key: &str,
+) -> anyhow::Result<T> {
+    let backup = pkv
+        .get::<T>(&backup_key(key))
+        .map_err(|e| anyhow::anyhow!("No readable backup of {}: {:?}", key, e))?;
+
+    quarantine(pkv, key);
+    pkv.set(key, &backup)
+        .map_err(|e| anyhow::anyhow!("Failed to set PKV: {:?}", e))?;
+
+    info!("Restored {} from its backup", key);
+    Ok(backup)
+}
+
+/// Reads `key`, falling back on its backup if it can't be read. If there's no backup either, the
+/// value gets quarantined and this acts like it was never there.
+pub fn get_or_recover<T: Serialize + DeserializeOwned>(pkv: &mut PkvStore, key: &str) -> Option<T> {
+    let e = match try_get(pkv, key) {
+        Ok(value) => return value,
+        Err(e) => e,
+    };
+
+    error!("{} can't be read: {:?}", key, e);
+    match restore_backup(pkv, key) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            error!("Giving up on {}: {:?}", key, e);
+            quarantine(pkv, key);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestSave {
+        name: String,
+        level: u32,
+    }
+
+    #[test]
+    fn test_backups_and_quarantine_stay_out_of_each_others_way() {
+        let key = "profile-1-unit-save-3";
+        assert_ne!(backup_key(key), quarantine_key(key));
+        assert_ne!(backup_key(key), key);
+        // A backup of a backup would be a different key again, not the original's
+        assert_ne!(backup_key(&backup_key(key)), backup_key(key));
+    }
+
+    #[test]
+    fn test_unreadable_saves_get_quarantined_and_restored() -> anyhow::Result<()> {
+        let dir =
+            std::env::temp_dir().join(format!("tactics_save_recovery_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut pkv = PkvStore::new_in_dir(&dir);
+        let key = "profile-1-unit-save-3";
+
+        let first = TestSave {
+            name: "Bob".to_string(),
+            level: 1,
+        };
+        set_with_backup(&mut pkv, key, &first)?;
+        set_with_backup(
+            &mut pkv,
+            key,
+            &TestSave {
+                name: "Bob".to_string(),
+                level: 2,
+            },
+        )?;
+        assert!(has_backup::<TestSave>(&pkv, key));
+
+        // Something that's still data, just not a save
+        pkv.set(key, &"half a save")
+            .map_err(|e| anyhow::anyhow!("Failed to set PKV: {:?}", e))?;
+        assert!(try_get::<TestSave>(&pkv, key).is_err());
+
+        assert_eq!(get_or_recover::<TestSave>(&mut pkv, key), Some(first));
+        assert_eq!(try_get::<TestSave>(&pkv, key)?.map(|t| t.level), Some(1));
+        assert_eq!(
+            pkv.get::<String>(quarantine_key(key)).ok().as_deref(),
+            Some("half a save")
+        );
+
+        drop(pkv);
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+//! Moving saves in and out of the game as JSON files.
+//!
+//! Mostly so playtesters can send over their save when something breaks, and so characters and
+//! runs survive the [`ProfileStore`] getting cleared. Native builds go through the system file
+//! dialogs. On the web, exporting downloads the file and importing asks for one to upload.
+//!
+//! Imported characters always get a fresh uid, so they can't clash with anyone already here.
+//! Importing a run brings its party along as characters, and makes it the run to continue.
+
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, Task, block_on, futures_lite::future},
+};
+
+use crate::{
+    GameState,
+    profile::{ActiveProfile, ProfileStore},
+    run_save::{RunSave, load_run_save, upgrade_run_save_to_latest, write_run_save},
+    save_game::{
+        SaveFileKey, SaveFileMetadata, SaveFiles, UnitSave, UnitSaveV2, upgrade_save_file_to_latest,
+    },
+};
+
+/// What's in an exported file
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind")]
+pub enum SaveExport {
+    Character { save: UnitSave },
+    Run { save: RunSave },
+}
+
+#[derive(Message, Debug, Clone)]
+pub enum SaveTransferMessage {
+    ExportCharacter(SaveFileKey),
+    /// Exports the run that can be continued
+    ExportRun,
+    /// Imports either kind of [`SaveExport`]
+    Import,
+}
+
+/// What an import added
+#[derive(Debug)]
+pub enum ImportedSave {
+    Character(SaveFileKey),
+    /// The id of the run, which is now the one to continue
+    Run(String),
+}
+
+/// A save dialog that's waiting on the player. Finishes with false if they backed out.
+#[derive(Component)]
+pub struct ExportTask(Task<anyhow::Result<bool>>);
+
+/// An open file dialog that's waiting on the player. Finishes with None if they backed out.
+#[derive(Component)]
+pub struct ImportTask(Task<Option<Vec<u8>>>);
+
+pub fn save_transfer_plugin(app: &mut App) {
+    app.add_message::<SaveTransferMessage>().add_systems(
+        Update,
+        (start_save_transfers, finish_exports, finish_imports)
+            .run_if(resource_exists::<ActiveProfile>),
+    );
+}
+
+fn start_save_transfers(
+    mut commands: Commands,
+    mut reader: MessageReader<SaveTransferMessage>,
+    mut pkv: ProfileStore,
+) {
+    for message in reader.read() {
+        let (file_name, export) = match message {
+            SaveTransferMessage::ExportCharacter(key) => {
+                match pkv.get::<UnitSave>(&key.pkv_key()) {
+                    Ok(save) => (format!("{}.json", key.name), SaveExport::Character { save }),
+                    Err(e) => {
+                        error!("Failed loading {} to export: {:?}", key.name, e);
+                        continue;
+                    }
+                }
+            }
+            SaveTransferMessage::ExportRun => {
+                let Some(save) = load_run_save(&mut pkv) else {
+                    error!("No run to export");
+                    continue;
+                };
+                (
+                    format!("run-{}.json", save.run_id),
+                    SaveExport::Run {
+                        save: RunSave::V2(save),
+                    },
+                )
+            }
+            SaveTransferMessage::Import => {
+                let task = IoTaskPool::get().spawn(async move {
+                    let file = rfd::AsyncFileDialog::new()
+                        .add_filter("JSON", &["json"])
+                        .pick_file()
+                        .await?;
+                    Some(file.read().await)
+                });
+                commands.spawn(ImportTask(task));
+                continue;
+            }
+        };
+
+        match serde_json::to_string_pretty(&export) {
+            Ok(contents) => export_file(&mut commands, file_name, contents),
+            Err(e) => error!("Failed serializing {}: {:?}", file_name, e),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn export_file(commands: &mut Commands, file_name: String, contents: String) {
+    let task = IoTaskPool::get().spawn(async move {
+        let Some(file) = rfd::AsyncFileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_file_name(&file_name)
+            .save_file()
+            .await
+        else {
+            return Ok(false);
+        };
+        file.write(contents.as_bytes()).await?;
+        Ok(true)
+    });
+    commands.spawn(ExportTask(task));
+}
+
+/// Browsers don't have a save dialog, so the file gets downloaded instead
+#[cfg(target_arch = "wasm32")]
+fn export_file(_commands: &mut Commands, file_name: String, contents: String) {
+    match download(&file_name, &contents) {
+        Ok(()) => info!("Exported {}", file_name),
+        Err(e) => error!("Failed exporting {}: {:?}", file_name, e),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn download(file_name: &str, contents: &str) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsCast;
+
+    let parts = js_sys::Array::of1(&wasm_bindgen::JsValue::from_str(contents));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type("application/json");
+    let blob = web_sys::Blob::new_with_str_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+
+    let document = web_sys::window()
+        .and_then(|t| t.document())
+        .ok_or("No document to download from")?;
+    let link = document
+        .create_element("a")?
+        .dyn_into::<web_sys::HtmlAnchorElement>()?;
+    link.set_href(&url);
+    link.set_download(file_name);
+    link.click();
+
+    web_sys::Url::revoke_object_url(&url)
+}
+
+fn finish_exports(mut commands: Commands, tasks: Query<(Entity, &mut ExportTask)>) {
+    for (entity, mut task) in tasks {
+        let Some(result) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+
+        match result {
+            Ok(true) => info!("Exported save"),
+            Ok(false) => info!("Export cancelled"),
+            Err(e) => error!("Failed exporting save: {:?}", e),
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+fn finish_imports(
+    mut commands: Commands,
+    tasks: Query<(Entity, &mut ImportTask)>,
+    mut pkv: ProfileStore,
+    mut save_files: ResMut<SaveFiles>,
+    game_state: Res<State<GameState>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    for (entity, mut task) in tasks {
+        let Some(result) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+        commands.entity(entity).despawn();
+
+        let Some(contents) = result else {
+            info!("Import cancelled");
+            continue;
+        };
+
+        match import_save(&mut pkv, &mut save_files, &contents) {
+            Ok(ImportedSave::Run(_)) if *game_state.get() == GameState::MainMenu => {
+                // Rebuilds the main menu, so Continue Run shows up
+                next_game_state.set(GameState::MainMenu);
+            }
+            Ok(_) => {}
+            Err(e) => error!("Failed importing save: {:?}", e),
+        }
+    }
+}
+
+/// Saves `unit` as a brand new character
+fn import_character(
+    pkv: &mut ProfileStore,
+    save_files: &mut SaveFiles,
+    mut unit: UnitSaveV2,
+) -> anyhow::Result<SaveFileKey> {
+    let key = save_files.add(
+        unit.save_file_key.name.clone(),
+        unit.save_file_key.color.clone(),
+    );
+    unit.save_file_key = key.clone();
+    save_files
+        .metadata
+        .insert(key.uid, SaveFileMetadata::new(&unit));
+
+    pkv.set(key.pkv_key(), &UnitSave::from(unit))?;
+
+    info!("Imported {}", key.name);
+    Ok(key)
+}
+
+/// Imports an exported file
+pub fn import_save(
+    pkv: &mut ProfileStore,
+    save_files: &mut SaveFiles,
+    contents: &[u8],
+) -> anyhow::Result<ImportedSave> {
+    let export = serde_json::from_slice::<SaveExport>(contents)?;
+
+    match export {
+        SaveExport::Character { save } => {
+            let unit = upgrade_save_file_to_latest(save)?;
+            Ok(ImportedSave::Character(import_character(
+                pkv, save_files, unit,
+            )?))
+        }
+        SaveExport::Run { save } => {
+            let mut run = upgrade_run_save_to_latest(save);
+            for unit in run.party.clone() {
+                let uid = unit.save_file_key.uid;
+                let key = import_character(pkv, save_files, unit)?;
+                run.rekey_character(uid, key);
+            }
+
+            let run_id = run.run_id.clone();
+            write_run_save(pkv, run);
+            Ok(ImportedSave::Run(run_id))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        difficulty::Difficulty,
+        inventory::PartyInventory,
+        loot::RunLoot,
+        run_save::{BattleSave, RunSaveV2},
+        run_stats::RunStats,
+        save_game::SaveFileColor,
+        unit::jobs::UnitJob,
+    };
+
+    #[test]
+    fn test_exports_survive_a_round_trip() -> anyhow::Result<()> {
+        let key = SaveFileKey {
+            uid: 3,
+            name: "Brandon".to_string(),
+            color: SaveFileColor::Blue,
+        };
+        let export = SaveExport::Run {
+            save: RunSave::V2(RunSaveV2 {
+                run_id: "abc".to_string(),
+                seed: "seed".to_string(),
+                room: 2,
+                party: vec![UnitSaveV2::new(key.clone(), UnitJob::Mage)],
+                battle: Some(BattleSave {
+                    turn: 5,
+                    units: Vec::new(),
+                }),
+                stats: RunStats::default(),
+                loot: RunLoot::default(),
+                inventory: PartyInventory::default(),
+                difficulty: Difficulty::Hard,
+            }),
+        };
+
+        let json = serde_json::to_string_pretty(&export)?;
+        let SaveExport::Run { save } = serde_json::from_str(&json)? else {
+            anyhow::bail!("Exported a run, imported something else");
+        };
+        let run = upgrade_run_save_to_latest(save);
+        assert_eq!(run.turn(), 5);
+        assert_eq!(run.party[0].save_file_key, key);
+        Ok(())
+    }
+}
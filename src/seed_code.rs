@@ -0,0 +1,200 @@
+//! Short codes for dungeon seeds, so friends can race the same dungeon.
+//!
+//! New runs get a seed like `K7QM-2XHD`, which is what shows on the join screen and in the pause
+//! menu. Typing a friend's code into the join screen swaps it in before the run starts. The
+//! alphabet leaves out characters that are easy to mix up (0/O, 1/I), and codes are read case
+//! insensitively with or without the dash.
+//!
+//! The code is the seed itself, so seeds from before there were codes still work as they are.
+
+use bevy::prelude::*;
+use bevy_simple_text_input::{
+    TextInput, TextInputInactive, TextInputPlaceholder, TextInputSettings, TextInputTextFont,
+    TextInputValue,
+};
+use rand::prelude::*;
+
+use crate::{
+    GameState, assets::FontResource, menu::ui_consts::UI_TEXT_COLOR, run_save::PendingRunRestore,
+    tr,
+};
+
+pub const SEED_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// Not counting the dash
+pub const SEED_CODE_LENGTH: usize = 8;
+
+pub fn random_seed_code() -> String {
+    let mut rng = rand::rng();
+    let code = (0..SEED_CODE_LENGTH)
+        .map(|_| *SEED_CODE_ALPHABET.choose(&mut rng).unwrap() as char)
+        .collect::<String>();
+    format_seed_code(&code)
+}
+
+fn format_seed_code(code: &str) -> String {
+    let (first, second) = code.split_at(SEED_CODE_LENGTH / 2);
+    format!("{}-{}", first, second)
+}
+
+/// Reads a code the way someone might type it in. None if it isn't a whole, valid code.
+pub fn parse_seed_code(input: &str) -> Option<String> {
+    let code = input
+        .chars()
+        .filter(|t| !t.is_whitespace() && *t != '-')
+        .map(|t| t.to_ascii_uppercase())
+        .collect::<String>();
+
+    let valid =
+        code.len() == SEED_CODE_LENGTH && code.bytes().all(|t| SEED_CODE_ALPHABET.contains(&t));
+    valid.then(|| format_seed_code(&code))
+}
+
+/// The seed the next run will be generated from
+#[derive(Resource, Debug, Clone)]
+pub struct ChosenSeed(pub String);
+
+/// Where a friend's code gets typed in
+#[derive(Component)]
+pub struct SeedCodeInput;
+
+/// Shows the [`ChosenSeed`]
+#[derive(Component)]
+pub struct SeedCodeText;
+
+pub fn seed_code_plugin(app: &mut App) {
+    app.add_systems(OnEnter(GameState::JoinGame), choose_seed)
+        .add_systems(
+            Update,
+            (
+                apply_seed_code_input,
+                display_seed_code.run_if(resource_changed::<ChosenSeed>),
+            )
+                .chain()
+                .run_if(in_state(GameState::JoinGame)),
+        )
+        .add_observer(focus_seed_code_input);
+}
+
+/// A continued run is stuck with the seed it started on
+fn choose_seed(mut commands: Commands, pending_restore: Option<Res<PendingRunRestore>>) {
+    let seed = match pending_restore {
+        Some(restore) => restore.0.seed.clone(),
+        None => random_seed_code(),
+    };
+    commands.insert_resource(ChosenSeed(seed));
+}
+
+/// The code for the upcoming run, along with somewhere to type in a friend's code when it's a
+/// new run
+pub fn spawn_seed_code_widget(
+    commands: &mut Commands,
+    fonts: &FontResource,
+    editable: bool,
+) -> Entity {
+    let font = TextFont {
+        font: fonts.pixelify_sans_regular.clone(),
+        ..Default::default()
+    };
+
+    let widget = commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                column_gap: px(16),
+                ..Default::default()
+            },
+            children![(
+                Text::default(),
+                font.clone(),
+                TextColor(UI_TEXT_COLOR),
+                SeedCodeText
+            )],
+        ))
+        .id();
+
+    if editable {
+        commands.entity(widget).with_child((
+            Button,
+            Node {
+                width: px(260),
+                border: UiRect::all(px(2)),
+                padding: UiRect::horizontal(px(8)),
+                border_radius: BorderRadius::all(percent(20)),
+                ..default()
+            },
+            BorderColor::all(UI_TEXT_COLOR),
+            TextInput,
+            TextInputTextFont(font),
+            TextInputPlaceholder {
+                value: tr!("seed_code.placeholder"),
+                ..default()
+            },
+            TextInputInactive(true),
+            TextInputSettings {
+                retain_on_submit: true,
+                ..default()
+            },
+            SeedCodeInput,
+        ));
+    }
+
+    widget
+}
+
+fn focus_seed_code_input(
+    click: On<Pointer<Click>>,
+    mut inputs: Query<&mut TextInputInactive, With<SeedCodeInput>>,
+) {
+    if let Ok(mut inactive) = inputs.get_mut(click.entity) {
+        inactive.0 = !inactive.0;
+    }
+}
+
+/// Switches to a friend's code as soon as a whole one has been typed in
+fn apply_seed_code_input(
+    mut chosen_seed: ResMut<ChosenSeed>,
+    inputs: Query<&TextInputValue, (With<SeedCodeInput>, Changed<TextInputValue>)>,
+) {
+    for input in inputs {
+        if let Some(code) = parse_seed_code(&input.0)
+            && chosen_seed.0 != code
+        {
+            info!("Using seed code {}", code);
+            chosen_seed.0 = code;
+        }
+    }
+}
+
+fn display_seed_code(
+    chosen_seed: Res<ChosenSeed>,
+    mut texts: Query<&mut Text, With<SeedCodeText>>,
+) {
+    for mut text in texts.iter_mut() {
+        text.0 = tr!("seed_code.label", code = chosen_seed.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_survive_being_typed_in_sloppily() {
+        let code = random_seed_code();
+        assert_eq!(parse_seed_code(&code), Some(code.clone()));
+
+        let sloppy = code.replace('-', " ").to_lowercase();
+        assert_eq!(parse_seed_code(&sloppy), Some(code));
+    }
+
+    #[test]
+    fn test_partial_and_ambiguous_codes_are_rejected() {
+        assert_eq!(parse_seed_code("ABCD-EFG"), None);
+        assert_eq!(parse_seed_code("ABCD-EFGHJ"), None);
+        // O and 0 are left out of the alphabet, since they're too easy to mix up
+        assert_eq!(parse_seed_code("ABCD-EFG0"), None);
+        assert_eq!(parse_seed_code("abcd efgh"), Some("ABCD-EFGH".to_string()));
+    }
+}
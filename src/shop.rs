@@ -0,0 +1,554 @@
+//! Rooms with somebody to spend gold with.
+//!
+//! A [`RoomKind::Shop`](crate::dungeon::RoomKind::Shop) room skips the battle like a rest room
+//! does, and has a [`Vendor`] selling everything in the [`ItemDB`], along with every
+//! [`Consumable`]. Every player gets their own panel to shop from, and has to confirm each
+//! purchase or sale before it goes through. Gold comes out of and goes into the party's shared
+//! [`RunLoot`], and whatever gets bought or sold goes through the [`PartyInventory`]. The vendor
+//! also [repairs](crate::durability) the party's gear, for a price.
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::{
+    assets::FontResource,
+    confirm_dialog::{ConfirmDialogAction, ConfirmDialogMessage, open_confirm_dialog},
+    dungeon::{DungeonEntity, DungeonState},
+    durability::{repair_cost, repair_party_gear},
+    equipment::{ItemDB, ItemId},
+    interactable::{InteractionEnabled, Vendor},
+    inventory::{Consumable, PartyInventory},
+    localization::localized_text,
+    loot::RunLoot,
+    map_generation::MapData,
+    menu::{
+        menu_navigation::{
+            ActiveMenu, GameMenuController, GameMenuGrid, GameMenuLatch,
+            handle_menu_cursor_navigation, highlight_menu_option,
+        },
+        ui_consts::{SELECTABLE_BUTTON_BACKGROUND, UI_MENU_BACKGROUND, UI_TEXT_COLOR},
+    },
+    player::{Player, RegisteredBattlePlayers},
+    tr,
+    unit::equip_starting_items_on_unit,
+};
+
+/// How much of an item's price the vendor pays to take it back
+const SELL_RATE: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ShopOffer {
+    Buy(ItemId),
+    Sell(ItemId),
+    BuyConsumable(Consumable),
+    /// Fixing up everything the party has, in the bag or not
+    Repair,
+    Leave,
+}
+
+/// A button on one player's shop panel
+#[derive(Component)]
+struct ShopButton {
+    player: Player,
+    offer: ShopOffer,
+    /// The panel the button is on, which the confirm dialog takes over while it's up
+    panel: Entity,
+}
+
+#[derive(Component)]
+struct ShopGoldLabel;
+
+/// What the gold label turns when somebody tries to buy something the party can't afford
+const SHORT_ON_GOLD_COLOR: Color = Color::srgb(0.9, 0.3, 0.3);
+
+pub fn shop_plugin(app: &mut App) {
+    app.add_systems(
+        OnEnter(DungeonState::ShopRoom),
+        (equip_starting_items_on_unit, open_shop),
+    )
+    .add_systems(
+        Update,
+        (
+            handle_menu_cursor_navigation,
+            highlight_menu_option,
+            complete_trades,
+            update_shop_labels.run_if(
+                resource_changed::<RunLoot>
+                    .or(resource_changed::<PartyInventory>)
+                    .or(resource_changed::<RegisteredBattlePlayers>),
+            ),
+        )
+            .chain()
+            .run_if(in_state(DungeonState::ShopRoom)),
+    )
+    .add_observer(shop_button_action);
+}
+
+pub fn spawn_vendor(commands: &mut Commands, map_data: &MapData) {
+    let Some(position) = map_data.open_tile_near_middle() else {
+        warn!("Nowhere to put the vendor");
+        return;
+    };
+
+    commands.spawn((
+        Name::new("Vendor"),
+        position,
+        Vendor,
+        InteractionEnabled,
+        DungeonEntity,
+    ));
+}
+
+fn sell_price(price: u32) -> u32 {
+    (price as f32 * SELL_RATE) as u32
+}
+
+/// Pays for the item and puts it in the bag, if the party can afford it
+fn buy_item(loot: &mut RunLoot, inventory: &mut PartyInventory, item: ItemId, price: u32) -> bool {
+    if loot.gold < price {
+        return false;
+    }
+    loot.gold -= price;
+    inventory.add_item(item);
+    true
+}
+
+/// Takes one of the item out of the bag and gets paid for it, if the party has one. The shop pays
+/// the same however it rolled.
+fn sell_item(loot: &mut RunLoot, inventory: &mut PartyInventory, item: ItemId, price: u32) -> bool {
+    if inventory.remove_item_of_kind(item).is_none() {
+        return false;
+    }
+    loot.gold += sell_price(price);
+    true
+}
+
+fn buy_consumable(
+    loot: &mut RunLoot,
+    inventory: &mut PartyInventory,
+    consumable: Consumable,
+    price: u32,
+) -> bool {
+    if loot.gold < price {
+        return false;
+    }
+    loot.gold -= price;
+    inventory.add_consumable(consumable, 1);
+    true
+}
+
+fn offer_text(
+    offer: ShopOffer,
+    players: &RegisteredBattlePlayers,
+    inventory: &PartyInventory,
+    item_db: &ItemDB,
+) -> String {
+    let (item_id, buying) = match offer {
+        ShopOffer::Buy(item_id) => (item_id, true),
+        ShopOffer::Sell(item_id) => (item_id, false),
+        ShopOffer::BuyConsumable(consumable) => {
+            return tr!(
+                "shop.buy_consumable",
+                item = tr!(consumable.name_key()),
+                count = inventory.consumable_count(consumable),
+                price = item_db.consumable(&consumable).price
+            );
+        }
+        ShopOffer::Repair => {
+            return match repair_cost(players, inventory) {
+                0 => tr!("shop.nothing_to_repair"),
+                price => tr!("shop.repair", price = price),
+            };
+        }
+        ShopOffer::Leave => return tr!("shop.leave"),
+    };
+    let Some(item) = item_db.equippable_item(&item_id) else {
+        return String::new();
+    };
+
+    if buying {
+        tr!("shop.buy", item = item.description(), price = item.price())
+    } else {
+        let count = inventory.item_count(item_id);
+        tr!(
+            "shop.sell",
+            item = item.name(),
+            count = count,
+            price = sell_price(item.price())
+        )
+    }
+}
+
+/// Items are written in the color of their rarity
+fn offer_color(offer: ShopOffer, item_db: &ItemDB) -> Color {
+    match offer {
+        ShopOffer::Buy(item) | ShopOffer::Sell(item) => item_db
+            .equippable_item(&item)
+            .map(|t| t.rarity().color())
+            .unwrap_or(UI_TEXT_COLOR),
+        ShopOffer::BuyConsumable(_) | ShopOffer::Repair | ShopOffer::Leave => UI_TEXT_COLOR,
+    }
+}
+
+fn open_shop(
+    mut commands: Commands,
+    fonts: Res<FontResource>,
+    registered_players: Res<RegisteredBattlePlayers>,
+    item_db: Res<ItemDB>,
+    loot: Res<RunLoot>,
+    inventory: Res<PartyInventory>,
+) {
+    let font = TextFont {
+        font_size: 22.0,
+        font: fonts.pixelify_sans_regular.clone(),
+        ..default()
+    };
+
+    let mut stock = item_db.equippable_items.keys().copied().collect::<Vec<_>>();
+    stock.sort_by_key(|t| t.0);
+    let offers = stock
+        .iter()
+        .flat_map(|t| [ShopOffer::Buy(*t), ShopOffer::Sell(*t)])
+        .chain(Consumable::ALL.map(ShopOffer::BuyConsumable))
+        .chain([ShopOffer::Repair, ShopOffer::Leave])
+        .collect::<Vec<_>>();
+
+    let mut players = registered_players
+        .save_files
+        .keys()
+        .copied()
+        .collect::<Vec<_>>();
+    players.sort_by_key(|t| t.id());
+
+    let panels = players
+        .into_iter()
+        .map(|player| {
+            let panel = commands.spawn_empty().id();
+            let buttons = offers
+                .iter()
+                .map(|offer| {
+                    commands
+                        .spawn((
+                            Button,
+                            Node {
+                                width: px(260),
+                                min_height: px(50),
+                                margin: UiRect::all(px(6)),
+                                padding: UiRect::all(px(8)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                border_radius: BorderRadius::all(percent(20)),
+                                ..default()
+                            },
+                            BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+                            ShopButton {
+                                player,
+                                offer: *offer,
+                                panel,
+                            },
+                            children![(
+                                Text(offer_text(
+                                    *offer,
+                                    &registered_players,
+                                    &inventory,
+                                    &item_db,
+                                )),
+                                font.clone(),
+                                TextColor(offer_color(*offer, &item_db)),
+                                TextLayout::new_with_justify(Justify::Center),
+                            )],
+                        ))
+                        .id()
+                })
+                .collect::<Vec<_>>();
+
+            let mut grid = GameMenuGrid::new_with_width(2);
+            grid.push_buttons_in_rows(&buttons);
+
+            commands
+                .entity(panel)
+                .insert((
+                    Name::new(format!("ShopPanel {:?}", player)),
+                    Node {
+                        display: Display::Grid,
+                        grid_template_columns: RepeatedGridTrack::auto(2),
+                        padding: UiRect::all(px(12)),
+                        border: UiRect::all(px(3)),
+                        border_radius: BorderRadius::all(percent(5)),
+                        ..default()
+                    },
+                    BorderColor::all(registered_players.player_color(&player)),
+                    BackgroundColor(UI_MENU_BACKGROUND),
+                    grid,
+                    GameMenuController {
+                        players: HashSet::from([player]),
+                    },
+                    GameMenuLatch::default(),
+                    ActiveMenu {},
+                ))
+                .add_children(&buttons);
+            panel
+        })
+        .collect::<Vec<_>>();
+
+    let panel_row = commands
+        .spawn(Node {
+            flex_direction: FlexDirection::Row,
+            flex_wrap: FlexWrap::Wrap,
+            justify_content: JustifyContent::Center,
+            column_gap: px(16),
+            row_gap: px(16),
+            ..default()
+        })
+        .add_children(&panels)
+        .id();
+
+    commands
+        .spawn((
+            Name::new("Shop"),
+            Node {
+                position_type: PositionType::Absolute,
+                width: percent(100),
+                bottom: px(20),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: px(12),
+                ..default()
+            },
+            DespawnOnExit(DungeonState::ShopRoom),
+            children![
+                (
+                    localized_text("shop.title"),
+                    TextFont {
+                        font_size: 50.0,
+                        font: fonts.pixelify_sans_medium.clone(),
+                        ..default()
+                    },
+                    TextColor(UI_TEXT_COLOR),
+                ),
+                (
+                    Text(tr!("shop.gold", gold = loot.gold)),
+                    TextFont {
+                        font_size: 30.0,
+                        font: fonts.pixelify_sans_regular.clone(),
+                        ..default()
+                    },
+                    TextColor(UI_TEXT_COLOR),
+                    ShopGoldLabel,
+                ),
+            ],
+        ))
+        .add_child(panel_row);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn shop_button_action(
+    mut click: On<Pointer<Click>>,
+    mut commands: Commands,
+    buttons: Query<&ShopButton>,
+    fonts: Res<FontResource>,
+    item_db: Option<Res<ItemDB>>,
+    loot: Option<Res<RunLoot>>,
+    inventory: Option<Res<PartyInventory>>,
+    registered_players: Option<Res<RegisteredBattlePlayers>>,
+    mut gold_label: Query<(&mut Text, &mut TextColor), With<ShopGoldLabel>>,
+    mut next_state: ResMut<NextState<DungeonState>>,
+) {
+    let (Ok(button), Some(item_db), Some(loot), Some(inventory), Some(registered_players)) = (
+        buttons.get(click.entity),
+        item_db,
+        loot,
+        inventory,
+        registered_players,
+    ) else {
+        return;
+    };
+    click.propagate(false);
+
+    let action = match button.offer {
+        ShopOffer::Leave => {
+            next_state.set(DungeonState::UnloadRoom);
+            return;
+        }
+        ShopOffer::Buy(item) => {
+            let Some(price) = item_db.equippable_item(&item).map(|t| t.price()) else {
+                return;
+            };
+            if price > loot.gold {
+                info!("The party can't afford {:?}", item);
+                flag_short_on_gold(&mut gold_label, price, loot.gold);
+                return;
+            }
+            ConfirmDialogAction::BuyItem(item)
+        }
+        ShopOffer::Sell(item) => {
+            if inventory.item_count(item) == 0 {
+                info!("The party doesn't have a {:?} to sell", item);
+                return;
+            }
+            ConfirmDialogAction::SellItem(item)
+        }
+        ShopOffer::BuyConsumable(consumable) => {
+            let price = item_db.consumable(&consumable).price;
+            if price > loot.gold {
+                info!("The party can't afford a {:?}", consumable);
+                flag_short_on_gold(&mut gold_label, price, loot.gold);
+                return;
+            }
+            ConfirmDialogAction::BuyConsumable(consumable)
+        }
+        ShopOffer::Repair => {
+            let price = repair_cost(&registered_players, &inventory);
+            if price == 0 {
+                info!("The party has nothing to repair");
+                return;
+            }
+            if price > loot.gold {
+                info!("The party can't repair anything for {} gold", loot.gold);
+                flag_short_on_gold(&mut gold_label, price, loot.gold);
+                return;
+            }
+            ConfirmDialogAction::RepairGear
+        }
+    };
+
+    open_confirm_dialog(
+        &mut commands,
+        &fonts,
+        action,
+        button.panel,
+        HashSet::from([button.player]),
+    );
+}
+
+/// Says how far off the party is, until the gold changes again
+fn flag_short_on_gold(
+    gold_label: &mut Query<(&mut Text, &mut TextColor), With<ShopGoldLabel>>,
+    price: u32,
+    gold: u32,
+) {
+    for (mut text, mut color) in gold_label.iter_mut() {
+        text.0 = tr!("shop.not_enough_gold", price = price, gold = gold);
+        color.0 = SHORT_ON_GOLD_COLOR;
+    }
+}
+
+/// Goes through with whatever got confirmed. The bag might have changed while the dialog was up,
+/// so everything gets checked again.
+fn complete_trades(
+    mut reader: MessageReader<ConfirmDialogMessage>,
+    mut loot: ResMut<RunLoot>,
+    mut inventory: ResMut<PartyInventory>,
+    mut registered_players: ResMut<RegisteredBattlePlayers>,
+    item_db: Res<ItemDB>,
+) {
+    for message in reader.read().filter(|t| t.confirmed) {
+        let (item, bought) = match message.action {
+            ConfirmDialogAction::BuyItem(item) => (item, true),
+            ConfirmDialogAction::SellItem(item) => (item, false),
+            ConfirmDialogAction::BuyConsumable(consumable) => {
+                let price = item_db.consumable(&consumable).price;
+                if !buy_consumable(&mut loot, &mut inventory, consumable, price) {
+                    info!("Couldn't buy a {:?} after all", consumable);
+                }
+                continue;
+            }
+            ConfirmDialogAction::RepairGear => {
+                let price = repair_cost(&registered_players, &inventory);
+                if price > loot.gold {
+                    info!("Couldn't afford the repairs after all");
+                    continue;
+                }
+                loot.gold -= price;
+                repair_party_gear(&mut registered_players, &mut inventory);
+                continue;
+            }
+            _ => continue,
+        };
+        let Some(price) = item_db.equippable_item(&item).map(|t| t.price()) else {
+            error!("Tried to trade an item that doesn't exist: {:?}", item);
+            continue;
+        };
+
+        let traded = if bought {
+            buy_item(&mut loot, &mut inventory, item, price)
+        } else {
+            sell_item(&mut loot, &mut inventory, item, price)
+        };
+        if !traded {
+            info!("Couldn't trade {:?} after all", item);
+        }
+    }
+}
+
+fn update_shop_labels(
+    buttons: Query<(&ShopButton, &Children)>,
+    mut texts: Query<&mut Text, Without<ShopGoldLabel>>,
+    mut gold_label: Query<(&mut Text, &mut TextColor), With<ShopGoldLabel>>,
+    item_db: Res<ItemDB>,
+    loot: Res<RunLoot>,
+    inventory: Res<PartyInventory>,
+    registered_players: Res<RegisteredBattlePlayers>,
+) {
+    for (button, children) in buttons.iter() {
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.0 = offer_text(button.offer, &registered_players, &inventory, &item_db);
+            }
+        }
+    }
+
+    for (mut text, mut color) in gold_label.iter_mut() {
+        text.0 = tr!("shop.gold", gold = loot.gold);
+        color.0 = UI_TEXT_COLOR;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buying_needs_enough_gold() {
+        let mut loot = RunLoot {
+            gold: 50,
+            ..Default::default()
+        };
+        let mut inventory = PartyInventory::default();
+        assert!(buy_item(&mut loot, &mut inventory, ItemId(1), 40));
+        assert!(!buy_item(&mut loot, &mut inventory, ItemId(1), 40));
+        assert_eq!(loot.gold, 10);
+        assert_eq!(inventory.items, vec![ItemId(1)]);
+
+        assert!(buy_consumable(
+            &mut loot,
+            &mut inventory,
+            Consumable::Antidote,
+            10
+        ));
+        assert!(!buy_consumable(
+            &mut loot,
+            &mut inventory,
+            Consumable::Potion,
+            15
+        ));
+        assert_eq!(loot.gold, 0);
+        assert_eq!(inventory.consumable_count(Consumable::Antidote), 1);
+    }
+
+    #[test]
+    fn test_selling_takes_one_out_of_the_bag() {
+        let mut loot = RunLoot::default();
+        let mut inventory = PartyInventory {
+            items: vec![ItemId(2), ItemId(1), ItemId(2)],
+            ..Default::default()
+        };
+        assert!(sell_item(&mut loot, &mut inventory, ItemId(2), 60));
+        assert_eq!(loot.gold, 30);
+        assert_eq!(inventory.items, vec![ItemId(1), ItemId(2)]);
+
+        assert!(!sell_item(&mut loot, &mut inventory, ItemId(3), 60));
+        assert_eq!(loot.gold, 30);
+    }
+}
@@ -0,0 +1,180 @@
+//! Ghost mode for players whose units are all downed.
+//!
+//! The battle carries on without them, so rather than leaving them with a dead screen their cursor
+//! is set free for the rest of the battle. They can roam the map and inspect units like normal,
+//! and pressing Select pings the tile under the cursor (see [`crate::ping`]).
+//!
+//! If one of their units gets back up, they're back in the fight from their next phase.
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    assets::{
+        FontResource,
+        sounds::{SoundManagerParam, UiSound},
+    },
+    battle_menu::{BattleUiContainer, player_battle_ui_systems::clean_stale_menu},
+    dungeon::DungeonState,
+    grid::GridPosition,
+    grid_cursor::{Cursor, LockedOn},
+    pause_menu::BattlePauseState,
+    ping::PingMessage,
+    player::{Player, PlayerCursorState, PlayerGameStates, PlayerInputAction},
+    tr,
+    unit::{
+        Unit,
+        overlay::{OverlaysAction, OverlaysMessage},
+    },
+    unit_stats::UnitDerivedStats,
+};
+
+/// Marks the cursor of a player with nothing left to command
+#[derive(Component)]
+pub struct Spectating;
+
+/// The label floating over a spectating player's cursor
+#[derive(Component)]
+pub struct SpectatingLabel;
+
+pub fn spectator_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (enter_spectator_mode, leave_spectator_mode, ping_on_select)
+            .chain()
+            .run_if(in_state(DungeonState::InBattle))
+            .run_if(in_state(BattlePauseState::Running)),
+    );
+}
+
+/// Whether every unit `player` controls is downed. Players that don't control anything aren't in
+/// the battle at all, so they don't count.
+fn all_units_downed<'a>(
+    player: &Player,
+    units: impl Iterator<Item = (&'a Player, &'a UnitDerivedStats)>,
+) -> bool {
+    let mut owned = units.filter(|(p, _)| *p == player).peekable();
+    owned.peek().is_some() && owned.all(|(_, stats)| stats.downed())
+}
+
+/// Frees the cursor of anyone whose units are all downed
+#[allow(clippy::too_many_arguments)]
+pub fn enter_spectator_mode(
+    mut commands: Commands,
+    fonts: Res<FontResource>,
+    mut player_states: ResMut<PlayerGameStates>,
+    cursors: Query<(Entity, &Player), (With<Cursor>, Without<Spectating>)>,
+    units: Query<(&Player, &UnitDerivedStats), With<Unit>>,
+    battle_ui: Query<(&Player, &BattleUiContainer)>,
+    mut overlay_writer: MessageWriter<OverlaysMessage>,
+    sounds: SoundManagerParam,
+) {
+    for (cursor, player) in cursors {
+        if !all_units_downed(player, units.iter()) {
+            continue;
+        }
+
+        info!("{:?} has no units left standing, spectating", player);
+
+        for (_, ui_container) in battle_ui.iter().filter(|(p, _)| *p == player) {
+            for menu in ui_container.menus() {
+                clean_stale_menu(&mut commands, menu);
+            }
+        }
+
+        if let Some(state) = player_states.player_state.get_mut(player) {
+            state.cursor_state = PlayerCursorState::Idle;
+        }
+        overlay_writer.write(OverlaysMessage {
+            player: *player,
+            action: OverlaysAction::Despawn,
+        });
+
+        commands
+            .entity(cursor)
+            .remove::<LockedOn>()
+            .insert(Spectating)
+            .with_child((
+                Text2d(tr!("spectator.label")),
+                TextFont {
+                    font: fonts.pixelify_sans_regular.clone(),
+                    font_size: 10.,
+                    font_smoothing: bevy::text::FontSmoothing::None,
+                    ..Default::default()
+                },
+                TextBackgroundColor(Color::BLACK.with_alpha(0.5)),
+                Transform::from_translation(Vec3::new(0., 16., 60.)),
+                SpectatingLabel,
+            ));
+        sounds.play_ui_sound(&mut commands, UiSound::CloseMenu);
+    }
+}
+
+/// Puts a player back in control once one of their units is back up. The battle menu comes back
+/// at the start of their next phase.
+pub fn leave_spectator_mode(
+    mut commands: Commands,
+    cursors: Query<(Entity, &Player, &Children), (With<Cursor>, With<Spectating>)>,
+    labels: Query<(), With<SpectatingLabel>>,
+    units: Query<(&Player, &UnitDerivedStats), With<Unit>>,
+) {
+    for (cursor, player, children) in cursors {
+        if !units
+            .iter()
+            .any(|(p, stats)| p == player && !stats.downed())
+        {
+            continue;
+        }
+
+        info!("{:?} is back in the fight", player);
+        commands.entity(cursor).remove::<Spectating>();
+        for label in children.iter().filter(|t| labels.contains(*t)) {
+            commands.entity(label).despawn();
+        }
+    }
+}
+
+/// Spectators have nothing else to do with Select, so it pings the tile under their cursor
+pub fn ping_on_select(
+    input_query: Query<(&Player, &ActionState<PlayerInputAction>)>,
+    cursors: Query<(&Player, &GridPosition), (With<Spectating>, Without<LockedOn>)>,
+    mut writer: MessageWriter<PingMessage>,
+) {
+    for (player, action_state) in input_query {
+        if !action_state.just_pressed(&PlayerInputAction::Select) {
+            continue;
+        }
+
+        if let Some((_, position)) = cursors.iter().find(|(p, _)| *p == player) {
+            writer.write(PingMessage {
+                player: *player,
+                position: *position,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unit_stats::{StatContainer, StatType, StatValue};
+
+    fn stats(health: f32) -> UnitDerivedStats {
+        let mut stats = StatContainer::default();
+        stats.with_stat(StatType::Health, StatValue(health));
+        UnitDerivedStats { stats }
+    }
+
+    #[test]
+    fn test_spectating_needs_every_unit_downed() {
+        let one = Player::PlayerId(1);
+        let two = Player::PlayerId(2);
+        let units = [(one, stats(0.)), (one, stats(10.)), (two, stats(0.))];
+        let iter = || units.iter().map(|(p, s)| (p, s));
+
+        assert!(!all_units_downed(&one, iter()));
+        assert!(all_units_downed(&two, iter()));
+        // Nobody to spectate for a player with no units
+        assert!(!all_units_downed(&Player::PlayerId(3), iter()));
+    }
+}
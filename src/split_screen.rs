@@ -0,0 +1,309 @@
+//! Splitting the screen between players in co-op battles.
+//!
+//! On a big map the players' cursors can end up a long way apart, and one shared camera can only
+//! look at one of them. With [`SplitScreenSettings`] turned on, every battle with two or more
+//! players gives each of them their own [`SplitScreenCamera`], in their own part of the window,
+//! following their own cursor, which draws the world along with that player's own
+//! [render layer](Player::render_layer). Each player's battle panel moves into their part of the
+//! window too.
+//!
+//! The [`MainCamera`] sticks around on top of the split views, but stops drawing the world. It
+//! only draws the UI everyone shares (the turn order, the battle log, banners and so on).
+//!
+//! Everything goes back to one camera as soon as the battle's over, or if it drops to one player.
+
+use bevy::{camera::Viewport, camera::visibility::RenderLayers, prelude::*, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    battle::BattleEntity,
+    battle_menu::{BattleUiContainer, BattleUiRoot},
+    camera::{MainCamera, glide_towards, shared_view_layers},
+    dungeon::DungeonState,
+    grid_cursor::Cursor,
+    player::{Player, RegisteredBattlePlayers},
+    tr,
+};
+
+/// How wide a player's battle panel is in their own part of the window
+const SPLIT_PANEL_WIDTH: f32 = 80.;
+
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SplitScreenSettings {
+    pub enabled: bool,
+}
+
+impl SplitScreenSettings {
+    pub fn text(enabled: bool) -> String {
+        if enabled {
+            tr!("settings.split_screen.on")
+        } else {
+            tr!("settings.split_screen.off")
+        }
+    }
+}
+
+/// One player's view of the battle
+#[derive(Component, Debug)]
+pub struct SplitScreenCamera {
+    pub player: Player,
+}
+
+/// A player's battle panel that's been moved into their part of the window, and how to put it back
+#[derive(Component, Debug)]
+struct SplitScreenUi {
+    root: Entity,
+    camera: Entity,
+    width: Val,
+}
+
+pub fn split_screen_plugin(app: &mut App) {
+    // Not just during battles, since it needs to put everything back after one
+    app.add_systems(
+        Update,
+        (sync_split_screen_cameras, follow_cursors, anchor_battle_ui).chain(),
+    );
+}
+
+/// Where each player's view goes in a window of `size`, as each one's top left corner and size.
+///
+/// Two players go side by side, three or four get a quarter each, except that a third player gets
+/// the whole bottom half so none of the window is left empty.
+pub fn split_viewports(count: usize, size: UVec2) -> Vec<(UVec2, UVec2)> {
+    let half = size / 2;
+    let rest = size - half;
+    match count {
+        0 | 1 => Vec::new(),
+        2 => vec![
+            (UVec2::ZERO, UVec2::new(half.x, size.y)),
+            (UVec2::new(half.x, 0), UVec2::new(rest.x, size.y)),
+        ],
+        _ => {
+            let mut viewports = vec![
+                (UVec2::ZERO, half),
+                (UVec2::new(half.x, 0), UVec2::new(rest.x, half.y)),
+            ];
+            if count == 3 {
+                viewports.push((UVec2::new(0, half.y), UVec2::new(size.x, rest.y)));
+            } else {
+                viewports.push((UVec2::new(0, half.y), UVec2::new(half.x, rest.y)));
+                viewports.push((half, rest));
+            }
+            viewports
+        }
+    }
+}
+
+/// Everyone who should get their own view right now, in player order
+fn split_players(
+    settings: &SplitScreenSettings,
+    dungeon_state: Option<&State<DungeonState>>,
+    registered_players: Option<&RegisteredBattlePlayers>,
+) -> Vec<Player> {
+    let in_battle = dungeon_state.is_some_and(|t| *t.get() == DungeonState::InBattle);
+    let Some(registered_players) = registered_players.filter(|_| settings.enabled && in_battle)
+    else {
+        return Vec::new();
+    };
+
+    let mut players = registered_players
+        .save_files
+        .keys()
+        .copied()
+        .collect::<Vec<_>>();
+    if players.len() < 2 {
+        return Vec::new();
+    }
+    players.sort_by_key(|t| t.id());
+    players
+}
+
+type MainCameraQuery<'a> = (
+    Entity,
+    &'a mut Camera,
+    &'a Projection,
+    &'a Transform,
+    &'a RenderLayers,
+);
+
+/// Spawns, lays out and gets rid of the split screen cameras to match who's playing
+fn sync_split_screen_cameras(
+    mut commands: Commands,
+    settings: Res<SplitScreenSettings>,
+    dungeon_state: Option<Res<State<DungeonState>>>,
+    registered_players: Option<Res<RegisteredBattlePlayers>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    main_camera: Single<MainCameraQuery, (With<MainCamera>, Without<SplitScreenCamera>)>,
+    mut split_cameras: Query<
+        (Entity, &SplitScreenCamera, &mut Camera, &mut Projection),
+        Without<MainCamera>,
+    >,
+) {
+    let players = split_players(
+        &settings,
+        dungeon_state.as_deref(),
+        registered_players.as_deref(),
+    );
+    let viewports = split_viewports(players.len(), window.physical_size());
+    let (main_entity, mut main, main_projection, main_transform, main_layers) =
+        main_camera.into_inner();
+    let main_hidden = *main_layers == RenderLayers::none();
+
+    // The main camera only draws the world when nobody has their own view of it
+    if players.is_empty() && main_hidden {
+        commands.entity(main_entity).insert(shared_view_layers());
+        main.clear_color = ClearColorConfig::Default;
+    } else if !players.is_empty() && !main_hidden {
+        commands.entity(main_entity).insert(RenderLayers::none());
+        main.clear_color = ClearColorConfig::None;
+    }
+
+    let mut has_camera = Vec::new();
+    for (entity, split, mut camera, mut projection) in split_cameras.iter_mut() {
+        let Some(index) = players.iter().position(|t| *t == split.player) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+
+        let (position, size) = viewports[index];
+        camera.viewport = Some(Viewport {
+            physical_position: position,
+            physical_size: size,
+            ..Default::default()
+        });
+        camera.order = -1 - index as isize;
+        // Zooming still zooms everyone
+        *projection = main_projection.clone();
+        has_camera.push(split.player);
+    }
+
+    for (index, player) in players.iter().enumerate() {
+        if has_camera.contains(player) {
+            continue;
+        }
+
+        let (position, size) = viewports[index];
+        commands.spawn((
+            Name::new(format!("Split Screen Camera {:?}", player)),
+            Camera2d,
+            Camera {
+                order: -1 - index as isize,
+                viewport: Some(Viewport {
+                    physical_position: position,
+                    physical_size: size,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            main_projection.clone(),
+            *main_transform,
+            RenderLayers::from_layers(&[0, player.render_layer()]),
+            SplitScreenCamera { player: *player },
+        ));
+    }
+}
+
+fn follow_cursors(
+    time: Res<Time>,
+    cursors: Query<(&Player, &Transform), (With<Cursor>, Without<SplitScreenCamera>)>,
+    mut cameras: Query<(&SplitScreenCamera, &mut Transform)>,
+) {
+    for (split, mut transform) in cameras.iter_mut() {
+        if let Some((_, cursor)) = cursors.iter().find(|(p, _)| **p == split.player) {
+            glide_towards(
+                &mut transform,
+                cursor.translation.truncate(),
+                time.delta_secs(),
+            );
+        }
+    }
+}
+
+/// Moves each player's battle panel into their own part of the window, and back into the shared
+/// row along the bottom once they don't have one
+fn anchor_battle_ui(
+    mut commands: Commands,
+    cameras: Query<(Entity, &SplitScreenCamera)>,
+    battle_ui: Query<(&Player, &ChildOf), With<BattleUiContainer>>,
+    mut panels: Query<(&mut Node, Option<&SplitScreenUi>)>,
+    battle_ui_root: Query<Entity, With<BattleUiRoot>>,
+) {
+    for (player, child_of) in battle_ui.iter() {
+        let panel = child_of.parent();
+        let Ok((mut node, anchored)) = panels.get_mut(panel) else {
+            continue;
+        };
+        let camera = cameras
+            .iter()
+            .find(|(_, t)| t.player == *player)
+            .map(|(e, _)| e);
+
+        match (camera, anchored) {
+            (Some(camera), None) => {
+                let root = commands
+                    .spawn((
+                        Name::new(format!("SplitScreenUi {:?}", player)),
+                        Node {
+                            display: Display::Flex,
+                            align_self: AlignSelf::FlexEnd,
+                            width: percent(100),
+                            height: percent(40),
+                            justify_content: JustifyContent::Center,
+                            ..Default::default()
+                        },
+                        UiTargetCamera(camera),
+                        BattleEntity {},
+                    ))
+                    .add_child(panel)
+                    .id();
+                commands.entity(panel).insert(SplitScreenUi {
+                    root,
+                    camera,
+                    width: node.width,
+                });
+                node.width = percent(SPLIT_PANEL_WIDTH);
+            }
+            (Some(camera), Some(anchored)) if anchored.camera != camera => {
+                commands
+                    .entity(anchored.root)
+                    .insert(UiTargetCamera(camera));
+                commands.entity(panel).insert(SplitScreenUi {
+                    camera,
+                    ..*anchored
+                });
+            }
+            (None, Some(anchored)) => {
+                if let Ok(root) = battle_ui_root.single() {
+                    commands.entity(root).add_child(panel);
+                }
+                node.width = anchored.width;
+                commands.entity(anchored.root).despawn();
+                commands.entity(panel).remove::<SplitScreenUi>();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_viewports_fill_the_window() {
+        let size = UVec2::new(1921, 1081);
+        assert!(split_viewports(1, size).is_empty());
+
+        for count in 2..=4 {
+            let viewports = split_viewports(count, size);
+            assert_eq!(viewports.len(), count);
+
+            let area: u32 = viewports.iter().map(|(_, t)| t.x * t.y).sum();
+            assert_eq!(area, size.x * size.y, "{} players", count);
+            for (position, viewport) in viewports {
+                assert!(position.x + viewport.x <= size.x);
+                assert!(position.y + viewport.y <= size.y);
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Where the enemy could hit you next phase.
+//!
+//! The [`ThreatMap`] is the union of every tile each enemy could attack after moving as far as
+//! it can. It's intentionally pessimistic: enemies get their full Movement stat, and we assume
+//! everything hits.
+//!
+//! Allies run on the same AI as enemies, so "enemy" here means anyone AI controlled on a team
+//! that's [against](Team::against_me) the units being threatened.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    combat::{
+        calculate_damage,
+        skills::{ATTACK_SKILL_ID, SkillDBResource},
+    },
+    enemy::behaviors::{Behavior, EnemyAiBehavior},
+    grid::{GridManagerResource, GridPosition},
+    player::Player,
+    unit::{MovementRequest, Team, Unit, build_attack_space_options, get_valid_moves_for_unit},
+    unit_stats::{StatType, UnitDerivedStats},
+};
+
+/// Every tile an enemy could attack next phase, and who could attack it.
+#[derive(Debug, Clone, Default)]
+pub struct ThreatMap {
+    pub threatened_tiles: HashMap<GridPosition, HashSet<Entity>>,
+}
+
+impl ThreatMap {
+    pub fn attackers_of(&self, position: &GridPosition) -> impl Iterator<Item = &Entity> {
+        self.threatened_tiles
+            .get(position)
+            .into_iter()
+            .flat_map(|t| t.iter())
+    }
+
+    pub fn is_threatened(&self, position: &GridPosition) -> bool {
+        self.threatened_tiles.contains_key(position)
+    }
+}
+
+/// A Player unit that might not survive the next enemy phase if it stays put.
+#[derive(Debug, Clone)]
+pub struct AtRiskUnit {
+    pub entity: Entity,
+    pub name: String,
+    pub potential_damage: u32,
+}
+
+type ThreateningUnit<'a> = (
+    Entity,
+    &'a Unit,
+    &'a UnitDerivedStats,
+    &'a GridPosition,
+    &'a EnemyAiBehavior,
+);
+
+#[derive(SystemParam)]
+pub struct ThreatMapParam<'w, 's> {
+    grid_manager: Res<'w, GridManagerResource>,
+    skill_db: Res<'w, SkillDBResource>,
+    enemies: Query<'w, 's, ThreateningUnit<'static>>,
+    // Used for obstruction checks when figuring out where enemies can move
+    units: Query<'w, 's, (Entity, &'static Unit, &'static UnitDerivedStats)>,
+    player_units: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Player,
+            &'static Unit,
+            &'static UnitDerivedStats,
+            &'static GridPosition,
+        ),
+    >,
+}
+
+impl<'w, 's> ThreatMapParam<'w, 's> {
+    /// Every tile that `team` could be attacked on next phase
+    pub fn build(&self, team: &Team) -> ThreatMap {
+        let grid_manager = &self.grid_manager.grid_manager;
+        let attack_skill = self.skill_db.skill_db.get_skill(&ATTACK_SKILL_ID);
+
+        let mut threat_map = ThreatMap::default();
+        for (enemy, unit, stats, position, behavior) in self.enemies.iter() {
+            if !team.against_me(&unit.team) {
+                continue;
+            }
+            // Pacifists are all talk
+            if stats.downed() || matches!(behavior.behavior, Behavior::Pacifist) {
+                continue;
+            }
+
+            let valid_moves = get_valid_moves_for_unit(
+                grid_manager,
+                MovementRequest {
+                    origin: *position,
+                    unit: unit.clone(),
+                    movement_points_available: stats.stats.stat(StatType::Movement).0 as u32,
+                },
+                self.units.as_readonly(),
+            );
+
+            let reachable = valid_moves.keys().chain(std::iter::once(position));
+            for tile in reachable {
+                for target in
+                    build_attack_space_options(grid_manager, &attack_skill.targeting, tile)
+                {
+                    threat_map
+                        .threatened_tiles
+                        .entry(target)
+                        .or_default()
+                        .insert(enemy);
+                }
+            }
+        }
+
+        threat_map
+    }
+
+    /// Units belonging to `player` that every threatening enemy could gang up on and down.
+    pub fn units_at_risk(&self, player: &Player) -> Vec<AtRiskUnit> {
+        let attack_skill = self.skill_db.skill_db.get_skill(&ATTACK_SKILL_ID);
+
+        let mut threat_maps = HashMap::new();
+        let mut at_risk = Vec::new();
+        for (entity, unit_player, unit, stats, position) in self.player_units.iter() {
+            if unit_player != player || stats.downed() {
+                continue;
+            }
+
+            let threat_map = threat_maps
+                .entry(unit.team)
+                .or_insert_with(|| self.build(&unit.team));
+            let potential_damage: u32 = threat_map
+                .attackers_of(position)
+                .filter_map(|attacker| self.enemies.get(*attacker).ok())
+                .map(|(_, _, attacker_stats, _, _)| {
+                    calculate_damage(Some(attacker_stats), stats, &attack_skill.actions)
+                        .min(0)
+                        .unsigned_abs()
+                })
+                .sum();
+
+            if potential_damage as f32 >= stats.stats.stat(StatType::Health).0 {
+                at_risk.push(AtRiskUnit {
+                    entity,
+                    name: unit.name.clone(),
+                    potential_damage,
+                });
+            }
+        }
+
+        at_risk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        combat::skills::setup_skill_system,
+        grid::GridManager,
+        unit::{ALLY_TEAM, ENEMY_TEAM, ObstacleType, PLAYER_TEAM},
+        unit_stats::{StatContainer, StatValue},
+    };
+
+    fn spawn_unit(world: &mut World, team: Team, position: GridPosition, health: f32) -> Entity {
+        let stats = StatContainer::new()
+            .with_stat(StatType::Health, StatValue(health))
+            .with_stat(StatType::MaxHealth, StatValue(health))
+            .with_stat(StatType::Movement, StatValue(0.))
+            .to_owned();
+        world
+            .spawn((
+                Unit {
+                    team,
+                    obstacle: ObstacleType::Filter(HashSet::from([team])),
+                    name: format!("{:?}", team),
+                },
+                UnitDerivedStats { stats },
+                position,
+            ))
+            .id()
+    }
+
+    #[test]
+    fn test_allies_dont_threaten_players() -> anyhow::Result<()> {
+        let mut world = World::new();
+        world.insert_resource(GridManagerResource {
+            grid_manager: GridManager::new(6, 6),
+        });
+        world
+            .run_system_once(setup_skill_system)
+            .map_err(|e| anyhow::anyhow!("Failed to run system: {:?}", e))?;
+
+        let player = Player::PlayerId(1);
+        let player_unit = spawn_unit(&mut world, PLAYER_TEAM, GridPosition { x: 2, y: 2 }, 1.);
+        world.entity_mut(player_unit).insert(player);
+        let ally = spawn_unit(&mut world, ALLY_TEAM, GridPosition { x: 3, y: 2 }, 10.);
+        world.entity_mut(ally).insert(EnemyAiBehavior {
+            behavior: Behavior::Berserker,
+        });
+
+        let at_risk = world
+            .run_system_once(move |threats: ThreatMapParam| threats.units_at_risk(&player))
+            .map_err(|e| anyhow::anyhow!("Failed to run system: {:?}", e))?;
+        assert!(at_risk.is_empty(), "{:?}", at_risk);
+
+        // The same unit on the other side is a problem
+        let enemy = spawn_unit(&mut world, ENEMY_TEAM, GridPosition { x: 2, y: 3 }, 10.);
+        world.entity_mut(enemy).insert(EnemyAiBehavior {
+            behavior: Behavior::Berserker,
+        });
+
+        let at_risk = world
+            .run_system_once(move |threats: ThreatMapParam| threats.units_at_risk(&player))
+            .map_err(|e| anyhow::anyhow!("Failed to run system: {:?}", e))?;
+        assert_eq!(
+            at_risk.iter().map(|t| t.entity).collect::<Vec<_>>(),
+            vec![player_unit]
+        );
+
+        Ok(())
+    }
+}
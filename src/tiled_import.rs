@@ -0,0 +1,398 @@
+//! Rooms drawn in [Tiled](https://www.mapeditor.org/), for when a layout needs more than a
+//! [`RoomTemplate`](crate::room_templates::RoomTemplate) can say.
+//!
+//! A Tiled map is turned into the same [`MapData`] the procedural rooms build, so it goes through
+//! the same battle loading path. Maps have to follow the same rules as every other room: they're
+//! drawn with the Tinytactics tileset, and units can only stand inside the two tile water border.
+//!
+//! - Every tile layer becomes a layer of the map, in order. Only CSV encoded layers are read.
+//! - Objects on a layer called `obstacles` become obstacles, by their class: `Rock1`, `Rock2`,
+//!   `Bush`, `Tree`, `Ruin` or `Fence`.
+//! - Everything else is a point object, by its class: four `PlayerStart`s, any number of `Exit`s,
+//!   `EnemySpawn`s, at most one `BossSpawn`, `AllySpawn`s for allied NPCs, and `Chest`s (with a
+//!   `loot_table` property of `Common` or `Rare`).
+//! - A tile in the tileset with `passable` set to false blocks whatever square it's the top tile
+//!   of, with its `obstacle` property if it has one, and a rock otherwise.
+//! - The map's `room_type` property says which rooms it can stand in for (`Standard` or
+//!   `BossRoom`), and an optional `biome` property pins it to a [`Biome`].
+//!
+//! Tiled counts rows down from the top of the map, while tile space counts up from the bottom, so
+//! rows get flipped on the way in.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Context;
+
+use crate::{
+    biome::Biome,
+    grid::GridPosition,
+    loot::LootTableId,
+    map_generation::{
+        ChestPlacement, LayerId, MapData, Obstacle, RoomType, TileType, to_game_space,
+    },
+};
+
+/// Tiled packs the flip flags into the top bits of each tile
+const TILED_FLIP_FLAGS: u32 = 0xE000_0000;
+
+pub struct TiledRoom {
+    pub name: String,
+    pub room_type: RoomType,
+    /// Left out to go with wherever the room lands
+    pub biome: Option<Biome>,
+    pub map_data: MapData,
+}
+
+fn parse_obstacle(name: &str) -> Option<Obstacle> {
+    let obstacle = match name {
+        "Rock1" => Obstacle::Rock1,
+        "Rock2" => Obstacle::Rock2,
+        "Bush" => Obstacle::Bush,
+        "Tree" => Obstacle::Tree,
+        "Ruin" => Obstacle::Ruin,
+        "Fence" => Obstacle::Fence,
+        _ => return None,
+    };
+    Some(obstacle)
+}
+
+fn parse_room_type(name: &str) -> Option<RoomType> {
+    match name {
+        "Standard" => Some(RoomType::Standard),
+        "BossRoom" => Some(RoomType::BossRoom),
+        _ => None,
+    }
+}
+
+fn parse_biome(name: &str) -> Option<Biome> {
+    Biome::OPTIONS
+        .into_iter()
+        .find(|t| format!("{:?}", t) == name)
+}
+
+fn parse_loot_table(name: &str) -> Option<LootTableId> {
+    match name {
+        "Common" => Some(LootTableId::Common),
+        "Rare" => Some(LootTableId::Rare),
+        _ => None,
+    }
+}
+
+fn child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    tag: &str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    node.children().filter(move |t| t.has_tag_name(tag))
+}
+
+/// The `<properties>` of a map, tile or object
+fn properties<'a>(node: roxmltree::Node<'a, '_>) -> HashMap<&'a str, &'a str> {
+    child(node, "properties")
+        .flat_map(|t| child(t, "property"))
+        .filter_map(|t| Some((t.attribute("name")?, t.attribute("value")?)))
+        .collect()
+}
+
+fn number_attribute<T: std::str::FromStr>(node: roxmltree::Node, name: &str) -> anyhow::Result<T> {
+    let value = node
+        .attribute(name)
+        .with_context(|| format!("<{}> is missing {}", node.tag_name().name(), name))?;
+    value
+        .parse::<T>()
+        .ok()
+        .with_context(|| format!("{} isn't a number: {:?}", name, value))
+}
+
+/// Reads a `.tmx` file into a room
+pub fn import_tiled_map(name: &str, tmx: &str) -> anyhow::Result<TiledRoom> {
+    let document = roxmltree::Document::parse(tmx)?;
+    let map = document.root_element();
+    if !map.has_tag_name("map") {
+        anyhow::bail!("{} isn't a Tiled map", name);
+    }
+
+    let width: u32 = number_attribute(map, "width")?;
+    let height: u32 = number_attribute(map, "height")?;
+    let tile_width: f32 = number_attribute(map, "tilewidth")?;
+    let tile_height: f32 = number_attribute(map, "tileheight")?;
+    if width < 5 || height < 5 {
+        anyhow::bail!("{} is too small to fit anyone inside the water", name);
+    }
+    // Isometric maps measure objects in tile heights along both axes
+    let object_tile_width = match map.attribute("orientation") {
+        Some("isometric") => tile_height,
+        _ => tile_width,
+    };
+
+    let map_properties = properties(map);
+    let room_type = map_properties
+        .get("room_type")
+        .and_then(|t| parse_room_type(t))
+        .with_context(|| format!("{} needs a room_type of Standard or BossRoom", name))?;
+    let biome = match map_properties.get("biome") {
+        Some(biome) => Some(
+            parse_biome(biome)
+                .with_context(|| format!("{} has an unknown biome {}", name, biome))?,
+        ),
+        None => None,
+    };
+
+    let mut tilesets = child(map, "tileset");
+    let tileset = tilesets
+        .next()
+        .with_context(|| format!("{} has no tileset", name))?;
+    if tilesets.next().is_some() {
+        anyhow::bail!("{} uses more than the Tinytactics tileset", name);
+    }
+    let first_gid: u32 = number_attribute(tileset, "firstgid")?;
+    let impassable = child(tileset, "tile")
+        .filter_map(|tile| {
+            let properties = properties(tile);
+            if properties.get("passable") != Some(&"false") {
+                return None;
+            }
+            let obstacle = properties
+                .get("obstacle")
+                .and_then(|t| parse_obstacle(t))
+                .unwrap_or(Obstacle::Rock1);
+            Some((tile.attribute("id")?.parse::<u32>().ok()?, obstacle))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let grid_size = (width, height);
+    // Tiled's rows count down, tile space counts up
+    let to_tile_space = |column: u32, row: u32| GridPosition {
+        x: column,
+        y: height - 1 - row,
+    };
+    let in_game_space =
+        |tile: GridPosition| (2..width - 2).contains(&tile.x) && (2..height - 2).contains(&tile.y);
+
+    let mut tiles = BTreeMap::new();
+    // Whichever tile ends up on top decides whether the square can be walked on
+    let mut top_tiles = HashMap::new();
+    for (layer_index, layer) in child(map, "layer").enumerate() {
+        let data = child(layer, "data")
+            .next()
+            .with_context(|| format!("{} has a layer with no data", name))?;
+        if data.attribute("encoding") != Some("csv") {
+            anyhow::bail!("{} has a layer that isn't CSV encoded", name);
+        }
+
+        let gids = data
+            .text()
+            .unwrap_or_default()
+            .split(',')
+            .map(|t| t.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("{} has a layer with a broken tile", name))?;
+        if gids.len() != (width * height) as usize {
+            anyhow::bail!("{} has a layer that doesn't fill the map", name);
+        }
+
+        let mut layer_tiles = BTreeMap::new();
+        for (i, gid) in gids.into_iter().enumerate() {
+            let gid = gid & !TILED_FLIP_FLAGS;
+            if gid == 0 {
+                continue;
+            }
+
+            let index = gid
+                .checked_sub(first_gid)
+                .with_context(|| format!("{} uses a tile from before the tileset", name))?;
+            let tile_type = TileType::from_texture_index(index)
+                .with_context(|| format!("{} uses tile {} which we can't draw", name, index))?;
+            let position = to_tile_space(i as u32 % width, i as u32 / width);
+            top_tiles.insert(position, index);
+            layer_tiles.insert(position, tile_type);
+        }
+        tiles.insert(LayerId(layer_index as u32), layer_tiles);
+    }
+
+    let mut obstacles = top_tiles
+        .into_iter()
+        .filter(|(position, _)| in_game_space(*position))
+        .filter_map(|(position, index)| Some((to_game_space(position), *impassable.get(&index)?)))
+        .collect::<HashMap<_, _>>();
+
+    let mut player_start_locations = Vec::new();
+    let mut bridge_end_locations = Vec::new();
+    let mut enemy_spawns = Vec::new();
+    let mut boss_spawn = None;
+    let mut ally_spawns = Vec::new();
+    let mut chests = Vec::new();
+    for group in child(map, "objectgroup") {
+        let is_obstacle_layer = group.attribute("name") == Some("obstacles");
+        for object in child(group, "object") {
+            let class = object
+                .attribute("class")
+                .or_else(|| object.attribute("type"))
+                .unwrap_or_default();
+            let x: f32 = number_attribute(object, "x")?;
+            let y: f32 = number_attribute(object, "y")?;
+            let (column, row) = (
+                (x / object_tile_width).floor() as u32,
+                (y / tile_height).floor() as u32,
+            );
+            if row >= height || !in_game_space(to_tile_space(column, row)) {
+                anyhow::bail!("{} has a {} out in the water", name, class);
+            }
+            let position = to_game_space(to_tile_space(column, row));
+
+            if is_obstacle_layer {
+                let obstacle = parse_obstacle(class)
+                    .with_context(|| format!("{} has an unknown obstacle {:?}", name, class))?;
+                obstacles.insert(position, obstacle);
+                continue;
+            }
+
+            match class {
+                "PlayerStart" => player_start_locations.push(position),
+                "Exit" => bridge_end_locations.push(position),
+                "EnemySpawn" => enemy_spawns.push(position),
+                "BossSpawn" if boss_spawn.is_none() => boss_spawn = Some(position),
+                "BossSpawn" => anyhow::bail!("{} has more than one boss", name),
+                "AllySpawn" => ally_spawns.push(position),
+                "Chest" => {
+                    let loot_table = properties(object)
+                        .get("loot_table")
+                        .map(|t| {
+                            parse_loot_table(t).with_context(|| {
+                                format!("{} has a chest with an unknown loot table {}", name, t)
+                            })
+                        })
+                        .transpose()?
+                        .unwrap_or(LootTableId::Common);
+                    chests.push(ChestPlacement {
+                        position,
+                        loot_table,
+                    });
+                }
+                _ => anyhow::bail!("{} has an unknown object {:?}", name, class),
+            }
+        }
+    }
+
+    let player_start_locations: [GridPosition; 4] =
+        player_start_locations.try_into().map_err(|t: Vec<_>| {
+            anyhow::anyhow!("{} has {} player starts instead of 4", name, t.len())
+        })?;
+    match (room_type, bridge_end_locations.is_empty()) {
+        (RoomType::Standard, true) => anyhow::bail!("{} has no way out", name),
+        (RoomType::BossRoom, false) => anyhow::bail!("{} lets you skip the boss", name),
+        _ => {}
+    }
+    if let Some(position) = player_start_locations
+        .iter()
+        .chain(&bridge_end_locations)
+        .chain(chests.iter().map(|t| &t.position))
+        .chain(&enemy_spawns)
+        .chain(&boss_spawn)
+        .chain(&ally_spawns)
+        .find(|t| obstacles.contains_key(t))
+    {
+        anyhow::bail!("{} has something in the way at {:?}", name, position);
+    }
+
+    Ok(TiledRoom {
+        name: map_properties
+            .get("name")
+            .map(|t| t.to_string())
+            .unwrap_or_else(|| name.to_string()),
+        room_type,
+        biome,
+        map_data: MapData {
+            biome: biome.unwrap_or_default(),
+            grid_size,
+            tiles,
+            player_start_locations,
+            bridge_start_locations: [player_start_locations[0], player_start_locations[1]],
+            bridge_end_locations,
+            obstacles,
+            patrol_routes: Vec::new(),
+            enemy_spawns,
+            boss_spawn,
+            ally_spawns,
+            chests,
+            elevation: HashMap::new(),
+            decorations: Vec::new(),
+        },
+    })
+}
+
+pub(crate) fn raw_tiled_maps() -> [(&'static str, &'static str); 1] {
+    [(
+        "sunken_courtyard.tmx",
+        include_str!("../assets/rooms/sunken_courtyard.tmx"),
+    )]
+}
+
+/// The Tiled maps that can stand in for a room of `room_type`. Broken maps get skipped.
+pub fn tiled_rooms(room_type: &RoomType) -> Vec<TiledRoom> {
+    raw_tiled_maps()
+        .into_iter()
+        .filter_map(|(name, tmx)| match import_tiled_map(name, tmx) {
+            Ok(room) => Some(room),
+            Err(e) => {
+                bevy::log::error!("Skipping a broken Tiled map: {:?}", e);
+                None
+            }
+        })
+        .filter(|t| t.room_type == *room_type)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_maps_import() {
+        for (name, tmx) in raw_tiled_maps() {
+            let room = match import_tiled_map(name, tmx) {
+                Ok(room) => room,
+                Err(e) => panic!("{:?}", e),
+            };
+            assert_eq!(room.map_data.tiles.len(), 2);
+            assert!(!room.map_data.enemy_spawns.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_objects_land_in_game_space() {
+        let tmx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="isometric" width="5" height="5" tilewidth="32" tileheight="16">
+ <properties>
+  <property name="room_type" value="BossRoom"/>
+ </properties>
+ <tileset firstgid="1" name="tinytactics" tilewidth="32" tileheight="32" columns="16"/>
+ <layer id="1" name="ground" width="5" height="5">
+  <data encoding="csv">
+34,34,34,34,34,
+34,34,34,34,34,
+34,34,2,34,34,
+34,34,34,34,34,
+34,34,34,34,34
+</data>
+ </layer>
+ <objectgroup id="2" name="spawns">
+  <object id="1" class="PlayerStart" x="32" y="32"><point/></object>
+  <object id="2" class="PlayerStart" x="32" y="32"><point/></object>
+  <object id="3" class="PlayerStart" x="32" y="32"><point/></object>
+  <object id="4" class="PlayerStart" x="32" y="32"><point/></object>
+  <object id="5" class="BossSpawn" x="40" y="40"><point/></object>
+ </objectgroup>
+</map>"#;
+        let room = import_tiled_map("test", tmx).expect("Map should import");
+        let middle = GridPosition { x: 0, y: 0 };
+        assert_eq!(room.map_data.boss_spawn, Some(middle));
+        assert_eq!(room.map_data.player_start_locations, [middle; 4]);
+        assert!(matches!(
+            room.map_data.tiles[&LayerId(0)][&GridPosition { x: 2, y: 2 }],
+            TileType::Grass(_)
+        ));
+
+        let bad = tmx.replace(r#"x="40" y="40""#, r#"x="0" y="0""#);
+        assert!(import_tiled_map("test", &bad).is_err());
+    }
+}
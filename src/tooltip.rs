@@ -0,0 +1,106 @@
+//! Extra info about whatever menu option is highlighted.
+//!
+//! Anything that can end up in a `GameMenuGrid` can carry a [`Tooltip`]. Rather than floating
+//! next to a mouse cursor that gamepad players don't have, the tooltip shows up in a panel in the
+//! corner of the screen while its option is highlighted in an active menu.
+
+use bevy::prelude::*;
+
+use crate::{
+    assets::FontResource,
+    menu::{
+        menu_navigation::{ActiveMenu, GameMenuGrid},
+        ui_consts::{UI_MENU_BACKGROUND, UI_TEXT_COLOR},
+    },
+};
+
+#[derive(Component, Debug, Clone)]
+pub struct Tooltip {
+    pub text: String,
+}
+
+impl Tooltip {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self { text: text.into() }
+    }
+}
+
+#[derive(Component)]
+pub struct TooltipPanel;
+
+#[derive(Component)]
+pub struct TooltipText;
+
+pub fn tooltip_plugin(app: &mut App) {
+    // Fonts get loaded in Startup
+    app.add_systems(PostStartup, spawn_tooltip_panel)
+        .add_systems(Update, update_tooltip_panel);
+}
+
+fn spawn_tooltip_panel(mut commands: Commands, fonts: Res<FontResource>) {
+    commands.spawn((
+        Name::new("TooltipPanel"),
+        Node {
+            display: Display::None,
+            position_type: PositionType::Absolute,
+            top: percent(1),
+            left: percent(1),
+            max_width: percent(30),
+            padding: UiRect::all(px(10)),
+            border_radius: BorderRadius::all(px(8)),
+            ..Default::default()
+        },
+        BackgroundColor(UI_MENU_BACKGROUND.with_alpha(0.9)),
+        // Above the pause menu, since the settings in there have tooltips too
+        GlobalZIndex(200),
+        TooltipPanel,
+        children![(
+            Text::default(),
+            TextColor(UI_TEXT_COLOR),
+            TextFont {
+                font: fonts.pixelify_sans_regular.clone(),
+                font_size: 20.,
+                ..Default::default()
+            },
+            TooltipText,
+        )],
+    ));
+}
+
+/// Shows the tooltips of every highlighted option in an active menu. There's usually only one,
+/// but in battle every player has their own menu open.
+pub fn update_tooltip_panel(
+    menus: Query<&GameMenuGrid, With<ActiveMenu>>,
+    tooltips: Query<&Tooltip>,
+    mut panel: Query<&mut Node, With<TooltipPanel>>,
+    mut text: Query<&mut Text, With<TooltipText>>,
+) {
+    let mut lines: Vec<&str> = Vec::new();
+    for menu in menus {
+        if let Some(tooltip) = menu
+            .get_active_menu_option()
+            .and_then(|t| tooltips.get(*t).ok())
+            && !lines.contains(&tooltip.text.as_str())
+        {
+            lines.push(&tooltip.text);
+        }
+    }
+
+    let contents = lines.join("\n\n");
+    for mut text in text.iter_mut() {
+        if text.0 != contents {
+            text.0 = contents.clone();
+        }
+    }
+
+    let display = if lines.is_empty() {
+        Display::None
+    } else {
+        Display::Flex
+    };
+    for mut node in panel.iter_mut() {
+        if node.display != display {
+            node.display = display;
+        }
+    }
+}
@@ -0,0 +1,317 @@
+//! Handing gear around in the middle of a battle.
+//!
+//! A unit standing next to a friendly one can pick "Trade" from the battle menu, choose who to
+//! trade with (or the party's [bag](crate::inventory::PartyInventory)), and then move equipment
+//! across a two column menu: their own gear on the left, the other side's on the right. Only gear
+//! changes hands, since consumables already come out of the shared bag. Each trade costs a minor
+//! action, and never any AP.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    animation::animation_db::AnimationDB,
+    assets::sprite_db::SpriteDB,
+    battle_log::BattleLogMessage,
+    battle_phase::{ActionCost, UnitPhaseResources},
+    dungeon::DungeonState,
+    equipment::{ItemDB, ItemId, UnitEquipment, equip_item_on_unit, unequip_items_on_unit},
+    gameplay_effects::ActiveEffects,
+    grid::{GridPosition, manhattan_distance},
+    inventory::PartyInventory,
+    player::RegisteredBattlePlayers,
+    save_game::SaveFileKey,
+    tr,
+    unit::{
+        Unit, UnitAction, UnitActionCompletedMessage, UnitExecuteAction, UnitExecuteActionMessage,
+        jobs::UnitJob,
+    },
+    unit_stats::UnitDerivedStats,
+};
+
+/// What it takes to hand something over
+pub const TRADE_COST: ActionCost = ActionCost {
+    move_actions: 0,
+    minor_actions: 1,
+    action_points: 0,
+};
+
+/// Somewhere gear can come from or go to in a trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TradePartner {
+    Unit(Entity),
+    /// The party's shared bag
+    Bag,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TradeOffer {
+    pub from: TradePartner,
+    pub to: TradePartner,
+    pub item: ItemId,
+}
+
+/// Whether two units are close enough to hand things to each other
+pub fn within_reach(a: &GridPosition, b: &GridPosition) -> bool {
+    manhattan_distance(a, b) == 1
+}
+
+type Trader<'a> = (
+    Entity,
+    &'a Unit,
+    &'a GridPosition,
+    &'a UnitEquipment,
+    &'a UnitDerivedStats,
+    &'a SaveFileKey,
+);
+
+/// Everything the battle menu needs to know to lay out a trade
+#[derive(SystemParam)]
+pub struct TradeParam<'w, 's> {
+    traders: Query<'w, 's, Trader<'static>>,
+    players: Res<'w, RegisteredBattlePlayers>,
+    inventory: Option<Res<'w, PartyInventory>>,
+    item_db: Res<'w, ItemDB>,
+}
+
+impl TradeParam<'_, '_> {
+    /// Friendly units standing next to `unit`, who are still up
+    pub fn neighbours(&self, unit: Entity) -> Vec<Entity> {
+        let Ok((_, trader, position, ..)) = self.traders.get(unit) else {
+            return Vec::new();
+        };
+
+        self.traders
+            .iter()
+            .filter(|(e, other, other_position, _, stats, _)| {
+                *e != unit
+                    && other.team == trader.team
+                    && !stats.downed()
+                    && within_reach(position, other_position)
+            })
+            .map(|t| t.0)
+            .collect()
+    }
+
+    pub fn name(&self, partner: TradePartner) -> String {
+        match partner {
+            TradePartner::Unit(e) => self
+                .traders
+                .get(e)
+                .map(|t| t.1.name.clone())
+                .unwrap_or_default(),
+            TradePartner::Bag => tr!("trade.bag"),
+        }
+    }
+
+    /// What `partner` has to offer
+    pub fn items(&self, partner: TradePartner) -> Vec<ItemId> {
+        match partner {
+            TradePartner::Unit(e) => {
+                let mut items = self
+                    .traders
+                    .get(e)
+                    .map(|t| t.3.equipped_items().map(|(_, t)| t.item_id()).collect())
+                    .unwrap_or_else(|_| Vec::new());
+                items.sort_by_key(|t| t.0);
+                items
+            }
+            TradePartner::Bag => self
+                .inventory
+                .as_ref()
+                .map(|t| t.items.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Whether `partner` could take `item`. The bag takes anything.
+    pub fn can_take(&self, partner: TradePartner, item: ItemId) -> bool {
+        let TradePartner::Unit(e) = partner else {
+            return true;
+        };
+        let Some(item) = self.item_db.equippable_item(&item) else {
+            return false;
+        };
+        self.traders
+            .get(e)
+            .ok()
+            .and_then(|t| job_of(&self.players, t.5))
+            .is_some_and(|job| item.usable_by(&job))
+    }
+
+    pub fn item_name(&self, item: ItemId) -> String {
+        self.item_db
+            .equippable_item(&item)
+            .map(|t| t.name().to_owned())
+            .unwrap_or_default()
+    }
+}
+
+fn job_of(players: &RegisteredBattlePlayers, key: &SaveFileKey) -> Option<UnitJob> {
+    players
+        .units()
+        .find(|(_, t)| t.save_file_key == *key)
+        .map(|(_, t)| t.job.clone())
+}
+
+pub fn trade_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        execute_trades.run_if(in_state(DungeonState::InBattle)),
+    );
+}
+
+type TradingUnit<'a> = (
+    &'a mut UnitEquipment,
+    &'a mut ActiveEffects,
+    &'a SaveFileKey,
+);
+
+#[allow(clippy::too_many_arguments)]
+fn execute_trades(
+    mut commands: Commands,
+    mut reader: MessageReader<UnitExecuteActionMessage>,
+    sprite_db: Res<SpriteDB>,
+    anim_db: Res<AnimationDB>,
+    item_db: Res<ItemDB>,
+    players: Res<RegisteredBattlePlayers>,
+    inventory: Option<ResMut<PartyInventory>>,
+    mut units: Query<TradingUnit>,
+    mut resources: Query<&mut UnitPhaseResources>,
+    mut battle_log: MessageWriter<BattleLogMessage>,
+    mut completed_writer: MessageWriter<UnitActionCompletedMessage>,
+) {
+    let Some(mut inventory) = inventory else {
+        return;
+    };
+
+    for message in reader.read() {
+        let UnitExecuteAction::Trade(offer) = message.action else {
+            continue;
+        };
+
+        if let Ok(mut resources) = resources.get_mut(message.entity) {
+            resources.spend(&TRADE_COST);
+        }
+
+        match hand_over(
+            &mut commands,
+            &sprite_db,
+            &anim_db,
+            &item_db,
+            &players,
+            &mut inventory,
+            &mut units,
+            offer,
+        ) {
+            Ok(()) => {
+                battle_log.write(BattleLogMessage::ItemTraded {
+                    from: match offer.from {
+                        TradePartner::Unit(e) => Some(e),
+                        TradePartner::Bag => None,
+                    },
+                    to: match offer.to {
+                        TradePartner::Unit(e) => Some(e),
+                        TradePartner::Bag => None,
+                    },
+                    item: item_db
+                        .equippable_item(&offer.item)
+                        .map(|t| t.name().to_owned())
+                        .unwrap_or_default(),
+                });
+            }
+            Err(e) => error!("Trade fell through: {:?}", e),
+        }
+
+        // The unit gets their turn back either way
+        completed_writer.write(UnitActionCompletedMessage {
+            unit: message.entity,
+            action: UnitAction::Trade,
+        });
+    }
+}
+
+/// Moves the item over. Whatever the receiver had to take off to make room goes in the bag, and so
+/// does the item itself if they can't hold it after all, so nothing ever goes missing.
+#[allow(clippy::too_many_arguments)]
+fn hand_over(
+    commands: &mut Commands,
+    sprite_db: &SpriteDB,
+    anim_db: &AnimationDB,
+    item_db: &ItemDB,
+    players: &RegisteredBattlePlayers,
+    inventory: &mut PartyInventory,
+    units: &mut Query<TradingUnit>,
+    offer: TradeOffer,
+) -> anyhow::Result<()> {
+    let Some(item) = item_db.equippable_item(&offer.item) else {
+        anyhow::bail!("No item for {:?}", offer.item);
+    };
+
+    match offer.from {
+        TradePartner::Bag => {
+            if !inventory.remove_item(offer.item) {
+                anyhow::bail!("The bag doesn't have {}", item.name());
+            }
+        }
+        TradePartner::Unit(giver) => {
+            let (mut equipment, mut effects, _) = units.get_mut(giver)?;
+            if !equipment
+                .equipped_items()
+                .any(|(_, t)| t.item_id() == offer.item)
+            {
+                anyhow::bail!("{:?} isn't holding {}", giver, item.name());
+            }
+            unequip_items_on_unit(commands, &mut equipment, &mut effects, giver, item.slot())?;
+        }
+    }
+
+    let TradePartner::Unit(receiver) = offer.to else {
+        inventory.add_item(offer.item);
+        return Ok(());
+    };
+
+    let (mut equipment, mut effects, key) = units.get_mut(receiver)?;
+    let Some(job) = job_of(players, key) else {
+        inventory.add_item(offer.item);
+        anyhow::bail!("No save file for {:?}", receiver);
+    };
+
+    let bumped = equipment
+        .equipped_items()
+        .filter(|(slot, _)| slot.overlaps(&item.slot()))
+        .map(|(_, t)| t.item_id())
+        .collect::<Vec<_>>();
+    if let Err(e) = equip_item_on_unit(
+        commands,
+        sprite_db,
+        anim_db,
+        &mut equipment,
+        &mut effects,
+        receiver,
+        &job,
+        item,
+    ) {
+        inventory.add_item(offer.item);
+        return Err(e);
+    }
+    for item in bumped {
+        inventory.add_item(item);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_neighbours_are_within_reach() {
+        let origin = GridPosition { x: 3, y: 3 };
+        assert!(within_reach(&origin, &GridPosition { x: 3, y: 4 }));
+        assert!(within_reach(&origin, &GridPosition { x: 2, y: 3 }));
+        assert!(!within_reach(&origin, &origin));
+        assert!(!within_reach(&origin, &GridPosition { x: 4, y: 4 }));
+        assert!(!within_reach(&origin, &GridPosition { x: 3, y: 5 }));
+    }
+}
@@ -0,0 +1,342 @@
+//! Things that happen on a particular turn of a battle.
+//!
+//! Each room can carry a [`TurnEventSchedule`] of one-off events (reinforcements showing up,
+//! running out of time), and [`DungeonModifier`]s that escalate every turn once they kick in.
+//! Everything in here is driven by the [`TurnAdvancedMessage`], rather than polling the
+//! `PhaseManager`'s turn count.
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::{
+    animation::{TinytacticsAssets, animation_db::AnimationDB},
+    battle::{BattleEndCondition, BattleEntity, BattleResultResource, finish_battle},
+    battle_phase::{TurnAdvancedMessage, phase_ui::ShowBattleBannerMessage},
+    combat::skills::UnitSkills,
+    dungeon::DungeonEntity,
+    grid::{GridManagerResource, GridPosition, init_grid_to_world_transform},
+    unit::{
+        ENEMY_TEAM, Unit,
+        overlay::{OverlayKind, TileOverlayAssets},
+        spawn_enemy,
+    },
+    unit_stats::{StatType, StatValue, UnitDerivedStats, UnitStatChangeRequest},
+};
+
+/// The turn the battle is lost on if it hasn't been won yet.
+pub const DEFAULT_TURN_LIMIT: u32 = 20;
+
+/// The turn enemy reinforcements show up on.
+pub const DEFAULT_REINFORCEMENT_TURN: u32 = 4;
+
+/// How much health a unit loses for ending up in the water.
+pub const FLOOD_DAMAGE: f32 = 2.;
+
+/// How much health every unit loses each turn of a blizzard.
+pub const BLIZZARD_DAMAGE: f32 = 1.;
+
+#[derive(Debug, Clone)]
+pub enum TurnEvent {
+    /// More enemies arrive at the given positions (in game space).
+    /// Positions that are already occupied are skipped.
+    Reinforcements { positions: Vec<GridPosition> },
+    /// The battle is lost if it hasn't been won by this turn.
+    TurnLimit,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScheduledTurnEvent {
+    pub turn: u32,
+    pub event: TurnEvent,
+}
+
+/// Hazards that get a little worse every turn for the rest of the battle.
+#[derive(Debug, Clone, Copy)]
+pub enum DungeonModifier {
+    /// Starting on `starting_turn`, the water floods one more row of the room each turn,
+    /// starting from the entrance. Units standing in the water take damage.
+    RisingWater { starting_turn: u32 },
+    /// Starting on `starting_turn`, every unit still standing takes damage from the cold each turn.
+    Blizzard { starting_turn: u32 },
+}
+
+/// The turn based events for the room that's currently loaded.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct TurnEventSchedule {
+    pub events: Vec<ScheduledTurnEvent>,
+    pub modifiers: Vec<DungeonModifier>,
+}
+
+impl TurnEventSchedule {
+    pub fn with_event(mut self, turn: u32, event: TurnEvent) -> Self {
+        self.events.push(ScheduledTurnEvent { turn, event });
+        self
+    }
+
+    pub fn with_modifier(mut self, modifier: DungeonModifier) -> Self {
+        self.modifiers.push(modifier);
+        self
+    }
+
+    /// The turn the room has to be cleared by, if there is one
+    pub fn turn_limit(&self) -> Option<u32> {
+        self.events
+            .iter()
+            .filter(|t| matches!(t.event, TurnEvent::TurnLimit))
+            .map(|t| t.turn)
+            .min()
+    }
+
+    fn events_on(&self, turn: u32) -> impl Iterator<Item = &TurnEvent> {
+        self.events
+            .iter()
+            .filter(move |t| t.turn == turn)
+            .map(|t| &t.event)
+    }
+
+    /// Every wave of reinforcements that arrived before the given turn
+    pub fn reinforcements_before(&self, turn: u32) -> impl Iterator<Item = &Vec<GridPosition>> {
+        self.events
+            .iter()
+            .filter(move |t| t.turn < turn)
+            .filter_map(|t| match &t.event {
+                TurnEvent::Reinforcements { positions } => Some(positions),
+                TurnEvent::TurnLimit => None,
+            })
+    }
+
+    /// How many rows of the room are underwater on the given turn, for a room `rows` deep.
+    /// Once the whole room's underwater it stays that way.
+    pub fn flood_level(&self, turn: u32, rows: u32) -> u32 {
+        self.modifiers
+            .iter()
+            .map(|modifier| match modifier {
+                DungeonModifier::RisingWater { starting_turn } => {
+                    (turn + 1).saturating_sub(*starting_turn)
+                }
+                DungeonModifier::Blizzard { .. } => 0,
+            })
+            .max()
+            .unwrap_or(0)
+            .min(rows)
+    }
+
+    /// Whether a blizzard is blowing on the given turn
+    pub fn blizzard(&self, turn: u32) -> bool {
+        self.modifiers.iter().any(|modifier| match modifier {
+            DungeonModifier::Blizzard { starting_turn } => turn >= *starting_turn,
+            DungeonModifier::RisingWater { .. } => false,
+        })
+    }
+}
+
+/// A tile that the rising water has claimed.
+///
+/// Deliberately doesn't have a GridPosition, so it isn't tracked by the GridManager.
+#[derive(Component, Debug)]
+pub struct FloodedTile {
+    pub row: u32,
+}
+
+pub fn spawn_reinforcements(
+    mut commands: Commands,
+    mut reader: MessageReader<TurnAdvancedMessage>,
+    schedule: Option<Res<TurnEventSchedule>>,
+    grid_manager: Res<GridManagerResource>,
+    tt_assets: Res<TinytacticsAssets>,
+    anim_db: Res<AnimationDB>,
+) {
+    let Some(schedule) = schedule else {
+        return;
+    };
+
+    for message in reader.read() {
+        for event in schedule.events_on(message.turn) {
+            let TurnEvent::Reinforcements { positions } = event else {
+                continue;
+            };
+
+            info!("Reinforcements have arrived on turn {}", message.turn);
+            for (i, position) in positions.iter().enumerate() {
+                if grid_manager
+                    .grid_manager
+                    .get_by_position(position)
+                    .is_some_and(|t| !t.is_empty())
+                {
+                    continue;
+                }
+
+                spawn_reinforcement(&mut commands, i, *position, &tt_assets, &anim_db);
+            }
+        }
+    }
+}
+
+/// The name of the `index`th enemy in a wave of reinforcements
+pub fn reinforcement_name(index: usize) -> String {
+    format!("Reinforcement {}", index + 1)
+}
+
+pub fn spawn_reinforcement(
+    commands: &mut Commands,
+    index: usize,
+    position: GridPosition,
+    tt_assets: &TinytacticsAssets,
+    anim_db: &AnimationDB,
+) -> Entity {
+    spawn_enemy(
+        commands,
+        reinforcement_name(index),
+        tt_assets,
+        anim_db,
+        position,
+        tt_assets.cleric_spritesheet.clone(),
+        UnitSkills {
+            learned_skills: HashSet::new(),
+            equipped_skill_categories: Vec::new(),
+            granted_skills: HashSet::new(),
+        },
+        ENEMY_TEAM,
+    )
+}
+
+pub fn check_turn_limit(
+    mut commands: Commands,
+    mut reader: MessageReader<TurnAdvancedMessage>,
+    schedule: Option<Res<TurnEventSchedule>>,
+    battle_result: Option<Res<BattleResultResource>>,
+    mut banner_writer: MessageWriter<ShowBattleBannerMessage>,
+) {
+    let Some(schedule) = schedule else {
+        return;
+    };
+
+    // The battle's already over some other way
+    if battle_result.is_some() {
+        return;
+    }
+
+    for message in reader.read() {
+        if schedule
+            .events_on(message.turn)
+            .any(|t| matches!(t, TurnEvent::TurnLimit))
+        {
+            info!("Ran out of time on turn {}", message.turn);
+            finish_battle(
+                &mut commands,
+                &mut banner_writer,
+                BattleEndCondition::Defeat,
+            );
+            return;
+        }
+    }
+}
+
+pub fn raise_water(
+    mut commands: Commands,
+    mut reader: MessageReader<TurnAdvancedMessage>,
+    schedule: Option<Res<TurnEventSchedule>>,
+    overlay_assets: Res<TileOverlayAssets>,
+    grid_manager: Res<GridManagerResource>,
+    flooded_tiles: Query<&FloodedTile>,
+    units: Query<(Entity, &GridPosition, &UnitDerivedStats), With<Unit>>,
+    mut stat_change_writer: MessageWriter<UnitStatChangeRequest>,
+) {
+    let Some(schedule) = schedule else {
+        return;
+    };
+
+    for message in reader.read() {
+        let flood_level = schedule.flood_level(message.turn, grid_manager.grid_manager.width());
+        if flood_level == 0 {
+            continue;
+        }
+
+        let already_flooded = flooded_tiles.iter().map(|t| t.row + 1).max().unwrap_or(0);
+        for row in already_flooded..flood_level {
+            for y in 0..grid_manager.grid_manager.height() {
+                let mut transform = init_grid_to_world_transform(&GridPosition { x: row, y });
+                transform.translation.z -= 60.;
+                let mut flooded = commands.spawn((
+                    FloodedTile { row },
+                    overlay_assets.overlay_sprite(OverlayKind::Flood),
+                    transform,
+                    BattleEntity {},
+                    DungeonEntity,
+                ));
+                if let Some(symbol) = overlay_assets.overlay_symbol(OverlayKind::Flood) {
+                    flooded.with_child(symbol);
+                }
+            }
+        }
+
+        for (entity, position, stats) in units.iter() {
+            if stats.downed() || position.x >= flood_level {
+                continue;
+            }
+
+            stat_change_writer.write(UnitStatChangeRequest {
+                entity,
+                stat: StatType::Health,
+                stat_change: StatValue(-FLOOD_DAMAGE),
+            });
+        }
+    }
+}
+
+pub fn blow_blizzard(
+    mut reader: MessageReader<TurnAdvancedMessage>,
+    schedule: Option<Res<TurnEventSchedule>>,
+    units: Query<(Entity, &UnitDerivedStats), With<Unit>>,
+    mut stat_change_writer: MessageWriter<UnitStatChangeRequest>,
+) {
+    let Some(schedule) = schedule else {
+        return;
+    };
+
+    for message in reader.read() {
+        if !schedule.blizzard(message.turn) {
+            continue;
+        }
+
+        for (entity, stats) in units.iter() {
+            if stats.downed() {
+                continue;
+            }
+
+            stat_change_writer.write(UnitStatChangeRequest {
+                entity,
+                stat: StatType::Health,
+                stat_change: StatValue(-BLIZZARD_DAMAGE),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flood_rises_a_row_each_turn() {
+        let schedule = TurnEventSchedule::default()
+            .with_modifier(DungeonModifier::RisingWater { starting_turn: 3 });
+
+        assert_eq!(schedule.flood_level(2, 10), 0);
+        assert_eq!(schedule.flood_level(3, 10), 1);
+        assert_eq!(schedule.flood_level(5, 10), 3);
+    }
+
+    #[test]
+    fn test_flood_stops_at_last_row() {
+        let schedule = TurnEventSchedule::default()
+            .with_modifier(DungeonModifier::RisingWater { starting_turn: 1 });
+
+        // Long after the whole room's flooded, there's still no row past the last one
+        for turn in 1..40 {
+            assert!(schedule.flood_level(turn, 8) <= 8);
+        }
+        assert_eq!(schedule.flood_level(39, 8), 8);
+    }
+}
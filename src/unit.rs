@@ -10,28 +10,31 @@ use crate::animation::{
     AnimationFollower, Direction, FacingDirection, TinytacticsAssets, UnitAnimationKind,
     UnitAnimationPlayer,
 };
+use crate::assets::FontResource;
 use crate::assets::sound_resolvers::Voice;
 use crate::assets::sounds::{SoundManagerParam, UiSound, VoiceId};
 use crate::assets::sprite_db::SpriteDB;
 use crate::battle::{
-    BattleEntity, Enemy, UnitCommand, UnitSelectionBackMessage, UnitSelectionMessage,
+    Ally, BattleEntity, Enemy, UnitCommand, UnitSelectionBackMessage, UnitSelectionMessage,
     UnitUiCommandMessage,
 };
-use crate::battle_phase::UnitPhaseResources;
-use crate::combat::AttackIntent;
-use crate::combat::skills::{SkillDBResource, Targeting, UnitSkills};
+use crate::battle_phase::{ActionCost, ActionEconomy, UnitPhaseResources};
+use crate::combat::skills::{SkillDBResource, SkillId, Targeting, UnitSkills};
+use crate::combat::{AttackIntent, DespawnTimer};
 use crate::dungeon::DungeonEntity;
 use crate::enemy::behaviors::EnemyAiBehavior;
-use crate::equipment::{ItemDB, ItemId, UnitEquipment, equip_item_on_unit};
+use crate::equipment::{ItemDB, ItemId, PlaceholderWeapon, UnitEquipment, equip_item_on_unit};
 use crate::gameplay_effects::ActiveEffects;
 use crate::grid::{GridManager, GridMovement, GridPosition, GridVec, manhattan_distance};
 use crate::grid_cursor::LockedOn;
-use crate::map_generation::TtIndex;
+use crate::inventory::Consumable;
+use crate::map_generation::{Obstacle, TtIndex};
 use crate::player::{
     Player, PlayerCursorState, PlayerInputAction, PlayerState, RegisteredBattlePlayers,
 };
-use crate::save_game::SaveFileKey;
-use crate::unit::jobs::UnitJob;
+use crate::save_game::{SaveFileKey, UnitSaveV2};
+use crate::spectator::Spectating;
+use crate::trade::TradeOffer;
 use crate::unit::overlay::{OverlaysMessage, TileOverlayBundle};
 use crate::unit_stats::experience::UnitLevelManager;
 use crate::unit_stats::{StatContainer, StatType, StatValue, UnitBaseStats, UnitDerivedStats};
@@ -53,12 +56,20 @@ pub struct Team(u32);
 
 impl Team {
     pub fn against_me(&self, team: &Team) -> bool {
-        team != self && *team != NEUTRAL_TEAM
+        !self.allied_with(team) && *team != NEUTRAL_TEAM
+    }
+
+    /// Players and their Allies fight on the same side
+    pub fn allied_with(&self, team: &Team) -> bool {
+        let friendly = [PLAYER_TEAM, ALLY_TEAM];
+        team == self || (friendly.contains(self) && friendly.contains(team))
     }
 }
 
 pub const PLAYER_TEAM: Team = Team(1);
 pub const ENEMY_TEAM: Team = Team(2);
+/// AI controlled units fighting alongside the Players
+pub const ALLY_TEAM: Team = Team(3);
 /// Meant for obstacles? This abstraction is a bit silly atm.
 pub const NEUTRAL_TEAM: Team = Team(0);
 
@@ -90,11 +101,25 @@ pub struct UnitBundle {
     pub base_stats: UnitBaseStats,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ObstacleSprite {
     Rock,
     Bush,
     Tree,
+    Ruin,
+    Fence,
+}
+
+impl From<Obstacle> for ObstacleSprite {
+    fn from(obstacle: Obstacle) -> Self {
+        match obstacle {
+            Obstacle::Rock1 | Obstacle::Rock2 => ObstacleSprite::Rock,
+            Obstacle::Bush => ObstacleSprite::Bush,
+            Obstacle::Tree => ObstacleSprite::Tree,
+            Obstacle::Ruin => ObstacleSprite::Ruin,
+            Obstacle::Fence => ObstacleSprite::Fence,
+        }
+    }
 }
 
 impl std::fmt::Display for ObstacleSprite {
@@ -103,6 +128,8 @@ impl std::fmt::Display for ObstacleSprite {
             ObstacleSprite::Rock => write!(f, "Rock"),
             ObstacleSprite::Bush => write!(f, "Bush"),
             ObstacleSprite::Tree => write!(f, "Tree"),
+            ObstacleSprite::Ruin => write!(f, "Ruin"),
+            ObstacleSprite::Fence => write!(f, "Fence"),
         }
     }
 }
@@ -113,6 +140,8 @@ impl ObstacleSprite {
             ObstacleSprite::Rock => 64,
             ObstacleSprite::Bush => 48,
             ObstacleSprite::Tree => (TtIndex::new(5, 2).index() - 1) as usize,
+            ObstacleSprite::Ruin => (TtIndex::new(5, 3).index() - 1) as usize,
+            ObstacleSprite::Fence => (TtIndex::new(5, 4).index() - 1) as usize,
         }
     }
 }
@@ -124,8 +153,10 @@ pub fn spawn_obstacle_unit(
     obstacle_sprite_type: ObstacleSprite,
 ) -> Entity {
     let anchor = match obstacle_sprite_type {
-        ObstacleSprite::Rock => TINY_TACTICS_ANCHOR,
-        ObstacleSprite::Bush => TINY_TACTICS_ANCHOR,
+        ObstacleSprite::Rock
+        | ObstacleSprite::Bush
+        | ObstacleSprite::Ruin
+        | ObstacleSprite::Fence => TINY_TACTICS_ANCHOR,
         // The Tree sprites have a weird amount of offset, and I want them to just
         // look like they are sitting on tiles. Definitely a hack.
         ObstacleSprite::Tree => TINY_TACTICS_ANCHOR.with_y(0.10).into(),
@@ -140,6 +171,7 @@ pub fn spawn_obstacle_unit(
                 team: Team(0),
                 name: obstacle_sprite_type.to_string(),
             },
+            obstacle_sprite_type,
             BattleEntity {},
             DungeonEntity,
             Sprite {
@@ -193,7 +225,7 @@ pub fn spawn_enemy(
     spritesheet: Handle<Image>,
     skills: UnitSkills,
     team: Team,
-) {
+) -> Entity {
     let transform = crate::grid::init_grid_to_world_transform(&grid_position);
     let direction = Direction::SW;
     let animation_start_index = anim_db
@@ -247,7 +279,6 @@ pub fn spawn_enemy(
             TINY_TACTICS_ANCHOR,
             UnitPhaseResources::default(),
             (
-                Enemy {},
                 EnemyAiBehavior {
                     behavior: enemy::behaviors::Behavior::Berserker,
                 },
@@ -279,12 +310,21 @@ pub fn spawn_enemy(
                 leader: unit_e,
                 animated_sprite_id: TT_WEAPON_ANIMATED_SPRITE_ID,
             },
+            PlaceholderWeapon,
             Visibility::Hidden,
             TINY_TACTICS_ANCHOR,
         ))
         .id();
 
+    // Allies share the Enemy AI, but take their own phase
+    if team == ALLY_TEAM {
+        commands.entity(unit_e).insert(Ally {});
+    } else {
+        commands.entity(unit_e).insert(Enemy {});
+    }
+
     commands.entity(unit_e).add_child(weapon);
+    unit_e
 }
 
 pub const TINY_TACTICS_ANCHOR: Anchor = Anchor(Vec2::new(0., -0.25));
@@ -299,33 +339,31 @@ pub fn equip_starting_items_on_unit(
     sprite_db: Res<SpriteDB>,
     item_db: Res<ItemDB>,
     players: Res<RegisteredBattlePlayers>,
-    query: Query<(Entity, &mut ActiveEffects, &mut UnitEquipment, &Player)>,
+    query: Query<(Entity, &mut ActiveEffects, &mut UnitEquipment, &SaveFileKey)>,
 ) {
-    for (e, mut active_effects, mut equipment, player) in query {
-        let Some(save_file) = players.save_files.get(player) else {
+    for (e, mut active_effects, mut equipment, key) in query {
+        let Some((_, save_file)) = players.units().find(|(_, t)| t.save_file_key == *key) else {
             continue;
         };
 
-        let weapon_item_id = match save_file.job {
-            crate::unit::jobs::UnitJob::Archer => ItemId(2),
-            _ => ItemId(1),
-        };
-
-        let Some(weapon) = item_db.equippable_items.get(&weapon_item_id) else {
-            error!("No weapon for hardcoded item id?");
-            continue;
-        };
+        for item_id in &save_file.equipped_items {
+            let Some(item) = item_db.equippable_item(item_id) else {
+                error!("No item for saved item id {:?}", item_id);
+                continue;
+            };
 
-        if let Err(e) = equip_item_on_unit(
-            &mut commands,
-            &sprite_db,
-            &anim_db,
-            &mut equipment,
-            &mut active_effects,
-            e,
-            weapon.clone(),
-        ) {
-            error!("Failed to equip starting item on unit: {:?}", e);
+            if let Err(e) = equip_item_on_unit(
+                &mut commands,
+                &sprite_db,
+                &anim_db,
+                &mut equipment,
+                &mut active_effects,
+                e,
+                &save_file.job,
+                item,
+            ) {
+                error!("Failed to equip starting item on unit: {:?}", e);
+            }
         }
     }
 }
@@ -337,17 +375,29 @@ pub fn spawn_unit(
     grid_position: crate::grid::GridPosition,
     spritesheet: Handle<Image>,
     texture_atlas: TextureAtlas,
-    skills: UnitSkills,
     player: crate::player::Player,
     team: Team,
     direction: Direction,
-    job: UnitJob,
-    key: SaveFileKey,
+    save: UnitSaveV2,
 ) -> Entity {
     let transform = crate::grid::init_grid_to_world_transform(&grid_position);
-    let stats = job.default_stats();
-    let growths = job.default_growths("Hello world".to_string());
-    let level_manager = UnitLevelManager::new(growths);
+    let stats = save.base_stats;
+    let growths = save
+        .job
+        .default_growths(format!("growths-{}", save.save_file_key.uid));
+    let mut level_manager = UnitLevelManager::new(growths);
+    level_manager.restore(save.level, save.experience);
+
+    // Anything the job should know by now comes along too, in case the save was made before it
+    // could be learned
+    let skills = UnitSkills {
+        learned_skills: save
+            .learned_skills
+            .into_iter()
+            .chain(save.job.skills_learned_by(save.level))
+            .collect(),
+        ..save.job.base_unit_skills()
+    };
     let unit = commands
         .spawn((
             UnitBundle {
@@ -388,8 +438,10 @@ pub fn spawn_unit(
             BattleEntity {},
             DungeonEntity,
             skills,
+            save.job.action_economy(),
+            save.job,
             level_manager,
-            key,
+            save.save_file_key,
         ))
         .id();
     unit
@@ -419,21 +471,21 @@ fn spawn_overlays(
     commands: &mut Commands,
     tile_overlay_assets: &Res<overlay::TileOverlayAssets>,
     player: Player,
+    player_color: Color,
     grid_positions: Vec<GridPosition>,
     grid_manager: &mut GridManager,
-    index: usize,
+    kind: overlay::OverlayKind,
 ) {
     for grid_pos in grid_positions {
-        let e = commands
-            .spawn((TileOverlayBundle::new(
-                grid_pos,
-                tile_overlay_assets.tile_overlay_image_handle.clone(),
-                tile_overlay_assets.tile_overlay_atlas_layout_handle.clone(),
-                player,
-                index,
-            ),))
-            .id();
-        grid_manager.add_entity(e, grid_pos);
+        let mut overlay =
+            commands.spawn((
+                TileOverlayBundle::new(grid_pos, tile_overlay_assets, player, kind)
+                    .with_player_tint(player_color),
+            ));
+        if let Some(symbol) = tile_overlay_assets.overlay_symbol(kind) {
+            overlay.with_child(symbol);
+        }
+        grid_manager.add_entity(overlay.id(), grid_pos);
     }
 }
 
@@ -565,6 +617,13 @@ pub struct AttackOption {
     grid_position: GridPosition,
 }
 
+/// Whatever a unit is picking a target for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TargetedAction {
+    Skill(SkillId),
+    Item(Consumable),
+}
+
 // Ideally runs directly after the UnitUiCommand was emitted
 pub fn unlock_cursor_after_unit_ui_command(
     mut commands: Commands,
@@ -597,7 +656,16 @@ pub struct UnitExecuteActionMessage {
 pub enum UnitExecuteAction {
     Move(ValidMove),
     Attack(AttackIntent),
-    Interact { interactable_entity: Entity },
+    Interact {
+        interactable_entity: Entity,
+    },
+    /// Resolved by the [inventory](crate::inventory), which has the bag
+    UseItem {
+        item: Consumable,
+        target: Entity,
+    },
+    /// Resolved by [trade](crate::trade)
+    Trade(TradeOffer),
     Wait,
 }
 
@@ -616,6 +684,13 @@ pub fn execute_unit_actions(
     for message in reader.read() {
         match &message.action {
             UnitExecuteAction::Move(valid_move) => {
+                if let Ok(mut resources) = unit_phase_resources.get_mut(message.entity) {
+                    resources.spend(&ActionCost {
+                        move_actions: 1,
+                        ..Default::default()
+                    });
+                }
+
                 commands
                     .entity(message.entity)
                     .insert(GridMovement::new(valid_move.path.clone(), 0.4));
@@ -633,7 +708,15 @@ pub fn execute_unit_actions(
                     action: UnitAction::Wait,
                 });
             }
-            _ => {}
+            UnitExecuteAction::Interact { .. } => {
+                if let Ok(mut resources) = unit_phase_resources.get_mut(message.entity) {
+                    resources.spend(&ActionCost {
+                        minor_actions: 1,
+                        ..Default::default()
+                    });
+                }
+            }
+            UnitExecuteAction::UseItem { .. } | UnitExecuteAction::Trade(_) => {}
         }
     }
 }
@@ -699,18 +782,61 @@ pub fn build_attack_space_options(
     }
 }
 
+/// Every unit standing somewhere in `target_options`, keyed by where they're standing
+fn find_targets(
+    grid_manager: &GridManager,
+    target_options: &[GridPosition],
+    unit_query: &Query<(Entity, &Unit, &UnitDerivedStats)>,
+) -> HashMap<GridPosition, AttackOption> {
+    let mut options_for_attack = HashMap::new();
+
+    // Assume all units have the same attack range for now
+    for possible_attack_pos in target_options {
+        // Is there a unit that can be attacked there?
+        //
+        // TODO: Add some form of "targeting options" or something for
+        // deciding if you can cast this on an enemy or player or self or not
+        if let Some((target_entity, _, _)) = grid_manager
+            .get_by_position(possible_attack_pos)
+            .cloned()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|e| unit_query.get(*e).ok())
+            .next()
+        {
+            options_for_attack.insert(
+                *possible_attack_pos,
+                AttackOption {
+                    target: target_entity,
+                    grid_position: *possible_attack_pos,
+                },
+            );
+        }
+    }
+
+    options_for_attack
+}
+
 // TODO: Don't make this dependent on PlayerGameStates
 // We need to drive the interaction between the cursor
 // in a better way. (Which will enable us to use this for AIs too!)
 //
 // PlayerGameStates probably could just be an Event we pass.
+#[allow(clippy::too_many_arguments)]
 pub fn handle_unit_ui_command(
     grid_manager_res: Res<grid::GridManagerResource>,
     skill_db: Res<SkillDBResource>,
+    item_db: Res<ItemDB>,
     mut player_state: ResMut<player::PlayerGameStates>,
     mut unit_command_message: MessageReader<UnitUiCommandMessage>,
     mut overlay_message_writer: MessageWriter<OverlaysMessage>,
-    mut controlled_unit_query: Query<(Entity, &Unit, &mut UnitPhaseResources, &GridPosition)>,
+    mut controlled_unit_query: Query<(
+        Entity,
+        &Unit,
+        &mut UnitPhaseResources,
+        &GridPosition,
+        Option<&ActiveEffects>,
+    )>,
     unit_query: Query<(Entity, &Unit, &UnitDerivedStats)>,
     mut execute_action_writer: MessageWriter<UnitExecuteActionMessage>,
 ) {
@@ -720,7 +846,7 @@ pub fn handle_unit_ui_command(
             continue;
         };
 
-        let Some((unit_entity, unit, unit_resources, position)) =
+        let Some((unit_entity, unit, unit_resources, position, effects)) =
             controlled_unit_query.get_mut(message.unit).ok()
         else {
             log::error!("No Unit found for Command message: {:?}", message);
@@ -735,7 +861,7 @@ pub fn handle_unit_ui_command(
                 let req = MovementRequest {
                     origin: *position,
                     unit: unit.clone(),
-                    movement_points_available: unit_resources.movement_points_left_in_phase,
+                    movement_points_available: unit_resources.movement_available(),
                 };
                 let valid_moves =
                     get_valid_moves_for_unit(&grid_manager_res.grid_manager, req, unit_query);
@@ -758,54 +884,64 @@ pub fn handle_unit_ui_command(
             crate::battle::UnitCommand::UseSkill(skill_id) => {
                 let skill = skill_db.skill_db.get_skill(&skill_id);
 
-                let mut options_for_attack = Vec::new();
-
                 // TODO: It'd be nice to block this before this point
                 // in le UI
-                if unit_resources.action_points_left_in_phase < skill.cost.ap.into() {
+                if !unit_resources.can_afford(&message.command.cost(&skill_db.skill_db)) {
                     warn!("Unit is attempting to attack with no AP!");
                     continue;
                 }
 
+                let targeting = match effects {
+                    Some(effects) => skill.targeting.seen_through(effects),
+                    None => skill.targeting.clone(),
+                };
                 let target_options = build_attack_space_options(
                     &grid_manager_res.grid_manager,
-                    &skill.targeting,
+                    &targeting,
                     position,
                 );
 
-                // Assume all units have the same attack range for now
-                for possible_attack_pos in &target_options {
-                    // Is there a unit that can be attacked there?
-                    //
-                    // TODO: Add some form of "targeting options" or something for
-                    // deciding if you can cast this on an enemy or player or self or not
-                    if let Some((target_entity, _, _)) = grid_manager_res
-                        .grid_manager
-                        .get_by_position(possible_attack_pos)
-                        .cloned()
-                        .unwrap_or_default()
-                        .iter()
-                        .filter_map(|e| unit_query.get(*e).ok())
-                        .next()
-                    {
-                        options_for_attack.push(AttackOption {
-                            target: target_entity,
-                            grid_position: *possible_attack_pos,
-                        });
-                    }
+                let options_map =
+                    find_targets(&grid_manager_res.grid_manager, &target_options, &unit_query);
+
+                // I hate this abstraction lol
+                player_state.cursor_state = player::PlayerCursorState::LookingForTargetWithAttack(
+                    unit_entity,
+                    options_map,
+                    TargetedAction::Skill(skill_id),
+                );
+
+                overlay_message_writer.write(OverlaysMessage {
+                    player: message.player,
+                    action: overlay::OverlaysAction::Spawn {
+                        spawn_type: overlay::OverlaysType::Attack,
+                        positions: target_options,
+                    },
+                });
+            }
+            crate::battle::UnitCommand::UseItem(item) => {
+                if !unit_resources.can_afford(&message.command.cost(&skill_db.skill_db)) {
+                    warn!("Unit is attempting to use an item with no AP!");
+                    continue;
                 }
 
-                let options_map: HashMap<GridPosition, AttackOption> = options_for_attack
-                    .iter()
-                    .cloned()
-                    .map(|t| (t.grid_position, t))
-                    .collect();
+                let targeting = Targeting::TargetInRange(item_db.consumable(&item).range);
+                let targeting = match effects {
+                    Some(effects) => targeting.seen_through(effects),
+                    None => targeting,
+                };
+                let target_options = build_attack_space_options(
+                    &grid_manager_res.grid_manager,
+                    &targeting,
+                    position,
+                );
+                let options_map =
+                    find_targets(&grid_manager_res.grid_manager, &target_options, &unit_query);
 
-                // I hate this abstraction lol
                 player_state.cursor_state = player::PlayerCursorState::LookingForTargetWithAttack(
                     unit_entity,
                     options_map,
-                    skill_id,
+                    TargetedAction::Item(item),
                 );
 
                 overlay_message_writer.write(OverlaysMessage {
@@ -838,28 +974,81 @@ pub fn handle_unit_ui_command(
                     },
                 });
             }
+            crate::battle::UnitCommand::Trade(offer) => {
+                execute_action_writer.write(UnitExecuteActionMessage {
+                    entity: unit_entity,
+                    action: UnitExecuteAction::Trade(offer),
+                });
+            }
         }
     }
 }
 
+/// Sent when a player tries to move a unit onto a tile that's taken.
+#[derive(Message, Debug)]
+pub struct MoveRejectedMessage {
+    pub cursor: Entity,
+}
+
+/// Marker for the little "Tile occupied" text that pops up over the cursor
+#[derive(Component)]
+pub struct TileOccupiedNudge;
+
+pub fn show_tile_occupied_nudge(
+    mut commands: Commands,
+    mut reader: MessageReader<MoveRejectedMessage>,
+    fonts: Res<FontResource>,
+) {
+    for message in reader.read() {
+        commands.entity(message.cursor).with_child((
+            Text2d("Tile occupied".to_string()),
+            TextColor(Color::linear_rgb(1.0, 0.2, 0.2)),
+            TextFont {
+                font: fonts.pixelify_sans_regular.clone(),
+                font_size: 12.,
+                font_smoothing: bevy::text::FontSmoothing::None,
+                ..Default::default()
+            },
+            TileOccupiedNudge,
+            DespawnTimer {
+                timer: Timer::from_seconds(0.75, TimerMode::Once),
+            },
+            Transform::from_translation(Vec3::new(0., 24., 0.)),
+            TextBackgroundColor(Color::WHITE.with_alpha(0.5)),
+        ));
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn handle_unit_cursor_actions(
     mut commands: Commands,
-    grid_manager_res: Res<grid::GridManagerResource>,
+    mut grid_manager_res: ResMut<grid::GridManagerResource>,
     mut player_state: ResMut<player::PlayerGameStates>,
     player_query: Query<(&Player, &ActionState<PlayerInputAction>)>,
     mut cursor_query: Query<
         (Entity, &Player, &mut grid::GridPosition),
-        (With<grid_cursor::Cursor>, Without<LockedOn>),
+        // Spectators have nothing to command, see `crate::spectator`
+        (
+            With<grid_cursor::Cursor>,
+            Without<LockedOn>,
+            Without<Spectating>,
+        ),
     >,
     player_unit_query: Query<
-        (Entity, &player::Player, &Unit, &GridPosition),
+        (
+            Entity,
+            &player::Player,
+            &Unit,
+            &GridPosition,
+            Option<&UnitPhaseResources>,
+        ),
         Without<grid_cursor::Cursor>,
     >,
     mut overlay_message_writer: MessageWriter<OverlaysMessage>,
     mut unit_selection_message: MessageWriter<UnitSelectionMessage>,
     mut unit_selection_back_message: MessageWriter<UnitSelectionBackMessage>,
     mut execute_action_writer: MessageWriter<UnitExecuteActionMessage>,
+    mut move_rejected_writer: MessageWriter<MoveRejectedMessage>,
     sounds: SoundManagerParam,
 ) {
     for (player, action_state) in player_query.iter() {
@@ -876,10 +1065,29 @@ pub fn handle_unit_cursor_actions(
             // If the cursor is "idle" while viewing the map
             // and the player presses back, go back to the UI Menu.
             if player_state.cursor_state == player::PlayerCursorState::Idle {
-                if action_state.just_pressed(&PlayerInputAction::Deselect) {
-                    let Some((controlled_unit, unit_pos)) = player_unit_query
+                // Players with more than one unit pick which one to command from the map
+                if action_state.just_pressed(&PlayerInputAction::Select)
+                    && let Some(unit_e) = player_unit_query
                         .iter()
-                        .find(|t| t.1 == player)
+                        .find(|t| t.1 == player && *t.3 == *cursor_grid_pos)
+                        .map(|t| t.0)
+                {
+                    unit_selection_message.write(UnitSelectionMessage {
+                        entity: unit_e,
+                        player: *player,
+                    });
+                    commands.entity(cursor_entity).insert(LockedOn {});
+                    sounds.play_ui_sound(&mut commands, UiSound::Select);
+                } else if action_state.just_pressed(&PlayerInputAction::Deselect) {
+                    // Head back to a unit that can still act, if there is one
+                    let owned_units = player_unit_query
+                        .iter()
+                        .filter(|t| t.1 == player)
+                        .collect::<Vec<_>>();
+                    let Some((controlled_unit, unit_pos)) = owned_units
+                        .iter()
+                        .find(|t| t.4.is_some_and(|r| r.can_act()))
+                        .or(owned_units.first())
                         .map(|t| (t.0, t.3))
                     else {
                         error!("No controlled unit for player: {:?}", player);
@@ -918,7 +1126,7 @@ pub fn handle_unit_cursor_actions(
                             player_unit_query
                                 .get(*entity)
                                 .ok()
-                                .map(|(a, b, c, _)| (a, *b, c))
+                                .map(|(a, b, c, _, _)| (a, *b, c))
                         },
                     );
 
@@ -928,6 +1136,26 @@ pub fn handle_unit_cursor_actions(
                             cursor_grid_pos
                         );
                         sounds.play_ui_sound(&mut commands, UiSound::Error);
+                        move_rejected_writer.write(MoveRejectedMessage {
+                            cursor: cursor_entity,
+                        });
+                        continue;
+                    }
+
+                    // Another unit might already be on it's way to this tile
+                    if let Err(holder) = grid_manager_res
+                        .grid_manager
+                        .reserve(*cursor_grid_pos, unit_entity)
+                    {
+                        log::warn!(
+                            "Cannot move unit to position {:?} because {:?} is already moving there",
+                            cursor_grid_pos,
+                            holder
+                        );
+                        sounds.play_ui_sound(&mut commands, UiSound::Error);
+                        move_rejected_writer.write(MoveRejectedMessage {
+                            cursor: cursor_entity,
+                        });
                         continue;
                     }
 
@@ -953,7 +1181,7 @@ pub fn handle_unit_cursor_actions(
             } else if let PlayerCursorState::LookingForTargetWithAttack(
                 unit_entity,
                 mut valid_attack_moves,
-                skill_id,
+                targeted_action,
             ) = player_state.cursor_state.clone()
             {
                 if action_state.just_pressed(&PlayerInputAction::Select) {
@@ -965,13 +1193,22 @@ pub fn handle_unit_cursor_actions(
                         continue;
                     };
 
+                    let action = match targeted_action {
+                        TargetedAction::Skill(skill_id) => {
+                            UnitExecuteAction::Attack(AttackIntent {
+                                attacker: unit_entity,
+                                defender: valid_move.target,
+                                skill: skill_id,
+                            })
+                        }
+                        TargetedAction::Item(item) => UnitExecuteAction::UseItem {
+                            item,
+                            target: valid_move.target,
+                        },
+                    };
                     execute_action_writer.write(UnitExecuteActionMessage {
                         entity: unit_entity,
-                        action: UnitExecuteAction::Attack(AttackIntent {
-                            attacker: unit_entity,
-                            defender: valid_move.target,
-                            skill: skill_id,
-                        }),
+                        action,
                     });
 
                     sounds.play_ui_sound(&mut commands, UiSound::Select);
@@ -1006,6 +1243,8 @@ pub enum UnitAction {
     Attack,
     Wait,
     Interact,
+    UseItem,
+    Trade,
 }
 
 #[derive(Message, Debug)]
@@ -1015,33 +1254,47 @@ pub struct UnitActionCompletedMessage {
 }
 
 pub mod jobs {
-    use std::collections::BTreeMap;
-
-    use rand_distr::Normal;
-
     use crate::{
         assets::sprite_db::{SpriteId, TinyTacticsSprites},
         combat::skills::{SkillCategoryId, SkillId},
-        unit_stats::growths::{StatGrowth, StatGrowthClampedNormalRounded, StatGrowths},
+        gameplay_effects::{AuraData, Operator, StatModification},
+        unit_stats::growths::{GrowthRate, StatGrowths},
     };
 
     use super::*;
 
-    #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, Reflect)]
+    #[derive(
+        Component, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, Reflect,
+    )]
     pub enum UnitJob {
         Knight,
         Mage,
         Archer,
         Mercenary,
+        /// A Knight who's taken up the healing arts
+        Paladin,
+        /// A Knight who's doubled down on holding the line
+        General,
     }
 
     impl UnitJob {
+        pub const ALL: [UnitJob; 6] = [
+            UnitJob::Knight,
+            UnitJob::Mage,
+            UnitJob::Archer,
+            UnitJob::Mercenary,
+            UnitJob::Paladin,
+            UnitJob::General,
+        ];
+
         pub fn name(&self) -> String {
             match self {
                 UnitJob::Knight => "Knight".to_string(),
                 UnitJob::Mage => "Mage".to_string(),
                 UnitJob::Archer => "Archer".to_string(),
                 UnitJob::Mercenary => "Mercenary".to_string(),
+                UnitJob::Paladin => "Paladin".to_string(),
+                UnitJob::General => "General".to_string(),
             }
         }
 
@@ -1061,6 +1314,110 @@ pub mod jobs {
                 UnitJob::Mercenary => {
                     "Hit your enemies hard. Don't worry about \"playing it safe\"".to_string()
                 }
+                UnitJob::Paladin => {
+                    "Keep your allies on their feet, and strike down anyone who comes for them"
+                        .to_string()
+                }
+                UnitJob::General => {
+                    "Become the wall your enemies break themselves against".to_string()
+                }
+            }
+        }
+
+        /// Skills the job picks up on the way up, and the level each one comes at
+        pub fn skill_table(&self) -> &'static [(u32, SkillId)] {
+            match self {
+                UnitJob::Knight => &[(2, SkillId(11)), (3, SkillId(9)), (6, SkillId(3))],
+                UnitJob::Mage => &[(5, SkillId(10))],
+                UnitJob::Archer => &[(4, SkillId(6))],
+                UnitJob::Mercenary => &[(3, SkillId(9)), (5, SkillId(12))],
+                UnitJob::Paladin => &[(12, SkillId(2))],
+                UnitJob::General => &[(12, SkillId(3))],
+            }
+        }
+
+        /// Skills the job learns on reaching exactly `level`
+        pub fn skills_learned_at(&self, level: u32) -> impl Iterator<Item = SkillId> {
+            self.skill_table()
+                .iter()
+                .filter(move |(at, _)| *at == level)
+                .map(|(_, skill)| *skill)
+        }
+
+        /// Every skill the job has learned by the time it's `level`
+        pub fn skills_learned_by(&self, level: u32) -> impl Iterator<Item = SkillId> {
+            self.skill_table()
+                .iter()
+                .filter(move |(at, _)| *at <= level)
+                .map(|(_, skill)| *skill)
+        }
+
+        /// What the job can be promoted to, once a unit has enough levels in it
+        pub fn promotions(&self) -> &'static [UnitJob] {
+            match self {
+                UnitJob::Knight => &[UnitJob::Paladin, UnitJob::General],
+                _ => &[],
+            }
+        }
+
+        /// The job this one is a promotion of, if it's one at all
+        pub fn promoted_from(&self) -> Option<UnitJob> {
+            match self {
+                UnitJob::Paladin | UnitJob::General => Some(UnitJob::Knight),
+                _ => None,
+            }
+        }
+
+        /// What a unit gets on top of their stats for being promoted to this job
+        pub fn promotion_bonus(&self) -> Vec<(StatType, f32)> {
+            match self {
+                UnitJob::Paladin => vec![
+                    (StatType::MaxHealth, 4.),
+                    (StatType::Magic, 3.),
+                    (StatType::Resistance, 3.),
+                ],
+                UnitJob::General => vec![
+                    (StatType::MaxHealth, 6.),
+                    (StatType::Strength, 1.),
+                    (StatType::Defense, 4.),
+                ],
+                _ => Vec::new(),
+            }
+        }
+
+        /// The most the job's base stat can grow to. Each job goes further in what it's good at,
+        /// and promotions raise the bar a bit more.
+        pub fn stat_cap(&self, stat: StatType) -> Option<f32> {
+            let cap = match (self, stat) {
+                (UnitJob::Knight, StatType::Defense) => 25.,
+                (UnitJob::Knight | UnitJob::General, StatType::Magic) => 10.,
+                (UnitJob::Paladin, StatType::Magic | StatType::Resistance) => 25.,
+                (UnitJob::General, StatType::Defense) => 30.,
+                (UnitJob::General, StatType::MaxHealth) => 70.,
+                (UnitJob::Mage, StatType::Magic) => 25.,
+                (UnitJob::Mage, StatType::Strength) => 10.,
+                (UnitJob::Mage, StatType::MaxHealth) => 45.,
+                (UnitJob::Archer, StatType::Skill) => 25.,
+                (UnitJob::Mercenary, StatType::Speed | StatType::Strength) => 25.,
+                _ => return stat.cap(),
+            };
+            Some(cap)
+        }
+
+        /// The aura a unit of this job always has on. Knights (promoted or not) Rally whoever's
+        /// fighting right next to them.
+        pub fn aura(&self) -> Option<AuraData> {
+            match self {
+                UnitJob::Knight | UnitJob::Paladin | UnitJob::General => Some(AuraData {
+                    radius: 1,
+                    modification: StatModification {
+                        attribute_type: StatType::Defense,
+                        operator: Operator::Add,
+                        value: 2.,
+                    },
+                    partner: None,
+                }),
+                _ => None,
             }
         }
 
@@ -1072,29 +1429,68 @@ pub mod jobs {
                 UnitJob::Mage => TinyTacticsSprites::Mage.into(),
                 UnitJob::Archer => TinyTacticsSprites::Fighter.into(),
                 UnitJob::Mercenary => TinyTacticsSprites::Fighter.into(),
+                UnitJob::Paladin => TinyTacticsSprites::Cleric.into(),
+                UnitJob::General => TinyTacticsSprites::Fighter.into(),
+            }
+        }
+
+        /// What a new character of this job starts out holding
+        pub fn starting_weapon(&self) -> ItemId {
+            match self {
+                UnitJob::Archer => ItemId(2),
+                _ => ItemId(1),
             }
         }
 
-        /// I'm also not stoked on this long term, but for the demo we aren't doing
-        /// progressions, so Jobs need to determine skills!
+        /// What the job knows from the start. Anything else comes from the
+        /// [`skill_table`](UnitJob::skill_table) as the unit levels up.
         pub fn base_unit_skills(&self) -> UnitSkills {
             match self {
                 UnitJob::Knight => UnitSkills {
                     learned_skills: HashSet::new(),
                     equipped_skill_categories: Vec::from(&[SkillCategoryId(4)]),
+                    granted_skills: HashSet::new(),
                 },
                 UnitJob::Mage => UnitSkills {
                     learned_skills: HashSet::from([SkillId(2), SkillId(8)]),
                     equipped_skill_categories: Vec::from(&[SkillCategoryId(1)]),
+                    granted_skills: HashSet::new(),
                 },
                 UnitJob::Archer => UnitSkills {
-                    learned_skills: HashSet::from([SkillId(6), SkillId(5)]),
+                    learned_skills: HashSet::from([SkillId(5)]),
                     equipped_skill_categories: Vec::from(&[SkillCategoryId(5)]),
+                    granted_skills: HashSet::new(),
                 },
                 UnitJob::Mercenary => UnitSkills {
-                    learned_skills: HashSet::from([SkillId(3), SkillId(9)]),
+                    learned_skills: HashSet::from([SkillId(3)]),
                     equipped_skill_categories: Vec::from(&[SkillCategoryId(6)]),
+                    granted_skills: HashSet::new(),
+                },
+                UnitJob::Paladin => UnitSkills {
+                    learned_skills: HashSet::from([SkillId(8)]),
+                    equipped_skill_categories: Vec::from(&[SkillCategoryId(4), SkillCategoryId(1)]),
+                    granted_skills: HashSet::new(),
+                },
+                UnitJob::General => UnitSkills {
+                    learned_skills: HashSet::from([SkillId(9)]),
+                    equipped_skill_categories: Vec::from(&[SkillCategoryId(4), SkillCategoryId(6)]),
+                    granted_skills: HashSet::new(),
+                },
+            }
+        }
+
+        /// Archers skirmish (move, shoot, move), while Knights plant their feet once they swing.
+        pub fn action_economy(&self) -> ActionEconomy {
+            match self {
+                UnitJob::Archer => ActionEconomy {
+                    move_actions: 2,
+                    ..Default::default()
+                },
+                UnitJob::Knight | UnitJob::General => ActionEconomy {
+                    move_after_acting: false,
+                    ..Default::default()
                 },
+                UnitJob::Mage | UnitJob::Mercenary | UnitJob::Paladin => ActionEconomy::default(),
             }
         }
 
@@ -1104,6 +1500,8 @@ pub mod jobs {
                 UnitJob::Mage => SpriteId(8),
                 UnitJob::Archer => SpriteId(9),
                 UnitJob::Mercenary => SpriteId(10),
+                // No art for the promoted jobs yet, so they keep looking like Knights
+                UnitJob::Paladin | UnitJob::General => SpriteId(11),
             }
         }
 
@@ -1153,46 +1551,80 @@ pub mod jobs {
                     .with_stat(StatType::Skill, 5.0.into())
                     .with_stat(StatType::Movement, 5.0.into())
                     .to_owned(),
+                UnitJob::Paladin | UnitJob::General => {
+                    let mut stats = UnitJob::Knight.default_stats();
+                    for (stat, bonus) in self.promotion_bonus() {
+                        let value = stats.stat(stat).0 + bonus;
+                        stats.with_stat(stat, StatValue(value));
+                    }
+                    let max_health = stats.stat(StatType::MaxHealth);
+                    stats.with_stat(StatType::Health, max_health).to_owned()
+                }
             }
         }
 
-        /// TODO: This probably should return data, that then gets turned into StatGrowth
-        /// moreover, this should probably be read from data itself :shrug:
-        ///
-        pub fn default_growths(&self, seed: String) -> StatGrowths {
-            StatGrowths {
-                growths: BTreeMap::from([
-                    (
-                        StatType::Strength,
-                        Box::new(StatGrowthClampedNormalRounded::new(
-                            seed.clone(),
-                            0.7,
-                            Normal::<f32>::new(1.0, 1.0).expect("Should be able to make distr"),
-                            0.0,
-                            2.0,
-                        )) as Box<dyn StatGrowth>,
-                    ),
-                    (
-                        StatType::Defense,
-                        Box::new(StatGrowthClampedNormalRounded::new(
-                            seed.clone(),
-                            0.7,
-                            Normal::<f32>::new(2.0, 1.0).expect("Should be able to make distr"),
-                            0.0,
-                            4.0,
-                        )) as Box<dyn StatGrowth>,
-                    ),
-                ]),
+        /// How likely each stat is to grow on a level up, and by about how much. Stats that
+        /// aren't listed never grow.
+        pub fn growth_rates(&self) -> Vec<(StatType, GrowthRate)> {
+            let rate = GrowthRate::new;
+            match self {
+                UnitJob::Knight => vec![
+                    (StatType::MaxHealth, rate(0.8, 2., 4.)),
+                    (StatType::Strength, rate(0.5, 1., 2.)),
+                    (StatType::Defense, rate(0.7, 2., 3.)),
+                    (StatType::Resistance, rate(0.3, 1., 1.)),
+                    (StatType::Skill, rate(0.3, 1., 1.)),
+                ],
+                UnitJob::Mage => vec![
+                    (StatType::MaxHealth, rate(0.5, 1., 2.)),
+                    (StatType::Magic, rate(0.8, 2., 3.)),
+                    (StatType::Resistance, rate(0.6, 1., 2.)),
+                    (StatType::Speed, rate(0.4, 1., 1.)),
+                    (StatType::Skill, rate(0.4, 1., 2.)),
+                ],
+                UnitJob::Archer => vec![
+                    (StatType::MaxHealth, rate(0.6, 1., 3.)),
+                    (StatType::Strength, rate(0.5, 1., 2.)),
+                    (StatType::Defense, rate(0.3, 1., 1.)),
+                    (StatType::Speed, rate(0.7, 1., 2.)),
+                    (StatType::Skill, rate(0.8, 2., 3.)),
+                ],
+                UnitJob::Mercenary => vec![
+                    (StatType::MaxHealth, rate(0.7, 2., 3.)),
+                    (StatType::Strength, rate(0.8, 2., 3.)),
+                    (StatType::Defense, rate(0.3, 1., 1.)),
+                    (StatType::Speed, rate(0.6, 1., 2.)),
+                    (StatType::Skill, rate(0.5, 1., 2.)),
+                ],
+                UnitJob::Paladin => vec![
+                    (StatType::MaxHealth, rate(0.8, 2., 4.)),
+                    (StatType::Strength, rate(0.5, 1., 2.)),
+                    (StatType::Magic, rate(0.5, 1., 2.)),
+                    (StatType::Defense, rate(0.5, 1., 2.)),
+                    (StatType::Resistance, rate(0.6, 1., 2.)),
+                ],
+                UnitJob::General => vec![
+                    (StatType::MaxHealth, rate(0.9, 3., 5.)),
+                    (StatType::Strength, rate(0.6, 1., 2.)),
+                    (StatType::Defense, rate(0.8, 2., 4.)),
+                    (StatType::Resistance, rate(0.3, 1., 1.)),
+                ],
             }
         }
+
+        pub fn default_growths(&self, seed: String) -> StatGrowths {
+            StatGrowths::from_rates(&seed, &self.growth_rates())
+        }
     }
 }
 
 pub mod overlay {
 
-    use bevy::image::ImageSampler;
+    use bevy::camera::visibility::RenderLayers;
 
-    use crate::grid::init_grid_to_world_transform;
+    use crate::{
+        accessibility::ColorPalette, grid::init_grid_to_world_transform, threat_map::ThreatMapParam,
+    };
 
     use super::*;
     #[derive(Component)]
@@ -1210,32 +1642,100 @@ pub mod overlay {
     impl TileOverlayBundle {
         pub fn new(
             grid_position: grid::GridPosition,
-            spritesheet: Handle<Image>,
-            atlas_layout_handle: Handle<TextureAtlasLayout>,
+            tile_overlay_assets: &TileOverlayAssets,
             player: Player,
-            spritesheet_index: usize,
+            kind: OverlayKind,
         ) -> Self {
             let mut initial_transform = init_grid_to_world_transform(&grid_position);
             initial_transform.translation.z -= 50.;
             Self {
                 grid_position,
-                sprite: Sprite {
-                    image: spritesheet,
-                    texture_atlas: Some(TextureAtlas {
-                        layout: atlas_layout_handle,
-                        index: spritesheet_index,
-                    }),
-                    custom_size: None,
-                    // TODO: Replace this with just a single image that's White and then use Color to
-                    // change the color?
-                    color: Color::linear_rgba(1.0, 1.0, 1.0, 0.7),
-                    ..Default::default()
-                },
+                sprite: tile_overlay_assets.overlay_sprite(kind),
                 transform: initial_transform,
                 tile_overlay: TileOverlay {},
                 player,
             }
         }
+
+        /// Nudges the overlay towards the player's color, so everyone can tell whose is whose
+        pub fn with_player_tint(mut self, player_color: Color) -> Self {
+            let alpha = self.sprite.color.alpha();
+            self.sprite.color = self
+                .sprite
+                .color
+                .mix(&player_color, PLAYER_TINT_AMOUNT)
+                .with_alpha(alpha);
+            self
+        }
+    }
+
+    /// How far an overlay leans towards its player's color
+    const PLAYER_TINT_AMOUNT: f32 = 0.35;
+
+    /// Everything that gets drawn on top of a tile, so the palette can decide how each one looks
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum OverlayKind {
+        Move,
+        Attack,
+        Interact,
+        DangerZone,
+        Telegraph,
+        Flood,
+    }
+
+    impl OverlayKind {
+        // TODO: Stop using indices for iso_color and replace with white image
+        // that can be overriden via Color of sprite.
+        fn tile_index(&self) -> usize {
+            match self {
+                OverlayKind::Move | OverlayKind::Flood => 1,
+                OverlayKind::Interact => 2,
+                OverlayKind::Attack | OverlayKind::DangerZone | OverlayKind::Telegraph => 3,
+            }
+        }
+
+        fn color(&self, palette: ColorPalette) -> Color {
+            let color = match self {
+                OverlayKind::Move | OverlayKind::Attack | OverlayKind::Interact => {
+                    Color::linear_rgba(1.0, 1.0, 1.0, 0.7)
+                }
+                OverlayKind::DangerZone => Color::linear_rgba(1.0, 0.3, 0.3, 0.4),
+                // The tiles a delayed skill is about to land on
+                OverlayKind::Telegraph => Color::linear_rgba(1.0, 0.5, 0.1, 0.6),
+                OverlayKind::Flood => Color::linear_rgba(0.2, 0.4, 1.0, 0.6),
+            };
+
+            match palette {
+                ColorPalette::Standard => color,
+                // The danger zone and telegraphs are both red-ish, lean on the symbols instead
+                ColorPalette::Colorblind => match self {
+                    OverlayKind::DangerZone => Color::srgba_u8(0xCC, 0x79, 0xA7, 0x80),
+                    _ => color,
+                },
+                ColorPalette::HighContrast => color.with_alpha(0.9),
+            }
+        }
+
+        fn symbol_index(&self) -> usize {
+            match self {
+                OverlayKind::Move => 0,
+                OverlayKind::Attack => 1,
+                OverlayKind::Interact => 2,
+                OverlayKind::DangerZone => 3,
+                OverlayKind::Telegraph => 4,
+                OverlayKind::Flood => 5,
+            }
+        }
+    }
+
+    impl From<&OverlaysType> for OverlayKind {
+        fn from(value: &OverlaysType) -> Self {
+            match value {
+                OverlaysType::Interact => OverlayKind::Interact,
+                OverlaysType::Move => OverlayKind::Move,
+                OverlaysType::Attack => OverlayKind::Attack,
+            }
+        }
     }
 
     #[derive(Resource, Default)]
@@ -1243,25 +1743,43 @@ pub mod overlay {
         pub tile_overlay_image_handle: Handle<Image>,
         pub cursor_image: Handle<Image>,
         pub tile_overlay_atlas_layout_handle: Handle<TextureAtlasLayout>,
+        pub symbol_image_handle: Handle<Image>,
+        pub symbol_atlas_layout_handle: Handle<TextureAtlasLayout>,
+        /// Kept in sync with the AccessibilitySettings
+        pub palette: ColorPalette,
     }
 
-    // This system reads all AssetEvents for the Image type and attempts to set the ImageSampler values to nearest to stop some texture bleeding
-    pub fn on_asset_event(
-        mut events: MessageReader<AssetEvent<Image>>,
-        asset_handles: Res<TileOverlayAssets>,
-        mut images: ResMut<Assets<Image>>,
-    ) {
-        for event in events.read() {
-            // You can check the type of event and the specific handle
-            if let AssetEvent::LoadedWithDependencies { id } = event
-                && *id == asset_handles.tile_overlay_image_handle.id()
-            {
-                info!("Our specific image asset and its dependencies are loaded!");
-                if let Some(image) = images.get_mut(*id) {
-                    image.sampler = ImageSampler::nearest();
-                }
+    impl TileOverlayAssets {
+        pub fn overlay_sprite(&self, kind: OverlayKind) -> Sprite {
+            Sprite {
+                image: self.tile_overlay_image_handle.clone(),
+                texture_atlas: Some(TextureAtlas {
+                    layout: self.tile_overlay_atlas_layout_handle.clone(),
+                    index: kind.tile_index(),
+                }),
+                color: kind.color(self.palette),
+                ..Default::default()
             }
         }
+
+        /// A symbol to spawn as a child of the overlay, if the palette wants one
+        pub fn overlay_symbol(&self, kind: OverlayKind) -> Option<impl Bundle> {
+            if !self.palette.marks_overlays() {
+                return None;
+            }
+
+            Some((
+                Sprite {
+                    image: self.symbol_image_handle.clone(),
+                    texture_atlas: Some(TextureAtlas {
+                        layout: self.symbol_atlas_layout_handle.clone(),
+                        index: kind.symbol_index(),
+                    }),
+                    ..Default::default()
+                },
+                Transform::from_xyz(0., 0., 0.1),
+            ))
+        }
     }
 
     // New event for overlay spawning
@@ -1287,11 +1805,92 @@ pub mod overlay {
         Despawn,
     }
 
+    /// A tile some enemy could attack next phase.
+    ///
+    /// Lives on its own layer, underneath the overlays for the current selection, and isn't
+    /// registered with the GridManager. There's one per tile however many players want to see it,
+    /// drawn on the [render layer](Player::render_layer) of each of them, so it only shows up in
+    /// the views of players who turned it on.
+    #[derive(Component)]
+    pub struct DangerZoneOverlay {}
+
+    pub fn toggle_danger_zone(
+        input_query: Query<(&Player, &ActionState<PlayerInputAction>)>,
+        mut player_states: ResMut<player::PlayerGameStates>,
+    ) {
+        for (player, action_state) in input_query {
+            if !action_state.just_pressed(&PlayerInputAction::ToggleDangerZone) {
+                continue;
+            }
+
+            if let Some(state) = player_states.player_state.get_mut(player) {
+                state.show_danger_zone = !state.show_danger_zone;
+                info!(
+                    "{:?} toggled the danger zone: {}",
+                    player, state.show_danger_zone
+                );
+            }
+        }
+    }
+
+    /// Rebuilds the danger zone whenever a unit moves, or someone toggles it.
+    pub fn update_danger_zone_overlays(
+        mut commands: Commands,
+        threat_map: ThreatMapParam,
+        tile_overlay_assets: Res<TileOverlayAssets>,
+        player_states: Res<player::PlayerGameStates>,
+        moved_units: Query<(), (With<Unit>, Changed<GridPosition>)>,
+        existing_overlays: Query<Entity, With<DangerZoneOverlay>>,
+    ) {
+        if !player_states.is_changed()
+            && !tile_overlay_assets.is_changed()
+            && moved_units.is_empty()
+        {
+            return;
+        }
+
+        for entity in existing_overlays {
+            commands.entity(entity).despawn();
+        }
+
+        let players = player_states
+            .player_state
+            .iter()
+            .filter(|(_, state)| state.show_danger_zone)
+            .map(|(player, _)| *player)
+            .collect::<Vec<_>>();
+        if players.is_empty() {
+            return;
+        }
+
+        let layers = players
+            .iter()
+            .map(Player::render_layer)
+            .collect::<RenderLayers>();
+        let threat_map = threat_map.build(&PLAYER_TEAM);
+        for position in threat_map.threatened_tiles.keys() {
+            let mut transform = init_grid_to_world_transform(position);
+            transform.translation.z -= 55.;
+            let mut overlay = commands.spawn((
+                DangerZoneOverlay {},
+                tile_overlay_assets.overlay_sprite(OverlayKind::DangerZone),
+                transform,
+                layers.clone(),
+                BattleEntity {},
+                DungeonEntity,
+            ));
+            if let Some(symbol) = tile_overlay_assets.overlay_symbol(OverlayKind::DangerZone) {
+                overlay.with_child((symbol, layers.clone()));
+            }
+        }
+    }
+
     /// Handle an OverlaysAction for spawning and despawning overlays
     pub fn handle_overlays_events_system(
         mut commands: Commands,
         mut grid_manager_res: ResMut<grid::GridManagerResource>,
         tile_overlay_assets: Res<overlay::TileOverlayAssets>,
+        registered_players: Res<RegisteredBattlePlayers>,
         overlay_query: Query<(Entity, &Player), With<TileOverlay>>,
         mut events: MessageReader<OverlaysMessage>,
     ) {
@@ -1301,20 +1900,14 @@ pub mod overlay {
                 positions,
             } = &event.action
             {
-                // TODO: Stop using indices for iso_color and replace with white image
-                // that can be overriden via Color of sprite.
-                let index = match spawn_type {
-                    OverlaysType::Interact => 2,
-                    OverlaysType::Move => 1,
-                    OverlaysType::Attack => 3,
-                };
                 spawn_overlays(
                     &mut commands,
                     &tile_overlay_assets,
                     event.player,
+                    registered_players.player_color(&event.player),
                     positions.clone(),
                     &mut grid_manager_res.grid_manager,
-                    index,
+                    spawn_type.into(),
                 );
             } else if let OverlaysAction::Despawn = &event.action {
                 for (entity, overlay_player) in overlay_query.iter() {
@@ -1337,11 +1930,14 @@ mod tests {
         battle::{
             UnitCommand, UnitSelectionBackMessage, UnitSelectionMessage, UnitUiCommandMessage,
         },
+        battle_log::BattleLogMessage,
         battle_phase::{
-            PhaseMessage, UnitPhaseResources, check_should_advance_phase, init_phase_system,
-            phase_ui::ShowBattleBannerMessage, prepare_for_phase,
+            PhaseMessage, TurnAdvancedMessage, TurnModel, UnitPhaseResources,
+            check_should_advance_phase, init_phase_system, phase_ui::ShowBattleBannerMessage,
+            prepare_for_phase,
         },
         combat::skills::setup_skill_system,
+        equipment::build_item_db,
         grid::{
             self, GridManager, GridManagerResource, GridMovement, GridPosition,
             sync_grid_positions_to_manager,
@@ -1381,10 +1977,14 @@ mod tests {
         app.add_message::<ShowBattleBannerMessage>();
         app.add_message::<UnitSelectionBackMessage>();
         app.add_message::<PhaseMessage>();
+        app.add_message::<TurnAdvancedMessage>();
+        app.add_message::<BattleLogMessage>();
+        app.init_resource::<TurnModel>();
         app.insert_resource(GridManagerResource {
             grid_manager: GridManager::new(6, 6),
         });
         app.insert_resource::<Time>(Time::default());
+        app.insert_resource(build_item_db().expect("Item DB should parse"));
         app.insert_resource(PlayerGameStates {
             player_state: HashMap::from([(Player::PlayerId(1), PlayerState::default())]),
         });
@@ -0,0 +1,401 @@
+//! A big panel with everything there is to know about a unit.
+//!
+//! Opened by pressing Inspect while the cursor is free and hovering over a unit. The panel is a
+//! `GameMenuGrid` of tabs, so it's navigated the same way as every other menu, and only the tab
+//! that's highlighted gets shown.
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    assets::{
+        FontResource,
+        sounds::{SoundManagerParam, UiSound},
+    },
+    battle::BattleEntity,
+    combat::skills::{SkillDBResource, UnitSkills},
+    equipment::UnitEquipment,
+    gameplay_effects::ActiveEffects,
+    grid::{GridManagerResource, GridPosition},
+    grid_cursor::{Cursor, LockedOn},
+    menu::{
+        menu_navigation::{ActiveMenu, GameMenuController, GameMenuGrid, GameMenuLatch},
+        ui_consts::{SELECTABLE_BUTTON_BACKGROUND, UI_MENU_BACKGROUND, UI_TEXT_COLOR},
+    },
+    player::{Player, PlayerCursorState, PlayerGameStates, PlayerInputAction},
+    tooltip::Tooltip,
+    tr,
+    unit::{Unit, jobs::UnitJob},
+    unit_stats::{StatType, UnitBaseStats, UnitDerivedStats, at_cap},
+};
+
+/// The open inspection panel for a player.
+#[derive(Component)]
+pub struct UnitInspectionScreen {
+    pub player: Player,
+    pub cursor: Entity,
+    /// Whatever menu the player had open before inspecting, so we can give it back.
+    pub suspended_menu: Option<Entity>,
+    pub tabs: Vec<Entity>,
+}
+
+/// A tab button in the inspection panel, and the section it shows.
+#[derive(Component)]
+pub struct InspectionTab {
+    pub section: Entity,
+}
+
+/// The tab that closes the inspection panel.
+#[derive(Component)]
+pub struct CloseInspectionTab;
+
+/// Stats that have grown as far as they can
+const CAPPED_STAT_COLOR: Color = Color::srgb(1.0, 0.8, 0.2);
+
+type InspectedUnit<'a> = (
+    &'a Unit,
+    &'a UnitBaseStats,
+    &'a UnitDerivedStats,
+    Option<&'a UnitJob>,
+    &'a Sprite,
+    Option<&'a UnitEquipment>,
+    Option<&'a ActiveEffects>,
+    Option<&'a UnitSkills>,
+);
+
+fn build_section(commands: &mut Commands, font: &TextFont, lines: Vec<String>) -> Entity {
+    let lines = lines.into_iter().map(|t| (t, UI_TEXT_COLOR)).collect();
+    build_colored_section(commands, font, lines)
+}
+
+fn build_colored_section(
+    commands: &mut Commands,
+    font: &TextFont,
+    lines: Vec<(String, Color)>,
+) -> Entity {
+    let section = commands
+        .spawn(Node {
+            display: Display::None,
+            flex_direction: FlexDirection::Column,
+            flex_grow: 1.,
+            row_gap: px(6),
+            padding: UiRect::all(percent(2)),
+            ..Default::default()
+        })
+        .id();
+
+    for (line, color) in lines {
+        let text = commands
+            .spawn((Text::new(line), font.clone(), TextColor(color)))
+            .id();
+        commands.entity(section).add_child(text);
+    }
+
+    section
+}
+
+fn build_tab(commands: &mut Commands, font: &TextFont, label: &str) -> Entity {
+    commands
+        .spawn((
+            Button,
+            Node {
+                width: percent(100),
+                height: px(48),
+                align_items: AlignItems::Center,
+                padding: UiRect::left(percent(5)),
+                border_radius: BorderRadius::all(percent(20)),
+                ..Default::default()
+            },
+            BackgroundColor(SELECTABLE_BUTTON_BACKGROUND),
+            children![(Text::new(label), font.clone(), TextColor(UI_TEXT_COLOR))],
+        ))
+        .id()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn open_unit_inspection(
+    mut commands: Commands,
+    fonts: Res<FontResource>,
+    grid_manager: Res<GridManagerResource>,
+    skill_db: Res<SkillDBResource>,
+    player_states: Res<PlayerGameStates>,
+    input_query: Query<(&Player, &ActionState<PlayerInputAction>)>,
+    cursors: Query<(Entity, &Player, &GridPosition), (With<Cursor>, Without<LockedOn>)>,
+    units: Query<InspectedUnit>,
+    open_menus: Query<(Entity, &GameMenuController), With<ActiveMenu>>,
+    sounds: SoundManagerParam,
+) {
+    for (player, action_state) in input_query {
+        if !action_state.just_pressed(&PlayerInputAction::Inspect) {
+            continue;
+        }
+
+        // Don't pull the rug out from under a move or an attack
+        if player_states
+            .player_state
+            .get(player)
+            .is_some_and(|t| t.cursor_state != PlayerCursorState::Idle)
+        {
+            continue;
+        }
+
+        let Some((cursor, _, cursor_pos)) = cursors.iter().find(|(_, p, _)| *p == player) else {
+            continue;
+        };
+
+        let Some((unit, base_stats, stats, job, sprite, equipment, effects, skills)) = grid_manager
+            .grid_manager
+            .get_by_position(cursor_pos)
+            .and_then(|t| t.iter().filter_map(|e| units.get(*e).ok()).next())
+        else {
+            sounds.play_ui_sound(&mut commands, UiSound::Error);
+            continue;
+        };
+
+        let font = TextFont {
+            font: fonts.pixelify_sans_regular.clone(),
+            font_size: 24.,
+            font_smoothing: bevy::text::FontSmoothing::None,
+            ..Default::default()
+        };
+
+        let stat_lines = StatType::VARIANTS
+            .iter()
+            .map(|stat| {
+                let line = format!(
+                    "{}: {}",
+                    stat.abbreviation(),
+                    stats.stats.stat(*stat).0.round() as i32
+                );
+                if at_cap(&base_stats.stats, job, *stat) {
+                    (line, CAPPED_STAT_COLOR)
+                } else {
+                    (line, UI_TEXT_COLOR)
+                }
+            })
+            .collect();
+
+        let mut equipment_lines = equipment
+            .map(|t| {
+                t.equipped_items()
+                    .map(|(slot, item)| format!("{:?}: {}", slot, item.name()))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        if equipment_lines.is_empty() {
+            equipment_lines.push("Nothing equipped".to_string());
+        }
+
+        let mut effect_lines = effects
+            .map(|t| {
+                t.effects
+                    .iter()
+                    .map(|t| t.description())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        if effect_lines.is_empty() {
+            effect_lines.push("No active effects".to_string());
+        }
+
+        let mut skill_lines = skills
+            .map(|t| {
+                t.known_skills()
+                    .map(|id| skill_db.skill_db.get_skill(id).name.clone())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        skill_lines.sort();
+        if skill_lines.is_empty() {
+            skill_lines.push("No skills learned".to_string());
+        }
+
+        let sections = [
+            (
+                "Stats",
+                build_colored_section(&mut commands, &font, stat_lines),
+            ),
+            (
+                "Equipment",
+                build_section(&mut commands, &font, equipment_lines),
+            ),
+            ("Effects", build_section(&mut commands, &font, effect_lines)),
+            ("Skills", build_section(&mut commands, &font, skill_lines)),
+        ];
+
+        let stat_tooltip = StatType::VARIANTS
+            .iter()
+            .map(|t| format!("{}: {}", t.abbreviation(), t.description()))
+            .chain([tr!("stat.capped")])
+            .collect::<Vec<_>>()
+            .join("\n");
+        let equipment_tooltip = equipment
+            .map(|t| {
+                t.equipped_items()
+                    .map(|(_, item)| item.description())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .filter(|t| !t.is_empty());
+        let tooltips = [Some(stat_tooltip), equipment_tooltip, None, None];
+
+        let mut tabs = Vec::new();
+        for ((label, section), tooltip) in sections.into_iter().zip(tooltips) {
+            let tab = build_tab(&mut commands, &font, label);
+            commands.entity(tab).insert(InspectionTab { section });
+            if let Some(tooltip) = tooltip {
+                commands.entity(tab).insert(Tooltip::new(tooltip));
+            }
+            tabs.push(tab);
+        }
+        let close_tab = build_tab(&mut commands, &font, "Close");
+        commands.entity(close_tab).insert(CloseInspectionTab);
+        tabs.push(close_tab);
+
+        let mut menu = GameMenuGrid::new_vertical();
+        menu.push_buttons_to_stack(&tabs);
+
+        let preview = commands
+            .spawn((
+                ImageNode {
+                    image: sprite.image.clone(),
+                    texture_atlas: sprite.texture_atlas.clone(),
+                    flip_x: sprite.flip_x,
+                    ..Default::default()
+                },
+                Node {
+                    width: px(128),
+                    height: px(128),
+                    ..Default::default()
+                },
+            ))
+            .id();
+        let name = commands
+            .spawn((
+                Text::new(unit.name.clone()),
+                TextFont {
+                    font: fonts.pixelify_sans_medium.clone(),
+                    font_size: 36.,
+                    ..Default::default()
+                },
+                TextColor(UI_TEXT_COLOR),
+            ))
+            .id();
+
+        let tab_column = commands
+            .spawn(Node {
+                flex_direction: FlexDirection::Column,
+                width: percent(30),
+                row_gap: px(8),
+                align_items: AlignItems::Center,
+                ..Default::default()
+            })
+            .add_children(&[preview, name])
+            .add_children(&tabs)
+            .id();
+
+        let section_entities = sections.map(|t| t.1);
+        let suspended_menu = open_menus
+            .iter()
+            .find(|(_, controller)| controller.players.contains(player))
+            .map(|t| t.0);
+        if let Some(menu) = suspended_menu {
+            commands.entity(menu).remove::<ActiveMenu>();
+        }
+        commands.entity(cursor).insert(LockedOn {});
+
+        commands
+            .spawn((
+                Name::new(format!("UnitInspection {:?}", player)),
+                Node {
+                    position_type: PositionType::Absolute,
+                    left: percent(10),
+                    top: percent(7.5),
+                    width: percent(80),
+                    height: percent(85),
+                    flex_direction: FlexDirection::Row,
+                    padding: UiRect::all(percent(2)),
+                    column_gap: percent(2),
+                    border_radius: BorderRadius::all(percent(5)),
+                    ..Default::default()
+                },
+                GlobalZIndex(10),
+                BackgroundColor(UI_MENU_BACKGROUND),
+                UnitInspectionScreen {
+                    player: *player,
+                    cursor,
+                    suspended_menu,
+                    tabs: tabs.clone(),
+                },
+                menu,
+                GameMenuController {
+                    players: HashSet::from([*player]),
+                },
+                GameMenuLatch::default(),
+                ActiveMenu {},
+                BattleEntity {},
+            ))
+            .add_child(tab_column)
+            .add_children(&section_entities);
+
+        sounds.play_ui_sound(&mut commands, UiSound::OpenMenu);
+    }
+}
+
+/// Only shows the section for the tab that's currently highlighted.
+pub fn show_highlighted_inspection_section(
+    screens: Query<(&UnitInspectionScreen, &GameMenuGrid), Changed<GameMenuGrid>>,
+    tabs: Query<&InspectionTab>,
+    mut nodes: Query<&mut Node>,
+) {
+    for (screen, menu) in screens {
+        let highlighted = menu.get_active_menu_option().copied();
+        for tab_entity in &screen.tabs {
+            let Ok(tab) = tabs.get(*tab_entity) else {
+                continue;
+            };
+
+            if let Ok(mut node) = nodes.get_mut(tab.section) {
+                node.display = if highlighted == Some(*tab_entity) {
+                    Display::Flex
+                } else {
+                    Display::None
+                };
+            }
+        }
+    }
+}
+
+/// Closes the panel on Deselect, or on selecting the Close tab.
+pub fn close_unit_inspection(
+    mut commands: Commands,
+    input_query: Query<(&Player, &ActionState<PlayerInputAction>)>,
+    screens: Query<(Entity, &UnitInspectionScreen, &GameMenuGrid)>,
+    close_tabs: Query<(), With<CloseInspectionTab>>,
+    sounds: SoundManagerParam,
+) {
+    for (player, action_state) in input_query {
+        for (screen_entity, screen, menu) in screens {
+            if screen.player != *player {
+                continue;
+            }
+
+            let close_selected = action_state.just_pressed(&PlayerInputAction::Select)
+                && menu
+                    .get_active_menu_option()
+                    .is_some_and(|t| close_tabs.contains(*t));
+            if !close_selected && !action_state.just_pressed(&PlayerInputAction::Deselect) {
+                continue;
+            }
+
+            commands.entity(screen_entity).despawn();
+            commands.entity(screen.cursor).remove::<LockedOn>();
+            if let Some(menu) = screen.suspended_menu {
+                commands.entity(menu).insert(ActiveMenu {});
+            }
+            sounds.play_ui_sound(&mut commands, UiSound::CloseMenu);
+        }
+    }
+}
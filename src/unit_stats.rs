@@ -4,10 +4,24 @@ use bevy::prelude::*;
 
 use crate::{
     combat::UnitHealthChangedEvent,
-    gameplay_effects::{ActiveEffects, Operator},
+    gameplay_effects::{ActiveEffects, Operator, StatModification},
+    tr,
+    unit::jobs::UnitJob,
 };
 
-#[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Clone, Copy, Reflect, Hash)]
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    Ord,
+    PartialOrd,
+    Clone,
+    Copy,
+    Reflect,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub enum StatType {
     // Not super sure if I want health here, but maybe fine for now?
     Health,
@@ -47,9 +61,70 @@ impl StatType {
             StatType::Health => "HP",
         }
     }
+
+    /// The most anyone's base stat can grow to, unless their job says otherwise (see
+    /// [`UnitJob::stat_cap`]). Health is only capped by MaxHealth.
+    pub fn cap(&self) -> Option<f32> {
+        match &self {
+            StatType::Health => None,
+            StatType::MaxHealth => Some(60.),
+            StatType::Movement => Some(6.),
+            StatType::Strength
+            | StatType::Magic
+            | StatType::Defense
+            | StatType::Resistance
+            | StatType::Speed
+            | StatType::Skill => Some(20.),
+        }
+    }
+
+    /// What the stat actually does, for tooltips
+    pub fn description(&self) -> String {
+        match &self {
+            StatType::Strength => tr!("stat.strength"),
+            StatType::Magic => tr!("stat.magic"),
+            StatType::Defense => tr!("stat.defense"),
+            StatType::Resistance => tr!("stat.resistance"),
+            StatType::Speed => tr!("stat.speed"),
+            StatType::Skill => tr!("stat.skill"),
+            StatType::MaxHealth => tr!("stat.max_health"),
+            StatType::Movement => tr!("stat.movement"),
+            StatType::Health => tr!("stat.health"),
+        }
+    }
 }
 
-#[derive(PartialEq, Clone, Copy, Default, Debug)]
+/// The cap on a unit's base stat, going by their job if they have one
+pub fn stat_cap(job: Option<&UnitJob>, stat: StatType) -> Option<f32> {
+    match job {
+        Some(job) => job.stat_cap(stat),
+        None => stat.cap(),
+    }
+}
+
+/// Whether the unit's base stat has grown as far as it can
+pub fn at_cap(stats: &StatContainer, job: Option<&UnitJob>, stat: StatType) -> bool {
+    stat_cap(job, stat).is_some_and(|cap| stats.stat(stat).0 >= cap)
+}
+
+/// Grows the stat by `growth`, only as far as its cap. Whatever extra max health actually got
+/// added comes already filled in.
+pub fn apply_growth(stats: &mut StatContainer, job: Option<&UnitJob>, stat: StatType, growth: f32) {
+    let current = stats.stat(stat).0;
+    let next = match stat_cap(job, stat) {
+        // Anything already over the cap (say from a promotion) stays put rather than shrinking
+        Some(cap) => (current + growth).min(cap.max(current)),
+        None => current + growth,
+    };
+    stats.with_stat(stat, StatValue(next));
+
+    if stat == StatType::MaxHealth {
+        let health = stats.stat(StatType::Health).0;
+        stats.with_stat(StatType::Health, StatValue(health + next - current));
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Default, Debug, Reflect, serde::Serialize, serde::Deserialize)]
 pub struct StatValue(pub f32);
 
 impl From<f32> for StatValue {
@@ -58,7 +133,7 @@ impl From<f32> for StatValue {
     }
 }
 
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Default, Debug, PartialEq, Reflect, serde::Serialize, serde::Deserialize)]
 pub struct StatContainer {
     stats: HashMap<StatType, StatValue>,
 }
@@ -100,27 +175,51 @@ pub fn derive_stats(
     >,
 ) {
     for (e, base_stats, mut derived, active_effects) in unit_query {
-        let stat_modifications = active_effects.map(|t| t.stat_buffs()).unwrap_or_default();
-        for stat in StatType::VARIANTS {
-            let mut base = base_stats.stats.stat(*stat);
-            for modification in &stat_modifications {
-                if modification.attribute_type != *stat {
-                    continue;
-                }
-
-                // TODO: Probably need to apply all adds first and then do mul?
-                match modification.operator {
-                    Operator::Add => base.0 += modification.value,
-                    Operator::Mul => base.0 *= modification.value,
-                };
-            }
-
-            derived.stats.with_stat(*stat, base);
-        }
+        let from_statuses = active_effects
+            .map(|t| t.statuses())
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|t| t.stat_modifications())
+            .collect::<Vec<_>>();
+        let mut stat_modifications = active_effects.map(|t| t.stat_buffs()).unwrap_or_default();
+        stat_modifications.extend(&from_statuses);
+        derived.stats = apply_stat_modifications(&base_stats.stats, &stat_modifications);
         commands.entity(e).remove::<StatsDirty>();
     }
 }
 
+/// Works the modifications into the base stats, always in the same order no matter what order
+/// they were picked up in:
+///
+/// 1. Every [`Operator::Add`] on a stat gets summed onto its base value
+/// 2. That gets multiplied by every [`Operator::Mul`] on the stat
+/// 3. Nothing goes below 0, and Health can't go over MaxHealth
+pub fn apply_stat_modifications(
+    base: &StatContainer,
+    modifications: &[&StatModification],
+) -> StatContainer {
+    let mut derived = StatContainer::new();
+    for stat in StatType::VARIANTS {
+        let on_stat = || modifications.iter().filter(|t| t.attribute_type == *stat);
+        let added = on_stat()
+            .filter(|t| matches!(t.operator, Operator::Add))
+            .map(|t| t.value)
+            .sum::<f32>();
+        let multiplier = on_stat()
+            .filter(|t| matches!(t.operator, Operator::Mul))
+            .map(|t| t.value)
+            .product::<f32>();
+
+        let value = (base.stat(*stat).0 + added) * multiplier;
+        derived.with_stat(*stat, StatValue(value.max(0.)));
+    }
+
+    let max_health = derived.stat(StatType::MaxHealth).0;
+    let health = derived.stat(StatType::Health).0;
+    derived.with_stat(StatType::Health, StatValue(health.min(max_health)));
+    derived
+}
+
 #[derive(Component)]
 pub struct UnitBaseStats {
     pub stats: StatContainer,
@@ -156,11 +255,12 @@ pub struct StatsDirty;
 
 pub fn handle_stat_changes(
     mut reader: MessageReader<UnitStatChangeRequest>,
-    mut query: Query<(&mut UnitBaseStats, &mut UnitDerivedStats)>,
+    mut query: Query<(&mut UnitBaseStats, &mut UnitDerivedStats, Option<&UnitJob>)>,
     mut health_changed_writer: MessageWriter<UnitHealthChangedEvent>,
 ) {
     for message in reader.read() {
-        let Some((mut base_stats, mut derived_stats)) = query.get_mut(message.entity).ok() else {
+        let Some((mut base_stats, mut derived_stats, job)) = query.get_mut(message.entity).ok()
+        else {
             error!(
                 "No stats found for Unit. Could not process request: {:?}",
                 message
@@ -183,7 +283,8 @@ pub fn handle_stat_changes(
                 0.,
             ))
         } else {
-            StatValue(f32::max(0., next.0))
+            let cap = stat_cap(job, message.stat).unwrap_or(f32::INFINITY);
+            StatValue(next.0.min(cap.max(current.0)).max(0.))
         };
 
         if message.stat == StatType::Health {
@@ -206,13 +307,22 @@ pub mod experience {
     use std::collections::BTreeMap;
 
     use crate::{
-        unit::{UnitAction, UnitActionCompletedMessage},
-        unit_stats::{StatType, StatValue, StatsDirty, UnitBaseStats, growths::StatGrowths},
+        assets::FontResource,
+        battle_log::BattleLogMessage,
+        combat::{
+            DespawnTimer,
+            skills::{SkillDBResource, UnitSkills},
+        },
+        tr,
+        unit::{UnitAction, UnitActionCompletedMessage, jobs::UnitJob},
+        unit_stats::{StatType, StatsDirty, UnitBaseStats, apply_growth, growths::StatGrowths},
     };
 
-    /// It'd be fun to make specific actions give more or less xp dependent on results
-    /// but I've always been frustrated with games that let u just farm xp. Maybe this should be
-    /// more of a contribution tracker and xp should be doled out at end of fight?
+    /// The old way of handing out XP, only used with [`XpModel::PerAttack`]. Every attack is
+    /// worth the same, which makes it easy to farm, so by default XP gets doled out at the end of
+    /// the fight by the [contribution tracker](crate::contribution) instead.
+    ///
+    /// [`XpModel::PerAttack`]: crate::contribution::XpModel::PerAttack
     pub fn give_flat_xp_after_attack_action_complete(
         mut unit_action_completed: MessageReader<UnitActionCompletedMessage>,
         mut xp_query: Query<&mut UnitLevelManager>,
@@ -257,6 +367,26 @@ pub mod experience {
                 experience: 0.0,
             }
         }
+
+        pub fn level(&self) -> u32 {
+            self.current_level
+        }
+
+        pub fn experience(&self) -> f32 {
+            self.experience
+        }
+
+        /// Puts a fresh level manager back where a saved one left off.
+        ///
+        /// The growths are seeded, so rolling them once per level gained gets their RNG back to
+        /// the same spot. The rolls themselves are thrown away, the saved stats already have them.
+        pub fn restore(&mut self, level: u32, experience: f32) {
+            for _ in self.current_level..level {
+                self.growths.get_growths_for_level_up();
+            }
+            self.current_level = level;
+            self.experience = experience;
+        }
     }
 
     impl UnitLevelManager {
@@ -290,17 +420,30 @@ pub mod experience {
 
     #[derive(Message, Debug)]
     pub struct LevelUpMessage {
-        entity: Entity,
-        level_up: LevelUp,
+        pub entity: Entity,
+        pub level_up: LevelUp,
     }
 
+    /// The little note over a unit's head when they level up
+    #[derive(Component)]
+    pub struct LevelUpPopup;
+
     pub fn apply_level_up_to_stats(
         mut commands: Commands,
         mut reader: MessageReader<LevelUpMessage>,
-        mut unit_query: Query<(&mut UnitBaseStats, &mut UnitLevelManager)>,
+        mut unit_query: Query<(
+            &mut UnitBaseStats,
+            &mut UnitLevelManager,
+            Option<&UnitJob>,
+            Option<&mut UnitSkills>,
+        )>,
+        skill_db: Res<SkillDBResource>,
+        fonts: Res<FontResource>,
+        mut battle_log: MessageWriter<BattleLogMessage>,
     ) {
         for m in reader.read() {
-            let Some((mut stats, mut level)) = unit_query.get_mut(m.entity).ok() else {
+            let Some((mut stats, mut level, job, skills)) = unit_query.get_mut(m.entity).ok()
+            else {
                 error!("Invalid Entity got a level up: {:?}", m.entity);
                 continue;
             };
@@ -311,15 +454,98 @@ pub mod experience {
             );
             // TODO: Should I send a "StatChangeRequest here?"
             for (stat, growth_value) in &m.level_up.growths {
-                let current = stats.stats.stat(*stat);
-                stats
-                    .stats
-                    .with_stat(*stat, StatValue(current.0 + growth_value));
+                apply_growth(&mut stats.stats, job, *stat, *growth_value);
             }
 
             level.current_level += 1;
+            battle_log.write(BattleLogMessage::LevelUp {
+                unit: m.entity,
+                level: level.current_level,
+            });
+
+            let mut popup = vec![tr!("level_up.popup", level = level.current_level)];
+            if let (Some(job), Some(mut skills)) = (job, skills) {
+                for skill in job.skills_learned_at(level.current_level) {
+                    if !skills.learned_skills.insert(skill) {
+                        continue;
+                    }
+                    let name = skill_db.skill_db.get_skill(&skill).name.clone();
+                    popup.push(tr!("level_up.learned", skill = name.clone()));
+                    battle_log.write(BattleLogMessage::SkillLearned {
+                        unit: m.entity,
+                        skill: name,
+                    });
+                }
+            }
 
-            commands.entity(m.entity).insert(StatsDirty);
+            commands.entity(m.entity).insert(StatsDirty).with_child((
+                Text2d(popup.join("\n")),
+                TextColor(Color::linear_rgb(1.0, 0.85, 0.2)),
+                TextFont {
+                    font: fonts.pixelify_sans_regular.clone(),
+                    font_size: 12.,
+                    font_smoothing: bevy::text::FontSmoothing::None,
+                    ..Default::default()
+                },
+                LevelUpPopup,
+                DespawnTimer {
+                    timer: Timer::from_seconds(1.5, TimerMode::Once),
+                },
+                Transform::from_translation(Vec3::new(0., 48., 0.)),
+                TextBackgroundColor(Color::BLACK.with_alpha(0.5)),
+            ));
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::combat::skills::build_skill_table;
+
+        fn level_manager() -> UnitLevelManager {
+            UnitLevelManager::new(UnitJob::Knight.default_growths("seed".to_string()))
+        }
+
+        #[test]
+        fn test_restored_level_manager_rolls_the_same_growths() {
+            let mut played = level_manager();
+            assert_eq!(played.accept_experience(250.).len(), 2);
+            played.current_level = 3;
+
+            let mut restored = level_manager();
+            restored.restore(played.level(), played.experience());
+            assert_eq!(restored.level(), 3);
+            assert_eq!(restored.experience(), 50.);
+
+            let next_played = played.accept_experience(100.);
+            let next_restored = restored.accept_experience(100.);
+            assert_eq!(next_played[0].growths, next_restored[0].growths);
+        }
+
+        #[test]
+        fn test_jobs_learn_real_skills_they_dont_start_with() {
+            let skill_db = build_skill_table().expect("Should be able to build skill table");
+            for job in [
+                UnitJob::Knight,
+                UnitJob::Mage,
+                UnitJob::Archer,
+                UnitJob::Mercenary,
+                UnitJob::Paladin,
+                UnitJob::General,
+            ] {
+                let base = job.base_unit_skills().learned_skills;
+                for (level, skill) in job.skill_table() {
+                    assert!(
+                        skill_db.has_skill(skill),
+                        "{:?} can't learn {:?}",
+                        job,
+                        skill
+                    );
+                    assert!(!base.contains(skill));
+                    assert!(job.skills_learned_at(*level).any(|t| t == *skill));
+                    assert!(!job.skills_learned_by(level - 1).any(|t| t == *skill));
+                }
+            }
         }
     }
 }
@@ -341,7 +567,45 @@ pub mod growths {
         pub growths: BTreeMap<StatType, Box<dyn StatGrowth>>,
     }
 
+    /// How likely a stat is to grow on a level up, and by about how much when it does
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct GrowthRate {
+        pub chance: f32,
+        pub mean: f32,
+        /// The most it can grow by in one level
+        pub max: f32,
+    }
+
+    impl GrowthRate {
+        pub const fn new(chance: f32, mean: f32, max: f32) -> Self {
+            Self { chance, mean, max }
+        }
+
+        /// What a level is worth on average, for anyone who skips the rolling (like enemies)
+        pub fn expected(&self) -> f32 {
+            self.chance * self.mean.min(self.max)
+        }
+    }
+
     impl StatGrowths {
+        /// Every stat gets its own rng, so how one stat rolls doesn't change another's
+        pub fn from_rates(seed: &str, rates: &[(StatType, GrowthRate)]) -> Self {
+            let growths = rates
+                .iter()
+                .map(|(stat, rate)| {
+                    let growth = StatGrowthClampedNormalRounded::new(
+                        format!("{}-{:?}", seed, stat),
+                        rate.chance,
+                        Normal::new(rate.mean, 1.0).expect("Should be able to make distr"),
+                        0.0,
+                        rate.max,
+                    );
+                    (*stat, Box::new(growth) as Box<dyn StatGrowth>)
+                })
+                .collect();
+            Self { growths }
+        }
+
         pub fn get_growths_for_level_up(&mut self) -> BTreeMap<StatType, f32> {
             let mut values = BTreeMap::new();
             for (stat_type, growth) in self.growths.iter_mut() {
@@ -423,3 +687,74 @@ pub mod growths {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modification(attribute_type: StatType, operator: Operator, value: f32) -> StatModification {
+        StatModification {
+            attribute_type,
+            operator,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_adds_always_go_before_multipliers() {
+        let mut base = StatContainer::new();
+        base.with_stat(StatType::Strength, StatValue(4.));
+
+        let double = modification(StatType::Strength, Operator::Mul, 2.);
+        let plus_two = modification(StatType::Strength, Operator::Add, 2.);
+        let plus_one = modification(StatType::Strength, Operator::Add, 1.);
+        let unrelated = modification(StatType::Defense, Operator::Add, 3.);
+
+        let one_way = apply_stat_modifications(&base, &[&double, &plus_two, &unrelated, &plus_one]);
+        let other_way =
+            apply_stat_modifications(&base, &[&plus_one, &unrelated, &plus_two, &double]);
+        assert_eq!(one_way.stat(StatType::Strength), StatValue(14.));
+        assert_eq!(one_way, other_way);
+        assert_eq!(one_way.stat(StatType::Defense), StatValue(3.));
+    }
+
+    #[test]
+    fn test_growth_stops_at_the_cap() {
+        let mut stats = UnitJob::Mage.default_stats();
+        let cap = UnitJob::Mage.stat_cap(StatType::Strength).unwrap();
+        assert!(cap < StatType::Strength.cap().unwrap());
+
+        stats.with_stat(StatType::Strength, StatValue(cap - 1.));
+        apply_growth(&mut stats, Some(&UnitJob::Mage), StatType::Strength, 3.);
+        assert_eq!(stats.stat(StatType::Strength), StatValue(cap));
+        assert!(at_cap(&stats, Some(&UnitJob::Mage), StatType::Strength));
+        assert!(!at_cap(&stats, None, StatType::Strength));
+
+        let max_health = UnitJob::Mage.stat_cap(StatType::MaxHealth).unwrap();
+        stats
+            .with_stat(StatType::MaxHealth, StatValue(max_health - 2.))
+            .with_stat(StatType::Health, StatValue(10.));
+        apply_growth(&mut stats, Some(&UnitJob::Mage), StatType::MaxHealth, 5.);
+        assert_eq!(stats.stat(StatType::MaxHealth), StatValue(max_health));
+        assert_eq!(stats.stat(StatType::Health), StatValue(12.));
+    }
+
+    #[test]
+    fn test_modified_stats_stay_in_bounds() {
+        let mut base = StatContainer::new();
+        base.with_stat(StatType::Health, StatValue(10.))
+            .with_stat(StatType::MaxHealth, StatValue(10.))
+            .with_stat(StatType::Speed, StatValue(2.));
+
+        let derived = apply_stat_modifications(
+            &base,
+            &[
+                &modification(StatType::MaxHealth, Operator::Mul, 0.5),
+                &modification(StatType::Speed, Operator::Add, -5.),
+            ],
+        );
+        assert_eq!(derived.stat(StatType::MaxHealth), StatValue(5.));
+        assert_eq!(derived.stat(StatType::Health), StatValue(5.));
+        assert_eq!(derived.stat(StatType::Speed), StatValue(0.));
+    }
+}
@@ -0,0 +1,157 @@
+//! Weather, and anything else that hangs over a whole battle.
+//!
+//! Some rooms get a [`RoomWeather`] rolled along with the rest of the dungeon. It gets announced
+//! with a banner when the battle starts, and lands on every unit it affects as a permanent
+//! [`Effect`], so it works like anything else that's on them. Reinforcements get it too, as soon as
+//! they show up.
+
+use bevy::prelude::*;
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use rand_seeder::Seeder;
+
+use crate::{
+    dungeon::{DungeonState, RoomKind},
+    gameplay_effects::{
+        ActiveEffects, Effect, EffectData, EffectDuration, EffectMetadata, EffectType, Operator,
+        StatModification, StatusTag,
+    },
+    unit::{ALLY_TEAM, PLAYER_TEAM, Team, Unit},
+    unit_stats::{StatType, StatsDirty},
+};
+
+/// How likely a room with a fight in it is to have any weather at all
+const WEATHER_CHANCE: f32 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoomWeather {
+    /// Everyone's ranged skills can miss
+    Rain,
+    /// Nobody can reach or spot as far
+    Fog,
+    /// The party and their allies get an extra tile of movement
+    BlessedGround,
+}
+
+impl RoomWeather {
+    const ALL: [RoomWeather; 3] = [
+        RoomWeather::Rain,
+        RoomWeather::Fog,
+        RoomWeather::BlessedGround,
+    ];
+
+    pub fn name_key(&self) -> &'static str {
+        match self {
+            RoomWeather::Rain => "weather.rain",
+            RoomWeather::Fog => "weather.fog",
+            RoomWeather::BlessedGround => "weather.blessed_ground",
+        }
+    }
+
+    pub fn description_key(&self) -> &'static str {
+        match self {
+            RoomWeather::Rain => "weather.rain.description",
+            RoomWeather::Fog => "weather.fog.description",
+            RoomWeather::BlessedGround => "weather.blessed_ground.description",
+        }
+    }
+
+    fn effect_type(&self) -> EffectType {
+        match self {
+            RoomWeather::Rain => EffectType::StatusInfliction(StatusTag::Drenched),
+            RoomWeather::Fog => EffectType::StatusInfliction(StatusTag::Fogbound),
+            RoomWeather::BlessedGround => EffectType::StatBuff(StatModification {
+                attribute_type: StatType::Movement,
+                operator: Operator::Add,
+                value: 1.,
+            }),
+        }
+    }
+
+    /// Rain and fog get everyone, the blessing is only for the party's side
+    fn affects(&self, team: Team) -> bool {
+        match self {
+            RoomWeather::Rain | RoomWeather::Fog => true,
+            RoomWeather::BlessedGround => team == PLAYER_TEAM || team == ALLY_TEAM,
+        }
+    }
+}
+
+/// Whatever weather the room gets, if any. Nobody needs weather where there's nothing to fight.
+pub fn roll_weather(seed: &str, kind: RoomKind) -> Option<RoomWeather> {
+    if matches!(kind, RoomKind::Rest | RoomKind::Shop) {
+        return None;
+    }
+
+    let mut rng: Pcg64 = Seeder::from(format!("{}-weather", seed)).into_rng();
+    if rng.random::<f32>() >= WEATHER_CHANCE {
+        return None;
+    }
+    RoomWeather::ALL.choose(&mut rng).copied()
+}
+
+/// The weather in the room that's currently loaded
+#[derive(Resource, Debug, Clone, Copy, Default)]
+pub struct CurrentWeather(pub Option<RoomWeather>);
+
+/// The unit has already gotten (or been skipped by) the room's weather
+#[derive(Component)]
+pub struct Weathered;
+
+pub fn weather_plugin(app: &mut App) {
+    app.init_resource::<CurrentWeather>().add_systems(
+        Update,
+        apply_weather.run_if(in_state(DungeonState::InBattle)),
+    );
+}
+
+fn apply_weather(
+    mut commands: Commands,
+    weather: Res<CurrentWeather>,
+    units: Query<(Entity, &Unit, &mut ActiveEffects), Without<Weathered>>,
+) {
+    let Some(weather) = weather.0 else {
+        return;
+    };
+
+    for (entity, unit, mut effects) in units {
+        commands.entity(entity).insert(Weathered);
+        if !weather.affects(unit.team) {
+            continue;
+        }
+
+        effects.apply_effect(Effect {
+            metadata: EffectMetadata {
+                target: entity,
+                source: None,
+            },
+            data: EffectData {
+                effect_type: weather.effect_type(),
+                duration: EffectDuration::Permanent,
+            },
+        });
+        commands.entity(entity).insert(StatsDirty);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weather_only_shows_up_where_theres_a_fight() {
+        let mut weathered_rooms = 0;
+        for seed in 0..50 {
+            let seed = seed.to_string();
+            assert_eq!(roll_weather(&seed, RoomKind::Rest), None);
+            assert_eq!(roll_weather(&seed, RoomKind::Shop), None);
+
+            let weather = roll_weather(&seed, RoomKind::Combat);
+            assert_eq!(weather, roll_weather(&seed, RoomKind::Combat));
+            if weather.is_some() {
+                weathered_rooms += 1;
+            }
+        }
+        assert!(weathered_rooms > 0 && weathered_rooms < 50);
+    }
+}